@@ -1,20 +1,21 @@
-use std::collections::HashMap;
 use std::error::Error;
 use std::io;
 use std::sync::Arc;
 use std::sync::RwLock;
 
 use crate::address_decoder;
+use crate::chain_params::ChainParams;
 use crate::custom_errors::NodeCustomErrors;
 use crate::transactions::transaction::Transaction;
 use crate::utxo_tuple::UtxoTuple;
 #[derive(Debug, Clone)]
 /// Represents a bitcoin account.
-/// Stores the compressed address and the private key (compressed or not).
-/// Also stores the utxos of the account, pending and confirmed transactions.
+/// Stores the compressed address (base58 P2PKH or bech32 P2WPKH), the private key (compressed or not) and
+/// the chain params of the network of the address. Also stores the utxos of the account, pending and confirmed transactions.
 pub struct Account {
     pub private_key: String,
     pub address: String,
+    pub chain_params: &'static ChainParams,
     pub utxo_set: Vec<UtxoTuple>,
     pub pending_transactions: Arc<RwLock<Vec<Transaction>>>,
     pub confirmed_transactions: Arc<RwLock<Vec<Transaction>>>,
@@ -22,15 +23,20 @@ pub struct Account {
 
 type TransactionInfo = (String, Transaction, i64);
 impl Account {
-    /// Receives the address in compressed format and the WIF private key, either in 
-    /// compressed or uncompressed format.
-    pub fn new(wif_private_key: String, address: String) -> Result<Account, Box<dyn Error>> {
+    /// Receives the address in compressed format, the WIF private key, either in 
+    /// compressed or uncompressed format, and the chain params of the network of the address.
+    pub fn new(
+        wif_private_key: String,
+        address: String,
+        chain_params: &'static ChainParams,
+    ) -> Result<Account, Box<dyn Error>> {
         let raw_private_key = address_decoder::decode_wif_private_key(wif_private_key.as_str())?;
 
-        address_decoder::validate_address_private_key(&raw_private_key, &address)?;
+        address_decoder::validate_address_private_key(&raw_private_key, &address, chain_params)?;
         Ok(Account {
             private_key: wif_private_key,
             address,
+            chain_params,
             utxo_set: Vec::new(),
             pending_transactions: Arc::new(RwLock::new(Vec::new())),
             confirmed_transactions: Arc::new(RwLock::new(Vec::new())),
@@ -56,6 +62,18 @@ impl Account {
         self.utxo_set = utxos;
     }
 
+    /// Stores the confirmed transactions of the account.
+    pub fn load_confirmed_transactions(
+        &self,
+        transactions: Vec<Transaction>,
+    ) -> Result<(), Box<dyn Error>> {
+        *self
+            .confirmed_transactions
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))? = transactions;
+        Ok(())
+    }
+
     /// Compares the amount received with the account balance.
     /// Returns true if the balance is greater. Otherwise false.
     pub fn has_balance(&self, value: i64) -> bool {
@@ -72,23 +90,7 @@ impl Account {
     }
     /// Returns a vec with the utxos to be spent in a new transaction, according to the amount received.
     fn get_utxos_for_amount(&mut self, value: i64) -> Vec<UtxoTuple> {
-        let mut utxos_to_spend = Vec::new();
-        let mut partial_amount: i64 = 0;
-        let mut position: usize = 0;
-        let length: usize = self.utxo_set.len();
-        while position < length {
-            if (partial_amount + self.utxo_set[position].balance()) < value {
-                partial_amount += self.utxo_set[position].balance();
-                utxos_to_spend.push(self.utxo_set[position].clone());
-                // As the tx is not confirmed yet, it is not necessary to remove them
-            } else {
-                utxos_to_spend
-                    .push(self.utxo_set[position].utxos_to_spend(value, &mut partial_amount));
-                break;
-            }
-            position += 1;
-        }
-        utxos_to_spend
+        get_utxos_for_amount(&mut self.utxo_set, value)
     }
 
     /// Add the transaction to the list of pending transactions.
@@ -101,15 +103,16 @@ impl Account {
         Ok(())
     }
 
-    /// Makes the transaction with the amount received. 
+    /// Makes the transaction with the amount received and the lock time (0 if it is not locked).
     /// Returns the hash of the transaction so that the node sends that hash to the remaining nodes in the network.
     pub fn make_transaction(
         &mut self,
         address_receiver: &str,
         amount: i64,
         fee: i64,
+        lock_time: u32,
     ) -> Result<Transaction, Box<dyn Error>> {
-        address_decoder::validate_address(address_receiver)?;
+        address_decoder::validate_address(address_receiver, self.chain_params)?;
         if !self.has_balance(amount + fee) {
             return Err(Box::new(std::io::Error::new(
                 io::ErrorKind::Other,
@@ -129,6 +132,8 @@ impl Account {
             amount,
             fee,
             &utxos_to_spend,
+            lock_time,
+            self.chain_params,
         )?;
         unsigned_transaction.sign(self, &utxos_to_spend)?;
         unsigned_transaction.validate(&utxos_to_spend)?;
@@ -136,28 +141,6 @@ impl Account {
         Ok(unsigned_transaction)
     }
 
-    /// Receives the utxo_set, iterates it and sets the account utxo_set.
-    pub fn set_utxos(
-        &mut self,
-        utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
-    ) -> Result<(), Box<dyn Error>> {
-        let mut account_utxo_set: Vec<UtxoTuple> = Vec::new();
-        for utxo in utxo_set
-            .read()
-            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-            .values()
-        {
-            let aux_utxo = utxo.referenced_utxos(&self.address);
-            let utxo_to_push = match aux_utxo {
-                Some(value) => value,
-                None => continue,
-            };
-            account_utxo_set.push(utxo_to_push);
-        }
-        self.utxo_set = account_utxo_set;
-        Ok(())
-    }
-
     /// Returns the pending and confirmed transactions of the account.
     /// Returns a list of tuples with the state, transaction and amount sent by the account.
     pub fn get_transactions(&self) -> Result<Vec<TransactionInfo>, Box<dyn Error>> {
@@ -172,7 +155,7 @@ impl Account {
             transactions.push((
                 "Pending".to_string(),
                 tx.clone(),
-                tx.amount_spent_by_account(&self.address)?,
+                tx.amount_spent_by_account(&self.address, self.chain_params)?,
             ));
         }
 
@@ -185,7 +168,7 @@ impl Account {
             transactions.push((
                 "Confirmed".to_string(),
                 tx.clone(),
-                tx.amount_spent_by_account(&self.address)?,
+                tx.amount_spent_by_account(&self.address, self.chain_params)?,
             ));
         }
 
@@ -193,6 +176,27 @@ impl Account {
     }
 }
 
+/// Receives the utxos of an account and the amount to spend.
+/// Returns a vec with the utxos to be spent in a new transaction, according to the amount received.
+pub fn get_utxos_for_amount(utxo_set: &mut [UtxoTuple], value: i64) -> Vec<UtxoTuple> {
+    let mut utxos_to_spend = Vec::new();
+    let mut partial_amount: i64 = 0;
+    let mut position: usize = 0;
+    let length: usize = utxo_set.len();
+    while position < length {
+        if (partial_amount + utxo_set[position].balance()) < value {
+            partial_amount += utxo_set[position].balance();
+            utxos_to_spend.push(utxo_set[position].clone());
+            // As the tx is not confirmed yet, it is not necessary to remove them
+        } else {
+            utxos_to_spend.push(utxo_set[position].utxos_to_spend(value, &mut partial_amount));
+            break;
+        }
+        position += 1;
+    }
+    utxos_to_spend
+}
+
 /// Converts the bytes to hexadecimal and returns it
 pub fn bytes_to_hex_string(bytes: &[u8]) -> String {
     let hex_chars: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
@@ -202,7 +206,7 @@ pub fn bytes_to_hex_string(bytes: &[u8]) -> String {
 #[cfg(test)]
 mod test {
 
-    use crate::account::Account;
+    use crate::{account::Account, chain_params::TESTNET_PARAMS};
     use std::{
         error::Error,
         io,
@@ -232,7 +236,7 @@ mod test {
         let address_expected: String = String::from("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV");
         let private_key: String =
             String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
-        let account_result = Account::new(private_key, address_expected);
+        let account_result = Account::new(private_key, address_expected, &TESTNET_PARAMS);
         assert!(account_result.is_ok());
     }
 
@@ -241,7 +245,7 @@ mod test {
         let address_expected: String = String::from("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV");
         let private_key: String =
             String::from("91dkDNCCaMp2f91sVQRGgdZRw1QY4aptaeZ4vxEvuG5PvZ9hftJ");
-        let account_result = Account::new(private_key, address_expected);
+        let account_result = Account::new(private_key, address_expected, &TESTNET_PARAMS);
         assert!(account_result.is_ok());
     }
 
@@ -250,7 +254,7 @@ mod test {
         let address_expected: String = String::from("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV");
         let private_key: String =
             String::from("K1dkDNCCaMp2f91sVQRGgdZRw1QY4aptaeZ4vxEvuG5PvZ9hftJ");
-        let account_result = Account::new(private_key, address_expected);
+        let account_result = Account::new(private_key, address_expected, &TESTNET_PARAMS);
         assert!(account_result.is_err());
     }
 
@@ -261,6 +265,7 @@ mod test {
         let user = Account {
             private_key,
             address,
+            chain_params: &TESTNET_PARAMS,
             utxo_set: Vec::new(),
             pending_transactions: Arc::new(RwLock::new(Vec::new())),
             confirmed_transactions: Arc::new(RwLock::new(Vec::new())),
@@ -277,9 +282,9 @@ mod test {
         let address_expected: String = String::from("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV");
         let private_key: String =
             String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
-        let mut account = Account::new(private_key, address_expected)?;
+        let mut account = Account::new(private_key, address_expected, &TESTNET_PARAMS)?;
         let transaction_result =
-            account.make_transaction("mocD12x6BV3qK71FwG98h5VWZ4qVsbaoi8", 1000, 10);
+            account.make_transaction("mocD12x6BV3qK71FwG98h5VWZ4qVsbaoi8", 1000, 10, 0);
         assert!(transaction_result.is_err());
         Ok(())
     }
@@ -1,17 +1,34 @@
-use bitcoin_hashes::{ripemd160, Hash};
+use bitcoin_hashes::{ripemd160, sha256, Hash};
 use k256::sha2::Digest;
 use k256::sha2::Sha256;
-use secp256k1::SecretKey;
+use secp256k1::{KeyPair, Scalar, SecretKey, XOnlyPublicKey};
 use std::error::Error;
 use std::io;
 
+use crate::chain_params::ChainParams;
+
 const UNCOMPRESSED_WIF_LEN: usize = 51;
 const COMPRESSED_WIF_LEN: usize = 52;
-const ADDRESS_LEN: usize = 34;
-
-/// Receives the private key in bytes.
+/// Length of a decoded base58 address: version byte, hash of 20 bytes and checksum of 4 bytes.
+const ADDRESS_BYTES_LEN: usize = 25;
+/// Characters used by bech32 to encode each group of 5 bits.
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+/// Constant that the checksum of a bech32 string must produce (BIP173).
+const BECH32_CONST: u32 = 1;
+/// Constant that the checksum of a bech32m string must produce (BIP350).
+/// It is used by the addresses of witness version 1 and above.
+const BECH32M_CONST: u32 = 0x2bc830a3;
+/// Amount of characters of the checksum of a bech32 string.
+const BECH32_CHECKSUM_LEN: usize = 6;
+/// Maximum amount of characters of a segwit address.
+const SEGWIT_ADDRESS_MAX_LEN: usize = 90;
+
+/// Receives the private key in bytes and the chain params of the network.
 /// Returns the compressed address.
-pub fn generate_address(private_key: &[u8]) -> Result<String, Box<dyn Error>> {
+pub fn generate_address(
+    private_key: &[u8],
+    params: &ChainParams,
+) -> Result<String, Box<dyn Error>> {
     // Applies the ECDSA algorithm to the private key, then to the public key
     let secp: secp256k1::Secp256k1<secp256k1::All> = secp256k1::Secp256k1::new();
     let key = SecretKey::from_slice(private_key)?;
@@ -20,10 +37,85 @@ pub fn generate_address(private_key: &[u8]) -> Result<String, Box<dyn Error>> {
 
     // Applies RIPEMD160(SHA256(ECDSA(public_key)))
     let ripemd160_hash = hash_160(&public_key_bytes_compressed);
+    Ok(encode_address(
+        params.p2pkh_address_version,
+        &ripemd160_hash,
+    ))
+}
+
+/// Receives the private key in bytes and the chain params of the network.
+/// Returns the native segwit (P2WPKH) address of the compressed public key.
+pub fn generate_p2wpkh_address(
+    private_key: &[u8],
+    params: &ChainParams,
+) -> Result<String, Box<dyn Error>> {
+    let secp: secp256k1::Secp256k1<secp256k1::All> = secp256k1::Secp256k1::new();
+    let key = SecretKey::from_slice(private_key)?;
+    let public_key: secp256k1::PublicKey = secp256k1::PublicKey::from_secret_key(&secp, &key);
+    let pubkey_hash = hash_160(&public_key.serialize());
+    Ok(encode_segwit_address(0, &pubkey_hash, params))
+}
 
-    // Add the version byte (0x00) at the beginning of the RIPEMD-160 hash
-    let mut extended_hash = vec![0x6f];
-    extended_hash.extend_from_slice(&ripemd160_hash);
+/// Receives the private key in bytes and the chain params of the network.
+/// Returns the taproot (P2TR) address of the output key obtained by tweaking the public key,
+/// which can only be spent with a key path spend (BIP86).
+pub fn generate_p2tr_address(
+    private_key: &[u8],
+    params: &ChainParams,
+) -> Result<String, Box<dyn Error>> {
+    let secp: secp256k1::Secp256k1<secp256k1::All> = secp256k1::Secp256k1::new();
+    let key = SecretKey::from_slice(private_key)?;
+    let (internal_key, _) = KeyPair::from_secret_key(&secp, &key).x_only_public_key();
+    let output_key = get_taproot_output_key(&internal_key.serialize())?;
+    Ok(encode_segwit_address(1, &output_key, params))
+}
+
+/// Receives the internal key of a taproot output without scripts (x coordinate, 32 bytes).
+/// Returns the output key: the internal key tweaked with the hash "TapTweak" of itself (BIP341).
+pub fn get_taproot_output_key(internal_key: &[u8; 32]) -> Result<[u8; 32], Box<dyn Error>> {
+    let secp: secp256k1::Secp256k1<secp256k1::All> = secp256k1::Secp256k1::new();
+    let internal_key = XOnlyPublicKey::from_slice(internal_key)?;
+    let tweak = Scalar::from_be_bytes(tagged_hash("TapTweak", &internal_key.serialize()))?;
+    let (output_key, _) = internal_key.add_tweak(&secp, &tweak)?;
+    Ok(output_key.serialize())
+}
+
+/// Receives the private key in bytes.
+/// Returns the key pair of the output key of the taproot output without scripts, used to sign its key
+/// path spends. The private key is negated if the public key has odd y coordinate before being tweaked.
+pub fn get_taproot_key_pair(
+    secp: &secp256k1::Secp256k1<secp256k1::All>,
+    private_key: &[u8],
+) -> Result<KeyPair, Box<dyn Error>> {
+    let key = SecretKey::from_slice(private_key)?;
+    let key_pair = KeyPair::from_secret_key(secp, &key);
+    let (internal_key, _) = key_pair.x_only_public_key();
+    let tweak = Scalar::from_be_bytes(tagged_hash("TapTweak", &internal_key.serialize()))?;
+    Ok(key_pair.add_xonly_tweak(secp, &tweak)?)
+}
+
+/// Receives the redeem script of a P2SH output and the chain params of the network.
+/// Returns the P2SH address, which contains the hash160 of the redeem script.
+pub fn generate_p2sh_address(redeem_script: &[u8], params: &ChainParams) -> String {
+    encode_address(params.p2sh_address_version, &hash_160(redeem_script))
+}
+
+/// Receives the witness script (for example a multisig script) and the chain params of the network.
+/// Returns the native segwit (P2WSH) address that pays to the sha256 hash of the script.
+pub fn generate_p2wsh_address(witness_script: &[u8], params: &ChainParams) -> String {
+    encode_segwit_address(
+        0,
+        sha256::Hash::hash(witness_script).as_byte_array(),
+        params,
+    )
+}
+
+/// Receives the version byte of the address and the hash it contains.
+/// Returns the address in base58 format, with the checksum at the end.
+pub fn encode_address(version: u8, hash: &[u8]) -> String {
+    // Add the version byte at the beginning of the hash
+    let mut extended_hash = vec![version];
+    extended_hash.extend_from_slice(hash);
 
     // Calculate the checksum (double SHA-256 hash) of the extended hash
     let checksum = Sha256::digest(Sha256::digest(&extended_hash));
@@ -33,21 +125,209 @@ pub fn generate_address(private_key: &[u8]) -> Result<String, Box<dyn Error>> {
 
     // Decode the extended hash to base58 format
     let encoded: bs58::encode::EncodeBuilder<&Vec<u8>> = bs58::encode(&extended_hash);
-    Ok(encoded.into_string())
+    encoded.into_string()
+}
+
+/// Receives the witness version and the witness program of a segwit output and the chain params of the network.
+/// Returns the address with the human readable part of the network, in bech32 format (BIP173) if the
+/// witness version is 0 or in bech32m format (BIP350) otherwise.
+pub fn encode_segwit_address(
+    witness_version: u8,
+    witness_program: &[u8],
+    params: &ChainParams,
+) -> String {
+    let mut data: Vec<u8> = vec![witness_version];
+    data.extend(convert_bits(witness_program, 8, 5, true));
+    let checksum = bech32_checksum(params.bech32_hrp, &data, bech32_constant(witness_version));
+    let mut address = String::from(params.bech32_hrp);
+    address.push('1');
+    for value in data.iter().chain(checksum.iter()) {
+        address.push(BECH32_CHARSET[*value as usize] as char);
+    }
+    address
+}
+
+/// Receives a segwit address in bech32 or bech32m format and the chain params of the network.
+/// Returns the witness version and the witness program, or an error if the address is invalid or
+/// belongs to another network. Only the programs of witness version 0 with 20 (P2WPKH) or 32 (P2WSH)
+/// bytes and the programs of witness version 1 with 32 bytes (P2TR) are supported.
+pub fn decode_segwit_address(
+    address: &str,
+    params: &ChainParams,
+) -> Result<(u8, Vec<u8>), Box<dyn Error>> {
+    let invalid_address = |reason: &str| -> Box<dyn Error> {
+        Box::new(std::io::Error::new(
+            io::ErrorKind::Other,
+            format!("The segwit address is invalid. {}", reason),
+        ))
+    };
+    if address.len() > SEGWIT_ADDRESS_MAX_LEN {
+        return Err(invalid_address("It has an invalid length."));
+    }
+    // bech32 strings can be in lowercase or uppercase, but not mixed
+    if address.to_lowercase() != address && address.to_uppercase() != address {
+        return Err(invalid_address(
+            "It mixes lowercase and uppercase characters.",
+        ));
+    }
+    let address = address.to_lowercase();
+    let (hrp, encoded_data) = match address.rfind('1') {
+        Some(separator) => (&address[..separator], &address[separator + 1..]),
+        None => return Err(invalid_address("It does not have a separator.")),
+    };
+    if hrp != params.bech32_hrp {
+        return Err(invalid_address("It is not an address of the network."));
+    }
+    if encoded_data.len() < BECH32_CHECKSUM_LEN + 1 {
+        return Err(invalid_address("It has an invalid length."));
+    }
+    let mut data: Vec<u8> = Vec::new();
+    for character in encoded_data.bytes() {
+        match BECH32_CHARSET.iter().position(|c| *c == character) {
+            Some(value) => data.push(value as u8),
+            None => return Err(invalid_address("It has an invalid character.")),
+        }
+    }
+    let witness_version = data[0];
+    // version 0 uses the bech32 checksum and the next versions the bech32m one
+    if bech32_polymod(&bech32_values(hrp, &data)) != bech32_constant(witness_version) {
+        return Err(invalid_address("The checksum is invalid."));
+    }
+    let program_data = &data[1..data.len() - BECH32_CHECKSUM_LEN];
+    let witness_program = convert_bits(program_data, 5, 8, false);
+    // the padding bits must be zeros and less than a group of 5 bits
+    if convert_bits(&witness_program, 8, 5, true) != program_data {
+        return Err(invalid_address("It has an invalid padding."));
+    }
+    let is_witness_v0 =
+        witness_version == 0 && (witness_program.len() == 20 || witness_program.len() == 32);
+    let is_taproot = witness_version == 1 && witness_program.len() == 32;
+    if !is_witness_v0 && !is_taproot {
+        return Err(invalid_address(
+            "It is not a P2WPKH, P2WSH or P2TR address.",
+        ));
+    }
+    Ok((witness_version, witness_program))
+}
+
+/// Returns true if the received address has the format of a segwit address of the network (tb1... in testnet).
+pub fn is_segwit_address(address: &str, params: &ChainParams) -> bool {
+    address
+        .to_lowercase()
+        .starts_with(&format!("{}1", params.bech32_hrp))
+}
+
+/// Returns true if the received address is a valid taproot address of the network (tb1p... in testnet).
+pub fn is_taproot_address(address: &str, params: &ChainParams) -> bool {
+    is_segwit_address(address, params)
+        && matches!(decode_segwit_address(address, params), Ok((witness_version, _)) if witness_version == 1)
+}
+
+/// Returns the values over which the bech32 checksum is calculated: the expanded human readable part
+/// (high bits of each character, a zero and the low bits of each character) followed by the data.
+fn bech32_values(hrp: &str, data: &[u8]) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|c| c & 31));
+    values.extend_from_slice(data);
+    values
+}
+
+/// Calculates the BCH code that checks the values of a bech32 string, as defined in BIP173.
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ *value as u32;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
 }
 
-/// Receives the compressed public key (33 bytes).
+/// Returns the constant of the checksum of the addresses with the received witness version.
+fn bech32_constant(witness_version: u8) -> u32 {
+    if witness_version == 0 {
+        BECH32_CONST
+    } else {
+        BECH32M_CONST
+    }
+}
+
+/// Returns the 6 values of the checksum of the received human readable part and data,
+/// with the constant of bech32 or bech32m.
+fn bech32_checksum(hrp: &str, data: &[u8], constant: u32) -> Vec<u8> {
+    let mut values = bech32_values(hrp, data);
+    values.extend_from_slice(&[0; BECH32_CHECKSUM_LEN]);
+    let polymod = bech32_polymod(&values) ^ constant;
+    (0..BECH32_CHECKSUM_LEN)
+        .map(|i| ((polymod >> (5 * (5 - i))) & 31) as u8)
+        .collect()
+}
+
+/// Regroups the bits of the received values, from groups of from_bits to groups of to_bits.
+/// If pad is true the last group is completed with zeros, otherwise the incomplete bits are discarded.
+fn convert_bits(data: &[u8], from_bits: u32, to_bits: u32, pad: bool) -> Vec<u8> {
+    let mut accumulator: u32 = 0;
+    let mut bits: u32 = 0;
+    let max_value: u32 = (1 << to_bits) - 1;
+    let mut result: Vec<u8> = Vec::new();
+    for value in data {
+        accumulator = (accumulator << from_bits) | *value as u32;
+        bits += from_bits;
+        while bits >= to_bits {
+            bits -= to_bits;
+            result.push(((accumulator >> bits) & max_value) as u8);
+        }
+    }
+    if pad && bits > 0 {
+        result.push(((accumulator << (to_bits - bits)) & max_value) as u8);
+    }
+    result
+}
+
+/// Receives the compressed public key (33 bytes) or a script.
 /// Applies RIPEMD160(SHA256(ECDSA(public_key))).
 pub fn hash_160(public_key_bytes_compressed: &[u8]) -> [u8; 20] {
     let sha256_hash = Sha256::digest(public_key_bytes_compressed);
     *ripemd160::Hash::hash(&sha256_hash).as_byte_array()
 }
 
-/// Receives the compressed address.
-/// Returns the PubkeyHash. If the address is invalid, returns an error.
-pub fn get_pubkey_hash_from_address(address: &str) -> Result<[u8; 20], Box<dyn Error>> {
+/// Receives the tag and the data to hash.
+/// Returns the tagged hash defined in BIP340: sha256(sha256(tag) || sha256(tag) || data).
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut message: Vec<u8> = Vec::new();
+    message.extend_from_slice(tag_hash.as_byte_array());
+    message.extend_from_slice(tag_hash.as_byte_array());
+    message.extend_from_slice(data);
+    *sha256::Hash::hash(&message).as_byte_array()
+}
+
+/// Receives the compressed address and the chain params of the network.
+/// Returns the PubkeyHash, or the hash of the redeem script if it is a P2SH address.
+/// If it is a P2WPKH address, returns the witness program, which is the PubkeyHash.
+/// If the address is invalid, returns an error.
+pub fn get_pubkey_hash_from_address(
+    address: &str,
+    params: &ChainParams,
+) -> Result<[u8; 20], Box<dyn Error>> {
+    if is_segwit_address(address, params) {
+        let (_, witness_program) = decode_segwit_address(address, params)?;
+        let pubkey_hash: [u8; 20] = witness_program.as_slice().try_into().map_err(|_| {
+            std::io::Error::new(
+                io::ErrorKind::Other,
+                "The address is invalid. It is not a P2WPKH address.",
+            )
+        })?;
+        return Ok(pubkey_hash);
+    }
     // decoded from &str to bytes, from base58 format to bytes
-    validate_address(address)?;
+    validate_address(address, params)?;
     let address_decoded_bytes = bs58::decode(address).into_vec()?;
     let length_bytes = address_decoded_bytes.len();
     let mut pubkey_hash: [u8; 20] = [0; 20];
@@ -68,10 +348,17 @@ pub fn get_pubkey_compressed(private_key: &str) -> Result<[u8; 33], Box<dyn Erro
     Ok(public_key.serialize())
 }
 
-/// Receives a bitcoin address.
-/// Checks the checksum and returns an error if it is invalid.
-pub fn validate_address(address: &str) -> Result<(), Box<dyn Error>> {
-    if address.len() != ADDRESS_LEN {
+/// Receives a bitcoin address and the chain params of the network.
+/// Checks the length, the checksum and the version (P2PKH or P2SH of the network) and returns an error if it is invalid.
+/// Segwit addresses are decoded from bech32 and must be P2WPKH, P2WSH or P2TR.
+pub fn validate_address(address: &str, params: &ChainParams) -> Result<(), Box<dyn Error>> {
+    if is_segwit_address(address, params) {
+        decode_segwit_address(address, params)?;
+        return Ok(());
+    }
+    let address_decoded_bytes = bs58::decode(address).into_vec()?;
+    let length_bytes = address_decoded_bytes.len();
+    if length_bytes != ADDRESS_BYTES_LEN {
         return Err(Box::new(std::io::Error::new(
             io::ErrorKind::Other,
             "The address is invalid. It has an invalid length.",
@@ -79,8 +366,6 @@ pub fn validate_address(address: &str) -> Result<(), Box<dyn Error>> {
     }
     // Checksum validation: avoids typing errors in the address
     // Calculate the checksum (double SHA-256 hash) of the extended hash
-    let address_decoded_bytes = bs58::decode(address).into_vec()?;
-    let length_bytes = address_decoded_bytes.len();
     let checksum_hash = Sha256::digest(Sha256::digest(
         &address_decoded_bytes[0..(length_bytes - 4)],
     ));
@@ -92,16 +377,43 @@ pub fn validate_address(address: &str) -> Result<(), Box<dyn Error>> {
             "The address is invalid. The checksum is invalid.",
         )));
     }
+    if address_decoded_bytes[0] != params.p2pkh_address_version
+        && address_decoded_bytes[0] != params.p2sh_address_version
+    {
+        return Err(Box::new(std::io::Error::new(
+            io::ErrorKind::Other,
+            "The address is invalid. It is not a P2PKH or P2SH address of the network.",
+        )));
+    }
     Ok(())
 }
 
-/// Receives a private key in bytes and a compressed address.
+/// Receives a bitcoin address and the chain params of the network.
+/// Returns true if it is a P2SH address, false if it is a P2PKH or segwit address or error if it is invalid.
+pub fn is_p2sh_address(address: &str, params: &ChainParams) -> Result<bool, Box<dyn Error>> {
+    validate_address(address, params)?;
+    if is_segwit_address(address, params) {
+        return Ok(false);
+    }
+    let address_decoded_bytes = bs58::decode(address).into_vec()?;
+    Ok(address_decoded_bytes[0] == params.p2sh_address_version)
+}
+
+/// Receives a private key in bytes, a compressed address, either P2PKH, P2WPKH or P2TR, and the chain params of the network.
 /// Returns true or false depending on whether they correspond or not.
 pub fn validate_address_private_key(
     private_key: &[u8],
     address: &String,
+    params: &ChainParams,
 ) -> Result<(), Box<dyn Error>> {
-    if !generate_address(private_key)?.eq(address) {
+    let expected_address = if is_taproot_address(address, params) {
+        generate_p2tr_address(private_key, params)?
+    } else if is_segwit_address(address, params) {
+        generate_p2wpkh_address(private_key, params)?
+    } else {
+        generate_address(private_key, params)?
+    };
+    if !expected_address.eq(address) {
         return Err(Box::new(std::io::Error::new(
             io::ErrorKind::Other,
             "The private key does not correspond to the address",
@@ -148,6 +460,11 @@ mod test {
     use super::get_pubkey_hash_from_address;
     use crate::address_decoder::decode_wif_private_key;
     use crate::address_decoder::generate_address;
+    use crate::address_decoder::{
+        decode_segwit_address, encode_segwit_address, generate_p2sh_address,
+        generate_p2wpkh_address, is_p2sh_address, validate_address, validate_address_private_key,
+    };
+    use crate::chain_params::{MAINNET_PARAMS, REGTEST_PARAMS, TESTNET_PARAMS};
     use secp256k1::SecretKey;
     use std::error::Error;
     use std::io;
@@ -215,7 +532,7 @@ mod test {
         let expected_address: &str = "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
         let private_key_wif: &str = "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR";
         let private_key_bytes = decode_wif_private_key(private_key_wif)?;
-        let address = generate_address(&private_key_bytes)?;
+        let address = generate_address(&private_key_bytes, &TESTNET_PARAMS)?;
         assert_eq!(expected_address, address);
         Ok(())
     }
@@ -223,7 +540,7 @@ mod test {
     #[test]
     fn test_valid_address_decoding_returns_ok() {
         let address = "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF";
-        let pubkey_hash_expected = get_pubkey_hash_from_address(address);
+        let pubkey_hash_expected = get_pubkey_hash_from_address(address, &TESTNET_PARAMS);
         assert!(pubkey_hash_expected.is_ok())
     }
 
@@ -233,7 +550,7 @@ mod test {
         let private_key: &str = "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR";
         let private_key_bytes = decode_wif_private_key(private_key)?;
         let pubkey_hash_expected = generate_pubkey_hash(&private_key_bytes)?;
-        let pubkey_hash_generated = get_pubkey_hash_from_address(address)?;
+        let pubkey_hash_generated = get_pubkey_hash_from_address(address, &TESTNET_PARAMS)?;
         assert_eq!(pubkey_hash_expected, pubkey_hash_generated);
         Ok(())
     }
@@ -241,16 +558,117 @@ mod test {
     #[test]
     fn test_pub_key_hash_is_generated_with_correct_length() -> Result<(), Box<dyn Error>> {
         let address = "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
-        let pub_key_hash = get_pubkey_hash_from_address(address)?;
+        let pub_key_hash = get_pubkey_hash_from_address(address, &TESTNET_PARAMS)?;
 
         assert_eq!(pub_key_hash.len(), 20);
         Ok(())
     }
     
+    #[test]
+    fn test_p2sh_address_is_valid_and_recognized_as_p2sh() -> Result<(), Box<dyn Error>> {
+        let address = generate_p2sh_address(&[0x52, 0x87], &TESTNET_PARAMS);
+        assert!(address.starts_with('2'));
+        assert!(is_p2sh_address(&address, &TESTNET_PARAMS)?);
+        assert!(!is_p2sh_address(
+            "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV",
+            &TESTNET_PARAMS
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bech32_address_is_decoded_into_its_witness_program() -> Result<(), Box<dyn Error>> {
+        // test vector of BIP173
+        let address = "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7";
+        let expected_program =
+            string_to_32_bytes("1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262")?;
+        let (witness_version, witness_program) = decode_segwit_address(address, &TESTNET_PARAMS)?;
+        assert_eq!(witness_version, 0);
+        assert_eq!(witness_program, expected_program.to_vec());
+        assert_eq!(
+            encode_segwit_address(0, &expected_program, &TESTNET_PARAMS),
+            address
+        );
+        assert!(decode_segwit_address(&address.to_uppercase(), &TESTNET_PARAMS).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_bech32_addresses_return_error() {
+        // invalid checksum
+        assert!(validate_address(
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k8",
+            &TESTNET_PARAMS
+        )
+        .is_err());
+        // mixed case
+        assert!(validate_address(
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3Q0sl5k7",
+            &TESTNET_PARAMS
+        )
+        .is_err());
+        // mainnet human readable part
+        assert!(validate_address(
+            "bc1qw508d6qejxtdg4c5w7ep7jcq5la0mly5zd5fla",
+            &TESTNET_PARAMS
+        )
+        .is_err());
+        assert!(validate_address(
+            &encode_segwit_address(0, &[1; 25], &TESTNET_PARAMS),
+            &TESTNET_PARAMS
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_p2wpkh_address_contains_the_pubkey_hash_and_corresponds_to_the_private_key(
+    ) -> Result<(), Box<dyn Error>> {
+        let private_key_bytes =
+            decode_wif_private_key("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR")?;
+        let address = generate_p2wpkh_address(&private_key_bytes, &TESTNET_PARAMS)?;
+        assert!(address.starts_with("tb1q"));
+        assert_eq!(
+            get_pubkey_hash_from_address(&address, &TESTNET_PARAMS)?,
+            generate_pubkey_hash(&private_key_bytes)?
+        );
+        assert!(!is_p2sh_address(&address, &TESTNET_PARAMS)?);
+        assert!(
+            validate_address_private_key(&private_key_bytes, &address, &TESTNET_PARAMS).is_ok()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mainnet_address_is_not_valid() {
+        assert!(validate_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", &TESTNET_PARAMS).is_err());
+    }
+
+    #[test]
+    fn test_addresses_are_encoded_with_the_prefixes_of_the_network() -> Result<(), Box<dyn Error>> {
+        let private_key_bytes =
+            decode_wif_private_key("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR")?;
+        assert!(validate_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", &MAINNET_PARAMS).is_ok());
+        let mainnet_address = generate_p2wpkh_address(&private_key_bytes, &MAINNET_PARAMS)?;
+        assert!(mainnet_address.starts_with("bc1q"));
+        assert!(validate_address(&mainnet_address, &MAINNET_PARAMS).is_ok());
+        assert!(validate_address(&mainnet_address, &TESTNET_PARAMS).is_err());
+        let regtest_address = generate_p2wpkh_address(&private_key_bytes, &REGTEST_PARAMS)?;
+        assert!(regtest_address.starts_with("bcrt1q"));
+        assert_eq!(
+            get_pubkey_hash_from_address(&regtest_address, &REGTEST_PARAMS)?,
+            generate_pubkey_hash(&private_key_bytes)?
+        );
+        assert_eq!(
+            generate_address(&private_key_bytes, &REGTEST_PARAMS)?,
+            "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV"
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_get_pubkey_hash_with_invalid_address_returns_error() -> Result<(), Box<dyn Error>> {
         let address = "1nEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
-        let pub_key_hash_result = get_pubkey_hash_from_address(address);
+        let pub_key_hash_result = get_pubkey_hash_from_address(address, &TESTNET_PARAMS);
 
         assert!(pub_key_hash_result.is_err());
         Ok(())
@@ -0,0 +1,600 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bitcoin_hashes::{sha256, Hash};
+
+use crate::{
+    blocks::{block::Block, block_undo::BlockUndo},
+    chain_params::ChainParams,
+    custom_errors::NodeCustomErrors,
+    record_log::RecordLog,
+    transactions::script::p2pkh_script::generate_pubkey_script,
+};
+
+const ADDRESS_INDEX_FILE_NAME: &str = "addrindex.dat";
+// Records of the address index file, all the numbers in little endian:
+// connected:    0x01 <block hash 32> <previous block hash 32> <height u32> <entry count u32> <entry>...
+// disconnected: 0x02 <block hash 32> <previous block hash 32> <height u32> <entry count u32> <entry>...
+// entry: <0x01 funding | 0x02 spending> <script hash 32> <txid 32> <outpoint txid 32> <outpoint index u32> <value i64>
+// The entries of a disconnected block are the same ones that were added when it was connected.
+// When the file is compacted there is a connected record for each height, and only the last one has the hash
+// of its block.
+const CONNECTED_RECORD: u8 = 0x01;
+const DISCONNECTED_RECORD: u8 = 0x02;
+const FUNDING_ENTRY: u8 = 0x01;
+const SPENDING_ENTRY: u8 = 0x02;
+const RECORD_HEADER_SIZE: usize = 1 + 32 + 32 + 4 + 4;
+const ENTRY_SIZE: usize = 1 + 32 + 32 + 32 + 4 + 8;
+
+/// Represents the sha256 hash of a scriptPubKey.
+pub type ScriptHash = [u8; 32];
+/// Represents a record of the file: its kind, the hash of the block, the hash of the previous block,
+/// its height and the entries with the script hash they belong to.
+type AddressIndexRecord = (
+    u8,
+    [u8; 32],
+    [u8; 32],
+    usize,
+    Vec<(ScriptHash, AddressHistoryEntry)>,
+);
+
+#[derive(Debug, Clone, PartialEq)]
+/// Represents a transaction of the history of a scriptPubKey. If it is a funding entry, the transaction
+/// created the output of the outpoint paying to the script. If it is a spending entry, the transaction
+/// spent that output. The value is the one of the output and the height is the one of the block of the transaction.
+pub struct AddressHistoryEntry {
+    pub is_spending: bool,
+    pub txid: [u8; 32],
+    pub outpoint: ([u8; 32], u32),
+    pub value: i64,
+    pub height: usize,
+}
+
+impl AddressHistoryEntry {
+    /// Creates a new AddressHistoryEntry with the received fields.
+    pub fn new(
+        is_spending: bool,
+        txid: [u8; 32],
+        outpoint: ([u8; 32], u32),
+        value: i64,
+        height: usize,
+    ) -> Self {
+        AddressHistoryEntry {
+            is_spending,
+            txid,
+            outpoint,
+            value,
+            height,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Maps the hash of each scriptPubKey of the active chain to the transactions that funded it and the ones
+/// that spent its outputs, in the order of the chain. The entries created or removed by each block are written
+/// in the addrindex.dat record log, and the outputs spent by a block are taken from its undo data.
+pub struct AddressIndex {
+    log: RecordLog,
+    history: HashMap<ScriptHash, Vec<AddressHistoryEntry>>,
+    best_block_hash: [u8; 32],
+    height: usize,
+    indexed_blocks: usize,
+}
+
+impl AddressIndex {
+    /// Opens the address index of the received directory, creating it if it does not exist, and
+    /// loads the history of the scripts. Returns an error if the directory or the file can not be read.
+    pub fn open(directory: &str) -> Result<AddressIndex, NodeCustomErrors> {
+        let mut address_index = AddressIndex {
+            log: RecordLog::open(directory, ADDRESS_INDEX_FILE_NAME)?,
+            history: HashMap::new(),
+            best_block_hash: [0; 32],
+            height: 0,
+            indexed_blocks: 0,
+        };
+        for (kind, block_hash, previous_hash, height, entries) in
+            address_index.log.load(unmarshalling_record)?
+        {
+            match kind {
+                CONNECTED_RECORD => address_index.apply_connected(block_hash, height, entries),
+                _ => address_index.apply_disconnected(previous_hash, height, &entries),
+            }
+        }
+        Ok(address_index)
+    }
+
+    /// Returns the funding and spending entries of the script, in the order of the chain.
+    pub fn history(&self, script_hash: &ScriptHash) -> Vec<AddressHistoryEntry> {
+        self.history.get(script_hash).cloned().unwrap_or_default()
+    }
+
+    /// Returns the funding entries of the script whose outputs were not spent.
+    pub fn unspent_outputs(&self, script_hash: &ScriptHash) -> Vec<AddressHistoryEntry> {
+        let entries = match self.history.get(script_hash) {
+            Some(entries) => entries,
+            None => return Vec::new(),
+        };
+        let spent: HashSet<([u8; 32], u32)> = entries
+            .iter()
+            .filter(|entry| entry.is_spending)
+            .map(|entry| entry.outpoint)
+            .collect();
+        entries
+            .iter()
+            .filter(|entry| !entry.is_spending && !spent.contains(&entry.outpoint))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the hash of the last block indexed, or zeros if the index is empty.
+    pub fn best_block_hash(&self) -> [u8; 32] {
+        self.best_block_hash
+    }
+
+    /// Returns the height of the last block indexed, or zero if the index is empty.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns true if no block is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.best_block_hash == [0; 32]
+    }
+
+    /// Adds to the history the outputs created by the block connected to the active chain with the
+    /// received height and the outputs spent by it, which are found in its undo data. If the block
+    /// is not above the last block indexed it was already indexed before the node stopped, so it is ignored.
+    /// Returns an error if the record can not be written.
+    pub fn block_connected(
+        &mut self,
+        block: &Block,
+        height: usize,
+        block_undo: &BlockUndo,
+    ) -> Result<(), NodeCustomErrors> {
+        if !self.is_empty() && height <= self.height {
+            return Ok(());
+        }
+        let entries = block_entries(block, height, block_undo);
+        self.log.append(&marshalling_record(
+            CONNECTED_RECORD,
+            block,
+            height,
+            &entries,
+        ))?;
+        self.apply_connected(block.hash(), height, entries);
+        self.compact_if_needed()
+    }
+
+    /// Removes from the history the entries of the block, with its undo data, disconnected from the tip
+    /// of the active chain. Returns an error if the record can not be written.
+    pub fn block_disconnected(
+        &mut self,
+        block: &Block,
+        block_undo: &BlockUndo,
+    ) -> Result<(), NodeCustomErrors> {
+        let entries = block_entries(block, self.height, block_undo);
+        self.log.append(&marshalling_record(
+            DISCONNECTED_RECORD,
+            block,
+            self.height,
+            &entries,
+        ))?;
+        self.apply_disconnected(
+            block.block_header.previous_block_header_hash,
+            self.height,
+            &entries,
+        );
+        self.compact_if_needed()
+    }
+
+    /// Removes all the history of the index, so it can be built again.
+    /// Returns an error if the file can not be written.
+    pub fn reset(&mut self) -> Result<(), NodeCustomErrors> {
+        self.log.reset()?;
+        self.history.clear();
+        self.best_block_hash = [0; 32];
+        self.height = 0;
+        self.indexed_blocks = 0;
+        Ok(())
+    }
+
+    /// Compacts the file if most of its records belong to blocks that were disconnected.
+    fn compact_if_needed(&mut self) -> Result<(), NodeCustomErrors> {
+        match self.log.needs_compaction(self.indexed_blocks + 1) {
+            true => self.compact(),
+            false => Ok(()),
+        }
+    }
+
+    /// Rewrites the file with a connected record with the entries of each height, in the order of the chain,
+    /// followed by the record of the best block. Returns an error if the file can not be written.
+    fn compact(&mut self) -> Result<(), NodeCustomErrors> {
+        let mut heights: BTreeMap<usize, Vec<(ScriptHash, AddressHistoryEntry)>> = BTreeMap::new();
+        for (script_hash, history) in &self.history {
+            for entry in history {
+                heights
+                    .entry(entry.height)
+                    .or_default()
+                    .push((*script_hash, entry.clone()));
+            }
+        }
+        let best_block_entries = heights.remove(&self.height).unwrap_or_default();
+        let mut records: Vec<Vec<u8>> = heights
+            .iter()
+            .map(|(height, entries)| {
+                record_bytes(CONNECTED_RECORD, &[0; 32], &[0; 32], *height, entries)
+            })
+            .collect();
+        records.push(record_bytes(
+            CONNECTED_RECORD,
+            &self.best_block_hash,
+            &[0; 32],
+            self.height,
+            &best_block_entries,
+        ));
+        self.log.compact(&records)?;
+        self.indexed_blocks = records.len();
+        Ok(())
+    }
+
+    /// Appends the entries to the history of their scripts and makes the block the best block.
+    fn apply_connected(
+        &mut self,
+        block_hash: [u8; 32],
+        height: usize,
+        entries: Vec<(ScriptHash, AddressHistoryEntry)>,
+    ) {
+        for (script_hash, entry) in entries {
+            self.history.entry(script_hash).or_default().push(entry);
+        }
+        self.best_block_hash = block_hash;
+        self.height = height;
+        self.indexed_blocks += 1;
+    }
+
+    /// Removes the entries from the history of their scripts and makes the previous block the best block.
+    fn apply_disconnected(
+        &mut self,
+        previous_hash: [u8; 32],
+        height: usize,
+        entries: &[(ScriptHash, AddressHistoryEntry)],
+    ) {
+        for (script_hash, entry) in entries {
+            if let Some(history) = self.history.get_mut(script_hash) {
+                history.retain(|indexed_entry| indexed_entry != entry);
+                if history.is_empty() {
+                    self.history.remove(script_hash);
+                }
+            }
+        }
+        self.best_block_hash = previous_hash;
+        self.height = height.saturating_sub(1);
+        self.indexed_blocks = self.indexed_blocks.saturating_sub(1);
+    }
+}
+
+/// Returns the bytes of the record with the entries of the block with the received height.
+fn marshalling_record(
+    kind: u8,
+    block: &Block,
+    height: usize,
+    entries: &[(ScriptHash, AddressHistoryEntry)],
+) -> Vec<u8> {
+    record_bytes(
+        kind,
+        &block.hash(),
+        &block.block_header.previous_block_header_hash,
+        height,
+        entries,
+    )
+}
+
+/// Returns the bytes of a record with the hash of the block, the hash of the previous block, its height and the entries.
+fn record_bytes(
+    kind: u8,
+    block_hash: &[u8; 32],
+    previous_hash: &[u8; 32],
+    height: usize,
+    entries: &[(ScriptHash, AddressHistoryEntry)],
+) -> Vec<u8> {
+    let mut record: Vec<u8> = Vec::with_capacity(RECORD_HEADER_SIZE + entries.len() * ENTRY_SIZE);
+    record.push(kind);
+    record.extend_from_slice(block_hash);
+    record.extend_from_slice(previous_hash);
+    record.extend_from_slice(&(height as u32).to_le_bytes());
+    record.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (script_hash, entry) in entries {
+        record.push(match entry.is_spending {
+            true => SPENDING_ENTRY,
+            false => FUNDING_ENTRY,
+        });
+        record.extend_from_slice(script_hash);
+        record.extend_from_slice(&entry.txid);
+        record.extend_from_slice(&entry.outpoint.0);
+        record.extend_from_slice(&entry.outpoint.1.to_le_bytes());
+        record.extend_from_slice(&entry.value.to_le_bytes());
+    }
+    record
+}
+
+/// Returns the sha256 hash of the scriptPubKey.
+pub fn script_hash(script: &[u8]) -> ScriptHash {
+    sha256::Hash::hash(script).to_byte_array()
+}
+
+/// Returns the hash of the scriptPubKey that pays to the address of the network of the chain params.
+/// Returns an error if the address is not valid.
+pub fn address_script_hash(
+    address: &str,
+    params: &ChainParams,
+) -> Result<ScriptHash, NodeCustomErrors> {
+    let script = generate_pubkey_script(address, params)
+        .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
+    Ok(script_hash(&script))
+}
+
+/// Returns the entries of the block with the received height, in order. Each transaction has a spending
+/// entry for each output it spends, taken from the undo data, and a funding entry for each output it creates.
+fn block_entries(
+    block: &Block,
+    height: usize,
+    block_undo: &BlockUndo,
+) -> Vec<(ScriptHash, AddressHistoryEntry)> {
+    let spent_outputs: HashMap<([u8; 32], u32), _> = block_undo
+        .spent_outputs
+        .iter()
+        .map(|spent_output| {
+            (
+                (
+                    spent_output.outpoint.hash(),
+                    spent_output.outpoint.index() as u32,
+                ),
+                &spent_output.tx_out,
+            )
+        })
+        .collect();
+    let mut entries = Vec::new();
+    for tx in &block.txn {
+        let txid = tx.hash();
+        for tx_in in &tx.tx_in {
+            let outpoint = (
+                tx_in.get_previous_output_hash(),
+                tx_in.get_previous_output_index() as u32,
+            );
+            // the coinbase input and the outputs created before the first block downloaded have no undo data
+            if let Some(tx_out) = spent_outputs.get(&outpoint) {
+                entries.push((
+                    script_hash(tx_out.get_pub_key_script()),
+                    AddressHistoryEntry::new(true, txid, outpoint, tx_out.value(), height),
+                ));
+            }
+        }
+        for (index, tx_out) in tx.tx_out.iter().enumerate() {
+            entries.push((
+                script_hash(tx_out.get_pub_key_script()),
+                AddressHistoryEntry::new(false, txid, (txid, index as u32), tx_out.value(), height),
+            ));
+        }
+    }
+    entries
+}
+
+/// Reads the record that starts in the offset and moves the offset to the next one. Returns None if
+/// the record was not completely written or has an unknown kind.
+fn unmarshalling_record(data: &[u8], offset: &mut usize) -> Option<AddressIndexRecord> {
+    let header = data.get(*offset..*offset + RECORD_HEADER_SIZE)?;
+    let kind = header[0];
+    if kind != CONNECTED_RECORD && kind != DISCONNECTED_RECORD {
+        return None;
+    }
+    let block_hash = read_hash(&header[1..33]);
+    let previous_hash = read_hash(&header[33..65]);
+    let height = read_u32(&header[65..69]) as usize;
+    let count = read_u32(&header[69..73]) as usize;
+    let entries_start = *offset + RECORD_HEADER_SIZE;
+    let entries_bytes = data.get(entries_start..entries_start + count * ENTRY_SIZE)?;
+    let mut entries = Vec::with_capacity(count);
+    for entry in entries_bytes.chunks_exact(ENTRY_SIZE) {
+        let mut value_bytes = [0u8; 8];
+        value_bytes.copy_from_slice(&entry[101..109]);
+        entries.push((
+            read_hash(&entry[1..33]),
+            AddressHistoryEntry::new(
+                entry[0] == SPENDING_ENTRY,
+                read_hash(&entry[33..65]),
+                (read_hash(&entry[65..97]), read_u32(&entry[97..101])),
+                i64::from_le_bytes(value_bytes),
+                height,
+            ),
+        ));
+    }
+    *offset = entries_start + count * ENTRY_SIZE;
+    Some((kind, block_hash, previous_hash, height, entries))
+}
+
+/// Reads a hash of 32 bytes from the slice.
+fn read_hash(bytes: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(bytes);
+    hash
+}
+
+/// Reads a u32 in little endian from the slice.
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut number = [0u8; 4];
+    number.copy_from_slice(bytes);
+    u32::from_le_bytes(number)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{script_hash, AddressIndex};
+    use crate::{
+        blocks::{
+            block::Block,
+            block_header::BlockHeader,
+            block_undo::{BlockUndo, SpentOutput},
+        },
+        compact_size_uint::CompactSizeUint,
+        custom_errors::NodeCustomErrors,
+        temp_directory::TempDirectory,
+        transactions::{
+            outpoint::Outpoint, script::sig_script::SigScript, transaction::Transaction,
+            tx_in::TxIn, tx_out::TxOut,
+        },
+    };
+
+    const SCRIPT: [u8; 3] = [0x51, 0x52, 0x87];
+
+    /// Creates a transaction that spends the received outpoint and has an output with the received value
+    fn create_transaction(previous_output: ([u8; 32], u32), value: i64) -> Transaction {
+        let tx_in = TxIn::new(
+            Outpoint::new(previous_output.0, previous_output.1),
+            CompactSizeUint::new(0),
+            None,
+            SigScript::new(vec![]),
+            0xffffffff,
+        );
+        let tx_out = TxOut::new(value, CompactSizeUint::new(3), SCRIPT.to_vec());
+        Transaction::new(
+            1,
+            CompactSizeUint::new(1),
+            vec![tx_in],
+            CompactSizeUint::new(1),
+            vec![tx_out],
+            0,
+        )
+    }
+
+    /// Creates a block with the received previous hash and transactions
+    fn create_block(previous_hash: [u8; 32], txn: Vec<Transaction>) -> Block {
+        let block_header = BlockHeader::new(1, previous_hash, [2; 32], 0, 0x1d00ffff, 0);
+        Block::new(block_header, CompactSizeUint::new(txn.len() as u128), txn)
+    }
+
+    /// Creates the undo data of a block that spends the first output of the received transaction
+    fn create_undo(spent_tx: &Transaction, height: usize) -> BlockUndo {
+        let mut block_undo = BlockUndo::new();
+        block_undo.add_spent_outputs(vec![SpentOutput::new(
+            Outpoint::new(spent_tx.hash(), 0),
+            spent_tx.tx_out[0].clone(),
+            height,
+            false,
+        )]);
+        block_undo
+    }
+
+    #[test]
+    fn test_spent_outputs_are_in_the_history_but_not_unspent() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("address_index_spent");
+        let directory_str = directory.path_string();
+        let funding_tx = create_transaction(([1; 32], 0), 100);
+        let first_block = create_block([0; 32], vec![funding_tx.clone()]);
+        let spending_tx = create_transaction((funding_tx.hash(), 0), 90);
+        let second_block = create_block(first_block.hash(), vec![spending_tx.clone()]);
+        let mut address_index = AddressIndex::open(&directory_str)?;
+        address_index.block_connected(&first_block, 1, &BlockUndo::new())?;
+        address_index.block_connected(&second_block, 2, &create_undo(&funding_tx, 1))?;
+
+        let history = address_index.history(&script_hash(&SCRIPT));
+        assert_eq!(history.len(), 3);
+        assert!(history[1].is_spending);
+        assert_eq!(history[1].txid, spending_tx.hash());
+        assert_eq!(history[1].outpoint, (funding_tx.hash(), 0));
+        assert_eq!(history[1].height, 2);
+        let unspent = address_index.unspent_outputs(&script_hash(&SCRIPT));
+        assert_eq!(unspent.len(), 1);
+        assert_eq!(unspent[0].outpoint, (spending_tx.hash(), 0));
+        assert_eq!(unspent[0].value, 90);
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_is_loaded_when_the_index_is_opened_again() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("address_index_reopen");
+        let directory_str = directory.path_string();
+        let funding_tx = create_transaction(([1; 32], 0), 100);
+        let first_block = create_block([0; 32], vec![funding_tx.clone()]);
+        let spending_tx = create_transaction((funding_tx.hash(), 0), 90);
+        let second_block = create_block(first_block.hash(), vec![spending_tx]);
+        let history = {
+            let mut address_index = AddressIndex::open(&directory_str)?;
+            address_index.block_connected(&first_block, 1, &BlockUndo::new())?;
+            address_index.block_connected(&second_block, 2, &create_undo(&funding_tx, 1))?;
+            address_index.history(&script_hash(&SCRIPT))
+        };
+        let address_index = AddressIndex::open(&directory_str)?;
+        assert_eq!(address_index.history(&script_hash(&SCRIPT)), history);
+        assert_eq!(address_index.best_block_hash(), second_block.hash());
+        assert_eq!(address_index.height(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_disconnected_block_restores_the_spent_output() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("address_index_disconnect");
+        let directory_str = directory.path_string();
+        let funding_tx = create_transaction(([1; 32], 0), 100);
+        let first_block = create_block([0; 32], vec![funding_tx.clone()]);
+        let spending_tx = create_transaction((funding_tx.hash(), 0), 90);
+        let second_block = create_block(first_block.hash(), vec![spending_tx]);
+        let block_undo = create_undo(&funding_tx, 1);
+        {
+            let mut address_index = AddressIndex::open(&directory_str)?;
+            address_index.block_connected(&first_block, 1, &BlockUndo::new())?;
+            address_index.block_connected(&second_block, 2, &block_undo)?;
+            address_index.block_disconnected(&second_block, &block_undo)?;
+        }
+        let address_index = AddressIndex::open(&directory_str)?;
+        assert_eq!(address_index.history(&script_hash(&SCRIPT)).len(), 1);
+        let unspent = address_index.unspent_outputs(&script_hash(&SCRIPT));
+        assert_eq!(unspent[0].outpoint, (funding_tx.hash(), 0));
+        assert_eq!(address_index.best_block_hash(), first_block.hash());
+        assert_eq!(address_index.height(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compacted_index_keeps_the_history_of_the_connected_blocks(
+    ) -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("address_index_compact");
+        let directory_str = directory.path_string();
+        let funding_tx = create_transaction(([1; 32], 0), 100);
+        let first_block = create_block([0; 32], vec![funding_tx.clone()]);
+        let spending_tx = create_transaction((funding_tx.hash(), 0), 90);
+        let second_block = create_block(first_block.hash(), vec![spending_tx.clone()]);
+        let stale_block = create_block(
+            second_block.hash(),
+            vec![create_transaction((spending_tx.hash(), 0), 80)],
+        );
+        let history = {
+            let mut address_index = AddressIndex::open(&directory_str)?;
+            address_index.block_connected(&first_block, 1, &BlockUndo::new())?;
+            address_index.block_connected(&second_block, 2, &create_undo(&funding_tx, 1))?;
+            let history = address_index.history(&script_hash(&SCRIPT));
+            address_index.block_connected(&stale_block, 3, &create_undo(&spending_tx, 2))?;
+            address_index.block_disconnected(&stale_block, &create_undo(&spending_tx, 2))?;
+            address_index.compact()?;
+            history
+        };
+        let address_index = AddressIndex::open(&directory_str)?;
+        assert_eq!(address_index.history(&script_hash(&SCRIPT)), history);
+        assert_eq!(address_index.best_block_hash(), second_block.hash());
+        assert_eq!(address_index.height(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_already_indexed_is_ignored() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("address_index_ignored");
+        let directory_str = directory.path_string();
+        let block = create_block([0; 32], vec![create_transaction(([1; 32], 0), 100)]);
+        let mut address_index = AddressIndex::open(&directory_str)?;
+        address_index.block_connected(&block, 1, &BlockUndo::new())?;
+        address_index.block_connected(&block, 1, &BlockUndo::new())?;
+        assert_eq!(address_index.history(&script_hash(&SCRIPT)).len(), 1);
+        address_index.reset()?;
+        assert!(address_index.is_empty());
+        assert!(address_index.history(&script_hash(&SCRIPT)).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,628 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bitcoin_hashes::{sha256, Hash};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    chain_params::ChainParams,
+    custom_errors::NodeCustomErrors,
+    messages::{
+        addr_message::{ip_to_ipv6_bytes, NetworkAddress},
+        message_header::get_checksum,
+    },
+};
+
+const NEW_BUCKET_COUNT: usize = 256;
+const TRIED_BUCKET_COUNT: usize = 64;
+const BUCKET_SIZE: usize = 64;
+/// Failed connection attempts after which an address that never connected is not selected
+const MAX_FAILED_ATTEMPTS: u32 = 3;
+/// Seconds in the future that the time of a received address can be, as the clocks of the nodes differ
+const MAX_TIME_IN_FUTURE: u32 = 10 * 60;
+// File of the addresses, all the numbers in little endian except the ports:
+// <start string 4> <key 32> <new count u32> <entry>... <tried count u32> <entry>... <checksum 4>
+// entry: <bucket u16> <time u32> <services u64> <ip 16> <port u16 big endian> <attempts u32>
+// The checksum is the one of the messages of the protocol, calculated over all the previous bytes.
+const ENTRY_SIZE: usize = 2 + 4 + 8 + 16 + 2 + 4;
+
+pub type AddressManagerPointer = Arc<RwLock<AddressManager>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Address of a node known by the address manager and the failed attempts to connect to it since the last connection.
+struct AddressEntry {
+    address: NetworkAddress,
+    attempts: u32,
+}
+
+#[derive(Debug)]
+/// Stores the addresses of the nodes of the network that the node knows, to choose the ones to connect to.
+/// The addresses received from other nodes are placed in the new table, and move to the tried table once the node
+/// connects to them. Each table is divided in buckets and the bucket of an address is chosen with a secret key, from
+/// the group of the address and, in the new table, the group of the node that sent it (Ipv4 /16 or Ipv6 /32 networks),
+/// so that a single node can not fill the tables with its own addresses. The tables are stored in the peers file.
+pub struct AddressManager {
+    path: PathBuf,
+    chain_params: &'static ChainParams,
+    key: [u8; 32],
+    new_buckets: Vec<Vec<AddressEntry>>,
+    tried_buckets: Vec<Vec<AddressEntry>>,
+}
+
+impl AddressManager {
+    /// Creates an empty address manager of the network of the chain params received, stored in the file received,
+    /// with a new random key.
+    pub fn new(path: &str, chain_params: &'static ChainParams) -> AddressManager {
+        AddressManager {
+            path: PathBuf::from(path),
+            chain_params,
+            key: rand::thread_rng().gen(),
+            new_buckets: vec![Vec::new(); NEW_BUCKET_COUNT],
+            tried_buckets: vec![Vec::new(); TRIED_BUCKET_COUNT],
+        }
+    }
+
+    /// Opens the address manager stored in the file received, or creates an empty one if the file does not exist.
+    /// Returns an error if the file can not be read, if it is corrupted or if it belongs to another network.
+    pub fn open(
+        path: &str,
+        chain_params: &'static ChainParams,
+    ) -> Result<AddressManager, NodeCustomErrors> {
+        let mut address_manager = AddressManager::new(path, chain_params);
+        if !Path::new(path).exists() {
+            return Ok(address_manager);
+        }
+        let mut data: Vec<u8> = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        address_manager
+            .unmarshalling(&data)
+            .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+        Ok(address_manager)
+    }
+
+    /// Writes the tables in the file of the address manager. They are written in a temporary file that is
+    /// renamed, so the file is never partially written. Returns an error if it can not be written.
+    pub fn save(&self) -> Result<(), NodeCustomErrors> {
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)
+                .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        }
+        let temporary_path = PathBuf::from(format!("{}.tmp", self.path.display()));
+        let mut file = File::create(&temporary_path)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        file.write_all(&self.marshalling())
+            .and_then(|_| file.sync_all())
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        fs::rename(&temporary_path, &self.path)
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))
+    }
+
+    /// Adds the address received from the node of the source ip to the new table. If it is already known,
+    /// its time and services are updated. If its bucket is full, the address seen longest ago is replaced.
+    /// The addresses seen in the future and, unless the network runs in local hosts, the ones that are not
+    /// reachable from the internet are ignored.
+    /// Returns true if the address was added.
+    pub fn add(&mut self, address: NetworkAddress, source: IpAddr) -> bool {
+        if address.port == 0
+            || address.ip.is_unspecified()
+            || !(is_routable(&address.ip) || self.chain_params.accept_local_addresses)
+            || address.time > current_time().saturating_add(MAX_TIME_IN_FUTURE)
+        {
+            return false;
+        }
+        if let Some(entry) = self.find_mut(&address.socket_addr()) {
+            if address.time > entry.address.time {
+                entry.address.time = address.time;
+            }
+            entry.address.services |= address.services;
+            return false;
+        }
+        let bucket = self.new_bucket(&address.ip, &source);
+        insert_in_bucket(
+            &mut self.new_buckets[bucket],
+            AddressEntry {
+                address,
+                attempts: 0,
+            },
+        );
+        true
+    }
+
+    /// Moves the address to the tried table after a successful connection, and resets its failed attempts.
+    /// If its bucket of the tried table is full, the address seen longest ago goes back to the new table.
+    pub fn mark_good(&mut self, socket_addr: SocketAddr) {
+        let time = current_time();
+        if let Some(entry) = find_in_buckets(&mut self.tried_buckets, &socket_addr) {
+            entry.address.time = time;
+            entry.attempts = 0;
+            return;
+        }
+        let mut address = remove_from_buckets(&mut self.new_buckets, &socket_addr)
+            .map(|entry| entry.address)
+            .unwrap_or(NetworkAddress::new(time, 0, socket_addr));
+        address.time = time;
+        let bucket = self.tried_bucket(&socket_addr);
+        let evicted = insert_in_bucket(
+            &mut self.tried_buckets[bucket],
+            AddressEntry {
+                address,
+                attempts: 0,
+            },
+        );
+        if let Some(evicted) = evicted {
+            let new_bucket = self.new_bucket(&evicted.address.ip, &evicted.address.ip);
+            insert_in_bucket(&mut self.new_buckets[new_bucket], evicted);
+        }
+    }
+
+    /// Counts a failed attempt to connect to the address.
+    pub fn mark_attempt(&mut self, socket_addr: SocketAddr) {
+        if let Some(entry) = self.find_mut(&socket_addr) {
+            entry.attempts += 1;
+        }
+    }
+
+    /// Chooses up to the amount received of addresses to connect to, randomly from both tables. The addresses
+    /// received and the ones whose connection failed too many times are not chosen.
+    pub fn select(&self, amount: usize, exclude: &[SocketAddr]) -> Vec<SocketAddr> {
+        let mut rng = rand::thread_rng();
+        let is_candidate = |entry: &&AddressEntry| {
+            entry.attempts < MAX_FAILED_ATTEMPTS && !exclude.contains(&entry.address.socket_addr())
+        };
+        let mut tried: Vec<&AddressEntry> = self
+            .tried_buckets
+            .iter()
+            .flatten()
+            .filter(is_candidate)
+            .collect();
+        let mut new: Vec<&AddressEntry> = self
+            .new_buckets
+            .iter()
+            .flatten()
+            .filter(is_candidate)
+            .collect();
+        tried.shuffle(&mut rng);
+        new.shuffle(&mut rng);
+        let mut selected = Vec::new();
+        while selected.len() < amount && !(tried.is_empty() && new.is_empty()) {
+            // both tables have the same chance of being chosen, so the known nodes are preferred without ignoring the new ones
+            let from_tried = !tried.is_empty() && (new.is_empty() || rng.gen_bool(0.5));
+            let entry = if from_tried { tried.pop() } else { new.pop() };
+            selected.extend(entry.map(|entry| entry.address.socket_addr()));
+        }
+        selected
+    }
+
+    /// Returns up to the amount received of random addresses of both tables, to send them to other nodes.
+    /// The addresses whose connection failed too many times are not returned.
+    pub fn get_addresses(&self, amount: usize) -> Vec<NetworkAddress> {
+        let mut addresses: Vec<NetworkAddress> = self
+            .tried_buckets
+            .iter()
+            .chain(self.new_buckets.iter())
+            .flatten()
+            .filter(|entry| entry.attempts < MAX_FAILED_ATTEMPTS)
+            .map(|entry| entry.address)
+            .collect();
+        addresses.shuffle(&mut rand::thread_rng());
+        addresses.truncate(amount);
+        addresses
+    }
+
+    /// Returns true if the address is in the tried table.
+    pub fn is_tried(&self, socket_addr: &SocketAddr) -> bool {
+        self.tried_buckets
+            .iter()
+            .flatten()
+            .any(|entry| entry.address.socket_addr() == *socket_addr)
+    }
+
+    /// Returns true if the address is in any of the tables.
+    pub fn contains(&self, socket_addr: &SocketAddr) -> bool {
+        self.is_tried(socket_addr)
+            || self
+                .new_buckets
+                .iter()
+                .flatten()
+                .any(|entry| entry.address.socket_addr() == *socket_addr)
+    }
+
+    /// Returns the amount of addresses of both tables.
+    pub fn len(&self) -> usize {
+        self.tried_buckets
+            .iter()
+            .chain(self.new_buckets.iter())
+            .map(|bucket| bucket.len())
+            .sum()
+    }
+
+    /// Returns true if there are no addresses.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the entry of the address in any of the tables.
+    fn find_mut(&mut self, socket_addr: &SocketAddr) -> Option<&mut AddressEntry> {
+        if let Some(entry) = find_in_buckets(&mut self.tried_buckets, socket_addr) {
+            return Some(entry);
+        }
+        find_in_buckets(&mut self.new_buckets, socket_addr)
+    }
+
+    /// Returns the bucket of the new table of the address received from the node of the source ip.
+    fn new_bucket(&self, ip: &IpAddr, source: &IpAddr) -> usize {
+        let mut data = self.key.to_vec();
+        data.extend(ip_group(ip));
+        data.extend(ip_group(source));
+        keyed_hash(&data) % NEW_BUCKET_COUNT
+    }
+
+    /// Returns the bucket of the tried table of the address.
+    fn tried_bucket(&self, socket_addr: &SocketAddr) -> usize {
+        let mut data = self.key.to_vec();
+        data.extend(ip_group(&socket_addr.ip()));
+        data.extend(ip_to_ipv6_bytes(&socket_addr.ip()));
+        data.extend(socket_addr.port().to_be_bytes());
+        keyed_hash(&data) % TRIED_BUCKET_COUNT
+    }
+
+    /// Serializes the key and the tables as they are stored in the file.
+    fn marshalling(&self) -> Vec<u8> {
+        let mut bytes = self.chain_params.magic.to_vec();
+        bytes.extend(self.key);
+        for buckets in [&self.new_buckets, &self.tried_buckets] {
+            let count: usize = buckets.iter().map(|bucket| bucket.len()).sum();
+            bytes.extend((count as u32).to_le_bytes());
+            for (index, bucket) in buckets.iter().enumerate() {
+                for entry in bucket {
+                    bytes.extend((index as u16).to_le_bytes());
+                    entry.address.marshalling(&mut bytes);
+                    bytes.extend(entry.attempts.to_le_bytes());
+                }
+            }
+        }
+        let checksum = get_checksum(&bytes);
+        bytes.extend(checksum);
+        bytes
+    }
+
+    /// Loads the key and the tables from the bytes of the file. Returns an error if the checksum does not match,
+    /// if the file belongs to another network or if its entries are not valid.
+    fn unmarshalling(&mut self, bytes: &[u8]) -> Result<(), &'static str> {
+        if bytes.len() < 4 + 32 + 4 + 4 {
+            return Err("The peers file is corrupted");
+        }
+        let (content, checksum) = bytes.split_at(bytes.len() - 4);
+        if get_checksum(content) != checksum {
+            return Err("The peers file is corrupted");
+        }
+        if content[..4] != self.chain_params.magic {
+            return Err("The peers file belongs to another network");
+        }
+        self.key.copy_from_slice(&content[4..36]);
+        let mut offset = 36;
+        for (buckets, bucket_count) in [
+            (&mut self.new_buckets, NEW_BUCKET_COUNT),
+            (&mut self.tried_buckets, TRIED_BUCKET_COUNT),
+        ] {
+            let count = read_u32(content, &mut offset)? as usize;
+            for _ in 0..count {
+                if content.len() < offset + ENTRY_SIZE {
+                    return Err("The peers file is corrupted");
+                }
+                let bucket = u16::from_le_bytes([content[offset], content[offset + 1]]) as usize;
+                offset += 2;
+                let address = NetworkAddress::unmarshalling(content, &mut offset)?;
+                let attempts = read_u32(content, &mut offset)?;
+                if bucket >= bucket_count {
+                    return Err("The peers file is corrupted");
+                }
+                insert_in_bucket(&mut buckets[bucket], AddressEntry { address, attempts });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Inserts the entry in the bucket. If it is full, the entry seen longest ago is removed and returned.
+fn insert_in_bucket(bucket: &mut Vec<AddressEntry>, entry: AddressEntry) -> Option<AddressEntry> {
+    let mut evicted = None;
+    if bucket.len() >= BUCKET_SIZE {
+        if let Some(oldest) = bucket
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, entry)| entry.address.time)
+            .map(|(index, _)| index)
+        {
+            evicted = Some(bucket.swap_remove(oldest));
+        }
+    }
+    bucket.push(entry);
+    evicted
+}
+
+/// Returns the entry of the address in the buckets received.
+fn find_in_buckets<'a>(
+    buckets: &'a mut [Vec<AddressEntry>],
+    socket_addr: &SocketAddr,
+) -> Option<&'a mut AddressEntry> {
+    buckets
+        .iter_mut()
+        .flatten()
+        .find(|entry| entry.address.socket_addr() == *socket_addr)
+}
+
+/// Removes the entry of the address from the buckets received and returns it.
+fn remove_from_buckets(
+    buckets: &mut [Vec<AddressEntry>],
+    socket_addr: &SocketAddr,
+) -> Option<AddressEntry> {
+    for bucket in buckets.iter_mut() {
+        if let Some(index) = bucket
+            .iter()
+            .position(|entry| entry.address.socket_addr() == *socket_addr)
+        {
+            return Some(bucket.swap_remove(index));
+        }
+    }
+    None
+}
+
+/// Returns the group of the ip: its /16 network if it is Ipv4 or its /32 network if it is Ipv6.
+/// The addresses of the same group are probably controlled by the same entity.
+fn ip_group(ip: &IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ipv4) => {
+            let octets = ipv4.octets();
+            vec![4, octets[0], octets[1]]
+        }
+        IpAddr::V6(ipv6) => {
+            let mut group = vec![6];
+            group.extend_from_slice(&ipv6.octets()[..4]);
+            group
+        }
+    }
+}
+
+/// Returns true if the ip can be reached from the internet, that is, if it is not unspecified, loopback,
+/// private, link-local, multicast or broadcast. The Ipv4-mapped Ipv6 addresses are checked as Ipv4.
+fn is_routable(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ipv4) => {
+            !(ipv4.is_unspecified()
+                || ipv4.is_loopback()
+                || ipv4.is_private()
+                || ipv4.is_link_local()
+                || ipv4.is_multicast()
+                || ipv4.is_broadcast())
+        }
+        IpAddr::V6(ipv6) => match ipv6.to_ipv4_mapped() {
+            Some(ipv4) => is_routable(&IpAddr::V4(ipv4)),
+            None => {
+                let first_segment = ipv6.segments()[0];
+                // fc00::/7 are the unique local addresses and fe80::/10 the link-local ones
+                !(ipv6.is_unspecified()
+                    || ipv6.is_loopback()
+                    || ipv6.is_multicast()
+                    || first_segment & 0xfe00 == 0xfc00
+                    || first_segment & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Returns the first 8 bytes of the sha256 hash of the data as a number.
+fn keyed_hash(data: &[u8]) -> usize {
+    let hash = sha256::Hash::hash(data).to_byte_array();
+    let mut number = [0; 8];
+    number.copy_from_slice(&hash[..8]);
+    u64::from_le_bytes(number) as usize
+}
+
+/// Reads a u32 in little endian from the offset received and updates it.
+fn read_u32(bytes: &[u8], offset: &mut usize) -> Result<u32, &'static str> {
+    if bytes.len() < *offset + 4 {
+        return Err("The peers file is corrupted");
+    }
+    let mut number = [0; 4];
+    number.copy_from_slice(&bytes[*offset..*offset + 4]);
+    *offset += 4;
+    Ok(u32::from_le_bytes(number))
+}
+
+/// Returns the current unix time in seconds.
+fn current_time() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as u32)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::{
+        chain_params::{MAINNET_PARAMS, REGTEST_PARAMS, TESTNET_PARAMS},
+        temp_directory::TempDirectory,
+    };
+
+    fn address(ip: &str, time: u32) -> Result<NetworkAddress, Box<dyn Error>> {
+        Ok(NetworkAddress::new(time, 1, ip.parse()?))
+    }
+
+    #[test]
+    fn test_addresses_received_are_added_to_the_new_table_once() -> Result<(), Box<dyn Error>> {
+        let mut address_manager = AddressManager::new("peers.dat", &REGTEST_PARAMS);
+        let source: IpAddr = "10.0.0.1".parse()?;
+
+        assert!(address_manager.add(address("1.2.3.4:8333", 10)?, source));
+        assert!(!address_manager.add(address("1.2.3.4:8333", 20)?, source));
+        assert!(!address_manager.add(address("0.0.0.0:8333", 20)?, source));
+
+        assert_eq!(address_manager.len(), 1);
+        assert!(!address_manager.is_tried(&"1.2.3.4:8333".parse()?));
+        assert_eq!(address_manager.get_addresses(10)[0].time, 20);
+        Ok(())
+    }
+
+    #[test]
+    fn test_addresses_not_reachable_from_the_internet_are_not_added_in_mainnet(
+    ) -> Result<(), Box<dyn Error>> {
+        let mut address_manager = AddressManager::new("peers.dat", &MAINNET_PARAMS);
+        let source: IpAddr = "10.0.0.1".parse()?;
+
+        for ip in [
+            "127.0.0.1:8333",
+            "10.1.2.3:8333",
+            "192.168.0.1:8333",
+            "172.16.0.1:8333",
+            "169.254.0.1:8333",
+            "[::1]:8333",
+            "[fd00::1]:8333",
+            "[fe80::1]:8333",
+            "[::ffff:192.168.0.1]:8333",
+        ] {
+            assert!(!address_manager.add(address(ip, 10)?, source));
+        }
+
+        assert!(address_manager.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_addresses_are_added_in_regtest() -> Result<(), Box<dyn Error>> {
+        let mut address_manager = AddressManager::new("peers.dat", &REGTEST_PARAMS);
+
+        assert!(address_manager.add(address("127.0.0.1:18444", 10)?, "127.0.0.1".parse()?));
+
+        assert_eq!(address_manager.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_addresses_seen_in_the_future_are_not_added() -> Result<(), Box<dyn Error>> {
+        let mut address_manager = AddressManager::new("peers.dat", &REGTEST_PARAMS);
+        let source: IpAddr = "10.0.0.1".parse()?;
+        let now = current_time();
+
+        assert!(!address_manager.add(
+            address("1.2.3.4:8333", now + 2 * MAX_TIME_IN_FUTURE)?,
+            source
+        ));
+        assert!(address_manager.add(address("5.6.7.8:8333", now + 60)?, source));
+
+        assert_eq!(address_manager.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_address_connected_moves_to_the_tried_table() -> Result<(), Box<dyn Error>> {
+        let mut address_manager = AddressManager::new("peers.dat", &REGTEST_PARAMS);
+        let socket_addr: SocketAddr = "1.2.3.4:8333".parse()?;
+        address_manager.add(address("1.2.3.4:8333", 10)?, "10.0.0.1".parse()?);
+
+        address_manager.mark_good(socket_addr);
+
+        assert!(address_manager.is_tried(&socket_addr));
+        assert_eq!(address_manager.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_addresses_that_failed_too_many_times_are_not_selected() -> Result<(), Box<dyn Error>> {
+        let mut address_manager = AddressManager::new("peers.dat", &REGTEST_PARAMS);
+        let source: IpAddr = "10.0.0.1".parse()?;
+        address_manager.add(address("1.2.3.4:8333", 10)?, source);
+        address_manager.add(address("5.6.7.8:8333", 10)?, source);
+        address_manager.add(address("9.9.9.9:8333", 10)?, source);
+        address_manager.add(address("[2001:db8::1]:8333", 10)?, source);
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            address_manager.mark_attempt("1.2.3.4:8333".parse()?);
+        }
+
+        let mut selected = address_manager.select(5, &["5.6.7.8:8333".parse()?]);
+        selected.sort();
+
+        assert_eq!(
+            selected,
+            vec!["9.9.9.9:8333".parse()?, "[2001:db8::1]:8333".parse()?]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_bucket_replaces_the_address_seen_longest_ago() -> Result<(), Box<dyn Error>> {
+        let mut address_manager = AddressManager::new("peers.dat", &REGTEST_PARAMS);
+        // all the addresses of the same group sent by the same source go to the same bucket
+        let source: IpAddr = "10.0.0.1".parse()?;
+        for i in 0..BUCKET_SIZE {
+            address_manager.add(
+                address(&format!("1.2.3.{}:8333", i), 100 + i as u32)?,
+                source,
+            );
+        }
+
+        address_manager.add(address("1.2.4.1:8333", 1000)?, source);
+
+        assert_eq!(address_manager.len(), BUCKET_SIZE);
+        assert!(!address_manager.contains(&"1.2.3.0:8333".parse()?));
+        assert!(address_manager.contains(&"1.2.4.1:8333".parse()?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_address_manager_is_loaded_from_the_peers_file() -> Result<(), Box<dyn Error>> {
+        let directory = TempDirectory::new("address_manager_load");
+        let path = directory
+            .path()
+            .join("peers.dat")
+            .to_string_lossy()
+            .to_string();
+        let mut address_manager = AddressManager::new(&path, &REGTEST_PARAMS);
+        address_manager.add(address("1.2.3.4:8333", 10)?, "10.0.0.1".parse()?);
+        address_manager.add(address("[2001:db8::1]:8333", 10)?, "10.0.0.1".parse()?);
+        address_manager.mark_good("5.6.7.8:18444".parse()?);
+        address_manager.save()?;
+
+        let address_manager_loaded = AddressManager::open(&path, &REGTEST_PARAMS)?;
+        let other_network = AddressManager::open(&path, &TESTNET_PARAMS);
+
+        assert_eq!(address_manager_loaded.len(), 3);
+        assert_eq!(address_manager_loaded.key, address_manager.key);
+        assert!(address_manager_loaded.is_tried(&"5.6.7.8:18444".parse()?));
+        assert!(address_manager_loaded.contains(&"[2001:db8::1]:8333".parse()?));
+        assert!(other_network.is_err());
+        assert!(!directory.path().join("peers.dat.tmp").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted_peers_file_is_not_loaded() -> Result<(), Box<dyn Error>> {
+        let directory = TempDirectory::new("address_manager_corrupted");
+        let path = directory
+            .path()
+            .join("peers.dat")
+            .to_string_lossy()
+            .to_string();
+        let mut address_manager = AddressManager::new(&path, &REGTEST_PARAMS);
+        address_manager.add(address("1.2.3.4:8333", 10)?, "10.0.0.1".parse()?);
+        let mut bytes = address_manager.marshalling();
+        bytes[40] ^= 0xff;
+        fs::write(&path, bytes)?;
+
+        let result = AddressManager::open(&path, &REGTEST_PARAMS);
+
+        assert!(result.is_err());
+        Ok(())
+    }
+}
@@ -1,48 +1,579 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, RwLock},
 };
 
 use crate::{
-    blocks::{block::Block, block_header::BlockHeader},
+    address_index::{address_script_hash, AddressIndex},
+    blocks::{
+        block::Block,
+        block_header::BlockHeader,
+        block_store::{BlockStore, MIN_BLOCKS_TO_KEEP},
+        header_store::HeaderStore,
+        header_tree::HeaderTree,
+        header_validation::HeaderChain,
+    },
+    chain_params::ChainParams,
+    chainstate::ChainState,
+    custom_errors::NodeCustomErrors,
+    filter_index::FilterIndex,
+    transactions::transaction::{Transaction, LOCKTIME_THRESHOLD},
+    tx_index::TxIndex,
     utxo_tuple::UtxoTuple,
 };
 type UtxoSetPointer = Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>;
+type TxIndexPointer = Arc<RwLock<TxIndex>>;
+type AddressIndexPointer = Arc<RwLock<AddressIndex>>;
+type FilterIndexPointer = Arc<RwLock<FilterIndex>>;
+type Height = usize;
+type Confirmations = usize;
+/// Amount of the most recent blocks stored that are also kept in memory, the older ones are read from the block store.
+const MAX_BLOCKS_IN_MEMORY: usize = 16;
+pub type TransactionSearchResult =
+    Result<Option<(Transaction, BlockHeader, Height, Confirmations)>, NodeCustomErrors>;
+
+/// Stores the pointers of the headers of the active chain with their heights, the header store that keeps them
+/// in disk and the header tree, to create a Blockchain.
+pub struct HeaderPointers {
+    pub headers: Arc<RwLock<Vec<BlockHeader>>>,
+    pub header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
+    pub header_store: Arc<RwLock<HeaderStore>>,
+    pub header_tree: Arc<RwLock<HeaderTree>>,
+}
+
+/// Stores the pointers of the blocks kept in memory, the block store, the UTXO set and its chainstate, to create
+/// a Blockchain.
+pub struct BlockPointers {
+    pub blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
+    pub block_store: Arc<RwLock<BlockStore>>,
+    pub utxo_set: UtxoSetPointer,
+    pub chainstate: Arc<RwLock<ChainState>>,
+}
+
+/// Stores the pointers of the indexes that are enabled, to create a Blockchain.
+pub struct IndexPointers {
+    pub tx_index: Option<TxIndexPointer>,
+    pub address_index: Option<AddressIndexPointer>,
+    pub filter_index: Option<FilterIndexPointer>,
+}
 
 #[derive(Debug, Clone)]
-/// Represents the blockchain with its blocks, headers, heights and UTXO set.
+/// Represents the blockchain of the network of the chain params with its blocks, headers, heights and UTXO set.
+/// The headers list is the active chain, the one with the most cumulative work, and the header store keeps it in disk.
+/// The header tree stores its chainwork and the headers of the competing branches.
+/// The blocks are stored in disk in the block store, with the undo data of the connected blocks (the outputs they spent),
+/// and the most recent ones are also kept in memory. If pruning is enabled, the oldest blocks are removed from both with their undo data,
+/// and the block store tracks the height up to which the blocks were pruned. The chainstate stores the UTXO set in disk. If it is enabled, the transaction
+/// index stores the block and position of the transactions of the active chain, the address index
+/// stores the outputs received and spent by each scriptPubKey and the filter index stores the compact
+/// block filters.
 pub struct Blockchain {
+    pub chain_params: &'static ChainParams,
     pub headers: Arc<RwLock<Vec<BlockHeader>>>,
+    pub header_store: Arc<RwLock<HeaderStore>>,
     pub blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
     pub header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
     pub utxo_set: UtxoSetPointer,
+    pub header_tree: Arc<RwLock<HeaderTree>>,
+    pub block_store: Arc<RwLock<BlockStore>>,
+    pub chainstate: Arc<RwLock<ChainState>>,
+    pub tx_index: Option<TxIndexPointer>,
+    pub address_index: Option<AddressIndexPointer>,
+    pub filter_index: Option<FilterIndexPointer>,
 }
 
 impl Blockchain {
-    /// Creates a new Blockchain that groups the headers, blocks, heights and UTXO set.
+    /// Creates a new Blockchain of the network of the chain params that groups the headers with their store and tree, blocks,
+    /// heights, UTXO set with its chainstate and block store with the optional transaction, address and filter indexes.
     pub fn new(
-        headers: Arc<RwLock<Vec<BlockHeader>>>,
-        blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
-        header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
-        utxo_set: UtxoSetPointer,
+        chain_params: &'static ChainParams,
+        headers: HeaderPointers,
+        blocks: BlockPointers,
+        indexes: IndexPointers,
     ) -> Self {
         Blockchain {
-            headers,
-            blocks,
-            header_heights,
-            utxo_set,
+            chain_params,
+            headers: headers.headers,
+            header_store: headers.header_store,
+            blocks: blocks.blocks,
+            header_heights: headers.header_heights,
+            utxo_set: blocks.utxo_set,
+            header_tree: headers.header_tree,
+            block_store: blocks.block_store,
+            chainstate: blocks.chainstate,
+            tx_index: indexes.tx_index,
+            address_index: indexes.address_index,
+            filter_index: indexes.filter_index,
+        }
+    }
+
+    /// Checks that the block extends the best block of the UTXO set, validates its transactions and their scripts
+    /// against the UTXO set, and their lock times against the headers of the active chain previous to the block, stores
+    /// the block and connects it to the UTXO set, with the received height, writing its undo data in the block store,
+    /// marking its outputs as dirty in the chainstate and adding its transactions to the transaction, address and filter indexes.
+    /// Returns InvalidBlockError if the block does not extend the UTXO set or its transactions are not valid,
+    /// or error if the block, the UTXO set, the undo data or the indexes cannot be updated.
+    pub fn connect_block(&self, block: &Block, height: usize) -> Result<(), NodeCustomErrors> {
+        let (utxo_set_is_complete, best_block_hash) = {
+            let chainstate = self
+                .chainstate
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+            // if no block was connected, the utxo set is complete if the block is the first one after the genesis
+            match chainstate.is_empty() {
+                true => (height <= 1, None),
+                false => (chainstate.is_complete(), Some(chainstate.best_block_hash())),
+            }
+        };
+        if best_block_hash.is_some_and(|hash| hash != block.block_header.previous_block_header_hash)
+        {
+            return Err(NodeCustomErrors::InvalidBlockError(
+                "The block does not extend the best block of the utxo set".to_string(),
+            ));
+        }
+        let (transactions_are_valid, reason) = {
+            let utxo_set = self
+                .utxo_set
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+            let headers = self
+                .headers
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+            let chain = HeaderChain::new(&headers[..height.min(headers.len())], &[]);
+            match block.validate_utxos(
+                &utxo_set,
+                height,
+                self.chain_params.subsidy_halving_interval,
+                utxo_set_is_complete,
+            ) {
+                (true, _) => match block.validate_lock_times(&utxo_set, height, &chain) {
+                    // the scripts are executed last, as it is the most expensive validation
+                    (true, _) => block.validate_scripts(&utxo_set),
+                    invalid => invalid,
+                },
+                invalid => invalid,
+            }
+        };
+        if !transactions_are_valid {
+            return Err(NodeCustomErrors::InvalidBlockError(reason.to_string()));
+        }
+        // the block is stored first, so that its undo data is written in the file of the block
+        self.store_block(block, height)?;
+        let block_undo = block
+            .give_me_utxos(self.utxo_set.clone(), height)
+            .map_err(|err| NodeCustomErrors::UtxoError(err.to_string()))?;
+        self.block_store
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .write_undo(&block.hash(), &block_undo)?;
+        if let Some(address_index) = &self.address_index {
+            address_index
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .block_connected(block, height, &block_undo)?;
+        }
+        if let Some(filter_index) = &self.filter_index {
+            filter_index
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .block_connected(block, height, &block_undo)?;
+        }
+        self.chainstate
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .block_connected(block, height);
+        if let Some(tx_index) = &self.tx_index {
+            tx_index
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .block_connected(block)?;
+        }
+        Ok(())
+    }
+
+    /// Disconnects the block from the UTXO set restoring the outputs it spent with its undo data,
+    /// marks the outputs as dirty in the chainstate and removes its transactions from the transaction,
+    /// address and filter indexes. The undo data is removed after all of them are updated, so that the
+    /// block can be disconnected again if any of them fails.
+    /// Returns error if the block has no undo data or the UTXO set cannot be updated.
+    pub fn disconnect_block(&self, block: &Block) -> Result<(), NodeCustomErrors> {
+        let block_undo = self
+            .block_store
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .read_undo(&block.hash())?
+            .ok_or_else(|| {
+                NodeCustomErrors::UtxoError(format!(
+                    "There is no undo data of the block {}",
+                    block.hex_hash()
+                ))
+            })?;
+        block
+            .disconnect_block(self.utxo_set.clone(), &block_undo)
+            .map_err(|err| NodeCustomErrors::UtxoError(err.to_string()))?;
+        self.chainstate
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .block_disconnected(block, &block_undo);
+        if let Some(tx_index) = &self.tx_index {
+            tx_index
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .block_disconnected(block)?;
+        }
+        if let Some(address_index) = &self.address_index {
+            address_index
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .block_disconnected(block, &block_undo)?;
+        }
+        if let Some(filter_index) = &self.filter_index {
+            filter_index
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .block_disconnected(block)?;
+        }
+        self.block_store
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .remove_undo(&block.hash());
+        Ok(())
+    }
+
+    /// Writes in disk the changes of the UTXO set since the last flush, with the tip of the active chain,
+    /// and then prunes the blocks that are no longer needed if pruning is enabled.
+    /// Returns error if the chainstate can not be written or the blocks can not be pruned.
+    pub fn flush_chainstate(&self) -> Result<(), NodeCustomErrors> {
+        let chainstate_height = {
+            let utxo_set = self
+                .utxo_set
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+            let mut chainstate = self
+                .chainstate
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+            chainstate.flush(&utxo_set)?;
+            chainstate.height()
+        };
+        self.prune_blocks(chainstate_height)?;
+        Ok(())
+    }
+
+    /// Removes from the block store and from memory the oldest blocks up to the received height of the
+    /// flushed chainstate, except the last MIN_BLOCKS_TO_KEEP blocks of the active chain, with their undo data.
+    /// Returns the amount of blocks pruned, 0 if pruning is disabled, or error if they can not be removed.
+    pub fn prune_blocks(&self, chainstate_height: usize) -> Result<usize, NodeCustomErrors> {
+        // the height of the tip is the amount of headers minus one, as the first one is the genesis
+        let amount_of_headers = self
+            .headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .len();
+        let max_height =
+            chainstate_height.min(amount_of_headers.saturating_sub(MIN_BLOCKS_TO_KEEP + 1));
+        let pruned_blocks = self
+            .block_store
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .prune(max_height)?;
+        let mut blocks = self
+            .blocks
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        for hash in &pruned_blocks {
+            blocks.remove(hash);
+        }
+        Ok(pruned_blocks.len())
+    }
+
+    /// Returns the height of the highest block pruned, 0 if no block was pruned or it can't get the lock.
+    pub fn pruned_height(&self) -> usize {
+        match self.block_store.read() {
+            Ok(block_store) => block_store.pruned_height(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Returns true if the block with the received hash was pruned, so it can't be sent to the peers.
+    pub fn is_block_pruned(&self, hash: &[u8; 32]) -> bool {
+        match self.block_store.read() {
+            Ok(block_store) => block_store.is_pruned(hash),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns true if the UTXO set was loaded from the first block after the genesis, so it contains
+    /// all the unspent outputs. If the blocks were downloaded from a later date it is incomplete.
+    pub fn utxo_set_is_complete(&self) -> bool {
+        match self.chainstate.read() {
+            Ok(chainstate) => chainstate.is_complete(),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns true if a transaction with the received lock time can be included in the next block of the active chain.
+    /// Lock times below 500000000 are heights and the rest are timestamps, which are compared with the median time
+    /// past of the tip (BIP113). Returns false if it can't get the lock.
+    pub fn lock_time_is_reached(&self, lock_time: u32) -> bool {
+        if lock_time == 0 {
+            return true;
+        }
+        let headers = match self.headers.read() {
+            Ok(headers) => headers,
+            Err(_) => return false,
+        };
+        if lock_time < LOCKTIME_THRESHOLD {
+            // the height of the next block is the amount of headers, since the first one is the genesis
+            return (lock_time as usize) < headers.len();
+        }
+        lock_time < HeaderChain::new(&headers, &[]).median_time_past()
+    }
+
+    /// Returns true if the undo data of the block with the received hash is stored.
+    pub fn has_undo_data(&self, hash: &[u8; 32]) -> bool {
+        match self.block_store.read() {
+            Ok(block_store) => block_store.has_undo(hash),
+            Err(_) => false,
         }
     }
 
     /// Searchs a block in the blockchain.
     /// Receives the hash of the block in hex format.
+    /// If the block is not in memory, it is read from the block store.
     /// Returns the block if it finds it, None if it can't get the lock or if it doesn't find it.
     pub fn search_block(&self, hash: [u8; 32]) -> Option<Block> {
         if let Ok(blocks) = self.blocks.read() {
-            return blocks.get(&hash).cloned();
-        } else {
-            None
+            if let Some(block) = blocks.get(&hash) {
+                return Some(block.clone());
+            }
+        }
+        match self.block_store.read() {
+            Ok(block_store) => block_store.read_block(&hash).ok().flatten(),
+            Err(_) => None,
+        }
+    }
+
+    /// Writes the block with the received height in the block store and adds it to the blocks in memory.
+    /// If there are more than MAX_BLOCKS_IN_MEMORY blocks in memory, the one with the lowest height is removed.
+    /// Returns error if the block can not be written in disk.
+    pub fn store_block(&self, block: &Block, height: usize) -> Result<(), NodeCustomErrors> {
+        let mut block_store = self
+            .block_store
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        block_store.write_block(block, height)?;
+        let mut blocks = self
+            .blocks
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        blocks.insert(block.hash(), block.clone());
+        if blocks.len() > MAX_BLOCKS_IN_MEMORY {
+            let oldest_block = blocks
+                .keys()
+                .min_by_key(|hash| block_store.location(hash).map(|location| location.height))
+                .copied();
+            if let Some(hash) = oldest_block {
+                blocks.remove(&hash);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds to the transaction index the stored blocks of the active chain after its last indexed block.
+    /// If that block is not in the active chain, the index is built again from the first block.
+    /// Returns the amount of blocks indexed, or error if the index can not be written.
+    pub fn sync_tx_index(&self) -> Result<usize, NodeCustomErrors> {
+        let tx_index = match &self.tx_index {
+            Some(tx_index) => tx_index,
+            None => return Ok(0),
+        };
+        let mut tx_index = tx_index
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        self.index_stored_blocks(&mut tx_index)
+    }
+
+    /// Adds to the received transaction index the stored blocks of the active chain after its last indexed block,
+    /// resetting it first if that block is not in the active chain. The caller holds the lock of the index for the
+    /// whole sync, so the blocks connected meanwhile are added after it.
+    /// Returns the amount of blocks indexed, or error if the index can not be written.
+    fn index_stored_blocks(&self, tx_index: &mut TxIndex) -> Result<usize, NodeCustomErrors> {
+        // the hash of the genesis is stored as zeros, as the best block of an empty index
+        let best_block_height = self
+            .header_heights
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .get(&tx_index.best_block_hash())
+            .copied();
+        let first_height = match best_block_height {
+            Some(height) => height + 1,
+            None => {
+                tx_index.reset()?;
+                1
+            }
+        };
+        let hashes: Vec<[u8; 32]> = self
+            .headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .iter()
+            .skip(first_height)
+            .map(|header| header.hash())
+            .collect();
+        let mut indexed_blocks = 0;
+        for hash in hashes {
+            // the blocks before the first block to download are not stored
+            if let Some(block) = self.search_block(hash) {
+                tx_index.block_connected(&block)?;
+                indexed_blocks += 1;
+            }
         }
+        Ok(indexed_blocks)
+    }
+
+    /// Removes all the transactions of the transaction index and builds it again from the stored blocks
+    /// of the active chain. The index is locked during the whole rebuild, so no block is indexed in the middle of it.
+    /// Returns the amount of blocks indexed, or error if the index is disabled or can not be written.
+    pub fn reindex_transactions(&self) -> Result<usize, NodeCustomErrors> {
+        let mut tx_index = self
+            .tx_index
+            .as_ref()
+            .ok_or_else(tx_index_disabled_error)?
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        tx_index.reset()?;
+        self.index_stored_blocks(&mut tx_index)
+    }
+
+    /// Searchs a transaction of the active chain in the transaction index.
+    /// Returns the transaction with the header and height of its block and its amount of confirmations
+    /// if it finds it, None if it doesn't find it, or error if the transaction index is disabled.
+    pub fn search_transaction(&self, txid: [u8; 32]) -> TransactionSearchResult {
+        let location = self
+            .tx_index
+            .as_ref()
+            .ok_or_else(tx_index_disabled_error)?
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .get(&txid);
+        let (block_hash, position) = match location {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let (block_header, height) = match self.search_header(block_hash) {
+            Some(header_and_height) => header_and_height,
+            None => return Ok(None),
+        };
+        let transaction = match self
+            .search_block(block_hash)
+            .and_then(|block| block.txn.get(position).cloned())
+        {
+            Some(transaction) => transaction,
+            None => return Ok(None),
+        };
+        // the confirmations are counted up to the last block connected to the utxo set
+        let chainstate_height = self
+            .chainstate
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .height();
+        let confirmations = (chainstate_height + 1).saturating_sub(height);
+        Ok(Some((transaction, block_header, height, confirmations)))
+    }
+
+    /// Returns the utxos of the UTXO set that pay to the address. If the address index is enabled, only
+    /// the outputs it has as unspent are read from the UTXO set, otherwise the whole UTXO set is scanned.
+    /// Returns error if the address is not valid or it can't get the locks.
+    pub fn utxos_of_address(&self, address: &str) -> Result<Vec<UtxoTuple>, NodeCustomErrors> {
+        let utxo_set = self
+            .utxo_set
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let address_index = match &self.address_index {
+            Some(address_index) => address_index,
+            None => {
+                return Ok(utxo_set
+                    .values()
+                    .filter_map(|utxo| utxo.referenced_utxos(address, self.chain_params))
+                    .collect())
+            }
+        };
+        let unspent_outputs = address_index
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .unspent_outputs(&address_script_hash(address, self.chain_params)?);
+        // the outputs are grouped by their transaction, in the order of the chain
+        let mut indexes_by_tx: Vec<([u8; 32], Vec<usize>)> = Vec::new();
+        for entry in unspent_outputs {
+            let (txid, index) = entry.outpoint;
+            match indexes_by_tx.iter_mut().find(|(hash, _)| *hash == txid) {
+                Some((_, indexes)) => indexes.push(index as usize),
+                None => indexes_by_tx.push((txid, vec![index as usize])),
+            }
+        }
+        let mut utxos: Vec<UtxoTuple> = Vec::new();
+        for (txid, indexes) in indexes_by_tx {
+            if let Some(utxo) = utxo_set.get(&txid) {
+                let outputs: Vec<_> = utxo
+                    .utxo_set
+                    .iter()
+                    .filter(|(_, index)| indexes.contains(index))
+                    .cloned()
+                    .collect();
+                if !outputs.is_empty() {
+                    utxos.push(UtxoTuple::new(txid, outputs, utxo.height, utxo.is_coinbase));
+                }
+            }
+        }
+        Ok(utxos)
+    }
+
+    /// Returns the transactions of the active chain that funded the address or spent its outputs, in the
+    /// order of the chain, reading their blocks from the address index entries. If the address index is
+    /// disabled there is no history, so it returns an empty list.
+    /// Returns error if the address is not valid or it can't get the locks.
+    pub fn transactions_of_address(
+        &self,
+        address: &str,
+    ) -> Result<Vec<Transaction>, NodeCustomErrors> {
+        let address_index = match &self.address_index {
+            Some(address_index) => address_index,
+            None => return Ok(Vec::new()),
+        };
+        let history = address_index
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .history(&address_script_hash(address, self.chain_params)?);
+        let mut transactions: Vec<Transaction> = Vec::new();
+        let mut added_txids: HashSet<[u8; 32]> = HashSet::new();
+        let mut block: Option<Block> = None;
+        for entry in history {
+            if !added_txids.insert(entry.txid) {
+                continue;
+            }
+            let block_hash = match self
+                .headers
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .get(entry.height)
+            {
+                Some(header) => header.hash(),
+                None => continue,
+            };
+            // the entries of the same block are consecutive, so it is only read once
+            if block.as_ref().map(|block| block.hash()) != Some(block_hash) {
+                block = self.search_block(block_hash);
+            }
+            if let Some(tx) = block
+                .as_ref()
+                .and_then(|block| block.txn.iter().find(|tx| tx.hash() == entry.txid))
+            {
+                transactions.push(tx.clone());
+            }
+        }
+        Ok(transactions)
     }
 
     /// Searchs a header in the blockchain.
@@ -61,3 +592,10 @@ impl Blockchain {
         None
     }
 }
+
+/// Returns the error of a request to the transaction index when it is disabled.
+fn tx_index_disabled_error() -> NodeCustomErrors {
+    NodeCustomErrors::OtherError(
+        "The transaction index is disabled, set TX_INDEX=true in the config file".to_string(),
+    )
+}
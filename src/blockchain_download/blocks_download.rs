@@ -2,7 +2,7 @@ use gtk::glib;
 
 use crate::{
     blockchain_download::headers_download::amount_of_headers,
-    blocks::{block::Block, block_header::BlockHeader},
+    blocks::{block::Block, block_header::BlockHeader, block_store::BlockStore},
     config::Config,
     custom_errors::NodeCustomErrors,
     gtk::ui_events::{send_event_to_ui, UIEvent},
@@ -12,7 +12,6 @@ use crate::{
     },
 };
 use std::{
-    collections::HashMap,
     net::TcpStream,
     sync::{
         mpsc::{Receiver, Sender},
@@ -26,14 +25,11 @@ use super::{
     utils::{get_node, return_node_to_vec},
 };
 
-type BlocksAndHeaders = (
-    Arc<RwLock<HashMap<[u8; 32], Block>>>,
-    Arc<RwLock<Vec<BlockHeader>>>,
-);
+type BlocksAndHeaders = (Arc<RwLock<usize>>, Arc<RwLock<Vec<BlockHeader>>>);
 
 type BlocksTuple = (
     Vec<BlockHeader>,
-    Arc<RwLock<HashMap<[u8; 32], Block>>>,
+    Arc<RwLock<usize>>,
     Arc<RwLock<Vec<BlockHeader>>>,
 );
 
@@ -42,10 +38,11 @@ type BlocksTuple = (
 /// Downloads the blocks concurrently.
 /// ### Receives:
 /// - The reference to the list of nodes connected to.
-/// - The reference to the hashmap of blocks where they will be stored
+/// - The reference to the amount of blocks downloaded, as they are not kept in memory
 /// - The reference to the block headers downloaded
 /// - The channel where it receives the block headers
 /// - The channel where it returns the block headers when it can't download them
+/// - The block store, from which the blocks stored in previous executions are read instead of downloaded
 /// ### Error handling:
 /// It tries to download the blocks from another node in the following cases:
 /// - It couldn't send the request of the blocks
@@ -57,10 +54,11 @@ pub fn download_blocks(
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
     nodes: Arc<RwLock<Vec<TcpStream>>>,
-    (blocks, headers): BlocksAndHeaders,
+    (downloaded_blocks, headers): BlocksAndHeaders,
     (tx, rx): (Sender<Vec<BlockHeader>>, Receiver<Vec<BlockHeader>>),
-    tx_utxo_set: Sender<Vec<Block>>,
+    (tx_utxo_set, block_store): (Sender<Vec<Block>>, Arc<RwLock<BlockStore>>),
 ) -> Result<(), NodeCustomErrors> {
+    let mut amount_of_stored_blocks = 0;
     // recieves in the channel the vec of headers sent by the function downloading headers
     for blocks_to_download in rx {
         if blocks_to_download.is_empty() {
@@ -68,34 +66,44 @@ pub fn download_blocks(
                 "The list has 0 elements!".to_string(),
             ));
         }
-        // should have received 2000 headers
-        let mut n_threads = config.n_threads;
-        if blocks_to_download.len() <= config.blocks_download_per_node {
-            n_threads = 1;
-        }
-        let blocks_to_download_chunks =
-            divide_blocks_to_download_in_equal_chunks(blocks_to_download, n_threads);
-        let mut join_handles = vec![];
-        for blocks_to_download_chunk in blocks_to_download_chunks
-            .read()
-            .map_err(|err| NodeCustomErrors::CanNotRead(err.to_string()))?
-            .iter()
-        {
-            join_handles.push(download_blocks_chunck(
-                config,
-                log_sender,
-                ui_sender,
-                (blocks_to_download_chunk.clone(), headers.clone()),
-                nodes.clone(),
-                (tx.clone(), tx_utxo_set.clone()),
-                blocks.clone(),
-            )?);
+        let (blocks_to_download, stored_blocks) = load_stored_blocks(
+            config,
+            log_sender,
+            &block_store,
+            blocks_to_download,
+            &tx_utxo_set,
+        )?;
+        amount_of_stored_blocks += stored_blocks;
+        if !blocks_to_download.is_empty() {
+            // should have received 2000 headers
+            let mut n_threads = config.n_threads;
+            if blocks_to_download.len() <= config.blocks_download_per_node {
+                n_threads = 1;
+            }
+            let blocks_to_download_chunks =
+                divide_blocks_to_download_in_equal_chunks(blocks_to_download, n_threads);
+            let mut join_handles = vec![];
+            for blocks_to_download_chunk in blocks_to_download_chunks
+                .read()
+                .map_err(|err| NodeCustomErrors::CanNotRead(err.to_string()))?
+                .iter()
+            {
+                join_handles.push(download_blocks_chunck(
+                    config,
+                    log_sender,
+                    ui_sender,
+                    (blocks_to_download_chunk.clone(), headers.clone()),
+                    nodes.clone(),
+                    (tx.clone(), tx_utxo_set.clone()),
+                    downloaded_blocks.clone(),
+                )?);
+            }
+            join_threads(join_handles)?;
         }
-        join_threads(join_handles)?;
         let (amount_of_headers, amount_of_blocks) =
-            get_amount_of_headers_and_blocks(&headers, &blocks)?;
+            get_amount_of_headers_and_blocks(&headers, &downloaded_blocks)?;
         let total_blocks_to_download = amount_of_headers - config.height_first_block_to_download;
-        if amount_of_blocks == total_blocks_to_download {
+        if amount_of_blocks + amount_of_stored_blocks == total_blocks_to_download {
             write_in_log(&log_sender.info_log_sender, format!("All the blocks were downloaded correctly! DOWNLOADED BLOCKS: {}\n", amount_of_blocks).as_str());
             return Ok(());
         }
@@ -112,7 +120,7 @@ fn download_blocks_chunck(
     (block_headers, headers): (Vec<BlockHeader>, Arc<RwLock<Vec<BlockHeader>>>),
     nodes: Arc<RwLock<Vec<TcpStream>>>,
     (tx, tx_utxo_set): (Sender<Vec<BlockHeader>>, Sender<Vec<Block>>),
-    blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
+    downloaded_blocks: Arc<RwLock<usize>>,
 ) -> Result<JoinHandle<Result<(), NodeCustomErrors>>, NodeCustomErrors> {
     let config_cloned = config.clone();
     let log_sender_cloned = log_sender.clone();
@@ -123,7 +131,7 @@ fn download_blocks_chunck(
             &config_cloned,
             &log_sender_cloned,
             &ui_sender,
-            (block_headers, blocks, headers),
+            (block_headers, downloaded_blocks, headers),
             node,
             (tx, tx_utxo_set),
             nodes,
@@ -132,7 +140,7 @@ fn download_blocks_chunck(
 }
 
 /// Downloads all the blocks from the same node, in the same thread.
-/// The blocks are stored in the blocks list received by parameter.
+/// The blocks are sent to the thread that loads the utxo set and their amount is added to the downloaded blocks.
 /// In the end, the node is also return to the list of nodes
 /// ## Errors
 /// In case of Read or Write error on the node, the function is terminated, discarding the problematic node.
@@ -142,12 +150,12 @@ fn download_blocks_single_thread(
     config: &Arc<Config>,
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
-    (block_headers, blocks, headers): BlocksTuple,
+    (block_headers, downloaded_blocks, headers): BlocksTuple,
     mut node: TcpStream,
     (tx, tx_utxo_set): (Sender<Vec<BlockHeader>>, Sender<Vec<Block>>),
     nodes: Arc<RwLock<Vec<TcpStream>>>,
 ) -> Result<(), NodeCustomErrors> {
-    let mut current_blocks: HashMap<[u8; 32], Block> = HashMap::new();
+    let mut amount_of_current_blocks = 0;
     // The thread should receive 250 headers
     write_in_log(
         &log_sender.info_log_sender,
@@ -155,6 +163,7 @@ fn download_blocks_single_thread(
     );
     for blocks_to_download in block_headers.chunks(config.blocks_download_per_node) {
         match request_blocks_from_node(
+            config,
             log_sender,
             &mut node,
             blocks_to_download,
@@ -176,20 +185,18 @@ fn download_blocks_single_thread(
             Err(NodeCustomErrors::ReadNodeError(_)) => return Ok(()),
             Err(error) => return Err(error),
         };
+        amount_of_current_blocks += received_blocks.len();
         tx_utxo_set
-            .send(received_blocks.clone())
+            .send(received_blocks)
             .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
-        for block in received_blocks.into_iter() {
-            current_blocks.insert(block.hash(), block);
-        }
     }
-    add_blocks_downloaded_to_local_blocks(
+    add_to_downloaded_blocks(
         config,
         log_sender,
         ui_sender,
         headers,
-        blocks,
-        current_blocks,
+        downloaded_blocks,
+        amount_of_current_blocks,
     )?;
     return_node_to_vec(nodes, node)?;
     Ok(())
@@ -200,6 +207,7 @@ fn download_blocks_single_thread(
 /// In case of error while sending the message, it returns the block headers back to the channel so
 /// they can be downloaded from another node. If this cannot be done, returns an error.
 fn request_blocks_from_node(
+    config: &Arc<Config>,
     log_sender: &LogSender,
     node: &mut TcpStream,
     blocks_chunk_to_download: &[BlockHeader],
@@ -209,9 +217,9 @@ fn request_blocks_from_node(
     //  Chunks of 16 blocks
     let mut inventory = vec![];
     for block in blocks_chunk_to_download {
-        inventory.push(Inventory::new_block(block.hash()));
+        inventory.push(Inventory::new_witness_block(block.hash()));
     }
-    match GetDataMessage::new(inventory).write_to(node) {
+    match GetDataMessage::new(config.chain_params.magic, inventory).write_to(node) {
         Ok(_) => Ok(()),
         Err(err) => {
             write_in_log(&log_sender.error_log_sender,format!("Error: {:?} amount of blocks can't be requested from the node: {:?}. I'll ask another node", blocks_chunk_to_download.len(), node.peer_addr()).as_str());
@@ -264,18 +272,29 @@ fn receive_requested_blocks_from_node(
     Ok(current_blocks)
 }
 
-/// Download all the blocks from a single node
+/// Download all the blocks from a single node. The blocks that are in the block store are read from disk
+/// instead of downloaded.
 /// Returns error in case of failure
 pub fn download_blocks_single_node(
     config: &Arc<Config>,
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
-    (blocks, headers): BlocksAndHeaders,
+    (downloaded_blocks, headers): BlocksAndHeaders,
     block_headers: Vec<BlockHeader>,
     node: &mut TcpStream,
-    tx_utxo_set: Sender<Vec<Block>>,
+    (tx_utxo_set, block_store): (Sender<Vec<Block>>, Arc<RwLock<BlockStore>>),
 ) -> Result<(), NodeCustomErrors> {
-    let mut current_blocks: HashMap<[u8; 32], Block> = HashMap::new();
+    let (block_headers, _) = load_stored_blocks(
+        config,
+        log_sender,
+        &block_store,
+        block_headers,
+        &tx_utxo_set,
+    )?;
+    if block_headers.is_empty() {
+        return Ok(());
+    }
+    let mut amount_of_current_blocks = 0;
     write_in_log(
         &log_sender.info_log_sender,
         format!(
@@ -288,6 +307,7 @@ pub fn download_blocks_single_node(
 
     for blocks_to_download in block_headers.chunks(config.blocks_download_per_node) {
         request_blocks_from_node(
+            config,
             log_sender,
             node,
             blocks_to_download,
@@ -301,20 +321,18 @@ pub fn download_blocks_single_node(
             block_headers.clone(),
             None,
         )?;
+        amount_of_current_blocks += received_blocks.len();
         tx_utxo_set
-            .send(received_blocks.clone())
+            .send(received_blocks)
             .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
-        for block in received_blocks.into_iter() {
-            current_blocks.insert(block.hash(), block);
-        }
     }
-    add_blocks_downloaded_to_local_blocks(
+    add_to_downloaded_blocks(
         config,
         log_sender,
         ui_sender,
         headers,
-        blocks,
-        current_blocks,
+        downloaded_blocks,
+        amount_of_current_blocks,
     )?;
     Ok(())
 }
@@ -325,6 +343,75 @@ pub fn download_blocks_single_node(
 ***************************************************************************
 */
 
+/// Reads from the block store the blocks of the received headers that were stored in a previous execution and sends
+/// them, in the order of the headers, to the thread that loads the utxo set, so that they are not downloaded again.
+/// The pruned blocks are already in the utxo set, so they are skipped. The blocks that can not be read from disk
+/// are downloaded again.
+/// Returns the headers of the blocks that have to be downloaded and the amount of blocks read from disk or pruned.
+fn load_stored_blocks(
+    config: &Arc<Config>,
+    log_sender: &LogSender,
+    block_store: &Arc<RwLock<BlockStore>>,
+    block_headers: Vec<BlockHeader>,
+    tx_utxo_set: &Sender<Vec<Block>>,
+) -> Result<(Vec<BlockHeader>, usize), NodeCustomErrors> {
+    let mut headers_to_download: Vec<BlockHeader> = Vec::new();
+    let mut stored_blocks: Vec<Block> = Vec::new();
+    let mut amount_of_stored_blocks = 0;
+    let mut amount_of_pruned_blocks = 0;
+    let block_store = block_store
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    for header in block_headers {
+        if block_store.is_pruned(&header.hash()) {
+            amount_of_pruned_blocks += 1;
+            continue;
+        }
+        match block_store.read_block(&header.hash()) {
+            Ok(Some(block)) => stored_blocks.push(block),
+            Ok(None) => headers_to_download.push(header),
+            Err(err) => {
+                write_in_log(
+                    &log_sender.error_log_sender,
+                    format!(
+                        "Error reading the block {:?} from disk: {}. It will be downloaded again",
+                        header.hash(),
+                        err
+                    )
+                    .as_str(),
+                );
+                headers_to_download.push(header);
+            }
+        }
+        if stored_blocks.len() == config.blocks_download_per_node {
+            amount_of_stored_blocks += stored_blocks.len();
+            tx_utxo_set
+                .send(std::mem::take(&mut stored_blocks))
+                .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
+        }
+    }
+    if !stored_blocks.is_empty() {
+        amount_of_stored_blocks += stored_blocks.len();
+        tx_utxo_set
+            .send(stored_blocks)
+            .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
+    }
+    if amount_of_stored_blocks > 0 {
+        write_in_log(
+            &log_sender.info_log_sender,
+            format!(
+                "{} blocks were read from disk, they are not downloaded again",
+                amount_of_stored_blocks
+            )
+            .as_str(),
+        );
+    }
+    Ok((
+        headers_to_download,
+        amount_of_stored_blocks + amount_of_pruned_blocks,
+    ))
+}
+
 /// Receives a vec of block headers and returns a vec of vecs of block headers, where each vec has the same amount of elements.
 /// Separates them into chunks of equal size.
 fn divide_blocks_to_download_in_equal_chunks(
@@ -342,37 +429,37 @@ fn divide_blocks_to_download_in_equal_chunks(
     blocks_to_download_chunks
 }
 
-/// Receives a hashmap of blocks and returns the amount of blocks in it
+/// Receives a pointer to the amount of downloaded blocks and returns it
 /// Error in case of not being able to read it
-pub fn amount_of_blocks(
-    blocks: &Arc<RwLock<HashMap<[u8; 32], Block>>>,
-) -> Result<usize, NodeCustomErrors> {
-    let amount_of_blocks = blocks
+pub fn amount_of_blocks(downloaded_blocks: &Arc<RwLock<usize>>) -> Result<usize, NodeCustomErrors> {
+    let amount_of_blocks = *downloaded_blocks
         .read()
-        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-        .len();
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
     Ok(amount_of_blocks)
 }
 
-/// Receives a pointer to a hashmap of blocks and a hashmap of downloaded blocks and adds them to the local hashmap of blocks
-/// in case of not being able to access the local hashmap of blocks returns error
-pub fn add_blocks_downloaded_to_local_blocks(
+/// Receives a pointer to the amount of downloaded blocks and adds to it the amount of blocks downloaded by a thread,
+/// which were already sent to be written in the block store, so they are not kept in memory.
+/// In case of not being able to access the amount of downloaded blocks returns error
+pub fn add_to_downloaded_blocks(
     config: &Arc<Config>,
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
     headers: Arc<RwLock<Vec<BlockHeader>>>,
-    blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
-    downloaded_blocks: HashMap<[u8; 32], Block>,
+    downloaded_blocks: Arc<RwLock<usize>>,
+    amount_of_current_blocks: usize,
 ) -> Result<(), NodeCustomErrors> {
-    blocks
-        .write()
-        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-        .extend(downloaded_blocks);
+    let amount_of_blocks = {
+        let mut downloaded_blocks = downloaded_blocks
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        *downloaded_blocks += amount_of_current_blocks;
+        *downloaded_blocks
+    };
     write_in_log(
         &log_sender.info_log_sender,
-        format!("DOWNLOADING BLOCKS: {:?} blocks downloaded", amount_of_blocks(&blocks)?).as_str(),
+        format!("DOWNLOADING BLOCKS: {:?} blocks downloaded", amount_of_blocks).as_str(),
     );
-    let amount_of_blocks = amount_of_blocks(&blocks)?;
     println!("{:?} blocks downloaded", amount_of_blocks);
     let total_blocks_to_download =
         amount_of_headers(&headers)? - config.height_first_block_to_download;
@@ -1,9 +1,6 @@
 use std::{
     collections::HashMap,
-    fs::File,
-    io::Read,
     net::TcpStream,
-    path::Path,
     sync::{mpsc::Sender, Arc, RwLock},
 };
 
@@ -11,7 +8,12 @@ use chrono::{TimeZone, Utc};
 use gtk::glib;
 
 use crate::{
-    blocks::block_header::BlockHeader,
+    blocks::{
+        block_header::BlockHeader,
+        header_store::HeaderStore,
+        header_validation::{validate_header_context, HeaderChain},
+    },
+    chain_params::ChainParams,
     config::Config,
     custom_errors::NodeCustomErrors,
     gtk::ui_events::{send_event_to_ui, UIEvent},
@@ -21,26 +23,19 @@ use crate::{
 
 use super::{
     utils::{get_node, return_node_to_vec},
-    GENESIS_BLOCK_HEADER,
+    HeaderHeightsAndStore, HeaderHeightsPointer, HeaderStorePointer,
 };
 
-const HEADERS_MESSAGE_SIZE: usize = 162003;
-
-const GENESIS_BLOCK_HASH: [u8; 32] = [
-    0x00, 0x00, 0x00, 0x00, 0x09, 0x33, 0xea, 0x01, 0xad, 0x0e, 0xe9, 0x84, 0x20, 0x97, 0x79, 0xba,
-    0xae, 0xc3, 0xce, 0xd9, 0x0f, 0xa3, 0xf4, 0x08, 0x71, 0x95, 0x26, 0xf8, 0xd7, 0x7f, 0x49, 0x43,
-];
-
 /*
 ***************************************************************************
 ***************** INITIAL HEADERS AND PERSISTANCE *************************
 ***************************************************************************
 */
 
-/// Downloads the first headers of the blockchain and saves them to disk.
-/// If they are already saved, it reads them from there, otherwise
-/// reads and saves them. If it is configured to read from disk, it will read from there.
-/// If it is configured to read from the network, it will read from there and save to disk.
+/// Opens the header store of the configured file and returns it. If it is configured to read from disk,
+/// the stored headers are loaded in the headers vector, otherwise the store is emptied. Then, if there are
+/// less headers than the configured amount of headers in disk and there are nodes connected, it downloads
+/// them from the network and saves them in the store. Returns an error if the store can not be opened or the headers can not be downloaded.
 pub fn get_initial_headers(
     config: &Arc<Config>,
     log_sender: &LogSender,
@@ -48,83 +43,61 @@ pub fn get_initial_headers(
     headers: Arc<RwLock<Vec<BlockHeader>>>,
     header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
     nodes: Arc<RwLock<Vec<TcpStream>>>,
-) -> Result<(), NodeCustomErrors> {
-    if config.read_headers_from_disk && Path::new(&config.headers_file).exists() {
-        if let Err(err) = read_headers_from_disk(
-            config,
+) -> Result<HeaderStorePointer, NodeCustomErrors> {
+    let (mut header_store, stored_headers) =
+        HeaderStore::open(&config.headers_file, config.chain_params.genesis_hash())?;
+    if config.read_headers_from_disk {
+        read_headers_from_disk(
             log_sender,
             ui_sender,
             headers.clone(),
             header_heights.clone(),
-        ) {
-            // si no se pudo descargar de disco, intento desde la red y guardo en disco
-            // If it cannot be downloaded from disk, it tries from the network and saves to disk
-            write_in_log(
-                &log_sender.error_log_sender,
-                format!("Error trying to read headers from disk: {}", err).as_str(),
-            );
-        } else {
-            return Ok(());
-        }
+            stored_headers,
+        )?;
+    } else {
+        header_store.truncate(0)?;
     }
-    download_and_persist_headers(
-        config,
-        log_sender,
-        ui_sender,
-        headers,
-        header_heights,
-        nodes,
-    )?;
-    Ok(())
+    let header_store = Arc::new(RwLock::new(header_store));
+    let there_are_nodes = !nodes
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .is_empty();
+    if there_are_nodes && amount_of_headers(&headers)? < config.headers_in_disk {
+        download_and_persist_headers(
+            config,
+            log_sender,
+            ui_sender,
+            headers,
+            (header_heights, header_store.clone()),
+            nodes,
+        )?;
+    }
+    Ok(header_store)
 }
 
 /// Lee los headers de disco y los guarda en el vector de headers.
-/// Devuelve un error en caso de no poder leer el archivo correctamente.
-/// Reads the headers from disk and saves them to the headers vector.
-/// Returns an error if you cannot read the file correctly or Ok(()) otherwise.
+/// Reads the headers stored in disk and saves them to the headers vector, loading their heights.
+/// Returns an error if the headers or the heights can not be written.
 fn read_headers_from_disk(
-    config: &Arc<Config>,
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
     headers: Arc<RwLock<Vec<BlockHeader>>>,
     header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
+    stored_headers: Vec<BlockHeader>,
 ) -> Result<(), NodeCustomErrors> {
     write_in_log(
         &log_sender.info_log_sender,
-        format!(
-            "Start reading first {} headers from disk",
-            config.headers_in_disk
-        )
-        .as_str(),
+        "Start reading headers from disk",
     );
     send_event_to_ui(ui_sender, UIEvent::StartDownloadingHeaders);
-    let mut data: Vec<u8> = Vec::new();
-    let mut file = File::open(&config.headers_file)
-        .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
-    file.read_to_end(&mut data)
-        .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
-    let mut amount = 0;
-    let mut i = 0;
-    while i < data.len() {
-        amount += 2000;
-        let mut message_bytes = Vec::new();
-        message_bytes.extend_from_slice(&data[i..i + HEADERS_MESSAGE_SIZE]);
-        let unmarshalled_headers = HeadersMessage::unmarshalling(&message_bytes)
-            .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
-
-        load_header_heights(&unmarshalled_headers, &header_heights, &headers)?;
-
-        headers
-            .write()
-            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-            .extend_from_slice(&unmarshalled_headers);
-        println!("{:?} headers read", amount);
-        send_event_to_ui(
-            ui_sender,
-            UIEvent::UpdateHeadersDownloaded(amount as usize),
-        );
-        i += HEADERS_MESSAGE_SIZE;
-    }
+    load_header_heights(&stored_headers, &header_heights, &headers)?;
+    headers
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .extend_from_slice(&stored_headers);
+    let amount = stored_headers.len();
+    println!("{:?} headers read", amount);
+    send_event_to_ui(ui_sender, UIEvent::UpdateHeadersDownloaded(amount));
     write_in_log(
         &log_sender.info_log_sender,
         format!("{:?} headers read correctly from disk", amount).as_str(),
@@ -154,10 +127,10 @@ pub fn load_header_heights(
     Ok(())
 }
 
-/// Descarga los primeros headers de la blockchain, crea el archivo para guardarlos y los guarda en disco
+/// Descarga los primeros headers de la blockchain y los guarda en disco
 /// En caso de que un nodo falle en la descarga, intenta con otro siempre y cuando tenga peers disponibles
 /// Devuelve un error en caso de no poder descargar los headers desde nignun nodo peer
-/// Downloads the first headers of the blockchain, creates the file to save them and saves them to disk.
+/// Downloads the first headers of the blockchain and saves them to disk.
 /// In case a node fails in the download, it tries with another as long as it has peers available. Returns
 /// Ok(()) if it is downloaded correctly or an error otherwise.
 fn download_and_persist_headers(
@@ -165,7 +138,7 @@ fn download_and_persist_headers(
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
     headers: Arc<RwLock<Vec<BlockHeader>>>,
-    header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
+    header_heights_and_store: HeaderHeightsAndStore,
     nodes: Arc<RwLock<Vec<TcpStream>>>,
 ) -> Result<(), NodeCustomErrors> {
     write_in_log(
@@ -177,8 +150,6 @@ fn download_and_persist_headers(
         .as_str(),
     );
     send_event_to_ui(ui_sender, UIEvent::StartDownloadingHeaders);
-    let mut file = File::create(&config.headers_file)
-        .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
     // get last node from list, if possible
     let mut node = get_node(nodes.clone())?;
     while let Err(err) = download_and_persist_initial_headers_from_node(
@@ -187,8 +158,7 @@ fn download_and_persist_headers(
         ui_sender,
         &mut node,
         headers.clone(),
-        header_heights.clone(),
-        &mut file,
+        &header_heights_and_store,
     ) {
         write_in_log(
             &log_sender.error_log_sender,
@@ -215,8 +185,7 @@ fn download_and_persist_initial_headers_from_node(
     ui_sender: &Option<glib::Sender<UIEvent>>,
     node: &mut TcpStream,
     headers: Arc<RwLock<Vec<BlockHeader>>>,
-    header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
-    file: &mut File,
+    (header_heights, header_store): &HeaderHeightsAndStore,
 ) -> Result<(), NodeCustomErrors> {
     write_in_log(
         &log_sender.info_log_sender,
@@ -233,9 +202,14 @@ fn download_and_persist_initial_headers_from_node(
         < config.headers_in_disk
     {
         request_headers_from_node(config, node, headers.clone())?;
-        let headers_read = receive_and_persist_initial_headers_from_node(log_sender, node, file)?;
-        load_header_heights(&headers_read, &header_heights, &headers)?;
-        store_headers_in_local_headers_vec(log_sender, headers.clone(), &headers_read)?;
+        let headers_read = receive_headers_from_node(log_sender, node)?;
+        store_headers_in_local_headers_vec(
+            config,
+            log_sender,
+            headers.clone(),
+            (header_heights, header_store),
+            &headers_read,
+        )?;
         let amount_of_headers = amount_of_headers(&headers)?;
         println!(
             "{:?} headers downloaded and saved in disk",
@@ -249,31 +223,14 @@ fn download_and_persist_initial_headers_from_node(
     Ok(())
 }
 
-/// Receives the headers from the node and saves them to disk.
-/// Returns an error if you cannot receive them correctly or Ok(()) otherwise.
-fn receive_and_persist_initial_headers_from_node(
-    log_sender: &LogSender,
-    node: &mut TcpStream,
-    file: &mut File,
-) -> Result<Vec<BlockHeader>, NodeCustomErrors> {
-    let headers: Vec<BlockHeader> = HeadersMessage::read_from_node_and_write_to_file(
-        log_sender, node, None, file,
-    )
-    .map_err(|_| {
-        NodeCustomErrors::BlockchainDownloadError(
-            "Error trying to read and save headers in disk".to_string(),
-        )
-    })?;
-    Ok(headers)
-}
-
 /*
 ***************************************************************************
 ******************* DOWNLOAD HEADERS FROM NETWORK *************************
 ***************************************************************************
 */
 
-/// Downloads the headers of the blockchain from the connected nodes.
+/// Downloads the headers of the blockchain from the connected nodes. The blocks of the headers already stored
+/// that meet the date of the first block to download are sent to download first.
 /// In case a node fails in the download, it tries with another as long as it has peers available. Returns
 /// Ok(()) if it is downloaded correctly or an error otherwise.
 pub fn download_missing_headers(
@@ -282,9 +239,10 @@ pub fn download_missing_headers(
     ui_sender: &Option<glib::Sender<UIEvent>>,
     nodes: Arc<RwLock<Vec<TcpStream>>>,
     headers: Arc<RwLock<Vec<BlockHeader>>>,
-    header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
+    header_heights_and_store: HeaderHeightsAndStore,
     tx: Sender<Vec<BlockHeader>>,
 ) -> Result<(), NodeCustomErrors> {
+    download_blocks_of_stored_headers(config, log_sender, ui_sender, &headers, tx.clone())?;
     // get last node from list, if possible
     let mut node = get_node(nodes.clone())?;
     while let Err(err) = download_missing_headers_from_node(
@@ -293,7 +251,7 @@ pub fn download_missing_headers(
         ui_sender,
         &mut node,
         headers.clone(),
-        header_heights.clone(),
+        &header_heights_and_store,
         tx.clone(),
     ) {
         write_in_log(
@@ -319,9 +277,46 @@ pub fn download_missing_headers(
     Ok(())
 }
 
+/// Sends the headers already stored that meet the date of the first block to download through the channel, in
+/// groups of 2000, so that their blocks are downloaded while the missing headers are downloaded.
+/// Returns an error if the channel is closed, otherwise Ok(()).
+fn download_blocks_of_stored_headers(
+    config: &Arc<Config>,
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    headers: &Arc<RwLock<Vec<BlockHeader>>>,
+    tx: Sender<Vec<BlockHeader>>,
+) -> Result<(), NodeCustomErrors> {
+    // The genesis block is not downloaded
+    let stored_headers = headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[1..]
+        .to_vec();
+    let mut first_block_found = false;
+    let headers_to_download =
+        search_first_header_block_to_download(config, stored_headers, &mut first_block_found)?;
+    if headers_to_download.is_empty() {
+        return Ok(());
+    }
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!(
+            "First block to download found in the {} headers to download stored in disk! Start blocks download\n",
+            headers_to_download.len()
+        )
+        .as_str(),
+    );
+    send_event_to_ui(ui_sender, UIEvent::StartDownloadingBlocks);
+    for headers_chunk in headers_to_download.chunks(2000) {
+        download_blocks_in_other_thread(tx.clone(), headers_chunk.to_vec())?;
+    }
+    Ok(())
+}
+
 /// Downloads the headers from a particular node and saves them to the headers vector.
 /// If the tx parameter is a Sender, it sends the headers it is downloading to the thread
 /// that downloads blocks to be downloaded in parallel, otherwise it does not send anything.
+/// The first block to download was already found if the last header of the vector meets its date.
 /// Devuelve error en caso de falla.
 fn download_missing_headers_from_node(
     config: &Arc<Config>,
@@ -329,7 +324,7 @@ fn download_missing_headers_from_node(
     ui_sender: &Option<glib::Sender<UIEvent>>,
     node: &mut TcpStream,
     headers: Arc<RwLock<Vec<BlockHeader>>>,
-    header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
+    (header_heights, header_store): &HeaderHeightsAndStore,
     tx: Sender<Vec<BlockHeader>>,
 ) -> Result<(), NodeCustomErrors> {
     write_in_log(
@@ -340,18 +335,25 @@ fn download_missing_headers_from_node(
         )
         .as_str(),
     );
-    let mut first_block_found = false;
-    request_headers_from_node(config, node, headers.clone())?;
-    let mut headers_read = receive_headers_from_node(log_sender, node)?;
-
-    load_header_heights(&headers_read, &header_heights, &headers)?;
-
-    store_headers_in_local_headers_vec(log_sender, headers.clone(), &headers_read)?;
-    while headers_read.len() == 2000 {
+    let mut first_block_found = first_block_to_download_is_in_headers(
+        config,
+        &headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?,
+    )?;
+    loop {
         request_headers_from_node(config, node, headers.clone())?;
-        headers_read = receive_headers_from_node(log_sender, node)?;
-        load_header_heights(&headers_read, &header_heights, &headers)?;
-        store_headers_in_local_headers_vec(log_sender, headers.clone(), &headers_read)?;
+        let headers_read = receive_headers_from_node(log_sender, node)?;
+        store_headers_in_local_headers_vec(
+            config,
+            log_sender,
+            headers.clone(),
+            (header_heights, header_store),
+            &headers_read,
+        )?;
+        if headers_read.is_empty() {
+            break;
+        }
         match first_block_found {
             true => {
                 // If the first block has already been found, I send all the headers to the thread that downloads the blocks
@@ -378,6 +380,9 @@ fn download_missing_headers_from_node(
             ui_sender,
             UIEvent::UpdateHeadersDownloaded(amount_of_headers - 1),
         );
+        if headers_read.len() < 2000 {
+            break;
+        }
     }
     Ok(())
 }
@@ -415,14 +420,28 @@ pub fn receive_headers_from_node(
     Ok(headers)
 }
 
-/// Receives a vector of headers, validates them and saves them in the local headers vector.
-/// If they are not valid, it does not save them and returns an error.
+/// Receives a vector of headers, validates them against the local headers vector and saves them in it and in
+/// the header store, loading their heights. If they are not valid, it does not save them and returns an error.
 fn store_headers_in_local_headers_vec(
+    config: &Arc<Config>,
     log_sender: &LogSender,
     headers: Arc<RwLock<Vec<BlockHeader>>>,
+    (header_heights, header_store): (&HeaderHeightsPointer, &HeaderStorePointer),
     headers_read: &Vec<BlockHeader>,
 ) -> Result<(), NodeCustomErrors> {
-    validate_headers(log_sender, headers_read)?;
+    validate_headers(
+        log_sender,
+        config.chain_params,
+        &headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?,
+        headers_read,
+    )?;
+    header_store
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .append(headers_read)?;
+    load_header_heights(headers_read, header_heights, &headers)?;
     headers
         .write()
         .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
@@ -492,25 +511,24 @@ fn get_last_hash_header_downloaded(
         .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
     let last_header = binding.last();
     match last_header {
-        Some(header) => {
-            if *header == GENESIS_BLOCK_HEADER {
-                return Ok(GENESIS_BLOCK_HASH);
-            }
-            Ok(header.hash())
-        }
+        Some(header) => Ok(header.hash()),
         None => Err(NodeCustomErrors::BlockchainDownloadError(
             "Error, there are not headers downloaded!\n".to_string(),
         )),
     }
 }
 
-/// Validates that the header has the correct proof of work.
-/// Returns an error if it is not valid or Ok(()) otherwise.
+/// Validates that the headers have the correct proof of work and that each one is valid as the next header
+/// of the chain formed by the local headers and the previous received headers (link to the parent,
+/// difficulty adjustment and timestamps) according to the chain params. Returns an error if any of them is not valid or Ok(()) otherwise.
 fn validate_headers(
     log_sender: &LogSender,
-    headers: &Vec<BlockHeader>,
+    params: &ChainParams,
+    local_headers: &[BlockHeader],
+    headers: &[BlockHeader],
 ) -> Result<(), NodeCustomErrors> {
-    for header in headers {
+    let current_time = Utc::now().timestamp();
+    for (index, header) in headers.iter().enumerate() {
         if !header.validate() {
             write_in_log(
                 &log_sender.error_log_sender,
@@ -520,6 +538,14 @@ fn validate_headers(
                 "partial validation of header is invalid!".to_string(),
             ));
         }
+        let chain = HeaderChain::new(local_headers, &headers[..index]);
+        if let Err(err) = validate_header_context(header, &chain, params, current_time) {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!("Error in the validation of the header: {}\n", err).as_str(),
+            );
+            return Err(NodeCustomErrors::InvalidHeaderError(err.to_string()));
+        }
     }
     Ok(())
 }
@@ -3,11 +3,17 @@ use gtk::glib;
 use self::blocks_download::{download_blocks, download_blocks_single_node};
 use self::headers_download::{download_missing_headers, get_initial_headers};
 use self::utils::{get_amount_of_headers_and_blocks, get_node, join_threads, return_node_to_vec};
+use super::address_index::AddressIndex;
 use super::blocks::block::Block;
-use super::blocks::block_header::BlockHeader;
+use super::blocks::block_store::BlockStore;
+use super::blocks::header_store::HeaderStore;
+use super::blocks::header_tree::HeaderTree;
+use super::chainstate::ChainState;
 use super::config::Config;
+use super::filter_index::FilterIndex;
 use super::logwriter::log_writer::{write_in_log, LogSender};
-use crate::blockchain::Blockchain;
+use super::tx_index::TxIndex;
+use crate::blockchain::{BlockPointers, Blockchain, HeaderPointers, IndexPointers};
 use crate::custom_errors::NodeCustomErrors;
 use crate::gtk::ui_events::{send_event_to_ui, UIEvent};
 use crate::utxo_tuple::UtxoTuple;
@@ -21,23 +27,9 @@ pub(crate) mod headers_download;
 mod utils;
 
 type UtxoSetPointer = Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>;
-type BlocksAndHeaders = (
-    Arc<RwLock<HashMap<[u8; 32], Block>>>,
-    Arc<RwLock<Vec<BlockHeader>>>,
-);
-// Gensis block header hardcoded to start the download (this is the first block of the blockchain)
-// data taken from: https://en.bitcoin.it/wiki/Genesis_block
-const GENESIS_BLOCK_HEADER: BlockHeader = BlockHeader {
-    version: 1,
-    previous_block_header_hash: [0; 32],
-    merkle_root_hash: [
-        59, 163, 237, 253, 122, 123, 18, 178, 122, 199, 44, 62, 103, 118, 143, 97, 127, 200, 27,
-        195, 136, 138, 81, 50, 58, 159, 184, 170, 75, 30, 94, 74,
-    ],
-    time: 1296677802,
-    n_bits: 486604799,
-    nonce: 414098458,
-};
+type HeaderHeightsPointer = Arc<RwLock<HashMap<[u8; 32], usize>>>;
+type HeaderStorePointer = Arc<RwLock<HeaderStore>>;
+type HeaderHeightsAndStore = (HeaderHeightsPointer, HeaderStorePointer);
 
 /// Recieves a list of TcpStreams that are the connection with nodes already established and downloads
 /// all the headers from the blockchain and the blocks from a config date. Returns the headers and blocks in
@@ -53,17 +45,66 @@ pub fn initial_block_download(
         "STARTS INITIAL BLOCK DOWNLOAD\n",
     );
     // The list of headers starts with the genesis block header
-    let headers = vec![GENESIS_BLOCK_HEADER];
+    let headers = vec![config.chain_params.genesis_header];
     let pointer_to_headers = Arc::new(RwLock::new(headers));
     let blocks: HashMap<[u8; 32], Block> = HashMap::new();
     let pointer_to_blocks = Arc::new(RwLock::new(blocks));
-    let utxo_set: UtxoSetPointer = Arc::new(RwLock::new(HashMap::new()));
+    // the downloaded blocks are written in the block store, so only their amount is kept
+    let downloaded_blocks = Arc::new(RwLock::new(0));
     let mut heights_hashmap: HashMap<[u8; 32], usize> = HashMap::new();
     heights_hashmap.insert([0u8; 32], 0); // genesis hash
+    heights_hashmap.insert(config.chain_params.genesis_hash(), 0);
     let header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>> =
         Arc::new(RwLock::new(heights_hashmap));
+    let mut block_store = BlockStore::open(&config.blocks_folder_path, config.chain_params.magic)?;
+    block_store.set_prune_target(config.prune_target_mb * 1024 * 1024);
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!(
+            "{} blocks stored in {}, pruned up to height {}",
+            block_store.len(),
+            config.blocks_folder_path,
+            block_store.pruned_height()
+        )
+        .as_str(),
+    );
+    let mut address_index = match config.address_index {
+        true => Some(AddressIndex::open(&config.indexes_folder_path)?),
+        false => None,
+    };
+    let mut filter_index = match config.block_filter_index {
+        true => Some(FilterIndex::open(&config.indexes_folder_path)?),
+        false => None,
+    };
+    let (chainstate, utxo_set) = open_chainstate(
+        config,
+        log_sender,
+        (address_index.as_mut(), filter_index.as_mut()),
+    )?;
+    // the pruned blocks have to be downloaded again to build the utxo set
+    if chainstate.is_empty() {
+        block_store.forget_pruned_blocks();
+    }
+    let block_store = Arc::new(RwLock::new(block_store));
+    let utxo_set: UtxoSetPointer = Arc::new(RwLock::new(utxo_set));
+    let chainstate = Arc::new(RwLock::new(chainstate));
+    let tx_index = match config.tx_index {
+        true => Some(Arc::new(RwLock::new(TxIndex::open(
+            &config.indexes_folder_path,
+        )?))),
+        false => None,
+    };
+    let indexes = IndexPointers {
+        tx_index,
+        address_index: address_index.map(|address_index| Arc::new(RwLock::new(address_index))),
+        filter_index: filter_index.map(|filter_index| Arc::new(RwLock::new(filter_index))),
+    };
 
-    get_initial_headers(
+    let amount_of_nodes = nodes
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(format!("{:?}", err)))?
+        .len();
+    let header_store = get_initial_headers(
         config,
         log_sender,
         ui_sender,
@@ -71,20 +112,38 @@ pub fn initial_block_download(
         header_heights.clone(),
         nodes.clone(),
     )?;
-    let amount_of_nodes = nodes
-        .read()
-        .map_err(|err| NodeCustomErrors::LockError(format!("{:?}", err)))?
-        .len();
 
-    if config.ibd_single_node || amount_of_nodes < 2 {
+    let blockchain = Blockchain::new(
+        config.chain_params,
+        HeaderPointers {
+            headers: pointer_to_headers,
+            header_heights,
+            header_store,
+            header_tree: Arc::new(RwLock::new(HeaderTree::new())),
+        },
+        BlockPointers {
+            blocks: pointer_to_blocks,
+            block_store,
+            utxo_set,
+            chainstate,
+        },
+        indexes,
+    );
+
+    if amount_of_nodes == 0 {
+        // The node starts without peers (the first node of a regtest network), so the blockchain is the one
+        // stored in disk and it grows with the blocks received or mined afterwards
+        write_in_log(
+            &log_sender.info_log_sender,
+            "There are no nodes to download the blockchain from, the blockchain stored is used",
+        );
+    } else if config.ibd_single_node || amount_of_nodes < 2 {
         download_full_blockchain_from_single_node(
             config,
             log_sender,
             ui_sender,
             nodes,
-            (pointer_to_blocks.clone(), pointer_to_headers.clone()),
-            header_heights.clone(),
-            utxo_set.clone(),
+            (&blockchain, downloaded_blocks.clone()),
         )?;
     } else {
         download_full_blockchain_from_multiple_nodes(
@@ -92,14 +151,12 @@ pub fn initial_block_download(
             log_sender,
             ui_sender,
             nodes,
-            (pointer_to_blocks.clone(), pointer_to_headers.clone()),
-            header_heights.clone(),
-            utxo_set.clone(),
+            (&blockchain, downloaded_blocks.clone()),
         )?;
     }
 
     let (amount_of_headers, amount_of_blocks) =
-        get_amount_of_headers_and_blocks(&pointer_to_headers, &pointer_to_blocks)?;
+        get_amount_of_headers_and_blocks(&blockchain.headers, &downloaded_blocks)?;
     write_in_log(
         &log_sender.info_log_sender,
         format!("TOTAL HEADERS DOWNLOADED: {}", amount_of_headers).as_str(),
@@ -108,12 +165,25 @@ pub fn initial_block_download(
         &log_sender.info_log_sender,
         format!("TOTAL BLOCKS DOWNLOADED: {}\n", amount_of_blocks).as_str(),
     );
-    Ok(Blockchain::new(
-        pointer_to_headers,
-        pointer_to_blocks,
-        header_heights,
-        utxo_set,
-    ))
+    blockchain
+        .header_tree
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(format!("{:?}", err)))?
+        .sync_active_chain(
+            &blockchain
+                .headers
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(format!("{:?}", err)))?,
+        );
+    // the blocks connected before the transaction index was enabled are indexed from the block store
+    let indexed_blocks = blockchain.sync_tx_index()?;
+    if indexed_blocks > 0 {
+        write_in_log(
+            &log_sender.info_log_sender,
+            format!("{} blocks added to the transaction index", indexed_blocks).as_str(),
+        );
+    }
+    Ok(blockchain)
 }
 
 /// Downloads all the headers and blocks of the blockchain in multiple threads. In one thread it downloads the headers
@@ -124,10 +194,9 @@ fn download_full_blockchain_from_multiple_nodes(
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
     nodes: Arc<RwLock<Vec<TcpStream>>>,
-    (blocks, headers): BlocksAndHeaders,
-    header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
-    utxo_set: UtxoSetPointer,
+    (blockchain, downloaded_blocks): (&Blockchain, Arc<RwLock<usize>>),
 ) -> Result<(), NodeCustomErrors> {
+    let headers = blockchain.headers.clone();
     // channel to comunicate headers download thread with blocks download thread
     let (tx, rx) = channel();
     let mut threads_handle = vec![];
@@ -135,6 +204,8 @@ fn download_full_blockchain_from_multiple_nodes(
     let log_sender_cloned = log_sender.clone();
     let nodes_cloned = nodes.clone();
     let headers_cloned = headers.clone();
+    let header_heights_cloned = blockchain.header_heights.clone();
+    let header_store = blockchain.header_store.clone();
     let tx_cloned = tx.clone();
     let ui_sender_clone = ui_sender.clone();
     threads_handle.push(thread::spawn(move || {
@@ -144,7 +215,7 @@ fn download_full_blockchain_from_multiple_nodes(
             &ui_sender_clone,
             nodes_cloned,
             headers_cloned,
-            header_heights,
+            (header_heights_cloned, header_store),
             tx_cloned,
         )
     }));
@@ -152,19 +223,21 @@ fn download_full_blockchain_from_multiple_nodes(
     let log_sender = log_sender.clone();
     let ui_sender = ui_sender.clone();
     let (tx_utxo_set, rx_utxo_set) = channel();
-    let utxo_set_clone = utxo_set;
+    let config_clone = config.clone();
+    let blockchain_clone = blockchain.clone();
     let join_handle = thread::spawn(move || -> Result<(), NodeCustomErrors> {
-        load_utxo_set(rx_utxo_set, utxo_set_clone)
+        load_utxo_set(&config_clone, rx_utxo_set, &blockchain_clone)
     });
+    let block_store = blockchain.block_store.clone();
     threads_handle.push(thread::spawn(move || {
         download_blocks(
             &config,
             &log_sender,
             &ui_sender,
             nodes,
-            (blocks, headers),
+            (downloaded_blocks, headers),
             (tx, rx),
-            tx_utxo_set,
+            (tx_utxo_set, block_store),
         )
     }));
     join_threads(threads_handle)?;
@@ -181,25 +254,29 @@ fn download_full_blockchain_from_single_node(
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
     nodes: Arc<RwLock<Vec<TcpStream>>>,
-    (blocks, headers): BlocksAndHeaders,
-    header_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
-    utxo_set: UtxoSetPointer,
+    (blockchain, downloaded_blocks): (&Blockchain, Arc<RwLock<usize>>),
 ) -> Result<(), NodeCustomErrors> {
     let (tx, rx) = channel();
+    let headers = blockchain.headers.clone();
+    let block_store = blockchain.block_store.clone();
     download_missing_headers(
         config,
         log_sender,
         ui_sender,
         nodes.clone(),
         headers.clone(),
-        header_heights,
+        (
+            blockchain.header_heights.clone(),
+            blockchain.header_store.clone(),
+        ),
         tx,
     )?;
     let mut node = get_node(nodes.clone())?;
     let (tx_utxo_set, rx_utxo_set) = channel();
-    let utxo_set_clone = utxo_set;
+    let config_clone = config.clone();
+    let blockchain_clone = blockchain.clone();
     let join_handle = thread::spawn(move || -> Result<(), NodeCustomErrors> {
-        load_utxo_set(rx_utxo_set, utxo_set_clone)
+        load_utxo_set(&config_clone, rx_utxo_set, &blockchain_clone)
     });
     send_event_to_ui(ui_sender, UIEvent::StartDownloadingBlocks);
     for blocks_to_download in rx {
@@ -207,10 +284,10 @@ fn download_full_blockchain_from_single_node(
             config,
             log_sender,
             ui_sender,
-            (blocks.clone(), headers.clone()),
+            (downloaded_blocks.clone(), headers.clone()),
             blocks_to_download,
             &mut node,
-            tx_utxo_set.clone(),
+            (tx_utxo_set.clone(), block_store.clone()),
         )?;
     }
     return_node_to_vec(nodes, node)?;
@@ -221,19 +298,142 @@ fn download_full_blockchain_from_single_node(
     Ok(())
 }
 
-/// Updates the utxo_set as it receives the blocks through the channel.
+/// Opens the chainstate of the configured folder and returns it with the utxo set stored. If the stored utxo
+/// set is behind the first block to download, the blocks in between would not be connected, so it is removed
+/// and built again. It is also built again if the address or filter index is behind it, as they are built with
+/// the undo data of the blocks, so that the stored blocks are connected again and the indexes built again too.
+/// Returns an error if the chainstate can not be read.
+fn open_chainstate(
+    config: &Arc<Config>,
+    log_sender: &LogSender,
+    (address_index, filter_index): (Option<&mut AddressIndex>, Option<&mut FilterIndex>),
+) -> Result<(ChainState, HashMap<[u8; 32], UtxoTuple>), NodeCustomErrors> {
+    let (mut chainstate, mut utxo_set) = ChainState::open(&config.chainstate_folder_path)?;
+    if !chainstate.is_empty() && chainstate.height() + 1 < config.height_first_block_to_download {
+        write_in_log(
+            &log_sender.error_log_sender,
+            format!(
+                "The utxo set stored is at height {}, before the first block to download. It is built again",
+                chainstate.height()
+            )
+            .as_str(),
+        );
+        chainstate.reset()?;
+        utxo_set = HashMap::new();
+    }
+    let indexes_height = [
+        address_index.as_ref().map(|index| index.height()),
+        filter_index.as_ref().map(|index| index.height()),
+    ]
+    .into_iter()
+    .flatten()
+    .min();
+    if let Some(indexes_height) = indexes_height {
+        if indexes_height < chainstate.height() {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!(
+                    "The indexes are at height {}, behind the utxo set stored. They are built again",
+                    indexes_height
+                )
+                .as_str(),
+            );
+            chainstate.reset()?;
+            utxo_set = HashMap::new();
+        }
+    }
+    // the blocks of the indexes will be connected again
+    if chainstate.is_empty() {
+        if let Some(address_index) = address_index {
+            address_index.reset()?;
+        }
+        if let Some(filter_index) = filter_index {
+            filter_index.reset()?;
+        }
+    }
+    if chainstate.is_empty() {
+        return Ok((chainstate, utxo_set));
+    }
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!(
+            "Utxo set of {} transactions loaded from disk at height {}",
+            utxo_set.len(),
+            chainstate.height()
+        )
+        .as_str(),
+    );
+    Ok((chainstate, utxo_set))
+}
+
+/// Updates the utxo_set as it receives the blocks through the channel. The blocks can be received in any order,
+/// so they are connected to the blockchain in the order of their height, starting after the last block of the
+/// chainstate (the previous ones are already in the utxo set) or from the first block to download if it is empty.
+/// Each block is written in the block store with its undo data when it is connected, and only the most recent ones
+/// are kept in memory. The chainstate is flushed periodically and when all the blocks are connected, and then the
+/// blocks already flushed are pruned if pruning is enabled.
+/// Returns an error if a block is invalid or if some blocks could not be connected because a previous one was
+/// not received.
 fn load_utxo_set(
+    config: &Arc<Config>,
     rx: Receiver<Vec<Block>>,
-    utxo_set: UtxoSetPointer,
+    blockchain: &Blockchain,
 ) -> Result<(), NodeCustomErrors> {
+    let mut next_height = {
+        let chainstate = blockchain
+            .chainstate
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        match chainstate.is_empty() {
+            true => config.height_first_block_to_download.max(1),
+            false => chainstate.height() + 1,
+        }
+    };
+    let mut pending_blocks: HashMap<usize, Block> = HashMap::new();
     for blocks in rx {
         for block in blocks {
-            block
-                .give_me_utxos(utxo_set.clone())
-                .map_err(|err| NodeCustomErrors::UtxoError(err.to_string()))?;
+            let height = blockchain
+                .header_heights
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .get(&block.hash())
+                .copied()
+                .unwrap_or_default();
+            // a block can be received twice if the download of its chunk was retried
+            if height >= next_height {
+                pending_blocks.insert(height, block);
+            }
+        }
+        while let Some(block) = pending_blocks.remove(&next_height) {
+            blockchain
+                .connect_block(&block, next_height)
+                .map_err(|err| match err {
+                    NodeCustomErrors::InvalidBlockError(reason) => {
+                        NodeCustomErrors::InvalidBlockError(format!(
+                            "{}: {}",
+                            block.hex_hash(),
+                            reason
+                        ))
+                    }
+                    err => err,
+                })?;
+            let needs_flush = blockchain
+                .chainstate
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .needs_flush();
+            if needs_flush {
+                blockchain.flush_chainstate()?;
+            }
+            next_height += 1;
         }
     }
-    Ok(())
+    if !pending_blocks.is_empty() {
+        return Err(NodeCustomErrors::UtxoError(format!(
+            "{} blocks could not be connected because the block with height {} was not received",
+            pending_blocks.len(),
+            next_height
+        )));
+    }
+    blockchain.flush_chainstate()
 }
-
-
@@ -1,9 +1,5 @@
-use crate::{
-    blocks::{block::Block, block_header::BlockHeader},
-    custom_errors::NodeCustomErrors,
-};
+use crate::{blocks::block_header::BlockHeader, custom_errors::NodeCustomErrors};
 use std::{
-    collections::HashMap,
     net::TcpStream,
     sync::{Arc, RwLock},
     thread,
@@ -51,12 +47,12 @@ pub fn join_threads(
     Ok(())
 }
 
-/// Receives a pointer to a vector of headers and a pointer to a hashmap of blocks and returns the amount of headers and blocks in each one.
+/// Receives a pointer to a vector of headers and a pointer to the amount of downloaded blocks and returns the amount of headers and blocks.
 pub fn get_amount_of_headers_and_blocks(
     headers: &Arc<RwLock<Vec<BlockHeader>>>,
-    blocks: &Arc<RwLock<HashMap<[u8; 32], Block>>>,
+    downloaded_blocks: &Arc<RwLock<usize>>,
 ) -> Result<(usize, usize), NodeCustomErrors> {
     let amount_of_headers = amount_of_headers(headers)?;
-    let amount_of_blocks = amount_of_blocks(blocks)?;
+    let amount_of_blocks = amount_of_blocks(downloaded_blocks)?;
     Ok((amount_of_headers, amount_of_blocks))
 }
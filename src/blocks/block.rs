@@ -1,5 +1,6 @@
 use super::{
-    block_header::BlockHeader, merkle_tree::MerkleTree, utils_block::concatenate_and_hash,
+    block_header::BlockHeader, block_undo::BlockUndo, header_validation::HeaderChain,
+    merkle_tree::MerkleTree, utils_block::concatenate_and_hash,
 };
 use crate::{
     account::Account,
@@ -7,16 +8,29 @@ use crate::{
     custom_errors::NodeCustomErrors,
     gtk::ui_events::{send_event_to_ui, UIEvent},
     logwriter::log_writer::{write_in_log, LogSender},
-    transactions::transaction::Transaction,
+    transactions::{
+        script::p2tr_script::is_p2tr,
+        transaction::{Transaction, MAX_MONEY},
+        tx_out::TxOut,
+    },
     utxo_tuple::UtxoTuple,
 };
 use gtk::glib;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     error::Error,
     sync::{Arc, RwLock},
 };
 
+const MAX_BLOCK_WEIGHT: usize = 4_000_000;
+/// Amount of blocks that have to be mined on top of a coinbase before its outputs can be spent.
+const COINBASE_MATURITY: usize = 100;
+/// Subsidy of the first blocks in satoshis (50 BTC), which is halved every subsidy halving interval of the chain.
+const INITIAL_BLOCK_SUBSIDY: i64 = 50 * 100_000_000;
+const WITNESS_SCALE_FACTOR: usize = 4;
+/// OP_RETURN, push of 36 bytes and the 4 bytes that identify the witness commitment (BIP141)
+pub(crate) const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
 #[derive(Debug, Clone)]
 /// Represents a block of the bitcoin protocol.
 pub struct Block {
@@ -64,6 +78,16 @@ impl Block {
         }
     }
 
+    /// Converts the block to bytes without the witness data of its transactions.
+    /// It is the format used by the nodes that do not support segwit.
+    pub fn marshalling_without_witness(&self, bytes: &mut Vec<u8>) {
+        self.block_header.marshalling(bytes);
+        bytes.extend_from_slice(&self.txn_count.marshalling());
+        for tx in &self.txn {
+            tx.marshalling_without_witness(bytes);
+        }
+    }
+
     /// Valida el bloque. Primero realiza la proof of work y
     /// Luego realiza la proof of inclusion sobre su lista de transacciones
     /// Validates the block. First performs the proof of work and
@@ -84,15 +108,333 @@ impl Block {
                 "The merkle root generated by the block does not match the one in the header",
             );
         }
-        let mut weight = Vec::new();
-        self.marshalling(&mut weight);
-        // Check that the block does not exceed 1 MB
-        if weight.len() > 1048576 {
-            return (false, "The block exceeds 1 MB");
+        let (witness_commitment_is_valid, reason) = self.validate_witness_commitment();
+        if !witness_commitment_is_valid {
+            return (false, reason);
+        }
+        // Check that the block does not exceed 4M weight units
+        if self.weight() > MAX_BLOCK_WEIGHT {
+            return (
+                false,
+                "The block exceeds the maximum weight of 4M weight units",
+            );
         }
         (true, "Valid block")
     }
 
+    /// Returns the weight of the block as defined in BIP141:
+    /// the size without witness data multiplied by 3 plus the total size.
+    pub fn weight(&self) -> usize {
+        let mut base_bytes = Vec::new();
+        self.marshalling_without_witness(&mut base_bytes);
+        let mut total_bytes = Vec::new();
+        self.marshalling(&mut total_bytes);
+        base_bytes.len() * (WITNESS_SCALE_FACTOR - 1) + total_bytes.len()
+    }
+
+    /// Validates the witness commitment of the block (BIP141).
+    /// If the coinbase has a commitment, its witness must be the 32 bytes reserved value and the commitment
+    /// must be the hash of the witness merkle root concatenated with that value.
+    /// If there is no commitment, no transaction of the block can have witness data.
+    pub fn validate_witness_commitment(&self) -> (bool, &'static str) {
+        let coinbase = match self.txn.first() {
+            Some(coinbase) => coinbase,
+            None => return (false, "The block has no transactions"),
+        };
+        let commitment = match self.witness_commitment() {
+            Some(commitment) => commitment,
+            None => {
+                if self.txn.iter().any(|tx| tx.has_witness()) {
+                    return (
+                        false,
+                        "The block has witness data but the coinbase has no witness commitment",
+                    );
+                }
+                return (true, "Valid witness commitment");
+            }
+        };
+        let coinbase_witness = match coinbase.tx_in.first() {
+            Some(tx_in) => tx_in.witness(),
+            None => return (false, "The coinbase transaction has no inputs"),
+        };
+        if coinbase_witness.len() != 1 || coinbase_witness[0].len() != 32 {
+            return (
+                false,
+                "The witness of the coinbase is not a 32 bytes reserved value",
+            );
+        }
+        let mut reserved_value: [u8; 32] = [0; 32];
+        reserved_value.copy_from_slice(&coinbase_witness[0]);
+        if concatenate_and_hash(self.generate_witness_merkle_root(), reserved_value) != commitment {
+            return (
+                false,
+                "The witness commitment of the coinbase does not match the witness merkle root",
+            );
+        }
+        (true, "Valid witness commitment")
+    }
+
+    /// Returns the witness commitment of the coinbase, if it has one.
+    /// If there is more than one output with a commitment, the last one is used.
+    fn witness_commitment(&self) -> Option<[u8; 32]> {
+        let coinbase = self.txn.first()?;
+        let pk_script = coinbase
+            .tx_out
+            .iter()
+            .rev()
+            .map(|tx_out| tx_out.get_pub_key_script())
+            .find(|pk_script| {
+                pk_script.len() >= 38 && pk_script[..6] == WITNESS_COMMITMENT_HEADER
+            })?;
+        let mut commitment: [u8; 32] = [0; 32];
+        commitment.copy_from_slice(&pk_script[6..38]);
+        Some(commitment)
+    }
+
+    /// Generates the witness merkle root from the wtxids of the transactions.
+    /// The wtxid of the coinbase is considered to be all zeros.
+    pub fn generate_witness_merkle_root(&self) -> [u8; 32] {
+        let mut witness_hashes: Vec<[u8; 32]> = Vec::new();
+        for (index, tx) in self.txn.iter().enumerate() {
+            if index == 0 {
+                witness_hashes.push([0; 32]);
+            } else {
+                witness_hashes.push(tx.wtxid());
+            }
+        }
+        Self::recursive_generation_merkle_root(witness_hashes)
+    }
+
+    /// Executes the scripts of the inputs of the block transactions against the outputs they spend.
+    /// The spent outputs are searched in the previous transactions of the block and in the utxo set received.
+    /// Inputs that spend outputs unknown by the node (created before the first block downloaded) can not be checked,
+    /// and neither can the taproot inputs of transactions that spend some unknown output, because their signatures
+    /// commit to all the outputs spent by the transaction.
+    pub fn validate_scripts(
+        &self,
+        utxo_set: &HashMap<[u8; 32], UtxoTuple>,
+    ) -> (bool, &'static str) {
+        let mut block_transactions: HashMap<[u8; 32], &Transaction> = HashMap::new();
+        for tx in &self.txn {
+            if !tx.is_coinbase_transaction() {
+                let spent_outputs: Vec<Option<TxOut>> = tx
+                    .tx_in
+                    .iter()
+                    .map(|tx_in| {
+                        let previous_hash = tx_in.get_previous_output_hash();
+                        let previous_index = tx_in.get_previous_output_index();
+                        match block_transactions.get(&previous_hash) {
+                            Some(previous_tx) => previous_tx.tx_out.get(previous_index).cloned(),
+                            None => utxo_set
+                                .get(&previous_hash)
+                                .and_then(|utxo| utxo.get_tx_out(previous_index).cloned()),
+                        }
+                    })
+                    .collect();
+                let all_outputs_known = spent_outputs.iter().all(|tx_out| tx_out.is_some());
+                for (index, spent_output) in spent_outputs.iter().enumerate() {
+                    let can_be_checked = match spent_output {
+                        Some(tx_out) => all_outputs_known || !is_p2tr(tx_out.get_pub_key_script()),
+                        None => false,
+                    };
+                    if can_be_checked && tx.validate_input(index, &spent_outputs).is_err() {
+                        return (false, "A transaction of the block has an invalid script");
+                    }
+                }
+            }
+            block_transactions.insert(tx.hash(), tx);
+        }
+        (true, "Valid scripts")
+    }
+
+    /// Validates the transactions of the block against the utxo_set in which the block will be connected,
+    /// at the received height of a chain whose subsidy is halved every subsidy_halving_interval blocks. Fails if an input spends an output that does not exist or was already spent,
+    /// if a coinbase is spent before 100 confirmations, if the outputs of a transaction exceed its inputs, if an amount
+    /// is out of the range from 0 to MAX_MONEY or if the coinbase claims more than the subsidy plus the fees.
+    /// The first transaction of the block must be its only coinbase.
+    /// If the utxo_set is not complete (it was loaded from a block after the genesis), the inputs whose previous
+    /// transaction is unknown are not checked, and neither are the amounts that depend on them.
+    pub fn validate_utxos(
+        &self,
+        utxo_set: &HashMap<[u8; 32], UtxoTuple>,
+        height: usize,
+        subsidy_halving_interval: usize,
+        utxo_set_is_complete: bool,
+    ) -> (bool, &'static str) {
+        let mut block_transactions: HashMap<[u8; 32], &Transaction> = HashMap::new();
+        let mut spent_outputs: HashSet<([u8; 32], usize)> = HashSet::new();
+        let mut fees: i64 = 0;
+        let mut all_inputs_are_known = true;
+        if !self
+            .txn
+            .first()
+            .is_some_and(|tx| tx.is_coinbase_transaction())
+        {
+            return (
+                false,
+                "The first transaction of the block is not a coinbase",
+            );
+        }
+        if self
+            .txn
+            .iter()
+            .skip(1)
+            .any(|tx| tx.is_coinbase_transaction())
+        {
+            return (false, "The block has more than one coinbase");
+        }
+        let mut coinbase_amount: i64 = 0;
+        for (index, tx) in self.txn.iter().enumerate() {
+            let outputs_amount = match tx.checked_amount() {
+                Some(outputs_amount) => outputs_amount,
+                None => {
+                    return (
+                        false,
+                        "The outputs of a transaction of the block are out of the valid range",
+                    )
+                }
+            };
+            if index == 0 {
+                coinbase_amount = outputs_amount;
+                block_transactions.insert(tx.hash(), tx);
+                continue;
+            }
+            let mut inputs_amount: Option<i64> = Some(0);
+            for tx_in in &tx.tx_in {
+                let previous_hash = tx_in.get_previous_output_hash();
+                let previous_index = tx_in.get_previous_output_index();
+                if !spent_outputs.insert((previous_hash, previous_index)) {
+                    return (
+                        false,
+                        "A transaction of the block spends an output already spent",
+                    );
+                }
+                let (spent_tx_out, creation_height, is_coinbase) =
+                    match block_transactions.get(&previous_hash) {
+                        Some(previous_tx) => (
+                            previous_tx.tx_out.get(previous_index),
+                            height,
+                            previous_tx.is_coinbase_transaction(),
+                        ),
+                        None => match utxo_set.get(&previous_hash) {
+                            Some(utxo) => (
+                                utxo.get_tx_out(previous_index),
+                                utxo.height,
+                                utxo.is_coinbase,
+                            ),
+                            None if utxo_set_is_complete => return (
+                                false,
+                                "A transaction of the block spends an output that does not exist",
+                            ),
+                            None => {
+                                all_inputs_are_known = false;
+                                inputs_amount = None;
+                                continue;
+                            }
+                        },
+                    };
+                let spent_tx_out = match spent_tx_out {
+                    Some(spent_tx_out) => spent_tx_out,
+                    None => {
+                        return (
+                            false,
+                            "A transaction of the block spends an output that does not exist or was already spent",
+                        )
+                    }
+                };
+                if is_coinbase && height.saturating_sub(creation_height) < COINBASE_MATURITY {
+                    return (
+                        false,
+                        "A transaction of the block spends a coinbase with less than 100 confirmations",
+                    );
+                }
+                if let Some(amount) = inputs_amount {
+                    match amount
+                        .checked_add(spent_tx_out.value())
+                        .filter(|amount| (0..=MAX_MONEY).contains(amount))
+                    {
+                        Some(amount) => inputs_amount = Some(amount),
+                        None => return (
+                            false,
+                            "The inputs of a transaction of the block are out of the valid range",
+                        ),
+                    }
+                }
+            }
+            if let Some(inputs_amount) = inputs_amount {
+                if outputs_amount > inputs_amount {
+                    return (
+                        false,
+                        "The outputs of a transaction of the block exceed its inputs",
+                    );
+                }
+                fees = match fees
+                    .checked_add(inputs_amount - outputs_amount)
+                    .filter(|fees| *fees <= MAX_MONEY)
+                {
+                    Some(fees) => fees,
+                    None => return (false, "The fees of the block are out of the valid range"),
+                };
+            }
+            block_transactions.insert(tx.hash(), tx);
+        }
+        if all_inputs_are_known
+            && coinbase_amount > block_subsidy(height, subsidy_halving_interval) + fees
+        {
+            return (
+                false,
+                "The coinbase of the block claims more than the subsidy plus the fees",
+            );
+        }
+        (true, "Valid transactions")
+    }
+
+    /// Validates the lock times of the transactions of the block, which will be connected at the received height
+    /// of the chain. The chain must contain the headers previous to the block, since the median time past of the
+    /// previous block is used as the time of the block (BIP113). Fails if a transaction is not final or if the
+    /// relative lock times of its inputs (BIP68) are not satisfied. The inputs that spend outputs that are not
+    /// in the utxo_set nor in the block are not checked.
+    pub fn validate_lock_times(
+        &self,
+        utxo_set: &HashMap<[u8; 32], UtxoTuple>,
+        height: usize,
+        chain: &HeaderChain,
+    ) -> (bool, &'static str) {
+        let median_time_past = chain.median_time_past_at(height.saturating_sub(1)) as i64;
+        let block_transactions: HashSet<[u8; 32]> = self.txn.iter().map(|tx| tx.hash()).collect();
+        for tx in &self.txn {
+            if !tx.is_final(height, median_time_past) {
+                return (false, "The block contains a transaction that is not final");
+            }
+            if tx.is_coinbase_transaction() {
+                continue;
+            }
+            let previous_heights: Vec<Option<usize>> = tx
+                .tx_in
+                .iter()
+                .map(|tx_in| {
+                    let previous_hash = tx_in.get_previous_output_hash();
+                    if block_transactions.contains(&previous_hash) {
+                        return Some(height);
+                    }
+                    utxo_set.get(&previous_hash).map(|utxo| utxo.height)
+                })
+                .collect();
+            if !tx.sequence_locks_are_satisfied(
+                &previous_heights,
+                height,
+                median_time_past,
+                |height| chain.median_time_past_at(height) as i64,
+            ) {
+                return (
+                    false,
+                    "The block contains a transaction whose relative lock times are not satisfied",
+                );
+            }
+        }
+        (true, "Valid lock times")
+    }
+
     /// Generates the merkle root root from the hashes of the transactions (tx_id).
     /// Reduces the elements of the tx_id vector, groups them in pairs, hashes them and saves them again
     /// in a vector which will be processed recursively until the merkle root hash is obtained.
@@ -135,22 +477,63 @@ impl Block {
         self.block_header.hash() == *block_id
     }
 
-    /// Updates the utxo_set received by parameter.
+    /// Updates the utxo_set received by parameter. The height is the one of the block in the blockchain.
     /// Processes the block transactions. Adds the new utxos and removes the spent ones.
+    /// Returns the undo data of the block, with the outputs that were spent.
     pub fn give_me_utxos(
         &self,
         utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
-    ) -> Result<(), Box<dyn Error>> {
+        height: usize,
+    ) -> Result<BlockUndo, Box<dyn Error>> {
+        let mut block_undo = BlockUndo::new();
         for tx in &self.txn {
             if tx.is_coinbase_transaction() {
                 // As it is a coinbase, being the first tx, only the utxos of this transaction will be loaded 
-                tx.load_utxos(utxo_set.clone())?;
+                tx.load_utxos(utxo_set.clone(), height)?;
             } else {
                 // Remove the utxos used by this tx
-                tx.remove_utxos(utxo_set.clone())?;
+                block_undo.add_spent_outputs(tx.remove_utxos(utxo_set.clone())?);
                 // Then load the utxos of this tx so that in the next iteration
                 // those that are used are removed
-                tx.load_utxos(utxo_set.clone())?;
+                tx.load_utxos(utxo_set.clone(), height)?;
+            }
+        }
+        Ok(block_undo)
+    }
+
+    /// Disconnects the block from the utxo_set received by parameter using its undo data.
+    /// Removes the outputs created by its transactions and restores the outputs they spent.
+    pub fn disconnect_block(
+        &self,
+        utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
+        block_undo: &BlockUndo,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut utxo_set = utxo_set
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        // The transactions are processed in reverse order, so that the outputs created and spent in the
+        // same block are not restored. The spent outputs of each transaction are at the end of the undo data
+        let mut spent_outputs = block_undo.spent_outputs.iter().rev().peekable();
+        for tx in self.txn.iter().rev() {
+            utxo_set.remove(&tx.hash());
+            while let Some(spent_output) = spent_outputs.next_if(|spent_output| {
+                tx.tx_in.iter().any(|tx_in| {
+                    tx_in.get_previous_output_hash() == spent_output.outpoint.hash()
+                        && tx_in.get_previous_output_index() == spent_output.outpoint.index()
+                })
+            }) {
+                let previous_hash = spent_output.outpoint.hash();
+                utxo_set
+                    .entry(previous_hash)
+                    .or_insert_with(|| {
+                        UtxoTuple::new(
+                            previous_hash,
+                            Vec::new(),
+                            spent_output.height,
+                            spent_output.is_coinbase,
+                        )
+                    })
+                    .add_utxo(spent_output.tx_out.clone(), spent_output.outpoint.index());
             }
         }
         Ok(())
@@ -251,6 +634,62 @@ impl Block {
         Ok(())
     }
 
+    /// Moves back to the pending transactions of the accounts the confirmed transactions included in the block.
+    /// It is used when the block is disconnected from the active chain.
+    pub fn revert_confirmed_txs(
+        &self,
+        log_sender: &LogSender,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
+    ) -> Result<(), NodeCustomErrors> {
+        for tx in &self.txn {
+            for account in &*accounts
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            {
+                let confirmed_transaction_index = account
+                    .confirmed_transactions
+                    .read()
+                    .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                    .iter()
+                    .position(|confirmed_tx| confirmed_tx.hash() == tx.hash());
+                if let Some(confirmed_transaction_index) = confirmed_transaction_index {
+                    let reverted_tx = account
+                        .confirmed_transactions
+                        .write()
+                        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                        .remove(confirmed_transaction_index);
+                    account
+                        .pending_transactions
+                        .write()
+                        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                        .push(reverted_tx.clone());
+                    write_in_log(
+                        &log_sender.info_log_sender,
+                        format!(
+                            "ACCOUNT: {}: TRANSACTION {} REVERTED, THE BLOCK --{}-- WAS DISCONNECTED",
+                            account.address,
+                            reverted_tx.hex_hash(),
+                            self.hex_hash()
+                        )
+                        .as_str(),
+                    );
+                    send_event_to_ui(
+                        ui_sender,
+                        UIEvent::ShowRevertedTransaction(
+                            self.clone(),
+                            account.clone(),
+                            reverted_tx,
+                        ),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Returns the block hash.
     pub fn hash(&self) -> [u8; 32] {
         self.block_header.hash()
@@ -267,19 +706,46 @@ impl Block {
     }
 }
 
+/// Returns the subsidy in satoshis of the block at the received height.
+/// It starts at 50 BTC and is halved every subsidy_halving_interval blocks (210000 in every network but regtest).
+pub fn block_subsidy(height: usize, subsidy_halving_interval: usize) -> i64 {
+    let halvings = height / subsidy_halving_interval;
+    if halvings >= 64 {
+        return 0;
+    }
+    INITIAL_BLOCK_SUBSIDY >> halvings
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
-        blocks::{block_header::BlockHeader, utils_block::concatenate_and_hash},
+        blocks::{
+            block_header::BlockHeader, header_validation::HeaderChain,
+            utils_block::concatenate_and_hash,
+        },
+        chain_params::REGTEST_PARAMS,
         compact_size_uint::CompactSizeUint,
         transactions::{
-            outpoint::Outpoint, script::sig_script::SigScript, transaction::Transaction,
-            tx_in::TxIn, tx_out::TxOut,
+            outpoint::Outpoint,
+            script::sig_script::SigScript,
+            transaction::{Transaction, MAX_MONEY},
+            tx_in::TxIn,
+            tx_out::TxOut,
         },
     };
-    use std::{error::Error, io, vec};
+    use std::{
+        collections::HashMap,
+        error::Error,
+        io,
+        sync::{Arc, RwLock},
+        vec,
+    };
 
-    use super::Block;
+    use super::{block_subsidy, Block};
+    use crate::utxo_tuple::UtxoTuple;
+
+    /// Subsidy halving interval of every network but regtest
+    const HALVING_INTERVAL: usize = 210_000;
 
     /// Converts the str received in hexadecimal, to bytes.
     fn string_to_bytes(input: &str) -> Result<[u8; 32], Box<dyn Error>> {
@@ -442,6 +908,432 @@ mod test {
     }
 
 
+    /// Creates a block whose second transaction has witness data, with a valid witness commitment in the coinbase.
+    fn create_block_with_witness_commitment() -> Block {
+        let block_header: BlockHeader = BlockHeader {
+            version: (0x30201000),
+            previous_block_header_hash: ([1; 32]),
+            merkle_root_hash: ([2; 32]),
+            time: (0x90807060),
+            n_bits: (0x04030201),
+            nonce: (0x30),
+        };
+        let mut coinbase = create_transaction(2, 1, 1, 0);
+        coinbase.tx_in[0].set_witness(vec![vec![0; 32]]);
+        let mut segwit_tx = create_transaction(2, 1, 1, 0);
+        segwit_tx.tx_in[0].set_witness(vec![vec![0x30; 71], vec![0x02; 33]]);
+        let mut block = Block::new(
+            block_header,
+            CompactSizeUint::new(2),
+            vec![coinbase, segwit_tx],
+        );
+        let commitment = concatenate_and_hash(block.generate_witness_merkle_root(), [0; 32]);
+        let mut pk_script: Vec<u8> = vec![0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+        pk_script.extend_from_slice(&commitment);
+        block.txn[0].tx_out.push(TxOut::new(
+            0,
+            CompactSizeUint::new(pk_script.len() as u128),
+            pk_script,
+        ));
+        block.txn[0].txout_count = CompactSizeUint::new(2);
+        block
+    }
+
+    /// Creates a coinbase transaction with an output of the received value.
+    fn create_coinbase(value: i64) -> Transaction {
+        let tx_in = TxIn::new(
+            Outpoint::new([0; 32], 0xffffffff),
+            CompactSizeUint::new(4),
+            Some(vec![3, 1, 0, 0]),
+            SigScript::new(vec![]),
+            0xffffffff,
+        );
+        let tx_out = TxOut::new(value, CompactSizeUint::new(0), Vec::new());
+        Transaction::new(
+            1,
+            CompactSizeUint::new(1),
+            vec![tx_in],
+            CompactSizeUint::new(1),
+            vec![tx_out],
+            0,
+        )
+    }
+
+    /// Creates a block with a coinbase of the received value and a transaction with an output of 43 satoshis
+    /// that spends the outputs received.
+    fn create_block_spending(outpoints: &[Outpoint], coinbase_value: i64) -> Block {
+        let mut tx = create_transaction(1, 1, 1, 0);
+        tx.tx_in = outpoints
+            .iter()
+            .map(|outpoint| TxIn::incomplete_txin(*outpoint))
+            .collect();
+        tx.txin_count = CompactSizeUint::new(outpoints.len() as u128);
+        let block_header: BlockHeader = BlockHeader::new(1, [1; 32], [2; 32], 0, 0x1d00ffff, 0);
+        Block::new(
+            block_header,
+            CompactSizeUint::new(2),
+            vec![create_coinbase(coinbase_value), tx],
+        )
+    }
+
+    /// Creates a utxo set with a transaction with two outputs of 1000 satoshis.
+    fn create_utxo_set(height: usize, is_coinbase: bool) -> HashMap<[u8; 32], UtxoTuple> {
+        let tx_out = TxOut::new(1000, CompactSizeUint::new(0), Vec::new());
+        let mut utxo_set = HashMap::new();
+        utxo_set.insert(
+            [7; 32],
+            UtxoTuple::new(
+                [7; 32],
+                vec![(tx_out.clone(), 0), (tx_out, 1)],
+                height,
+                is_coinbase,
+            ),
+        );
+        utxo_set
+    }
+
+    #[test]
+    fn test_block_subsidy_is_halved_every_210000_blocks() {
+        assert_eq!(block_subsidy(0, HALVING_INTERVAL), 5_000_000_000);
+        assert_eq!(block_subsidy(209_999, HALVING_INTERVAL), 5_000_000_000);
+        assert_eq!(block_subsidy(210_000, HALVING_INTERVAL), 2_500_000_000);
+        assert_eq!(block_subsidy(2_500_000, HALVING_INTERVAL), 2_441_406);
+        assert_eq!(block_subsidy(64 * 210_000, HALVING_INTERVAL), 0);
+    }
+
+    #[test]
+    fn test_block_subsidy_is_halved_every_150_blocks_in_regtest() {
+        let interval = REGTEST_PARAMS.subsidy_halving_interval;
+        assert_eq!(block_subsidy(149, interval), 5_000_000_000);
+        assert_eq!(block_subsidy(150, interval), 2_500_000_000);
+        assert_eq!(block_subsidy(300, interval), 1_250_000_000);
+        assert_eq!(block_subsidy(64 * 150, interval), 0);
+    }
+
+    #[test]
+    fn test_coinbase_can_claim_the_subsidy_plus_the_fees() {
+        let utxo_set = create_utxo_set(10, false);
+        let block = create_block_spending(&[Outpoint::new([7; 32], 0)], 5_000_000_000 + 957);
+        assert!(
+            block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true)
+                .0
+        );
+    }
+
+    #[test]
+    fn test_coinbase_that_claims_more_than_the_subsidy_plus_the_fees_is_invalid() {
+        let utxo_set = create_utxo_set(10, false);
+        let block = create_block_spending(&[Outpoint::new([7; 32], 0)], 5_000_000_000 + 958);
+        assert_eq!(
+            block.validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true),
+            (
+                false,
+                "The coinbase of the block claims more than the subsidy plus the fees"
+            )
+        );
+    }
+
+    #[test]
+    fn test_block_that_spends_a_missing_output_is_invalid_if_the_utxo_set_is_complete() {
+        let utxo_set = create_utxo_set(10, false);
+        let block = create_block_spending(&[Outpoint::new([8; 32], 0)], 0);
+        assert!(
+            !block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true)
+                .0
+        );
+        assert!(
+            block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, false)
+                .0
+        );
+    }
+
+    #[test]
+    fn test_block_that_spends_an_output_already_spent_is_invalid() {
+        let mut utxo_set = create_utxo_set(10, false);
+        if let Some(utxo) = utxo_set.get_mut(&[7; 32]) {
+            utxo.remove_utxo(1);
+        }
+        let block = create_block_spending(&[Outpoint::new([7; 32], 1)], 0);
+        assert!(
+            !block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, false)
+                .0
+        );
+        let block =
+            create_block_spending(&[Outpoint::new([7; 32], 0), Outpoint::new([7; 32], 0)], 0);
+        assert!(
+            !block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, false)
+                .0
+        );
+    }
+
+    #[test]
+    fn test_block_that_spends_a_coinbase_with_less_than_100_confirmations_is_invalid() {
+        let utxo_set = create_utxo_set(101, true);
+        let block = create_block_spending(&[Outpoint::new([7; 32], 0)], 0);
+        assert!(
+            !block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true)
+                .0
+        );
+        assert!(
+            block
+                .validate_utxos(&utxo_set, 201, HALVING_INTERVAL, true)
+                .0
+        );
+    }
+
+    #[test]
+    fn test_transaction_whose_outputs_exceed_its_inputs_is_invalid() {
+        let mut utxo_set = create_utxo_set(10, false);
+        if let Some(utxo) = utxo_set.get_mut(&[7; 32]) {
+            utxo.utxo_set[0].0 = TxOut::new(42, CompactSizeUint::new(0), Vec::new());
+        }
+        let block = create_block_spending(&[Outpoint::new([7; 32], 0)], 0);
+        assert_eq!(
+            block.validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true),
+            (
+                false,
+                "The outputs of a transaction of the block exceed its inputs"
+            )
+        );
+    }
+
+    #[test]
+    fn test_transaction_with_an_output_out_of_the_valid_range_is_invalid() {
+        let utxo_set = create_utxo_set(10, false);
+        let mut block = create_block_spending(&[Outpoint::new([7; 32], 0)], 0);
+        // a negative output would let the other outputs exceed the inputs
+        block.txn[1].tx_out = vec![
+            TxOut::new(-1_000_000, CompactSizeUint::new(0), Vec::new()),
+            TxOut::new(1_000_500, CompactSizeUint::new(0), Vec::new()),
+        ];
+        assert_eq!(
+            block.validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true),
+            (
+                false,
+                "The outputs of a transaction of the block are out of the valid range"
+            )
+        );
+        block.txn[1].tx_out = vec![TxOut::new(i64::MAX, CompactSizeUint::new(0), Vec::new()); 2];
+        assert!(
+            !block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true)
+                .0
+        );
+        let block = create_block_spending(&[Outpoint::new([7; 32], 0)], MAX_MONEY + 1);
+        assert!(
+            !block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, false)
+                .0
+        );
+    }
+
+    #[test]
+    fn test_block_whose_only_coinbase_is_not_the_first_transaction_is_invalid() {
+        let utxo_set = create_utxo_set(10, false);
+        let mut block = create_block_spending(&[Outpoint::new([7; 32], 0)], 0);
+        block.txn.push(create_coinbase(5_000_000_000));
+        assert_eq!(
+            block.validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true),
+            (false, "The block has more than one coinbase")
+        );
+        block.txn.swap(0, 1);
+        block.txn.pop();
+        assert_eq!(
+            block.validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true),
+            (
+                false,
+                "The first transaction of the block is not a coinbase"
+            )
+        );
+    }
+
+    /// Creates the headers of a chain with the received amount of blocks, separated by 10 minutes.
+    fn create_headers(amount: usize) -> Vec<BlockHeader> {
+        (0..amount)
+            .map(|height| BlockHeader::new(1, [0; 32], [0; 32], height as u32 * 600, 0x1d00ffff, 0))
+            .collect()
+    }
+
+    #[test]
+    fn test_block_with_a_transaction_that_is_not_final_is_invalid() {
+        let utxo_set = create_utxo_set(10, false);
+        let mut block = create_block_spending(&[Outpoint::new([7; 32], 0)], 0);
+        block.txn[1].lock_time = 200;
+        block.txn[1].tx_in[0].set_sequence(0xfffffffe);
+        let headers = create_headers(201);
+        assert_eq!(
+            block.validate_lock_times(&utxo_set, 200, &HeaderChain::new(&headers[..200], &[])),
+            (false, "The block contains a transaction that is not final")
+        );
+        assert!(
+            block
+                .validate_lock_times(&utxo_set, 201, &HeaderChain::new(&headers, &[]))
+                .0
+        );
+    }
+
+    #[test]
+    fn test_block_with_a_transaction_whose_relative_lock_time_is_not_satisfied_is_invalid() {
+        let utxo_set = create_utxo_set(195, false);
+        let mut block = create_block_spending(&[Outpoint::new([7; 32], 0)], 0);
+        block.txn[1].version = 2;
+        block.txn[1].tx_in[0].set_sequence(10);
+        let headers = create_headers(205);
+        assert!(
+            !block
+                .validate_lock_times(&utxo_set, 200, &HeaderChain::new(&headers[..200], &[]))
+                .0
+        );
+        assert!(
+            block
+                .validate_lock_times(&utxo_set, 205, &HeaderChain::new(&headers, &[]))
+                .0
+        );
+    }
+
+    #[test]
+    fn test_transaction_can_spend_an_output_created_earlier_in_the_same_block() {
+        let utxo_set = create_utxo_set(10, false);
+        let mut block = create_block_spending(&[Outpoint::new([7; 32], 0)], 0);
+        let mut second_tx = create_transaction(1, 1, 1, 0);
+        second_tx.tx_in[0] = TxIn::incomplete_txin(Outpoint::new(block.txn[1].hash(), 0));
+        block.txn.push(second_tx);
+        assert!(
+            block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true)
+                .0
+        );
+        block.txn.swap(1, 2);
+        assert!(
+            !block
+                .validate_utxos(&utxo_set, 200, HALVING_INTERVAL, true)
+                .0
+        );
+    }
+
+    #[test]
+    fn test_block_with_valid_witness_commitment_is_valid() {
+        let block = create_block_with_witness_commitment();
+        assert!(block.validate_witness_commitment().0);
+    }
+
+    #[test]
+    fn test_block_with_modified_witness_does_not_match_the_commitment() {
+        let mut block = create_block_with_witness_commitment();
+        block.txn[1].tx_in[0].set_witness(vec![vec![0x30; 70], vec![0x02; 33]]);
+        assert_eq!(
+            block.validate_witness_commitment(),
+            (
+                false,
+                "The witness commitment of the coinbase does not match the witness merkle root"
+            )
+        );
+    }
+
+    #[test]
+    fn test_block_with_invalid_coinbase_reserved_value_is_not_valid() {
+        let mut block = create_block_with_witness_commitment();
+        block.txn[0].tx_in[0].set_witness(vec![vec![0; 31]]);
+        assert!(!block.validate_witness_commitment().0);
+    }
+
+    #[test]
+    fn test_block_with_witness_data_and_without_commitment_is_not_valid() {
+        let mut block = create_block_with_witness_commitment();
+        block.txn[0].tx_out.pop();
+        block.txn[0].txout_count = CompactSizeUint::new(1);
+        assert_eq!(
+            block.validate_witness_commitment(),
+            (
+                false,
+                "The block has witness data but the coinbase has no witness commitment"
+            )
+        );
+    }
+
+    #[test]
+    fn test_weight_of_block_counts_witness_data_once() {
+        let block = create_block_with_witness_commitment();
+        let mut base_bytes = Vec::new();
+        block.marshalling_without_witness(&mut base_bytes);
+        let mut total_bytes = Vec::new();
+        block.marshalling(&mut total_bytes);
+        assert!(total_bytes.len() > base_bytes.len());
+        assert_eq!(block.weight(), base_bytes.len() * 3 + total_bytes.len());
+        let mut block_without_witness = block.clone();
+        for tx in block_without_witness.txn.iter_mut() {
+            tx.tx_in[0].set_witness(vec![]);
+        }
+        assert_eq!(block_without_witness.weight(), base_bytes.len() * 4);
+    }
+
+    #[test]
+    fn test_disconnect_block_restores_the_spent_outputs_and_removes_the_created_ones(
+    ) -> Result<(), Box<dyn Error>> {
+        let previous_tx = create_transaction(1, 1, 2, 0);
+        let mut spending_tx = create_transaction(1, 1, 1, 0);
+        spending_tx.tx_in[0] = TxIn::incomplete_txin(Outpoint::new(previous_tx.hash(), 1));
+        let block_header: BlockHeader = BlockHeader::new(1, [1; 32], [2; 32], 0, 0x1d00ffff, 0);
+        let coinbase = create_transaction(1, 1, 1, 7);
+        let block = Block::new(
+            block_header,
+            CompactSizeUint::new(2),
+            vec![coinbase, spending_tx.clone()],
+        );
+        let utxo_set = Arc::new(RwLock::new(HashMap::new()));
+        previous_tx.load_utxos(utxo_set.clone(), 10)?;
+        let block_undo = block.give_me_utxos(utxo_set.clone(), 11)?;
+        assert_eq!(block_undo.spent_outputs.len(), 1);
+        assert_eq!(block_undo.spent_outputs[0].height, 10);
+        assert!(
+            utxo_set.read().map_err(|err| err.to_string())?[&previous_tx.hash()]
+                .find(previous_tx.hash(), 1)
+                .is_none()
+        );
+        block.disconnect_block(utxo_set.clone(), &block_undo)?;
+        let utxo_set = utxo_set.read().map_err(|err| err.to_string())?;
+        assert!(utxo_set[&previous_tx.hash()]
+            .find(previous_tx.hash(), 1)
+            .is_some());
+        assert_eq!(utxo_set[&previous_tx.hash()].height, 10);
+        assert!(!utxo_set.contains_key(&spending_tx.hash()));
+        assert!(!utxo_set.contains_key(&block.txn[0].hash()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_disconnect_block_restores_an_output_created_and_spent_in_the_same_block(
+    ) -> Result<(), Box<dyn Error>> {
+        let previous_tx = create_transaction(1, 1, 1, 0);
+        let mut first_tx = create_transaction(1, 1, 1, 0);
+        first_tx.tx_in[0] = TxIn::incomplete_txin(Outpoint::new(previous_tx.hash(), 0));
+        let mut second_tx = create_transaction(1, 1, 1, 0);
+        second_tx.tx_in[0] = TxIn::incomplete_txin(Outpoint::new(first_tx.hash(), 0));
+        let block_header: BlockHeader = BlockHeader::new(1, [1; 32], [2; 32], 0, 0x1d00ffff, 0);
+        let block = Block::new(
+            block_header,
+            CompactSizeUint::new(3),
+            vec![create_transaction(1, 1, 1, 7), first_tx.clone(), second_tx],
+        );
+        let utxo_set = Arc::new(RwLock::new(HashMap::new()));
+        previous_tx.load_utxos(utxo_set.clone(), 3)?;
+        let block_undo = block.give_me_utxos(utxo_set.clone(), 4)?;
+        assert_eq!(block_undo.spent_outputs.len(), 2);
+        block.disconnect_block(utxo_set.clone(), &block_undo)?;
+        let utxo_set = utxo_set.read().map_err(|err| err.to_string())?;
+        assert_eq!(utxo_set.len(), 1);
+        assert!(utxo_set[&previous_tx.hash()]
+            .find(previous_tx.hash(), 0)
+            .is_some());
+        Ok(())
+    }
+
     #[test]
     fn test_merkle_root_of_block_with_2_transactions_is_generated_correctly() {
         let block_header: BlockHeader = BlockHeader {
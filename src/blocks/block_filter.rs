@@ -0,0 +1,300 @@
+use std::collections::HashSet;
+
+use bitcoin_hashes::{sha256d, siphash24, Hash};
+
+use super::{block::Block, block_undo::BlockUndo};
+use crate::compact_size_uint::CompactSizeUint;
+
+/// Type of the basic filter defined in BIP158.
+pub const BASIC_FILTER_TYPE: u8 = 0x00;
+/// Amount of bits of the remainder of the Golomb-Rice coding of the basic filter.
+const BASIC_FILTER_P: u8 = 19;
+/// Inverse of the false positive rate of the basic filter.
+const BASIC_FILTER_M: u64 = 784931;
+const OP_RETURN: u8 = 0x6a;
+/// Output script of the coinbase of the genesis block, which is the same in every network.
+const GENESIS_OUTPUT_SCRIPT: [u8; 67] = [
+    0x41, 0x04, 0x67, 0x8a, 0xfd, 0xb0, 0xfe, 0x55, 0x48, 0x27, 0x19, 0x67, 0xf1, 0xa6, 0x71, 0x30,
+    0xb7, 0x10, 0x5c, 0xd6, 0xa8, 0x28, 0xe0, 0x39, 0x09, 0xa6, 0x79, 0x62, 0xe0, 0xea, 0x1f, 0x61,
+    0xde, 0xb6, 0x49, 0xf6, 0xbc, 0x3f, 0x4c, 0xef, 0x38, 0xc4, 0xf3, 0x55, 0x04, 0xe5, 0x1e, 0xc1,
+    0x12, 0xde, 0x5c, 0x38, 0x4d, 0xf7, 0xba, 0x0b, 0x8d, 0x57, 0x8a, 0x4c, 0x70, 0x2b, 0x6b, 0xf1,
+    0x1d, 0x5f, 0xac,
+];
+
+/// Returns the basic filter (BIP158) of the block: a Golomb-coded set of the output scripts of its
+/// transactions, except the empty and OP_RETURN ones, and of the scripts of the outputs spent by them,
+/// which are found in the undo data of the block.
+pub fn basic_filter(block: &Block, block_undo: &BlockUndo) -> Vec<u8> {
+    let mut scripts: Vec<&[u8]> = Vec::new();
+    for tx in &block.txn {
+        for tx_out in &tx.tx_out {
+            let script = tx_out.get_pub_key_script();
+            if !script.is_empty() && script[0] != OP_RETURN {
+                scripts.push(script);
+            }
+        }
+    }
+    for spent_output in &block_undo.spent_outputs {
+        let script = spent_output.tx_out.get_pub_key_script();
+        if !script.is_empty() {
+            scripts.push(script);
+        }
+    }
+    golomb_coded_set(&block.hash(), &scripts)
+}
+
+/// Returns the basic filter of the genesis block with the received hash. Its outputs can't be spent,
+/// so it is not connected and its filter is built from the output script of its coinbase.
+pub fn genesis_basic_filter(genesis_hash: &[u8; 32]) -> Vec<u8> {
+    golomb_coded_set(genesis_hash, &[&GENESIS_OUTPUT_SCRIPT])
+}
+
+/// Returns the hash of the filter, which is the double sha256 of its bytes.
+pub fn filter_hash(filter: &[u8]) -> [u8; 32] {
+    sha256d::Hash::hash(filter).to_byte_array()
+}
+
+/// Returns the header of the filter, which is the double sha256 of the hash of the filter followed
+/// by the header of the filter of the previous block (zeros for the genesis block).
+pub fn filter_header(filter: &[u8], previous_filter_header: &[u8; 32]) -> [u8; 32] {
+    let mut bytes: Vec<u8> = Vec::with_capacity(64);
+    bytes.extend_from_slice(&filter_hash(filter));
+    bytes.extend_from_slice(previous_filter_header);
+    sha256d::Hash::hash(&bytes).to_byte_array()
+}
+
+/// Returns true if the filter of the block with the received hash may contain the script. There are
+/// no false negatives, and the false positives have a probability of 1/784931.
+pub fn filter_matches(filter: &[u8], block_hash: &[u8; 32], script: &[u8]) -> bool {
+    let mut offset = 0;
+    let amount_of_items = match CompactSizeUint::unmarshalling(filter, &mut offset) {
+        Ok(amount_of_items) => amount_of_items.decoded_value(),
+        Err(_) => return false,
+    };
+    let target = hash_to_range(block_hash, script, amount_of_items * BASIC_FILTER_M);
+    let mut reader = BitReader::new(&filter[offset..]);
+    let mut value: u64 = 0;
+    for _ in 0..amount_of_items {
+        let delta = match golomb_rice_decode(&mut reader) {
+            Some(delta) => delta,
+            None => return false,
+        };
+        value += delta;
+        if value == target {
+            return true;
+        }
+        if value > target {
+            return false;
+        }
+    }
+    false
+}
+
+/// Builds the Golomb-coded set of the items with the key of the block: the amount of distinct items
+/// followed by the sorted differences between their hashes, coded with Golomb-Rice.
+fn golomb_coded_set(block_hash: &[u8; 32], items: &[&[u8]]) -> Vec<u8> {
+    let distinct_items: HashSet<&[u8]> = items.iter().copied().collect();
+    let amount_of_items = distinct_items.len() as u64;
+    let range = amount_of_items * BASIC_FILTER_M;
+    let mut hashed_items: Vec<u64> = distinct_items
+        .iter()
+        .map(|item| hash_to_range(block_hash, item, range))
+        .collect();
+    hashed_items.sort_unstable();
+    let mut filter = CompactSizeUint::new(amount_of_items as u128).marshalling();
+    let mut writer = BitWriter::new();
+    let mut previous_value: u64 = 0;
+    for value in hashed_items {
+        golomb_rice_encode(&mut writer, value - previous_value);
+        previous_value = value;
+    }
+    filter.extend_from_slice(&writer.into_bytes());
+    filter
+}
+
+/// Hashes the item with SipHash-2-4, with the first 16 bytes of the block hash as key, and maps the
+/// hash uniformly to the range [0, range).
+fn hash_to_range(block_hash: &[u8; 32], item: &[u8], range: u64) -> u64 {
+    let mut k0 = [0u8; 8];
+    k0.copy_from_slice(&block_hash[..8]);
+    let mut k1 = [0u8; 8];
+    k1.copy_from_slice(&block_hash[8..16]);
+    let hash = siphash24::Hash::hash_to_u64_with_keys(
+        u64::from_le_bytes(k0),
+        u64::from_le_bytes(k1),
+        item,
+    );
+    ((hash as u128 * range as u128) >> 64) as u64
+}
+
+/// Writes the value as the quotient of the division by 2^P in unary (ones ended by a zero) followed
+/// by the P bits of the remainder.
+fn golomb_rice_encode(writer: &mut BitWriter, value: u64) {
+    for _ in 0..(value >> BASIC_FILTER_P) {
+        writer.write_bit(true);
+    }
+    writer.write_bit(false);
+    for bit in (0..BASIC_FILTER_P).rev() {
+        writer.write_bit((value >> bit) & 1 == 1);
+    }
+}
+
+/// Reads a value written with Golomb-Rice coding. Returns None if there are not enough bits.
+fn golomb_rice_decode(reader: &mut BitReader) -> Option<u64> {
+    let mut quotient: u64 = 0;
+    while reader.read_bit()? {
+        quotient += 1;
+    }
+    let mut remainder: u64 = 0;
+    for _ in 0..BASIC_FILTER_P {
+        remainder = (remainder << 1) | reader.read_bit()? as u64;
+    }
+    Some((quotient << BASIC_FILTER_P) | remainder)
+}
+
+/// Writes bits starting from the most significant bit of each byte.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits_in_last_byte: u8,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            bytes: Vec::new(),
+            bits_in_last_byte: 8,
+        }
+    }
+
+    fn write_bit(&mut self, bit: bool) {
+        if self.bits_in_last_byte == 8 {
+            self.bytes.push(0);
+            self.bits_in_last_byte = 0;
+        }
+        if bit {
+            if let Some(last_byte) = self.bytes.last_mut() {
+                *last_byte |= 0x80 >> self.bits_in_last_byte;
+            }
+        }
+        self.bits_in_last_byte += 1;
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads bits starting from the most significant bit of each byte.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.position / 8)?;
+        let bit = (byte >> (7 - self.position % 8)) & 1 == 1;
+        self.position += 1;
+        Some(bit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        basic_filter, filter_header, filter_matches, genesis_basic_filter, GENESIS_OUTPUT_SCRIPT,
+    };
+    use crate::{
+        blocks::{
+            block::Block,
+            block_header::BlockHeader,
+            block_undo::{BlockUndo, SpentOutput},
+        },
+        compact_size_uint::CompactSizeUint,
+        transactions::{
+            outpoint::Outpoint, script::sig_script::SigScript, transaction::Transaction,
+            tx_in::TxIn, tx_out::TxOut,
+        },
+    };
+
+    /// Hash of the testnet genesis block, in the order of the bytes of the protocol
+    const TESTNET_GENESIS_HASH: [u8; 32] = [
+        0x43, 0x49, 0x7f, 0xd7, 0xf8, 0x26, 0x95, 0x71, 0x08, 0xf4, 0xa3, 0x0f, 0xd9, 0xce, 0xc3,
+        0xae, 0xba, 0x79, 0x97, 0x20, 0x84, 0xe9, 0x0e, 0xad, 0x01, 0xea, 0x33, 0x09, 0x00, 0x00,
+        0x00, 0x00,
+    ];
+
+    /// Creates a block with a transaction with an output for each script
+    fn create_block(scripts: Vec<Vec<u8>>) -> Block {
+        let tx_in = TxIn::new(
+            Outpoint::new([1; 32], 0),
+            CompactSizeUint::new(0),
+            None,
+            SigScript::new(vec![]),
+            0xffffffff,
+        );
+        let tx_out: Vec<TxOut> = scripts
+            .into_iter()
+            .map(|script| TxOut::new(1000, CompactSizeUint::new(script.len() as u128), script))
+            .collect();
+        let tx = Transaction::new(
+            1,
+            CompactSizeUint::new(1),
+            vec![tx_in],
+            CompactSizeUint::new(tx_out.len() as u128),
+            tx_out,
+            0,
+        );
+        let block_header = BlockHeader::new(1, [3; 32], [2; 32], 0, 0x1d00ffff, 0);
+        Block::new(block_header, CompactSizeUint::new(1), vec![tx])
+    }
+
+    #[test]
+    fn test_genesis_filter_matches_the_bip158_test_vector() {
+        let filter = genesis_basic_filter(&TESTNET_GENESIS_HASH);
+        assert_eq!(filter, vec![0x01, 0x9d, 0xfc, 0xa8]);
+        let mut header = filter_header(&filter, &[0; 32]);
+        header.reverse();
+        assert_eq!(
+            crate::account::bytes_to_hex_string(&header),
+            "21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750"
+        );
+        assert!(filter_matches(
+            &filter,
+            &TESTNET_GENESIS_HASH,
+            &GENESIS_OUTPUT_SCRIPT
+        ));
+    }
+
+    #[test]
+    fn test_filter_matches_the_output_and_spent_scripts_of_the_block() {
+        let block = create_block(vec![vec![0x51], vec![0x52, 0x87], vec![0x6a, 0x01]]);
+        let mut block_undo = BlockUndo::new();
+        block_undo.add_spent_outputs(vec![SpentOutput::new(
+            Outpoint::new([1; 32], 0),
+            TxOut::new(5000, CompactSizeUint::new(2), vec![0x53, 0x87]),
+            1,
+            false,
+        )]);
+        let filter = basic_filter(&block, &block_undo);
+        // the OP_RETURN output is not included
+        assert_eq!(filter[0], 3);
+        assert!(filter_matches(&filter, &block.hash(), &[0x51]));
+        assert!(filter_matches(&filter, &block.hash(), &[0x52, 0x87]));
+        assert!(filter_matches(&filter, &block.hash(), &[0x53, 0x87]));
+        assert!(!filter_matches(&filter, &block.hash(), &[0x6a, 0x01]));
+        assert!(!filter_matches(&filter, &block.hash(), &[0x54, 0x87]));
+    }
+
+    #[test]
+    fn test_filter_of_block_without_scripts_is_empty() {
+        let block = create_block(vec![vec![]]);
+        let filter = basic_filter(&block, &BlockUndo::new());
+        assert_eq!(filter, vec![0x00]);
+        assert!(!filter_matches(&filter, &block.hash(), &[0x51]));
+    }
+}
@@ -1,6 +1,8 @@
 use bitcoin_hashes::{sha256d, Hash};
 use chrono::{TimeZone, Utc, DateTime, Local};
 
+use super::chainwork::ChainWork;
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 /// Represents the Block Header of the bitcoin protocol.
 pub struct BlockHeader {
@@ -135,6 +137,15 @@ impl BlockHeader {
         false
     }
 
+    /// Returns the amount of work represented by the header, calculated from the target encoded in n_bits.
+    /// It is the expected number of hashes needed to find a hash lower or equal to the target: 2^256 / (target + 1).
+    /// Returns 0 if the target is 0, negative or does not fit in 256 bits.
+    pub fn work(&self) -> ChainWork {
+        ChainWork::target_from_n_bits(self.n_bits)
+            .map(ChainWork::from_target)
+            .unwrap_or_default()
+    }
+
     /// Compares the merkle root hash of the block with the received hash.
     pub fn is_same_merkle_root_hash(&self, received_hash: &[u8; 32]) -> bool {
         self.merkle_root_hash == *received_hash
@@ -171,6 +182,7 @@ fn bytes_to_hex_hash(hash_as_bytes: [u8; 32]) -> String {
 #[cfg(test)]
 mod tests {
     use super::BlockHeader;
+    use crate::blocks::chainwork::ChainWork;
     use bitcoin_hashes::{sha256d, Hash};
 
     /// Auxiliary function that initializes a Block Header
@@ -184,6 +196,21 @@ mod tests {
         Ok(block_header)
     }
 
+    #[test]
+    fn test_work_of_header_with_minimum_difficulty_is_the_expected() {
+        let mut block_header = BlockHeader::new(1, [0; 32], [0; 32], 0, 0x1d00ffff, 0);
+        // Chainwork of the genesis block: 0x100010001
+        assert_eq!(block_header.work(), ChainWork::from(0x100010001));
+        block_header.n_bits = 0x1c00ffff;
+        assert_eq!(block_header.work(), ChainWork::from(0x100010001 << 8));
+    }
+
+    #[test]
+    fn test_work_of_header_with_zero_target_is_zero() {
+        let block_header = BlockHeader::new(1, [0; 32], [0; 32], 0, 0x1d000000, 0);
+        assert_eq!(block_header.work(), ChainWork::default());
+    }
+
     #[test]
     fn test_deserialization_of_header_generates_expected_version() -> Result<(), &'static str> {
         let block_header: BlockHeader = generate_block_header()?;
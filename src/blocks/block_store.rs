@@ -0,0 +1,827 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use bitcoin_hashes::{sha256d, Hash};
+
+use super::{block::Block, block_undo::BlockUndo};
+use crate::custom_errors::NodeCustomErrors;
+
+/// Maximum size of a blkNNNNN.dat file. When a block does not fit, the next file is created.
+const MAX_BLOCK_FILE_SIZE: u64 = 128 * 1024 * 1024;
+/// Minimum size of a blkNNNNN.dat file when the store is pruned.
+const MIN_PRUNED_BLOCK_FILE_SIZE: u64 = 1024 * 1024;
+/// Amount of blocks below the tip that are never pruned, so that reorgs can be handled and the peers
+/// can download the last blocks, as required by NODE_NETWORK_LIMITED (BIP159).
+pub const MIN_BLOCKS_TO_KEEP: usize = 288;
+/// Start string of the network followed by the size of the block (u32 little endian)
+const RECORD_HEADER_SIZE: u64 = 8;
+/// Hash of the block, number of file (u32), offset in the file (u64) and height (u64), little endian
+const INDEX_RECORD_SIZE: usize = 52;
+const INDEX_FILE_NAME: &str = "index.dat";
+/// First 4 bytes of the double sha256 of the undo data, written after it
+const UNDO_CHECKSUM_SIZE: usize = 4;
+/// Hash of the block, number of file (u32) and offset in the file (u64), little endian
+const UNDO_INDEX_RECORD_SIZE: usize = 44;
+const UNDO_INDEX_FILE_NAME: &str = "undo_index.dat";
+
+/// Number of the revNNNNN.dat file and offset in which the record of the undo data of a block starts.
+type UndoLocation = (u32, u64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Represents the position of a stored block: the number of the blkNNNNN.dat file, the offset in
+/// which its record starts and the height of the block when it was stored.
+pub struct BlockLocation {
+    pub file: u32,
+    pub offset: u64,
+    pub height: usize,
+}
+
+#[derive(Debug)]
+/// Stores the raw serialized blocks appended to rotating blkNNNNN.dat files in a directory, as the
+/// reference client does. Each record has the start string of the network, the size of the block and
+/// the block. The index of the hash of each block to its location is kept in memory and appended to
+/// the index.dat file after the block is written, so that the stored blocks are not downloaded again.
+/// The undo data of the connected blocks is appended to the revNNNNN.dat file with the number of the file of
+/// the block, with the same format and a checksum at the end, and its location to the undo_index.dat file.
+/// If a prune target is set, the oldest files are removed while the store is bigger than the target.
+/// The blocks of the removed files (below the first file) are kept in the index as pruned.
+pub struct BlockStore {
+    directory: PathBuf,
+    start_string: [u8; 4],
+    max_file_size: u64,
+    index: HashMap<[u8; 32], BlockLocation>,
+    undo_index: HashMap<[u8; 32], UndoLocation>,
+    current_file: u32,
+    current_file_size: u64,
+    first_file: u32,
+    prune_target: u64,
+    pruned_height: usize,
+}
+
+impl BlockStore {
+    /// Opens the block store of the directory received, creating it if it does not exist, and loads
+    /// the index of the blocks stored. The start string identifies the network of the blocks.
+    /// Returns an error if the directory or the index can not be read.
+    pub fn open(directory: &str, start_string: [u8; 4]) -> Result<BlockStore, NodeCustomErrors> {
+        Self::open_with_max_file_size(directory, start_string, MAX_BLOCK_FILE_SIZE)
+    }
+
+    /// Opens the block store with the received maximum size of the blkNNNNN.dat files.
+    fn open_with_max_file_size(
+        directory: &str,
+        start_string: [u8; 4],
+        max_file_size: u64,
+    ) -> Result<BlockStore, NodeCustomErrors> {
+        fs::create_dir_all(directory)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        let mut block_store = BlockStore {
+            directory: PathBuf::from(directory),
+            start_string,
+            max_file_size,
+            index: HashMap::new(),
+            undo_index: HashMap::new(),
+            current_file: 0,
+            current_file_size: 0,
+            first_file: 0,
+            prune_target: 0,
+            pruned_height: 0,
+        };
+        block_store.load_index()?;
+        block_store.load_undo_index()?;
+        block_store.current_file_size =
+            file_size(&block_store.block_file_path(block_store.current_file));
+        Ok(block_store)
+    }
+
+    /// Reads the index file and loads the location of the stored blocks. A record that was not
+    /// completely written (the node stopped while writing it) is removed from the file, and the
+    /// locations that are beyond the end of their block file are discarded. The blocks of the files
+    /// before the first block file that exists were pruned.
+    fn load_index(&mut self) -> Result<(), NodeCustomErrors> {
+        let data = read_records_file(&self.directory.join(INDEX_FILE_NAME), INDEX_RECORD_SIZE)?;
+        self.first_file = self.first_block_file()?;
+        let mut file_sizes: HashMap<u32, u64> = HashMap::new();
+        for record in data.chunks_exact(INDEX_RECORD_SIZE) {
+            let (hash, location) = unmarshalling_index_record(record);
+            if location.file < self.first_file {
+                self.pruned_height = self.pruned_height.max(location.height);
+                self.index.insert(hash, location);
+                continue;
+            }
+            let size = *file_sizes
+                .entry(location.file)
+                .or_insert_with(|| file_size(&self.block_file_path(location.file)));
+            if location.offset + RECORD_HEADER_SIZE > size {
+                continue;
+            }
+            self.current_file = self.current_file.max(location.file);
+            self.index.insert(hash, location);
+        }
+        self.current_file = self.current_file.max(self.first_file);
+        Ok(())
+    }
+
+    /// Reads the undo index file and loads the location of the undo data stored, as load_index does with
+    /// the blocks. The undo data of the pruned files and the locations beyond the end of their revNNNNN.dat
+    /// file are discarded.
+    fn load_undo_index(&mut self) -> Result<(), NodeCustomErrors> {
+        let data = read_records_file(
+            &self.directory.join(UNDO_INDEX_FILE_NAME),
+            UNDO_INDEX_RECORD_SIZE,
+        )?;
+        let mut file_sizes: HashMap<u32, u64> = HashMap::new();
+        for record in data.chunks_exact(UNDO_INDEX_RECORD_SIZE) {
+            let (hash, (file, offset)) = unmarshalling_undo_index_record(record);
+            if file < self.first_file {
+                continue;
+            }
+            let size = *file_sizes
+                .entry(file)
+                .or_insert_with(|| file_size(&self.undo_file_path(file)));
+            if offset + RECORD_HEADER_SIZE > size {
+                continue;
+            }
+            self.undo_index.insert(hash, (file, offset));
+        }
+        Ok(())
+    }
+
+    /// Returns the lowest number of the blkNNNNN.dat files of the directory, 0 if there are none.
+    fn first_block_file(&self) -> Result<u32, NodeCustomErrors> {
+        let entries = fs::read_dir(&self.directory)
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        let first_file = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.strip_prefix("blk")?
+                    .strip_suffix(".dat")?
+                    .parse::<u32>()
+                    .ok()
+            })
+            .min();
+        Ok(first_file.unwrap_or(0))
+    }
+
+    /// Sets the maximum size in bytes of the blkNNNNN.dat files kept by the store, 0 to disable pruning.
+    /// The size of the new files is reduced so that they are removed in smaller steps.
+    pub fn set_prune_target(&mut self, prune_target: u64) {
+        self.prune_target = prune_target;
+        if prune_target > 0 {
+            self.max_file_size = self
+                .max_file_size
+                .min((prune_target / 8).max(MIN_PRUNED_BLOCK_FILE_SIZE));
+        }
+    }
+
+    /// Returns true if the store removes its oldest blocks.
+    pub fn is_pruning(&self) -> bool {
+        self.prune_target > 0
+    }
+
+    /// Returns the height of the highest block pruned, or 0 if no block was pruned.
+    pub fn pruned_height(&self) -> usize {
+        self.pruned_height
+    }
+
+    /// Returns true if the block with the received hash was stored and then pruned.
+    pub fn is_pruned(&self, hash: &[u8; 32]) -> bool {
+        self.index
+            .get(hash)
+            .is_some_and(|location| location.file < self.first_file)
+    }
+
+    /// Removes the oldest blkNNNNN.dat files, with their revNNNNN.dat files, while the store is bigger than
+    /// the prune target. A file is only removed if all its blocks are at the received height or below it, and
+    /// the file being written is never removed. Returns the hashes of the pruned blocks or an error if a file can not be removed.
+    pub fn prune(&mut self, max_height: usize) -> Result<Vec<[u8; 32]>, NodeCustomErrors> {
+        let mut pruned_blocks: Vec<[u8; 32]> = Vec::new();
+        if !self.is_pruning() {
+            return Ok(pruned_blocks);
+        }
+        while self.first_file < self.current_file && self.stored_size() > self.prune_target {
+            let blocks_of_file: Vec<([u8; 32], usize)> = self
+                .index
+                .iter()
+                .filter(|(_, location)| location.file == self.first_file)
+                .map(|(hash, location)| (*hash, location.height))
+                .collect();
+            if blocks_of_file
+                .iter()
+                .any(|(_, height)| *height > max_height)
+            {
+                break;
+            }
+            for path in [
+                self.block_file_path(self.first_file),
+                self.undo_file_path(self.first_file),
+            ] {
+                if path.exists() {
+                    fs::remove_file(path)
+                        .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+                }
+            }
+            let first_file = self.first_file;
+            self.undo_index.retain(|_, (file, _)| *file != first_file);
+            self.first_file += 1;
+            for (hash, height) in blocks_of_file {
+                self.pruned_height = self.pruned_height.max(height);
+                pruned_blocks.push(hash);
+            }
+        }
+        Ok(pruned_blocks)
+    }
+
+    /// Removes the pruned blocks from the index, so that they are stored again if they are downloaded.
+    /// It is used when the blocks have to be connected again.
+    pub fn forget_pruned_blocks(&mut self) {
+        let first_file = self.first_file;
+        self.index.retain(|_, location| location.file >= first_file);
+        self.pruned_height = 0;
+    }
+
+    /// Returns the size in bytes of the blkNNNNN.dat and revNNNNN.dat files that were not pruned.
+    fn stored_size(&self) -> u64 {
+        (self.first_file..=self.current_file)
+            .map(|file| {
+                file_size(&self.block_file_path(file)) + file_size(&self.undo_file_path(file))
+            })
+            .sum()
+    }
+
+    /// Returns the path of the blkNNNNN.dat file with the received number.
+    fn block_file_path(&self, file: u32) -> PathBuf {
+        self.directory.join(format!("blk{:05}.dat", file))
+    }
+
+    /// Returns the path of the revNNNNN.dat file with the received number.
+    fn undo_file_path(&self, file: u32) -> PathBuf {
+        self.directory.join(format!("rev{:05}.dat", file))
+    }
+
+    /// Appends the block to the current blkNNNNN.dat file, or to a new one if it does not fit, and
+    /// then adds its location to the index. The block is not written again if it is already stored.
+    /// Returns the location of the block or an error if it can not be written.
+    pub fn write_block(
+        &mut self,
+        block: &Block,
+        height: usize,
+    ) -> Result<BlockLocation, NodeCustomErrors> {
+        let hash = block.hash();
+        if let Some(location) = self.index.get(&hash) {
+            if location.file >= self.first_file {
+                return Ok(*location);
+            }
+        }
+        let mut block_bytes: Vec<u8> = Vec::new();
+        block.marshalling(&mut block_bytes);
+        let record_size = RECORD_HEADER_SIZE + block_bytes.len() as u64;
+        if self.current_file_size > 0 && self.current_file_size + record_size > self.max_file_size {
+            self.current_file += 1;
+            self.current_file_size = file_size(&self.block_file_path(self.current_file));
+        }
+        let location = BlockLocation {
+            file: self.current_file,
+            offset: self.current_file_size,
+            height,
+        };
+        let mut record: Vec<u8> = Vec::new();
+        record.extend_from_slice(&self.start_string);
+        record.extend_from_slice(&(block_bytes.len() as u32).to_le_bytes());
+        record.extend_from_slice(&block_bytes);
+        append_to_file(&self.block_file_path(location.file), &record)?;
+        self.current_file_size += record_size;
+        // the location is indexed after the block is written, so it never points to missing data
+        append_to_file(
+            &self.directory.join(INDEX_FILE_NAME),
+            &marshalling_index_record(&hash, &location),
+        )?;
+        self.index.insert(hash, location);
+        Ok(location)
+    }
+
+    /// Reads the block with the received hash from its blkNNNNN.dat file.
+    /// Returns None if it is not stored or was pruned, or an error if the file can not be read or the record is invalid.
+    pub fn read_block(&self, hash: &[u8; 32]) -> Result<Option<Block>, NodeCustomErrors> {
+        let location = match self.index.get(hash) {
+            Some(location) if location.file >= self.first_file => location,
+            _ => return Ok(None),
+        };
+        let mut file = File::open(self.block_file_path(location.file))
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        let mut record_header = [0u8; RECORD_HEADER_SIZE as usize];
+        file.seek(SeekFrom::Start(location.offset))
+            .and_then(|_| file.read_exact(&mut record_header))
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        if record_header[..4] != self.start_string {
+            return Err(NodeCustomErrors::ReadingFileError(
+                "The stored block does not start with the start string of the network".to_string(),
+            ));
+        }
+        let mut size_bytes = [0u8; 4];
+        size_bytes.copy_from_slice(&record_header[4..]);
+        let mut block_bytes = vec![0u8; u32::from_le_bytes(size_bytes) as usize];
+        file.read_exact(&mut block_bytes)
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        let block = Block::unmarshalling(&block_bytes, &mut 0)
+            .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+        if block.hash() != *hash {
+            return Err(NodeCustomErrors::ReadingFileError(
+                "The stored block does not have the hash of the index".to_string(),
+            ));
+        }
+        Ok(Some(block))
+    }
+
+    /// Appends the undo data of the block with the received hash to the revNNNNN.dat file of the number of
+    /// the file of the block, or of the file being written if the block is not stored, and then adds its
+    /// location to the undo index. If the block already had undo data, it is replaced by the new one.
+    /// Returns an error if it can not be written.
+    pub fn write_undo(
+        &mut self,
+        hash: &[u8; 32],
+        block_undo: &BlockUndo,
+    ) -> Result<(), NodeCustomErrors> {
+        let file = match self.location(hash) {
+            Some(location) => location.file,
+            None => self.current_file,
+        };
+        let path = self.undo_file_path(file);
+        let offset = file_size(&path);
+        let mut undo_bytes: Vec<u8> = Vec::new();
+        block_undo.marshalling(&mut undo_bytes);
+        let mut record: Vec<u8> = Vec::new();
+        record.extend_from_slice(&self.start_string);
+        record.extend_from_slice(&(undo_bytes.len() as u32).to_le_bytes());
+        record.extend_from_slice(&undo_bytes);
+        record.extend_from_slice(&undo_checksum(&undo_bytes));
+        append_to_file(&path, &record)?;
+        // the location is indexed after the undo data is written, so it never points to missing data
+        append_to_file(
+            &self.directory.join(UNDO_INDEX_FILE_NAME),
+            &marshalling_undo_index_record(hash, (file, offset)),
+        )?;
+        self.undo_index.insert(*hash, (file, offset));
+        Ok(())
+    }
+
+    /// Reads the undo data of the block with the received hash from its revNNNNN.dat file.
+    /// Returns None if there is no undo data of the block or it was pruned, or an error if the file can not be
+    /// read or the record is invalid.
+    pub fn read_undo(&self, hash: &[u8; 32]) -> Result<Option<BlockUndo>, NodeCustomErrors> {
+        let (file, offset) = match self.undo_index.get(hash) {
+            Some(location) => *location,
+            None => return Ok(None),
+        };
+        let mut undo_file = File::open(self.undo_file_path(file))
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        let mut record_header = [0u8; RECORD_HEADER_SIZE as usize];
+        undo_file
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| undo_file.read_exact(&mut record_header))
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        if record_header[..4] != self.start_string {
+            return Err(NodeCustomErrors::ReadingFileError(
+                "The stored undo data does not start with the start string of the network"
+                    .to_string(),
+            ));
+        }
+        let mut size_bytes = [0u8; 4];
+        size_bytes.copy_from_slice(&record_header[4..]);
+        let size = u32::from_le_bytes(size_bytes) as usize;
+        let mut undo_bytes = vec![0u8; size + UNDO_CHECKSUM_SIZE];
+        undo_file
+            .read_exact(&mut undo_bytes)
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        let checksum = undo_bytes.split_off(size);
+        if checksum != undo_checksum(&undo_bytes) {
+            return Err(NodeCustomErrors::ReadingFileError(
+                "The checksum of the stored undo data is not valid".to_string(),
+            ));
+        }
+        let block_undo = BlockUndo::unmarshalling(&undo_bytes, &mut 0)
+            .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+        Ok(Some(block_undo))
+    }
+
+    /// Returns true if the undo data of the block with the received hash is stored and was not pruned.
+    pub fn has_undo(&self, hash: &[u8; 32]) -> bool {
+        self.undo_index.contains_key(hash)
+    }
+
+    /// Removes the undo data of the block with the received hash from the undo index, after the block was
+    /// disconnected. Its record stays in the revNNNNN.dat file until the file is pruned.
+    pub fn remove_undo(&mut self, hash: &[u8; 32]) {
+        self.undo_index.remove(hash);
+    }
+
+    /// Returns true if the block with the received hash is stored and was not pruned.
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.location(hash).is_some()
+    }
+
+    /// Returns the location of the block with the received hash, if it is stored and was not pruned.
+    pub fn location(&self, hash: &[u8; 32]) -> Option<BlockLocation> {
+        self.index
+            .get(hash)
+            .filter(|location| location.file >= self.first_file)
+            .copied()
+    }
+
+    /// Returns the amount of blocks stored that were not pruned.
+    pub fn len(&self) -> usize {
+        self.index
+            .values()
+            .filter(|location| location.file >= self.first_file)
+            .count()
+    }
+
+    /// Returns true if there are no blocks stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the hashes of the stored blocks that were not pruned with their location, in ascending order by height.
+    pub fn stored_blocks(&self) -> Vec<([u8; 32], BlockLocation)> {
+        let mut stored_blocks: Vec<([u8; 32], BlockLocation)> = self
+            .index
+            .iter()
+            .filter(|(_, location)| location.file >= self.first_file)
+            .map(|(hash, location)| (*hash, *location))
+            .collect();
+        stored_blocks
+            .sort_by_key(|(_, location)| (location.height, location.file, location.offset));
+        stored_blocks
+    }
+}
+
+/// Returns the size of the file, 0 if it does not exist.
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+/// Appends the bytes at the end of the file, creating it if it does not exist.
+fn append_to_file(path: &Path, bytes: &[u8]) -> Result<(), NodeCustomErrors> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+    file.write_all(bytes)
+        .and_then(|_| file.flush())
+        .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))
+}
+
+/// Reads the file of fixed size records with the received path, empty if it does not exist. A record that
+/// was not completely written (the node stopped while writing it) is removed from the file.
+/// Returns the bytes of the complete records or an error if the file can not be read.
+fn read_records_file(path: &Path, record_size: usize) -> Result<Vec<u8>, NodeCustomErrors> {
+    let mut data: Vec<u8> = Vec::new();
+    if !path.exists() {
+        return Ok(data);
+    }
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut data))
+        .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+    let complete_records_size = data.len() - data.len() % record_size;
+    if complete_records_size != data.len() {
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_len(complete_records_size as u64))
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        data.truncate(complete_records_size);
+    }
+    Ok(data)
+}
+
+/// Returns the first 4 bytes of the double sha256 of the undo data.
+fn undo_checksum(undo_bytes: &[u8]) -> [u8; UNDO_CHECKSUM_SIZE] {
+    let mut checksum = [0u8; UNDO_CHECKSUM_SIZE];
+    checksum.copy_from_slice(&sha256d::Hash::hash(undo_bytes)[..UNDO_CHECKSUM_SIZE]);
+    checksum
+}
+
+/// Converts the hash of a block and the location of its undo data to the bytes of its record in the undo index file.
+fn marshalling_undo_index_record(hash: &[u8; 32], (file, offset): UndoLocation) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend_from_slice(hash);
+    bytes.extend_from_slice(&file.to_le_bytes());
+    bytes.extend_from_slice(&offset.to_le_bytes());
+    bytes
+}
+
+/// Receives the bytes of a record of the undo index file and returns the hash of the block and the location of its undo data.
+fn unmarshalling_undo_index_record(record: &[u8]) -> ([u8; 32], UndoLocation) {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&record[..32]);
+    let mut file = [0u8; 4];
+    file.copy_from_slice(&record[32..36]);
+    let mut offset = [0u8; 8];
+    offset.copy_from_slice(&record[36..44]);
+    (hash, (u32::from_le_bytes(file), u64::from_le_bytes(offset)))
+}
+
+/// Converts the hash and the location of a block to the bytes of its record in the index file.
+fn marshalling_index_record(hash: &[u8; 32], location: &BlockLocation) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend_from_slice(hash);
+    bytes.extend_from_slice(&location.file.to_le_bytes());
+    bytes.extend_from_slice(&location.offset.to_le_bytes());
+    bytes.extend_from_slice(&(location.height as u64).to_le_bytes());
+    bytes
+}
+
+/// Receives the bytes of a record of the index file and returns the hash and the location of the block.
+fn unmarshalling_index_record(record: &[u8]) -> ([u8; 32], BlockLocation) {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&record[..32]);
+    let mut file = [0u8; 4];
+    file.copy_from_slice(&record[32..36]);
+    let mut offset = [0u8; 8];
+    offset.copy_from_slice(&record[36..44]);
+    let mut height = [0u8; 8];
+    height.copy_from_slice(&record[44..52]);
+    (
+        hash,
+        BlockLocation {
+            file: u32::from_le_bytes(file),
+            offset: u64::from_le_bytes(offset),
+            height: u64::from_le_bytes(height) as usize,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{BlockLocation, BlockStore, INDEX_FILE_NAME};
+    use crate::{
+        blocks::{
+            block::Block,
+            block_header::BlockHeader,
+            block_undo::{BlockUndo, SpentOutput},
+        },
+        compact_size_uint::CompactSizeUint,
+        custom_errors::NodeCustomErrors,
+        temp_directory::TempDirectory,
+        transactions::{
+            outpoint::Outpoint, script::sig_script::SigScript, transaction::Transaction,
+            tx_in::TxIn, tx_out::TxOut,
+        },
+    };
+
+    const START_STRING: [u8; 4] = [0x0b, 0x11, 0x09, 0x07];
+
+    /// Creates a block with a coinbase transaction whose output has the received value
+    fn create_block(previous_hash: [u8; 32], value: i64) -> Block {
+        let tx_in = TxIn::new(
+            Outpoint::new([0; 32], 0xffffffff),
+            CompactSizeUint::new(4),
+            Some(vec![3, 1, 0, 0]),
+            SigScript::new(vec![]),
+            0xffffffff,
+        );
+        let tx_out = TxOut::new(value, CompactSizeUint::new(0), Vec::new());
+        let coinbase = Transaction::new(
+            1,
+            CompactSizeUint::new(1),
+            vec![tx_in],
+            CompactSizeUint::new(1),
+            vec![tx_out],
+            0,
+        );
+        let block_header = BlockHeader::new(1, previous_hash, [2; 32], 0, 0x1d00ffff, 0);
+        Block::new(block_header, CompactSizeUint::new(1), vec![coinbase])
+    }
+
+    #[test]
+    fn test_stored_block_is_read_from_its_file() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("block_store_read");
+        let path = directory.path_string();
+        let block = create_block([1; 32], 5000);
+        let mut block_store = BlockStore::open(&path, START_STRING)?;
+        assert_eq!(
+            block_store.write_block(&block, 7)?,
+            BlockLocation {
+                file: 0,
+                offset: 0,
+                height: 7
+            }
+        );
+        assert!(block_store.contains(&block.hash()));
+        let stored_block = block_store.read_block(&block.hash())?;
+        assert_eq!(stored_block.map(|block| block.hash()), Some(block.hash()));
+        assert!(block_store.read_block(&[9; 32])?.is_none());
+        assert!(directory.path().join("blk00000.dat").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_is_loaded_when_the_store_is_opened_again() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("block_store_reopen");
+        let path = directory.path_string();
+        let first_block = create_block([1; 32], 5000);
+        let second_block = create_block(first_block.hash(), 6000);
+        {
+            let mut block_store = BlockStore::open(&path, START_STRING)?;
+            block_store.write_block(&second_block, 2)?;
+            block_store.write_block(&first_block, 1)?;
+            // a block already stored is not written again
+            block_store.write_block(&first_block, 1)?;
+        }
+        let block_store = BlockStore::open(&path, START_STRING)?;
+        assert_eq!(block_store.len(), 2);
+        let hashes: Vec<[u8; 32]> = block_store
+            .stored_blocks()
+            .iter()
+            .map(|(hash, _)| *hash)
+            .collect();
+        assert_eq!(hashes, vec![first_block.hash(), second_block.hash()]);
+        let stored_block = block_store.read_block(&second_block.hash())?;
+        assert_eq!(
+            stored_block.map(|block| block.hash()),
+            Some(second_block.hash())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocks_are_written_in_a_new_file_when_they_do_not_fit() -> Result<(), NodeCustomErrors>
+    {
+        let directory = TempDirectory::new("block_store_rotation");
+        let path = directory.path_string();
+        let mut block_store = BlockStore::open_with_max_file_size(&path, START_STRING, 200)?;
+        let mut previous_hash = [1; 32];
+        for height in 0..3 {
+            let block = create_block(previous_hash, 5000);
+            previous_hash = block.hash();
+            let location = block_store.write_block(&block, height)?;
+            assert_eq!(location.file, height as u32);
+            assert_eq!(location.offset, 0);
+        }
+        assert!(directory.path().join("blk00002.dat").exists());
+        let block_store = BlockStore::open_with_max_file_size(&path, START_STRING, 200)?;
+        assert!(block_store.read_block(&previous_hash)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_incomplete_index_record_is_discarded() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("block_store_incomplete");
+        let path = directory.path_string();
+        let block = create_block([1; 32], 5000);
+        {
+            let mut block_store = BlockStore::open(&path, START_STRING)?;
+            block_store.write_block(&block, 1)?;
+        }
+        // the node stopped while writing the record of another block
+        let index_path = directory.path().join(INDEX_FILE_NAME);
+        let mut index = fs::read(&index_path).unwrap_or_default();
+        index.extend_from_slice(&[7; 20]);
+        let _ = fs::write(&index_path, index);
+        let mut block_store = BlockStore::open(&path, START_STRING)?;
+        assert_eq!(block_store.len(), 1);
+        let other_block = create_block(block.hash(), 6000);
+        block_store.write_block(&other_block, 2)?;
+        let block_store = BlockStore::open(&path, START_STRING)?;
+        assert_eq!(block_store.len(), 2);
+        assert!(block_store.read_block(&other_block.hash())?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_oldest_files_are_pruned_until_the_store_fits_in_the_target(
+    ) -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("block_store_prune");
+        let path = directory.path_string();
+        let mut hashes: Vec<[u8; 32]> = Vec::new();
+        {
+            let mut block_store = BlockStore::open_with_max_file_size(&path, START_STRING, 200)?;
+            block_store.set_prune_target(400);
+            let mut previous_hash = [1; 32];
+            for height in 1..=4 {
+                let block = create_block(previous_hash, 5000);
+                previous_hash = block.hash();
+                hashes.push(block.hash());
+                block_store.write_block(&block, height)?;
+            }
+            // the blocks above the height received are not pruned
+            assert_eq!(block_store.prune(1)?, vec![hashes[0]]);
+            assert_eq!(block_store.prune(4)?, vec![hashes[1]]);
+            assert!(block_store.is_pruned(&hashes[0]));
+            assert!(block_store.read_block(&hashes[1])?.is_none());
+            assert_eq!(block_store.pruned_height(), 2);
+            assert_eq!(block_store.len(), 2);
+        }
+        assert!(!directory.path().join("blk00000.dat").exists());
+        let mut block_store = BlockStore::open_with_max_file_size(&path, START_STRING, 200)?;
+        assert!(block_store.is_pruned(&hashes[1]));
+        assert_eq!(block_store.pruned_height(), 2);
+        assert!(block_store.read_block(&hashes[3])?.is_some());
+        let other_block = create_block(hashes[3], 6000);
+        assert_eq!(block_store.write_block(&other_block, 5)?.file, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_forgotten_pruned_block_is_stored_again() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("block_store_forget");
+        let path = directory.path_string();
+        let mut block_store = BlockStore::open_with_max_file_size(&path, START_STRING, 200)?;
+        block_store.set_prune_target(1);
+        let first_block = create_block([1; 32], 5000);
+        let second_block = create_block(first_block.hash(), 6000);
+        block_store.write_block(&first_block, 1)?;
+        block_store.write_block(&second_block, 2)?;
+        assert_eq!(block_store.prune(2)?, vec![first_block.hash()]);
+        block_store.forget_pruned_blocks();
+        assert!(!block_store.is_pruned(&first_block.hash()));
+        assert_eq!(block_store.pruned_height(), 0);
+        block_store.write_block(&first_block, 1)?;
+        assert!(block_store.read_block(&first_block.hash())?.is_some());
+        Ok(())
+    }
+
+    /// Creates the undo data of a block that spent an output with the received value
+    fn create_block_undo(value: i64) -> BlockUndo {
+        let mut block_undo = BlockUndo::new();
+        block_undo.add_spent_outputs(vec![SpentOutput::new(
+            Outpoint::new([3; 32], 1),
+            TxOut::new(value, CompactSizeUint::new(0), Vec::new()),
+            5,
+            false,
+        )]);
+        block_undo
+    }
+
+    #[test]
+    fn test_undo_data_is_read_after_the_store_is_opened_again() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("block_store_undo");
+        let path = directory.path_string();
+        let block = create_block([1; 32], 5000);
+        {
+            let mut block_store = BlockStore::open(&path, START_STRING)?;
+            block_store.write_block(&block, 1)?;
+            block_store.write_undo(&block.hash(), &create_block_undo(100))?;
+            // the undo data of a block connected again replaces the previous one
+            block_store.write_undo(&block.hash(), &create_block_undo(200))?;
+        }
+        assert!(directory.path().join("rev00000.dat").exists());
+        let mut block_store = BlockStore::open(&path, START_STRING)?;
+        assert!(block_store.has_undo(&block.hash()));
+        assert_eq!(
+            block_store.read_undo(&block.hash())?,
+            Some(create_block_undo(200))
+        );
+        block_store.remove_undo(&block.hash());
+        assert!(!block_store.has_undo(&block.hash()));
+        assert!(block_store.read_undo(&block.hash())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_data_is_pruned_with_the_file_of_its_block() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("block_store_undo_prune");
+        let path = directory.path_string();
+        let mut block_store = BlockStore::open_with_max_file_size(&path, START_STRING, 200)?;
+        block_store.set_prune_target(1);
+        let first_block = create_block([1; 32], 5000);
+        let second_block = create_block(first_block.hash(), 6000);
+        block_store.write_block(&first_block, 1)?;
+        block_store.write_undo(&first_block.hash(), &create_block_undo(100))?;
+        block_store.write_block(&second_block, 2)?;
+        block_store.write_undo(&second_block.hash(), &create_block_undo(200))?;
+        assert!(directory.path().join("rev00001.dat").exists());
+        assert_eq!(block_store.prune(2)?, vec![first_block.hash()]);
+        assert!(!directory.path().join("rev00000.dat").exists());
+        assert!(!block_store.has_undo(&first_block.hash()));
+        let block_store = BlockStore::open_with_max_file_size(&path, START_STRING, 200)?;
+        assert!(!block_store.has_undo(&first_block.hash()));
+        assert!(block_store.has_undo(&second_block.hash()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_data_with_invalid_checksum_is_not_read() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("block_store_undo_checksum");
+        let path = directory.path_string();
+        let block = create_block([1; 32], 5000);
+        let mut block_store = BlockStore::open(&path, START_STRING)?;
+        block_store.write_block(&block, 1)?;
+        block_store.write_undo(&block.hash(), &create_block_undo(100))?;
+        let undo_path = directory.path().join("rev00000.dat");
+        let mut undo_file = fs::read(&undo_path).unwrap_or_default();
+        undo_file[10] ^= 0xff;
+        let _ = fs::write(&undo_path, undo_file);
+        assert!(block_store.read_undo(&block.hash()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    compact_size_uint::CompactSizeUint,
+    transactions::{outpoint::Outpoint, tx_out::TxOut},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Represents an output spent by a transaction of a block, with the outpoint that references it,
+/// the height of the block that created it and if it was created by a coinbase.
+pub struct SpentOutput {
+    pub outpoint: Outpoint,
+    pub tx_out: TxOut,
+    pub height: usize,
+    pub is_coinbase: bool,
+}
+
+impl SpentOutput {
+    /// Creates a new SpentOutput with the received fields.
+    pub fn new(outpoint: Outpoint, tx_out: TxOut, height: usize, is_coinbase: bool) -> Self {
+        SpentOutput {
+            outpoint,
+            tx_out,
+            height,
+            is_coinbase,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Undo data of a block. Stores the outputs spent by its transactions, in the order in which
+/// they were spent, so that the block can be disconnected and the utxo set restored.
+pub struct BlockUndo {
+    pub spent_outputs: Vec<SpentOutput>,
+}
+
+impl BlockUndo {
+    /// Creates an empty BlockUndo.
+    pub fn new() -> Self {
+        BlockUndo {
+            spent_outputs: Vec::new(),
+        }
+    }
+
+    /// Adds the outputs spent by a transaction of the block.
+    pub fn add_spent_outputs(&mut self, spent_outputs: Vec<SpentOutput>) {
+        self.spent_outputs.extend(spent_outputs);
+    }
+
+    /// Marshalls the undo data to bytes and saves them in the vector received by parameter: the amount of
+    /// spent outputs and, for each one, its outpoint, the height (u32 little endian), 1 if it was created by
+    /// a coinbase or 0 if it was not, and the output.
+    pub fn marshalling(&self, bytes: &mut Vec<u8>) {
+        bytes.extend(CompactSizeUint::new(self.spent_outputs.len() as u128).marshalling());
+        for spent_output in &self.spent_outputs {
+            spent_output.outpoint.marshalling(bytes);
+            bytes.extend_from_slice(&(spent_output.height as u32).to_le_bytes());
+            bytes.push(spent_output.is_coinbase as u8);
+            spent_output.tx_out.marshalling(bytes);
+        }
+    }
+
+    /// Receives the bytes of undo data and the offset where it starts and returns the BlockUndo.
+    /// Updates the offset. Returns an error if the bytes are not enough.
+    pub fn unmarshalling(bytes: &Vec<u8>, offset: &mut usize) -> Result<BlockUndo, &'static str> {
+        let amount_of_spent_outputs =
+            CompactSizeUint::unmarshalling(bytes, offset)?.decoded_value();
+        let mut block_undo = BlockUndo::new();
+        for _ in 0..amount_of_spent_outputs {
+            let outpoint = Outpoint::unmarshalling(bytes, offset)?;
+            if bytes.len().saturating_sub(*offset) < 5 {
+                return Err("The bytes are not enough to unmarshall the undo data of a block");
+            }
+            let mut height_bytes = [0u8; 4];
+            height_bytes.copy_from_slice(&bytes[*offset..*offset + 4]);
+            let is_coinbase = bytes[*offset + 4] == 1;
+            *offset += 5;
+            let tx_out = TxOut::unmarshalling(bytes, offset)?;
+            block_undo.spent_outputs.push(SpentOutput::new(
+                outpoint,
+                tx_out,
+                u32::from_le_bytes(height_bytes) as usize,
+                is_coinbase,
+            ));
+        }
+        Ok(block_undo)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlockUndo, SpentOutput};
+    use crate::{
+        compact_size_uint::CompactSizeUint,
+        transactions::{outpoint::Outpoint, tx_out::TxOut},
+    };
+
+    #[test]
+    fn test_undo_data_is_the_same_after_marshalling_and_unmarshalling() -> Result<(), &'static str>
+    {
+        let mut block_undo = BlockUndo::new();
+        block_undo.add_spent_outputs(vec![
+            SpentOutput::new(
+                Outpoint::new([1; 32], 0),
+                TxOut::new(5000, CompactSizeUint::new(2), vec![0x51, 0x87]),
+                10,
+                true,
+            ),
+            SpentOutput::new(
+                Outpoint::new([2; 32], 3),
+                TxOut::new(700, CompactSizeUint::new(0), vec![]),
+                2_428_246,
+                false,
+            ),
+        ]);
+        let mut bytes: Vec<u8> = Vec::new();
+        block_undo.marshalling(&mut bytes);
+        let mut offset = 0;
+        assert_eq!(BlockUndo::unmarshalling(&bytes, &mut offset)?, block_undo);
+        assert_eq!(offset, bytes.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unmarshalling_incomplete_undo_data_returns_error() {
+        let mut block_undo = BlockUndo::new();
+        block_undo.add_spent_outputs(vec![SpentOutput::new(
+            Outpoint::new([1; 32], 0),
+            TxOut::new(5000, CompactSizeUint::new(0), vec![]),
+            10,
+            false,
+        )]);
+        let mut bytes: Vec<u8> = Vec::new();
+        block_undo.marshalling(&mut bytes);
+        bytes.truncate(40);
+        assert!(BlockUndo::unmarshalling(&bytes, &mut 0).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::ops::Add;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+/// Represents an amount of work as an unsigned number of 256 bits, the size of the targets of the headers, so that
+/// the work of any header and the cumulative work of any chain fit in it. The words are stored from the most
+/// significant one, so the amounts are compared word by word.
+pub struct ChainWork([u64; 4]);
+
+impl ChainWork {
+    /// Returns the work of a header with the received target: the expected number of hashes needed to find a hash
+    /// lower or equal to the target, 2^256 / (target + 1). Calculated as (!target / (target + 1)) + 1, as 2^256
+    /// does not fit in 256 bits. Returns 0 if the target is 0.
+    pub fn from_target(target: ChainWork) -> ChainWork {
+        if target == ChainWork::default() {
+            return ChainWork::default();
+        }
+        let divisor = target + ChainWork::from(1);
+        let dividend = ChainWork(target.0.map(|word| !word));
+        dividend.divide(divisor) + ChainWork::from(1)
+    }
+
+    /// Returns the target encoded in n_bits (the compact format of the headers), or None if it is negative or
+    /// does not fit in 256 bits.
+    pub fn target_from_n_bits(n_bits: u32) -> Option<ChainWork> {
+        let exponent = n_bits >> 24;
+        let mut mantissa = n_bits & 0x007fffff;
+        let is_negative = mantissa != 0 && n_bits & 0x00800000 != 0;
+        let overflows = mantissa != 0
+            && (exponent > 34
+                || (mantissa > 0xff && exponent > 33)
+                || (mantissa > 0xffff && exponent > 32));
+        if is_negative || overflows {
+            return None;
+        }
+        if exponent <= 3 {
+            mantissa >>= 8 * (3 - exponent);
+            return Some(ChainWork::from(mantissa as u128));
+        }
+        Some(ChainWork::from(mantissa as u128).shift_left(8 * (exponent - 3)))
+    }
+
+    /// Returns the amount shifted left by the received amount of bits, discarding the bits that do not fit.
+    fn shift_left(self, bits: u32) -> ChainWork {
+        let mut result = self;
+        for _ in 0..bits {
+            result = result.shift_left_one_bit().0;
+        }
+        result
+    }
+
+    /// Returns the amount shifted left by one bit and the most significant bit, that does not fit.
+    fn shift_left_one_bit(self) -> (ChainWork, bool) {
+        let mut words = [0; 4];
+        for (index, word) in self.0.iter().enumerate() {
+            let next_bit = self.0.get(index + 1).map(|next| next >> 63).unwrap_or(0);
+            words[index] = (word << 1) | next_bit;
+        }
+        (ChainWork(words), self.0[0] >> 63 == 1)
+    }
+
+    /// Returns the amount minus the received one, wrapping around 2^256.
+    fn wrapping_sub(self, other: ChainWork) -> ChainWork {
+        let mut words = [0; 4];
+        let mut borrow = false;
+        for index in (0..4).rev() {
+            let (difference, first_borrow) = self.0[index].overflowing_sub(other.0[index]);
+            let (difference, second_borrow) = difference.overflowing_sub(u64::from(borrow));
+            words[index] = difference;
+            borrow = first_borrow || second_borrow;
+        }
+        ChainWork(words)
+    }
+
+    /// Returns the quotient of the long division, bit by bit, of the amount by the received divisor.
+    /// The divisor must not be 0.
+    fn divide(self, divisor: ChainWork) -> ChainWork {
+        let mut quotient = ChainWork::default();
+        let mut remainder = ChainWork::default();
+        for bit in (0..256).rev() {
+            let (shifted, carry) = remainder.shift_left_one_bit();
+            remainder = shifted;
+            remainder.0[3] |= (self.0[3 - bit / 64] >> (bit % 64)) & 1;
+            quotient = quotient.shift_left_one_bit().0;
+            // if the remainder does not fit in 256 bits it is greater than the divisor
+            if carry || remainder >= divisor {
+                remainder = remainder.wrapping_sub(divisor);
+                quotient.0[3] |= 1;
+            }
+        }
+        quotient
+    }
+}
+
+impl From<u128> for ChainWork {
+    fn from(amount: u128) -> Self {
+        ChainWork([0, 0, (amount >> 64) as u64, amount as u64])
+    }
+}
+
+impl Add for ChainWork {
+    type Output = ChainWork;
+
+    /// Adds the amounts of work. As no chain has 2^256 hashes of work, the sum saturates at the maximum amount.
+    fn add(self, other: ChainWork) -> ChainWork {
+        let mut words = [0; 4];
+        let mut carry = false;
+        for index in (0..4).rev() {
+            let (sum, first_carry) = self.0[index].overflowing_add(other.0[index]);
+            let (sum, second_carry) = sum.overflowing_add(u64::from(carry));
+            words[index] = sum;
+            carry = first_carry || second_carry;
+        }
+        if carry {
+            return ChainWork([u64::MAX; 4]);
+        }
+        ChainWork(words)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ChainWork;
+
+    #[test]
+    fn test_target_from_n_bits_is_the_mantissa_shifted_by_the_exponent() {
+        assert_eq!(
+            ChainWork::target_from_n_bits(0x1d00ffff),
+            Some(ChainWork([0x00000000ffff0000, 0, 0, 0]))
+        );
+        assert_eq!(
+            ChainWork::target_from_n_bits(0x02008000),
+            Some(ChainWork::from(0x80))
+        );
+        // negative and overflowing targets
+        assert_eq!(ChainWork::target_from_n_bits(0x04923456), None);
+        assert_eq!(ChainWork::target_from_n_bits(0x23000001), None);
+    }
+
+    #[test]
+    fn test_work_of_the_minimum_target_does_not_fit_in_128_bits() {
+        // 2^256 / (1 + 1) = 2^255
+        let work = ChainWork::from_target(ChainWork::from(1));
+        assert_eq!(work, ChainWork([1 << 63, 0, 0, 0]));
+        assert!(work > ChainWork::from(u128::MAX));
+    }
+
+    #[test]
+    fn test_work_of_the_regtest_target_is_two() {
+        let target = ChainWork::target_from_n_bits(0x207fffff).unwrap_or_default();
+        assert_eq!(ChainWork::from_target(target), ChainWork::from(2));
+    }
+
+    #[test]
+    fn test_sum_carries_to_the_next_word() {
+        assert_eq!(
+            ChainWork::from(u128::MAX) + ChainWork::from(1),
+            ChainWork([0, 1, 0, 0])
+        );
+        assert_eq!(
+            ChainWork([u64::MAX; 4]) + ChainWork::from(1),
+            ChainWork([u64::MAX; 4])
+        );
+    }
+}
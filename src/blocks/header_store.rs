@@ -0,0 +1,321 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use bitcoin_hashes::{sha256d, Hash};
+
+use super::block_header::BlockHeader;
+use crate::custom_errors::NodeCustomErrors;
+
+const HEADER_SIZE: usize = 80;
+const CHECKSUM_SIZE: usize = 4;
+/// Amount of headers (u64 little endian), hash of the last one and checksum
+const TIP_MARKER_SIZE: usize = 8 + 32 + CHECKSUM_SIZE;
+
+#[derive(Debug)]
+/// Stores the headers of the active chain, without the genesis, appended one after the other in 80 byte
+/// records. Next to the file there is a tip marker (the same path ending in .tip) with the amount of headers
+/// and the hash of the last one, protected by a checksum, which is replaced after each write. When the store
+/// is opened, a record that was not completely written is removed, and the records after the tip marker are
+/// kept only while they are linked to the previous header and have a valid proof of work.
+pub struct HeaderStore {
+    path: PathBuf,
+    tip_path: PathBuf,
+    amount_of_headers: usize,
+}
+
+impl HeaderStore {
+    /// Opens the header store of the received path, creating the file if it does not exist, and returns it with
+    /// the stored headers. The first header must be the child of the genesis block with the received hash.
+    /// Returns an error if the file can not be read or repaired.
+    pub fn open(
+        path: &str,
+        genesis_hash: [u8; 32],
+    ) -> Result<(HeaderStore, Vec<BlockHeader>), NodeCustomErrors> {
+        let mut header_store = HeaderStore {
+            path: PathBuf::from(path),
+            tip_path: PathBuf::from(format!("{}.tip", path)),
+            amount_of_headers: 0,
+        };
+        let mut data: Vec<u8> = Vec::new();
+        OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&header_store.path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        for record in data.chunks_exact(HEADER_SIZE) {
+            match BlockHeader::unmarshalling(record, &mut 0) {
+                Ok(header) => headers.push(header),
+                Err(_) => break,
+            }
+        }
+        let trusted_headers = header_store.read_tip_marker(&headers);
+        let mut previous_hash = match trusted_headers {
+            0 => genesis_hash,
+            amount => headers[amount - 1].hash(),
+        };
+        let mut valid_headers = trusted_headers;
+        for header in &headers[trusted_headers..] {
+            if header.previous_block_header_hash != previous_hash || !header.validate() {
+                break;
+            }
+            previous_hash = header.hash();
+            valid_headers += 1;
+        }
+        headers.truncate(valid_headers);
+        header_store.amount_of_headers = valid_headers;
+        if valid_headers * HEADER_SIZE != data.len() {
+            header_store.set_file_length(valid_headers)?;
+        }
+        if valid_headers != trusted_headers || valid_headers * HEADER_SIZE != data.len() {
+            header_store.write_tip_marker(headers.last().map(|header| header.hash()))?;
+        }
+        Ok((header_store, headers))
+    }
+
+    /// Returns the amount of headers of the tip marker if it is valid and its hash is the one of the header in
+    /// that position, so those headers were completely written. Otherwise returns 0.
+    fn read_tip_marker(&self, headers: &[BlockHeader]) -> usize {
+        let marker = match fs::read(&self.tip_path) {
+            Ok(marker) if marker.len() == TIP_MARKER_SIZE => marker,
+            _ => return 0,
+        };
+        if marker[TIP_MARKER_SIZE - CHECKSUM_SIZE..]
+            != checksum(&marker[..TIP_MARKER_SIZE - CHECKSUM_SIZE])
+        {
+            return 0;
+        }
+        let mut amount_bytes = [0u8; 8];
+        amount_bytes.copy_from_slice(&marker[..8]);
+        let amount = u64::from_le_bytes(amount_bytes) as usize;
+        match amount.checked_sub(1).and_then(|index| headers.get(index)) {
+            Some(header) if header.hash()[..] == marker[8..40] => amount,
+            _ => 0,
+        }
+    }
+
+    /// Replaces the tip marker with the amount of headers and the hash of the last one. It is written in a
+    /// temporary file that is renamed, so the marker is never partially written.
+    fn write_tip_marker(&self, tip_hash: Option<[u8; 32]>) -> Result<(), NodeCustomErrors> {
+        let mut marker: Vec<u8> = Vec::new();
+        marker.extend_from_slice(&(self.amount_of_headers as u64).to_le_bytes());
+        marker.extend_from_slice(&tip_hash.unwrap_or([0; 32]));
+        let marker_checksum = checksum(&marker);
+        marker.extend_from_slice(&marker_checksum);
+        let temporary_path = PathBuf::from(format!("{}.tmp", self.tip_path.display()));
+        let mut file = File::create(&temporary_path)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        file.write_all(&marker)
+            .and_then(|_| file.sync_all())
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        fs::rename(&temporary_path, &self.tip_path)
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))
+    }
+
+    /// Changes the size of the file to the one of the received amount of headers.
+    fn set_file_length(&self, amount_of_headers: usize) -> Result<(), NodeCustomErrors> {
+        OpenOptions::new()
+            .write(true)
+            .open(&self.path)
+            .and_then(|file| file.set_len((amount_of_headers * HEADER_SIZE) as u64))
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))
+    }
+
+    /// Appends the headers at the end of the file and updates the tip marker.
+    /// Returns an error if they can not be written.
+    pub fn append(&mut self, headers: &[BlockHeader]) -> Result<(), NodeCustomErrors> {
+        let last_header = match headers.last() {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        let mut bytes: Vec<u8> = Vec::new();
+        for header in headers {
+            header.marshalling(&mut bytes);
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_data())
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        self.amount_of_headers += headers.len();
+        self.write_tip_marker(Some(last_header.hash()))
+    }
+
+    /// Removes the headers after the received amount, when the active chain is reorganized.
+    /// Returns an error if the file can not be written.
+    pub fn truncate(&mut self, amount_of_headers: usize) -> Result<(), NodeCustomErrors> {
+        if amount_of_headers >= self.amount_of_headers {
+            return Ok(());
+        }
+        self.set_file_length(amount_of_headers)?;
+        self.amount_of_headers = amount_of_headers;
+        let tip_hash = match amount_of_headers {
+            0 => None,
+            amount => Some(self.read_header(amount - 1)?.hash()),
+        };
+        self.write_tip_marker(tip_hash)
+    }
+
+    /// Reads the header stored in the received position.
+    fn read_header(&self, index: usize) -> Result<BlockHeader, NodeCustomErrors> {
+        let mut record = [0u8; HEADER_SIZE];
+        let mut file = File::open(&self.path)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        file.seek(SeekFrom::Start((index * HEADER_SIZE) as u64))
+            .and_then(|_| file.read_exact(&mut record))
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        BlockHeader::unmarshalling(&record, &mut 0)
+            .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))
+    }
+
+    /// Returns the amount of headers stored.
+    pub fn len(&self) -> usize {
+        self.amount_of_headers
+    }
+
+    /// Returns true if there are no headers stored.
+    pub fn is_empty(&self) -> bool {
+        self.amount_of_headers == 0
+    }
+}
+
+/// Returns the first 4 bytes of the double sha256 of the received bytes.
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let mut checksum = [0u8; CHECKSUM_SIZE];
+    checksum.copy_from_slice(&sha256d::Hash::hash(bytes)[..CHECKSUM_SIZE]);
+    checksum
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{HeaderStore, HEADER_SIZE};
+    use crate::{
+        blocks::block_header::BlockHeader, custom_errors::NodeCustomErrors,
+        temp_directory::TempDirectory,
+    };
+
+    const GENESIS_HASH: [u8; 32] = [1; 32];
+
+    /// Creates a chain of headers after the genesis with a valid proof of work
+    fn create_headers(amount: usize) -> Vec<BlockHeader> {
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        let mut previous_hash = GENESIS_HASH;
+        for index in 0..amount {
+            let mut header =
+                BlockHeader::new(1, previous_hash, [2; 32], index as u32, 0x207fffff, 0);
+            while !header.validate() {
+                header.nonce += 1;
+            }
+            previous_hash = header.hash();
+            headers.push(header);
+        }
+        headers
+    }
+
+    #[test]
+    fn test_appended_headers_are_read_when_the_store_is_opened_again(
+    ) -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("header_store_reopen");
+        let path = directory.path().join("headers.dat");
+        let path_str = path.to_string_lossy().to_string();
+        let headers = create_headers(5);
+        {
+            let (mut header_store, stored_headers) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+            assert!(stored_headers.is_empty());
+            header_store.append(&headers[..3])?;
+            header_store.append(&headers[3..])?;
+            assert_eq!(header_store.len(), 5);
+        }
+        let (header_store, stored_headers) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+        assert_eq!(header_store.len(), 5);
+        assert_eq!(stored_headers, headers);
+        assert_eq!(
+            fs::metadata(&path).map(|metadata| metadata.len()).ok(),
+            Some(400)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_that_was_not_completely_written_is_removed() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("header_store_torn");
+        let path = directory.path().join("headers.dat");
+        let path_str = path.to_string_lossy().to_string();
+        let headers = create_headers(3);
+        {
+            let (mut header_store, _) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+            header_store.append(&headers[..2])?;
+        }
+        // the node stopped while writing the third header
+        let mut bytes: Vec<u8> = Vec::new();
+        headers[2].marshalling(&mut bytes);
+        let mut data = fs::read(&path).unwrap_or_default();
+        data.extend_from_slice(&bytes[..50]);
+        let _ = fs::write(&path, data);
+
+        let (mut header_store, stored_headers) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+        assert_eq!(stored_headers, headers[..2].to_vec());
+        assert_eq!(
+            fs::metadata(&path).map(|metadata| metadata.len()).ok(),
+            Some(2 * HEADER_SIZE as u64)
+        );
+        header_store.append(&headers[2..])?;
+        let (_, stored_headers) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+        assert_eq!(stored_headers, headers);
+        Ok(())
+    }
+
+    #[test]
+    fn test_headers_after_the_tip_marker_are_kept_only_if_they_are_linked(
+    ) -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("header_store_unlinked");
+        let path = directory.path().join("headers.dat");
+        let path_str = path.to_string_lossy().to_string();
+        let headers = create_headers(4);
+        {
+            let (mut header_store, _) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+            header_store.append(&headers[..2])?;
+        }
+        // the third header was written but not the tip marker, and then a header of other chain
+        let mut data = fs::read(&path).unwrap_or_default();
+        headers[2].marshalling(&mut data);
+        headers[0].marshalling(&mut data);
+        let _ = fs::write(&path, data);
+
+        let (header_store, stored_headers) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+        assert_eq!(header_store.len(), 3);
+        assert_eq!(stored_headers, headers[..3].to_vec());
+        let (_, stored_headers) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+        assert_eq!(stored_headers, headers[..3].to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_headers_are_removed_from_the_store() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("header_store_truncate");
+        let path = directory.path().join("headers.dat");
+        let path_str = path.to_string_lossy().to_string();
+        let headers = create_headers(4);
+        let (mut header_store, _) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+        header_store.append(&headers)?;
+        header_store.truncate(2)?;
+        assert_eq!(header_store.len(), 2);
+        let (_, stored_headers) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+        assert_eq!(stored_headers, headers[..2].to_vec());
+        header_store.truncate(0)?;
+        let (header_store, stored_headers) = HeaderStore::open(&path_str, GENESIS_HASH)?;
+        assert!(header_store.is_empty());
+        assert!(stored_headers.is_empty());
+        Ok(())
+    }
+}
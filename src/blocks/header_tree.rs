@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use super::{block_header::BlockHeader, chainwork::ChainWork};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Represents a header that is not part of the active chain, with its height and the
+/// cumulative chainwork of the branch up to it.
+pub struct HeaderNode {
+    pub header: BlockHeader,
+    pub height: usize,
+    pub chainwork: ChainWork,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Stores the cumulative chainwork of each header of the active chain (by height) and the headers
+/// of the competing branches indexed by hash, so that the chain with the most work can be selected.
+/// The headers of the active chain are stored in the headers list of the blockchain.
+pub struct HeaderTree {
+    active_chainwork: Vec<ChainWork>,
+    side_headers: HashMap<[u8; 32], HeaderNode>,
+}
+
+impl HeaderTree {
+    /// Creates an empty HeaderTree.
+    pub fn new() -> Self {
+        HeaderTree {
+            active_chainwork: Vec::new(),
+            side_headers: HashMap::new(),
+        }
+    }
+
+    /// Calculates the cumulative chainwork of the headers of the active chain that were not processed yet.
+    /// Receives the complete list of headers of the active chain.
+    pub fn sync_active_chain(&mut self, headers: &[BlockHeader]) {
+        if self.active_chainwork.len() > headers.len() {
+            self.active_chainwork.truncate(headers.len());
+        }
+        for header in &headers[self.active_chainwork.len()..] {
+            let previous_chainwork = self.active_chainwork.last().copied().unwrap_or_default();
+            self.active_chainwork
+                .push(previous_chainwork + header.work());
+        }
+    }
+
+    /// Removes the chainwork of the headers of the active chain above the received height.
+    pub fn truncate_active_chain(&mut self, height: usize) {
+        self.active_chainwork.truncate(height + 1);
+    }
+
+    /// Returns the cumulative chainwork of the header of the active chain at the received height.
+    pub fn active_chainwork(&self, height: usize) -> Option<ChainWork> {
+        self.active_chainwork.get(height).copied()
+    }
+
+    /// Returns the cumulative chainwork of the tip of the active chain.
+    pub fn tip_chainwork(&self) -> ChainWork {
+        self.active_chainwork.last().copied().unwrap_or_default()
+    }
+
+    /// Returns true if the header with the received hash is in one of the competing branches.
+    pub fn contains_side_header(&self, hash: &[u8; 32]) -> bool {
+        self.side_headers.contains_key(hash)
+    }
+
+    /// Returns the node of the competing branches with the received hash.
+    pub fn get_side_header(&self, hash: &[u8; 32]) -> Option<&HeaderNode> {
+        self.side_headers.get(hash)
+    }
+
+    /// Adds a header to the competing branches. Receives the height and the chainwork of its parent.
+    /// Returns the node created.
+    pub fn insert_side_header(
+        &mut self,
+        header: BlockHeader,
+        parent_height: usize,
+        parent_chainwork: ChainWork,
+    ) -> HeaderNode {
+        let node = HeaderNode {
+            header,
+            height: parent_height + 1,
+            chainwork: parent_chainwork + header.work(),
+        };
+        self.side_headers.insert(header.hash(), node);
+        node
+    }
+
+    /// Removes the header with the received hash from the competing branches.
+    pub fn remove_side_header(&mut self, hash: &[u8; 32]) -> Option<HeaderNode> {
+        self.side_headers.remove(hash)
+    }
+
+    /// Returns the height of the header of the active chain where the branch that ends in tip_hash forks
+    /// and the headers of the branch from the fork (excluded) to the tip, in ascending order.
+    /// Returns None if tip_hash is not in a competing branch or the branch does not connect with the active chain.
+    pub fn branch_to_active_chain(
+        &self,
+        tip_hash: &[u8; 32],
+        header_heights: &HashMap<[u8; 32], usize>,
+    ) -> Option<(usize, Vec<BlockHeader>)> {
+        let mut branch: Vec<BlockHeader> = Vec::new();
+        let mut current = self.side_headers.get(tip_hash)?;
+        loop {
+            branch.push(current.header);
+            let parent_hash = current.header.previous_block_header_hash;
+            if let Some(parent_height) = header_heights.get(&parent_hash) {
+                branch.reverse();
+                return Some((*parent_height, branch));
+            }
+            current = self.side_headers.get(&parent_hash)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::HeaderTree;
+    use crate::blocks::{block_header::BlockHeader, chainwork::ChainWork};
+
+    const N_BITS: u32 = 0x1d00ffff;
+    const WORK: u128 = 0x100010001;
+
+    /// Creates a chain of headers that starts from the received hash.
+    fn create_chain(previous_hash: [u8; 32], amount: usize, nonce: u32) -> Vec<BlockHeader> {
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        let mut previous_hash = previous_hash;
+        for _ in 0..amount {
+            let header = BlockHeader::new(1, previous_hash, [0; 32], 0, N_BITS, nonce);
+            previous_hash = header.hash();
+            headers.push(header);
+        }
+        headers
+    }
+
+    fn create_header_heights(headers: &[BlockHeader]) -> HashMap<[u8; 32], usize> {
+        headers
+            .iter()
+            .enumerate()
+            .map(|(height, header)| (header.hash(), height))
+            .collect()
+    }
+
+    #[test]
+    fn test_chainwork_of_active_chain_is_cumulative() {
+        let headers = create_chain([0; 32], 5, 0);
+        let mut header_tree = HeaderTree::new();
+        header_tree.sync_active_chain(&headers[..3]);
+        assert_eq!(header_tree.tip_chainwork(), ChainWork::from(3 * WORK));
+        header_tree.sync_active_chain(&headers);
+        assert_eq!(header_tree.tip_chainwork(), ChainWork::from(5 * WORK));
+        assert_eq!(
+            header_tree.active_chainwork(1),
+            Some(ChainWork::from(2 * WORK))
+        );
+        header_tree.truncate_active_chain(1);
+        assert_eq!(header_tree.tip_chainwork(), ChainWork::from(2 * WORK));
+    }
+
+    #[test]
+    fn test_side_header_adds_its_work_to_the_chainwork_of_the_parent() {
+        let headers = create_chain([0; 32], 3, 0);
+        let mut header_tree = HeaderTree::new();
+        header_tree.sync_active_chain(&headers);
+        let side_header = create_chain(headers[1].hash(), 1, 1)[0];
+        let node = header_tree.insert_side_header(
+            side_header,
+            1,
+            header_tree.active_chainwork(1).unwrap_or_default(),
+        );
+        assert_eq!(node.height, 2);
+        assert_eq!(node.chainwork, ChainWork::from(3 * WORK));
+        assert!(header_tree.contains_side_header(&side_header.hash()));
+    }
+
+    #[test]
+    fn test_branch_to_active_chain_returns_the_fork_height_and_the_headers_of_the_branch() {
+        let headers = create_chain([0; 32], 4, 0);
+        let header_heights = create_header_heights(&headers);
+        let mut header_tree = HeaderTree::new();
+        header_tree.sync_active_chain(&headers);
+        let branch = create_chain(headers[1].hash(), 3, 1);
+        let mut parent_height = 1;
+        let mut parent_chainwork = header_tree.active_chainwork(1).unwrap_or_default();
+        for header in &branch {
+            let node = header_tree.insert_side_header(*header, parent_height, parent_chainwork);
+            parent_height = node.height;
+            parent_chainwork = node.chainwork;
+        }
+        assert!(parent_chainwork > header_tree.tip_chainwork());
+        assert_eq!(
+            header_tree.branch_to_active_chain(&branch[2].hash(), &header_heights),
+            Some((1, branch))
+        );
+    }
+
+    #[test]
+    fn test_branch_that_does_not_connect_with_the_active_chain_returns_none() {
+        let headers = create_chain([0; 32], 2, 0);
+        let header_heights = create_header_heights(&headers);
+        let mut header_tree = HeaderTree::new();
+        let orphan = create_chain([9; 32], 1, 1)[0];
+        header_tree.insert_side_header(orphan, 5, ChainWork::default());
+        assert_eq!(
+            header_tree.branch_to_active_chain(&orphan.hash(), &header_heights),
+            None
+        );
+    }
+}
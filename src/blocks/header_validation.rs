@@ -0,0 +1,433 @@
+use super::block_header::BlockHeader;
+use crate::chain_params::ChainParams;
+
+/// Amount of previous blocks used to calculate the median time past.
+const MEDIAN_TIME_SPAN: usize = 11;
+/// Maximum time that the timestamp of a header can be ahead of the current time: two hours.
+pub const MAX_FUTURE_BLOCK_TIME: i64 = 2 * 60 * 60;
+
+/// View of a chain of headers ordered by height: the headers of the active chain up to
+/// the point where the branch forks, followed by the headers of the branch.
+/// Allows validating headers of the active chain and of competing branches without copying the active chain.
+pub struct HeaderChain<'a> {
+    active_chain: &'a [BlockHeader],
+    branch: &'a [BlockHeader],
+}
+
+impl<'a> HeaderChain<'a> {
+    /// Creates a new HeaderChain. The active chain must contain the headers from the genesis
+    /// to the fork (included) and the branch the headers after the fork, in ascending order.
+    pub fn new(active_chain: &'a [BlockHeader], branch: &'a [BlockHeader]) -> Self {
+        HeaderChain {
+            active_chain,
+            branch,
+        }
+    }
+
+    /// Returns the header of the chain at the received height.
+    pub fn get(&self, height: usize) -> Option<&BlockHeader> {
+        if height < self.active_chain.len() {
+            return self.active_chain.get(height);
+        }
+        self.branch.get(height - self.active_chain.len())
+    }
+
+    /// Returns the height of the last header of the chain, None if it is empty.
+    pub fn tip_height(&self) -> Option<usize> {
+        (self.active_chain.len() + self.branch.len()).checked_sub(1)
+    }
+
+    /// Returns the median of the timestamps of the last 11 headers of the chain.
+    pub fn median_time_past(&self) -> u32 {
+        match self.tip_height() {
+            Some(tip_height) => self.median_time_past_at(tip_height),
+            None => 0,
+        }
+    }
+
+    /// Returns the median of the timestamps of the 11 headers of the chain that end at the received height.
+    pub fn median_time_past_at(&self, height: usize) -> u32 {
+        let first_height = (height + 1).saturating_sub(MEDIAN_TIME_SPAN);
+        let mut times: Vec<u32> = (first_height..=height)
+            .filter_map(|height| self.get(height))
+            .map(|header| header.time)
+            .collect();
+        times.sort_unstable();
+        times.get(times.len() / 2).copied().unwrap_or(0)
+    }
+
+    /// Returns the n_bits that the next header of the chain must have, which has the received timestamp, according to
+    /// the difficulty adjustment rules of the chain params. Every 2016 blocks the target is adjusted according to the
+    /// time it took to mine the previous 2016 blocks, unless the network does not retarget (regtest).
+    /// In testnet, if the header is 20 minutes newer than its parent it can have the minimum difficulty, and otherwise
+    /// it must have the n_bits of the last block of the chain that does not have the minimum difficulty.
+    pub fn next_work_required(&self, new_header_time: u32, params: &ChainParams) -> Option<u32> {
+        let tip_height = self.tip_height()?;
+        let tip = self.get(tip_height)?;
+        let interval = params.difficulty_adjustment_interval();
+        if (tip_height + 1) % interval != 0 {
+            if params.allow_min_difficulty_blocks {
+                if new_header_time > tip.time.saturating_add(params.pow_target_spacing * 2) {
+                    return Some(params.pow_limit_bits);
+                }
+                let mut height = tip_height;
+                while height > 0
+                    && height % interval != 0
+                    && self.get(height)?.n_bits == params.pow_limit_bits
+                {
+                    height -= 1;
+                }
+                return Some(self.get(height)?.n_bits);
+            }
+            return Some(tip.n_bits);
+        }
+        if params.no_retargeting {
+            return Some(tip.n_bits);
+        }
+        let first = self.get(tip_height + 1 - interval)?;
+        Some(calculate_next_work_required(
+            tip.n_bits, first.time, tip.time, params,
+        ))
+    }
+}
+
+/// Calculates the n_bits of the target after a difficulty adjustment. Receives the n_bits of the last block of
+/// the period, the timestamps of the first and the last block and the chain params. The target is multiplied by
+/// the time it took to mine the period divided by two weeks, limiting the adjustment to a factor of 4 and to the
+/// proof of work limit of the network.
+pub fn calculate_next_work_required(
+    last_bits: u32,
+    first_time: u32,
+    last_time: u32,
+    params: &ChainParams,
+) -> u32 {
+    let target_timespan = params.pow_target_timespan;
+    let actual_timespan = (last_time as i64 - first_time as i64)
+        .clamp((target_timespan / 4) as i64, (target_timespan * 4) as i64)
+        as u64;
+    let pow_limit = match compact_to_target(params.pow_limit_bits) {
+        Some(pow_limit) => pow_limit,
+        None => return params.pow_limit_bits,
+    };
+    let new_target = match compact_to_target(last_bits)
+        .and_then(|target| multiply_target(&target, actual_timespan))
+    {
+        Some(target) => divide_target(&target, target_timespan as u64),
+        None => return params.pow_limit_bits,
+    };
+    if new_target > pow_limit {
+        return params.pow_limit_bits;
+    }
+    target_to_compact(&new_target)
+}
+
+/// Validates the header as the next header of the chain received: it must reference the last header of the chain,
+/// have the n_bits expected by the difficulty adjustment rules of the chain params, a timestamp greater than the
+/// median time past of the chain and no more than two hours ahead of the current time (in seconds since epoch).
+/// Returns an error with the reason if it is not valid.
+pub fn validate_header_context(
+    header: &BlockHeader,
+    chain: &HeaderChain,
+    params: &ChainParams,
+    current_time: i64,
+) -> Result<(), &'static str> {
+    let tip = chain
+        .tip_height()
+        .and_then(|tip_height| chain.get(tip_height))
+        .ok_or("The chain of the header is empty")?;
+    if header.previous_block_header_hash != tip.hash() {
+        return Err("The header does not reference the last header of its chain");
+    }
+    if chain.next_work_required(header.time, params) != Some(header.n_bits) {
+        return Err("The n_bits of the header do not match the difficulty adjustment");
+    }
+    if header.time <= chain.median_time_past() {
+        return Err("The timestamp of the header is not greater than the median time past");
+    }
+    if header.time as i64 > current_time + MAX_FUTURE_BLOCK_TIME {
+        return Err("The timestamp of the header is more than two hours in the future");
+    }
+    Ok(())
+}
+
+/// Converts the n_bits of a header to the target it represents, in big endian.
+/// Returns None if the target does not fit in 256 bits.
+fn compact_to_target(n_bits: u32) -> Option<[u8; 32]> {
+    let size = (n_bits >> 24) as usize;
+    let mut mantissa = n_bits & 0x007fffff;
+    let mut target = [0; 32];
+    if size <= 3 {
+        mantissa >>= 8 * (3 - size);
+        target[29..].copy_from_slice(&mantissa.to_be_bytes()[1..]);
+        return Some(target);
+    }
+    let mantissa_bytes = &mantissa.to_be_bytes()[1..];
+    for (i, byte) in mantissa_bytes.iter().enumerate() {
+        // position of the byte counting from the most significant byte of the target
+        let position = (32 + i).checked_sub(size);
+        match position {
+            Some(position) => target[position] = *byte,
+            None if *byte != 0 => return None,
+            None => {}
+        }
+    }
+    Some(target)
+}
+
+/// Converts a target in big endian to its compact representation (n_bits).
+fn target_to_compact(target: &[u8; 32]) -> u32 {
+    let first_non_zero = match target.iter().position(|byte| *byte != 0) {
+        Some(position) => position,
+        None => return 0,
+    };
+    let mut size = (32 - first_non_zero) as u32;
+    let mut mantissa: u32 = 0;
+    for i in 0..3 {
+        mantissa <<= 8;
+        mantissa |= *target.get(first_non_zero + i).unwrap_or(&0) as u32;
+    }
+    // The mantissa is signed, so if its highest bit is set it is shifted a byte
+    if mantissa & 0x00800000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    mantissa | (size << 24)
+}
+
+/// Multiplies a target in big endian by a factor. Returns None if the result does not fit in 256 bits.
+fn multiply_target(target: &[u8; 32], factor: u64) -> Option<[u8; 32]> {
+    let mut result = [0; 32];
+    let mut carry: u128 = 0;
+    for i in (0..32).rev() {
+        let product = target[i] as u128 * factor as u128 + carry;
+        result[i] = (product & 0xff) as u8;
+        carry = product >> 8;
+    }
+    if carry != 0 {
+        return None;
+    }
+    Some(result)
+}
+
+/// Divides a target in big endian by a divisor.
+fn divide_target(target: &[u8; 32], divisor: u64) -> [u8; 32] {
+    let mut result = [0; 32];
+    let mut remainder: u128 = 0;
+    for i in 0..32 {
+        let dividend = (remainder << 8) | target[i] as u128;
+        result[i] = (dividend / divisor as u128) as u8;
+        remainder = dividend % divisor as u128;
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chain_params::{REGTEST_PARAMS, TESTNET_PARAMS};
+
+    /// Creates a chain of headers with the received n_bits, separated by the received amount of seconds.
+    fn create_chain(amount: usize, n_bits: u32, spacing: u32) -> Vec<BlockHeader> {
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        let mut previous_hash = [0; 32];
+        for i in 0..amount {
+            let header = BlockHeader::new(
+                1,
+                previous_hash,
+                [0; 32],
+                1_600_000_000 + i as u32 * spacing,
+                n_bits,
+                0,
+            );
+            previous_hash = header.hash();
+            headers.push(header);
+        }
+        headers
+    }
+
+    fn create_next_header(chain: &HeaderChain, time: u32, n_bits: u32) -> BlockHeader {
+        let tip = chain.get(chain.tip_height().unwrap_or(0)).copied();
+        let previous_hash = tip.map(|tip| tip.hash()).unwrap_or([0; 32]);
+        BlockHeader::new(1, previous_hash, [0; 32], time, n_bits, 0)
+    }
+
+    #[test]
+    fn test_compact_to_target_and_back_returns_the_same_n_bits() {
+        for n_bits in [
+            0x1d00ffff, 0x1c05a3f4, 0x1b0404cb, 0x1a01aa3d, 0x04123456, 0x01120000,
+        ] {
+            let target = compact_to_target(n_bits).unwrap_or([0; 32]);
+            assert_eq!(target_to_compact(&target), n_bits);
+        }
+    }
+
+    #[test]
+    fn test_next_work_required_is_calculated_correctly() {
+        assert_eq!(
+            calculate_next_work_required(0x1d00ffff, 1261130161, 1262152739, &TESTNET_PARAMS),
+            0x1d00d86a
+        );
+    }
+
+    #[test]
+    fn test_next_work_required_is_limited_by_the_pow_limit() {
+        assert_eq!(
+            calculate_next_work_required(0x1d00ffff, 1231006505, 1233061996, &TESTNET_PARAMS),
+            0x1d00ffff
+        );
+    }
+
+    #[test]
+    fn test_next_work_required_is_limited_to_a_quarter_of_the_timespan() {
+        assert_eq!(
+            calculate_next_work_required(0x1c05a3f4, 1279008237, 1279297671, &TESTNET_PARAMS),
+            0x1c0168fd
+        );
+    }
+
+    #[test]
+    fn test_next_work_required_is_limited_to_four_times_the_timespan() {
+        assert_eq!(
+            calculate_next_work_required(0x1c387f6f, 1263163443, 1269211443, &TESTNET_PARAMS),
+            0x1d00e1fd
+        );
+    }
+
+    #[test]
+    fn test_header_with_the_same_n_bits_as_its_parent_is_valid() {
+        let headers = create_chain(10, 0x1c05a3f4, 600);
+        let chain = HeaderChain::new(&headers, &[]);
+        let header = create_next_header(&chain, headers[9].time + 600, 0x1c05a3f4);
+        assert_eq!(
+            validate_header_context(&header, &chain, &TESTNET_PARAMS, header.time as i64),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_header_with_an_easier_target_is_invalid() {
+        let headers = create_chain(10, 0x1c05a3f4, 600);
+        let chain = HeaderChain::new(&headers, &[]);
+        let header = create_next_header(&chain, headers[9].time + 600, 0x1c05a3f5);
+        assert!(
+            validate_header_context(&header, &chain, &TESTNET_PARAMS, header.time as i64).is_err()
+        );
+    }
+
+    #[test]
+    fn test_testnet_header_20_minutes_after_its_parent_can_have_the_minimum_difficulty() {
+        let headers = create_chain(10, 0x1c05a3f4, 600);
+        let chain = HeaderChain::new(&headers, &[]);
+        let header = create_next_header(
+            &chain,
+            headers[9].time + 1201,
+            TESTNET_PARAMS.pow_limit_bits,
+        );
+        assert_eq!(
+            validate_header_context(&header, &chain, &TESTNET_PARAMS, header.time as i64),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_header_after_minimum_difficulty_blocks_must_have_the_last_real_difficulty() {
+        let mut headers = create_chain(10, 0x1c05a3f4, 600);
+        headers[8].n_bits = TESTNET_PARAMS.pow_limit_bits;
+        headers[9].n_bits = TESTNET_PARAMS.pow_limit_bits;
+        let chain = HeaderChain::new(&headers, &[]);
+        assert_eq!(
+            chain.next_work_required(headers[9].time + 600, &TESTNET_PARAMS),
+            Some(0x1c05a3f4)
+        );
+    }
+
+    #[test]
+    fn test_header_that_does_not_reference_the_tip_is_invalid() {
+        let headers = create_chain(10, 0x1c05a3f4, 600);
+        let chain = HeaderChain::new(&headers, &[]);
+        let mut header = create_next_header(&chain, headers[9].time + 600, 0x1c05a3f4);
+        header.previous_block_header_hash = headers[8].hash();
+        assert!(
+            validate_header_context(&header, &chain, &TESTNET_PARAMS, header.time as i64).is_err()
+        );
+    }
+
+    #[test]
+    fn test_header_older_than_the_median_time_past_is_invalid() {
+        let headers = create_chain(11, 0x1c05a3f4, 600);
+        let chain = HeaderChain::new(&headers, &[]);
+        assert_eq!(chain.median_time_past(), headers[5].time);
+        let header = create_next_header(&chain, headers[5].time, 0x1c05a3f4);
+        assert!(
+            validate_header_context(&header, &chain, &TESTNET_PARAMS, header.time as i64).is_err()
+        );
+    }
+
+    #[test]
+    fn test_median_time_past_at_a_height_uses_the_headers_up_to_it() {
+        let headers = create_chain(20, 0x1c05a3f4, 600);
+        let chain = HeaderChain::new(&headers, &[]);
+        assert_eq!(chain.median_time_past_at(15), headers[10].time);
+        assert_eq!(chain.median_time_past_at(2), headers[1].time);
+        assert_eq!(chain.median_time_past_at(19), chain.median_time_past());
+    }
+
+    #[test]
+    fn test_header_more_than_two_hours_in_the_future_is_invalid() {
+        let headers = create_chain(10, 0x1c05a3f4, 600);
+        let chain = HeaderChain::new(&headers, &[]);
+        let header = create_next_header(&chain, headers[9].time + 600, 0x1c05a3f4);
+        let current_time = header.time as i64 - MAX_FUTURE_BLOCK_TIME - 1;
+        assert!(validate_header_context(&header, &chain, &TESTNET_PARAMS, current_time).is_err());
+    }
+
+    #[test]
+    fn test_header_of_a_branch_is_validated_against_the_branch() {
+        let headers = create_chain(10, 0x1c05a3f4, 600);
+        let branch = vec![BlockHeader::new(
+            1,
+            headers[5].hash(),
+            [1; 32],
+            headers[5].time + 600,
+            0x1c05a3f4,
+            0,
+        )];
+        let chain = HeaderChain::new(&headers[..6], &branch);
+        assert_eq!(chain.tip_height(), Some(6));
+        let header = create_next_header(&chain, branch[0].time + 600, 0x1c05a3f4);
+        assert_eq!(header.previous_block_header_hash, branch[0].hash());
+        assert_eq!(
+            validate_header_context(&header, &chain, &TESTNET_PARAMS, header.time as i64),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_retarget_uses_the_timestamps_of_the_period() {
+        let interval = TESTNET_PARAMS.difficulty_adjustment_interval();
+        let headers = create_chain(interval, 0x1c05a3f4, 300);
+        let chain = HeaderChain::new(&headers, &[]);
+        let expected_n_bits = calculate_next_work_required(
+            0x1c05a3f4,
+            headers[0].time,
+            headers[interval - 1].time,
+            &TESTNET_PARAMS,
+        );
+        assert_ne!(expected_n_bits, 0x1c05a3f4);
+        assert_eq!(
+            chain.next_work_required(headers[interval - 1].time + 300, &TESTNET_PARAMS),
+            Some(expected_n_bits)
+        );
+    }
+
+    #[test]
+    fn test_regtest_does_not_retarget() {
+        let interval = REGTEST_PARAMS.difficulty_adjustment_interval();
+        let headers = create_chain(interval, REGTEST_PARAMS.pow_limit_bits, 1);
+        let chain = HeaderChain::new(&headers, &[]);
+        assert_eq!(
+            chain.next_work_required(headers[interval - 1].time + 1, &REGTEST_PARAMS),
+            Some(REGTEST_PARAMS.pow_limit_bits)
+        );
+    }
+}
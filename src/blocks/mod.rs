@@ -1,4 +1,11 @@
 pub mod block;
+pub mod block_filter;
 pub mod block_header;
+pub mod block_store;
+pub mod block_undo;
+pub mod chainwork;
+pub mod header_store;
+pub mod header_tree;
+pub mod header_validation;
 pub mod merkle_tree;
 pub mod utils_block;
@@ -0,0 +1,247 @@
+use std::{error::Error, io, str::FromStr};
+
+use crate::blocks::block_header::BlockHeader;
+
+/// Merkle root of the genesis block of every network: all of them have the same coinbase transaction.
+const GENESIS_MERKLE_ROOT: [u8; 32] = [
+    59, 163, 237, 253, 122, 123, 18, 178, 122, 199, 44, 62, 103, 118, 143, 97, 127, 200, 27, 195,
+    136, 138, 81, 50, 58, 159, 184, 170, 75, 30, 94, 74,
+];
+/// Expected time between two difficulty adjustments: two weeks.
+const POW_TARGET_TIMESPAN: u32 = 14 * 24 * 60 * 60;
+/// Expected time between two blocks: ten minutes.
+const POW_TARGET_SPACING: u32 = 10 * 60;
+/// Amount of blocks after which the subsidy is halved, in every network but regtest.
+const SUBSIDY_HALVING_INTERVAL: usize = 210_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents the bitcoin networks that the node can join.
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    /// Returns the parameters of the chain of the network.
+    pub fn chain_params(&self) -> &'static ChainParams {
+        match self {
+            Network::Mainnet => &MAINNET_PARAMS,
+            Network::Testnet => &TESTNET_PARAMS,
+            Network::Signet => &SIGNET_PARAMS,
+            Network::Regtest => &REGTEST_PARAMS,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = Box<dyn Error>;
+
+    /// Receives the name of the network as it is written in the config file (mainnet, testnet,
+    /// signet or regtest) and returns it, or an error if the network is unknown.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "signet" => Ok(Network::Signet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown network: {}", name),
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+/// Stores the parameters that distinguish the chain of each network: the start string of its messages,
+/// its genesis block, the port where its nodes listen, the prefixes of its addresses, its difficulty
+/// adjustment rules, the interval at which the subsidy is halved and the DNS seeds from where its nodes are obtained.
+pub struct ChainParams {
+    pub network: Network,
+    pub magic: [u8; 4],
+    pub genesis_header: BlockHeader,
+    pub default_port: u16,
+    pub p2pkh_address_version: u8,
+    pub p2sh_address_version: u8,
+    pub bech32_hrp: &'static str,
+    pub pow_limit_bits: u32,
+    pub pow_target_timespan: u32,
+    pub pow_target_spacing: u32,
+    pub allow_min_difficulty_blocks: bool, // A block 20 minutes newer than its parent can have the minimum difficulty
+    pub no_retargeting: bool,              // The difficulty is never adjusted
+    pub accept_local_addresses: bool, // The nodes run in local hosts, so their local addresses are kept in the address manager
+    pub subsidy_halving_interval: usize,
+    pub dns_seeds: &'static [&'static str],
+}
+
+impl ChainParams {
+    /// Returns the hash of the genesis block of the chain.
+    pub fn genesis_hash(&self) -> [u8; 32] {
+        self.genesis_header.hash()
+    }
+
+    /// Returns the amount of blocks between each difficulty adjustment.
+    pub fn difficulty_adjustment_interval(&self) -> usize {
+        (self.pow_target_timespan / self.pow_target_spacing) as usize
+    }
+}
+
+/// Parameters of the main network.
+pub static MAINNET_PARAMS: ChainParams = ChainParams {
+    network: Network::Mainnet,
+    magic: [0xf9, 0xbe, 0xb4, 0xd9],
+    genesis_header: BlockHeader {
+        version: 1,
+        previous_block_header_hash: [0; 32],
+        merkle_root_hash: GENESIS_MERKLE_ROOT,
+        time: 1231006505,
+        n_bits: 0x1d00ffff,
+        nonce: 2083236893,
+    },
+    default_port: 8333,
+    p2pkh_address_version: 0x00,
+    p2sh_address_version: 0x05,
+    bech32_hrp: "bc",
+    pow_limit_bits: 0x1d00ffff,
+    pow_target_timespan: POW_TARGET_TIMESPAN,
+    pow_target_spacing: POW_TARGET_SPACING,
+    allow_min_difficulty_blocks: false,
+    no_retargeting: false,
+    accept_local_addresses: false,
+    subsidy_halving_interval: SUBSIDY_HALVING_INTERVAL,
+    dns_seeds: &[
+        "seed.bitcoin.sipa.be",
+        "dnsseed.bluematt.me",
+        "seed.bitcoinstats.com",
+        "seed.btc.petertodd.net",
+        "seed.bitcoin.sprovoost.nl",
+    ],
+};
+
+/// Parameters of the test network (testnet3).
+pub static TESTNET_PARAMS: ChainParams = ChainParams {
+    network: Network::Testnet,
+    magic: [0x0b, 0x11, 0x09, 0x07],
+    genesis_header: BlockHeader {
+        version: 1,
+        previous_block_header_hash: [0; 32],
+        merkle_root_hash: GENESIS_MERKLE_ROOT,
+        time: 1296688602,
+        n_bits: 0x1d00ffff,
+        nonce: 414098458,
+    },
+    default_port: 18333,
+    p2pkh_address_version: 0x6f,
+    p2sh_address_version: 0xc4,
+    bech32_hrp: "tb",
+    pow_limit_bits: 0x1d00ffff,
+    pow_target_timespan: POW_TARGET_TIMESPAN,
+    pow_target_spacing: POW_TARGET_SPACING,
+    allow_min_difficulty_blocks: true,
+    no_retargeting: false,
+    accept_local_addresses: false,
+    subsidy_halving_interval: SUBSIDY_HALVING_INTERVAL,
+    dns_seeds: &[
+        "seed.testnet.bitcoin.sprovoost.nl",
+        "testnet-seed.bitcoin.jonasschnelli.ch",
+        "seed.tbtc.petertodd.net",
+        "testnet-seed.bluematt.me",
+    ],
+};
+
+/// Parameters of the default signet (BIP325). The signature of the blocks is not validated.
+pub static SIGNET_PARAMS: ChainParams = ChainParams {
+    network: Network::Signet,
+    magic: [0x0a, 0x03, 0xcf, 0x40],
+    genesis_header: BlockHeader {
+        version: 1,
+        previous_block_header_hash: [0; 32],
+        merkle_root_hash: GENESIS_MERKLE_ROOT,
+        time: 1598918400,
+        n_bits: 0x1e0377ae,
+        nonce: 52613770,
+    },
+    default_port: 38333,
+    p2pkh_address_version: 0x6f,
+    p2sh_address_version: 0xc4,
+    bech32_hrp: "tb",
+    pow_limit_bits: 0x1e0377ae,
+    pow_target_timespan: POW_TARGET_TIMESPAN,
+    pow_target_spacing: POW_TARGET_SPACING,
+    allow_min_difficulty_blocks: false,
+    no_retargeting: false,
+    accept_local_addresses: false,
+    subsidy_halving_interval: SUBSIDY_HALVING_INTERVAL,
+    dns_seeds: &["seed.signet.bitcoin.sprovoost.nl"],
+};
+
+/// Parameters of the regression test network, a local chain with the minimum difficulty.
+pub static REGTEST_PARAMS: ChainParams = ChainParams {
+    network: Network::Regtest,
+    magic: [0xfa, 0xbf, 0xb5, 0xda],
+    genesis_header: BlockHeader {
+        version: 1,
+        previous_block_header_hash: [0; 32],
+        merkle_root_hash: GENESIS_MERKLE_ROOT,
+        time: 1296688602,
+        n_bits: 0x207fffff,
+        nonce: 2,
+    },
+    default_port: 18444,
+    p2pkh_address_version: 0x6f,
+    p2sh_address_version: 0xc4,
+    bech32_hrp: "bcrt",
+    pow_limit_bits: 0x207fffff,
+    pow_target_timespan: POW_TARGET_TIMESPAN,
+    pow_target_spacing: POW_TARGET_SPACING,
+    allow_min_difficulty_blocks: true,
+    no_retargeting: true,
+    accept_local_addresses: true,
+    subsidy_halving_interval: 150,
+    dns_seeds: &[],
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_genesis_headers_have_the_hash_of_the_genesis_block_of_each_network() {
+        let expected_hashes = [
+            (
+                Network::Mainnet,
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            ),
+            (
+                Network::Testnet,
+                "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+            ),
+            (
+                Network::Signet,
+                "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6",
+            ),
+            (
+                Network::Regtest,
+                "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+            ),
+        ];
+        for (network, expected_hash) in expected_hashes {
+            let params = network.chain_params();
+            assert_eq!(params.network, network);
+            assert_eq!(params.genesis_header.hex_hash(), expected_hash);
+            assert!(params.genesis_header.validate());
+        }
+    }
+
+    #[test]
+    fn test_network_is_parsed_from_its_name() -> Result<(), Box<dyn Error>> {
+        assert_eq!(Network::from_str("mainnet")?, Network::Mainnet);
+        assert_eq!(Network::from_str("Testnet")?, Network::Testnet);
+        assert_eq!(Network::from_str("signet")?, Network::Signet);
+        assert_eq!(Network::from_str("regtest")?, Network::Regtest);
+        assert!(Network::from_str("testnet4").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,627 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use bitcoin_hashes::{sha256d, Hash};
+
+use crate::{
+    blocks::{block::Block, block_undo::BlockUndo},
+    compact_size_uint::CompactSizeUint,
+    custom_errors::NodeCustomErrors,
+    transactions::tx_out::TxOut,
+    utxo_tuple::UtxoTuple,
+};
+
+/// Amount of blocks connected during the initial block download between two flushes of the chainstate.
+pub const CHAINSTATE_FLUSH_INTERVAL: usize = 1000;
+const CHAINSTATE_FILE_NAME: &str = "chainstate.dat";
+const TEMPORARY_FILE_NAME: &str = "chainstate.dat.tmp";
+/// Minimum amount of records in the file before it is compacted
+const MIN_RECORDS_TO_COMPACT: usize = 100_000;
+const CHECKSUM_SIZE: usize = 4;
+// Records of the chainstate file, all the numbers in little endian:
+// coin:       0x01 <txid 32> <index u32> <height u32> <is_coinbase u8> <value i64> <script length u32> <script>
+// spent:      0x02 <txid 32> <index u32>
+// best block: 0x03 <hash 32> <height u64> <is_complete u8> <checksum 4>
+// Each flush appends a batch of coin and spent records followed by the best block record. The checksum
+// is the first 4 bytes of the double sha256 of the batch, so a batch that was not completely written
+// is discarded when the chainstate is opened.
+const COIN_RECORD: u8 = 0x01;
+const SPENT_RECORD: u8 = 0x02;
+const BEST_BLOCK_RECORD: u8 = 0x03;
+
+type OutpointKey = ([u8; 32], usize);
+
+/// Represents a record of the chainstate file.
+enum ChainStateRecord {
+    Coin(OutpointKey, usize, bool, TxOut),
+    Spent(OutpointKey),
+    BestBlock([u8; 32], usize, bool, [u8; CHECKSUM_SIZE]),
+}
+
+#[derive(Debug)]
+/// Stores the utxo set in disk, keyed by outpoint, with the hash and height of the best block it
+/// belongs to. The utxo set in memory works as a write-back cache: the outpoints created or spent by
+/// the connected and disconnected blocks are marked as dirty, and each flush appends their current
+/// state in a single batch that ends with the best block. When the node starts, the utxo set is
+/// rebuilt from the batches that were completely written, and the blocks after the flushed height
+/// are connected again.
+pub struct ChainState {
+    directory: PathBuf,
+    best_block_hash: [u8; 32],
+    height: usize,
+    is_complete: bool,
+    dirty_outpoints: HashSet<OutpointKey>,
+    blocks_since_flush: usize,
+    records_in_file: usize,
+}
+
+impl ChainState {
+    /// Opens the chainstate of the received directory, creating it if it does not exist, and returns
+    /// it with the utxo set of the last flush. A batch that was not completely written is removed from
+    /// the file. Returns an error if the directory or the file can not be read.
+    pub fn open(
+        directory: &str,
+    ) -> Result<(ChainState, HashMap<[u8; 32], UtxoTuple>), NodeCustomErrors> {
+        fs::create_dir_all(directory)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        let mut chainstate = ChainState {
+            directory: PathBuf::from(directory),
+            best_block_hash: [0; 32],
+            height: 0,
+            is_complete: false,
+            dirty_outpoints: HashSet::new(),
+            blocks_since_flush: 0,
+            records_in_file: 0,
+        };
+        let utxo_set = chainstate.load()?;
+        Ok((chainstate, utxo_set))
+    }
+
+    /// Reads the records of the file and applies the batches that end with a valid best block record.
+    /// Returns the utxo set loaded.
+    fn load(&mut self) -> Result<HashMap<[u8; 32], UtxoTuple>, NodeCustomErrors> {
+        let mut utxo_set: HashMap<[u8; 32], UtxoTuple> = HashMap::new();
+        let path = self.directory.join(CHAINSTATE_FILE_NAME);
+        if !path.exists() {
+            return Ok(utxo_set);
+        }
+        let mut data: Vec<u8> = Vec::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        let mut offset = 0;
+        let mut batch_start = 0;
+        let mut batch: Vec<ChainStateRecord> = Vec::new();
+        while let Some(record) = unmarshalling_record(&data, &mut offset) {
+            match record {
+                ChainStateRecord::BestBlock(hash, height, is_complete, checksum) => {
+                    if checksum != batch_checksum(&data[batch_start..offset - CHECKSUM_SIZE]) {
+                        break;
+                    }
+                    self.records_in_file += batch.len() + 1;
+                    for record in batch.drain(..) {
+                        apply_record(&mut utxo_set, record);
+                    }
+                    self.best_block_hash = hash;
+                    self.height = height;
+                    self.is_complete = is_complete;
+                    batch_start = offset;
+                }
+                record => batch.push(record),
+            }
+        }
+        // the node stopped while a batch was written
+        if batch_start != data.len() {
+            OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_len(batch_start as u64))
+                .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        }
+        Ok(utxo_set)
+    }
+
+    /// Returns the hash of the last block connected to the utxo set.
+    pub fn best_block_hash(&self) -> [u8; 32] {
+        self.best_block_hash
+    }
+
+    /// Returns the height of the last block connected to the utxo set.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns true if the utxo set was built from the first block after the genesis, so it contains
+    /// all the unspent outputs.
+    pub fn is_complete(&self) -> bool {
+        self.is_complete
+    }
+
+    /// Returns true if no block was connected to the utxo set.
+    pub fn is_empty(&self) -> bool {
+        self.best_block_hash == [0; 32]
+    }
+
+    /// Returns true if enough blocks were connected since the last flush to flush the chainstate again.
+    pub fn needs_flush(&self) -> bool {
+        self.blocks_since_flush >= CHAINSTATE_FLUSH_INTERVAL
+    }
+
+    /// Marks as dirty the outputs spent and created by the block that was connected to the utxo set
+    /// with the received height, which becomes the best block. If it is the first block connected,
+    /// the utxo set is complete only if it is the first block after the genesis.
+    pub fn block_connected(&mut self, block: &Block, height: usize) {
+        if self.is_empty() {
+            self.is_complete = height <= 1;
+        }
+        self.mark_block_outpoints(block);
+        self.best_block_hash = block.hash();
+        self.height = height;
+        self.blocks_since_flush += 1;
+    }
+
+    /// Marks as dirty the outputs created by the block that was disconnected from the utxo set and the
+    /// ones restored with its undo data. The previous block becomes the best block.
+    pub fn block_disconnected(&mut self, block: &Block, block_undo: &BlockUndo) {
+        self.mark_block_outpoints(block);
+        for spent_output in &block_undo.spent_outputs {
+            self.dirty_outpoints
+                .insert((spent_output.outpoint.hash(), spent_output.outpoint.index()));
+        }
+        self.best_block_hash = block.block_header.previous_block_header_hash;
+        self.height = self.height.saturating_sub(1);
+        self.blocks_since_flush += 1;
+    }
+
+    /// Marks as dirty the outputs spent and created by the transactions of the block.
+    fn mark_block_outpoints(&mut self, block: &Block) {
+        for tx in &block.txn {
+            if !tx.is_coinbase_transaction() {
+                for tx_in in &tx.tx_in {
+                    self.dirty_outpoints.insert((
+                        tx_in.get_previous_output_hash(),
+                        tx_in.get_previous_output_index(),
+                    ));
+                }
+            }
+            let hash = tx.hash();
+            for index in 0..tx.tx_out.len() {
+                self.dirty_outpoints.insert((hash, index));
+            }
+        }
+    }
+
+    /// Writes the state of the dirty outpoints in the received utxo set and the best block in a single
+    /// batch at the end of the file. The file is compacted when it has many more records than coins.
+    /// Returns an error if the file can not be written, in which case the outpoints remain dirty.
+    pub fn flush(
+        &mut self,
+        utxo_set: &HashMap<[u8; 32], UtxoTuple>,
+    ) -> Result<(), NodeCustomErrors> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let mut batch: Vec<u8> = Vec::new();
+        for (hash, index) in &self.dirty_outpoints {
+            match utxo_set.get(hash).and_then(|utxo| {
+                utxo.get_tx_out(*index)
+                    .map(|tx_out| (utxo.height, utxo.is_coinbase, tx_out))
+            }) {
+                Some((height, is_coinbase, tx_out)) => {
+                    marshalling_coin(&mut batch, (*hash, *index), height, is_coinbase, tx_out)
+                }
+                None => marshalling_spent(&mut batch, (*hash, *index)),
+            }
+        }
+        self.marshalling_best_block(&mut batch);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.directory.join(CHAINSTATE_FILE_NAME))
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        file.write_all(&batch)
+            .and_then(|_| file.sync_data())
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        self.records_in_file += self.dirty_outpoints.len() + 1;
+        self.dirty_outpoints.clear();
+        self.blocks_since_flush = 0;
+        if self.records_in_file > MIN_RECORDS_TO_COMPACT
+            && self.records_in_file > 2 * amount_of_coins(utxo_set)
+        {
+            self.compact(utxo_set)?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the file with a single batch with all the coins of the utxo set. The batch is written
+    /// in a temporary file that replaces the chainstate file once it is completely written.
+    fn compact(&mut self, utxo_set: &HashMap<[u8; 32], UtxoTuple>) -> Result<(), NodeCustomErrors> {
+        let mut batch: Vec<u8> = Vec::new();
+        for utxo in utxo_set.values() {
+            for (tx_out, index) in &utxo.utxo_set {
+                marshalling_coin(
+                    &mut batch,
+                    (utxo.hash, *index),
+                    utxo.height,
+                    utxo.is_coinbase,
+                    tx_out,
+                );
+            }
+        }
+        self.marshalling_best_block(&mut batch);
+        let temporary_path = self.directory.join(TEMPORARY_FILE_NAME);
+        let mut file = File::create(&temporary_path)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        file.write_all(&batch)
+            .and_then(|_| file.sync_all())
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        fs::rename(&temporary_path, self.directory.join(CHAINSTATE_FILE_NAME))
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        self.records_in_file = amount_of_coins(utxo_set) + 1;
+        Ok(())
+    }
+
+    /// Removes the stored utxo set, so that it is built again from the downloaded blocks.
+    /// Returns an error if the file can not be removed.
+    pub fn reset(&mut self) -> Result<(), NodeCustomErrors> {
+        let path = self.directory.join(CHAINSTATE_FILE_NAME);
+        if path.exists() {
+            fs::remove_file(path)
+                .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        }
+        self.best_block_hash = [0; 32];
+        self.height = 0;
+        self.is_complete = false;
+        self.dirty_outpoints.clear();
+        self.blocks_since_flush = 0;
+        self.records_in_file = 0;
+        Ok(())
+    }
+
+    /// Appends the best block record, with the checksum of the batch, to the bytes of the batch.
+    fn marshalling_best_block(&self, batch: &mut Vec<u8>) {
+        batch.push(BEST_BLOCK_RECORD);
+        batch.extend_from_slice(&self.best_block_hash);
+        batch.extend_from_slice(&(self.height as u64).to_le_bytes());
+        batch.push(self.is_complete as u8);
+        let checksum = batch_checksum(batch);
+        batch.extend_from_slice(&checksum);
+    }
+}
+
+/// Returns the amount of unspent outputs of the utxo set.
+fn amount_of_coins(utxo_set: &HashMap<[u8; 32], UtxoTuple>) -> usize {
+    utxo_set.values().map(|utxo| utxo.utxo_set.len()).sum()
+}
+
+/// Returns the first 4 bytes of the double sha256 of the bytes of the batch.
+fn batch_checksum(batch: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let mut checksum = [0u8; CHECKSUM_SIZE];
+    checksum.copy_from_slice(&sha256d::Hash::hash(batch)[..CHECKSUM_SIZE]);
+    checksum
+}
+
+/// Appends the record of an unspent output to the bytes of the batch.
+fn marshalling_coin(
+    batch: &mut Vec<u8>,
+    (hash, index): OutpointKey,
+    height: usize,
+    is_coinbase: bool,
+    tx_out: &TxOut,
+) {
+    let pk_script = tx_out.get_pub_key_script();
+    batch.push(COIN_RECORD);
+    batch.extend_from_slice(&hash);
+    batch.extend_from_slice(&(index as u32).to_le_bytes());
+    batch.extend_from_slice(&(height as u32).to_le_bytes());
+    batch.push(is_coinbase as u8);
+    batch.extend_from_slice(&tx_out.value().to_le_bytes());
+    batch.extend_from_slice(&(pk_script.len() as u32).to_le_bytes());
+    batch.extend_from_slice(pk_script);
+}
+
+/// Appends the record of a spent output to the bytes of the batch.
+fn marshalling_spent(batch: &mut Vec<u8>, (hash, index): OutpointKey) {
+    batch.push(SPENT_RECORD);
+    batch.extend_from_slice(&hash);
+    batch.extend_from_slice(&(index as u32).to_le_bytes());
+}
+
+/// Reads the record that starts at the offset and moves the offset to the end of it.
+/// Returns None if there are no more records or the record is incomplete or invalid.
+fn unmarshalling_record(data: &[u8], offset: &mut usize) -> Option<ChainStateRecord> {
+    let record_type = *data.get(*offset)?;
+    let mut position = *offset + 1;
+    let record = match record_type {
+        COIN_RECORD => {
+            let hash = read_bytes::<32>(data, &mut position)?;
+            let index = u32::from_le_bytes(read_bytes::<4>(data, &mut position)?) as usize;
+            let height = u32::from_le_bytes(read_bytes::<4>(data, &mut position)?) as usize;
+            let is_coinbase = read_bytes::<1>(data, &mut position)?[0] == 1;
+            let value = i64::from_le_bytes(read_bytes::<8>(data, &mut position)?);
+            let script_length = u32::from_le_bytes(read_bytes::<4>(data, &mut position)?) as usize;
+            let pk_script = data
+                .get(position..position.checked_add(script_length)?)?
+                .to_vec();
+            position += script_length;
+            let tx_out = TxOut::new(
+                value,
+                CompactSizeUint::new(script_length as u128),
+                pk_script,
+            );
+            ChainStateRecord::Coin((hash, index), height, is_coinbase, tx_out)
+        }
+        SPENT_RECORD => {
+            let hash = read_bytes::<32>(data, &mut position)?;
+            let index = u32::from_le_bytes(read_bytes::<4>(data, &mut position)?) as usize;
+            ChainStateRecord::Spent((hash, index))
+        }
+        BEST_BLOCK_RECORD => {
+            let hash = read_bytes::<32>(data, &mut position)?;
+            let height = u64::from_le_bytes(read_bytes::<8>(data, &mut position)?) as usize;
+            let is_complete = read_bytes::<1>(data, &mut position)?[0] == 1;
+            let checksum = read_bytes::<CHECKSUM_SIZE>(data, &mut position)?;
+            ChainStateRecord::BestBlock(hash, height, is_complete, checksum)
+        }
+        _ => return None,
+    };
+    *offset = position;
+    Some(record)
+}
+
+/// Reads N bytes from the position and moves the position after them.
+/// Returns None if there are not enough bytes.
+fn read_bytes<const N: usize>(data: &[u8], position: &mut usize) -> Option<[u8; N]> {
+    let bytes: [u8; N] = data
+        .get(*position..position.checked_add(N)?)?
+        .try_into()
+        .ok()?;
+    *position += N;
+    Some(bytes)
+}
+
+/// Applies a coin or spent record to the utxo set.
+fn apply_record(utxo_set: &mut HashMap<[u8; 32], UtxoTuple>, record: ChainStateRecord) {
+    match record {
+        ChainStateRecord::Coin((hash, index), height, is_coinbase, tx_out) => {
+            utxo_set
+                .entry(hash)
+                .or_insert_with(|| UtxoTuple::new(hash, Vec::new(), height, is_coinbase))
+                .add_utxo(tx_out, index);
+        }
+        ChainStateRecord::Spent((hash, index)) => {
+            if let Some(utxo) = utxo_set.get_mut(&hash) {
+                utxo.remove_utxo(index);
+                if utxo.utxo_set.is_empty() {
+                    utxo_set.remove(&hash);
+                }
+            }
+        }
+        ChainStateRecord::BestBlock(..) => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        fs,
+        sync::{Arc, RwLock},
+    };
+
+    use super::{ChainState, CHAINSTATE_FILE_NAME};
+    use crate::{
+        blocks::{block::Block, block_header::BlockHeader},
+        compact_size_uint::CompactSizeUint,
+        custom_errors::NodeCustomErrors,
+        temp_directory::TempDirectory,
+        transactions::{
+            outpoint::Outpoint, script::sig_script::SigScript, transaction::Transaction,
+            tx_in::TxIn, tx_out::TxOut,
+        },
+        utxo_tuple::UtxoTuple,
+    };
+
+    type UtxoSetPointer = Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>;
+
+    /// Creates a transaction that spends the received outpoint and has an output with the received value
+    fn create_transaction(outpoint: Outpoint, sequence: u32, value: i64) -> Transaction {
+        let height = match outpoint.is_a_coinbase_outpoint() {
+            true => Some(vec![3, 1, 0, 0]),
+            false => None,
+        };
+        let tx_in = TxIn::new(
+            outpoint,
+            CompactSizeUint::new(height.as_ref().map_or(0, |height| height.len() as u128)),
+            height,
+            SigScript::new(vec![]),
+            sequence,
+        );
+        let tx_out = TxOut::new(value, CompactSizeUint::new(1), vec![0x51]);
+        Transaction::new(
+            1,
+            CompactSizeUint::new(1),
+            vec![tx_in],
+            CompactSizeUint::new(1),
+            vec![tx_out],
+            0,
+        )
+    }
+
+    /// Creates a block with the received transactions and a coinbase whose value depends on the height
+    fn create_block(previous_hash: [u8; 32], height: usize, mut txn: Vec<Transaction>) -> Block {
+        let coinbase = create_transaction(
+            Outpoint::new([0; 32], 0xffffffff),
+            0xffffffff,
+            5000 + height as i64,
+        );
+        txn.insert(0, coinbase);
+        let block_header = BlockHeader::new(1, previous_hash, [2; 32], 0, 0x1d00ffff, 0);
+        Block::new(block_header, CompactSizeUint::new(txn.len() as u128), txn)
+    }
+
+    /// Connects the block to the utxo set and to the chainstate
+    fn connect_block(
+        chainstate: &mut ChainState,
+        utxo_set: &UtxoSetPointer,
+        block: &Block,
+        height: usize,
+    ) -> Result<(), NodeCustomErrors> {
+        block
+            .give_me_utxos(utxo_set.clone(), height)
+            .map_err(|err| NodeCustomErrors::UtxoError(err.to_string()))?;
+        chainstate.block_connected(block, height);
+        Ok(())
+    }
+
+    /// Writes the utxo set in the chainstate
+    fn flush(
+        chainstate: &mut ChainState,
+        utxo_set: &UtxoSetPointer,
+    ) -> Result<(), NodeCustomErrors> {
+        let utxo_set = utxo_set
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        chainstate.flush(&utxo_set)
+    }
+
+    /// Returns the outpoints of the unspent outputs of the utxo set
+    fn coins(utxo_set: &HashMap<[u8; 32], UtxoTuple>) -> Vec<([u8; 32], usize, i64)> {
+        let mut coins: Vec<([u8; 32], usize, i64)> = utxo_set
+            .values()
+            .flat_map(|utxo| {
+                utxo.utxo_set
+                    .iter()
+                    .map(|(tx_out, index)| (utxo.hash, *index, tx_out.value()))
+            })
+            .collect();
+        coins.sort();
+        coins
+    }
+
+    #[test]
+    fn test_flushed_utxo_set_is_loaded_when_the_chainstate_is_opened_again(
+    ) -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("chainstate_reopen");
+        let path = directory.path_string();
+        let (mut chainstate, utxo_set) = ChainState::open(&path)?;
+        assert!(chainstate.is_empty());
+        let utxo_set = Arc::new(RwLock::new(utxo_set));
+        let first_block = create_block([1; 32], 1, vec![]);
+        connect_block(&mut chainstate, &utxo_set, &first_block, 1)?;
+        let coinbase_hash = first_block.txn[0].hash();
+        let spending_tx = create_transaction(Outpoint::new(coinbase_hash, 0), 0xfffffffe, 4000);
+        let second_block = create_block(first_block.hash(), 2, vec![spending_tx.clone()]);
+        connect_block(&mut chainstate, &utxo_set, &second_block, 2)?;
+        let utxo_set = utxo_set
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        chainstate.flush(&utxo_set)?;
+
+        let (chainstate, loaded_utxo_set) = ChainState::open(&path)?;
+        assert_eq!(chainstate.best_block_hash(), second_block.hash());
+        assert_eq!(chainstate.height(), 2);
+        assert!(chainstate.is_complete());
+        assert_eq!(coins(&loaded_utxo_set), coins(&utxo_set));
+        assert!(loaded_utxo_set.get(&coinbase_hash).is_none());
+        assert_eq!(
+            loaded_utxo_set
+                .get(&spending_tx.hash())
+                .map(|utxo| (utxo.height, utxo.balance())),
+            Some((2, 4000))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_that_was_not_completely_written_is_discarded() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("chainstate_incomplete");
+        let path = directory.path_string();
+        let (mut chainstate, utxo_set) = ChainState::open(&path)?;
+        let utxo_set = Arc::new(RwLock::new(utxo_set));
+        let first_block = create_block([1; 32], 10, vec![]);
+        connect_block(&mut chainstate, &utxo_set, &first_block, 10)?;
+        flush(&mut chainstate, &utxo_set)?;
+        let file_path = directory.path().join(CHAINSTATE_FILE_NAME);
+        let flushed_size = fs::metadata(&file_path).map(|metadata| metadata.len()).ok();
+        // the node stopped while writing the next batch
+        let second_block = create_block(first_block.hash(), 11, vec![]);
+        connect_block(&mut chainstate, &utxo_set, &second_block, 11)?;
+        flush(&mut chainstate, &utxo_set)?;
+        let mut data = fs::read(&file_path).unwrap_or_default();
+        data.truncate(data.len() - 3);
+        let _ = fs::write(&file_path, data);
+
+        let (chainstate, loaded_utxo_set) = ChainState::open(&path)?;
+        assert_eq!(chainstate.best_block_hash(), first_block.hash());
+        assert_eq!(chainstate.height(), 10);
+        assert!(!chainstate.is_complete());
+        assert_eq!(loaded_utxo_set.len(), 1);
+        assert!(loaded_utxo_set.contains_key(&first_block.txn[0].hash()));
+        assert_eq!(
+            fs::metadata(&file_path).map(|metadata| metadata.len()).ok(),
+            flushed_size
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_disconnected_block_is_flushed_with_the_previous_block_as_best_block(
+    ) -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("chainstate_disconnect");
+        let path = directory.path_string();
+        let (mut chainstate, utxo_set) = ChainState::open(&path)?;
+        let utxo_set = Arc::new(RwLock::new(utxo_set));
+        let first_block = create_block([1; 32], 1, vec![]);
+        connect_block(&mut chainstate, &utxo_set, &first_block, 1)?;
+        let coinbase_hash = first_block.txn[0].hash();
+        let spending_tx = create_transaction(Outpoint::new(coinbase_hash, 0), 0xfffffffe, 4000);
+        let second_block = create_block(first_block.hash(), 2, vec![spending_tx]);
+        let block_undo = second_block
+            .give_me_utxos(utxo_set.clone(), 2)
+            .map_err(|err| NodeCustomErrors::UtxoError(err.to_string()))?;
+        chainstate.block_connected(&second_block, 2);
+        flush(&mut chainstate, &utxo_set)?;
+        second_block
+            .disconnect_block(utxo_set.clone(), &block_undo)
+            .map_err(|err| NodeCustomErrors::UtxoError(err.to_string()))?;
+        chainstate.block_disconnected(&second_block, &block_undo);
+        flush(&mut chainstate, &utxo_set)?;
+
+        let (chainstate, loaded_utxo_set) = ChainState::open(&path)?;
+        assert_eq!(chainstate.best_block_hash(), first_block.hash());
+        assert_eq!(chainstate.height(), 1);
+        assert_eq!(coins(&loaded_utxo_set), vec![(coinbase_hash, 0, 5001)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compacted_chainstate_keeps_the_utxo_set() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("chainstate_compact");
+        let path = directory.path_string();
+        let (mut chainstate, utxo_set) = ChainState::open(&path)?;
+        let utxo_set = Arc::new(RwLock::new(utxo_set));
+        let mut previous_hash = [1; 32];
+        for height in 1..4 {
+            let block = create_block(previous_hash, height, vec![]);
+            previous_hash = block.hash();
+            connect_block(&mut chainstate, &utxo_set, &block, height)?;
+            flush(&mut chainstate, &utxo_set)?;
+        }
+        let utxo_set = utxo_set
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        chainstate.compact(&utxo_set)?;
+        assert_eq!(chainstate.records_in_file, 4);
+
+        let (chainstate, loaded_utxo_set) = ChainState::open(&path)?;
+        assert_eq!(chainstate.best_block_hash(), previous_hash);
+        assert_eq!(chainstate.height(), 3);
+        assert_eq!(coins(&loaded_utxo_set), coins(&utxo_set));
+        Ok(())
+    }
+}
@@ -4,15 +4,21 @@ use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr};
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::chain_params::{ChainParams, Network};
 use crate::custom_errors::NodeCustomErrors;
 
 /// Useful to validate the amount of attributes in the config file
-/// If the amount of attributes in the config file changes, this constant
-/// must be updated
+/// If the amount of required attributes in the config file changes, this constant
+/// must be updated. The folders of the blocks, the chainstate and the indexes, the
+/// prune target, the indexes, the peers file and the listen ips are optional
 const AMOUNT_OF_ATTRIBUTES: usize = 23;
+/// Minimum size in MB of the stored blocks of a pruned node, as in Bitcoin Core: the last 288 blocks
+/// (MIN_BLOCKS_TO_KEEP) are always kept, so a lower target could not be respected
+const MIN_PRUNE_TARGET_MB: u64 = 550;
 
 #[derive(Debug, Clone)]
 /// Stores the configuration of the node
@@ -22,7 +28,7 @@ pub struct Config {
     pub connect_to_dns_nodes: bool,
     pub custom_nodes_ips: Vec<String>,
     pub net_port: u16,
-    pub start_string: [u8; 4],
+    pub chain_params: &'static ChainParams,
     pub protocol_version: i32,
     pub user_agent: String,
     pub n_threads: usize,
@@ -40,6 +46,15 @@ pub struct Config {
     pub height_first_block_to_download: usize,
     pub headers_file: String,
     pub logs_folder_path: String,
+    pub blocks_folder_path: String,
+    pub chainstate_folder_path: String,
+    pub prune_target_mb: u64,
+    pub tx_index: bool,
+    pub address_index: bool,
+    pub block_filter_index: bool,
+    pub indexes_folder_path: String,
+    pub peers_file: String,
+    pub listen_ips: Vec<IpAddr>,
 }
 impl Config {
 
@@ -77,7 +92,7 @@ impl Config {
             connect_to_dns_nodes: true,
             custom_nodes_ips: Vec::new(),
             net_port: 0,
-            start_string: [0; 4],
+            chain_params: Network::Testnet.chain_params(),
             protocol_version: 0,
             user_agent: String::new(),
             n_threads: 0,
@@ -95,6 +110,15 @@ impl Config {
             height_first_block_to_download: 0,
             headers_file: String::new(),
             logs_folder_path: String::new(),
+            blocks_folder_path: String::from("./blocks"),
+            chainstate_folder_path: String::from("./chainstate"),
+            prune_target_mb: 0,
+            tx_index: false,
+            address_index: false,
+            block_filter_index: false,
+            indexes_folder_path: String::from("./indexes"),
+            peers_file: String::from("peers.dat"),
+            listen_ips: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
         };
 
         let mut number_of_settings_loaded: usize = 0;
@@ -120,6 +144,11 @@ impl Config {
             )?;
         }
         Self::check_number_of_attributes(number_of_settings_loaded)?;
+        Self::check_block_filter_index(&cfg)?;
+        // the port and the DNS seeds of the network are used if they are not configured
+        if cfg.net_port == 0 {
+            cfg.net_port = cfg.chain_params.default_port;
+        }
         Ok(Arc::new(cfg))
     }
 
@@ -135,8 +164,20 @@ impl Config {
         Ok(())
     }
 
+    /// Checks that the blocks are downloaded from the first one if the filter index is enabled, as each filter
+    /// header is built with all the previous filters. Returns an error if they are downloaded from a later height.
+    fn check_block_filter_index(cfg: &Config) -> Result<(), Box<dyn Error>> {
+        if cfg.block_filter_index && cfg.height_first_block_to_download > 1 {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "BLOCK_FILTER_INDEX=true needs HEIGHT_FIRST_BLOCK_TO_DOWNLOAD=1".to_string(),
+            )));
+        }
+        Ok(())
+    }
+
     /// Receives the name of the attribute and saves it in the configuration struct.
-    /// Updates the amount of attributes read for later verification.
+    /// Updates the amount of required attributes read for later verification.
     fn load_setting(
         &mut self,
         name: &str,
@@ -163,11 +204,18 @@ impl Config {
                 *number_of_settings_loaded += 1;
             }
             "NET_PORT" => {
-                self.net_port = u16::from_str(value)?;
+                if !value.is_empty() {
+                    self.net_port = u16::from_str(value)?;
+                }
+                *number_of_settings_loaded += 1;
+            }
+            "NETWORK" => {
+                self.chain_params = Network::from_str(value)?.chain_params();
                 *number_of_settings_loaded += 1;
             }
+            // the config files written before the NETWORK setting have the start string of the network
             "START_STRING" => {
-                self.start_string = i32::from_str(value)?.to_be_bytes();
+                self.chain_params = network_of_start_string(u32::from_str(value)?)?.chain_params();
                 *number_of_settings_loaded += 1;
             }
             "PROTOCOL_VERSION" => {
@@ -238,6 +286,46 @@ impl Config {
                 self.logs_folder_path = String::from(value);
                 *number_of_settings_loaded += 1;
             }
+            "BLOCKS_FOLDER" => {
+                self.blocks_folder_path = String::from(value);
+            }
+            "CHAINSTATE_FOLDER" => {
+                self.chainstate_folder_path = String::from(value);
+            }
+            "PRUNE_TARGET_MB" => {
+                self.prune_target_mb = u64::from_str(value)?;
+                if self.prune_target_mb != 0 && self.prune_target_mb < MIN_PRUNE_TARGET_MB {
+                    return Err(Box::new(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "PRUNE_TARGET_MB must be 0 or at least {} MB",
+                            MIN_PRUNE_TARGET_MB
+                        ),
+                    )));
+                }
+            }
+            "TX_INDEX" => {
+                self.tx_index = bool::from_str(value)?;
+            }
+            "ADDRESS_INDEX" => {
+                self.address_index = bool::from_str(value)?;
+            }
+            "BLOCK_FILTER_INDEX" => {
+                self.block_filter_index = bool::from_str(value)?;
+            }
+            "INDEXES_FOLDER" => {
+                self.indexes_folder_path = String::from(value);
+            }
+            "PEERS_FILE" => {
+                self.peers_file = String::from(value);
+            }
+            "LISTEN_IPS" => {
+                if !value.is_empty() {
+                    let listen_ips: Result<Vec<IpAddr>, _> =
+                        value.split(',').map(IpAddr::from_str).collect();
+                    self.listen_ips = listen_ips?;
+                }
+            }
             _ => {
                 return Err(Box::new(io::Error::new(
                     io::ErrorKind::InvalidInput,
@@ -247,6 +335,39 @@ impl Config {
         }
         Ok(())
     }
+
+    /// Returns the DNS seeds from where the nodes are obtained: the one configured or,
+    /// if it is empty, the ones of the network.
+    pub fn dns_seeds(&self) -> Vec<String> {
+        if self.dns_seed.is_empty() {
+            return self
+                .chain_params
+                .dns_seeds
+                .iter()
+                .map(|seed| seed.to_string())
+                .collect();
+        }
+        vec![self.dns_seed.clone()]
+    }
+}
+
+/// Returns the network whose messages start with the received start string, written as the u32 of its
+/// big endian bytes. Returns an error if no network has that start string.
+fn network_of_start_string(start_string: u32) -> Result<Network, Box<dyn Error>> {
+    [
+        Network::Mainnet,
+        Network::Testnet,
+        Network::Signet,
+        Network::Regtest,
+    ]
+    .into_iter()
+    .find(|network| network.chain_params().magic == start_string.to_be_bytes())
+    .ok_or_else(|| {
+        Box::new(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unknown start string: {}", start_string),
+        )) as Box<dyn Error>
+    })
 }
 
 #[cfg(test)]
@@ -279,6 +400,143 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn config_uses_the_chain_params_of_the_network() -> Result<(), Box<dyn Error>> {
+        // GIVEN: the config file with the regtest network and without port and DNS seed
+        let content = std::fs::read_to_string("nodo.conf")?
+            .lines()
+            .map(|line| match line.split('=').next() {
+                Some("NETWORK") => "NETWORK=regtest",
+                Some("NET_PORT") => "NET_PORT=",
+                Some("DNS_SEED") => "DNS_SEED=",
+                _ => line,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        // WHEN: the function from_reader is executed with that content
+        let cfg = Config::from_reader(content.as_bytes())?;
+
+        // THEN: the config has the parameters, the port and the DNS seeds of regtest
+        assert_eq!(cfg.chain_params.network, Network::Regtest);
+        assert_eq!(cfg.chain_params.magic, [0xfa, 0xbf, 0xb5, 0xda]);
+        assert_eq!(cfg.net_port, 18444);
+        assert!(cfg.dns_seeds().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn config_with_ipv4_and_ipv6_ips_to_listen() -> Result<(), Box<dyn Error>> {
+        // GIVEN: the config file with an Ipv4 and an Ipv6 ip to listen for incoming connections
+        let content = std::fs::read_to_string("nodo.conf")?
+            .lines()
+            .map(|line| match line.split('=').next() {
+                Some("LISTEN_IPS") => "LISTEN_IPS=0.0.0.0,::",
+                _ => line,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        // WHEN: the function from_reader is executed with that content
+        let cfg = Config::from_reader(content.as_bytes())?;
+
+        // THEN: the config has both ips
+        assert_eq!(
+            cfg.listen_ips,
+            vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED), "::".parse::<IpAddr>()?]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn config_with_block_filter_index_needs_the_first_block() -> Result<(), Box<dyn Error>> {
+        // GIVEN: the config file with the filter index enabled and the given first block to download
+        let config_with_first_block = |height: &str| -> Result<String, Box<dyn Error>> {
+            Ok(std::fs::read_to_string("nodo.conf")?
+                .lines()
+                .map(|line| match line.split('=').next() {
+                    Some("BLOCK_FILTER_INDEX") => "BLOCK_FILTER_INDEX=true".to_string(),
+                    Some("HEIGHT_FIRST_BLOCK_TO_DOWNLOAD") => {
+                        format!("HEIGHT_FIRST_BLOCK_TO_DOWNLOAD={}", height)
+                    }
+                    _ => line.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join("\n"))
+        };
+
+        // WHEN: the function from_reader is executed with those contents
+        let from_later_block = Config::from_reader(config_with_first_block("2428246")?.as_bytes());
+        let from_first_block = Config::from_reader(config_with_first_block("1")?.as_bytes())?;
+
+        // THEN: the filter index is only accepted if the blocks are downloaded from the first one
+        assert!(from_later_block.is_err());
+        assert!(from_first_block.block_filter_index);
+        Ok(())
+    }
+
+    #[test]
+    fn config_with_prune_target_below_the_minimum() -> Result<(), Box<dyn Error>> {
+        // GIVEN: the config file with the given prune target
+        let config_with_prune_target = |prune_target: &str| -> Result<String, Box<dyn Error>> {
+            Ok(std::fs::read_to_string("nodo.conf")?
+                .lines()
+                .map(|line| match line.split('=').next() {
+                    Some("PRUNE_TARGET_MB") => format!("PRUNE_TARGET_MB={}", prune_target),
+                    _ => line.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join("\n"))
+        };
+
+        // WHEN: the function from_reader is executed with those contents
+        let below_minimum = Config::from_reader(config_with_prune_target("549")?.as_bytes());
+        let minimum = Config::from_reader(config_with_prune_target("550")?.as_bytes())?;
+
+        // THEN: the prune target is only accepted if it is at least 550 MB
+        assert!(below_minimum.is_err());
+        assert_eq!(minimum.prune_target_mb, 550);
+        Ok(())
+    }
+
+    #[test]
+    fn config_file_without_the_optional_attributes_and_with_start_string(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: the config file written before the network setting and the optional attributes
+        let optional_attributes = [
+            "BLOCKS_FOLDER",
+            "CHAINSTATE_FOLDER",
+            "PRUNE_TARGET_MB",
+            "TX_INDEX",
+            "ADDRESS_INDEX",
+            "BLOCK_FILTER_INDEX",
+            "INDEXES_FOLDER",
+            "PEERS_FILE",
+            "LISTEN_IPS",
+        ];
+        let content = std::fs::read_to_string("nodo.conf")?
+            .lines()
+            .filter(|line| !optional_attributes.contains(&line.split('=').next().unwrap_or("")))
+            .map(|line| match line.split('=').next() {
+                Some("NETWORK") => "START_STRING=185665799",
+                _ => line,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        // WHEN: the function from_reader is executed with that content
+        let cfg = Config::from_reader(content.as_bytes())?;
+
+        // THEN: the config has the testnet parameters and the default values of the optional attributes
+        assert_eq!(cfg.chain_params.network, Network::Testnet);
+        assert_eq!(cfg.blocks_folder_path, "./blocks");
+        assert_eq!(cfg.peers_file, "peers.dat");
+        assert_eq!(cfg.prune_target_mb, 0);
+        assert!(!cfg.tx_index);
+        assert_eq!(cfg.listen_ips, vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        Ok(())
+    }
+
     #[test]
     fn config_with_one_less_arg() {
         // GIVEN: an argument without file path
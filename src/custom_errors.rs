@@ -22,6 +22,7 @@ pub enum NodeCustomErrors {
     BlockchainDownloadError(String),
     OtherError(String),
     UtxoError(String),
+    InvalidBlockError(String),
 }
 
 impl fmt::Display for NodeCustomErrors {
@@ -82,6 +83,9 @@ impl fmt::Display for NodeCustomErrors {
             NodeCustomErrors::UtxoError(msg) => {
                 write!(f, "Error during the Utxo setup: {}", msg)
             }
+            NodeCustomErrors::InvalidBlockError(msg) => {
+                write!(f, "InvalidBlock Error: {}", msg)
+            }
         }
     }
 }
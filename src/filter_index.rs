@@ -0,0 +1,449 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    blocks::{
+        block::Block,
+        block_filter::{basic_filter, filter_hash, filter_header, genesis_basic_filter},
+        block_undo::BlockUndo,
+    },
+    custom_errors::NodeCustomErrors,
+    record_log::RecordLog,
+};
+
+const FILTER_INDEX_FILE_NAME: &str = "filterindex.dat";
+const FILTERS_FILE_NAME: &str = "filters.dat";
+// Records of the filter index file, all the numbers in little endian:
+// connected:    0x01 <block hash 32> <height u32> <filter header 32> <filter hash 32> <filter offset u64> <filter size u32>
+// disconnected: 0x02 <block hash 32> <height u32> <filter header 32> <filter hash 32> <filter offset u64> <filter size u32>
+// The filters are indexed from the genesis block, so the height of each record is the amount of filters
+// before it. Each filter is appended to the filters.dat file before the record with its offset is written.
+const CONNECTED_RECORD: u8 = 0x01;
+const DISCONNECTED_RECORD: u8 = 0x02;
+const RECORD_SIZE: usize = 1 + 32 + 4 + 32 + 32 + 8 + 4;
+
+#[derive(Debug, Clone)]
+/// Represents the basic filter of a block of the active chain and its filter header.
+pub struct FilterEntry {
+    pub block_hash: [u8; 32],
+    pub filter: Vec<u8>,
+    pub filter_header: [u8; 32],
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Represents the hash and the header of the filter of a block and where the filter is stored in the filters file.
+struct FilterLocation {
+    block_hash: [u8; 32],
+    filter_header: [u8; 32],
+    filter_hash: [u8; 32],
+    offset: u64,
+    size: usize,
+}
+
+#[derive(Debug)]
+/// Stores the basic filters (BIP158) of the blocks of the active chain and their filter headers, which
+/// chain each filter with the previous one from the genesis block. The filters are appended to the filters.dat
+/// file and read from it when they are asked, while the filterindex.dat record log keeps where each one is
+/// stored with its hash and header. As the filter headers need all the previous filters, the blocks are only
+/// indexed if they are connected from the first block after the genesis.
+pub struct FilterIndex {
+    log: RecordLog,
+    filters_path: PathBuf,
+    locations: Vec<FilterLocation>,
+    heights: HashMap<[u8; 32], usize>,
+}
+
+impl FilterIndex {
+    /// Opens the filter index of the received directory, creating it if it does not exist, and
+    /// loads where the filters are stored. Returns an error if the directory or the file can not be read.
+    pub fn open(directory: &str) -> Result<FilterIndex, NodeCustomErrors> {
+        let mut filter_index = FilterIndex {
+            log: RecordLog::open(directory, FILTER_INDEX_FILE_NAME)?,
+            filters_path: PathBuf::from(directory).join(FILTERS_FILE_NAME),
+            locations: Vec::new(),
+            heights: HashMap::new(),
+        };
+        for (kind, height, location) in filter_index.log.load(unmarshalling_record)? {
+            match kind {
+                CONNECTED_RECORD if height == filter_index.locations.len() => {
+                    filter_index.apply_connected(location)
+                }
+                DISCONNECTED_RECORD if height + 1 == filter_index.locations.len() => {
+                    filter_index.apply_disconnected()
+                }
+                // a record that does not follow the last filter is ignored
+                _ => {}
+            }
+        }
+        Ok(filter_index)
+    }
+
+    /// Returns the filter of the block with the received height, read from the filters file, or None if it is
+    /// not indexed. Returns an error if the filter can not be read.
+    pub fn get(&self, height: usize) -> Result<Option<FilterEntry>, NodeCustomErrors> {
+        let location = match self.locations.get(height) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let mut filter = vec![0u8; location.size];
+        File::open(&self.filters_path)
+            .and_then(|mut file| {
+                file.seek(SeekFrom::Start(location.offset))?;
+                file.read_exact(&mut filter)
+            })
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        Ok(Some(FilterEntry {
+            block_hash: location.block_hash,
+            filter,
+            filter_header: location.filter_header,
+        }))
+    }
+
+    /// Returns the height of the block with the received hash, if its filter is indexed.
+    pub fn height_of(&self, block_hash: &[u8; 32]) -> Option<usize> {
+        self.heights.get(block_hash).copied()
+    }
+
+    /// Returns the hash of the filter of the block with the received height, if it is indexed.
+    pub fn filter_hash(&self, height: usize) -> Option<[u8; 32]> {
+        self.locations
+            .get(height)
+            .map(|location| location.filter_hash)
+    }
+
+    /// Returns the filter header of the block with the received height, if it is indexed.
+    pub fn filter_header(&self, height: usize) -> Option<[u8; 32]> {
+        self.locations
+            .get(height)
+            .map(|location| location.filter_header)
+    }
+
+    /// Returns the height of the last block indexed, or zero if the index is empty.
+    pub fn height(&self) -> usize {
+        self.locations.len().saturating_sub(1)
+    }
+
+    /// Returns true if no block is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Adds the filter of the block connected to the active chain with the received height, built with
+    /// its undo data. The filter of the genesis block is added before the one of the first block. The
+    /// block is ignored if it was already indexed before the node stopped, or if the previous filters are
+    /// not indexed because the blocks were downloaded from a later height.
+    /// Returns an error if the filter or its record can not be written.
+    pub fn block_connected(
+        &mut self,
+        block: &Block,
+        height: usize,
+        block_undo: &BlockUndo,
+    ) -> Result<(), NodeCustomErrors> {
+        if self.is_empty() && height == 1 {
+            let genesis_hash = block.block_header.previous_block_header_hash;
+            let filter = genesis_basic_filter(&genesis_hash);
+            self.add_filter(genesis_hash, filter)?;
+        }
+        if height != self.locations.len() {
+            return Ok(());
+        }
+        self.add_filter(block.hash(), basic_filter(block, block_undo))
+    }
+
+    /// Removes the filter of the block disconnected from the tip of the active chain. The filter remains in
+    /// the filters file. Returns an error if the record can not be written.
+    pub fn block_disconnected(&mut self, block: &Block) -> Result<(), NodeCustomErrors> {
+        let location = match self.locations.last() {
+            Some(location) if location.block_hash == block.hash() => *location,
+            _ => return Ok(()),
+        };
+        self.log.append(&marshalling_record(
+            DISCONNECTED_RECORD,
+            self.height(),
+            &location,
+        ))?;
+        self.apply_disconnected();
+        self.compact_if_needed()
+    }
+
+    /// Removes all the filters of the index, so it can be built again.
+    /// Returns an error if the files can not be written.
+    pub fn reset(&mut self) -> Result<(), NodeCustomErrors> {
+        self.log.reset()?;
+        File::create(&self.filters_path)
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        self.locations.clear();
+        self.heights.clear();
+        Ok(())
+    }
+
+    /// Chains the filter with the header of the last filter, appends it to the filters file, writes the record
+    /// with its location and adds it to the index.
+    fn add_filter(
+        &mut self,
+        block_hash: [u8; 32],
+        filter: Vec<u8>,
+    ) -> Result<(), NodeCustomErrors> {
+        let previous_filter_header = self
+            .locations
+            .last()
+            .map(|location| location.filter_header)
+            .unwrap_or([0; 32]);
+        // a filter written before the node stopped without its record is left unused
+        let offset = fs::metadata(&self.filters_path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.filters_path)
+            .and_then(|mut file| file.write_all(&filter))
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        let location = FilterLocation {
+            block_hash,
+            filter_header: filter_header(&filter, &previous_filter_header),
+            filter_hash: filter_hash(&filter),
+            offset,
+            size: filter.len(),
+        };
+        self.log.append(&marshalling_record(
+            CONNECTED_RECORD,
+            self.locations.len(),
+            &location,
+        ))?;
+        self.apply_connected(location);
+        self.compact_if_needed()
+    }
+
+    /// Compacts the file if most of its records belong to blocks that were disconnected.
+    fn compact_if_needed(&mut self) -> Result<(), NodeCustomErrors> {
+        match self.log.needs_compaction(self.locations.len()) {
+            true => self.compact(),
+            false => Ok(()),
+        }
+    }
+
+    /// Rewrites the file with a connected record for each indexed filter, in the order of the chain.
+    /// The filters file is not rewritten. Returns an error if the file can not be written.
+    fn compact(&mut self) -> Result<(), NodeCustomErrors> {
+        let records: Vec<Vec<u8>> = self
+            .locations
+            .iter()
+            .enumerate()
+            .map(|(height, location)| marshalling_record(CONNECTED_RECORD, height, location))
+            .collect();
+        self.log.compact(&records)
+    }
+
+    /// Adds the location of the filter at the end of the index.
+    fn apply_connected(&mut self, location: FilterLocation) {
+        self.heights
+            .insert(location.block_hash, self.locations.len());
+        self.locations.push(location);
+    }
+
+    /// Removes the location of the filter at the end of the index.
+    fn apply_disconnected(&mut self) {
+        if let Some(location) = self.locations.pop() {
+            self.heights.remove(&location.block_hash);
+        }
+    }
+}
+
+/// Returns the bytes of the record with the location of the filter of the block with the received height.
+fn marshalling_record(kind: u8, height: usize, location: &FilterLocation) -> Vec<u8> {
+    let mut record: Vec<u8> = Vec::with_capacity(RECORD_SIZE);
+    record.push(kind);
+    record.extend_from_slice(&location.block_hash);
+    record.extend_from_slice(&(height as u32).to_le_bytes());
+    record.extend_from_slice(&location.filter_header);
+    record.extend_from_slice(&location.filter_hash);
+    record.extend_from_slice(&location.offset.to_le_bytes());
+    record.extend_from_slice(&(location.size as u32).to_le_bytes());
+    record
+}
+
+/// Reads the record that starts in the offset and moves the offset to the next one. Returns None if
+/// the record was not completely written or has an unknown kind.
+fn unmarshalling_record(data: &[u8], offset: &mut usize) -> Option<(u8, usize, FilterLocation)> {
+    let record = data.get(*offset..*offset + RECORD_SIZE)?;
+    let kind = record[0];
+    if kind != CONNECTED_RECORD && kind != DISCONNECTED_RECORD {
+        return None;
+    }
+    let mut height_bytes = [0u8; 4];
+    height_bytes.copy_from_slice(&record[33..37]);
+    let mut offset_bytes = [0u8; 8];
+    offset_bytes.copy_from_slice(&record[101..109]);
+    let mut size_bytes = [0u8; 4];
+    size_bytes.copy_from_slice(&record[109..113]);
+    let mut location = FilterLocation {
+        block_hash: [0; 32],
+        filter_header: [0; 32],
+        filter_hash: [0; 32],
+        offset: u64::from_le_bytes(offset_bytes),
+        size: u32::from_le_bytes(size_bytes) as usize,
+    };
+    location.block_hash.copy_from_slice(&record[1..33]);
+    location.filter_header.copy_from_slice(&record[37..69]);
+    location.filter_hash.copy_from_slice(&record[69..101]);
+    *offset += RECORD_SIZE;
+    Some((kind, u32::from_le_bytes(height_bytes) as usize, location))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{FilterIndex, FILTER_INDEX_FILE_NAME};
+    use crate::{
+        blocks::{
+            block::Block,
+            block_filter::{filter_hash, filter_header, filter_matches},
+            block_header::BlockHeader,
+            block_undo::BlockUndo,
+        },
+        compact_size_uint::CompactSizeUint,
+        custom_errors::NodeCustomErrors,
+        temp_directory::TempDirectory,
+        transactions::{
+            outpoint::Outpoint, script::sig_script::SigScript, transaction::Transaction,
+            tx_in::TxIn, tx_out::TxOut,
+        },
+    };
+
+    /// Creates a block with the received previous hash and a transaction with an output with the received script
+    fn create_block(previous_hash: [u8; 32], script: Vec<u8>) -> Block {
+        let tx_in = TxIn::new(
+            Outpoint::new([1; 32], 0),
+            CompactSizeUint::new(0),
+            None,
+            SigScript::new(vec![]),
+            0xffffffff,
+        );
+        let tx_out = TxOut::new(1000, CompactSizeUint::new(script.len() as u128), script);
+        let tx = Transaction::new(
+            1,
+            CompactSizeUint::new(1),
+            vec![tx_in],
+            CompactSizeUint::new(1),
+            vec![tx_out],
+            0,
+        );
+        let block_header = BlockHeader::new(1, previous_hash, [2; 32], 0, 0x1d00ffff, 0);
+        Block::new(block_header, CompactSizeUint::new(1), vec![tx])
+    }
+
+    #[test]
+    fn test_filters_are_chained_from_the_genesis_block() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("filter_index_chain");
+        let directory_str = directory.path_string();
+        let first_block = create_block([7; 32], vec![0x51]);
+        let second_block = create_block(first_block.hash(), vec![0x52]);
+        {
+            let mut filter_index = FilterIndex::open(&directory_str)?;
+            filter_index.block_connected(&first_block, 1, &BlockUndo::new())?;
+            filter_index.block_connected(&second_block, 2, &BlockUndo::new())?;
+        }
+        let filter_index = FilterIndex::open(&directory_str)?;
+        assert_eq!(filter_index.height(), 2);
+        assert_eq!(filter_index.height_of(&[7; 32]), Some(0));
+        assert_eq!(filter_index.height_of(&second_block.hash()), Some(2));
+        let missing_filter = || NodeCustomErrors::OtherError("Missing filter".to_string());
+        let genesis = filter_index.get(0)?.ok_or_else(missing_filter)?;
+        let second = filter_index.get(2)?.ok_or_else(missing_filter)?;
+        assert!(filter_matches(
+            &second.filter,
+            &second_block.hash(),
+            &[0x52]
+        ));
+        let first_header = filter_index.filter_header(1).unwrap_or_default();
+        assert_eq!(
+            second.filter_header,
+            filter_header(&second.filter, &first_header)
+        );
+        assert_eq!(
+            genesis.filter_header,
+            filter_header(&genesis.filter, &[0; 32])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compacted_index_reads_the_filters_of_the_connected_blocks(
+    ) -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("filter_index_compact");
+        let directory_str = directory.path_string();
+        let first_block = create_block([7; 32], vec![0x51]);
+        let mut stale_block = create_block(first_block.hash(), vec![0x52]);
+        stale_block.block_header.time = 1;
+        let second_block = create_block(first_block.hash(), vec![0x53]);
+        {
+            let mut filter_index = FilterIndex::open(&directory_str)?;
+            filter_index.block_connected(&first_block, 1, &BlockUndo::new())?;
+            filter_index.block_connected(&stale_block, 2, &BlockUndo::new())?;
+            filter_index.block_disconnected(&stale_block)?;
+            filter_index.block_connected(&second_block, 2, &BlockUndo::new())?;
+            filter_index.compact()?;
+        }
+        let filter_index = FilterIndex::open(&directory_str)?;
+        assert_eq!(filter_index.height(), 2);
+        assert_eq!(filter_index.height_of(&stale_block.hash()), None);
+        let second = filter_index
+            .get(2)?
+            .ok_or_else(|| NodeCustomErrors::OtherError("Missing filter".to_string()))?;
+        assert_eq!(second.block_hash, second_block.hash());
+        assert!(filter_matches(
+            &second.filter,
+            &second_block.hash(),
+            &[0x53]
+        ));
+        assert_eq!(
+            filter_index.filter_hash(2),
+            Some(filter_hash(&second.filter))
+        );
+        assert!(filter_index.get(3)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocks_after_a_gap_are_not_indexed() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("filter_index_gap");
+        let directory_str = directory.path_string();
+        let block = create_block([7; 32], vec![0x51]);
+        let mut filter_index = FilterIndex::open(&directory_str)?;
+        filter_index.block_connected(&block, 5, &BlockUndo::new())?;
+        assert!(filter_index.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_disconnected_block_filter_is_removed() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("filter_index_disconnect");
+        let directory_str = directory.path_string();
+        let first_block = create_block([7; 32], vec![0x51]);
+        let second_block = create_block(first_block.hash(), vec![0x52]);
+        {
+            let mut filter_index = FilterIndex::open(&directory_str)?;
+            filter_index.block_connected(&first_block, 1, &BlockUndo::new())?;
+            filter_index.block_connected(&second_block, 2, &BlockUndo::new())?;
+            filter_index.block_disconnected(&second_block)?;
+        }
+        // the node stopped while writing another record
+        let path = directory.path().join(FILTER_INDEX_FILE_NAME);
+        let data = fs::read(&path).unwrap_or_default();
+        let mut torn_data = data.clone();
+        torn_data.extend_from_slice(&[1; 10]);
+        let _ = fs::write(&path, torn_data);
+
+        let filter_index = FilterIndex::open(&directory_str)?;
+        assert_eq!(filter_index.height(), 1);
+        assert_eq!(filter_index.height_of(&second_block.hash()), None);
+        assert_eq!(fs::read(&path).unwrap_or_default(), data);
+        Ok(())
+    }
+}
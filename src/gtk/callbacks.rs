@@ -25,6 +25,7 @@ pub fn connect_ui_callbacks(builder: &Builder, sender_to_node: &Sender<WalletEve
     close_main_window_on_exit(builder, sender_to_node.clone());
     change_loading_account_label_periodically(builder);
     search_tx_poi_button_clicked(builder, sender_to_node.clone());
+    search_transaction_button_clicked(builder, sender_to_node.clone());
 }
 
 /// Connects the callback of the start button. When the button is clicked, it sends a Start event to the node.
@@ -88,21 +89,29 @@ fn send_button_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
     let amount_entry: gtk::Entry = builder
         .object("amount-entry")
         .expect("error trying to get amount entry");
+    let lock_time_entry: gtk::Entry = builder
+        .object("lock-time-entry")
+        .expect("error trying to get lock time entry");
     send_button.connect_clicked(move |_| {
         let address_to_send = String::from(pay_to_entry.text());
         let amount = String::from(amount_entry.text());
         let fee: String = String::from(fee_entry.text());
+        let lock_time: String = String::from(lock_time_entry.text());
         pay_to_entry.set_text("");
         amount_entry.set_text("");
         fee_entry.set_text("");
+        lock_time_entry.set_text("");
         if let Some((valid_amount, valid_fee)) = validate_amount_and_fee(amount, fee) {
-            sender
-                .send(WalletEvent::MakeTransaction(
-                    address_to_send,
-                    valid_amount,
-                    valid_fee,
-                ))
-                .expect("error sending make transaction event to node");
+            if let Some(valid_lock_time) = validate_lock_time(lock_time) {
+                sender
+                    .send(WalletEvent::MakeTransaction(
+                        address_to_send,
+                        valid_amount,
+                        valid_fee,
+                        valid_lock_time,
+                    ))
+                    .expect("error sending make transaction event to node");
+            }
         }
     });
 }
@@ -301,6 +310,32 @@ pub fn search_tx_poi_button_clicked(builder: &Builder, sender: mpsc::Sender<Wall
     });
 }
 
+/// Connects the callback of the search transaction button. When the button is clicked, it sends a SearchTransaction
+/// event to the node. In case the hash is valid, it shows a pop up with the transaction and its block.
+/// Otherwise, it shows an error message.
+fn search_transaction_button_clicked(builder: &Builder, sender: mpsc::Sender<WalletEvent>) {
+    let search_tx_entry: gtk::Entry = builder
+        .object("search-tx")
+        .expect("error trying to get the entry of search tx in callback");
+    let search_transaction_button: gtk::Button = builder
+        .object("search-transaction-button")
+        .expect("error trying to get the button of search transaction in callback");
+    search_transaction_button.connect_clicked(move |_| {
+        let tx_hash_string = search_tx_entry.text().to_string();
+        if hex_string_to_bytes(tx_hash_string.as_str()).is_some() {
+            sender
+                .send(WalletEvent::SearchTransaction(tx_hash_string))
+                .expect("Error sending search transaction event to node");
+        } else {
+            show_dialog_message_pop_up(
+                format!("Error {tx_hash_string} is not a valid tx hash").as_str(),
+                "Error searching tx",
+            )
+        }
+        search_tx_entry.set_text("");
+    });
+}
+
 /*
 ***************************************************************************
 ************************ AUXILIAR FUNCTIONS *******************************
@@ -333,6 +368,24 @@ fn validate_amount_and_fee(amount: String, fee: String) -> Option<(i64, i64)> {
     Some((valid_amount, valid_fee))
 }
 
+/// Checks if the user entered a valid lock time (a block height or a timestamp). An empty lock time is 0,
+/// the transaction is not locked. In case it is not valid, it shows a pop up with an error message.
+fn validate_lock_time(lock_time: String) -> Option<u32> {
+    if lock_time.trim().is_empty() {
+        return Some(0);
+    }
+    match lock_time.trim().parse::<u32>() {
+        Ok(lock_time) => Some(lock_time),
+        Err(_) => {
+            show_dialog_message_pop_up(
+                "Error, please enter a valid lock time (block height or timestamp)",
+                "Failed to make transaction",
+            );
+            None
+        }
+    }
+}
+
 /// Receives a Label and changes its text to the next one in the waiting_labels list.
 fn update_label(label: Rc<RefCell<gtk::Label>>) -> Continue {
     let waiting_labels = [
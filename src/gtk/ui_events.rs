@@ -1,18 +1,12 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
-};
-
 use gtk::glib;
 
 use crate::{
-    account::Account, blocks::block::Block, blocks::block_header::BlockHeader,
-    transactions::transaction::Transaction,
+    account::Account, blockchain::Blockchain, blocks::block::Block,
+    blocks::block_header::BlockHeader, transactions::transaction::Transaction,
 };
 
-type Blocks = Arc<RwLock<HashMap<[u8; 32], Block>>>;
-type Headers = Arc<RwLock<Vec<BlockHeader>>>;
 type Height = usize;
+type Confirmations = usize;
 
 #[derive(Clone, Debug)]
 /// Represents the events that the wallet sends to the UI to display
@@ -22,13 +16,14 @@ pub enum UIEvent {
     FinsihDownloadingHeaders(usize),
     StartDownloadingBlocks,
     ShowConfirmedTransaction(Block, Account, Transaction),
+    ShowRevertedTransaction(Block, Account, Transaction),
     AccountAddedSuccesfully(Account),
     AddAccountError(String),
     AccountChanged(Account),
     ChangeAccountError(String),
     ShowPendingTransaction(Account, Transaction),
     AddBlock(Block),
-    InitializeUITabs((Headers, Blocks)),
+    InitializeUITabs(Blockchain),
     UpdateHeadersDownloaded(usize),
     UpdateBlocksDownloaded(usize, usize),
     MakeTransactionStatus(String),
@@ -36,6 +31,8 @@ pub enum UIEvent {
     UpdateTransactions(Vec<(String, Transaction, i64)>),
     BlockFound(Block),
     HeaderFound(BlockHeader, Height),
+    TransactionFound(Transaction, BlockHeader, Height, Confirmations),
+    SearchTransactionError(String),
     POIResult(String),
     NotFound,
 }
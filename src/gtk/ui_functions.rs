@@ -1,9 +1,6 @@
-use std::{
-    collections::HashMap,
-    sync::{
-        mpsc::{self},
-        Arc, RwLock,
-    },
+use std::sync::{
+    mpsc::{self},
+    Arc, RwLock,
 };
 
 use gtk::{
@@ -15,6 +12,7 @@ use gtk::{
 
 use crate::{
     account::Account,
+    blockchain::Blockchain,
     blocks::{block::Block, block_header::BlockHeader},
     transactions::transaction::Transaction,
     wallet_event::WalletEvent,
@@ -22,13 +20,11 @@ use crate::{
 
 use super::ui_events::UIEvent;
 
-type Blocks = Arc<RwLock<HashMap<[u8; 32], Block>>>;
 type Headers = Arc<RwLock<Vec<BlockHeader>>>;
 
 const AMOUNT_TO_SHOW: usize = 500;
 const ICON_FILE: &str = "src/gtk/resources/icon.png";
 
-
 /// Handles each event received from the wallet. Decide what to do with each event.
 pub fn handle_ui_event(
     builder: Builder,
@@ -51,8 +47,8 @@ pub fn handle_ui_event(
                 format!("Headers downloaded: {}", headers_downloaded).as_str(),
             );
         }
-        UIEvent::InitializeUITabs((headers, blocks)) => {
-            render_main_window(&builder, &headers, &blocks);
+        UIEvent::InitializeUITabs(blockchain) => {
+            render_main_window(&builder, &blockchain);
         }
         UIEvent::StartDownloadingHeaders => {
             let message_header: gtk::Label = builder
@@ -147,6 +143,21 @@ pub fn handle_ui_event(
                 .send(WalletEvent::GetTransactionsRequest)
                 .expect("Error sending get transactions request");
         }
+        UIEvent::ShowRevertedTransaction(block, account, transaction) => {
+            show_dialog_message_pop_up(
+                format!(
+                    "Transaction reverted: {} for account: {}. The block {} is no longer in the active chain, the transaction is pending again",
+                    transaction.hex_hash(),
+                    account.address,
+                    block.hex_hash()
+                )
+                .as_str(),
+                "Transaction reverted",
+            );
+            sender_to_node
+                .send(WalletEvent::GetTransactionsRequest)
+                .expect("Error sending get transactions request");
+        }
         UIEvent::BlockFound(block) => {
             show_dialog_message_pop_up(
                 format!(
@@ -172,6 +183,26 @@ pub fn handle_ui_event(
                 "Header found",
             );
         }
+        UIEvent::TransactionFound(transaction, header, height, confirmations) => {
+            let total_output: i64 = transaction.tx_out.iter().map(|tx_out| tx_out.value()).sum();
+            show_dialog_message_pop_up(
+                format!(
+                    "Hash: {} \nBlock: {} \nHeight: {} \nConfirmations: {} \nInputs: {} \nOutputs: {} \nTotal output (sat): {}",
+                    transaction.hex_hash(),
+                    header.hex_hash(),
+                    height,
+                    confirmations,
+                    transaction.tx_in.len(),
+                    transaction.tx_out.len(),
+                    total_output
+                )
+                .as_str(),
+                "Transaction found",
+            );
+        }
+        UIEvent::SearchTransactionError(error) => {
+            show_dialog_message_pop_up(error.as_str(), "Error searching transaction");
+        }
         UIEvent::NotFound => {
             show_dialog_message_pop_up("Not found", "Not found");
         }
@@ -208,7 +239,7 @@ fn render_transactions(transactions: &Vec<(String, Transaction, i64)>, tx_table:
                     (0, &pixbuf.to_value()),
                     (1, &tx.0.to_value()),
                     (2, &tx.1.hex_hash().to_value()),
-                    (3, &"P2PKH".to_value()),
+                    (3, &tx.1.script_type().to_value()),
                     (4, &tx.2.to_value()),
                 ],
             );
@@ -276,6 +307,7 @@ fn render_recent_transactions(transactions: &Vec<(String, Transaction, i64)>, bu
         let type_label: gtk::AccelLabel = builder
             .object(type_labels[i])
             .expect("error trying to get the type label of the recent transaction");
+        type_label.set_label(tx.1.script_type());
         type_label.set_visible(true);
     }
 }
@@ -356,7 +388,7 @@ fn render_progress_bar(builder: &Builder) {
 }
 
 /// Closes the initial window and initializes tand shows the main window.
-fn render_main_window(builder: &Builder, headers: &Headers, blocks: &Blocks) {
+fn render_main_window(builder: &Builder, blockchain: &Blockchain) {
     let initial_window: gtk::Window = builder
         .object("initial-window")
         .expect("Error trying to get the initial window");
@@ -379,8 +411,8 @@ fn render_main_window(builder: &Builder, headers: &Headers, blocks: &Blocks) {
     main_window.set_title("Bitcoin Wallet");
     set_icon(&main_window);
     main_window.show();
-    initialize_headers_tab(&liststore_headers, &header_table, headers);
-    initialize_blocks_tab(&liststore_blocks, &block_table, headers, blocks);
+    initialize_headers_tab(&liststore_headers, &header_table, &blockchain.headers);
+    initialize_blocks_tab(&liststore_blocks, &block_table, blockchain);
 }
 
 /// Updates the account tab with the account received.
@@ -478,13 +510,12 @@ pub fn get_entries(builder: &Builder) -> Vec<gtk::Entry> {
     entries
 }
 
-/// Receives the liststore of blocks, a Treeview to show the blocks, the headers and the blocks.
-/// Initializes the blocks tab with the blocks and headers received.
+/// Receives the liststore of blocks, a Treeview to show the blocks and the blockchain.
+/// Initializes the blocks tab with the last blocks of the blockchain, reading from disk the ones that are not in memory.
 fn initialize_blocks_tab(
     liststore_blocks: &gtk::ListStore,
     block_table: &TreeView,
-    headers: &Headers,
-    blocks: &Blocks,
+    blockchain: &Blockchain,
 ) {
     // temporal tree model
     let tree_model = gtk::ListStore::new(&[
@@ -494,14 +525,21 @@ fn initialize_blocks_tab(
     ]);
     block_table.set_model(Some(&tree_model));
     let mut block_hash: Vec<[u8; 32]> = Vec::new();
-    for header in headers.read().unwrap().iter().rev().take(AMOUNT_TO_SHOW) {
+    for header in blockchain
+        .headers
+        .read()
+        .unwrap()
+        .iter()
+        .rev()
+        .take(AMOUNT_TO_SHOW)
+    {
         block_hash.push(header.hash());
     }
 
     for hash in block_hash {
-        let blocks_lock = blocks.read().unwrap();
-        let block = blocks_lock.get(&hash).unwrap();
-        add_row_last_to_liststore_block(liststore_blocks, block)
+        if let Some(block) = blockchain.search_block(hash) {
+            add_row_last_to_liststore_block(liststore_blocks, &block)
+        }
     }
     block_table.set_model(Some(liststore_blocks));
 }
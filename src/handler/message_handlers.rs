@@ -1,27 +1,48 @@
+use chrono::Utc;
 use gtk::glib;
 
-use crate::blockchain_download::headers_download::load_header_heights;
 use crate::gtk::ui_events::{send_event_to_ui, UIEvent};
 use crate::{
     account::Account,
-    blocks::{block::Block, block_header::BlockHeader},
+    address_manager::AddressManagerPointer,
+    blockchain::Blockchain,
+    blocks::{
+        block::Block,
+        block_filter::BASIC_FILTER_TYPE,
+        block_header::BlockHeader,
+        header_store::HeaderStore,
+        header_validation::{validate_header_context, HeaderChain},
+    },
     compact_size_uint::CompactSizeUint,
+    filter_index::FilterIndex,
     logwriter::log_writer::{write_in_log, LogSender},
     messages::{
+        addr_message::{
+            get_addr_message, unmarshalling_addr_payload, unmarshalling_addrv2_payload,
+            MAX_ADDR_PER_MESSAGE,
+        },
         block_message::{get_block_message, BlockMessage},
+        compact_filters_message::{
+            get_cfcheckpt_message, get_cfheaders_message, get_cfilter_message,
+        },
         get_data_message::GetDataMessage,
+        getheaders_message::GetHeadersMessage,
         headers_message::HeadersMessage,
         inventory::Inventory,
         message_header::{get_checksum, HeaderMessage},
         notfound_message::get_notfound_message,
-        payload::{get_data_payload::unmarshalling, getheaders_payload::GetHeadersPayload},
+        payload::{
+            compact_filters_payload::{GetCFCheckptPayload, GetCFiltersPayload},
+            get_data_payload::unmarshalling,
+            getheaders_payload::GetHeadersPayload,
+        },
     },
     node_data_pointers::NodeDataPointers,
     transactions::transaction::Transaction,
-    utxo_tuple::UtxoTuple,
 };
 use std::{
     collections::HashMap,
+    net::IpAddr,
     sync::{mpsc::Sender, Arc, RwLock},
 };
 
@@ -30,13 +51,14 @@ use crate::custom_errors::NodeCustomErrors;
 type NodeMessageHandlerResult = Result<(), NodeCustomErrors>;
 type NodeSender = Sender<Vec<u8>>;
 
-const START_STRING: [u8; 4] = [0x0b, 0x11, 0x09, 0x07];
 const MSG_TX: u32 = 1;
 const MSG_BLOCK: u32 = 2;
-const GENESIS_BLOCK_HASH: [u8; 32] = [
-    0x00, 0x00, 0x00, 0x00, 0x09, 0x33, 0xea, 0x01, 0xad, 0x0e, 0xe9, 0x84, 0x20, 0x97, 0x79, 0xba,
-    0xae, 0xc3, 0xce, 0xd9, 0x0f, 0xa3, 0xf4, 0x08, 0x71, 0x95, 0x26, 0xf8, 0xd7, 0x7f, 0x49, 0x43,
-];
+const MSG_WITNESS_BLOCK: u32 = 0x40000002;
+const MAX_CFILTERS_PER_REQUEST: usize = 1000;
+const MAX_CFHEADERS_PER_REQUEST: usize = 2000;
+const CFCHECKPT_INTERVAL: usize = 1000;
+// Version of the getheaders messages sent while listening to the nodes (the nodes do not use it)
+const GETHEADERS_VERSION: u32 = 70015;
 
 /*
 ***************************************************************************
@@ -46,41 +68,139 @@ const GENESIS_BLOCK_HASH: [u8; 32] = [
 
 /// Unmarshalls the payload of the headers message and if they are valid, checks if they are not included in the headers chain. If they are not,
 /// sends the getData message with the block to ask for by the channel that writes in the node.
+/// If the previous header of any of them is unknown (for example, because its block was announced before the previous block
+/// was received), sends the getheaders message to ask for the headers after the tip of the active chain.
 pub fn handle_headers_message(
     log_sender: &LogSender,
     tx: NodeSender,
     payload: &[u8],
-    headers: Arc<RwLock<Vec<BlockHeader>>>,
     node_pointers: NodeDataPointers,
 ) -> NodeMessageHandlerResult {
     let new_headers = HeadersMessage::unmarshalling(&payload.to_vec())
         .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+    // Branch of the last header validated of the message, so the next one can be validated against it
+    let mut branch_of_last_header: Option<(usize, Vec<BlockHeader>)> = None;
+    let mut previous_header_is_missing = false;
     for header in new_headers {
         if !header.validate() {
             write_in_log(
                 &log_sender.error_log_sender,
                 "Error in the validation of the proof of work of the header",
             );
-        } else {
-            // Check if the header is already included in the headers chain (with the last 10 is enough)
-            let header_not_included = header_is_not_included(header, headers.clone())?;
-            if header_not_included {
-                let get_data_message =
-                    GetDataMessage::new(vec![Inventory::new_block(header.hash())]);
-                let get_data_message_bytes = get_data_message.marshalling();
-                tx.send(get_data_message_bytes)
-                    .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
+            continue;
+        }
+        // Check if the header is already included in the active chain or in a competing branch
+        let header_not_included = header_is_not_included(header, &node_pointers.blockchain)?;
+        if !header_not_included {
+            continue;
+        }
+        let branch = match branch_of_last_header.take() {
+            Some((fork_height, branch))
+                if branch.last().map(|last| last.hash())
+                    == Some(header.previous_block_header_hash) =>
+            {
+                Some((fork_height, branch))
             }
+            _ => get_branch_of_parent(&node_pointers.blockchain, &header)?,
+        };
+        let (fork_height, mut branch) = match branch {
+            Some(branch) => branch,
+            None => {
+                write_in_log(
+                    &log_sender.error_log_sender,
+                    format!(
+                        "Error in the validation of the header {}: the previous header is unknown",
+                        header.hex_hash()
+                    )
+                    .as_str(),
+                );
+                previous_header_is_missing = true;
+                continue;
+            }
+        };
+        let context_validation = validate_header_context(
+            &header,
+            &HeaderChain::new(
+                &node_pointers
+                    .blockchain
+                    .headers
+                    .read()
+                    .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[..=fork_height],
+                &branch,
+            ),
+            node_pointers.blockchain.chain_params,
+            Utc::now().timestamp(),
+        );
+        if let Err(err) = context_validation {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!(
+                    "Error in the validation of the header {}: {}",
+                    header.hex_hash(),
+                    err
+                )
+                .as_str(),
+            );
+            continue;
         }
-        load_header_heights(
-            &vec![header],
-            &node_pointers.blockchain.header_heights,
-            &headers,
-        )?;
+        let get_data_message = GetDataMessage::new(
+            node_pointers.blockchain.chain_params.magic,
+            vec![Inventory::new_witness_block(header.hash())],
+        );
+        let get_data_message_bytes = get_data_message.marshalling();
+        tx.send(get_data_message_bytes)
+            .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
+        branch.push(header);
+        branch_of_last_header = Some((fork_height, branch));
+    }
+    if previous_header_is_missing {
+        ask_for_missing_headers(tx, &node_pointers.blockchain)?;
     }
     Ok(())
 }
 
+/// Sends the getheaders message with the hash of the tip of the active chain by the channel that writes in the node,
+/// so that the node sends the headers that are missing. Returns error of type NodeCustomErrors in case of failure.
+fn ask_for_missing_headers(tx: NodeSender, blockchain: &Blockchain) -> NodeMessageHandlerResult {
+    let tip_hash = blockchain
+        .headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .last()
+        .map(|tip| tip.hash())
+        .unwrap_or(blockchain.chain_params.genesis_hash());
+    let mut getheaders_message = Vec::new();
+    GetHeadersMessage::new(
+        blockchain.chain_params.magic,
+        GETHEADERS_VERSION,
+        vec![tip_hash],
+    )
+    .write_to(&mut getheaders_message)
+    .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))?;
+    write_to_node(&tx, getheaders_message)
+}
+
+/// Returns the height of the header of the active chain where the chain of the parent of the header forks and
+/// the headers of the competing branch from the fork to the parent. If the parent is in the active chain the branch
+/// is empty. Returns None if the parent is unknown.
+fn get_branch_of_parent(
+    blockchain: &Blockchain,
+    header: &BlockHeader,
+) -> Result<Option<(usize, Vec<BlockHeader>)>, NodeCustomErrors> {
+    let parent_hash = header.previous_block_header_hash;
+    let header_heights = blockchain
+        .header_heights
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    if let Some(parent_height) = header_heights.get(&parent_hash) {
+        return Ok(Some((*parent_height, Vec::new())));
+    }
+    Ok(blockchain
+        .header_tree
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .branch_to_active_chain(&parent_hash, &header_heights))
+}
 
 /// Looks in the headers chain for the first header in common with the locator hashes provided in the getheaders message.
 /// Writes the headers message with the headers to send to the node. Ok(()) in case of success or error in case of failure.
@@ -92,6 +212,7 @@ pub fn handle_getheaders_message(
 ) -> NodeMessageHandlerResult {
     let getheaders_payload = GetHeadersPayload::read_from(payload)
         .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+    let start_string = node_pointers.blockchain.chain_params.magic;
     // check first header in common (provided in locator hashes)
     let first_header_asked = getheaders_payload.locator_hashes[0];
     // check if stop hash is provided
@@ -130,7 +251,10 @@ pub fn handle_getheaders_message(
                 [index_of_first_header_asked..index_of_stop_hash],
         );
     }
-    write_to_node(&tx, HeadersMessage::marshalling(headers_to_send))?;
+    write_to_node(
+        &tx,
+        HeadersMessage::marshalling(start_string, headers_to_send),
+    )?;
     Ok(())
 }
 
@@ -141,7 +265,7 @@ pub fn handle_getdata_message(
     log_sender: &LogSender,
     node_sender: NodeSender,
     payload: &[u8],
-    blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
+    blockchain: &Blockchain,
     accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
 ) -> Result<(), NodeCustomErrors> {
     let mut message_to_send: Vec<u8> = Vec::new();
@@ -150,13 +274,19 @@ pub fn handle_getdata_message(
     let mut notfound_inventories: Vec<Inventory> = Vec::new();
     for inv in inventories {
         if inv.type_identifier == MSG_TX {
-            handle_tx_inventory(log_sender, &inv, &accounts, &node_sender)?;
+            handle_tx_inventory(
+                log_sender,
+                blockchain.chain_params.magic,
+                &inv,
+                &accounts,
+                &node_sender,
+            )?;
         }
-        if inv.type_identifier == MSG_BLOCK {
+        if inv.type_identifier == MSG_BLOCK || inv.type_identifier == MSG_WITNESS_BLOCK {
             handle_block_inventory(
                 log_sender,
                 &inv,
-                &blocks,
+                blockchain,
                 &mut message_to_send,
                 &mut notfound_inventories,
             )?;
@@ -164,7 +294,8 @@ pub fn handle_getdata_message(
     }
     if !notfound_inventories.is_empty() {
         // There is a block or more that were not found in the blockchain
-        let notfound_message = get_notfound_message(notfound_inventories);
+        let notfound_message =
+            get_notfound_message(blockchain.chain_params.magic, notfound_inventories);
         message_to_send.extend_from_slice(&notfound_message);
     }
     write_to_node(&node_sender, message_to_send)?;
@@ -172,23 +303,35 @@ pub fn handle_getdata_message(
 }
 
 /// Receives an inventory, a pointer to the blockchain, a pointer to the node sender and a pointer to the log sender.
-/// It checks if the block of the inventory is in the blockchain and if so it adds it to the message to send. If it is not in the blockchain
-/// it adds it to the notfound inventories list. Returns Ok(()) if it can be added correctly or error of type NodeHandlerError if it cannot.
+/// It checks if the block of the inventory is in the blockchain (in memory or in disk) and if so it adds it to the message to send.
+/// If it is not in the blockchain or it was pruned it adds it to the notfound inventories list. Returns Ok(()) if it can be added correctly or error
+/// of type NodeHandlerError if it cannot.
 fn handle_block_inventory(
     log_sender: &LogSender,
     inventory: &Inventory,
-    blocks: &Arc<RwLock<HashMap<[u8; 32], Block>>>,
+    blockchain: &Blockchain,
     message_to_send: &mut Vec<u8>,
     notfound_inventories: &mut Vec<Inventory>,
 ) -> Result<(), NodeCustomErrors> {
     let block_hash = inventory.hash;
-    match blocks
-        .read()
-        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-        .get(&block_hash)
-    {
+    if blockchain.is_block_pruned(&block_hash) {
+        write_in_log(
+            &log_sender.error_log_sender,
+            &format!(
+                "Block requested was pruned: {}",
+                crate::account::bytes_to_hex_string(&inventory.hash)
+            ),
+        );
+        notfound_inventories.push(inventory.clone());
+        return Ok(());
+    }
+    match blockchain.search_block(block_hash) {
         Some(block) => {
-            message_to_send.extend_from_slice(&get_block_message(block));
+            message_to_send.extend_from_slice(&get_block_message(
+                blockchain.chain_params.magic,
+                &block,
+                inventory.type_identifier == MSG_WITNESS_BLOCK,
+            ));
         }
         None => {
             write_in_log(
@@ -207,6 +350,7 @@ fn handle_block_inventory(
 /// Checks if the transaction of the inventory is in any of the accounts of the wallet and if so it sends it through the channel to be written in the node.
 fn handle_tx_inventory(
     log_sender: &LogSender,
+    start_string: [u8; 4],
     inventory: &Inventory,
     accounts: &Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
     node_sender: &NodeSender,
@@ -223,7 +367,7 @@ fn handle_tx_inventory(
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
         {
             if tx.hash() == inventory.hash {
-                let tx_message = get_tx_message(tx);
+                let tx_message = get_tx_message(start_string, tx);
                 write_to_node(node_sender, tx_message)?;
                 write_in_log(
                     &log_sender.info_log_sender,
@@ -245,46 +389,88 @@ pub fn handle_block_message(
 ) -> NodeMessageHandlerResult {
     let new_block = BlockMessage::unmarshalling(&payload.to_vec())
         .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
-    if new_block.validate().0 {
-        let header_is_not_included_yet = header_is_not_included(
-            new_block.block_header,
-            node_pointers.blockchain.headers.clone(),
-        )?;
-        if header_is_not_included_yet {
-            include_new_header(
-                log_sender,
-                new_block.block_header,
-                node_pointers.blockchain.headers.clone(),
-                node_pointers.blockchain.header_heights.clone(),
-            )?;
-            new_block
-                .give_me_utxos(node_pointers.blockchain.utxo_set.clone())
-                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
-            update_accounts_utxo_set(
-                node_pointers.accounts.clone(),
-                node_pointers.blockchain.utxo_set,
-            )?;
-            new_block.contains_pending_tx(log_sender, ui_sender, node_pointers.accounts.clone())?;
-            include_new_block(
-                log_sender,
-                ui_sender,
-                new_block,
-                node_pointers.blockchain.blocks,
-            )?;
-        }
-    } else {
+    process_new_block(log_sender, ui_sender, new_block, &node_pointers)?;
+    Ok(())
+}
+
+/// Receives a new block, received from a node or mined locally, and if it is valid and is not included yet, connects it to the
+/// active chain or adds it to the competing branches. If the block becomes the tip of the active chain it is announced to all the
+/// connected nodes with a headers message, so that it is relayed through the network.
+/// Returns Ok(true) if the block is the new tip of the active chain, Ok(false) if it is not or error of type NodeCustomErrors.
+pub fn process_new_block(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    new_block: Block,
+    node_pointers: &NodeDataPointers,
+) -> Result<bool, NodeCustomErrors> {
+    // The transactions and their scripts are validated when the block is connected, against the utxo set it spends
+    if !new_block.validate().0 {
         write_in_log(
             &log_sender.error_log_sender,
             "NEW BLOCK MESSAGE ERROR: The block is not valid",
         );
+        return Ok(false);
     }
-    Ok(())
+    if !header_is_not_included(new_block.block_header, &node_pointers.blockchain)? {
+        return Ok(false);
+    }
+    let header = new_block.block_header;
+    // If the block extends the tip its header is validated against the active chain, as the block may not
+    // have been announced before in a headers message (for example, if it was mined locally)
+    let context_validation = {
+        let headers = node_pointers
+            .blockchain
+            .headers
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        match headers.last() {
+            Some(tip) if tip.hash() == header.previous_block_header_hash => {
+                Some(validate_header_context(
+                    &header,
+                    &HeaderChain::new(&headers, &[]),
+                    node_pointers.blockchain.chain_params,
+                    Utc::now().timestamp(),
+                ))
+            }
+            _ => None,
+        }
+    };
+    if let Some(context_validation) = context_validation {
+        if let Err(err) = context_validation {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!(
+                    "NEW BLOCK MESSAGE ERROR: The header of the block {} is not valid: {}",
+                    header.hex_hash(),
+                    err
+                )
+                .as_str(),
+            );
+            return Ok(false);
+        }
+        connect_new_block(log_sender, ui_sender, new_block, node_pointers)?;
+    } else {
+        include_block_of_competing_branch(log_sender, ui_sender, new_block, node_pointers)?;
+    }
+    let new_tip_hash = node_pointers
+        .blockchain
+        .headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .last()
+        .map(|tip| tip.hash());
+    if new_tip_hash != Some(header.hash()) {
+        return Ok(false);
+    }
+    announce_new_block(node_pointers, header)?;
+    Ok(true)
 }
 
-/// Recieves a NodeSender and the payload of the inv message and creates the inventories to ask for the incoming
-/// txs the node sent via inv. Returns error in case of failure or Ok(()) otherwise.
+/// Recieves a NodeSender, the start string of the network and the payload of the inv message and creates the inventories
+/// to ask for the incoming txs the node sent via inv. Returns error in case of failure or Ok(()) otherwise.
 pub fn handle_inv_message(
     tx: NodeSender,
+    start_string: [u8; 4],
     payload: &[u8],
     transactions_received: Arc<RwLock<Vec<[u8; 32]>>>,
 ) -> NodeMessageHandlerResult {
@@ -311,16 +497,20 @@ pub fn handle_inv_message(
         offset += 36;
     }
     if !inventories.is_empty() {
-        ask_for_incoming_tx(tx, inventories)?;
+        ask_for_incoming_tx(tx, start_string, inventories)?;
     }
     Ok(())
 }
 
-/// Receives a NodeSender and a payload and sends the corresponding pong message through the channel to be written by the node
-/// and the ping is answered. Returns Ok(()) in case it can be sent well by the channel or Error of channel otherwise.
-pub fn handle_ping_message(tx: NodeSender, payload: &[u8]) -> NodeMessageHandlerResult {
+/// Receives a NodeSender, the start string of the network and a payload and sends the corresponding pong message through the
+/// channel to be written by the node and the ping is answered. Returns Ok(()) in case it can be sent well by the channel or Error of channel otherwise.
+pub fn handle_ping_message(
+    tx: NodeSender,
+    start_string: [u8; 4],
+    payload: &[u8],
+) -> NodeMessageHandlerResult {
     let header = HeaderMessage {
-        start_string: START_STRING,
+        start_string,
         command_name: "pong".to_string(),
         payload_size: payload.len() as u32,
         checksum: get_checksum(payload),
@@ -348,33 +538,272 @@ pub fn handle_tx_message(
     Ok(())
 }
 
+/// Receives the payload of an addr message, or of an addrv2 message (BIP155) if addrv2 is true, and adds its addresses
+/// to the address manager with the ip of the node that sent them as their source. Returns Ok(()) in case of success
+/// or error of type NodeCustomErrors if the payload is not valid.
+pub fn handle_addr_message(
+    log_sender: &LogSender,
+    payload: &[u8],
+    addrv2: bool,
+    source: IpAddr,
+    address_manager: &AddressManagerPointer,
+) -> NodeMessageHandlerResult {
+    let addresses = match addrv2 {
+        true => unmarshalling_addrv2_payload(payload),
+        false => unmarshalling_addr_payload(payload),
+    }
+    .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+    let mut address_manager = address_manager
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let amount_of_new_addresses = addresses
+        .into_iter()
+        .filter(|address| address_manager.add(*address, source))
+        .count();
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!(
+            "{} new addresses of nodes received from {}",
+            amount_of_new_addresses, source
+        )
+        .as_str(),
+    );
+    Ok(())
+}
+
+/// Answers the getaddr message with an addr message with up to 1000 random addresses of the address manager,
+/// sent through the channel to be written by the node. Nothing is sent if the address manager is empty.
+/// Returns Ok(()) in case of success or error of type NodeCustomErrors in case of failure.
+pub fn handle_getaddr_message(
+    tx: NodeSender,
+    start_string: [u8; 4],
+    address_manager: &AddressManagerPointer,
+) -> NodeMessageHandlerResult {
+    let addresses = address_manager
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .get_addresses(MAX_ADDR_PER_MESSAGE);
+    if addresses.is_empty() {
+        return Ok(());
+    }
+    write_to_node(&tx, get_addr_message(start_string, &addresses))
+}
+
+/// Receives the payload of the getcfilters message and sends a cfilter message with the basic filter of each block
+/// of the range asked (BIP157). The request is logged and ignored if the filter index is disabled or the range is not
+/// valid or not indexed. Returns Ok(()) in case of success or error of type NodeCustomErrors in case of failure.
+pub fn handle_getcfilters_message(
+    log_sender: &LogSender,
+    tx: NodeSender,
+    payload: &[u8],
+    blockchain: &Blockchain,
+) -> NodeMessageHandlerResult {
+    let getcfilters_payload = GetCFiltersPayload::read_from(payload)
+        .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+    let filter_index = match &blockchain.filter_index {
+        Some(filter_index) => filter_index
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?,
+        None => {
+            return ignore_filters_request(
+                log_sender,
+                "getcfilters",
+                "the filter index is disabled",
+            )
+        }
+    };
+    let stop_height = match get_stop_height_of_range(
+        &filter_index,
+        &getcfilters_payload,
+        MAX_CFILTERS_PER_REQUEST,
+    ) {
+        Ok(stop_height) => stop_height,
+        Err(reason) => return ignore_filters_request(log_sender, "getcfilters", reason),
+    };
+    let mut message_to_send: Vec<u8> = Vec::new();
+    for height in getcfilters_payload.start_height as usize..=stop_height {
+        if let Some(entry) = filter_index.get(height)? {
+            message_to_send.extend(get_cfilter_message(
+                blockchain.chain_params.magic,
+                getcfilters_payload.filter_type,
+                entry.block_hash,
+                &entry.filter,
+            ));
+        }
+    }
+    write_to_node(&tx, message_to_send)?;
+    Ok(())
+}
+
+/// Receives the payload of the getcfheaders message and sends the cfheaders message with the hashes of the filters of the
+/// range asked and the filter header of the block before it (BIP157). The request is logged and ignored if the filter index
+/// is disabled or the range is not valid or not indexed. Returns Ok(()) in case of success or error of type NodeCustomErrors in case of failure.
+pub fn handle_getcfheaders_message(
+    log_sender: &LogSender,
+    tx: NodeSender,
+    payload: &[u8],
+    blockchain: &Blockchain,
+) -> NodeMessageHandlerResult {
+    let getcfheaders_payload = GetCFiltersPayload::read_from(payload)
+        .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+    let filter_index = match &blockchain.filter_index {
+        Some(filter_index) => filter_index
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?,
+        None => {
+            return ignore_filters_request(
+                log_sender,
+                "getcfheaders",
+                "the filter index is disabled",
+            )
+        }
+    };
+    let stop_height = match get_stop_height_of_range(
+        &filter_index,
+        &getcfheaders_payload,
+        MAX_CFHEADERS_PER_REQUEST,
+    ) {
+        Ok(stop_height) => stop_height,
+        Err(reason) => return ignore_filters_request(log_sender, "getcfheaders", reason),
+    };
+    let start_height = getcfheaders_payload.start_height as usize;
+    // the filter header before the genesis block is zero
+    let previous_filter_header = match start_height {
+        0 => [0u8; 32],
+        _ => filter_index
+            .filter_header(start_height - 1)
+            .unwrap_or([0u8; 32]),
+    };
+    let filter_hashes: Vec<[u8; 32]> = (start_height..=stop_height)
+        .filter_map(|height| filter_index.filter_hash(height))
+        .collect();
+    write_to_node(
+        &tx,
+        get_cfheaders_message(
+            blockchain.chain_params.magic,
+            getcfheaders_payload.filter_type,
+            getcfheaders_payload.stop_hash,
+            previous_filter_header,
+            &filter_hashes,
+        ),
+    )?;
+    Ok(())
+}
+
+/// Receives the payload of the getcfcheckpt message and sends the cfcheckpt message with the filter headers of every
+/// 1000 blocks up to the stop hash (BIP157). The request is logged and ignored if the filter index is disabled or the
+/// stop hash is not indexed. Returns Ok(()) in case of success or error of type NodeCustomErrors in case of failure.
+pub fn handle_getcfcheckpt_message(
+    log_sender: &LogSender,
+    tx: NodeSender,
+    payload: &[u8],
+    blockchain: &Blockchain,
+) -> NodeMessageHandlerResult {
+    let getcfcheckpt_payload = GetCFCheckptPayload::read_from(payload)
+        .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+    let filter_index = match &blockchain.filter_index {
+        Some(filter_index) => filter_index
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?,
+        None => {
+            return ignore_filters_request(
+                log_sender,
+                "getcfcheckpt",
+                "the filter index is disabled",
+            )
+        }
+    };
+    if getcfcheckpt_payload.filter_type != BASIC_FILTER_TYPE {
+        return ignore_filters_request(log_sender, "getcfcheckpt", "the filter type is unknown");
+    }
+    let stop_height = match filter_index.height_of(&getcfcheckpt_payload.stop_hash) {
+        Some(stop_height) => stop_height,
+        None => {
+            return ignore_filters_request(
+                log_sender,
+                "getcfcheckpt",
+                "the stop hash is not indexed",
+            )
+        }
+    };
+    let filter_headers: Vec<[u8; 32]> = (1..=stop_height / CFCHECKPT_INTERVAL)
+        .filter_map(|checkpoint| filter_index.filter_header(checkpoint * CFCHECKPT_INTERVAL))
+        .collect();
+    write_to_node(
+        &tx,
+        get_cfcheckpt_message(
+            blockchain.chain_params.magic,
+            getcfcheckpt_payload.filter_type,
+            getcfcheckpt_payload.stop_hash,
+            &filter_headers,
+        ),
+    )?;
+    Ok(())
+}
+
 /*
 ***************************************************************************
 ********************** AUXILIAR FUNCTIONS *********************************
 ***************************************************************************
 */
 
-/// Receives the inventories with the tx and the sender to write in the node. Sends the getdata message to ask for the tx.
-fn ask_for_incoming_tx(tx: NodeSender, inventories: Vec<Inventory>) -> NodeMessageHandlerResult {
-    let get_data_message = GetDataMessage::new(inventories);
+/// Returns the height of the stop hash of the range of blocks asked in a getcfilters or getcfheaders message,
+/// or the reason why the range can not be served: the filter type is unknown, the stop hash is not indexed, the
+/// start height is after it or the range has more blocks than the maximum received.
+fn get_stop_height_of_range(
+    filter_index: &FilterIndex,
+    payload: &GetCFiltersPayload,
+    max_blocks: usize,
+) -> Result<usize, &'static str> {
+    if payload.filter_type != BASIC_FILTER_TYPE {
+        return Err("the filter type is unknown");
+    }
+    let stop_height = filter_index
+        .height_of(&payload.stop_hash)
+        .ok_or("the stop hash is not indexed")?;
+    let start_height = payload.start_height as usize;
+    if start_height > stop_height {
+        return Err("the start height is after the stop hash");
+    }
+    if stop_height - start_height >= max_blocks {
+        return Err("too many blocks were asked");
+    }
+    Ok(stop_height)
+}
+
+/// Writes in the error log that the received request of filters is ignored and the reason.
+fn ignore_filters_request(
+    log_sender: &LogSender,
+    command: &str,
+    reason: &str,
+) -> NodeMessageHandlerResult {
+    write_in_log(
+        &log_sender.error_log_sender,
+        format!("The {} message is ignored: {}", command, reason).as_str(),
+    );
+    Ok(())
+}
+
+/// Receives the inventories with the tx, the start string of the network and the sender to write in the node. Sends the getdata message to ask for the tx.
+fn ask_for_incoming_tx(
+    tx: NodeSender,
+    start_string: [u8; 4],
+    inventories: Vec<Inventory>,
+) -> NodeMessageHandlerResult {
+    let get_data_message = GetDataMessage::new(start_string, inventories);
     let get_data_message_bytes = get_data_message.marshalling();
     tx.send(get_data_message_bytes)
         .map_err(|err| NodeCustomErrors::ThreadChannelError(err.to_string()))?;
     Ok(())
 }
 
-/// Receives a block to add to the chain and the Arc pointer pointing to the chain of blocks and adds it.
-/// Returns Ok(()) if it can be added correctly or error of type NodeHandlerError if it cannot.
+/// Receives a block added to the chain, which was already stored when it was connected, and shows it
+/// in the UI and in the log. Returns Ok(()).
 fn include_new_block(
     log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
     block: Block,
-    blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
 ) -> NodeMessageHandlerResult {
-    blocks
-        .write()
-        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-        .insert(block.hash(), block.clone());
     println!("\nNEW BLOCK RECEIVED: {} \n", block.hex_hash());
     send_event_to_ui(ui_sender, UIEvent::AddBlock(block.clone()));
     write_in_log(
@@ -386,14 +815,19 @@ fn include_new_block(
 }
 
 /// Receives a header to add to the headers chain and the Arc pointer pointing to the headers chain and adds it
-/// to the list of headers and to the dictionary of headers heights. 
+/// to the list of headers, to the header store and to the dictionary of headers heights. 
 /// Returns Ok(()) if it can be added correctly or error of type NodeHandlerError if it cannot.
 fn include_new_header(
     log_sender: &LogSender,
     header: BlockHeader,
     headers: Arc<RwLock<Vec<BlockHeader>>>,
     headers_heights: Arc<RwLock<HashMap<[u8; 32], usize>>>,
+    header_store: Arc<RwLock<HeaderStore>>,
 ) -> NodeMessageHandlerResult {
+    header_store
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .append(&[header])?;
     headers
         .write()
         .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
@@ -416,33 +850,372 @@ fn include_new_header(
     Ok(())
 }
 
-/// Recibe un header y la lista de headers y se fija en los ulitmos 10 headers de la lista, si es que existen, que el header
-/// no este incluido ya. En caso de estar incluido devuelve false y en caso de nos estar incluido devuelve true. Devuelve error en caso de
-/// que no se pueda leer la lista de headers
-/// Receives a header and the headers list and checks the last 10 headers of the list, if they exist, that the header
-/// is not already included. If it is included it returns false, otherwise true. Returns error if the list of headers cannot be read.
+/// Receives a header and the blockchain and checks that the header is not already included in the active chain
+/// or in a competing branch. If it is included it returns false, otherwise true. Returns error if the blockchain cannot be read.
 fn header_is_not_included(
     header: BlockHeader,
-    headers: Arc<RwLock<Vec<BlockHeader>>>,
+    blockchain: &Blockchain,
 ) -> Result<bool, NodeCustomErrors> {
-    let headers_guard = headers
+    let hash = header.hash();
+    let is_in_active_chain = blockchain
+        .header_heights
         .read()
-        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
-    let start_index = headers_guard.len().saturating_sub(10);
-    let last_10_headers = &headers_guard[start_index..];
-    // Verify that the header is not included in the last 10 headers
-    for included_header in last_10_headers.iter() {
-        if *included_header == header {
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .contains_key(&hash);
+    let is_in_competing_branch = blockchain
+        .header_tree
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .contains_side_header(&hash);
+    Ok(!is_in_active_chain && !is_in_competing_branch)
+}
+
+/// Connects the block to the tip of the active chain and writes the changes of the utxo set in the chainstate.
+/// Returns Ok(true) if it is connected, Ok(false) if its transactions are not valid or error of type
+/// NodeCustomErrors if it cannot be connected.
+fn connect_new_block(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    block: Block,
+    node_pointers: &NodeDataPointers,
+) -> Result<bool, NodeCustomErrors> {
+    let connected = connect_block_to_tip(log_sender, ui_sender, block, node_pointers)?;
+    if connected {
+        node_pointers.blockchain.flush_chainstate()?;
+    }
+    Ok(connected)
+}
+
+/// Connects the block to the tip of the active chain. Validates its transactions and updates the utxo set, adds
+/// its header to the headers chain, updates the accounts and adds the block to the blocks chain. The chainstate
+/// is not written, so that the blocks of a reorganization are written at once.
+/// Returns Ok(true) if it is connected, Ok(false) if its transactions are not valid or error of type
+/// NodeCustomErrors if it cannot be connected.
+fn connect_block_to_tip(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    block: Block,
+    node_pointers: &NodeDataPointers,
+) -> Result<bool, NodeCustomErrors> {
+    let blockchain = &node_pointers.blockchain;
+    let height = blockchain
+        .headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .len();
+    match blockchain.connect_block(&block, height) {
+        Err(NodeCustomErrors::InvalidBlockError(reason)) => {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!(
+                    "NEW BLOCK MESSAGE ERROR: The block {} is invalid: {}",
+                    block.hex_hash(),
+                    reason
+                )
+                .as_str(),
+            );
             return Ok(false);
         }
+        result => result?,
     }
+    include_new_header(
+        log_sender,
+        block.block_header,
+        blockchain.headers.clone(),
+        blockchain.header_heights.clone(),
+        blockchain.header_store.clone(),
+    )?;
+    sync_header_tree(blockchain)?;
+    update_accounts_utxo_set(node_pointers.accounts.clone(), blockchain)?;
+    block.contains_pending_tx(log_sender, ui_sender, node_pointers.accounts.clone())?;
+    include_new_block(log_sender, ui_sender, block)?;
     Ok(true)
 }
 
-/// Updates the utxo_set of each account. Returns Ok(()) if it can be updated correctly or error of type NodeHandlerError if it cannot.
+/// Receives a block that does not extend the tip of the active chain and adds it to the competing branches.
+/// If the branch of the block has more cumulative work than the active chain, the chain is reorganized.
+/// Blocks whose parent is unknown or whose header is not valid in the chain of its ancestors are discarded.
+fn include_block_of_competing_branch(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    block: Block,
+    node_pointers: &NodeDataPointers,
+) -> NodeMessageHandlerResult {
+    let blockchain = &node_pointers.blockchain;
+    sync_header_tree(blockchain)?;
+    // The header is validated against the chain of its ancestors, as the block may not have been announced
+    // before in a headers message and it can become part of the active chain in a reorganization
+    if let Some((fork_height, branch)) = get_branch_of_parent(blockchain, &block.block_header)? {
+        let context_validation = validate_header_context(
+            &block.block_header,
+            &HeaderChain::new(
+                &blockchain
+                    .headers
+                    .read()
+                    .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[..=fork_height],
+                &branch,
+            ),
+            blockchain.chain_params,
+            Utc::now().timestamp(),
+        );
+        if let Err(err) = context_validation {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!(
+                    "NEW BLOCK MESSAGE ERROR: The header of the block {} is not valid: {}",
+                    block.hex_hash(),
+                    err
+                )
+                .as_str(),
+            );
+            return Ok(());
+        }
+    }
+    let parent_hash = block.block_header.previous_block_header_hash;
+    let parent_height = blockchain
+        .header_heights
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .get(&parent_hash)
+        .copied();
+    let mut header_tree = blockchain
+        .header_tree
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let parent = match parent_height {
+        Some(height) => header_tree
+            .active_chainwork(height)
+            .map(|chainwork| (height, chainwork)),
+        None => header_tree
+            .get_side_header(&parent_hash)
+            .map(|node| (node.height, node.chainwork)),
+    };
+    let (parent_height, parent_chainwork) = match parent {
+        Some(parent) => parent,
+        None => {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!(
+                    "NEW BLOCK MESSAGE ERROR: The parent of the block {} is unknown",
+                    block.hex_hash()
+                )
+                .as_str(),
+            );
+            return Ok(());
+        }
+    };
+    let node = header_tree.insert_side_header(block.block_header, parent_height, parent_chainwork);
+    let branch_has_more_work = node.chainwork > header_tree.tip_chainwork();
+    drop(header_tree);
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!(
+            "NEW BLOCK OF A COMPETING BRANCH RECEIVED: -- {} -- at height {}",
+            block.hex_hash(),
+            node.height
+        )
+        .as_str(),
+    );
+    let block_hash = block.hash();
+    blockchain.store_block(&block, node.height)?;
+    if branch_has_more_work {
+        reorganize_chain(log_sender, ui_sender, node_pointers, block_hash)?;
+    }
+    Ok(())
+}
+
+/// Makes the branch that ends in new_tip_hash the active chain. Disconnects the blocks of the active chain
+/// from the tip to the fork, restoring the outputs they spent and reverting the wallet transactions they confirmed,
+/// and then connects the blocks of the new branch. The chainstate is written once all the blocks are connected.
+/// If any of the blocks is not available the chain is not modified.
+fn reorganize_chain(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    node_pointers: &NodeDataPointers,
+    new_tip_hash: [u8; 32],
+) -> NodeMessageHandlerResult {
+    let blockchain = &node_pointers.blockchain;
+    let branch = blockchain
+        .header_tree
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .branch_to_active_chain(
+            &new_tip_hash,
+            &*blockchain
+                .header_heights
+                .read()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?,
+        );
+    let (fork_height, branch) = match branch {
+        Some(branch) => branch,
+        None => return Ok(()),
+    };
+    let disconnected_headers: Vec<BlockHeader> = blockchain
+        .headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[fork_height + 1..]
+        .to_vec();
+    // The blocks to disconnect also need their undo data to restore the utxo set
+    let disconnected_blocks: Option<Vec<Block>> = disconnected_headers
+        .iter()
+        .rev()
+        .filter(|header| blockchain.has_undo_data(&header.hash()))
+        .map(|header| blockchain.search_block(header.hash()))
+        .collect();
+    let connected_blocks: Option<Vec<Block>> = branch
+        .iter()
+        .map(|header| blockchain.search_block(header.hash()))
+        .collect();
+    let (disconnected_blocks, connected_blocks) = match (disconnected_blocks, connected_blocks) {
+        (Some(disconnected_blocks), Some(connected_blocks))
+            if disconnected_blocks.len() == disconnected_headers.len() =>
+        {
+            (disconnected_blocks, connected_blocks)
+        }
+        _ => {
+            write_in_log(
+                &log_sender.error_log_sender,
+                "REORGANIZATION ERROR: Not all the blocks of the branches are available",
+            );
+            return Ok(());
+        }
+    };
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!(
+            "REORGANIZATION: disconnecting {} blocks and connecting {} blocks from height {}",
+            disconnected_blocks.len(),
+            connected_blocks.len(),
+            fork_height + 1
+        )
+        .as_str(),
+    );
+    disconnect_blocks(log_sender, ui_sender, node_pointers, &disconnected_blocks)?;
+    move_headers_to_competing_branch(blockchain, fork_height)?;
+    for block in connected_blocks {
+        let block_hash = block.hash();
+        blockchain
+            .header_tree
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .remove_side_header(&block_hash);
+        if !connect_block_to_tip(log_sender, ui_sender, block, node_pointers)? {
+            // The invalid block is discarded and the previous chain is restored if it has more work
+            blockchain
+                .blocks
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .remove(&block_hash);
+            restore_previous_chain(log_sender, ui_sender, node_pointers, &disconnected_headers)?;
+            break;
+        }
+    }
+    blockchain.flush_chainstate()?;
+    Ok(())
+}
+
+/// Reorganizes the blockchain back to the chain that ends in the last of the disconnected headers,
+/// if it has more work than the active chain.
+fn restore_previous_chain(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    node_pointers: &NodeDataPointers,
+    disconnected_headers: &[BlockHeader],
+) -> NodeMessageHandlerResult {
+    let previous_tip_hash = match disconnected_headers.last() {
+        Some(previous_tip) => previous_tip.hash(),
+        None => return Ok(()),
+    };
+    let previous_chain_has_more_work = {
+        let header_tree = node_pointers
+            .blockchain
+            .header_tree
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        header_tree
+            .get_side_header(&previous_tip_hash)
+            .map(|node| node.chainwork > header_tree.tip_chainwork())
+            .unwrap_or(false)
+    };
+    if previous_chain_has_more_work {
+        reorganize_chain(log_sender, ui_sender, node_pointers, previous_tip_hash)?;
+    }
+    Ok(())
+}
+
+/// Reverts the changes made by the blocks in the utxo set, using their undo data, and in the transactions of the accounts.
+/// The blocks must be ordered from the tip of the active chain backwards.
+fn disconnect_blocks(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    node_pointers: &NodeDataPointers,
+    blocks_to_disconnect: &[Block],
+) -> NodeMessageHandlerResult {
+    let blockchain = &node_pointers.blockchain;
+    for block in blocks_to_disconnect {
+        blockchain.disconnect_block(block)?;
+        block.revert_confirmed_txs(log_sender, ui_sender, node_pointers.accounts.clone())?;
+        write_in_log(
+            &log_sender.info_log_sender,
+            format!("BLOCK DISCONNECTED: -- {} --", block.hex_hash()).as_str(),
+        );
+    }
+    update_accounts_utxo_set(node_pointers.accounts.clone(), blockchain)?;
+    Ok(())
+}
+
+/// Removes from the active chain and from the header store the headers above the fork height and adds them to the
+/// competing branches.
+fn move_headers_to_competing_branch(
+    blockchain: &Blockchain,
+    fork_height: usize,
+) -> NodeMessageHandlerResult {
+    let mut headers = blockchain
+        .headers
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let mut header_heights = blockchain
+        .header_heights
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let mut header_tree = blockchain
+        .header_tree
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    for (height, header) in headers.iter().enumerate().skip(fork_height + 1) {
+        header_heights.remove(&header.hash());
+        let parent_chainwork = header_tree.active_chainwork(height - 1).unwrap_or_default();
+        header_tree.insert_side_header(*header, height - 1, parent_chainwork);
+    }
+    headers.truncate(fork_height + 1);
+    // The genesis header is not in the header store
+    blockchain
+        .header_store
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .truncate(fork_height)?;
+    header_tree.truncate_active_chain(fork_height);
+    Ok(())
+}
+
+/// Calculates the chainwork of the headers of the active chain that are not in the header tree yet.
+fn sync_header_tree(blockchain: &Blockchain) -> NodeMessageHandlerResult {
+    let headers = blockchain
+        .headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    blockchain
+        .header_tree
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .sync_active_chain(&headers);
+    Ok(())
+}
+
+/// Updates the utxo_set of each account with its utxos in the blockchain. Returns Ok(()) if it can be updated correctly or error of type NodeHandlerError if it cannot.
 fn update_accounts_utxo_set(
     accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
-    utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
+    blockchain: &Blockchain,
 ) -> Result<(), NodeCustomErrors> {
     let accounts_lock = accounts
         .read()
@@ -452,18 +1225,37 @@ fn update_accounts_utxo_set(
         .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
 
     for account_lock in accounts_inner_lock.iter_mut() {
-        account_lock
-            .set_utxos(utxo_set.clone())
-            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let utxos = blockchain.utxos_of_address(account_lock.get_address())?;
+        account_lock.load_utxos(utxos);
+    }
+    Ok(())
+}
+
+/// Announces the header of a new block to all the connected nodes with a headers message, so that they ask for the block.
+/// The nodes whose channel is closed are disconnected, so they are ignored.
+/// Returns error of type NodeCustomErrors if the senders of the nodes cannot be read.
+fn announce_new_block(
+    node_pointers: &NodeDataPointers,
+    header: BlockHeader,
+) -> NodeMessageHandlerResult {
+    let headers_message =
+        HeadersMessage::marshalling(node_pointers.blockchain.chain_params.magic, vec![header]);
+    for node_sender in node_pointers
+        .nodes_senders
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .iter()
+    {
+        let _ = write_to_node(node_sender, headers_message.clone());
     }
     Ok(())
 }
 
-/// Returns the tx message according to the received transaction
-fn get_tx_message(tx: &Transaction) -> Vec<u8> {
+/// Returns the tx message according to the received transaction and the start string of the network
+fn get_tx_message(start_string: [u8; 4], tx: &Transaction) -> Vec<u8> {
     let mut tx_payload = vec![];
     tx.marshalling(&mut tx_payload);
-    let header = HeaderMessage::new("tx".to_string(), Some(&tx_payload));
+    let header = HeaderMessage::new(start_string, "tx".to_string(), Some(&tx_payload));
     let mut tx_message = vec![];
     tx_message.extend_from_slice(&header.to_le_bytes());
     tx_message.extend_from_slice(&tx_payload);
@@ -487,7 +1279,7 @@ fn get_index_of_header(
     header_hash: [u8; 32],
     node_pointers: NodeDataPointers,
 ) -> Result<usize, NodeCustomErrors> {
-    if header_hash == GENESIS_BLOCK_HASH {
+    if header_hash == node_pointers.blockchain.chain_params.genesis_hash() {
         return Ok(0);
     }
     match node_pointers
@@ -4,7 +4,10 @@ use crate::{
     custom_errors::NodeCustomErrors,
     gtk::ui_events::UIEvent,
     logwriter::log_writer::{write_in_log, LogSender},
-    messages::{message_header::is_terminated, message_header::HeaderMessage},
+    messages::{
+        addr_message::get_getaddr_message, message_header::is_terminated,
+        message_header::HeaderMessage,
+    },
     node_data_pointers::NodeDataPointers,
 };
 use std::{
@@ -16,24 +19,29 @@ use std::{
         Arc, Mutex, RwLock,
     },
     thread::{self, JoinHandle},
+    time::Duration,
 };
 
 use super::message_handlers::{
-    handle_block_message, handle_getdata_message, handle_getheaders_message,
-    handle_headers_message, handle_inv_message, handle_ping_message, handle_tx_message,
-    write_to_node,
+    handle_addr_message, handle_block_message, handle_getaddr_message, handle_getcfcheckpt_message,
+    handle_getcfheaders_message, handle_getcfilters_message, handle_getdata_message,
+    handle_getheaders_message, handle_headers_message, handle_inv_message, handle_ping_message,
+    handle_tx_message, write_to_node,
 };
 
 type NodeMessageHandlerResult = Result<(), NodeCustomErrors>;
 type NodeSender = Sender<Vec<u8>>;
 type NodeReceiver = Receiver<Vec<u8>>;
 
+// Time that the thread of a node waits for a message before writing the pending messages again
+const MESSAGE_WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+
 #[derive(Debug, Clone)]
 /// Struct to control all the nodes connected to ours. It listens permanently
 /// to these and decides what to do with the messages that arrive and with those that it has to write.
 pub struct NodeMessageHandler {
     nodes_handle: Arc<Mutex<Vec<JoinHandle<()>>>>,
-    nodes_sender: Vec<NodeSender>, // Stores all the sender to write to the nodes
+    nodes_sender: Arc<RwLock<Vec<NodeSender>>>, // Stores all the sender to write to the nodes, shared with the node pointers
     transactions_recieved: Arc<RwLock<Vec<[u8; 32]>>>,
     finish: Arc<RwLock<bool>>,
 }
@@ -41,7 +49,7 @@ pub struct NodeMessageHandler {
 impl NodeMessageHandler {
     /// Receives the information that the node has (headers, blocks and connected nodes)
     /// and is responsible for creating a thread for each node and leaving it listening to messages
-    /// and handling them in a timely manner. If an error occurs, it returns an Error of the enum
+    /// and handling them in a timely manner. The addresses of other nodes are asked to each connected node. If an error occurs, it returns an Error of the enum
     /// NodeCustomErrors and otherwise returns the new struct.
     pub fn new(
         log_sender: &LogSender,
@@ -55,12 +63,20 @@ impl NodeMessageHandler {
         let finish = Arc::new(RwLock::new(false));
         let mut nodes_handle: Vec<JoinHandle<()>> = vec![];
         let amount_nodes = get_amount_of_nodes(node_pointers.connected_nodes.clone())?;
-        let mut nodes_sender = vec![];
+        let nodes_sender = node_pointers.nodes_senders.clone();
         // list of received transactions to not receive the same from several nodes
         let transactions_recieved: Arc<RwLock<Vec<[u8; 32]>>> = Arc::new(RwLock::new(Vec::new()));
         for _ in 0..amount_nodes {
             let (tx, rx) = channel();
-            nodes_sender.push(tx.clone());
+            nodes_sender
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                .push(tx.clone());
+            // the getaddr message is written as soon as the thread of the node starts
+            write_to_node(
+                &tx,
+                get_getaddr_message(node_pointers.blockchain.chain_params.magic),
+            )?;
             let node = get_last_node(node_pointers.connected_nodes.clone())?;
             println!(
                 "Node -{:?}- Listening for new blocks and transactions...\n",
@@ -89,8 +105,12 @@ impl NodeMessageHandler {
     /// In this way the message is broadcast to all connected nodes.
     /// Returns Ok(()) in case of success or a ThreadChannelError error otherwise.
     pub fn broadcast_to_nodes(&self, message: Vec<u8>) -> NodeMessageHandlerResult {
+        let nodes_sender = self
+            .nodes_sender
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
         let mut amount_of_failed_nodes = 0;
-        for node_sender in &self.nodes_sender {
+        for node_sender in nodes_sender.iter() {
             // If any of the channels is closed it means that for some reason the node failed so I ignore it and try to broadcast
             // in the remaining next nodes
             if write_to_node(node_sender, message.clone()).is_err() {
@@ -99,7 +119,7 @@ impl NodeMessageHandler {
             }
         }
         // If all the nodes failed, it means that there are no nodes connected to the node --> Broadcasting failed
-        if amount_of_failed_nodes == self.nodes_sender.len() {
+        if amount_of_failed_nodes == nodes_sender.len() {
             return Err(NodeCustomErrors::ThreadChannelError(
                 "All nodes failed".to_string(),
             ));
@@ -109,7 +129,7 @@ impl NodeMessageHandler {
 
     /// Updates the value of the finish pointer that cuts the cycles of the nodes that are being listened to.
     /// It does the join in each one of the threads for each node that was being listened to.
-    /// For each end of the channel to write to the nodes it performs drop() to close the channel.
+    /// The ends of the channels to write to the nodes are removed from the shared list to close the channels.
     /// Returns Ok(()) if everything went well or specific Error otherwise.
     pub fn finish(&self) -> NodeMessageHandlerResult {
         *self
@@ -128,9 +148,10 @@ impl NodeMessageHandler {
                 .join()
                 .map_err(|err| NodeCustomErrors::ThreadJoinError(format!("{:?}", err)))?;
         }
-        for node_sender in self.nodes_sender.clone() {
-            drop(node_sender);
-        }
+        self.nodes_sender
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .clear();
         Ok(())
     }
 
@@ -146,7 +167,10 @@ impl NodeMessageHandler {
         connection: TcpStream,
     ) -> NodeMessageHandlerResult {
         let (tx, rx) = channel();
-        self.nodes_sender.push(tx.clone());
+        self.nodes_sender
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .push(tx.clone());
         println!(
             "Node -{:?}- Listening for new blocks and transactions...\n NEW CONNECTION ADDED!!!",
             connection.peer_addr()
@@ -187,13 +211,29 @@ pub fn handle_messages_from_node(
         let mut error: Option<NodeCustomErrors> = None;
         while !is_terminated(finish.clone()) {
             // If something was sent to write, it is written
-            if let Ok(message) = rx.try_recv() {
+            while let Ok(message) = rx.try_recv() {
                 if let Err(err) = write_message_in_node(&mut node, &message) {
                     error = Some(err);
                     break;
                 }
             }
-            let header = match read_header(&mut node, finish.clone()) {
+            if error.is_some() {
+                break;
+            }
+            // If the node is quiet (as in a local regtest network), it does not block the writing of the messages
+            match wait_for_message(&node) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            }
+            let header = match read_header(
+                &mut node,
+                node_pointers.blockchain.chain_params.magic,
+                finish.clone(),
+            ) {
                 Err(NodeCustomErrors::OtherError(_)) => {
                     // Not enough data available, continue
                     continue;
@@ -218,20 +258,14 @@ pub fn handle_messages_from_node(
 
             match command_name {
                 "headers" => handle_message(&mut error, || {
-                    handle_headers_message(
-                        &log_sender,
-                        tx.clone(),
-                        &payload,
-                        node_pointers.blockchain.headers.clone(),
-                        node_pointers.clone(),
-                    )
+                    handle_headers_message(&log_sender, tx.clone(), &payload, node_pointers.clone())
                 }),
                 "getdata" => handle_message(&mut error, || {
                     handle_getdata_message(
                         &log_sender,
                         tx.clone(),
                         &payload,
-                        node_pointers.blockchain.blocks.clone(),
+                        &node_pointers.blockchain,
                         node_pointers.accounts.clone(),
                     )
                 }),
@@ -239,9 +273,20 @@ pub fn handle_messages_from_node(
                     handle_block_message(&log_sender, &ui_sender, &payload, node_pointers.clone())
                 }),
                 "inv" => handle_message(&mut error, || {
-                    handle_inv_message(tx.clone(), &payload, transactions_recieved.clone())
+                    handle_inv_message(
+                        tx.clone(),
+                        node_pointers.blockchain.chain_params.magic,
+                        &payload,
+                        transactions_recieved.clone(),
+                    )
+                }),
+                "ping" => handle_message(&mut error, || {
+                    handle_ping_message(
+                        tx.clone(),
+                        node_pointers.blockchain.chain_params.magic,
+                        &payload,
+                    )
                 }),
-                "ping" => handle_message(&mut error, || handle_ping_message(tx.clone(), &payload)),
                 "tx" => handle_message(&mut error, || {
                     handle_tx_message(
                         &log_sender,
@@ -258,6 +303,51 @@ pub fn handle_messages_from_node(
                         node_pointers.clone(),
                     )
                 }),
+                "addr" | "addrv2" => handle_message(&mut error, || {
+                    let source = node
+                        .peer_addr()
+                        .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?
+                        .ip()
+                        .to_canonical();
+                    handle_addr_message(
+                        &log_sender,
+                        &payload,
+                        command_name == "addrv2",
+                        source,
+                        &node_pointers.address_manager,
+                    )
+                }),
+                "getaddr" => handle_message(&mut error, || {
+                    handle_getaddr_message(
+                        tx.clone(),
+                        node_pointers.blockchain.chain_params.magic,
+                        &node_pointers.address_manager,
+                    )
+                }),
+                "getcfilters" => handle_message(&mut error, || {
+                    handle_getcfilters_message(
+                        &log_sender,
+                        tx.clone(),
+                        &payload,
+                        &node_pointers.blockchain,
+                    )
+                }),
+                "getcfheaders" => handle_message(&mut error, || {
+                    handle_getcfheaders_message(
+                        &log_sender,
+                        tx.clone(),
+                        &payload,
+                        &node_pointers.blockchain,
+                    )
+                }),
+                "getcfcheckpt" => handle_message(&mut error, || {
+                    handle_getcfcheckpt_message(
+                        &log_sender,
+                        tx.clone(),
+                        &payload,
+                        &node_pointers.blockchain,
+                    )
+                }),
                 _ => {
                     write_in_log(
                         &log_sender.message_log_sender,
@@ -337,9 +427,39 @@ pub fn write_message_in_node(node: &mut dyn Write, message: &[u8]) -> NodeMessag
     Ok(())
 }
 
-/// Reads a header message from the node socket and returns it or an error if it failed.
+/// Waits a short time for a message from the node without consuming it. Returns Ok(true) if a message started
+/// to arrive, in which case the socket blocks until it is read completely, Ok(false) if nothing arrived or error
+/// if the node closed the connection or the socket failed.
+fn wait_for_message(node: &TcpStream) -> Result<bool, NodeCustomErrors> {
+    node.set_read_timeout(Some(MESSAGE_WAIT_TIMEOUT))
+        .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
+    let mut first_byte = [0; 1];
+    let message_arrived = match node.peek(&mut first_byte) {
+        Ok(0) => {
+            return Err(NodeCustomErrors::ReadNodeError(
+                "The node closed the connection".to_string(),
+            ))
+        }
+        Ok(_) => true,
+        Err(ref err)
+            if err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut =>
+        {
+            false
+        }
+        Err(err) => return Err(NodeCustomErrors::ReadNodeError(err.to_string())),
+    };
+    if message_arrived {
+        node.set_read_timeout(None)
+            .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
+    }
+    Ok(message_arrived)
+}
+
+/// Reads a header message from the node socket and returns it or an error if it failed or
+/// if its start string is not the one of the network of the node.
 fn read_header(
     node: &mut dyn Read,
+    start_string: [u8; 4],
     finish: Option<Arc<RwLock<bool>>>,
 ) -> Result<HeaderMessage, NodeCustomErrors> {
     let mut buffer_num = [0; 24];
@@ -356,10 +476,16 @@ fn read_header(
     if is_terminated(finish) {
         // Returns any header so that it does not fail in the function in which read_header is called
         // and in this way break the while cycle well.
-        return Ok(HeaderMessage::new("none".to_string(), None));
+        return Ok(HeaderMessage::new(start_string, "none".to_string(), None));
+    }
+    let header = HeaderMessage::from_le_bytes(buffer_num)
+        .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))?;
+    if header.start_string != start_string {
+        return Err(NodeCustomErrors::UnmarshallingError(
+            "The message is not from the network of the node".to_string(),
+        ));
     }
-    HeaderMessage::from_le_bytes(buffer_num)
-        .map_err(|err| NodeCustomErrors::UnmarshallingError(err.to_string()))
+    Ok(header)
 }
 
 /// Reads from the node socket until receiving the expected payload.
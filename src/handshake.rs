@@ -1,26 +1,44 @@
+use crate::address_manager::AddressManagerPointer;
+use crate::chain_params::Network;
 use crate::config::Config;
 use crate::custom_errors::NodeCustomErrors;
 use crate::logwriter::log_writer::{write_in_log, LogSender};
 use crate::messages::message_header::{
-    read_verack_message, write_sendheaders_message, write_verack_message,
+    read_verack_message, write_sendaddrv2_message, write_sendheaders_message, write_verack_message,
 };
 use crate::messages::version_message::{get_version_message, VersionMessage};
 use std::error::Error;
-use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::net::{SocketAddr, TcpStream};
 use std::result::Result;
 use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::Duration;
 
 /// Makes the connection to the nodes with multiple threads.
-/// Receives the IP addresses of the nodes.
+/// Receives the socket addresses of the nodes. The nodes connected move to the tried table of the address manager
+/// and the failed attempts are counted.
 /// Returns a vector of sockets or an error if it could not be completed.
+/// In regtest there may be no nodes to connect to, as the first node of a local network starts its own chain,
+/// so an empty vector of sockets is returned.
 pub fn handshake_with_nodes(
     config: &Arc<Config>,
     log_sender: &LogSender,
-    node_ips: Vec<Ipv4Addr>,
+    node_ips: Vec<SocketAddr>,
+    address_manager: &AddressManagerPointer,
 ) -> Result<Arc<RwLock<Vec<TcpStream>>>, NodeCustomErrors> {
     write_in_log(&log_sender.info_log_sender, "START OF HANDSHAKE");
+    if node_ips.is_empty() {
+        if config.chain_params.network != Network::Regtest {
+            return Err(NodeCustomErrors::HandshakeError(
+                "There are no nodes to connect to".to_string(),
+            ));
+        }
+        write_in_log(
+            &log_sender.info_log_sender,
+            "There are no nodes to connect to, the node starts without peers",
+        );
+        return Ok(Arc::new(RwLock::new(vec![])));
+    }
     println!("making handshake with nodes...");
     let chunk_size = (node_ips.len() as f64 / config.n_threads as f64).ceil() as usize;
     let active_nodes_chunks = Arc::new(RwLock::new(
@@ -47,8 +65,15 @@ pub fn handshake_with_nodes(
         let config = config.clone();
         let log_sender_clone = log_sender.clone();
         let sockets: Arc<RwLock<Vec<TcpStream>>> = Arc::clone(&sockets_lock);
+        let address_manager = address_manager.clone();
         thread_handles.push(thread::spawn(move || {
-            connect_to_nodes(&config, &log_sender_clone, sockets, &chunk)
+            connect_to_nodes(
+                &config,
+                &log_sender_clone,
+                sockets,
+                &address_manager,
+                &chunk,
+            )
         }));
     }
     for handle in thread_handles {
@@ -72,16 +97,27 @@ pub fn handshake_with_nodes(
 }
 
 /// Makes the connection with all the nodes in the list received by parameter.
-/// Stores them in the list of sockets received.
-/// If one can't connect, it continues trying with the next one.
+/// Stores them in the list of sockets received and marks them as good in the address manager.
+/// If one can't connect, the attempt is counted and it continues trying with the next one.
 fn connect_to_nodes(
     config: &Arc<Config>,
     log_sender: &LogSender,
     sockets: Arc<RwLock<Vec<TcpStream>>>,
-    nodes: &[Ipv4Addr],
+    address_manager: &AddressManagerPointer,
+    nodes: &[SocketAddr],
 ) -> Result<(), NodeCustomErrors> {
     for node in nodes {
-        match connect_to_node(config, log_sender, node) {
+        let result = connect_to_node(config, log_sender, node);
+        {
+            let mut address_manager = address_manager
+                .write()
+                .map_err(|err| NodeCustomErrors::LockError(format!("{}", err)))?;
+            match &result {
+                Ok(_) => address_manager.mark_good(*node),
+                Err(_) => address_manager.mark_attempt(*node),
+            }
+        }
+        match result {
             Ok(stream) => {
                 write_in_log(
                     &log_sender.info_log_sender,
@@ -119,17 +155,18 @@ fn connect_to_nodes(
 fn connect_to_node(
     config: &Arc<Config>,
     log_sender: &LogSender,
-    node_ip: &Ipv4Addr,
+    socket_addr: &SocketAddr,
 ) -> Result<TcpStream, Box<dyn Error>> {
-    let socket_addr = SocketAddr::new((*node_ip).into(), config.net_port);
     let mut stream: TcpStream =
-        TcpStream::connect_timeout(&socket_addr, Duration::from_secs(config.connect_timeout))?;
+        TcpStream::connect_timeout(socket_addr, Duration::from_secs(config.connect_timeout))?;
     let local_ip_addr = stream.local_addr()?;
-    let version_message = get_version_message(config, socket_addr, local_ip_addr)?;
+    let version_message = get_version_message(config, *socket_addr, local_ip_addr)?;
     version_message.write_to(&mut stream)?;
-    VersionMessage::read_from(log_sender, &mut stream)?;
-    write_verack_message(&mut stream)?;
+    VersionMessage::read_from(log_sender, &mut stream, config.chain_params.magic)?;
+    // the node accepts the addresses of other nodes in addrv2 messages (BIP155), it must be signaled before the verack
+    write_sendaddrv2_message(&mut stream, config.chain_params.magic)?;
+    write_verack_message(&mut stream, config.chain_params.magic)?;
     read_verack_message(log_sender, &mut stream)?;
-    write_sendheaders_message(&mut stream)?;
+    write_sendheaders_message(&mut stream, config.chain_params.magic)?;
     Ok(stream)
 }
@@ -1,22 +1,33 @@
 pub mod account;
 pub mod address_decoder;
+pub mod address_index;
+pub mod address_manager;
 pub mod blockchain;
 pub mod blockchain_download;
 pub mod blocks;
+pub mod chain_params;
+pub mod chainstate;
 pub mod compact_size_uint;
 pub mod config;
 pub mod custom_errors;
+pub mod filter_index;
 pub mod gtk;
 pub mod handler;
 pub mod handshake;
 pub mod logwriter;
 pub mod messages;
+pub mod miner;
+pub mod multisig_account;
 pub mod network;
 pub mod node;
 pub mod node_data_pointers;
+pub mod record_log;
 pub mod server;
+#[cfg(test)]
+mod temp_directory;
 pub mod terminal_ui;
 pub mod transactions;
+pub mod tx_index;
 pub mod utxo_tuple;
 pub mod wallet;
 pub mod wallet_event;
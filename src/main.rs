@@ -7,7 +7,7 @@ use bitcoin::handshake::handshake_with_nodes;
 use bitcoin::logwriter::log_writer::{
     set_up_loggers, shutdown_loggers, LogSender, LogSenderHandles,
 };
-use bitcoin::network::get_active_nodes_from_dns_seed;
+use bitcoin::network::{get_active_nodes_from_dns_seed, open_address_manager};
 use bitcoin::node::Node;
 use bitcoin::server::NodeServer;
 use bitcoin::terminal_ui::terminal_ui;
@@ -77,17 +77,21 @@ fn run_node(
     send_event_to_ui(&ui_sender, UIEvent::StartHandshake);
     let config = Config::from(args)?;
     let (log_sender, log_sender_handles) = set_up_loggers(&config)?;
-    let node_ips = get_active_nodes_from_dns_seed(&config, &log_sender)?;
-    let nodes = handshake_with_nodes(&config, &log_sender, node_ips)?;
+    let address_manager = open_address_manager(&config, &log_sender);
+    let node_ips = get_active_nodes_from_dns_seed(&config, &log_sender, &address_manager)?;
+    let nodes = handshake_with_nodes(&config, &log_sender, node_ips, &address_manager)?;
     let blockchain = initial_block_download(&config, &log_sender, &ui_sender, nodes.clone())?;
-    let mut node = Node::new(&log_sender, &ui_sender, nodes, blockchain.clone())?;
-    send_event_to_ui(
+    let mut node = Node::new(
+        &log_sender,
         &ui_sender,
-        UIEvent::InitializeUITabs((blockchain.headers, blockchain.blocks)),
-    );
+        nodes,
+        blockchain.clone(),
+        address_manager,
+    )?;
+    send_event_to_ui(&ui_sender, UIEvent::InitializeUITabs(blockchain));
     let mut wallet = Wallet::new(node.clone())?;
     let server = NodeServer::new(&config, &log_sender, &ui_sender, &mut node)?;
-    handle_ui_events(&ui_sender, node_rx, &mut wallet);
+    handle_ui_events(&log_sender, &ui_sender, node_rx, &mut wallet);
     shut_down(node, server, log_sender, log_sender_handles)?;
     Ok(())
 }
@@ -117,10 +121,11 @@ fn shut_down(
     Ok(())
 }
 
-/// Receives a sender that sends events to the UI or None, a receiver that receives events from the UI or none and a wallet.
+/// Receives the log sender, a sender that sends events to the UI or None, a receiver that receives events from the UI or none and a wallet.
 /// If the Receiver is Some it is responsible for handling the UI events, if it is None it is responsible for showing the terminal interface
 /// so that the user interacts with the wallet
 fn handle_ui_events(
+    log_sender: &LogSender,
     ui_sender: &Option<glib::Sender<UIEvent>>,
     node_rx: Option<Receiver<WalletEvent>>,
     wallet: &mut Wallet,
@@ -128,6 +133,6 @@ fn handle_ui_events(
     if let Some(rx) = node_rx {
         handle_ui_request(ui_sender, rx, wallet)
     } else {
-        terminal_ui(ui_sender, wallet)
+        terminal_ui(log_sender, ui_sender, wallet)
     }
 }
@@ -0,0 +1,260 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::compact_size_uint::CompactSizeUint;
+
+use super::message_header::HeaderMessage;
+
+/// Maximum amount of addresses that an addr or addrv2 message can have.
+pub const MAX_ADDR_PER_MESSAGE: usize = 1000;
+// Ids of the networks of the addresses of the addrv2 message (BIP155) that the node can connect to
+const BIP155_IPV4: u8 = 1;
+const BIP155_IPV6: u8 = 2;
+// Maximum length of an address of the addrv2 message (BIP155)
+const MAX_BIP155_ADDR_SIZE: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents the address of a node as it is gossiped in the addr and addrv2 messages: the last time it was seen,
+/// the services it supports and its ip and port.
+pub struct NetworkAddress {
+    pub time: u32,
+    pub services: u64,
+    pub ip: IpAddr,
+    pub port: u16,
+}
+
+impl NetworkAddress {
+    /// Creates the address of the node of the socket received, seen at the time received
+    /// with the services received.
+    pub fn new(time: u32, services: u64, socket_addr: SocketAddr) -> Self {
+        NetworkAddress {
+            time,
+            services,
+            ip: socket_addr.ip(),
+            port: socket_addr.port(),
+        }
+    }
+
+    /// Returns the socket address of the node.
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
+
+    /// Serializes the address as it is written in the addr message. The Ipv4 addresses are written as Ipv4-mapped Ipv6
+    /// addresses and the port in big endian.
+    pub fn marshalling(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.time.to_le_bytes());
+        bytes.extend_from_slice(&self.services.to_le_bytes());
+        bytes.extend_from_slice(&ip_to_ipv6_bytes(&self.ip));
+        bytes.extend_from_slice(&self.port.to_be_bytes());
+    }
+
+    /// Receives the bytes of an addr message and the offset where an address begins and returns the address.
+    /// Updates the offset. Returns an error if there are not enough bytes.
+    pub fn unmarshalling(bytes: &[u8], offset: &mut usize) -> Result<NetworkAddress, &'static str> {
+        let time = u32::from_le_bytes(read_array(bytes, offset)?);
+        let services = u64::from_le_bytes(read_array(bytes, offset)?);
+        let ip = ipv6_bytes_to_ip(read_array(bytes, offset)?);
+        let port = u16::from_be_bytes(read_array(bytes, offset)?);
+        Ok(NetworkAddress {
+            time,
+            services,
+            ip,
+            port,
+        })
+    }
+
+    /// Receives the bytes of an addrv2 message (BIP155) and the offset where an address begins and returns the address,
+    /// or None if it belongs to a network to which the node can not connect (Tor, I2P or CJDNS). Updates the offset.
+    /// Returns an error if there are not enough bytes or if the length of the address does not match its network.
+    pub fn unmarshalling_v2(
+        bytes: &[u8],
+        offset: &mut usize,
+    ) -> Result<Option<NetworkAddress>, &'static str> {
+        let time = u32::from_le_bytes(read_array(bytes, offset)?);
+        let services = CompactSizeUint::unmarshalling(bytes, offset)?.decoded_value();
+        let network_id: [u8; 1] = read_array(bytes, offset)?;
+        let addr_size = CompactSizeUint::unmarshalling(bytes, offset)?.decoded_value() as usize;
+        if addr_size > MAX_BIP155_ADDR_SIZE || bytes.len() < *offset + addr_size {
+            return Err("The bytes received do not correspond to an addrv2 address");
+        }
+        let addr_bytes = &bytes[*offset..*offset + addr_size];
+        *offset += addr_size;
+        let port = u16::from_be_bytes(read_array(bytes, offset)?);
+        let ip = match (network_id[0], addr_bytes.len()) {
+            (BIP155_IPV4, 4) => IpAddr::V4(Ipv4Addr::new(
+                addr_bytes[0],
+                addr_bytes[1],
+                addr_bytes[2],
+                addr_bytes[3],
+            )),
+            (BIP155_IPV6, 16) => {
+                let mut ipv6_bytes = [0; 16];
+                ipv6_bytes.copy_from_slice(addr_bytes);
+                IpAddr::V6(Ipv6Addr::from(ipv6_bytes))
+            }
+            (BIP155_IPV4, _) | (BIP155_IPV6, _) => {
+                return Err("The length of the addrv2 address does not match its network")
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(NetworkAddress {
+            time,
+            services,
+            ip,
+            port,
+        }))
+    }
+}
+
+/// Receives the start string of the network and the addresses of nodes and returns the serialized addr message.
+pub fn get_addr_message(start_string: [u8; 4], addresses: &[NetworkAddress]) -> Vec<u8> {
+    let mut payload = CompactSizeUint::new(addresses.len() as u128).marshalling();
+    for address in addresses {
+        address.marshalling(&mut payload);
+    }
+    let header = HeaderMessage::new(start_string, "addr".to_string(), Some(&payload));
+    let mut message = vec![];
+    message.extend_from_slice(&header.to_le_bytes());
+    message.extend_from_slice(&payload);
+    message
+}
+
+/// Receives the start string of the network and returns the serialized getaddr message, which asks the node
+/// for the addresses of other nodes it knows. It does not have payload.
+pub fn get_getaddr_message(start_string: [u8; 4]) -> Vec<u8> {
+    HeaderMessage::new(start_string, "getaddr".to_string(), None)
+        .to_le_bytes()
+        .to_vec()
+}
+
+/// Receives the payload of an addr message and returns its addresses.
+/// Returns an error if the bytes are not valid or if the message has more than 1000 addresses.
+pub fn unmarshalling_addr_payload(payload: &[u8]) -> Result<Vec<NetworkAddress>, &'static str> {
+    let mut offset = 0;
+    let count = read_addresses_count(payload, &mut offset)?;
+    let mut addresses = Vec::with_capacity(count);
+    for _ in 0..count {
+        addresses.push(NetworkAddress::unmarshalling(payload, &mut offset)?);
+    }
+    Ok(addresses)
+}
+
+/// Receives the payload of an addrv2 message (BIP155) and returns its Ipv4 and Ipv6 addresses. The addresses of
+/// other networks are skipped. Returns an error if the bytes are not valid or if the message has more than 1000 addresses.
+pub fn unmarshalling_addrv2_payload(payload: &[u8]) -> Result<Vec<NetworkAddress>, &'static str> {
+    let mut offset = 0;
+    let count = read_addresses_count(payload, &mut offset)?;
+    let mut addresses = Vec::with_capacity(count);
+    for _ in 0..count {
+        if let Some(address) = NetworkAddress::unmarshalling_v2(payload, &mut offset)? {
+            addresses.push(address);
+        }
+    }
+    Ok(addresses)
+}
+
+/// Reads the amount of addresses of an addr or addrv2 message. Returns an error if it exceeds the maximum.
+fn read_addresses_count(payload: &[u8], offset: &mut usize) -> Result<usize, &'static str> {
+    let count = CompactSizeUint::unmarshalling(payload, offset)?.decoded_value() as usize;
+    if count > MAX_ADDR_PER_MESSAGE {
+        return Err("The message has more than 1000 addresses");
+    }
+    Ok(count)
+}
+
+/// Reads N bytes from the offset received and updates it. Returns an error if there are not enough bytes.
+fn read_array<const N: usize>(bytes: &[u8], offset: &mut usize) -> Result<[u8; N], &'static str> {
+    if bytes.len() < *offset + N {
+        return Err("The bytes received do not correspond to a network address");
+    }
+    let mut array = [0; N];
+    array.copy_from_slice(&bytes[*offset..*offset + N]);
+    *offset += N;
+    Ok(array)
+}
+
+/// Returns the 16 bytes of the ip as it is written in the messages of the protocol: the Ipv4 addresses
+/// are mapped to Ipv6 (::ffff:x.x.x.x).
+pub fn ip_to_ipv6_bytes(ip: &IpAddr) -> [u8; 16] {
+    match ip {
+        IpAddr::V4(ipv4) => ipv4.to_ipv6_mapped().octets(),
+        IpAddr::V6(ipv6) => ipv6.octets(),
+    }
+}
+
+/// Returns the ip of the 16 bytes of an address of the messages of the protocol. The Ipv4-mapped
+/// addresses are returned as Ipv4.
+fn ipv6_bytes_to_ip(bytes: [u8; 16]) -> IpAddr {
+    let ipv6 = Ipv6Addr::from(bytes);
+    match ipv6.to_ipv4_mapped() {
+        Some(ipv4) => IpAddr::V4(ipv4),
+        None => IpAddr::V6(ipv6),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_addresses_of_the_addr_message_are_read_as_they_were_written() -> Result<(), &'static str>
+    {
+        let addresses = vec![
+            NetworkAddress::new(1_700_000_000, 1, "127.0.0.1:18444".parse().unwrap()),
+            NetworkAddress::new(1_700_000_001, 9, "[2001:db8::1]:8333".parse().unwrap()),
+        ];
+        let message = get_addr_message([0xfa, 0xbf, 0xb5, 0xda], &addresses);
+
+        let addresses_read = unmarshalling_addr_payload(&message[24..])?;
+
+        assert_eq!(addresses_read, addresses);
+        assert!(addresses_read[0].ip.is_ipv4());
+        Ok(())
+    }
+
+    #[test]
+    fn test_addrv2_message_keeps_the_ipv4_and_ipv6_addresses() -> Result<(), &'static str> {
+        let mut payload = vec![3];
+        // ipv4 192.168.0.1:8333 with services 1
+        payload.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+        payload.extend_from_slice(&[1, BIP155_IPV4, 4, 192, 168, 0, 1, 0x20, 0x8d]);
+        // torv3 address, skipped
+        payload.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+        payload.extend_from_slice(&[1, 4, 32]);
+        payload.extend_from_slice(&[0xab; 32]);
+        payload.extend_from_slice(&[0x20, 0x8d]);
+        // ipv6 2001:db8::1:18333 with services 1033
+        payload.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+        payload.extend_from_slice(&[0xfd, 0x09, 0x04, BIP155_IPV6, 16]);
+        payload.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        payload.extend_from_slice(&18333u16.to_be_bytes());
+
+        let addresses = unmarshalling_addrv2_payload(&payload)?;
+
+        assert_eq!(
+            addresses,
+            vec![
+                NetworkAddress::new(1_700_000_000, 1, "192.168.0.1:8333".parse().unwrap()),
+                NetworkAddress::new(1_700_000_000, 1033, "[2001:db8::1]:18333".parse().unwrap()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_addrv2_address_with_invalid_length_is_an_error() {
+        let mut payload = vec![1];
+        payload.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+        payload.extend_from_slice(&[1, BIP155_IPV4, 3, 192, 168, 0, 0x20, 0x8d]);
+
+        assert!(unmarshalling_addrv2_payload(&payload).is_err());
+    }
+
+    #[test]
+    fn test_addr_message_with_more_than_1000_addresses_is_an_error() {
+        let addresses = vec![NetworkAddress::new(0, 1, "127.0.0.1:18444".parse().unwrap()); 1001];
+        let message = get_addr_message([0xfa, 0xbf, 0xb5, 0xda], &addresses);
+
+        assert!(unmarshalling_addr_payload(&message[24..]).is_err());
+    }
+}
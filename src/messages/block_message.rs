@@ -33,11 +33,16 @@ impl BlockMessage {
     }
 }
 
-/// Returns the block message with the block passed by parameter.
-pub fn get_block_message(block: &Block) -> Vec<u8> {
+/// Returns the block message with the block passed by parameter and the start string of the network.
+/// The witness data of the transactions is included only if with_witness is true.
+pub fn get_block_message(start_string: [u8; 4], block: &Block, with_witness: bool) -> Vec<u8> {
     let mut block_payload = vec![];
-    block.marshalling(&mut block_payload);
-    let header = HeaderMessage::new("block".to_string(), Some(&block_payload));
+    if with_witness {
+        block.marshalling(&mut block_payload);
+    } else {
+        block.marshalling_without_witness(&mut block_payload);
+    }
+    let header = HeaderMessage::new(start_string, "block".to_string(), Some(&block_payload));
     let mut block_message = vec![];
     block_message.extend_from_slice(&header.to_le_bytes());
     block_message.extend_from_slice(&block_payload);
@@ -0,0 +1,64 @@
+use crate::compact_size_uint::CompactSizeUint;
+
+use super::message_header::HeaderMessage;
+
+/// Receives the start string of the network, the type of the filter, the hash of the block and its filter
+/// and returns the serialized cfilter message (BIP157).
+pub fn get_cfilter_message(
+    start_string: [u8; 4],
+    filter_type: u8,
+    block_hash: [u8; 32],
+    filter: &[u8],
+) -> Vec<u8> {
+    let mut payload = vec![filter_type];
+    payload.extend(block_hash);
+    payload.extend_from_slice(&CompactSizeUint::new(filter.len() as u128).marshalling());
+    payload.extend_from_slice(filter);
+    get_message(start_string, "cfilter", &payload)
+}
+
+/// Receives the start string of the network, the type of the filter, the hash of the last block of the range,
+/// the filter header of the block before the range and the hashes of the filters of the range and returns the
+/// serialized cfheaders message (BIP157).
+pub fn get_cfheaders_message(
+    start_string: [u8; 4],
+    filter_type: u8,
+    stop_hash: [u8; 32],
+    previous_filter_header: [u8; 32],
+    filter_hashes: &[[u8; 32]],
+) -> Vec<u8> {
+    let mut payload = vec![filter_type];
+    payload.extend(stop_hash);
+    payload.extend(previous_filter_header);
+    payload.extend_from_slice(&CompactSizeUint::new(filter_hashes.len() as u128).marshalling());
+    for filter_hash in filter_hashes {
+        payload.extend(filter_hash);
+    }
+    get_message(start_string, "cfheaders", &payload)
+}
+
+/// Receives the start string of the network, the type of the filter, the hash of the last block of the chain
+/// and the filter headers of every 1000 blocks and returns the serialized cfcheckpt message (BIP157).
+pub fn get_cfcheckpt_message(
+    start_string: [u8; 4],
+    filter_type: u8,
+    stop_hash: [u8; 32],
+    filter_headers: &[[u8; 32]],
+) -> Vec<u8> {
+    let mut payload = vec![filter_type];
+    payload.extend(stop_hash);
+    payload.extend_from_slice(&CompactSizeUint::new(filter_headers.len() as u128).marshalling());
+    for filter_header in filter_headers {
+        payload.extend(filter_header);
+    }
+    get_message(start_string, "cfcheckpt", &payload)
+}
+
+/// Returns the message with the received command and payload, preceded by its header with the start string.
+fn get_message(start_string: [u8; 4], command: &str, payload: &[u8]) -> Vec<u8> {
+    let header = HeaderMessage::new(start_string, command.to_string(), Some(payload));
+    let mut message = vec![];
+    message.extend_from_slice(&header.to_le_bytes());
+    message.extend_from_slice(payload);
+    message
+}
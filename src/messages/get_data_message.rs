@@ -5,8 +5,6 @@ use super::{
     inventory::Inventory, message_header::HeaderMessage, payload::get_data_payload::GetDataPayload,
 };
 
-const START_STRING: [u8; 4] = [0x0b, 0x11, 0x09, 0x07];
-
 #[derive(Debug)]
 /// Implemnts the getdata message needed to request objects from another node.
 /// It can be used to request transactions, blocks, etc.
@@ -16,7 +14,7 @@ pub struct GetDataMessage {
     pub payload: GetDataPayload,
 }
 impl GetDataMessage {
-    /// Creates a new getdata message from the inventories,
+    /// Creates a new getdata message with the start string of the network from the inventories,
     /// which are the hashes of some object, such as tx or block
     /// # EXAMPLE of use:
     ///```no_test
@@ -33,12 +31,12 @@ impl GetDataMessage {
     ///     let mut inventories = Vec::new();
     ///     inventories.push(Inventory::new_block(hash));
     ///
-    ///     let data_message = GetDataMessage::new(inventories);
+    ///     let data_message = GetDataMessage::new(config.chain_params.magic, inventories);
     ///     data_message.write_to(&mut stream);
     /// ```
-    pub fn new(inventories: Vec<Inventory>) -> GetDataMessage {
+    pub fn new(start_string: [u8; 4], inventories: Vec<Inventory>) -> GetDataMessage {
         let payload = GetDataPayload::get_payload(inventories);
-        let header = get_data_header_message(&payload);
+        let header = get_data_header_message(start_string, &payload);
         GetDataMessage { header, payload }
     }
 
@@ -62,13 +60,13 @@ impl GetDataMessage {
     }
 }
 
-/// Returns the Header Message of the getdata message.
-fn get_data_header_message(payload: &GetDataPayload) -> HeaderMessage {
+/// Returns the Header Message of the getdata message, with the start string of the network.
+fn get_data_header_message(start_string: [u8; 4], payload: &GetDataPayload) -> HeaderMessage {
     let payload_bytes = payload.to_le_bytes();
     let binding = sha256d::Hash::hash(payload_bytes);
     let checksum = binding.as_byte_array();
     HeaderMessage {
-        start_string: START_STRING,
+        start_string,
         command_name: "getdata".to_string(),
         payload_size: payload_bytes.len() as u32,
         checksum: [checksum[0], checksum[1], checksum[2], checksum[3]],
@@ -84,7 +82,7 @@ mod tests {
         let mut inventories = Vec::new();
         inventories.push(Inventory::new_block([0; 32]));
         // WHEN: the get_payload method is called
-        let message = GetDataMessage::new(inventories);
+        let message = GetDataMessage::new([0x0b, 0x11, 0x09, 0x07], inventories);
         // THEN: the message header is created with the correct command_name.
         assert!(message.header.command_name.contains("getdata"));
     }
@@ -28,10 +28,14 @@ impl GetHeadersMessage {
         Ok(())
     }
 
-    /// Given a vector of bytes, it tries to interpret the vec as a getheaders message.
-    pub fn read_from(payload_bytes: &[u8]) -> Result<GetHeadersMessage, Box<dyn Error>> {
+    /// Given the start string of the network and a vector of bytes, it tries to interpret the vec as a getheaders message.
+    pub fn read_from(
+        start_string: [u8; 4],
+        payload_bytes: &[u8],
+    ) -> Result<GetHeadersMessage, Box<dyn Error>> {
         let payload = GetHeadersPayload::read_from(payload_bytes)?;
-        let header = HeaderMessage::new("getheaders".to_string(), Some(payload_bytes));
+        let header =
+            HeaderMessage::new(start_string, "getheaders".to_string(), Some(payload_bytes));
         Ok(GetHeadersMessage { header, payload })
     }
     /// Receives a Config struct with the constants to use in the header of the getheaders message and a vector
@@ -40,16 +44,31 @@ impl GetHeadersMessage {
     pub fn build_getheaders_message(
         config: &Arc<Config>,
         locator_hashes: Vec<[u8; 32]>,
+    ) -> GetHeadersMessage {
+        Self::new(
+            config.chain_params.magic,
+            config.protocol_version as u32,
+            locator_hashes,
+        )
+    }
+
+    /// Receives the start string of the network, the protocol version and a vector of block hashes and builds the
+    /// getheaders message to request the headers after the last hash in the vector, with stop_hash in 0.
+    pub fn new(
+        start_string: [u8; 4],
+        version: u32,
+        locator_hashes: Vec<[u8; 32]>,
     ) -> GetHeadersMessage {
         let hash_count = CompactSizeUint::new(1u128);
         let stop_hash = [0; 32];
         let getheaders_payload = GetHeadersPayload {
-            version: config.protocol_version as u32,
+            version,
             hash_count,
             locator_hashes,
             stop_hash,
         };
         let header_of_getheaders = HeaderMessage::new(
+            start_string,
             "getheaders".to_string(),
             Some(&getheaders_payload.to_le_bytes()),
         );
@@ -1,9 +1,8 @@
 use super::message_header::{is_terminated, HeaderMessage};
 use crate::blocks::block_header::BlockHeader;
 use crate::compact_size_uint::CompactSizeUint;
-use crate::logwriter::log_writer::{write_in_log, LogSender};
-use std::fs::File;
-use std::io::{Read, Write};
+use crate::logwriter::log_writer::LogSender;
+use std::io::Read;
 use std::net::TcpStream;
 use std::sync::{Arc, RwLock};
 const BLOCK_HEADER_SIZE: usize = 80;
@@ -58,37 +57,9 @@ impl HeadersMessage {
         Ok(headers)
     }
 
-    /// Stores the headers received in disk. Reads the headers from the stream and writes them in the file
-    /// in the same format as they are read from the stream.
-    pub fn read_from_node_and_write_to_file(
-        log_sender: &LogSender,
-        stream: &mut TcpStream,
-        finish: Option<Arc<RwLock<bool>>>,
-        file: &mut File,
-    ) -> Result<Vec<BlockHeader>, Box<dyn std::error::Error>> {
-        let header =
-            HeaderMessage::read_from(log_sender, stream, "headers".to_string(), finish.clone())?;
-        if is_terminated(finish) {
-            let headers: Vec<BlockHeader> = Vec::new();
-            return Ok(headers);
-        }
-        let payload_size = header.payload_size as usize;
-        let mut buffer_num = vec![0; payload_size];
-        stream.read_exact(&mut buffer_num)?;
-        let mut vec: Vec<u8> = vec![];
-        vec.extend_from_slice(&buffer_num);
-        let headers = Self::unmarshalling(&vec)?;
-        // write in file
-        if let Err(err) = file.write_all(&vec) {
-            write_in_log(
-                &log_sender.error_log_sender,
-                format!("Error trying to write in file: {:?}", err).as_str(),
-            );
-        }
-        Ok(headers)
-    }
-    /// Given a vector of block headers, it builds the headers message and returns it in a vector of bytes.
-    pub fn marshalling(headers: Vec<BlockHeader>) -> Vec<u8> {
+    /// Given the start string of the network and a vector of block headers, it builds the headers message
+    /// and returns it in a vector of bytes.
+    pub fn marshalling(start_string: [u8; 4], headers: Vec<BlockHeader>) -> Vec<u8> {
         let mut headers_message_payload: Vec<u8> = Vec::new();
         let count = CompactSizeUint::new(headers.len() as u128);
         headers_message_payload.extend_from_slice(count.value());
@@ -98,7 +69,11 @@ impl HeadersMessage {
             header_bytes.extend_from_slice(&[0x00]); // transaction_count
             headers_message_payload.extend_from_slice(&header_bytes);
         }
-        let header = HeaderMessage::new("headers".to_string(), Some(&headers_message_payload));
+        let header = HeaderMessage::new(
+            start_string,
+            "headers".to_string(),
+            Some(&headers_message_payload),
+        );
         let mut headers_message: Vec<u8> = Vec::new();
         headers_message.extend_from_slice(&header.to_le_bytes());
         headers_message.extend_from_slice(&headers_message_payload);
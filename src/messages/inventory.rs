@@ -20,6 +20,14 @@ impl Inventory {
         }
     }
 
+    /// Creates an inventory with the hash of a block, asking for it with the witness data (BIP144).
+    pub fn new_witness_block(hash: [u8; 32]) -> Inventory {
+        Inventory {
+            type_identifier: 0x40000002, // MSG_WITNESS_BLOCK: Block with witness data
+            hash,
+        }
+    }
+
     /// Creates an inventory with the hash of a transaction.
     pub fn new_tx(hash: [u8; 32]) -> Inventory {
         Inventory {
@@ -55,16 +63,16 @@ impl Inventory {
     }
 }
 
-/// Receives a vector of Inventory and serializes the inv message with that vector. Returns a vector
-/// of u8 representing the serialized bytes.
-pub fn inv_mershalling(inventories: Vec<Inventory>) -> Vec<u8> {
+/// Receives the start string of the network and a vector of Inventory and serializes the inv message with
+/// that vector. Returns a vector of u8 representing the serialized bytes.
+pub fn inv_mershalling(start_string: [u8; 4], inventories: Vec<Inventory>) -> Vec<u8> {
     let count = CompactSizeUint::new(inventories.len() as u128);
     let mut inv_payload = vec![];
     inv_payload.extend_from_slice(&count.marshalling());
     for inventory in inventories {
         inv_payload.extend(inventory.to_le_bytes());
     }
-    let header = HeaderMessage::new("inv".to_string(), Some(&inv_payload));
+    let header = HeaderMessage::new(start_string, "inv".to_string(), Some(&inv_payload));
     let mut inv_message = vec![];
     inv_message.extend_from_slice(&header.to_le_bytes());
     inv_message.extend_from_slice(&inv_payload);
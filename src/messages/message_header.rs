@@ -8,7 +8,6 @@ use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use std::vec;
 
-const START_STRING_TESTNET: [u8; 4] = [0x0b, 0x11, 0x09, 0x07];
 const CHECKSUM_EMPTY_PAYLOAD: [u8; 4] = [0x5d, 0xf6, 0xe0, 0xe2];
 
 #[derive(Clone, Debug)]
@@ -21,19 +20,19 @@ pub struct HeaderMessage {
 }
 
 impl HeaderMessage {
-    /// Given the command name and an Option (if it is None represents that the command
+    /// Given the start string of the network, the command name and an Option (if it is None represents that the command
     /// does not have payload or a Vec<u8> representing the payload of the message) 
     /// returns the HeaderMessage of that message.
-    pub fn new(command_name: String, payload: Option<&[u8]>) -> Self {
+    pub fn new(start_string: [u8; 4], command_name: String, payload: Option<&[u8]>) -> Self {
         match payload {
             None => HeaderMessage {
-                start_string: START_STRING_TESTNET,
+                start_string,
                 command_name,
                 payload_size: 0,
                 checksum: CHECKSUM_EMPTY_PAYLOAD,
             },
             Some(payload) => HeaderMessage {
-                start_string: START_STRING_TESTNET,
+                start_string,
                 command_name,
                 payload_size: payload.len() as u32,
                 checksum: get_checksum(payload),
@@ -117,7 +116,7 @@ impl HeaderMessage {
                     )
                     .as_str(),
                 );
-                write_pong_message(&mut stream, &payload)?;
+                write_pong_message(&mut stream, header.start_string, &payload)?;
             }
             write_in_log(
                 &log_sender.message_log_sender,
@@ -169,22 +168,26 @@ fn read_payload(stream: &mut dyn Read, header: &HeaderMessage) -> io::Result<Vec
 /// Recibe un stream que implemente el trait Write (algo donde se pueda escribir) y escribe el mensaje verack segun
 /// el protocolo de bitcoin, si se escribe correctamente devuelve Ok(()) y sino devuelve un error
 /// Receives a stream that implements the Write trait (something where you can write) and writes the verack message according to
-/// the bitcoin protocol. If it is written correctly it returns Ok(()), otherwise it returns an error.
-pub fn write_verack_message(stream: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
-    let header = HeaderMessage::new("verack".to_string(), None);
+/// the bitcoin protocol, with the start string of the network. If it is written correctly it returns Ok(()), otherwise it returns an error.
+pub fn write_verack_message(
+    stream: &mut dyn Write,
+    start_string: [u8; 4],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let header = HeaderMessage::new(start_string, "verack".to_string(), None);
     header.write_to(stream)?;
     Ok(())
 }
 
-/// Receives a stream that implements the Write trait (something where you can write) and 
-/// the nonce of the ping message to which it must respond and writes the pong message 
+/// Receives a stream that implements the Write trait (something where you can write), the start string
+/// of the network and the nonce of the ping message to which it must respond and writes the pong message 
 /// according to the bitcoin protocol. If it is written 
 /// correctly it returns Ok(()), otherwise it returns an error.
 pub fn write_pong_message(
     stream: &mut dyn Write,
+    start_string: [u8; 4],
     payload: &[u8],
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let header = HeaderMessage::new("pong".to_string(), Some(payload));
+    let header = HeaderMessage::new(start_string, "pong".to_string(), Some(payload));
     let header_bytes = HeaderMessage::to_le_bytes(&header);
     let mut message: Vec<u8> = Vec::new();
     message.extend_from_slice(&header_bytes);
@@ -195,13 +198,29 @@ pub fn write_pong_message(
 }
 
 /// Receives a stream that implements the Write trait (something where you can write) and 
-/// writes the sendheaders message according to the bitcoin protocol. If it is written
-/// correctly it returns Ok(()), otherwise it returns an error.
-pub fn write_sendheaders_message(stream: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
-    let header = HeaderMessage::new("sendheaders".to_string(), None);
+/// writes the sendheaders message according to the bitcoin protocol, with the start string of the network.
+/// If it is written correctly it returns Ok(()), otherwise it returns an error.
+pub fn write_sendheaders_message(
+    stream: &mut dyn Write,
+    start_string: [u8; 4],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let header = HeaderMessage::new(start_string, "sendheaders".to_string(), None);
     header.write_to(stream)?;
     Ok(())
 }
+
+/// Receives a stream that implements the Write trait (something where you can write) and writes the sendaddrv2
+/// message (BIP155), with the start string of the network, to receive the addresses of other nodes in addrv2 messages.
+/// If it is written correctly it returns Ok(()), otherwise it returns an error.
+pub fn write_sendaddrv2_message(
+    stream: &mut dyn Write,
+    start_string: [u8; 4],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let header = HeaderMessage::new(start_string, "sendaddrv2".to_string(), None);
+    header.write_to(stream)?;
+    Ok(())
+}
+
 /// Receives a stream that implements the Read trait (something from which you can read) and 
 /// reads the verack message according to the bitcoin protocol. If it is read correctly it returns
 /// Ok(HeaderMessage), otherwise it returns an error.
@@ -1,4 +1,6 @@
+pub mod addr_message;
 pub mod block_message;
+pub mod compact_filters_message;
 pub mod get_data_message;
 pub mod getheaders_message;
 pub mod headers_message;
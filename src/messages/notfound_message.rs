@@ -3,11 +3,11 @@ use super::{
     message_header::HeaderMessage,
 };
 
-/// Receives a vector of Inventory and returns the serialized notfound message.
-pub fn get_notfound_message(inventories: Vec<Inventory>) -> Vec<u8> {
+/// Receives the start string of the network and a vector of Inventory and returns the serialized notfound message.
+pub fn get_notfound_message(start_string: [u8; 4], inventories: Vec<Inventory>) -> Vec<u8> {
     let mut message = vec![];
-    let payload = inv_mershalling(inventories);
-    let header = HeaderMessage::new("notfound".to_string(), Some(&payload));
+    let payload = inv_mershalling(start_string, inventories);
+    let header = HeaderMessage::new(start_string, "notfound".to_string(), Some(&payload));
     message.extend_from_slice(&header.to_le_bytes());
     message.extend_from_slice(&payload);
     message
@@ -0,0 +1,108 @@
+use std::error::Error;
+
+const SIZE_OF_HASH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+/// Represents the payload of the getcfilters and getcfheaders messages according to the BIP157. Both
+/// ask for the filters (or their headers) of the blocks of the chain of the stop hash from the start height.
+pub struct GetCFiltersPayload {
+    pub filter_type: u8,   // The type of the filters asked, 0 for the basic filters
+    pub start_height: u32, // The height of the first block of the range
+    pub stop_hash: [u8; SIZE_OF_HASH], // The hash of the last block of the range
+}
+
+impl GetCFiltersPayload {
+    /// Given a GetCFiltersPayload struct, serialize the payload to bytes according to the BIP157
+    /// and returns a vector of bytes representing it.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        let mut payload_bytes: Vec<u8> = vec![self.filter_type];
+        payload_bytes.extend_from_slice(&self.start_height.to_le_bytes());
+        payload_bytes.extend(self.stop_hash);
+        payload_bytes
+    }
+
+    /// Given a vector of bytes, it tries to interpret the same as a payload of the getcfilters or getcfheaders message.
+    pub fn read_from(payload: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if payload.len() < 1 + 4 + SIZE_OF_HASH {
+            return Err("The payload is shorter than expected".into());
+        }
+        let mut start_height_bytes: [u8; 4] = [0u8; 4];
+        start_height_bytes.copy_from_slice(&payload[1..5]);
+        let mut stop_hash: [u8; SIZE_OF_HASH] = [0u8; SIZE_OF_HASH];
+        stop_hash.copy_from_slice(&payload[5..5 + SIZE_OF_HASH]);
+        Ok(GetCFiltersPayload {
+            filter_type: payload[0],
+            start_height: u32::from_le_bytes(start_height_bytes),
+            stop_hash,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Represents the payload of the getcfcheckpt message according to the BIP157, which asks for the
+/// filter headers of every 1000 blocks of the chain of the stop hash.
+pub struct GetCFCheckptPayload {
+    pub filter_type: u8, // The type of the filters asked, 0 for the basic filters
+    pub stop_hash: [u8; SIZE_OF_HASH], // The hash of the last block of the chain
+}
+
+impl GetCFCheckptPayload {
+    /// Given a GetCFCheckptPayload struct, serialize the payload to bytes according to the BIP157
+    /// and returns a vector of bytes representing it.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        let mut payload_bytes: Vec<u8> = vec![self.filter_type];
+        payload_bytes.extend(self.stop_hash);
+        payload_bytes
+    }
+
+    /// Given a vector of bytes, it tries to interpret the same as a payload of the getcfcheckpt message.
+    pub fn read_from(payload: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if payload.len() < 1 + SIZE_OF_HASH {
+            return Err("The payload is shorter than expected".into());
+        }
+        let mut stop_hash: [u8; SIZE_OF_HASH] = [0u8; SIZE_OF_HASH];
+        stop_hash.copy_from_slice(&payload[1..1 + SIZE_OF_HASH]);
+        Ok(GetCFCheckptPayload {
+            filter_type: payload[0],
+            stop_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn getcfilters_payload_is_the_same_after_marshalling_and_unmarshalling(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: a getcfilters payload
+        let payload = GetCFiltersPayload {
+            filter_type: 0,
+            start_height: 1000,
+            stop_hash: [7; 32],
+        };
+        // WHEN: it is serialized and read again
+        let bytes = payload.to_le_bytes();
+        // THEN: the same payload is obtained
+        assert_eq!(bytes.len(), 37);
+        assert_eq!(payload, GetCFiltersPayload::read_from(&bytes)?);
+        Ok(())
+    }
+
+    #[test]
+    fn getcfcheckpt_payload_is_the_same_after_marshalling_and_unmarshalling(
+    ) -> Result<(), Box<dyn Error>> {
+        // GIVEN: a getcfcheckpt payload
+        let payload = GetCFCheckptPayload {
+            filter_type: 0,
+            stop_hash: [9; 32],
+        };
+        // WHEN: it is serialized and read again
+        let bytes = payload.to_le_bytes();
+        // THEN: the same payload is obtained, and a shorter payload can not be read
+        assert_eq!(payload, GetCFCheckptPayload::read_from(&bytes)?);
+        assert!(GetCFCheckptPayload::read_from(&bytes[..32]).is_err());
+        Ok(())
+    }
+}
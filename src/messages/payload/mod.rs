@@ -1,3 +1,4 @@
+pub mod compact_filters_payload;
 pub mod get_data_payload;
 pub mod getheaders_payload;
 pub mod version_payload;
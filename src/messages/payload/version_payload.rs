@@ -1,5 +1,6 @@
 use crate::compact_size_uint::CompactSizeUint;
 use crate::config::Config;
+use crate::messages::addr_message::ip_to_ipv6_bytes;
 use rand::Rng;
 use std::error::Error;
 use std::net::SocketAddr;
@@ -7,6 +8,11 @@ use std::str::Utf8Error;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Service bit of the nodes that can serve the last 288 blocks (BIP159), advertised by the pruned nodes.
+pub const NODE_NETWORK_LIMITED: u64 = 1 << 10;
+/// Service bit of the nodes that serve the compact block filters (BIP157).
+pub const NODE_COMPACT_FILTERS: u64 = 1 << 6;
+
 #[derive(Clone, Debug)]
 /// Represents the payload of a Version message according to the bitcoin protocol, with all its respective fields
 /// (corresponds to protocol version 70015)
@@ -212,17 +218,22 @@ pub fn get_current_unix_epoch_time() -> Result<i64, Box<dyn Error>> {
     Ok(seconds)
 }
 /// Receive a socket address and returns a [u8; 16] vector that represents the socket address.
+/// The Ipv4 addresses are mapped to Ipv6 and the Ipv6 ones are kept as they are.
 pub fn get_ipv6_address_ip(socket_addr: SocketAddr) -> [u8; 16] {
-    let mut addr_recv_ip: [u8; 16] = [0; 16];
-    let addr_recv_ip_aux: [u16; 8] = match socket_addr {
-        SocketAddr::V4(addr) => addr.ip().to_ipv6_mapped().segments(),
-        SocketAddr::V6(addr) => addr.ip().segments(),
+    ip_to_ipv6_bytes(&socket_addr.ip())
+}
+
+/// Returns the services supported by the node. A pruned node only serves the last blocks, and the compact
+/// filters are served if the filter index is enabled.
+fn get_services(config: &Arc<Config>) -> u64 {
+    let mut services = match config.prune_target_mb {
+        0 => 0u64,
+        _ => NODE_NETWORK_LIMITED,
     };
-    for (i, num) in addr_recv_ip_aux.iter().enumerate() {
-        let bytes = num.to_be_bytes(); // convert to bytes 
-        addr_recv_ip[(i * 2)..(i * 2 + 2)].copy_from_slice(&bytes); // copy bytes in the vsc of 8 bits
+    if config.block_filter_index {
+        services |= NODE_COMPACT_FILTERS;
     }
-    addr_recv_ip
+    services
 }
 
 /// Generates the payload for the bitcoin protocol version message.
@@ -232,16 +243,17 @@ pub fn get_version_payload(
     local_ip_addr: SocketAddr,
 ) -> Result<VersionPayload, Box<dyn Error>> {
     let timestamp: i64 = get_current_unix_epoch_time()?;
+    let services = get_services(config);
     Ok(VersionPayload {
         version: config.protocol_version,
-        services: 0u64,
+        services,
         timestamp,
         addr_recv_service: 1u64,
         addr_recv_ip: get_ipv6_address_ip(socket_addr),
-        addr_recv_port: 18333,
-        addr_trans_service: 0u64,
+        addr_recv_port: socket_addr.port(),
+        addr_trans_service: services,
         addr_trans_ip: get_ipv6_address_ip(local_ip_addr),
-        addr_trans_port: 18333,
+        addr_trans_port: config.net_port,
         nonce: rand::thread_rng().gen(),
         user_agent_bytes: CompactSizeUint::new(16u128),
         user_agent: config.user_agent.to_string(),
@@ -532,4 +544,15 @@ mod tests {
         assert_eq!(expected_bytes, ipv6_add_ip);
         Ok(())
     }
+    #[test]
+    fn get_ipv6_address_ip_keeps_the_bytes_of_an_ipv6_address_ip() -> Result<(), Box<dyn Error>> {
+        // GIVEN: a String representing an Ipv6 address ip
+        let add_ip = "[2001:db8::1]:18333".to_string();
+        // WHEN: the function get_ipv6_address_ip is call with the socket address as parameter
+        let ipv6_add_ip = get_ipv6_address_ip(add_ip.parse()?);
+        // THEN: returns the 16 bytes of the Ipv6 address without mapping them
+        let expected_bytes: [u8; 16] = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        assert_eq!(expected_bytes, ipv6_add_ip);
+        Ok(())
+    }
 }
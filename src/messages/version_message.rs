@@ -34,12 +34,20 @@ impl VersionMessage {
     /// version message according to the bitcoin protocol. Returns an error in case it cannot be read correctly 
     /// from the stream or in case the bytes read cannot be deserialized to a VersionMessage struct, otherwise,
     /// returns an Ok() with a VersionMessage deserialized from the bytes it read from the stream.
+    /// The message must have the start string received, the one of the network of the node.
     pub fn read_from(
         log_sender: &LogSender,
         stream: &mut TcpStream,
+        start_string: [u8; 4],
     ) -> Result<VersionMessage, std::io::Error> {
         let header = HeaderMessage::read_from(log_sender, stream, "version".to_string(), None)
             .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))?;
+        if header.start_string != start_string {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "The node is not from the network of the node",
+            ));
+        }
         let payload_large = header.payload_size;
         let mut buffer_num = vec![0; payload_large as usize];
         stream.read_exact(&mut buffer_num)?;
@@ -59,7 +67,7 @@ pub fn get_version_message(
 ) -> Result<VersionMessage, Box<dyn Error>> {
     let version_payload = get_version_payload(config, socket_addr, local_ip_addr)?;
     let version_header = HeaderMessage {
-        start_string: config.start_string,
+        start_string: config.chain_params.magic,
         command_name: "version".to_string(),
         payload_size: version_payload.to_le_bytes().len() as u32,
         checksum: get_checksum(&version_payload.to_le_bytes()),
@@ -0,0 +1,282 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    blockchain::Blockchain,
+    blocks::{
+        block::{block_subsidy, Block, WITNESS_COMMITMENT_HEADER},
+        block_header::BlockHeader,
+        header_validation::HeaderChain,
+        utils_block::concatenate_and_hash,
+    },
+    compact_size_uint::CompactSizeUint,
+    custom_errors::NodeCustomErrors,
+    transactions::{
+        outpoint::Outpoint, script::p2pkh_script::generate_pubkey_script,
+        script::sig_script::SigScript, transaction::Transaction, tx_in::TxIn, tx_out::TxOut,
+    },
+    utxo_tuple::UtxoTuple,
+};
+
+/// Version of the blocks mined: the top bits of BIP9 without any deployment signaled.
+const BLOCK_VERSION: i32 = 0x20000000;
+/// Data pushed in the coinbase after the height, so that its script has at least the 4 bytes
+/// that are read as the height of the coinbase.
+const COINBASE_EXTRA_DATA: [u8; 5] = [0x04, 0x00, 0x00, 0x00, 0x00];
+/// Reserved value of the witness of the coinbase used in the witness commitment (BIP141).
+const WITNESS_RESERVED_VALUE: [u8; 32] = [0; 32];
+
+/// Builds the template of the block that extends the tip of the active chain: a coinbase that pays the subsidy and the
+/// fees to the address received, followed by the transactions received that can be included. A transaction is discarded
+/// if it is already in the active chain or if the block with it would not be valid. The timestamp of the block is
+/// the current time (or the median time past plus one if it is greater) and its difficulty the one required after the tip.
+/// The nonce of the block is not searched. Returns an error if the address is not valid or the blockchain cannot be read.
+pub fn build_block_template(
+    blockchain: &Blockchain,
+    address: &str,
+    transactions: &[Transaction],
+    current_time: u32,
+) -> Result<Block, NodeCustomErrors> {
+    let params = blockchain.chain_params;
+    let headers = blockchain
+        .headers
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let utxo_set = blockchain
+        .utxo_set
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    let tip = headers
+        .last()
+        .ok_or(NodeCustomErrors::OtherError(
+            "There are no headers in the blockchain".to_string(),
+        ))?
+        .hash();
+    let height = headers.len();
+    let chain = HeaderChain::new(&headers, &[]);
+    let time = current_time.max(chain.median_time_past() + 1);
+    let n_bits = chain
+        .next_work_required(time, params)
+        .ok_or(NodeCustomErrors::OtherError(
+            "The difficulty of the next block cannot be calculated".to_string(),
+        ))?;
+    let pk_script = generate_pubkey_script(address, params)
+        .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
+    let header = BlockHeader::new(BLOCK_VERSION, tip, [0; 32], time, n_bits, 0);
+    let coinbase = coinbase_transaction(
+        height,
+        block_subsidy(height, params.subsidy_halving_interval),
+        pk_script.clone(),
+    );
+    let mut block = Block::new(header, CompactSizeUint::new(1), vec![coinbase]);
+    let utxo_set_is_complete = blockchain.utxo_set_is_complete();
+    let mut included_transactions: HashSet<[u8; 32]> = HashSet::new();
+    for transaction in transactions {
+        let hash = transaction.hash();
+        if transaction.is_coinbase_transaction()
+            || utxo_set.contains_key(&hash)
+            || !included_transactions.insert(hash)
+        {
+            continue;
+        }
+        block.txn.push(transaction.clone());
+        let transactions_are_valid = block.validate_scripts(&utxo_set).0
+            && block
+                .validate_utxos(
+                    &utxo_set,
+                    height,
+                    params.subsidy_halving_interval,
+                    utxo_set_is_complete,
+                )
+                .0
+            && block.validate_lock_times(&utxo_set, height, &chain).0;
+        if !transactions_are_valid {
+            block.txn.pop();
+        }
+    }
+    let fees = fees_of_transactions(&block.txn[1..], &utxo_set);
+    block.txn[0] = coinbase_transaction(
+        height,
+        block_subsidy(height, params.subsidy_halving_interval) + fees,
+        pk_script,
+    );
+    if block.txn.iter().any(|tx| tx.has_witness()) {
+        add_witness_commitment(&mut block);
+    }
+    block.txn_count = CompactSizeUint::new(block.txn.len() as u128);
+    block.block_header.merkle_root_hash = block.generate_merkle_root();
+    Ok(block)
+}
+
+/// Searches the nonce of the block until its hash meets the difficulty of its header. If all the nonces
+/// are tried, the timestamp is increased by one second and they are tried again.
+pub fn mine_block(block: &mut Block) {
+    while !block.block_header.validate() {
+        if block.block_header.nonce == u32::MAX {
+            block.block_header.nonce = 0;
+            block.block_header.time += 1;
+        } else {
+            block.block_header.nonce += 1;
+        }
+    }
+}
+
+/// Returns the coinbase transaction of the block of the received height, which pays the value received
+/// to the pubkey script. Its script starts with the height as BIP34 indicates.
+fn coinbase_transaction(height: usize, value: i64, pk_script: Vec<u8>) -> Transaction {
+    let mut script = bip34_height_script(height);
+    script.extend_from_slice(&COINBASE_EXTRA_DATA);
+    // The first 4 bytes of the script of the coinbase are kept as its height, as they are read from the network
+    let tx_in = TxIn::new(
+        Outpoint::new([0; 32], 0xffffffff),
+        CompactSizeUint::new(script.len() as u128),
+        Some(script[..4].to_vec()),
+        SigScript::new(script[4..].to_vec()),
+        0xffffffff,
+    );
+    let tx_out = TxOut::new(
+        value,
+        CompactSizeUint::new(pk_script.len() as u128),
+        pk_script,
+    );
+    Transaction::new(
+        1,
+        CompactSizeUint::new(1),
+        vec![tx_in],
+        CompactSizeUint::new(1),
+        vec![tx_out],
+        0,
+    )
+}
+
+/// Returns the script that pushes the height as the minimal script number, as BIP34 requires
+/// for the beginning of the script of the coinbase. Heights from 1 to 16 are pushed with OP_1 to OP_16.
+fn bip34_height_script(height: usize) -> Vec<u8> {
+    if (1..=16).contains(&height) {
+        return vec![0x50 + height as u8];
+    }
+    let mut number: Vec<u8> = Vec::new();
+    let mut value = height;
+    while value > 0 {
+        number.push((value & 0xff) as u8);
+        value >>= 8;
+    }
+    // The most significant bit is the sign, so a byte is added if it is set
+    if number.last().is_some_and(|byte| byte & 0x80 != 0) {
+        number.push(0);
+    }
+    let mut script = vec![number.len() as u8];
+    script.extend(number);
+    script
+}
+
+/// Returns the sum of the fees of the transactions, whose inputs spend outputs of the utxo set or
+/// of the previous transactions received.
+fn fees_of_transactions(
+    transactions: &[Transaction],
+    utxo_set: &HashMap<[u8; 32], UtxoTuple>,
+) -> i64 {
+    let mut previous_transactions: HashMap<[u8; 32], &Transaction> = HashMap::new();
+    let mut fees = 0;
+    for transaction in transactions {
+        let inputs_amount: i64 = transaction
+            .tx_in
+            .iter()
+            .filter_map(|tx_in| {
+                let hash = tx_in.get_previous_output_hash();
+                let index = tx_in.get_previous_output_index();
+                match previous_transactions.get(&hash) {
+                    Some(previous_tx) => previous_tx.tx_out.get(index).map(|tx_out| tx_out.value()),
+                    None => utxo_set
+                        .get(&hash)
+                        .and_then(|utxo| utxo.get_tx_out(index))
+                        .map(|tx_out| tx_out.value()),
+                }
+            })
+            .sum();
+        fees += inputs_amount - transaction.amount();
+        previous_transactions.insert(transaction.hash(), transaction);
+    }
+    fees
+}
+
+/// Adds the witness commitment (BIP141) to the coinbase of the block, so that it can include transactions
+/// with witness data: the reserved value as the witness of the coinbase and an output with the hash of
+/// the witness merkle root and that value.
+fn add_witness_commitment(block: &mut Block) {
+    let commitment =
+        concatenate_and_hash(block.generate_witness_merkle_root(), WITNESS_RESERVED_VALUE);
+    let mut pk_script = WITNESS_COMMITMENT_HEADER.to_vec();
+    pk_script.extend_from_slice(&commitment);
+    let coinbase = &mut block.txn[0];
+    coinbase.tx_in[0].set_witness(vec![WITNESS_RESERVED_VALUE.to_vec()]);
+    coinbase.tx_out.push(TxOut::new(
+        0,
+        CompactSizeUint::new(pk_script.len() as u128),
+        pk_script,
+    ));
+    coinbase.txout_count = CompactSizeUint::new(coinbase.tx_out.len() as u128);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chain_params::REGTEST_PARAMS;
+
+    #[test]
+    fn test_heights_are_encoded_as_minimal_script_numbers() {
+        assert_eq!(bip34_height_script(1), vec![0x51]);
+        assert_eq!(bip34_height_script(16), vec![0x60]);
+        assert_eq!(bip34_height_script(17), vec![0x01, 0x11]);
+        assert_eq!(bip34_height_script(128), vec![0x02, 0x80, 0x00]);
+        assert_eq!(bip34_height_script(2428246), vec![0x03, 0x56, 0x0d, 0x25]);
+    }
+
+    #[test]
+    fn test_coinbase_keeps_its_height_when_it_is_serialized_and_read_again(
+    ) -> Result<(), &'static str> {
+        let coinbase = coinbase_transaction(200, 5_000_000_000, vec![0x51]);
+        let mut bytes = Vec::new();
+        coinbase.marshalling(&mut bytes);
+
+        let coinbase_read = Transaction::unmarshalling(&bytes, &mut 0)?;
+
+        assert!(coinbase_read.is_coinbase_transaction());
+        assert_eq!(coinbase_read.hash(), coinbase.hash());
+        assert_eq!(coinbase_read.get_height(), 200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mined_block_meets_the_difficulty_of_its_header() {
+        let header = BlockHeader::new(
+            BLOCK_VERSION,
+            REGTEST_PARAMS.genesis_hash(),
+            [0; 32],
+            REGTEST_PARAMS.genesis_header.time + 1,
+            REGTEST_PARAMS.pow_limit_bits,
+            0,
+        );
+        let coinbase = coinbase_transaction(1, 5_000_000_000, vec![0x51]);
+        let mut block = Block::new(header, CompactSizeUint::new(1), vec![coinbase]);
+        block.block_header.merkle_root_hash = block.generate_merkle_root();
+
+        mine_block(&mut block);
+
+        assert!(block.validate().0);
+    }
+
+    #[test]
+    fn test_witness_commitment_added_to_the_coinbase_is_valid() {
+        let coinbase = coinbase_transaction(1, 5_000_000_000, vec![0x51]);
+        let mut block = Block::new(
+            REGTEST_PARAMS.genesis_header,
+            CompactSizeUint::new(1),
+            vec![coinbase],
+        );
+
+        add_witness_commitment(&mut block);
+
+        assert_eq!(block.txn[0].tx_out.len(), 2);
+        assert!(block.validate_witness_commitment().0);
+    }
+}
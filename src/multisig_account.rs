@@ -0,0 +1,417 @@
+use std::error::Error;
+use std::io;
+
+use crate::account::{self, Account};
+use crate::address_decoder;
+use crate::chain_params::ChainParams;
+use crate::transactions::script::multisig_script::generate_multisig_script;
+use crate::transactions::transaction::Transaction;
+use crate::utxo_tuple::UtxoTuple;
+
+const MAX_REDEEM_SCRIPT_SIZE: usize = 520;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Represents the type of the outputs that lock the funds of a multisig account.
+pub enum MultisigType {
+    /// Pays to the hash160 of the redeem script, with a base58 address.
+    P2SH,
+    /// Pays to the sha256 of the witness script, with a bech32 address.
+    P2WSH,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Represents the signatures of one of the keys of a multisig account for the inputs of a transaction.
+/// Stores the compressed public key of the signer and a signature for each input, in the same order.
+pub struct PartialSignatures {
+    pub pubkey: [u8; 33],
+    pub signatures: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+/// Represents an m-of-n multisig account shared by several keys.
+/// Stores the amount of required signatures, the compressed public keys, the multisig script
+/// (redeem script or witness script), the P2SH or P2WSH address, the chain params of its network and the
+/// utxos of the account. It does not have private keys: each input is signed by the accounts of the keys.
+pub struct MultisigAccount {
+    pub required_signatures: usize,
+    pub pubkeys: Vec<[u8; 33]>,
+    pub multisig_script: Vec<u8>,
+    pub multisig_type: MultisigType,
+    pub address: String,
+    pub chain_params: &'static ChainParams,
+    pub utxo_set: Vec<UtxoTuple>,
+}
+
+impl MultisigAccount {
+    /// Receives the amount of required signatures, the compressed public keys (in the order in which
+    /// they must sign), the type of the outputs of the account and the chain params of its network.
+    /// Returns an error if the multisig script can not be built with the received keys.
+    pub fn new(
+        required_signatures: usize,
+        pubkeys: Vec<[u8; 33]>,
+        multisig_type: MultisigType,
+        chain_params: &'static ChainParams,
+    ) -> Result<MultisigAccount, Box<dyn Error>> {
+        let multisig_script = generate_multisig_script(required_signatures, &pubkeys)
+            .map_err(|err| std::io::Error::new(io::ErrorKind::Other, err))?;
+        let address = match multisig_type {
+            MultisigType::P2SH => {
+                // the redeem script is pushed in the scriptSig, so it can not exceed the size of an element
+                if multisig_script.len() > MAX_REDEEM_SCRIPT_SIZE {
+                    return Err(Box::new(std::io::Error::new(
+                        io::ErrorKind::Other,
+                        "The redeem script of the multisig exceeds the maximum size",
+                    )));
+                }
+                address_decoder::generate_p2sh_address(&multisig_script, chain_params)
+            }
+            MultisigType::P2WSH => {
+                address_decoder::generate_p2wsh_address(&multisig_script, chain_params)
+            }
+        };
+        Ok(MultisigAccount {
+            required_signatures,
+            pubkeys,
+            multisig_script,
+            multisig_type,
+            address,
+            chain_params,
+            utxo_set: Vec::new(),
+        })
+    }
+
+    /// Returns the address of the account.
+    pub fn get_address(&self) -> &String {
+        &self.address
+    }
+
+    /// Stores the utxos in the account.
+    pub fn load_utxos(&mut self, utxos: Vec<UtxoTuple>) {
+        self.utxo_set = utxos;
+    }
+
+    /// Returns the balance of the account.
+    pub fn balance(&self) -> i64 {
+        self.utxo_set.iter().map(|utxo| utxo.balance()).sum()
+    }
+
+    /// Makes the transaction that sends the amount to the receiver, returning the change to the address
+    /// of the multisig, without signatures.
+    /// Returns the transaction and the utxos it spends, which the signers need to sign it.
+    pub fn make_unsigned_transaction(
+        &mut self,
+        address_receiver: &str,
+        amount: i64,
+        fee: i64,
+        lock_time: u32,
+    ) -> Result<(Transaction, Vec<UtxoTuple>), Box<dyn Error>> {
+        address_decoder::validate_address(address_receiver, self.chain_params)?;
+        if self.balance() <= amount + fee {
+            return Err(Box::new(std::io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "The balance of the account {} has less than {} satoshis",
+                    self.address,
+                    amount + fee,
+                ),
+            )));
+        }
+        let utxos_to_spend = account::get_utxos_for_amount(&mut self.utxo_set, amount + fee);
+        let unsigned_transaction = Transaction::generate_unsigned_transaction(
+            address_receiver,
+            &self.address,
+            amount,
+            fee,
+            &utxos_to_spend,
+            lock_time,
+            self.chain_params,
+        )?;
+        Ok((unsigned_transaction, utxos_to_spend))
+    }
+
+    /// Signs the inputs of the transaction with the key of the received account.
+    /// Returns an error if the account is not one of the keys of the multisig.
+    pub fn partial_sign(
+        &self,
+        transaction: &Transaction,
+        account: &Account,
+        utxos_to_spend: &[UtxoTuple],
+    ) -> Result<PartialSignatures, Box<dyn Error>> {
+        let pubkey = account.get_pubkey_compressed()?;
+        if !self.pubkeys.contains(&pubkey) {
+            return Err(Box::new(std::io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "The account {} is not one of the keys of the multisig",
+                    account.address
+                ),
+            )));
+        }
+        let signatures = transaction.generate_multisig_signatures(
+            account,
+            &self.multisig_script,
+            utxos_to_spend,
+        )?;
+        Ok(PartialSignatures { pubkey, signatures })
+    }
+
+    /// Combines the partial signatures collected from the keys of the multisig and adds them to the
+    /// transaction. The signatures are sorted in the order of the keys and only the required ones are used.
+    /// Returns an error if there are not enough signatures or if the signed transaction is not valid.
+    pub fn combine_signatures(
+        &self,
+        transaction: &mut Transaction,
+        partial_signatures: &[PartialSignatures],
+        utxos_to_spend: &[UtxoTuple],
+    ) -> Result<(), Box<dyn Error>> {
+        let signers: Vec<&PartialSignatures> = self
+            .pubkeys
+            .iter()
+            .filter_map(|pubkey| {
+                partial_signatures
+                    .iter()
+                    .find(|partial| partial.pubkey == *pubkey)
+            })
+            .take(self.required_signatures)
+            .collect();
+        if signers.len() < self.required_signatures {
+            return Err(Box::new(std::io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "The multisig needs {} signatures but only {} were collected",
+                    self.required_signatures,
+                    signers.len()
+                ),
+            )));
+        }
+        let mut signatures: Vec<Vec<Vec<u8>>> = Vec::new();
+        for index in 0..transaction.tx_in.len() {
+            let mut input_signatures = Vec::new();
+            for signer in &signers {
+                let signature = signer.signatures.get(index).ok_or_else(|| {
+                    std::io::Error::new(
+                        io::ErrorKind::Other,
+                        "A partial signature does not sign all the inputs of the transaction",
+                    )
+                })?;
+                input_signatures.push(signature.clone());
+            }
+            signatures.push(input_signatures);
+        }
+        transaction.add_multisig_signatures(&self.multisig_script, &signatures, utxos_to_spend)?;
+        transaction.validate(utxos_to_spend)?;
+        Ok(())
+    }
+
+    /// Makes the transaction that sends the amount to the receiver and signs it with the received
+    /// accounts, which must hold at least the required amount of keys of the multisig.
+    /// Returns the signed and validated transaction.
+    pub fn make_transaction(
+        &mut self,
+        signers: &[Account],
+        address_receiver: &str,
+        amount: i64,
+        fee: i64,
+        lock_time: u32,
+    ) -> Result<Transaction, Box<dyn Error>> {
+        let (mut transaction, utxos_to_spend) =
+            self.make_unsigned_transaction(address_receiver, amount, fee, lock_time)?;
+        let mut partial_signatures = Vec::new();
+        for signer in signers {
+            partial_signatures.push(self.partial_sign(&transaction, signer, &utxos_to_spend)?);
+        }
+        self.combine_signatures(&mut transaction, &partial_signatures, &utxos_to_spend)?;
+        Ok(transaction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use super::{MultisigAccount, MultisigType};
+    use crate::{
+        account::Account,
+        address_decoder::{decode_wif_private_key, generate_address},
+        chain_params::TESTNET_PARAMS,
+        compact_size_uint::CompactSizeUint,
+        transactions::{
+            script::{p2pkh_script::generate_pubkey_script, p2wsh_script::is_p2wsh},
+            transaction::Transaction,
+            tx_out::TxOut,
+        },
+        utxo_tuple::UtxoTuple,
+    };
+
+    const PRIVATE_KEYS: [&str; 3] = [
+        "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR",
+        "cQojsQ5fSonENC5EnrzzTAWSGX8PB4TBh6GunBxcCdGMJJiLULwZ",
+        "cNzfV2EAoKW8Xxi72kDyKRg3CoLtF7AkiB8HoYghfhhvMQV9EwY8",
+    ];
+    const RECEIVER_ADDRESS: &str = "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF";
+
+    /// Creates the P2PKH accounts of the private keys
+    fn create_accounts() -> Result<Vec<Account>, Box<dyn Error>> {
+        let mut accounts = Vec::new();
+        for private_key in PRIVATE_KEYS {
+            let address = generate_address(&decode_wif_private_key(private_key)?, &TESTNET_PARAMS)?;
+            accounts.push(Account::new(
+                private_key.to_string(),
+                address,
+                &TESTNET_PARAMS,
+            )?);
+        }
+        Ok(accounts)
+    }
+
+    /// Creates a 2-of-3 multisig account of the accounts with an output of 10000 satoshis
+    fn create_multisig_account(
+        accounts: &[Account],
+        multisig_type: MultisigType,
+    ) -> Result<MultisigAccount, Box<dyn Error>> {
+        let mut pubkeys = Vec::new();
+        for account in accounts {
+            pubkeys.push(account.get_pubkey_compressed()?);
+        }
+        let mut multisig = MultisigAccount::new(2, pubkeys, multisig_type, &TESTNET_PARAMS)?;
+        let pk_script = generate_pubkey_script(&multisig.address, &TESTNET_PARAMS)?;
+        let tx_out = TxOut::new(
+            10000,
+            CompactSizeUint::new(pk_script.len() as u128),
+            pk_script,
+        );
+        multisig.load_utxos(vec![UtxoTuple::new([7; 32], vec![(tx_out, 0)], 0, false)]);
+        Ok(multisig)
+    }
+
+    #[test]
+    fn test_p2sh_multisig_is_spent_with_the_signatures_and_the_redeem_script(
+    ) -> Result<(), Box<dyn Error>> {
+        let accounts = create_accounts()?;
+        let mut multisig = create_multisig_account(&accounts, MultisigType::P2SH)?;
+        assert!(multisig.address.starts_with('2'));
+        let transaction =
+            multisig.make_transaction(&accounts[1..], RECEIVER_ADDRESS, 5000, 1000, 0)?;
+        let script_sig = transaction.tx_in[0].signature_script.get_bytes();
+        assert_eq!(script_sig[0], 0x00);
+        assert!(script_sig.ends_with(&multisig.multisig_script));
+        assert!(transaction.tx_in[0].witness().is_empty());
+        assert_eq!(
+            transaction.tx_out[1].get_address(&TESTNET_PARAMS)?,
+            multisig.address
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_p2wsh_multisig_is_spent_with_the_signatures_in_the_witness(
+    ) -> Result<(), Box<dyn Error>> {
+        let accounts = create_accounts()?;
+        let mut multisig = create_multisig_account(&accounts, MultisigType::P2WSH)?;
+        assert!(multisig.address.starts_with("tb1q"));
+        assert!(is_p2wsh(&generate_pubkey_script(
+            &multisig.address,
+            &TESTNET_PARAMS
+        )?));
+        let transaction = multisig.make_transaction(
+            &[accounts[2].clone(), accounts[0].clone()],
+            RECEIVER_ADDRESS,
+            5000,
+            1000,
+            0,
+        )?;
+        assert!(transaction.tx_in[0].signature_script.get_bytes().is_empty());
+        let witness = transaction.tx_in[0].witness();
+        assert_eq!(witness.len(), 4);
+        assert!(witness[0].is_empty());
+        assert_eq!(witness[3], multisig.multisig_script);
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_signatures_are_combined_in_the_order_of_the_keys() -> Result<(), Box<dyn Error>>
+    {
+        let accounts = create_accounts()?;
+        let mut multisig = create_multisig_account(&accounts, MultisigType::P2SH)?;
+        let (mut transaction, utxos) =
+            multisig.make_unsigned_transaction(RECEIVER_ADDRESS, 5000, 1000, 0)?;
+        let third = multisig.partial_sign(&transaction, &accounts[2], &utxos)?;
+        let first = multisig.partial_sign(&transaction, &accounts[0], &utxos)?;
+        multisig.combine_signatures(&mut transaction, &[third, first], &utxos)?;
+        assert!(transaction.validate(&utxos).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bare_multisig_output_is_spent_with_the_signatures_in_the_script_sig(
+    ) -> Result<(), Box<dyn Error>> {
+        let accounts = create_accounts()?;
+        let multisig = create_multisig_account(&accounts, MultisigType::P2SH)?;
+        let pk_script = multisig.multisig_script.clone();
+        let tx_out = TxOut::new(
+            10000,
+            CompactSizeUint::new(pk_script.len() as u128),
+            pk_script,
+        );
+        let utxos = vec![UtxoTuple::new([8; 32], vec![(tx_out.clone(), 0)], 0, false)];
+        assert_eq!(tx_out.script_type(), "Multisig");
+        let mut transaction = Transaction::generate_unsigned_transaction(
+            RECEIVER_ADDRESS,
+            RECEIVER_ADDRESS,
+            5000,
+            1000,
+            &utxos,
+            0,
+            &TESTNET_PARAMS,
+        )?;
+        let mut signatures = vec![vec![]];
+        for account in &accounts[..2] {
+            signatures[0].extend(transaction.generate_multisig_signatures(
+                account,
+                &multisig.multisig_script,
+                &utxos,
+            )?);
+        }
+        transaction.add_multisig_signatures(&multisig.multisig_script, &signatures, &utxos)?;
+        assert!(!transaction.tx_in[0]
+            .signature_script
+            .get_bytes()
+            .ends_with(&multisig.multisig_script));
+        assert!(transaction.validate(&utxos).is_ok());
+        // the signatures must be in the order of the keys
+        signatures[0].reverse();
+        transaction.add_multisig_signatures(&multisig.multisig_script, &signatures, &utxos)?;
+        assert!(transaction.validate(&utxos).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_multisig_without_enough_signatures_returns_error() -> Result<(), Box<dyn Error>> {
+        let accounts = create_accounts()?;
+        let mut multisig = create_multisig_account(&accounts, MultisigType::P2WSH)?;
+        let result = multisig.make_transaction(&accounts[..1], RECEIVER_ADDRESS, 5000, 1000, 0);
+        assert!(result.is_err());
+        // the same key can not sign twice
+        let result = multisig.make_transaction(
+            &[accounts[1].clone(), accounts[1].clone()],
+            RECEIVER_ADDRESS,
+            5000,
+            1000,
+            0,
+        );
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_that_is_not_a_key_of_the_multisig_can_not_sign() -> Result<(), Box<dyn Error>> {
+        let accounts = create_accounts()?;
+        let mut multisig = create_multisig_account(&accounts[..2], MultisigType::P2SH)?;
+        let (transaction, utxos) =
+            multisig.make_unsigned_transaction(RECEIVER_ADDRESS, 5000, 1000, 0)?;
+        assert!(multisig
+            .partial_sign(&transaction, &accounts[2], &utxos)
+            .is_err());
+        Ok(())
+    }
+}
@@ -1,34 +1,47 @@
 use std::{
-    net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
-    sync::Arc,
+    net::{AddrParseError, IpAddr, SocketAddr, ToSocketAddrs},
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
+    address_manager::{AddressManager, AddressManagerPointer},
     config::Config,
     custom_errors::NodeCustomErrors,
     logwriter::log_writer::{write_in_log, LogSender},
+    messages::addr_message::NetworkAddress,
 };
 
-/// Returns a list of Ipv4 addresses obtained from the DNS seed and the nodes manually entered 
-/// in the configuration file
+/// Returns a list of the socket addresses of the nodes obtained from the DNS seed and the nodes manually entered 
+/// in the configuration file. The nodes of the DNS seed are added to the address manager, and if the DNS seed
+/// is unavailable the nodes are chosen from the addresses of the address manager.
 pub fn get_active_nodes_from_dns_seed(
     config: &Arc<Config>,
     log_sender: &LogSender,
-) -> Result<Vec<Ipv4Addr>, NodeCustomErrors> {
+    address_manager: &AddressManagerPointer,
+) -> Result<Vec<SocketAddr>, NodeCustomErrors> {
     let mut node_ips = Vec::new();
     if config.connect_to_dns_nodes {
         // If in the configuration file it is set that it connects to the nodes of the dns seed
-        get_nodes_from_dns_seed(config, log_sender, &mut node_ips)?;
+        let dns_seed_result = get_nodes_from_dns_seed(config, log_sender, &mut node_ips);
+        if node_ips.is_empty() {
+            get_nodes_from_address_manager(config, log_sender, address_manager, &mut node_ips)?;
+            if node_ips.is_empty() {
+                dns_seed_result?;
+            }
+        } else {
+            add_nodes_to_address_manager(address_manager, &node_ips)?;
+        }
     }
     for custom_node in config.custom_nodes_ips.iter() {
         // For each node manually entered in the configuration file
-        let custom_node_ip = match custom_node.parse::<Ipv4Addr>() {
-            Ok(ip) => ip,
+        let custom_node_addr = match parse_custom_node(custom_node, config.net_port) {
+            Ok(addr) => addr,
             Err(err) => {
                 write_in_log(
                     &log_sender.error_log_sender,
                     format!(
-                        "Error trying to parse the ip {} of the manually entered node: {}. It must be Ipv4 format: xxx.x.x.x",
+                        "Error trying to parse the ip {} of the manually entered node: {}. It must be an Ipv4 or Ipv6 address, optionally followed by the port: xxx.x.x.x:port or [x:x::x]:port",
                         custom_node,
                         err
                     )
@@ -37,25 +50,38 @@ pub fn get_active_nodes_from_dns_seed(
                 continue;
             }
         };
-        node_ips.push(custom_node_ip);
+        node_ips.push(custom_node_addr);
     }
     Ok(node_ips)
 }
 
-/// Gets the addresses of the nodes from the DNS seed
+/// Gets the Ipv4 and Ipv6 addresses of the nodes from the DNS seed configured, or from the DNS seeds of the network
+/// if it is not configured. Returns an error if none of them can be resolved.
 fn get_nodes_from_dns_seed(
     config: &Arc<Config>,
     log_sender: &LogSender,
-    node_ips: &mut Vec<Ipv4Addr>,
+    node_ips: &mut Vec<SocketAddr>,
 ) -> Result<(), NodeCustomErrors> {
-    let host = config.dns_seed.clone();
     let port = config.net_port;
-    let addrs = (host, port)
-        .to_socket_addrs()
-        .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
-    for addr in addrs {
-        if let SocketAddr::V4(v4_addr) = addr {
-            node_ips.push(*v4_addr.ip());
+    let dns_seeds = config.dns_seeds();
+    let mut last_error = None;
+    for host in dns_seeds.iter() {
+        let addrs = match (host.as_str(), port).to_socket_addrs() {
+            Ok(addrs) => addrs,
+            Err(err) => {
+                write_in_log(
+                    &log_sender.error_log_sender,
+                    format!("Error trying to resolve the DNS seed {}: {}", host, err).as_str(),
+                );
+                last_error = Some(NodeCustomErrors::SocketError(err.to_string()));
+                continue;
+            }
+        };
+        node_ips.extend(addrs);
+    }
+    if let Some(err) = last_error {
+        if node_ips.is_empty() {
+            return Err(err);
         }
     }
     write_in_log(
@@ -69,3 +95,129 @@ fn get_nodes_from_dns_seed(
     );
     Ok(())
 }
+
+/// Chooses nodes from the addresses of the address manager, as many as the number of nodes configured.
+fn get_nodes_from_address_manager(
+    config: &Arc<Config>,
+    log_sender: &LogSender,
+    address_manager: &AddressManagerPointer,
+    node_ips: &mut Vec<SocketAddr>,
+) -> Result<(), NodeCustomErrors> {
+    let selected_ips = address_manager
+        .read()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+        .select(config.number_of_nodes, node_ips);
+    node_ips.extend(selected_ips);
+    write_in_log(
+        &log_sender.info_log_sender,
+        format!(
+            "The DNS seed is unavailable, {} ips get from the address manager: {:?}\n",
+            node_ips.len(),
+            node_ips
+        )
+        .as_str(),
+    );
+    Ok(())
+}
+
+/// Adds the nodes to the new table of the address manager, as if they were seen now.
+fn add_nodes_to_address_manager(
+    address_manager: &AddressManagerPointer,
+    node_ips: &[SocketAddr],
+) -> Result<(), NodeCustomErrors> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?
+        .as_secs() as u32;
+    let mut address_manager = address_manager
+        .write()
+        .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+    for node_ip in node_ips {
+        address_manager.add(NetworkAddress::new(time, 0, *node_ip), node_ip.ip());
+    }
+    Ok(())
+}
+
+/// Opens the address manager stored in the peers file of the configuration. If it can not be read, the error is logged
+/// and an empty address manager is returned, so that the addresses are learned again from the network.
+pub fn open_address_manager(config: &Arc<Config>, log_sender: &LogSender) -> AddressManagerPointer {
+    let address_manager = match AddressManager::open(&config.peers_file, config.chain_params) {
+        Ok(address_manager) => address_manager,
+        Err(err) => {
+            write_in_log(
+                &log_sender.error_log_sender,
+                format!(
+                    "Error trying to read the peers file {}: {}. The addresses are learned again",
+                    config.peers_file, err
+                )
+                .as_str(),
+            );
+            AddressManager::new(&config.peers_file, config.chain_params)
+        }
+    };
+    Arc::new(RwLock::new(address_manager))
+}
+
+/// Parses the address of a node manually entered in the configuration file. It can be an Ipv4 or Ipv6 address
+/// (the Ipv6 one can be written between brackets), in which case the port of the network is used, or an address
+/// followed by its port (xxx.x.x.x:port or [x:x::x]:port), so that several nodes can run in the same host.
+/// Returns an error if it has neither format.
+fn parse_custom_node(custom_node: &str, net_port: u16) -> Result<SocketAddr, AddrParseError> {
+    match custom_node.parse::<SocketAddr>() {
+        Ok(addr) => Ok(addr),
+        Err(_) => {
+            let ip = custom_node
+                .strip_prefix('[')
+                .and_then(|ip| ip.strip_suffix(']'))
+                .unwrap_or(custom_node);
+            Ok(SocketAddr::new(ip.parse::<IpAddr>()?, net_port))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn test_custom_node_without_port_uses_the_port_of_the_network() -> Result<(), AddrParseError> {
+        let addr = parse_custom_node("127.0.0.1", 18444)?;
+
+        assert_eq!(addr, SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 18444));
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_node_with_port_uses_its_own_port() -> Result<(), AddrParseError> {
+        let addr = parse_custom_node("127.0.0.1:18500", 18444)?;
+
+        assert_eq!(addr, SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 18500));
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_node_with_ipv6_address_is_parsed_with_and_without_port(
+    ) -> Result<(), AddrParseError> {
+        assert_eq!(
+            parse_custom_node("::1", 18444)?,
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 18444)
+        );
+        assert_eq!(
+            parse_custom_node("[::1]", 18444)?,
+            SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 18444)
+        );
+        assert_eq!(
+            parse_custom_node("[2001:db8::1]:18500", 18444)?,
+            SocketAddr::new("2001:db8::1".parse::<Ipv6Addr>().unwrap().into(), 18500)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_node_with_invalid_format_is_not_parsed() {
+        assert!(parse_custom_node("127.0.0", 18444).is_err());
+        assert!(parse_custom_node("localhost:18444", 18444).is_err());
+        assert!(parse_custom_node("[::1", 18444).is_err());
+    }
+}
@@ -2,20 +2,26 @@ use gtk::glib;
 
 use crate::{
     account::Account,
-    blockchain::Blockchain,
+    address_decoder::validate_address,
+    address_manager::AddressManagerPointer,
+    blockchain::{Blockchain, TransactionSearchResult},
     blocks::{block::Block, block_header::BlockHeader},
+    chain_params::Network,
     custom_errors::NodeCustomErrors,
     gtk::ui_events::UIEvent,
-    handler::node_message_handler::NodeMessageHandler,
+    handler::{message_handlers::process_new_block, node_message_handler::NodeMessageHandler},
     logwriter::log_writer::LogSender,
     messages::inventory::{inv_mershalling, Inventory},
+    miner::{build_block_template, mine_block},
     node_data_pointers::NodeDataPointers,
+    transactions::transaction::Transaction,
     utxo_tuple::UtxoTuple,
 };
 use std::{
     error::Error,
     net::TcpStream,
     sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 type MerkleProofOfInclusionResult = Result<Option<Vec<([u8; 32], bool)>>, NodeCustomErrors>;
@@ -32,18 +38,21 @@ pub struct Node {
 }
 
 impl Node {
-    /// Initializes the node. Receives the blockchain already downloaded.
+    /// Initializes the node. Receives the blockchain already downloaded and the address manager
+    /// with the addresses of the nodes of the network.
     pub fn new(
         log_sender: &LogSender,
         ui_sender: &Option<glib::Sender<UIEvent>>,
         connected_nodes: Arc<RwLock<Vec<TcpStream>>>,
         blockchain: Blockchain,
+        address_manager: AddressManagerPointer,
     ) -> Result<Self, NodeCustomErrors> {
         let pointer_to_accounts_in_node = Arc::new(RwLock::new(Arc::new(RwLock::new(vec![]))));
         let node_pointers = NodeDataPointers::new(
             connected_nodes.clone(),
             blockchain.clone(),
             pointer_to_accounts_in_node.clone(),
+            address_manager,
         );
         let peers_handler = NodeMessageHandler::new(log_sender, ui_sender, node_pointers.clone())?;
         Ok(Node {
@@ -64,37 +73,120 @@ impl Node {
         &self,
         address: &str,
     ) -> Result<Vec<UtxoTuple>, Box<dyn Error>> {
-        let mut account_utxo_set: Vec<UtxoTuple> = Vec::new();
-        for utxo in self
-            .blockchain
-            .utxo_set
-            .read()
-            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-            .values()
-        {
-            let aux_utxo = utxo.referenced_utxos(address);
-            let utxo_to_push = match aux_utxo {
-                Some(value) => value,
-                None => continue,
-            };
-            account_utxo_set.push(utxo_to_push);
-        }
-        Ok(account_utxo_set)
+        Ok(self.blockchain.utxos_of_address(address)?)
     }
 
-    /// Calls the finish() function of the node's peers_handler
+    /// Returns the confirmed transactions that funded the address or spent its outputs, found in the
+    /// address index. It is empty if the address index is disabled.
+    pub fn transactions_referenced_to_account(
+        &self,
+        address: &str,
+    ) -> Result<Vec<Transaction>, Box<dyn Error>> {
+        Ok(self.blockchain.transactions_of_address(address)?)
+    }
+
+    /// Calls the finish() function of the node's peers_handler and writes the utxo set and the addresses
+    /// of the address manager in disk
     pub fn shutdown_node(&self) -> Result<(), NodeCustomErrors> {
-        self.peers_handler.finish()
+        self.peers_handler.finish()?;
+        self.node_pointers
+            .address_manager
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .save()?;
+        self.blockchain.flush_chainstate()
     }
 
     /// Receives a vec of bytes that represents the raw format transaction to be sent
     /// to all the connected nodes
     pub fn broadcast_tx(&self, raw_tx: [u8; 32]) -> Result<(), NodeCustomErrors> {
         let inventories = vec![Inventory::new_tx(raw_tx)];
-        let inv_message_bytes = inv_mershalling(inventories);
+        let inv_message_bytes = inv_mershalling(self.blockchain.chain_params.magic, inventories);
         self.peers_handler.broadcast_to_nodes(inv_message_bytes)
     }
 
+    /// Receives a block built outside the network, for example a block mined locally in regtest, and connects it
+    /// as if it was received from a node. If it becomes the tip of the active chain it is announced to all the connected nodes.
+    /// Returns Ok(true) if the block is the new tip, Ok(false) if it is not valid or does not extend the active chain
+    /// or an error if it cannot be connected.
+    pub fn submit_block(
+        &self,
+        log_sender: &LogSender,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        block: Block,
+    ) -> Result<bool, NodeCustomErrors> {
+        process_new_block(log_sender, ui_sender, block, &self.node_pointers)
+    }
+
+    /// Mines the amount of blocks received over the tip of the active chain, only in regtest. Each block pays the
+    /// subsidy and the fees to the address received and includes the pending transactions of the accounts that
+    /// can be confirmed. The blocks are connected and announced to the connected nodes.
+    /// Returns the hashes of the blocks mined or an error if the address is not valid or a block is not accepted.
+    pub fn generate_to_address(
+        &self,
+        log_sender: &LogSender,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        address: &str,
+        amount_of_blocks: usize,
+    ) -> Result<Vec<[u8; 32]>, NodeCustomErrors> {
+        let params = self.blockchain.chain_params;
+        if params.network != Network::Regtest {
+            return Err(NodeCustomErrors::OtherError(
+                "Blocks can only be mined in regtest".to_string(),
+            ));
+        }
+        validate_address(address, params)
+            .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
+        let mut hashes = Vec::new();
+        for _ in 0..amount_of_blocks {
+            let current_time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?
+                .as_secs() as u32;
+            let pending_transactions = self.pending_transactions()?;
+            let mut block = build_block_template(
+                &self.blockchain,
+                address,
+                &pending_transactions,
+                current_time,
+            )?;
+            mine_block(&mut block);
+            let hash = block.hash();
+            if !self.submit_block(log_sender, ui_sender, block)? {
+                return Err(NodeCustomErrors::InvalidBlockError(
+                    "The block mined was not accepted by the node".to_string(),
+                ));
+            }
+            hashes.push(hash);
+        }
+        Ok(hashes)
+    }
+
+    /// Returns the pending transactions of the accounts of the node, the ones that were sent or
+    /// received and are not confirmed yet.
+    fn pending_transactions(&self) -> Result<Vec<Transaction>, NodeCustomErrors> {
+        let accounts_pointer = self
+            .accounts
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+            .clone();
+        let accounts = accounts_pointer
+            .read()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
+        let mut pending_transactions = Vec::new();
+        for account in accounts.iter() {
+            pending_transactions.extend(
+                account
+                    .pending_transactions
+                    .read()
+                    .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
+                    .iter()
+                    .cloned(),
+            );
+        }
+        Ok(pending_transactions)
+    }
+
     /// Actualize what the accounts pointer points to another pointer that is passed by parameter
     /// in this way the pointer is pointing to a pointer with a vector of accounts that is pointed by the wallet
     pub fn set_accounts(
@@ -116,12 +208,7 @@ impl Node {
         block_hash: &[u8; 32],
         tx_hash: &[u8; 32],
     ) -> MerkleProofOfInclusionResult {
-        let block_chain = self
-            .blockchain
-            .blocks
-            .read()
-            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
-        let block_option = block_chain.get(block_hash);
+        let block_option = self.blockchain.search_block(*block_hash);
 
         match block_option {
             Some(block) => Ok(block.merkle_proof_of_inclusion(tx_hash)),
@@ -159,4 +246,17 @@ impl Node {
     pub fn search_header(&self, hash: [u8; 32]) -> Option<(BlockHeader, usize)> {
         self.blockchain.search_header(hash)
     }
+
+    /// Searchs a transaction of the blockchain by its txid in the transaction index.
+    /// Returns the transaction with the header and height of its block and its amount of confirmations,
+    /// None if it is not found or an error if the transaction index is disabled.
+    pub fn search_transaction(&self, txid: [u8; 32]) -> TransactionSearchResult {
+        self.blockchain.search_transaction(txid)
+    }
+
+    /// Builds again the transaction index from the stored blocks of the blockchain.
+    /// Returns the amount of blocks indexed or an error if the transaction index is disabled.
+    pub fn reindex_transactions(&self) -> Result<usize, NodeCustomErrors> {
+        self.blockchain.reindex_transactions()
+    }
 }
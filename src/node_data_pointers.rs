@@ -1,29 +1,37 @@
 use std::{
     net::TcpStream,
-    sync::{Arc, RwLock},
+    sync::{mpsc::Sender, Arc, RwLock},
 };
 
-use crate::{account::Account, blockchain::Blockchain};
+use crate::{account::Account, address_manager::AddressManagerPointer, blockchain::Blockchain};
 
 #[derive(Debug, Clone)]
 /// Stores the pointers of the node data that are shared between threads.
+/// The senders of the nodes are the ends of the channels that write to each connected node, so that
+/// any thread can relay a message to all of them. The address manager stores the addresses of the nodes
+/// of the network that are received from the connected nodes.
 pub struct NodeDataPointers {
     pub connected_nodes: Arc<RwLock<Vec<TcpStream>>>,
     pub blockchain: Blockchain,
     pub accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
+    pub nodes_senders: Arc<RwLock<Vec<Sender<Vec<u8>>>>>,
+    pub address_manager: AddressManagerPointer,
 }
 
 impl NodeDataPointers {
-    /// Returns a new instance of NodeDataPointers
+    /// Returns a new instance of NodeDataPointers without senders of nodes
     pub fn new(
         connected_nodes: Arc<RwLock<Vec<TcpStream>>>,
         blockchain: Blockchain,
         accounts: Arc<RwLock<Arc<RwLock<Vec<Account>>>>>,
+        address_manager: AddressManagerPointer,
     ) -> Self {
         NodeDataPointers {
             connected_nodes,
             blockchain,
             accounts,
+            nodes_senders: Arc::new(RwLock::new(Vec::new())),
+            address_manager,
         }
     }
 }
@@ -0,0 +1,196 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use crate::custom_errors::NodeCustomErrors;
+
+/// Minimum amount of records in the file before it is compacted
+const MIN_RECORDS_TO_COMPACT: usize = 1000;
+const TEMPORARY_FILE_EXTENSION: &str = "tmp";
+
+#[derive(Debug)]
+/// Represents the append-only file of records of an index. Each change of the index is appended in a single
+/// record, and the index is rebuilt from the records when the node starts. A record that was not completely
+/// written is removed from the file when it is loaded. As the records of the disconnected blocks cancel the
+/// ones of their connection, the file is compacted by rewriting it with the records of the current state of
+/// the index once most of its records are no longer needed.
+pub struct RecordLog {
+    path: PathBuf,
+    records_in_file: usize,
+}
+
+impl RecordLog {
+    /// Opens the file of records with the received name in the directory, creating the directory if it
+    /// does not exist. The records are not read until the log is loaded.
+    /// Returns an error if the directory can not be created.
+    pub fn open(directory: &str, file_name: &str) -> Result<RecordLog, NodeCustomErrors> {
+        fs::create_dir_all(directory)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        Ok(RecordLog {
+            path: PathBuf::from(directory).join(file_name),
+            records_in_file: 0,
+        })
+    }
+
+    /// Reads the records of the file with the received function, which reads the record that starts in the
+    /// offset and moves the offset to the next one, or returns None if the record was not completely written.
+    /// The bytes after the last complete record are removed from the file, as the node stopped while they were
+    /// written. Returns the records in the order they were appended, or an error if the file can not be read.
+    pub fn load<T>(
+        &mut self,
+        unmarshalling_record: impl Fn(&[u8], &mut usize) -> Option<T>,
+    ) -> Result<Vec<T>, NodeCustomErrors> {
+        let mut records = Vec::new();
+        if !self.path.exists() {
+            return Ok(records);
+        }
+        let mut data: Vec<u8> = Vec::new();
+        File::open(&self.path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|err| NodeCustomErrors::ReadingFileError(err.to_string()))?;
+        let mut offset = 0;
+        while let Some(record) = unmarshalling_record(&data, &mut offset) {
+            records.push(record);
+        }
+        if offset != data.len() {
+            OpenOptions::new()
+                .write(true)
+                .open(&self.path)
+                .and_then(|file| file.set_len(offset as u64))
+                .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        }
+        self.records_in_file = records.len();
+        Ok(records)
+    }
+
+    /// Appends the bytes of the record at the end of the file.
+    /// Returns an error if the file can not be written.
+    pub fn append(&mut self, record: &[u8]) -> Result<(), NodeCustomErrors> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        file.write_all(record)
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        self.records_in_file += 1;
+        Ok(())
+    }
+
+    /// Returns the amount of records in the file.
+    pub fn records_in_file(&self) -> usize {
+        self.records_in_file
+    }
+
+    /// Returns true if the file has many more records than the received amount of records that are needed
+    /// to rebuild the current state of the index.
+    pub fn needs_compaction(&self, needed_records: usize) -> bool {
+        self.records_in_file > MIN_RECORDS_TO_COMPACT && self.records_in_file > 2 * needed_records
+    }
+
+    /// Rewrites the file with the received records, which rebuild the current state of the index. The records
+    /// are written in a temporary file that replaces the file once it is completely written.
+    /// Returns an error if the temporary file can not be written or renamed.
+    pub fn compact(&mut self, records: &[Vec<u8>]) -> Result<(), NodeCustomErrors> {
+        let temporary_path = self.path.with_extension(TEMPORARY_FILE_EXTENSION);
+        let mut file = File::create(&temporary_path)
+            .map_err(|err| NodeCustomErrors::OpeningFileError(err.to_string()))?;
+        for record in records {
+            file.write_all(record)
+                .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        }
+        file.sync_all()
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        fs::rename(&temporary_path, &self.path)
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        self.records_in_file = records.len();
+        Ok(())
+    }
+
+    /// Removes all the records of the file.
+    /// Returns an error if the file can not be written.
+    pub fn reset(&mut self) -> Result<(), NodeCustomErrors> {
+        File::create(&self.path)
+            .map_err(|err| NodeCustomErrors::WritingInFileError(err.to_string()))?;
+        self.records_in_file = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::RecordLog;
+    use crate::{custom_errors::NodeCustomErrors, temp_directory::TempDirectory};
+
+    const FILE_NAME: &str = "records.dat";
+
+    /// Reads a record with a length byte followed by that amount of bytes
+    fn unmarshalling_record(data: &[u8], offset: &mut usize) -> Option<Vec<u8>> {
+        let length = *data.get(*offset)? as usize;
+        let record = data.get(*offset + 1..*offset + 1 + length)?.to_vec();
+        *offset += 1 + length;
+        Some(record)
+    }
+
+    #[test]
+    fn test_records_are_loaded_in_the_order_they_were_appended() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("record_log_order");
+        let directory_str = directory.path_string();
+        {
+            let mut record_log = RecordLog::open(&directory_str, FILE_NAME)?;
+            record_log.append(&[2, 10, 11])?;
+            record_log.append(&[1, 12])?;
+        }
+        let mut record_log = RecordLog::open(&directory_str, FILE_NAME)?;
+        let records = record_log.load(unmarshalling_record)?;
+        assert_eq!(records, vec![vec![10, 11], vec![12]]);
+        assert_eq!(record_log.records_in_file(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_that_was_not_completely_written_is_removed() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("record_log_torn");
+        let directory_str = directory.path_string();
+        {
+            let mut record_log = RecordLog::open(&directory_str, FILE_NAME)?;
+            record_log.append(&[1, 10])?;
+            // the node stopped while writing the second record
+            record_log.append(&[3, 11])?;
+        }
+        let mut record_log = RecordLog::open(&directory_str, FILE_NAME)?;
+        assert_eq!(record_log.load(unmarshalling_record)?, vec![vec![10]]);
+        assert_eq!(
+            fs::read(directory.path().join(FILE_NAME)).unwrap_or_default(),
+            vec![1, 10]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compacted_file_only_has_the_received_records() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("record_log_compact");
+        let directory_str = directory.path_string();
+        let mut record_log = RecordLog::open(&directory_str, FILE_NAME)?;
+        for value in 0..=1000 {
+            record_log.append(&[1, (value % 256) as u8])?;
+        }
+        assert!(record_log.needs_compaction(500));
+        assert!(!record_log.needs_compaction(501));
+        record_log.compact(&[vec![1, 20], vec![1, 21]])?;
+        assert_eq!(record_log.records_in_file(), 2);
+        assert!(!directory.path().join("records.tmp").exists());
+        let mut record_log = RecordLog::open(&directory_str, FILE_NAME)?;
+        assert_eq!(
+            record_log.load(unmarshalling_record)?,
+            vec![vec![20], vec![21]]
+        );
+        record_log.reset()?;
+        assert!(record_log.load(unmarshalling_record)?.is_empty());
+        Ok(())
+    }
+}
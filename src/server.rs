@@ -1,5 +1,5 @@
 use std::{
-    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    net::{SocketAddr, TcpListener, TcpStream},
     sync::{
         mpsc::{self, Receiver, Sender},
         Arc,
@@ -15,14 +15,12 @@ use crate::{
     gtk::ui_events::UIEvent,
     logwriter::log_writer::{write_in_log, LogSender},
     messages::{
-        message_header::{read_verack_message, write_verack_message},
+        message_header::{read_verack_message, write_sendaddrv2_message, write_verack_message},
         version_message::{get_version_message, VersionMessage},
     },
     node::Node,
 };
 
-const LOCALHOST: &str = "127.0.0.1";
-
 #[derive(Debug)]
 /// Represents a node server.
 /// Sender to indicate to the TcpListener to stop listening for incoming connections
@@ -33,15 +31,38 @@ pub struct NodeServer {
 }
 
 impl NodeServer {
-    /// Creates a new NodeServer
+    /// Creates a new NodeServer that listens in the port of the config in each of the listen ips.
+    /// Returns an error if none of them can be bound.
     pub fn new(
         config: &Arc<Config>,
         log_sender: &LogSender,
         ui_sender: &Option<glib::Sender<UIEvent>>,
         node: &mut Node,
     ) -> Result<NodeServer, NodeCustomErrors> {
+        let addresses: Vec<SocketAddr> = config
+            .listen_ips
+            .iter()
+            .map(|ip| SocketAddr::new(*ip, config.net_port))
+            .collect();
+        let listeners = Self::bind_listeners(log_sender, &addresses)?;
+        Self::with_listeners(config, log_sender, ui_sender, node, listeners)
+    }
+
+    /// Creates a new NodeServer that listens in the listeners received, which are already bound (for example
+    /// to a port chosen by the system). Returns an error if they can not be set as non blocking.
+    pub fn with_listeners(
+        config: &Arc<Config>,
+        log_sender: &LogSender,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        node: &mut Node,
+        listeners: Vec<TcpListener>,
+    ) -> Result<NodeServer, NodeCustomErrors> {
+        for listener in listeners.iter() {
+            listener
+                .set_nonblocking(true)
+                .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
+        }
         let (sender, rx) = mpsc::channel();
-        let address = get_socket(LOCALHOST.to_string(), config.net_port)?;
         let mut node_clone = node.clone();
         let log_sender_clone = log_sender.clone();
         let config = config.clone();
@@ -52,14 +73,14 @@ impl NodeServer {
                 &log_sender_clone,
                 &ui_sender,
                 &mut node_clone,
-                address,
+                listeners,
                 rx,
             )
         });
         Ok(NodeServer { sender, handle })
     }
 
-    /// Listen for incoming connections and handles them.
+    /// Listen for incoming connections in all the listeners received and handles them.
     /// If a message arrives by the channel, it means that it must stop listening and cut the loop.
     /// Returns an error if any occurs that is not of the type WouldBlock.
     fn listen(
@@ -67,53 +88,85 @@ impl NodeServer {
         log_sender: &LogSender,
         ui_sender: &Option<glib::Sender<UIEvent>>,
         node: &mut Node,
-        address: SocketAddr,
+        listeners: Vec<TcpListener>,
         rx: Receiver<String>,
     ) -> Result<(), NodeCustomErrors> {
-        let address = format!("{}:{}", address.ip(), address.port());
-        let listener: TcpListener = TcpListener::bind(&address)
-            .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
-        listener
-            .set_nonblocking(true)
-            .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
         let mut amount_of_connections = 0;
         write_in_log(
             &log_sender.info_log_sender,
             "Start listening for incoming connections!",
         );
-        for stream in listener.incoming() {
+        loop {
             // stop message
             if rx.try_recv().is_ok() {
                 write_in_log(
                     &log_sender.info_log_sender,
                     "Stop listening for incoming connections!",
                 );
-                break;
+                return Ok(());
             }
-            match stream {
-                Ok(stream) => {
-                    if amount_of_connections > config.max_connections_to_server {
-                        break;
+            for listener in listeners.iter() {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if amount_of_connections > config.max_connections_to_server {
+                            return Ok(());
+                        }
+                        write_in_log(
+                            &log_sender.info_log_sender,
+                            format!(
+                                "Receives new incoming connection from --{:?}--",
+                                stream.peer_addr()
+                            )
+                            .as_str(),
+                        );
+                        Self::handle_incoming_connection(
+                            config, log_sender, ui_sender, node, stream,
+                        )?;
+                        amount_of_connections += 1;
+                    }
+                    Err(ref err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                        // This doesen't mean an error ocurred, there just wasn't a connection at the moment
+                        continue;
                     }
+                    Err(err) => return Err(NodeCustomErrors::CanNotRead(err.to_string())),
+                }
+            }
+        }
+    }
+
+    /// Binds a listener in each of the addresses received. The addresses that can not be bound
+    /// (for example 0.0.0.0 after :: in a dual-stack host) are logged and skipped, so that the node listens in the rest.
+    /// Returns an error if none of them can be bound.
+    fn bind_listeners(
+        log_sender: &LogSender,
+        addresses: &[SocketAddr],
+    ) -> Result<Vec<TcpListener>, NodeCustomErrors> {
+        let mut listeners = Vec::new();
+        let mut last_error = NodeCustomErrors::SocketError(
+            "There are no ips to listen for incoming connections".to_string(),
+        );
+        for address in addresses {
+            match TcpListener::bind(address) {
+                Ok(listener) => {
                     write_in_log(
                         &log_sender.info_log_sender,
-                        format!(
-                            "Receives new incoming connection from --{:?}--",
-                            stream.peer_addr()
-                        )
-                        .as_str(),
+                        format!("Listening for incoming connections in {}", address).as_str(),
                     );
-                    Self::handle_incoming_connection(config, log_sender, ui_sender, node, stream)?;
-                    amount_of_connections += 1;
+                    listeners.push(listener);
                 }
-                Err(ref err) if err.kind() == std::io::ErrorKind::WouldBlock => {
-                    // This doesen't mean an error ocurred, there just wasn't a connection at the moment
-                    continue;
+                Err(err) => {
+                    write_in_log(
+                        &log_sender.error_log_sender,
+                        format!("Error trying to listen in {}: {}", address, err).as_str(),
+                    );
+                    last_error = NodeCustomErrors::SocketError(err.to_string());
                 }
-                Err(err) => return Err(NodeCustomErrors::CanNotRead(err.to_string())),
             }
         }
-        Ok(())
+        if listeners.is_empty() {
+            return Err(last_error);
+        }
+        Ok(listeners)
     }
 
     /// Handles an incoming connection.
@@ -130,10 +183,12 @@ impl NodeServer {
         let local_ip_addr = stream
             .local_addr()
             .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
+        // the Ipv4 nodes connected to a dual-stack listener have Ipv4-mapped Ipv6 addresses
         let socket_addr = stream
             .peer_addr()
+            .map(|addr| SocketAddr::new(addr.ip().to_canonical(), addr.port()))
             .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
-        VersionMessage::read_from(log_sender, &mut stream)
+        VersionMessage::read_from(log_sender, &mut stream, config.chain_params.magic)
             .map_err(|err| NodeCustomErrors::CanNotRead(err.to_string()))?;
         let version_message = get_version_message(config, socket_addr, local_ip_addr)
             .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
@@ -142,7 +197,9 @@ impl NodeServer {
             .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))?;
         read_verack_message(log_sender, &mut stream)
             .map_err(|err| NodeCustomErrors::CanNotRead(err.to_string()))?;
-        write_verack_message(&mut stream)
+        write_sendaddrv2_message(&mut stream, config.chain_params.magic)
+            .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))?;
+        write_verack_message(&mut stream, config.chain_params.magic)
             .map_err(|err| NodeCustomErrors::WriteNodeError(err.to_string()))?;
         write_in_log(
             &log_sender.info_log_sender,
@@ -169,11 +226,3 @@ impl NodeServer {
         Ok(())
     }
 }
-
-/// Returns a SocketAddr from an ip and a port
-fn get_socket(ip: String, port: u16) -> Result<SocketAddr, NodeCustomErrors> {
-    let ip = ip
-        .parse::<IpAddr>()
-        .map_err(|err| NodeCustomErrors::SocketError(err.to_string()))?;
-    Ok(SocketAddr::new(ip, port))
-}
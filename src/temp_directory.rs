@@ -0,0 +1,46 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Amount of directories created by the tests of this process, to give each one a different name
+static CREATED_DIRECTORIES: AtomicUsize = AtomicUsize::new(0);
+
+/// Represents an empty directory of a test in the temporary directory, whose name is unique among the tests
+/// that run at the same time. The directory is removed with its files when it is dropped.
+pub struct TempDirectory {
+    path: PathBuf,
+}
+
+impl TempDirectory {
+    /// Creates a new empty directory whose name starts with the received name.
+    pub fn new(name: &str) -> TempDirectory {
+        let path = std::env::temp_dir().join(format!(
+            "{}_{}_{}",
+            name,
+            process::id(),
+            CREATED_DIRECTORIES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        let _ = fs::create_dir_all(&path);
+        TempDirectory { path }
+    }
+
+    /// Returns the path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the directory as a String.
+    pub fn path_string(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+}
+
+impl Drop for TempDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
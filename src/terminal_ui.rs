@@ -1,10 +1,16 @@
-use crate::{gtk::ui_events::UIEvent, wallet};
+use crate::{
+    account::bytes_to_hex_string, gtk::ui_events::UIEvent, logwriter::log_writer::LogSender, wallet,
+};
 use ::gtk::glib;
 use wallet::Wallet;
 
 /// Shows the options to interact with the program from the terminal, waits for some command
 /// and handle it or shows an error message
-pub fn terminal_ui(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
+pub fn terminal_ui(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
     show_options();
     loop {
         let mut input = String::new();
@@ -30,6 +36,15 @@ pub fn terminal_ui(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Walle
                         4 => {
                             handle_poi_request(wallet);
                         }
+                        5 => {
+                            handle_search_transaction_request(wallet);
+                        }
+                        6 => {
+                            handle_reindex_request(wallet);
+                        }
+                        7 => {
+                            handle_mine_blocks_request(log_sender, ui_sender, wallet);
+                        }
                         _ => {
                             println!("Not valid number. Try again! \n");
                         }
@@ -55,6 +70,9 @@ fn show_options() {
     println!("2: Show the balance of the accounts");
     println!("3: Make a transaction from an account");
     println!("4: Proof of inclusion of a transaction in a block");
+    println!("5: Search a transaction");
+    println!("6: Rebuild the transaction index");
+    println!("7: Mine blocks to an account (regtest)");
     println!("-----------------------------------------------------------\n");
 }
 
@@ -84,8 +102,16 @@ fn handle_transaccion_request(ui_sender: &Option<glib::Sender<UIEvent>>, wallet:
         println!("Error trying to read the input: {}", err);
         0
     });
+    let lock_time: u32 =
+        read_input("Lock time (block height or timestamp, 0 if it is not locked): ")
+            .unwrap_or_else(|err| {
+                println!("Error trying to read the input: {}", err);
+                0
+            });
     println!("Broadcasting transaction...");
-    if let Err(error) = wallet.make_transaction(ui_sender, &address_receiver, amount, fee) {
+    if let Err(error) =
+        wallet.make_transaction(ui_sender, &address_receiver, amount, fee, lock_time)
+    {
         println!("Error trying to make the transaction: {}", error);
     } else {
         println!("TRANSACTION MADE SUCCESSFULLY!");
@@ -194,3 +220,80 @@ fn handle_poi_request(wallet: &mut Wallet) {
         }
     }
 }
+
+/// Asks the user to enter the hash of a transaction by terminal and shows the transaction, its block and its
+/// confirmations. In case the transaction is not found or the data entered is incorrect, it shows it on the screen
+fn handle_search_transaction_request(wallet: &mut Wallet) {
+    let txid: String = match read_input("Enter the hash of the transaction: ") {
+        Ok(txid) => txid,
+        Err(error) => {
+            println!("Error trying to read the input: {}", error);
+            return;
+        }
+    };
+    match wallet.search_transaction(txid) {
+        Ok(Some((transaction, header, height, confirmations))) => {
+            let total_output: i64 = transaction.tx_out.iter().map(|tx_out| tx_out.value()).sum();
+            println!("TRANSACTION FOUND!\n");
+            println!("Hash: {}", transaction.hex_hash());
+            println!("Block: {}", header.hex_hash());
+            println!("Height: {}", height);
+            println!("Confirmations: {}", confirmations);
+            println!("Inputs: {}", transaction.tx_in.len());
+            println!("Outputs: {}", transaction.tx_out.len());
+            println!("Total output (sat): {}", total_output);
+        }
+        Ok(None) => println!("The transaction was not found in the blockchain."),
+        Err(err) => println!("ERROR: {err}\n"),
+    }
+}
+
+/// Rebuilds the transaction index from the stored blocks and shows the amount of blocks indexed
+fn handle_reindex_request(wallet: &mut Wallet) {
+    println!("Rebuilding the transaction index...\n");
+    match wallet.reindex_transactions() {
+        Ok(indexed_blocks) => println!("TRANSACTION INDEX REBUILT WITH {} BLOCKS!", indexed_blocks),
+        Err(err) => println!("ERROR: {err}\n"),
+    }
+}
+
+/// Asks the user to enter the account that receives the coinbases and the amount of blocks to mine by terminal,
+/// mines them and shows their hashes. In case of error it shows it on the screen
+fn handle_mine_blocks_request(
+    log_sender: &LogSender,
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+) {
+    if wallet.show_indexes_of_accounts().is_err() {
+        return;
+    }
+    let account_index: usize = match read_input("Account index: ") {
+        Ok(account_index) => account_index,
+        Err(error) => {
+            println!("Error trying to read the input: {}", error);
+            return;
+        }
+    };
+    if let Err(err) = wallet.change_account(ui_sender, account_index) {
+        println!("Error trying to change account: {}", err);
+        return;
+    }
+    let amount_of_blocks: usize = match read_input("Amount of blocks: ") {
+        Ok(amount_of_blocks) => amount_of_blocks,
+        Err(error) => {
+            println!("Error trying to read the input: {}", error);
+            return;
+        }
+    };
+    println!("Mining blocks...\n");
+    match wallet.generate_blocks(log_sender, ui_sender, amount_of_blocks) {
+        Ok(hashes) => {
+            println!("{} BLOCKS MINED!", hashes.len());
+            for mut hash in hashes {
+                hash.reverse();
+                println!("{}", bytes_to_hex_string(&hash));
+            }
+        }
+        Err(err) => println!("ERROR: {err}\n"),
+    }
+}
@@ -0,0 +1,1113 @@
+use super::{
+    p2sh_script::is_p2sh, p2tr_script::TAPROOT_WITNESS_VERSION,
+    p2wpkh_script::generate_p2wpkh_script_code, script_opcodes::ScriptOpcodes,
+    signature_checker::SignatureChecker,
+};
+use crate::transactions::transaction::SEQUENCE_LOCKTIME_DISABLE_FLAG;
+use bitcoin_hashes::{hash160, ripemd160, sha1, sha256, sha256d, Hash};
+
+/// Maximum size of a script in bytes
+const MAX_SCRIPT_SIZE: usize = 10000;
+/// Maximum amount of bytes of an element pushed to the stack
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Maximum amount of non push operations per script
+const MAX_OPS_PER_SCRIPT: usize = 201;
+/// Maximum amount of elements in the stack and the altstack together
+const MAX_STACK_SIZE: usize = 1000;
+/// Maximum amount of public keys of a CHECKMULTISIG
+const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+/// Maximum amount of bytes of the numbers used in arithmetic operations
+const MAX_NUMBER_SIZE: usize = 4;
+/// Maximum amount of bytes of the lock times of CHECKLOCKTIMEVERIFY and CHECKSEQUENCEVERIFY
+const MAX_LOCK_TIME_SIZE: usize = 5;
+/// First byte of the annex of a taproot witness
+const TAPROOT_ANNEX_TAG: u8 = 0x50;
+/// Minimum and maximum amount of bytes of a witness program
+const MIN_WITNESS_PROGRAM_SIZE: usize = 2;
+const MAX_WITNESS_PROGRAM_SIZE: usize = 40;
+/// Minimum and maximum amount of bytes of a DER signature with the sighash type byte
+const MIN_SIGNATURE_SIZE: usize = 9;
+const MAX_SIGNATURE_SIZE: usize = 73;
+
+type Stack = Vec<Vec<u8>>;
+/// Opcode of an instruction and the data pushed by it, if it is a push of data.
+type Instruction = (u8, Option<Vec<u8>>);
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+/// Indicates how the signatures of a script are checked: legacy scripts sign the transaction with
+/// the original algorithm and the scripts of witness v0 programs sign it as defined in BIP143.
+pub enum SigVersion {
+    Base,
+    WitnessV0,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+/// Represents a script (scriptSig or scriptPubKey) as defined in the bitcoin protocol.
+/// It can be evaluated over a stack, executing its opcodes one by one.
+pub struct Script {
+    bytes: Vec<u8>,
+    sig_version: SigVersion,
+}
+
+impl Script {
+    /// Creates the script with the bytes received.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Script {
+            bytes,
+            sig_version: SigVersion::Base,
+        }
+    }
+
+    /// Creates the script of a witness v0 program with the bytes received.
+    /// Its signatures are checked against the BIP143 signature hash.
+    pub fn new_witness_v0(bytes: Vec<u8>) -> Self {
+        Script {
+            bytes,
+            sig_version: SigVersion::WitnessV0,
+        }
+    }
+
+    /// Returns the bytes of the script.
+    pub fn get_bytes(&self) -> &Vec<u8> {
+        &self.bytes
+    }
+
+    /// Returns the instructions of the script. Each one contains the opcode and the data
+    /// pushed by it, if it is a push of data. Returns error if the script is malformed.
+    pub fn instructions(&self) -> Result<Vec<Instruction>, &'static str> {
+        let mut instructions = Vec::new();
+        let mut offset: usize = 0;
+        while offset < self.bytes.len() {
+            let (opcode, data) = read_instruction(&self.bytes, &mut offset)?;
+            instructions.push((opcode, data.map(|data| data.to_vec())));
+        }
+        Ok(instructions)
+    }
+
+    /// Returns true if the script only contains pushes of data.
+    pub fn is_push_only(&self) -> bool {
+        match self.instructions() {
+            Ok(instructions) => instructions
+                .iter()
+                .all(|(opcode, _)| *opcode <= ScriptOpcodes::OP_16),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the witness version and the witness program if the script is a witness program:
+    /// a push of a number from 0 to 16 followed by a push of 2 to 40 bytes (BIP141).
+    pub fn witness_program(&self) -> Option<(u8, &[u8])> {
+        let length = self.bytes.len();
+        if !(MIN_WITNESS_PROGRAM_SIZE + 2..=MAX_WITNESS_PROGRAM_SIZE + 2).contains(&length)
+            || self.bytes[1] as usize != length - 2
+        {
+            return None;
+        }
+        let witness_version = match self.bytes[0] {
+            ScriptOpcodes::OP_0 => 0,
+            opcode if (ScriptOpcodes::OP_1..=ScriptOpcodes::OP_16).contains(&opcode) => {
+                opcode - ScriptOpcodes::OP_1 + 1
+            }
+            _ => return None,
+        };
+        Some((witness_version, &self.bytes[2..]))
+    }
+
+    /// Verifies that the script_sig unlocks the script_pubkey.
+    /// Evaluates the script_sig, then the script_pubkey over the resulting stack and checks that the
+    /// element on the top of the stack is true. Returns the reason of the failure otherwise.
+    /// If the script_pubkey is P2SH, the script_sig can only push data and the last element it pushes is
+    /// the redeem script, which is evaluated over the rest of the stack left by the script_sig (BIP16).
+    pub fn verify(
+        script_sig: &Script,
+        script_pubkey: &Script,
+        checker: &dyn SignatureChecker,
+    ) -> Result<(), &'static str> {
+        Self::verify_with_witness(script_sig, script_pubkey, &[], checker)
+    }
+
+    /// Verifies that the script_sig and the witness unlock the script_pubkey, as Script::verify does.
+    /// If the script_pubkey (or the redeem script of a P2SH output) is a witness program, the script_sig
+    /// can not contain anything else and the witness is verified against the program (BIP141).
+    /// Taproot programs are only verified if they are not nested in P2SH (BIP341).
+    /// Inputs that spend outputs without a witness program can not have a witness.
+    pub fn verify_with_witness(
+        script_sig: &Script,
+        script_pubkey: &Script,
+        witness: &[Vec<u8>],
+        checker: &dyn SignatureChecker,
+    ) -> Result<(), &'static str> {
+        let mut stack: Stack = Vec::new();
+        script_sig.eval(&mut stack, checker)?;
+        let stack_of_script_sig = stack.clone();
+        script_pubkey.eval(&mut stack, checker)?;
+        match stack.last() {
+            Some(top) if cast_to_bool(top) => {}
+            _ => return Err("The script evaluated to false"),
+        }
+        let mut has_witness_program = false;
+        if let Some((witness_version, witness_program)) = script_pubkey.witness_program() {
+            if !script_sig.bytes.is_empty() {
+                return Err("The scriptSig of a witness output must be empty");
+            }
+            verify_witness_program(witness_version, witness_program, witness, checker, false)?;
+            has_witness_program = true;
+        }
+        if is_p2sh(&script_pubkey.bytes) {
+            if !script_sig.is_push_only() {
+                return Err("The scriptSig of a P2SH output can only push data");
+            }
+            let mut stack = stack_of_script_sig;
+            let redeem_script = Script::new(pop(&mut stack)?);
+            redeem_script.eval(&mut stack, checker)?;
+            match stack.last() {
+                Some(top) if cast_to_bool(top) => {}
+                _ => return Err("The redeem script evaluated to false"),
+            }
+            if let Some((witness_version, witness_program)) = redeem_script.witness_program() {
+                // the witness program nested in P2SH must be the only element pushed by the scriptSig
+                if script_sig.bytes != push_data_bytes(&redeem_script.bytes) {
+                    return Err(
+                        "The scriptSig of a P2SH witness output can only push the redeem script",
+                    );
+                }
+                verify_witness_program(witness_version, witness_program, witness, checker, true)?;
+                has_witness_program = true;
+            }
+        }
+        if !has_witness_program && !witness.is_empty() {
+            return Err("The input has a witness but it does not spend a witness program");
+        }
+        Ok(())
+    }
+
+    /// Evaluates the script over the received stack.
+    /// Returns error if any operation fails or if the script breaks one of the limits of the protocol.
+    pub fn eval(
+        &self,
+        stack: &mut Stack,
+        checker: &dyn SignatureChecker,
+    ) -> Result<(), &'static str> {
+        if self.bytes.len() > MAX_SCRIPT_SIZE {
+            return Err("The script exceeds the maximum size");
+        }
+        let mut altstack: Stack = Vec::new();
+        // each element indicates if the branch of the IF is executed or not
+        let mut exec_stack: Vec<bool> = Vec::new();
+        let mut op_count: usize = 0;
+        // the signed script code begins after the last OP_CODESEPARATOR executed
+        let mut code_separator_position: usize = 0;
+        let mut offset: usize = 0;
+        while offset < self.bytes.len() {
+            let executing = !exec_stack.contains(&false);
+            let (opcode, data) = read_instruction(&self.bytes, &mut offset)?;
+            if let Some(data) = data {
+                if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                    return Err("The script pushes an element bigger than 520 bytes");
+                }
+                if executing {
+                    stack.push(data.to_vec());
+                }
+            } else {
+                if opcode > ScriptOpcodes::OP_16 {
+                    op_count += 1;
+                    if op_count > MAX_OPS_PER_SCRIPT {
+                        return Err("The script exceeds the maximum amount of operations");
+                    }
+                }
+                // disabled opcodes make the script invalid even if they are not executed
+                if ScriptOpcodes::is_disabled(opcode) {
+                    return Err("The script contains a disabled opcode");
+                }
+                if executing || (ScriptOpcodes::OP_IF..=ScriptOpcodes::OP_ENDIF).contains(&opcode) {
+                    match opcode {
+                        ScriptOpcodes::OP_IF | ScriptOpcodes::OP_NOTIF => {
+                            let mut value = false;
+                            if executing {
+                                let top = pop(stack)?;
+                                value = cast_to_bool(&top);
+                                if opcode == ScriptOpcodes::OP_NOTIF {
+                                    value = !value;
+                                }
+                            }
+                            exec_stack.push(value);
+                        }
+                        ScriptOpcodes::OP_ELSE => match exec_stack.last_mut() {
+                            Some(value) => *value = !*value,
+                            None => return Err("The script has an unbalanced conditional"),
+                        },
+                        ScriptOpcodes::OP_ENDIF => {
+                            if exec_stack.pop().is_none() {
+                                return Err("The script has an unbalanced conditional");
+                            }
+                        }
+                        ScriptOpcodes::OP_CODESEPARATOR => code_separator_position = offset,
+                        ScriptOpcodes::OP_CHECKSIG | ScriptOpcodes::OP_CHECKSIGVERIFY => {
+                            let script_code = &self.bytes[code_separator_position..];
+                            check_sig(stack, script_code, checker, opcode, self.sig_version)?;
+                        }
+                        ScriptOpcodes::OP_CHECKMULTISIG | ScriptOpcodes::OP_CHECKMULTISIGVERIFY => {
+                            let script_code = &self.bytes[code_separator_position..];
+                            check_multisig(
+                                stack,
+                                script_code,
+                                checker,
+                                opcode,
+                                &mut op_count,
+                                self.sig_version,
+                            )?;
+                        }
+                        ScriptOpcodes::OP_CHECKLOCKTIMEVERIFY
+                        | ScriptOpcodes::OP_CHECKSEQUENCEVERIFY => {
+                            check_lock_time(stack, checker, opcode)?
+                        }
+                        _ => execute_opcode(opcode, stack, &mut altstack)?,
+                    }
+                }
+            }
+            if stack.len() + altstack.len() > MAX_STACK_SIZE {
+                return Err("The stack exceeds the maximum size");
+            }
+        }
+        if !exec_stack.is_empty() {
+            return Err("The script has an unbalanced conditional");
+        }
+        Ok(())
+    }
+}
+
+/// Reads the instruction located at the offset of the script and updates the offset.
+/// Returns the opcode and the data pushed by it, if it is a push of data.
+fn read_instruction<'a>(
+    bytes: &'a [u8],
+    offset: &mut usize,
+) -> Result<(u8, Option<&'a [u8]>), &'static str> {
+    let opcode = bytes[*offset];
+    *offset += 1;
+    let length_size: usize = match opcode {
+        ScriptOpcodes::OP_PUSHDATA1 => 1,
+        ScriptOpcodes::OP_PUSHDATA2 => 2,
+        ScriptOpcodes::OP_PUSHDATA4 => 4,
+        0x00..=0x4b => 0,
+        _ => return Ok((opcode, None)),
+    };
+    let length: usize = if length_size == 0 {
+        opcode as usize
+    } else {
+        if bytes.len() - *offset < length_size {
+            return Err("The script ends in the middle of a push of data");
+        }
+        let mut length_bytes: [u8; 4] = [0; 4];
+        length_bytes[..length_size].copy_from_slice(&bytes[*offset..*offset + length_size]);
+        *offset += length_size;
+        u32::from_le_bytes(length_bytes) as usize
+    };
+    if bytes.len() - *offset < length {
+        return Err("The script ends in the middle of a push of data");
+    }
+    let data = &bytes[*offset..*offset + length];
+    *offset += length;
+    Ok((opcode, Some(data)))
+}
+
+/// Returns the bytes of the instruction that pushes the received data to the stack,
+/// using the smallest push opcode.
+pub fn push_data_bytes(data: &[u8]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let length = data.len();
+    if length < ScriptOpcodes::OP_PUSHDATA1 as usize {
+        bytes.push(length as u8);
+    } else if length <= 0xff {
+        bytes.push(ScriptOpcodes::OP_PUSHDATA1);
+        bytes.push(length as u8);
+    } else if length <= 0xffff {
+        bytes.push(ScriptOpcodes::OP_PUSHDATA2);
+        bytes.extend_from_slice(&(length as u16).to_le_bytes());
+    } else {
+        bytes.push(ScriptOpcodes::OP_PUSHDATA4);
+        bytes.extend_from_slice(&(length as u32).to_le_bytes());
+    }
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+/// Removes from the script every occurrence of the pattern that starts at the beginning of an instruction.
+/// It is used to remove the signatures from the script code before checking them.
+fn find_and_delete(script: &[u8], pattern: &[u8]) -> Vec<u8> {
+    if pattern.is_empty() {
+        return script.to_vec();
+    }
+    let mut result: Vec<u8> = Vec::new();
+    let mut offset: usize = 0;
+    let mut copied_until: usize = 0;
+    loop {
+        result.extend_from_slice(&script[copied_until..offset]);
+        while script.len() - offset >= pattern.len()
+            && script[offset..offset + pattern.len()] == *pattern
+        {
+            offset += pattern.len();
+        }
+        copied_until = offset;
+        if offset >= script.len() || read_instruction(script, &mut offset).is_err() {
+            break;
+        }
+    }
+    result.extend_from_slice(&script[copied_until..]);
+    result
+}
+
+/// Decodes a number of the stack. Numbers are encoded in little endian, with the sign in the
+/// most significant bit of the last byte. Returns error if the number exceeds the maximum size.
+pub fn decode_number(bytes: &[u8], max_size: usize) -> Result<i64, &'static str> {
+    if bytes.len() > max_size {
+        return Err("The number exceeds the maximum size for an arithmetic operation");
+    }
+    if bytes.is_empty() {
+        return Ok(0);
+    }
+    let mut result: i64 = 0;
+    for (position, byte) in bytes.iter().enumerate() {
+        result |= (*byte as i64) << (8 * position);
+    }
+    let last_position = bytes.len() - 1;
+    if bytes[last_position] & 0x80 != 0 {
+        return Ok(-(result & !(0x80_i64 << (8 * last_position))));
+    }
+    Ok(result)
+}
+
+/// Encodes the number in the format used in the stack.
+pub fn encode_number(value: i64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    if value == 0 {
+        return bytes;
+    }
+    let negative = value < 0;
+    let mut absolute = value.unsigned_abs();
+    while absolute > 0 {
+        bytes.push((absolute & 0xff) as u8);
+        absolute >>= 8;
+    }
+    let last_position = bytes.len() - 1;
+    // if the most significant byte is already using the sign bit, another byte is needed
+    if bytes[last_position] & 0x80 != 0 {
+        bytes.push(if negative { 0x80 } else { 0x00 });
+    } else if negative {
+        bytes[last_position] |= 0x80;
+    }
+    bytes
+}
+
+/// Returns the boolean value of an element of the stack.
+/// It is false if all the bytes are zero, being the sign bit of the last one also accepted (negative zero).
+pub fn cast_to_bool(bytes: &[u8]) -> bool {
+    for (position, byte) in bytes.iter().enumerate() {
+        if *byte != 0 {
+            return !(position == bytes.len() - 1 && *byte == 0x80);
+        }
+    }
+    false
+}
+
+/// Removes the element on the top of the stack and returns it.
+fn pop(stack: &mut Stack) -> Result<Vec<u8>, &'static str> {
+    stack
+        .pop()
+        .ok_or("The script executes an operation without enough elements in the stack")
+}
+
+/// Removes the element on the top of the stack and returns it as a number.
+fn pop_number(stack: &mut Stack) -> Result<i64, &'static str> {
+    decode_number(&pop(stack)?, MAX_NUMBER_SIZE)
+}
+
+/// Checks that the stack has at least the received amount of elements.
+fn require(stack: &Stack, amount: usize) -> Result<(), &'static str> {
+    if stack.len() < amount {
+        return Err("The script executes an operation without enough elements in the stack");
+    }
+    Ok(())
+}
+
+/// Executes the opcodes that only operate over the stack and the altstack (constants, flow control
+/// without branches, stack, splice, bitwise, arithmetic and hash operations).
+fn execute_opcode(opcode: u8, stack: &mut Stack, altstack: &mut Stack) -> Result<(), &'static str> {
+    match opcode {
+        ScriptOpcodes::OP_1NEGATE => stack.push(encode_number(-1)),
+        ScriptOpcodes::OP_1..=ScriptOpcodes::OP_16 => {
+            stack.push(encode_number((opcode - ScriptOpcodes::OP_1 + 1) as i64))
+        }
+        // NOP2 and NOP3 are CHECKLOCKTIMEVERIFY and CHECKSEQUENCEVERIFY, which are executed with the checker
+        ScriptOpcodes::OP_NOP
+        | ScriptOpcodes::OP_NOP1
+        | ScriptOpcodes::OP_NOP4..=ScriptOpcodes::OP_NOP10 => {}
+        ScriptOpcodes::OP_VERIFY => {
+            if !cast_to_bool(&pop(stack)?) {
+                return Err("The script failed an OP_VERIFY operation");
+            }
+        }
+        ScriptOpcodes::OP_RETURN => return Err("The script executed an OP_RETURN"),
+        ScriptOpcodes::OP_TOALTSTACK => {
+            let top = pop(stack)?;
+            altstack.push(top);
+        }
+        ScriptOpcodes::OP_FROMALTSTACK => match altstack.pop() {
+            Some(top) => stack.push(top),
+            None => return Err("The script executes an OP_FROMALTSTACK with the altstack empty"),
+        },
+        ScriptOpcodes::OP_2DROP..=ScriptOpcodes::OP_TUCK => execute_stack_opcode(opcode, stack)?,
+        ScriptOpcodes::OP_SIZE => {
+            require(stack, 1)?;
+            let size = stack[stack.len() - 1].len() as i64;
+            stack.push(encode_number(size));
+        }
+        ScriptOpcodes::OP_EQUAL | ScriptOpcodes::OP_EQUALVERIFY => {
+            let second = pop(stack)?;
+            let first = pop(stack)?;
+            let equal = first == second;
+            if opcode == ScriptOpcodes::OP_EQUALVERIFY {
+                if !equal {
+                    return Err("The script failed an OP_EQUALVERIFY operation");
+                }
+            } else {
+                stack.push(bool_to_bytes(equal));
+            }
+        }
+        ScriptOpcodes::OP_1ADD..=ScriptOpcodes::OP_0NOTEQUAL => {
+            let number = pop_number(stack)?;
+            let result = match opcode {
+                ScriptOpcodes::OP_1ADD => number + 1,
+                ScriptOpcodes::OP_1SUB => number - 1,
+                ScriptOpcodes::OP_NEGATE => -number,
+                ScriptOpcodes::OP_ABS => number.abs(),
+                ScriptOpcodes::OP_NOT => (number == 0) as i64,
+                _ => (number != 0) as i64,
+            };
+            stack.push(encode_number(result));
+        }
+        ScriptOpcodes::OP_ADD..=ScriptOpcodes::OP_MAX => {
+            require(stack, 2)?;
+            let second = pop_number(stack)?;
+            let first = pop_number(stack)?;
+            let result = match opcode {
+                ScriptOpcodes::OP_ADD => first + second,
+                ScriptOpcodes::OP_SUB => first - second,
+                ScriptOpcodes::OP_BOOLAND => (first != 0 && second != 0) as i64,
+                ScriptOpcodes::OP_BOOLOR => (first != 0 || second != 0) as i64,
+                ScriptOpcodes::OP_NUMEQUAL | ScriptOpcodes::OP_NUMEQUALVERIFY => {
+                    (first == second) as i64
+                }
+                ScriptOpcodes::OP_NUMNOTEQUAL => (first != second) as i64,
+                ScriptOpcodes::OP_LESSTHAN => (first < second) as i64,
+                ScriptOpcodes::OP_GREATERTHAN => (first > second) as i64,
+                ScriptOpcodes::OP_LESSTHANOREQUAL => (first <= second) as i64,
+                ScriptOpcodes::OP_GREATERTHANOREQUAL => (first >= second) as i64,
+                ScriptOpcodes::OP_MIN => first.min(second),
+                _ => first.max(second),
+            };
+            if opcode == ScriptOpcodes::OP_NUMEQUALVERIFY {
+                if result == 0 {
+                    return Err("The script failed an OP_NUMEQUALVERIFY operation");
+                }
+            } else {
+                stack.push(encode_number(result));
+            }
+        }
+        ScriptOpcodes::OP_WITHIN => {
+            require(stack, 3)?;
+            let max = pop_number(stack)?;
+            let min = pop_number(stack)?;
+            let number = pop_number(stack)?;
+            stack.push(bool_to_bytes(min <= number && number < max));
+        }
+        ScriptOpcodes::OP_RIPEMD160..=ScriptOpcodes::OP_HASH256 => {
+            let data = pop(stack)?;
+            let hash: Vec<u8> = match opcode {
+                ScriptOpcodes::OP_RIPEMD160 => {
+                    ripemd160::Hash::hash(&data).to_byte_array().to_vec()
+                }
+                ScriptOpcodes::OP_SHA1 => sha1::Hash::hash(&data).to_byte_array().to_vec(),
+                ScriptOpcodes::OP_SHA256 => sha256::Hash::hash(&data).to_byte_array().to_vec(),
+                ScriptOpcodes::OP_HASH160 => hash160::Hash::hash(&data).to_byte_array().to_vec(),
+                _ => sha256d::Hash::hash(&data).to_byte_array().to_vec(),
+            };
+            stack.push(hash);
+        }
+        _ => return Err("The script executes an invalid opcode"),
+    }
+    Ok(())
+}
+
+/// Executes the opcodes that reorder, copy or remove elements of the stack.
+fn execute_stack_opcode(opcode: u8, stack: &mut Stack) -> Result<(), &'static str> {
+    let length = stack.len();
+    match opcode {
+        ScriptOpcodes::OP_2DROP => {
+            require(stack, 2)?;
+            stack.truncate(length - 2);
+        }
+        ScriptOpcodes::OP_2DUP => {
+            require(stack, 2)?;
+            stack.extend_from_within(length - 2..);
+        }
+        ScriptOpcodes::OP_3DUP => {
+            require(stack, 3)?;
+            stack.extend_from_within(length - 3..);
+        }
+        ScriptOpcodes::OP_2OVER => {
+            require(stack, 4)?;
+            stack.extend_from_within(length - 4..length - 2);
+        }
+        ScriptOpcodes::OP_2ROT => {
+            require(stack, 6)?;
+            let first = stack.remove(length - 6);
+            let second = stack.remove(length - 6);
+            stack.push(first);
+            stack.push(second);
+        }
+        ScriptOpcodes::OP_2SWAP => {
+            require(stack, 4)?;
+            stack.swap(length - 4, length - 2);
+            stack.swap(length - 3, length - 1);
+        }
+        ScriptOpcodes::OP_IFDUP => {
+            require(stack, 1)?;
+            if cast_to_bool(&stack[length - 1]) {
+                stack.push(stack[length - 1].clone());
+            }
+        }
+        ScriptOpcodes::OP_DEPTH => stack.push(encode_number(length as i64)),
+        ScriptOpcodes::OP_DROP => {
+            pop(stack)?;
+        }
+        ScriptOpcodes::OP_DUP => {
+            require(stack, 1)?;
+            stack.push(stack[length - 1].clone());
+        }
+        ScriptOpcodes::OP_NIP => {
+            require(stack, 2)?;
+            stack.remove(length - 2);
+        }
+        ScriptOpcodes::OP_OVER => {
+            require(stack, 2)?;
+            stack.push(stack[length - 2].clone());
+        }
+        ScriptOpcodes::OP_PICK | ScriptOpcodes::OP_ROLL => {
+            require(stack, 2)?;
+            let position = pop_number(stack)?;
+            if position < 0 || position as usize >= stack.len() {
+                return Err(
+                    "The script executes an operation without enough elements in the stack",
+                );
+            }
+            let index = stack.len() - 1 - position as usize;
+            let element = if opcode == ScriptOpcodes::OP_ROLL {
+                stack.remove(index)
+            } else {
+                stack[index].clone()
+            };
+            stack.push(element);
+        }
+        ScriptOpcodes::OP_ROT => {
+            require(stack, 3)?;
+            stack.swap(length - 3, length - 2);
+            stack.swap(length - 2, length - 1);
+        }
+        ScriptOpcodes::OP_SWAP => {
+            require(stack, 2)?;
+            stack.swap(length - 2, length - 1);
+        }
+        ScriptOpcodes::OP_TUCK => {
+            require(stack, 2)?;
+            stack.insert(length - 2, stack[length - 1].clone());
+        }
+        _ => return Err("The script executes an invalid opcode"),
+    }
+    Ok(())
+}
+
+/// Verifies the witness of an input against the witness program of the output it spends.
+/// The witness of a P2WPKH program contains the signature and the public key, which are evaluated with
+/// the P2PKH script of the program. The last element of the witness of a P2WSH program is the witness
+/// script, whose sha256 is the program and which is evaluated with the rest of the witness.
+/// The witness of a taproot program (witness version 1 with 32 bytes) that is not nested in P2SH is verified
+/// with verify_taproot_program.
+/// Programs of other witness versions are reserved for future upgrades and are not checked.
+fn verify_witness_program(
+    witness_version: u8,
+    witness_program: &[u8],
+    witness: &[Vec<u8>],
+    checker: &dyn SignatureChecker,
+    is_p2sh: bool,
+) -> Result<(), &'static str> {
+    if witness_version == TAPROOT_WITNESS_VERSION && witness_program.len() == 32 && !is_p2sh {
+        return verify_taproot_program(witness_program, witness, checker);
+    }
+    if witness_version != 0 {
+        return Ok(());
+    }
+    let mut stack: Stack = witness.to_vec();
+    let witness_script = match witness_program.len() {
+        20 => {
+            if stack.len() != 2 {
+                return Err(
+                    "The witness of a P2WPKH output must have a signature and a public key",
+                );
+            }
+            Script::new_witness_v0(generate_p2wpkh_script_code(witness_program))
+        }
+        32 => {
+            let witness_script = pop(&mut stack)
+                .map_err(|_| "The witness of a P2WSH output must have the witness script")?;
+            if sha256::Hash::hash(&witness_script).as_byte_array()[..] != *witness_program {
+                return Err("The witness script does not match the witness program");
+            }
+            Script::new_witness_v0(witness_script)
+        }
+        _ => return Err("The witness program has an invalid length"),
+    };
+    if stack
+        .iter()
+        .any(|element| element.len() > MAX_SCRIPT_ELEMENT_SIZE)
+    {
+        return Err("The script pushes an element bigger than 520 bytes");
+    }
+    witness_script.eval(&mut stack, checker)?;
+    // the evaluation must leave only one element and it must be true
+    if stack.len() != 1 || !cast_to_bool(&stack[0]) {
+        return Err("The witness program evaluated to false");
+    }
+    Ok(())
+}
+
+/// Verifies the witness of an input that spends a taproot output (BIP341).
+/// If the witness has at least two elements and the last one starts with 0x50, it is the annex, which
+/// is removed and committed by the signature. A key path spend has only one element, the Schnorr signature
+/// of the output key (the witness program). Script path spends (a script, its inputs and the control block)
+/// are not verified yet and are accepted without checking them, so that the valid blocks that include them are not rejected.
+fn verify_taproot_program(
+    output_key: &[u8],
+    witness: &[Vec<u8>],
+    checker: &dyn SignatureChecker,
+) -> Result<(), &'static str> {
+    let mut stack: Stack = witness.to_vec();
+    if stack.is_empty() {
+        return Err("The witness of a taproot output can not be empty");
+    }
+    let mut annex: Option<Vec<u8>> = None;
+    if stack.len() >= 2 && stack[stack.len() - 1].first() == Some(&TAPROOT_ANNEX_TAG) {
+        annex = Some(pop(&mut stack)?);
+    }
+    if stack.len() == 1 && !checker.check_schnorr_sig(&stack[0], output_key, annex.as_deref()) {
+        return Err("The taproot signature is not valid");
+    }
+    Ok(())
+}
+
+/// Checks the signature with the algorithm of the received signature version.
+fn check_signature(
+    checker: &dyn SignatureChecker,
+    sig: &[u8],
+    pubkey: &[u8],
+    script_code: &[u8],
+    sig_version: SigVersion,
+) -> bool {
+    match sig_version {
+        SigVersion::Base => checker.check_sig(sig, pubkey, script_code),
+        SigVersion::WitnessV0 => checker.check_witness_v0_sig(sig, pubkey, script_code),
+    }
+}
+
+/// Executes OP_CHECKSIG and OP_CHECKSIGVERIFY.
+/// Pops the public key and the signature and checks the signature over the script code.
+fn check_sig(
+    stack: &mut Stack,
+    script_code: &[u8],
+    checker: &dyn SignatureChecker,
+    opcode: u8,
+    sig_version: SigVersion,
+) -> Result<(), &'static str> {
+    require(stack, 2)?;
+    let pubkey = pop(stack)?;
+    let sig = pop(stack)?;
+    check_signature_encoding(&sig)?;
+    // the legacy signature can not sign itself, so it is removed from the script code
+    let script_code = match sig_version {
+        SigVersion::Base => find_and_delete(script_code, &push_data_bytes(&sig)),
+        SigVersion::WitnessV0 => script_code.to_vec(),
+    };
+    let success = check_signature(checker, &sig, &pubkey, &script_code, sig_version);
+    if opcode == ScriptOpcodes::OP_CHECKSIGVERIFY {
+        if !success {
+            return Err("The script failed an OP_CHECKSIGVERIFY operation");
+        }
+    } else {
+        stack.push(bool_to_bytes(success));
+    }
+    Ok(())
+}
+
+/// Executes OP_CHECKLOCKTIMEVERIFY and OP_CHECKSEQUENCEVERIFY.
+/// The lock time on the top of the stack is not removed, so the script usually drops it after the operation.
+/// A relative lock time with the disable flag set does not check anything.
+fn check_lock_time(
+    stack: &mut Stack,
+    checker: &dyn SignatureChecker,
+    opcode: u8,
+) -> Result<(), &'static str> {
+    require(stack, 1)?;
+    let lock_time = decode_number(&stack[stack.len() - 1], MAX_LOCK_TIME_SIZE)?;
+    if lock_time < 0 {
+        return Err("The script has a negative lock time");
+    }
+    if opcode == ScriptOpcodes::OP_CHECKLOCKTIMEVERIFY {
+        if !checker.check_lock_time(lock_time) {
+            return Err("The script failed an OP_CHECKLOCKTIMEVERIFY operation");
+        }
+    } else if lock_time & SEQUENCE_LOCKTIME_DISABLE_FLAG as i64 == 0
+        && !checker.check_sequence(lock_time)
+    {
+        return Err("The script failed an OP_CHECKSEQUENCEVERIFY operation");
+    }
+    Ok(())
+}
+
+/// Executes OP_CHECKMULTISIG and OP_CHECKMULTISIGVERIFY.
+/// The stack contains: <dummy> <sig 1> ... <sig m> <m> <pubkey 1> ... <pubkey n> <n>.
+/// The signatures must be in the same order as the public keys they correspond to.
+fn check_multisig(
+    stack: &mut Stack,
+    script_code: &[u8],
+    checker: &dyn SignatureChecker,
+    opcode: u8,
+    op_count: &mut usize,
+    sig_version: SigVersion,
+) -> Result<(), &'static str> {
+    require(stack, 1)?;
+    let keys_count = decode_number(&stack[stack.len() - 1], MAX_NUMBER_SIZE)?;
+    if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&keys_count) {
+        return Err("The script has an invalid amount of public keys in a multisig");
+    }
+    let keys_count = keys_count as usize;
+    *op_count += keys_count;
+    if *op_count > MAX_OPS_PER_SCRIPT {
+        return Err("The script exceeds the maximum amount of operations");
+    }
+    // positions counted from the top of the stack
+    let first_key_position = 2;
+    let sigs_count_position = first_key_position + keys_count;
+    require(stack, sigs_count_position)?;
+    let sigs_count = decode_number(&stack[stack.len() - sigs_count_position], MAX_NUMBER_SIZE)?;
+    if sigs_count < 0 || sigs_count as usize > keys_count {
+        return Err("The script has an invalid amount of signatures in a multisig");
+    }
+    let sigs_count = sigs_count as usize;
+    let first_sig_position = sigs_count_position + 1;
+    // the extra element is the dummy consumed by the operation
+    let elements_to_consume = first_sig_position + sigs_count;
+    require(stack, elements_to_consume)?;
+
+    let mut script_code = script_code.to_vec();
+    if sig_version == SigVersion::Base {
+        for position in first_sig_position..first_sig_position + sigs_count {
+            let sig = &stack[stack.len() - position];
+            script_code = find_and_delete(&script_code, &push_data_bytes(sig));
+        }
+    }
+    let mut success = true;
+    let mut sig_position = first_sig_position;
+    let mut key_position = first_key_position;
+    let mut remaining_sigs = sigs_count;
+    let mut remaining_keys = keys_count;
+    while success && remaining_sigs > 0 {
+        let sig = &stack[stack.len() - sig_position];
+        let pubkey = &stack[stack.len() - key_position];
+        check_signature_encoding(sig)?;
+        if check_signature(checker, sig, pubkey, &script_code, sig_version) {
+            sig_position += 1;
+            remaining_sigs -= 1;
+        }
+        key_position += 1;
+        remaining_keys -= 1;
+        // there are more signatures left than keys to check them
+        if remaining_sigs > remaining_keys {
+            success = false;
+        }
+    }
+    // the dummy element must be empty (NULLDUMMY, BIP147)
+    if !stack[stack.len() - elements_to_consume].is_empty() {
+        return Err("The dummy element of a multisig is not empty");
+    }
+    stack.truncate(stack.len() - elements_to_consume);
+    if opcode == ScriptOpcodes::OP_CHECKMULTISIGVERIFY {
+        if !success {
+            return Err("The script failed an OP_CHECKMULTISIGVERIFY operation");
+        }
+    } else {
+        stack.push(bool_to_bytes(success));
+    }
+    Ok(())
+}
+
+/// Checks that the signature is strictly DER encoded (BIP66). The empty signature is allowed, as it is
+/// a compact way to provide a signature that is not valid.
+fn check_signature_encoding(sig: &[u8]) -> Result<(), &'static str> {
+    if !sig.is_empty() && !is_valid_signature_encoding(sig) {
+        return Err("The script has a signature that is not strictly DER encoded");
+    }
+    Ok(())
+}
+
+/// Returns true if the signature with the sighash type byte at the end has the format
+/// 0x30 [total-length] 0x02 [R-length] [R] 0x02 [S-length] [S] [sighash], where R and S are
+/// positive integers encoded with the minimum amount of bytes, as Bitcoin Core checks it.
+fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    if !(MIN_SIGNATURE_SIZE..=MAX_SIGNATURE_SIZE).contains(&sig.len()) {
+        return false;
+    }
+    if sig[0] != 0x30 || sig[1] as usize != sig.len() - 3 {
+        return false;
+    }
+    let r_len = sig[3] as usize;
+    if 5 + r_len >= sig.len() {
+        return false;
+    }
+    let s_len = sig[5 + r_len] as usize;
+    if r_len + s_len + 7 != sig.len() {
+        return false;
+    }
+    // R must be a positive integer without unnecessary leading zeros
+    if sig[2] != 0x02 || r_len == 0 || sig[4] & 0x80 != 0 {
+        return false;
+    }
+    if r_len > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 {
+        return false;
+    }
+    // and so must be S
+    if sig[r_len + 4] != 0x02 || s_len == 0 || sig[r_len + 6] & 0x80 != 0 {
+        return false;
+    }
+    if s_len > 1 && sig[r_len + 6] == 0x00 && sig[r_len + 7] & 0x80 == 0 {
+        return false;
+    }
+    true
+}
+
+/// Returns the element of the stack that represents the received boolean.
+fn bool_to_bytes(value: bool) -> Vec<u8> {
+    if value {
+        vec![1]
+    } else {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        cast_to_bool, decode_number, encode_number, find_and_delete, is_valid_signature_encoding,
+        verify_taproot_program, Script,
+    };
+    use crate::transactions::script::signature_checker::SignatureChecker;
+
+    /// Checker used to evaluate scripts without signatures
+    struct NoSignatureChecker;
+
+    impl SignatureChecker for NoSignatureChecker {
+        fn check_sig(&self, _sig: &[u8], _pubkey: &[u8], _script_code: &[u8]) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_numbers_are_encoded_and_decoded_correctly() -> Result<(), &'static str> {
+        for number in [
+            0, 1, -1, 127, 128, -128, 255, 256, 32767, -32768, 2147483647,
+        ] {
+            assert_eq!(decode_number(&encode_number(number), 4)?, number);
+        }
+        assert_eq!(encode_number(128), vec![0x80, 0x00]);
+        assert_eq!(encode_number(-1), vec![0x81]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decoding_a_number_bigger_than_4_bytes_returns_error() {
+        assert!(decode_number(&[1, 2, 3, 4, 5], 4).is_err());
+    }
+
+    #[test]
+    fn test_negative_zero_is_false() {
+        assert!(!cast_to_bool(&[0x00, 0x80]));
+        assert!(cast_to_bool(&[0x80, 0x00]));
+        assert!(!cast_to_bool(&[]));
+    }
+
+    #[test]
+    fn test_find_and_delete_removes_only_complete_instructions() {
+        // OP_1 <0x51> OP_1 : only the push of 0x51 is removed
+        let script = vec![0x51, 0x01, 0x51, 0x51];
+        assert_eq!(find_and_delete(&script, &[0x01, 0x51]), vec![0x51, 0x51]);
+        // the pattern inside the data of a push is not removed
+        let script = vec![0x02, 0x01, 0x51];
+        assert_eq!(find_and_delete(&script, &[0x01, 0x51]), script);
+    }
+
+    #[test]
+    fn test_push_bigger_than_520_bytes_is_not_valid() {
+        let mut bytes = vec![0x4d, 0x09, 0x02];
+        bytes.extend_from_slice(&[1; 521]);
+        let script_sig = Script::new(bytes);
+        let result = Script::verify(&script_sig, &Script::new(vec![0x51]), &NoSignatureChecker);
+        assert_eq!(
+            result,
+            Err("The script pushes an element bigger than 520 bytes")
+        );
+    }
+
+    #[test]
+    fn test_script_with_more_than_201_operations_is_not_valid() {
+        let script_pubkey = Script::new(vec![0x61; 202]);
+        let result = Script::verify(
+            &Script::new(vec![0x51]),
+            &script_pubkey,
+            &NoSignatureChecker,
+        );
+        assert_eq!(
+            result,
+            Err("The script exceeds the maximum amount of operations")
+        );
+        let script_pubkey = Script::new(vec![0x61; 201]);
+        assert!(Script::verify(
+            &Script::new(vec![0x51]),
+            &script_pubkey,
+            &NoSignatureChecker
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_stack_with_more_than_1000_elements_is_not_valid() {
+        let script_sig = Script::new(vec![0x51; 999]);
+        let result = Script::verify(&script_sig, &Script::new(vec![0x51]), &NoSignatureChecker);
+        assert!(result.is_ok());
+        let result = Script::verify(
+            &script_sig,
+            &Script::new(vec![0x51; 2]),
+            &NoSignatureChecker,
+        );
+        assert_eq!(result, Err("The stack exceeds the maximum size"));
+    }
+
+    #[test]
+    fn test_script_bigger_than_10000_bytes_is_not_valid() {
+        let mut bytes = vec![0x51];
+        for _ in 0..20 {
+            bytes.extend_from_slice(&[0x4d, 0x08, 0x02]);
+            bytes.extend_from_slice(&[0; 520]);
+            bytes.push(0x75);
+        }
+        let result = Script::verify(
+            &Script::new(vec![]),
+            &Script::new(bytes),
+            &NoSignatureChecker,
+        );
+        assert_eq!(result, Err("The script exceeds the maximum size"));
+    }
+
+    #[test]
+    fn test_checklocktimeverify_with_negative_lock_time_is_not_valid() {
+        // -1 CHECKLOCKTIMEVERIFY
+        let result = Script::verify(
+            &Script::new(vec![0x4f]),
+            &Script::new(vec![0xb1]),
+            &NoSignatureChecker,
+        );
+        assert_eq!(result, Err("The script has a negative lock time"));
+    }
+
+    #[test]
+    fn test_checksequenceverify_with_the_disable_flag_does_not_check_the_sequence() {
+        // 2^31 CHECKSEQUENCEVERIFY
+        let script_sig = Script::new(vec![0x05, 0x00, 0x00, 0x00, 0x80, 0x00]);
+        let result = Script::verify(&script_sig, &Script::new(vec![0xb2]), &NoSignatureChecker);
+        assert!(result.is_ok());
+        // 1 CHECKSEQUENCEVERIFY
+        let result = Script::verify(
+            &Script::new(vec![0x51]),
+            &Script::new(vec![0xb2]),
+            &NoSignatureChecker,
+        );
+        assert_eq!(
+            result,
+            Err("The script failed an OP_CHECKSEQUENCEVERIFY operation")
+        );
+    }
+
+    #[test]
+    fn test_is_push_only() {
+        assert!(Script::new(vec![0x00, 0x01, 0x02, 0x51]).is_push_only());
+        assert!(!Script::new(vec![0x51, 0x76]).is_push_only());
+    }
+
+    #[test]
+    fn test_taproot_script_path_spend_is_not_checked() {
+        let output_key = [2; 32];
+        // a script, its input and a control block, with and without annex
+        let witness = vec![vec![0x51], vec![0x51], vec![0xc0; 33]];
+        assert_eq!(
+            verify_taproot_program(&output_key, &witness, &NoSignatureChecker),
+            Ok(())
+        );
+        let witness = vec![vec![0x51], vec![0xc0; 33], vec![0x50, 0x01]];
+        assert!(verify_taproot_program(&output_key, &witness, &NoSignatureChecker).is_ok());
+        // a key path spend with an invalid signature
+        assert_eq!(
+            verify_taproot_program(&output_key, &[vec![0; 64]], &NoSignatureChecker),
+            Err("The taproot signature is not valid")
+        );
+    }
+
+    #[test]
+    fn test_only_strictly_der_encoded_signatures_are_valid() {
+        // 0x30 [length] 0x02 [1] [R] 0x02 [1] [S] [sighash]
+        assert!(is_valid_signature_encoding(&[
+            0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01
+        ]));
+        // wrong total length
+        assert!(!is_valid_signature_encoding(&[
+            0x30, 0x07, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01
+        ]));
+        // negative R
+        assert!(!is_valid_signature_encoding(&[
+            0x30, 0x06, 0x02, 0x01, 0x81, 0x02, 0x01, 0x01, 0x01
+        ]));
+        // R with an unnecessary leading zero
+        assert!(!is_valid_signature_encoding(&[
+            0x30, 0x07, 0x02, 0x02, 0x00, 0x01, 0x02, 0x01, 0x01, 0x01
+        ]));
+        // zero-length S
+        assert!(!is_valid_signature_encoding(&[
+            0x30, 0x07, 0x02, 0x03, 0x01, 0x01, 0x01, 0x02, 0x00, 0x01
+        ]));
+    }
+
+    #[test]
+    fn test_multisig_with_a_dummy_element_that_is_not_empty_is_not_valid() {
+        let script_pubkey = Script::new(vec![0x00, 0x00, 0xae]);
+        assert!(Script::verify(
+            &Script::new(vec![0x00]),
+            &script_pubkey,
+            &NoSignatureChecker
+        )
+        .is_ok());
+        assert_eq!(
+            Script::verify(
+                &Script::new(vec![0x51]),
+                &script_pubkey,
+                &NoSignatureChecker
+            ),
+            Err("The dummy element of a multisig is not empty")
+        );
+    }
+}
@@ -1,4 +1,11 @@
+pub mod interpreter;
+pub mod multisig_script;
 pub mod p2pkh_script;
+pub mod p2sh_script;
+pub mod p2tr_script;
+pub mod p2wpkh_script;
+pub mod p2wsh_script;
 pub mod pubkey;
 pub mod script_opcodes;
 pub mod sig_script;
+pub mod signature_checker;
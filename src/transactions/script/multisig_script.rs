@@ -0,0 +1,143 @@
+use super::{interpreter::push_data_bytes, script_opcodes::ScriptOpcodes};
+
+const COMPRESSED_PUBKEY_LENGTH: usize = 33;
+const MAX_MULTISIG_PUBKEYS: usize = 16;
+//      scriptSig: OP_0 <sig 1> ... <sig m>   (OP_0 is the dummy element consumed by OP_CHECKMULTISIG)
+//
+// script: OP_m <pubkey 1> ... <pubkey n> OP_n OP_CHECKMULTISIG
+// HEXA:   0x50+m <0x21> <pubkey 1> ... <0x21> <pubkey n> 0x50+n 0xAE
+// bytes length: 1 + 34 * n + 1 + 1
+// <pubkey>: 33 bytes. Compressed public key SEC.
+// The signatures must be in the same order as the public keys they belong to. The script can be
+// used directly as pubkey script (bare multisig), as redeem script (P2SH) or as witness script (P2WSH).
+
+/// Generates the multisig script that requires required_signatures signatures of the received
+/// compressed public keys (m-of-n).
+/// Returns an error if there are more than 16 keys, if a key is not compressed or if the amount of
+/// required signatures is not between 1 and the amount of keys.
+pub fn generate_multisig_script(
+    required_signatures: usize,
+    pubkeys: &[[u8; 33]],
+) -> Result<Vec<u8>, &'static str> {
+    if pubkeys.is_empty() || pubkeys.len() > MAX_MULTISIG_PUBKEYS {
+        return Err("The multisig must have between 1 and 16 public keys");
+    }
+    if required_signatures == 0 || required_signatures > pubkeys.len() {
+        return Err("The required signatures must be between 1 and the amount of public keys");
+    }
+    if pubkeys
+        .iter()
+        .any(|pubkey| pubkey[0] != 0x02 && pubkey[0] != 0x03)
+    {
+        return Err("The public keys of the multisig must be compressed");
+    }
+    let mut script: Vec<u8> = vec![small_number_opcode(required_signatures)];
+    for pubkey in pubkeys {
+        script.extend_from_slice(&push_data_bytes(pubkey));
+    }
+    script.push(small_number_opcode(pubkeys.len()));
+    script.push(ScriptOpcodes::OP_CHECKMULTISIG);
+    Ok(script)
+}
+
+/// Receives a script and, if it has the multisig format, returns the amount of required signatures
+/// and the compressed public keys in the order in which they appear. Otherwise returns None.
+pub fn parse_multisig_script(script: &[u8]) -> Option<(usize, Vec<[u8; 33]>)> {
+    let required_signatures = small_number(*script.first()?)?;
+    let keys_count = small_number(*script.get(script.len().checked_sub(2)?)?)?;
+    if script.last() != Some(&ScriptOpcodes::OP_CHECKMULTISIG)
+        || script.len() != 3 + (COMPRESSED_PUBKEY_LENGTH + 1) * keys_count
+        || required_signatures > keys_count
+    {
+        return None;
+    }
+    let mut pubkeys: Vec<[u8; 33]> = Vec::new();
+    for chunk in script[1..script.len() - 2].chunks(COMPRESSED_PUBKEY_LENGTH + 1) {
+        if chunk[0] as usize != COMPRESSED_PUBKEY_LENGTH {
+            return None;
+        }
+        pubkeys.push(chunk[1..].try_into().ok()?);
+    }
+    Some((required_signatures, pubkeys))
+}
+
+/// Returns true if the received script has the multisig format.
+pub fn is_multisig(script: &[u8]) -> bool {
+    parse_multisig_script(script).is_some()
+}
+
+/// Returns the opcode that pushes the received number (between 1 and 16).
+fn small_number_opcode(number: usize) -> u8 {
+    ScriptOpcodes::OP_1 + (number as u8) - 1
+}
+
+/// Returns the number pushed by the received opcode if it is between OP_1 and OP_16.
+fn small_number(opcode: u8) -> Option<usize> {
+    if (ScriptOpcodes::OP_1..=ScriptOpcodes::OP_16).contains(&opcode) {
+        return Some((opcode - ScriptOpcodes::OP_1) as usize + 1);
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::{generate_multisig_script, is_multisig, parse_multisig_script};
+    use crate::transactions::script::script_opcodes::ScriptOpcodes;
+
+    fn pubkeys(amount: u8) -> Vec<[u8; 33]> {
+        (0..amount)
+            .map(|index| {
+                let mut pubkey = [index; 33];
+                pubkey[0] = 0x02;
+                pubkey
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_multisig_script_has_the_required_signatures_the_keys_and_checkmultisig(
+    ) -> Result<(), &'static str> {
+        let keys = pubkeys(3);
+        let script = generate_multisig_script(2, &keys)?;
+        assert_eq!(script.len(), 105);
+        assert_eq!(script[0], ScriptOpcodes::OP_2);
+        assert_eq!(script[1], 33);
+        assert_eq!(script[2..35], keys[0]);
+        assert_eq!(script[103], ScriptOpcodes::OP_3);
+        assert_eq!(script[104], ScriptOpcodes::OP_CHECKMULTISIG);
+        assert_eq!(parse_multisig_script(&script), Some((2, keys)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_multisig_script_with_invalid_amount_of_signatures_or_keys_returns_error() {
+        assert!(generate_multisig_script(0, &pubkeys(2)).is_err());
+        assert!(generate_multisig_script(3, &pubkeys(2)).is_err());
+        assert!(generate_multisig_script(1, &[]).is_err());
+        assert!(generate_multisig_script(1, &pubkeys(17)).is_err());
+        assert!(generate_multisig_script(16, &pubkeys(16)).is_ok());
+        let mut uncompressed = pubkeys(2);
+        uncompressed[1][0] = 0x04;
+        assert_eq!(
+            generate_multisig_script(1, &uncompressed),
+            Err("The public keys of the multisig must be compressed")
+        );
+    }
+
+    #[test]
+    fn test_script_that_is_not_multisig_is_not_parsed() -> Result<(), &'static str> {
+        let script = generate_multisig_script(1, &pubkeys(2))?;
+        assert!(is_multisig(&script));
+        // the amount of keys does not match
+        let mut other_count = script.clone();
+        other_count[script.len() - 2] = ScriptOpcodes::OP_3;
+        assert!(!is_multisig(&other_count));
+        // it does not end with OP_CHECKMULTISIG
+        let mut other_opcode = script.clone();
+        other_opcode[script.len() - 1] = ScriptOpcodes::OP_CHECKSIG;
+        assert!(!is_multisig(&other_opcode));
+        assert!(!is_multisig(&script[1..]));
+        assert!(!is_multisig(&[]));
+        Ok(())
+    }
+}
@@ -1,8 +1,19 @@
-use super::script_opcodes::ScriptOpcodes;
-use crate::address_decoder::{self, get_pubkey_hash_from_address};
+use super::{
+    interpreter::Script, p2sh_script::generate_p2sh_pubkey_script,
+    p2wpkh_script::generate_witness_pubkey_script, script_opcodes::ScriptOpcodes,
+    signature_checker::TransactionSignatureChecker,
+};
+use crate::{
+    address_decoder::{
+        decode_segwit_address, get_pubkey_hash_from_address, is_p2sh_address, is_segwit_address,
+    },
+    chain_params::ChainParams,
+    transactions::transaction::Transaction,
+};
 use std::error::Error;
 
 const BYTES_TO_PUSH: u8 = 20;
+const P2PKH_SCRIPT_LENGTH: usize = 25;
 //      <Sig> <PubKey> OP_DUP OP_HASH160 <PubkeyHash> OP_EQUALVERIFY OP_CHECKSIG
 //
 // scriptPubKey: OP_DUP OP_HASH160 <bytes_to_push> <pubKeyHash> OP_EQUALVERIFY OP_CHECKSIG
@@ -11,9 +22,24 @@ const BYTES_TO_PUSH: u8 = 20;
 // Si una Tx es P2PKH el largo de su pk_script debe ser == 25
 // <pubKeyHash>: 20 bytes. The result of hash160 (sha256 + ripemd160 hash) to the compressed public key SEC.
 
-/// Generates the pubkey script from the compressed address.
-pub fn generate_pubkey_script(address: &str) -> Result<Vec<u8>, Box<dyn Error>> {
-    let pubkey_hash = get_pubkey_hash_from_address(address)?;
+/// Generates the pubkey script from the compressed address of the network of the chain params.
+/// If it is a P2SH address, generates the P2SH pubkey script with the hash of the redeem script.
+/// If it is a segwit address, generates the pubkey script with its witness program.
+pub fn generate_pubkey_script(
+    address: &str,
+    params: &ChainParams,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    if is_segwit_address(address, params) {
+        let (witness_version, witness_program) = decode_segwit_address(address, params)?;
+        return Ok(generate_witness_pubkey_script(
+            witness_version,
+            &witness_program,
+        ));
+    }
+    let pubkey_hash = get_pubkey_hash_from_address(address, params)?;
+    if is_p2sh_address(address, params)? {
+        return Ok(generate_p2sh_pubkey_script(&pubkey_hash));
+    }
     let mut pk_script: Vec<u8> = Vec::new();
     pk_script.push(ScriptOpcodes::OP_DUP);
     pk_script.push(ScriptOpcodes::OP_HASH160);
@@ -24,46 +50,39 @@ pub fn generate_pubkey_script(address: &str) -> Result<Vec<u8>, Box<dyn Error>>
     Ok(pk_script)
 }
 
-/// Receives the p2pkh_script and the sig_script.
-/// Validates and returns true or false.
-pub fn validate(p2pkh_script: &[u8], sig_script: &[u8]) -> Result<bool, Box<dyn Error>> {
-    // scriptSig:   <length sig>     <sig>   <length pubKey>   <pubKey>
-    // <pubkey> it is the compressed SEC public key (33 bytes) of the receiver of the tx
-    // bytes length: 1 + 71 + 1 + 33 = 106
-    // the <sig> length depends on the DER key, it can vary between 71 or 72
-    let length_sig = sig_script[0];
-    let mut sig_script_pubkey: [u8; 33] = [0; 33];
-    sig_script_pubkey
-        .copy_from_slice(&sig_script[length_sig as usize + 2..length_sig as usize + 35]);
-
-    // 1) Check that the first command is OP_DUP (0x76)
-    if p2pkh_script[0..1] != [ScriptOpcodes::OP_DUP] {
-        return Ok(false);
-    }
-
-    // 2) Check that the second command is OP_HASH_160 (0xA9)
-    if p2pkh_script[1..2] != [ScriptOpcodes::OP_HASH160] {
-        return Ok(false);
-    }
-
-    // 3) Apply hash160 on the pubkey of the sig_script
-    let ripemd160_hash = address_decoder::hash_160(&sig_script_pubkey);
-
-    // 4) Check that the next command is OP_EQUALVERIFY (0x88)
-    if p2pkh_script[23..24] != [ScriptOpcodes::OP_EQUALVERIFY] {
-        return Ok(false);
-    }
-
-    // 5) Check that the hash matches
-    if p2pkh_script[3..23] != ripemd160_hash {
-        return Ok(false);
-    }
+/// Returns true if the received pubkey script has the P2PKH format.
+pub fn is_p2pkh(pk_script: &[u8]) -> bool {
+    pk_script.len() == P2PKH_SCRIPT_LENGTH
+        && pk_script[0] == ScriptOpcodes::OP_DUP
+        && pk_script[1] == ScriptOpcodes::OP_HASH160
+        && pk_script[2] == BYTES_TO_PUSH
+        && pk_script[23] == ScriptOpcodes::OP_EQUALVERIFY
+        && pk_script[24] == ScriptOpcodes::OP_CHECKSIG
+}
 
-    // 6) Check that the next command is OP_CHECKSIG (0xAC)
-    if p2pkh_script[24..25] != [ScriptOpcodes::OP_CHECKSIG] {
+/// Receives the p2pkh_script of the output being spent, the sig_script of the input that spends it,
+/// the transaction and the index of that input. Checks the format of the script and executes both scripts,
+/// verifying the signature against the signature hash of the input.
+/// Returns true or false.
+pub fn validate(
+    p2pkh_script: &[u8],
+    sig_script: &[u8],
+    transaction: &Transaction,
+    tx_in_index: usize,
+) -> Result<bool, Box<dyn Error>> {
+    // scriptSig:   <length sig>     <sig>   <length pubKey>   <pubKey>
+    // <pubkey> it is the SEC public key (33 bytes compressed, 65 uncompressed) of the receiver of the tx
+    // the <sig> length depends on the DER key, it can vary between 71 or 72 (sighash byte included)
+    if !is_p2pkh(p2pkh_script) {
         return Ok(false);
     }
-    Ok(true)
+    let checker = TransactionSignatureChecker::new(transaction, tx_in_index);
+    let result = Script::verify(
+        &Script::new(sig_script.to_vec()),
+        &Script::new(p2pkh_script.to_vec()),
+        &checker,
+    );
+    Ok(result.is_ok())
 }
 
 #[cfg(test)]
@@ -72,16 +91,47 @@ mod test {
 
     use crate::{
         account::Account,
-        transactions::script::{
-            p2pkh_script::{self, generate_pubkey_script},
-            sig_script::SigScript,
+        chain_params::TESTNET_PARAMS,
+        compact_size_uint::CompactSizeUint,
+        transactions::{
+            script::{
+                p2pkh_script::{self, generate_pubkey_script},
+                sig_script::SigScript,
+            },
+            transaction::Transaction,
+            tx_out::TxOut,
         },
+        utxo_tuple::UtxoTuple,
     };
 
+    /// Creates a transaction that spends an output of the account and signs it
+    fn create_signed_transaction(
+        account: &Account,
+    ) -> Result<(Transaction, Vec<u8>), Box<dyn Error>> {
+        let p2pkh_script = generate_pubkey_script(&account.address, &TESTNET_PARAMS)?;
+        let txout = TxOut::new(
+            10000,
+            CompactSizeUint::new(p2pkh_script.len() as u128),
+            p2pkh_script.clone(),
+        );
+        let utxos = vec![UtxoTuple::new([7; 32], vec![(txout, 0)], 0, false)];
+        let mut transaction = Transaction::generate_unsigned_transaction(
+            "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF",
+            &account.address,
+            5000,
+            1000,
+            &utxos,
+            0,
+            &TESTNET_PARAMS,
+        )?;
+        transaction.sign(account, &utxos)?;
+        Ok((transaction, p2pkh_script))
+    }
+
     #[test]
     fn test_pk_script_is_generated_with_correct_length() -> Result<(), Box<dyn Error>> {
         let address = "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
-        let pk_script = generate_pubkey_script(address)?;
+        let pk_script = generate_pubkey_script(address, &TESTNET_PARAMS)?;
 
         assert_eq!(pk_script.len(), 25);
         Ok(())
@@ -90,7 +140,7 @@ mod test {
     #[test]
     fn test_pk_script_is_generated_with_correct_content() -> Result<(), Box<dyn Error>> {
         let address = "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
-        let pk_script = generate_pubkey_script(address)?;
+        let pk_script = generate_pubkey_script(address, &TESTNET_PARAMS)?;
 
         assert_eq!(pk_script[..1], [0x76]);
         assert_eq!(pk_script[1..2], [0xA9]);
@@ -102,19 +152,55 @@ mod test {
 
     #[test]
     fn test_p2pkh_script_is_validated_correctly() -> Result<(), Box<dyn Error>> {
-        let hash: [u8; 32] = [123; 32];
-
         let address = "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
         let private_key: String =
             String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
-        let account = Account::new(private_key, address.to_string())?;
+        let account = Account::new(private_key, address.to_string(), &TESTNET_PARAMS)?;
 
-        let p2pkh_script = generate_pubkey_script(address)?;
-        let sig = SigScript::generate_sig_script(hash, &account)?;
-        let validation = p2pkh_script::validate(&p2pkh_script, sig.get_bytes())?;
+        let (transaction, p2pkh_script) = create_signed_transaction(&account)?;
+        let sig = transaction.tx_in[0].signature_script.get_bytes();
+        let validation = p2pkh_script::validate(&p2pkh_script, sig, &transaction, 0)?;
 
         assert!(validation);
         Ok(())
     }
-}
 
+    #[test]
+    fn test_p2pkh_script_with_signature_of_another_hash_is_not_valid() -> Result<(), Box<dyn Error>>
+    {
+        let address = "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
+        let private_key: String =
+            String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
+        let account = Account::new(private_key, address.to_string(), &TESTNET_PARAMS)?;
+
+        let (transaction, p2pkh_script) = create_signed_transaction(&account)?;
+        // the pubkey matches the pubkey hash but the signature does not sign the transaction
+        let forged_sig = SigScript::generate_sig_script([123; 32], &account)?;
+        let validation =
+            p2pkh_script::validate(&p2pkh_script, forged_sig.get_bytes(), &transaction, 0)?;
+
+        assert!(!validation);
+        Ok(())
+    }
+
+    #[test]
+    fn test_p2pkh_script_of_modified_transaction_is_not_valid() -> Result<(), Box<dyn Error>> {
+        let address = "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV";
+        let private_key: String =
+            String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
+        let account = Account::new(private_key, address.to_string(), &TESTNET_PARAMS)?;
+
+        let (mut transaction, p2pkh_script) = create_signed_transaction(&account)?;
+        let receiver_script = transaction.tx_out[0].get_pub_key_script().clone();
+        transaction.tx_out[0] = TxOut::new(
+            9000,
+            CompactSizeUint::new(receiver_script.len() as u128),
+            receiver_script,
+        );
+        let sig = transaction.tx_in[0].signature_script.get_bytes().clone();
+        let validation = p2pkh_script::validate(&p2pkh_script, &sig, &transaction, 0)?;
+
+        assert!(!validation);
+        Ok(())
+    }
+}
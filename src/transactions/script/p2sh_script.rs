@@ -0,0 +1,156 @@
+use super::script_opcodes::ScriptOpcodes;
+
+const BYTES_TO_PUSH: u8 = 20;
+const P2SH_SCRIPT_LENGTH: usize = 23;
+//      <...signatures...> <redeemScript>  (the scriptSig can only push data)
+//
+// scriptPubKey: OP_HASH160 <bytes_to_push> <scriptHash> OP_EQUAL
+// HEXA:         0xA9       <bytes_to_push> <scriptHash> 0x87
+// bytes length:  1 + 1 + 20 + 1 = 23
+// <scriptHash>: 20 bytes. The result of hash160 (sha256 + ripemd160 hash) to the redeem script.
+// If the scriptPubKey is satisfied, the redeem script is executed over the stack left by the scriptSig (BIP16).
+
+/// Generates the pubkey script that pays to the hash of a redeem script.
+pub fn generate_p2sh_pubkey_script(script_hash: &[u8; 20]) -> Vec<u8> {
+    let mut pk_script: Vec<u8> = Vec::new();
+    pk_script.push(ScriptOpcodes::OP_HASH160);
+    pk_script.push(BYTES_TO_PUSH);
+    pk_script.extend_from_slice(script_hash);
+    pk_script.push(ScriptOpcodes::OP_EQUAL);
+    pk_script
+}
+
+/// Returns true if the received pubkey script has the P2SH format.
+pub fn is_p2sh(pk_script: &[u8]) -> bool {
+    pk_script.len() == P2SH_SCRIPT_LENGTH
+        && pk_script[0] == ScriptOpcodes::OP_HASH160
+        && pk_script[1] == BYTES_TO_PUSH
+        && pk_script[22] == ScriptOpcodes::OP_EQUAL
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use super::{generate_p2sh_pubkey_script, is_p2sh};
+    use crate::{
+        address_decoder::{generate_p2sh_address, get_pubkey_hash_from_address, hash_160},
+        chain_params::TESTNET_PARAMS,
+        compact_size_uint::CompactSizeUint,
+        transactions::{
+            script::{
+                interpreter::{push_data_bytes, Script},
+                p2pkh_script::generate_pubkey_script,
+                script_opcodes::ScriptOpcodes,
+                signature_checker::SignatureChecker,
+            },
+            tx_out::TxOut,
+        },
+    };
+
+    /// Checker used to evaluate scripts without signatures
+    struct NoSignatureChecker;
+
+    impl SignatureChecker for NoSignatureChecker {
+        fn check_sig(&self, _sig: &[u8], _pubkey: &[u8], _script_code: &[u8]) -> bool {
+            false
+        }
+    }
+
+    /// Returns the scriptPubKey that pays to the redeem script
+    fn p2sh_script_of(redeem_script: &[u8]) -> Script {
+        Script::new(generate_p2sh_pubkey_script(&hash_160(redeem_script)))
+    }
+
+    #[test]
+    fn test_p2sh_address_generates_the_p2sh_script_of_the_redeem_script(
+    ) -> Result<(), Box<dyn Error>> {
+        let redeem_script = vec![ScriptOpcodes::OP_2, ScriptOpcodes::OP_EQUAL];
+        let address = generate_p2sh_address(&redeem_script, &TESTNET_PARAMS);
+        assert!(address.starts_with('2'));
+        assert_eq!(
+            get_pubkey_hash_from_address(&address, &TESTNET_PARAMS)?,
+            hash_160(&redeem_script)
+        );
+        let pk_script = generate_pubkey_script(&address, &TESTNET_PARAMS)?;
+        assert!(is_p2sh(&pk_script));
+        assert_eq!(
+            pk_script,
+            generate_p2sh_pubkey_script(&hash_160(&redeem_script))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_address_of_a_p2sh_output_is_the_p2sh_address() -> Result<(), &'static str> {
+        let redeem_script = vec![ScriptOpcodes::OP_2, ScriptOpcodes::OP_EQUAL];
+        let pk_script = generate_p2sh_pubkey_script(&hash_160(&redeem_script));
+        let tx_out = TxOut::new(
+            1000,
+            CompactSizeUint::new(pk_script.len() as u128),
+            pk_script,
+        );
+        assert_eq!(
+            tx_out.get_address(&TESTNET_PARAMS)?,
+            generate_p2sh_address(&redeem_script, &TESTNET_PARAMS)
+        );
+        assert_eq!(tx_out.script_type(), "P2SH");
+        Ok(())
+    }
+
+    #[test]
+    fn test_redeem_script_is_evaluated_with_the_stack_of_the_script_sig() {
+        // redeem script: 2 EQUAL
+        let redeem_script = vec![ScriptOpcodes::OP_2, ScriptOpcodes::OP_EQUAL];
+        let mut script_sig = vec![ScriptOpcodes::OP_2];
+        script_sig.extend_from_slice(&push_data_bytes(&redeem_script));
+        let result = Script::verify(
+            &Script::new(script_sig),
+            &p2sh_script_of(&redeem_script),
+            &NoSignatureChecker,
+        );
+        assert!(result.is_ok());
+
+        let mut script_sig = vec![ScriptOpcodes::OP_3];
+        script_sig.extend_from_slice(&push_data_bytes(&redeem_script));
+        let result = Script::verify(
+            &Script::new(script_sig),
+            &p2sh_script_of(&redeem_script),
+            &NoSignatureChecker,
+        );
+        assert_eq!(result, Err("The redeem script evaluated to false"));
+    }
+
+    #[test]
+    fn test_script_sig_of_a_p2sh_output_must_be_push_only() {
+        let redeem_script = vec![ScriptOpcodes::OP_2, ScriptOpcodes::OP_EQUAL];
+        // 1 1 ADD <redeem script>
+        let mut script_sig = vec![
+            ScriptOpcodes::OP_1,
+            ScriptOpcodes::OP_1,
+            ScriptOpcodes::OP_ADD,
+        ];
+        script_sig.extend_from_slice(&push_data_bytes(&redeem_script));
+        let result = Script::verify(
+            &Script::new(script_sig),
+            &p2sh_script_of(&redeem_script),
+            &NoSignatureChecker,
+        );
+        assert_eq!(
+            result,
+            Err("The scriptSig of a P2SH output can only push data")
+        );
+    }
+
+    #[test]
+    fn test_script_sig_with_another_redeem_script_does_not_unlock_the_p2sh_output() {
+        let redeem_script = vec![ScriptOpcodes::OP_2, ScriptOpcodes::OP_EQUAL];
+        let other_redeem_script = vec![ScriptOpcodes::OP_1];
+        let result = Script::verify(
+            &Script::new(push_data_bytes(&other_redeem_script)),
+            &p2sh_script_of(&redeem_script),
+            &NoSignatureChecker,
+        );
+        assert!(result.is_err());
+    }
+}
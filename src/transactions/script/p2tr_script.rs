@@ -0,0 +1,192 @@
+use super::{p2wpkh_script::generate_witness_pubkey_script, script_opcodes::ScriptOpcodes};
+
+const BYTES_TO_PUSH: u8 = 32;
+const P2TR_SCRIPT_LENGTH: usize = 34;
+/// Witness version of the taproot outputs
+pub const TAPROOT_WITNESS_VERSION: u8 = 1;
+//      scriptSig: (empty)    witness (key path): <Schnorr sig>
+//
+// scriptPubKey: OP_1 <bytes_to_push> <outputKey>
+// HEXA:         0x51 <bytes_to_push> <outputKey>
+// bytes length:  1 + 1 + 32 = 34
+// <outputKey>: 32 bytes. The x coordinate of the internal public key tweaked with the hash "TapTweak" of itself (BIP341).
+// The key path spend is a BIP340 Schnorr signature of the output key over the BIP341 signature hash,
+// which commits to the amounts and pubkey scripts of all the outputs spent by the transaction.
+
+/// Generates the pubkey script that pays to the received taproot output key.
+pub fn generate_p2tr_pubkey_script(output_key: &[u8; 32]) -> Vec<u8> {
+    generate_witness_pubkey_script(TAPROOT_WITNESS_VERSION, output_key)
+}
+
+/// Returns true if the received pubkey script has the P2TR format.
+pub fn is_p2tr(pk_script: &[u8]) -> bool {
+    pk_script.len() == P2TR_SCRIPT_LENGTH
+        && pk_script[0] == ScriptOpcodes::OP_1
+        && pk_script[1] == BYTES_TO_PUSH
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use super::{generate_p2tr_pubkey_script, is_p2tr};
+    use crate::{
+        account::Account,
+        address_decoder::{
+            decode_segwit_address, decode_wif_private_key, generate_p2tr_address,
+            get_taproot_output_key,
+        },
+        chain_params::TESTNET_PARAMS,
+        compact_size_uint::CompactSizeUint,
+        transactions::{
+            script::p2pkh_script::generate_pubkey_script, transaction::Transaction, tx_out::TxOut,
+        },
+        utxo_tuple::UtxoTuple,
+    };
+
+    const PRIVATE_KEY: &str = "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR";
+
+    /// Converts the received hexadecimal string into bytes
+    fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        for i in (0..hex.len()).step_by(2) {
+            bytes.push(u8::from_str_radix(&hex[i..i + 2], 16)?);
+        }
+        Ok(bytes)
+    }
+
+    /// Creates the account of the private key with its P2TR address
+    fn create_taproot_account() -> Result<Account, Box<dyn Error>> {
+        let address =
+            generate_p2tr_address(&decode_wif_private_key(PRIVATE_KEY)?, &TESTNET_PARAMS)?;
+        Account::new(PRIVATE_KEY.to_string(), address, &TESTNET_PARAMS)
+    }
+
+    /// Creates a P2TR output of the account, a transaction that spends it and signs it.
+    /// Returns the transaction and the outputs it spends.
+    fn create_signed_transaction(
+        account: &Account,
+    ) -> Result<(Transaction, Vec<Option<TxOut>>), Box<dyn Error>> {
+        let pk_script = generate_pubkey_script(&account.address, &TESTNET_PARAMS)?;
+        let txout = TxOut::new(
+            10000,
+            CompactSizeUint::new(pk_script.len() as u128),
+            pk_script,
+        );
+        let utxos = vec![UtxoTuple::new([7; 32], vec![(txout.clone(), 0)], 0, false)];
+        let mut transaction = Transaction::generate_unsigned_transaction(
+            "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF",
+            &account.address,
+            5000,
+            1000,
+            &utxos,
+            0,
+            &TESTNET_PARAMS,
+        )?;
+        transaction.sign(account, &utxos)?;
+        assert!(transaction.validate(&utxos).is_ok());
+        Ok((transaction, vec![Some(txout)]))
+    }
+
+    #[test]
+    fn test_output_key_of_bip86_internal_key() -> Result<(), Box<dyn Error>> {
+        let mut internal_key: [u8; 32] = [0; 32];
+        internal_key.copy_from_slice(&hex_to_bytes(
+            "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115",
+        )?);
+        assert_eq!(
+            get_taproot_output_key(&internal_key)?.to_vec(),
+            hex_to_bytes("a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bech32m_address_is_decoded_into_its_taproot_output_key() -> Result<(), Box<dyn Error>> {
+        // test vector of BIP350
+        let address = "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c";
+        let (witness_version, witness_program) = decode_segwit_address(address, &TESTNET_PARAMS)?;
+        assert_eq!(witness_version, 1);
+        assert_eq!(
+            witness_program,
+            hex_to_bytes("000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433")?
+        );
+        let pk_script = generate_pubkey_script(address, &TESTNET_PARAMS)?;
+        assert!(is_p2tr(&pk_script));
+        let tx_out = TxOut::new(1000, CompactSizeUint::new(34), pk_script);
+        assert_eq!(tx_out.get_address(&TESTNET_PARAMS)?, address);
+        assert_eq!(tx_out.script_type(), "P2TR");
+        Ok(())
+    }
+
+    #[test]
+    fn test_taproot_address_of_the_account_pays_to_its_output_key() -> Result<(), Box<dyn Error>> {
+        let account = create_taproot_account()?;
+        assert!(account.address.starts_with("tb1p"));
+        let mut internal_key: [u8; 32] = [0; 32];
+        internal_key.copy_from_slice(&account.get_pubkey_compressed()?[1..]);
+        let output_key = get_taproot_output_key(&internal_key)?;
+        assert_eq!(
+            generate_pubkey_script(&account.address, &TESTNET_PARAMS)?,
+            generate_p2tr_pubkey_script(&output_key)
+        );
+
+        let tx_out = TxOut::new(
+            1000,
+            CompactSizeUint::new(34),
+            generate_p2tr_pubkey_script(&output_key),
+        );
+        let utxo = UtxoTuple::new([1; 32], vec![(tx_out, 0)], 0, false);
+        assert!(utxo
+            .referenced_utxos(&account.address, &TESTNET_PARAMS)
+            .is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_path_spend_is_a_schnorr_signature_in_the_witness() -> Result<(), Box<dyn Error>> {
+        let account = create_taproot_account()?;
+        let (transaction, spent_outputs) = create_signed_transaction(&account)?;
+        assert!(transaction.tx_in[0].signature_script.get_bytes().is_empty());
+        assert_eq!(transaction.tx_in[0].witness().len(), 1);
+        assert_eq!(transaction.tx_in[0].witness()[0].len(), 64);
+        assert!(transaction.validate_input(0, &spent_outputs).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_taproot_signature_commits_to_the_spent_outputs() -> Result<(), Box<dyn Error>> {
+        let account = create_taproot_account()?;
+        let (transaction, spent_outputs) = create_signed_transaction(&account)?;
+        let pk_script = generate_pubkey_script(&account.address, &TESTNET_PARAMS)?;
+        let other_amount = vec![Some(TxOut::new(
+            20000,
+            CompactSizeUint::new(pk_script.len() as u128),
+            pk_script,
+        ))];
+        assert!(transaction.validate_input(0, &other_amount).is_err());
+        // the signature can not be checked if the spent outputs are unknown
+        let mut unknown_outputs = spent_outputs.clone();
+        unknown_outputs.push(None);
+        assert!(transaction.validate_input(0, &unknown_outputs).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_taproot_signature_with_another_sighash_type_is_not_valid() -> Result<(), Box<dyn Error>>
+    {
+        let account = create_taproot_account()?;
+        let (mut transaction, spent_outputs) = create_signed_transaction(&account)?;
+        let signature = transaction.tx_in[0].witness()[0].clone();
+        // the default sighash type can not be explicit and SIGHASH_ALL signs another hash
+        for hash_type in [0x00, 0x01] {
+            let mut signature_with_hash_type = signature.clone();
+            signature_with_hash_type.push(hash_type);
+            transaction.tx_in[0].set_witness(vec![signature_with_hash_type]);
+            assert!(transaction.validate_input(0, &spent_outputs).is_err());
+        }
+        transaction.tx_in[0].set_witness(vec![]);
+        assert!(transaction.validate_input(0, &spent_outputs).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use super::script_opcodes::ScriptOpcodes;
+
+const BYTES_TO_PUSH: u8 = 20;
+const P2WPKH_SCRIPT_LENGTH: usize = 22;
+//      scriptSig: (empty)    witness: <Sig> <PubKey>
+//
+// scriptPubKey: OP_0 <bytes_to_push> <pubKeyHash>
+// HEXA:         0x00 <bytes_to_push> <pubKeyHash>
+// bytes length:  1 + 1 + 20 = 22
+// <pubKeyHash>: 20 bytes. The witness program, result of hash160 (sha256 + ripemd160 hash) to the compressed public key SEC.
+// The witness is evaluated with the P2PKH script of the pubKeyHash and the signatures commit to the
+// amount of the output being spent (BIP143).
+
+/// Generates the pubkey script of a segwit output with the received witness version and witness program.
+pub fn generate_witness_pubkey_script(witness_version: u8, witness_program: &[u8]) -> Vec<u8> {
+    let mut pk_script: Vec<u8> = Vec::new();
+    if witness_version == 0 {
+        pk_script.push(ScriptOpcodes::OP_0);
+    } else {
+        pk_script.push(ScriptOpcodes::OP_1 + witness_version - 1);
+    }
+    pk_script.push(witness_program.len() as u8);
+    pk_script.extend_from_slice(witness_program);
+    pk_script
+}
+
+/// Generates the pubkey script that pays to the hash of a compressed public key with segwit.
+pub fn generate_p2wpkh_pubkey_script(pubkey_hash: &[u8; 20]) -> Vec<u8> {
+    generate_witness_pubkey_script(0, pubkey_hash)
+}
+
+/// Returns true if the received pubkey script has the P2WPKH format.
+pub fn is_p2wpkh(pk_script: &[u8]) -> bool {
+    pk_script.len() == P2WPKH_SCRIPT_LENGTH
+        && pk_script[0] == ScriptOpcodes::OP_0
+        && pk_script[1] == BYTES_TO_PUSH
+}
+
+/// Generates the script code with which the witness of a P2WPKH output is evaluated and signed:
+/// the P2PKH pubkey script of the pubkey hash.
+pub fn generate_p2wpkh_script_code(pubkey_hash: &[u8]) -> Vec<u8> {
+    let mut script_code: Vec<u8> = Vec::new();
+    script_code.push(ScriptOpcodes::OP_DUP);
+    script_code.push(ScriptOpcodes::OP_HASH160);
+    script_code.push(BYTES_TO_PUSH);
+    script_code.extend_from_slice(pubkey_hash);
+    script_code.push(ScriptOpcodes::OP_EQUALVERIFY);
+    script_code.push(ScriptOpcodes::OP_CHECKSIG);
+    script_code
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use super::{generate_p2wpkh_pubkey_script, generate_p2wpkh_script_code, is_p2wpkh};
+    use crate::{
+        account::Account,
+        address_decoder::{decode_wif_private_key, generate_p2wpkh_address, hash_160},
+        chain_params::TESTNET_PARAMS,
+        compact_size_uint::CompactSizeUint,
+        transactions::{
+            script::p2pkh_script::generate_pubkey_script, transaction::Transaction, tx_out::TxOut,
+        },
+        utxo_tuple::UtxoTuple,
+    };
+
+    const PRIVATE_KEY: &str = "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR";
+
+    /// Converts the received hexadecimal string into bytes
+    fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        for i in (0..hex.len()).step_by(2) {
+            bytes.push(u8::from_str_radix(&hex[i..i + 2], 16)?);
+        }
+        Ok(bytes)
+    }
+
+    /// Returns the outputs spent by a transaction whose input at tx_in_index spends the received output
+    fn spent_outputs(tx_in_index: usize, pk_script: &[u8], amount: i64) -> Vec<Option<TxOut>> {
+        let mut spent_outputs = vec![None; tx_in_index + 1];
+        spent_outputs[tx_in_index] = Some(TxOut::new(
+            amount,
+            CompactSizeUint::new(pk_script.len() as u128),
+            pk_script.to_vec(),
+        ));
+        spent_outputs
+    }
+
+    /// Creates the account of the private key with its P2WPKH address
+    fn create_segwit_account() -> Result<Account, Box<dyn Error>> {
+        let address =
+            generate_p2wpkh_address(&decode_wif_private_key(PRIVATE_KEY)?, &TESTNET_PARAMS)?;
+        Account::new(PRIVATE_KEY.to_string(), address, &TESTNET_PARAMS)
+    }
+
+    /// Creates a P2WPKH output of the account, a transaction that spends it and signs it
+    fn create_signed_transaction(
+        account: &Account,
+    ) -> Result<(Transaction, Vec<UtxoTuple>), Box<dyn Error>> {
+        let pk_script = generate_pubkey_script(&account.address, &TESTNET_PARAMS)?;
+        let txout = TxOut::new(
+            10000,
+            CompactSizeUint::new(pk_script.len() as u128),
+            pk_script,
+        );
+        let utxos = vec![UtxoTuple::new([7; 32], vec![(txout, 0)], 0, false)];
+        let mut transaction = Transaction::generate_unsigned_transaction(
+            "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF",
+            &account.address,
+            5000,
+            1000,
+            &utxos,
+            0,
+            &TESTNET_PARAMS,
+        )?;
+        transaction.sign(account, &utxos)?;
+        Ok((transaction, utxos))
+    }
+
+    #[test]
+    fn test_p2wpkh_address_generates_the_p2wpkh_script_of_the_pubkey_hash(
+    ) -> Result<(), Box<dyn Error>> {
+        let account = create_segwit_account()?;
+        assert!(account.address.starts_with("tb1q"));
+        let pk_script = generate_pubkey_script(&account.address, &TESTNET_PARAMS)?;
+        let pubkey_hash = hash_160(&account.get_pubkey_compressed()?);
+        assert!(is_p2wpkh(&pk_script));
+        assert_eq!(pk_script, generate_p2wpkh_pubkey_script(&pubkey_hash));
+
+        let tx_out = TxOut::new(1000, CompactSizeUint::new(22), pk_script);
+        assert_eq!(tx_out.get_address(&TESTNET_PARAMS)?, account.address);
+        assert_eq!(tx_out.script_type(), "P2WPKH");
+        Ok(())
+    }
+
+    #[test]
+    fn test_utxos_of_p2wpkh_outputs_are_referenced_by_the_segwit_address(
+    ) -> Result<(), Box<dyn Error>> {
+        let account = create_segwit_account()?;
+        let pk_script = generate_pubkey_script(&account.address, &TESTNET_PARAMS)?;
+        let p2wpkh_out = TxOut::new(1000, CompactSizeUint::new(22), pk_script);
+        let p2pkh_script =
+            generate_pubkey_script("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV", &TESTNET_PARAMS)?;
+        let p2pkh_out = TxOut::new(2000, CompactSizeUint::new(25), p2pkh_script);
+        let utxo = UtxoTuple::new([1; 32], vec![(p2pkh_out, 0), (p2wpkh_out, 1)], 0, false);
+
+        let referenced = utxo.referenced_utxos(&account.address, &TESTNET_PARAMS);
+        assert_eq!(
+            referenced.map(|utxo| utxo.get_indexes_from_utxos()),
+            Some(vec![1])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_input_that_spends_a_p2wpkh_output_is_signed_in_the_witness(
+    ) -> Result<(), Box<dyn Error>> {
+        let account = create_segwit_account()?;
+        let (transaction, utxos) = create_signed_transaction(&account)?;
+
+        assert!(transaction.tx_in[0].signature_script.get_bytes().is_empty());
+        assert_eq!(transaction.tx_in[0].witness().len(), 2);
+        assert!(transaction.has_witness());
+        assert_ne!(transaction.hash(), transaction.wtxid());
+        assert!(transaction.validate(&utxos).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_p2wpkh_signature_commits_to_the_amount_of_the_spent_output(
+    ) -> Result<(), Box<dyn Error>> {
+        let account = create_segwit_account()?;
+        let (transaction, utxos) = create_signed_transaction(&account)?;
+        let pk_script = utxos[0].utxo_set[0].0.get_pub_key_script().clone();
+
+        assert!(transaction
+            .validate_input(0, &spent_outputs(0, &pk_script, 10000))
+            .is_ok());
+        assert!(transaction
+            .validate_input(0, &spent_outputs(0, &pk_script, 20000))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_p2wpkh_output_can_not_be_spent_with_the_key_of_another_pubkey_hash(
+    ) -> Result<(), Box<dyn Error>> {
+        let account = create_segwit_account()?;
+        let (mut transaction, _) = create_signed_transaction(&account)?;
+        let other_pk_script = generate_p2wpkh_pubkey_script(&[9; 20]);
+        assert!(transaction
+            .validate_input(0, &spent_outputs(0, &other_pk_script, 10000))
+            .is_err());
+
+        // the witness without the public key is not valid
+        let signature = transaction.tx_in[0].witness()[0].clone();
+        transaction.tx_in[0].set_witness(vec![signature]);
+        let pk_script = generate_pubkey_script(&account.address, &TESTNET_PARAMS)?;
+        assert!(transaction
+            .validate_input(0, &spent_outputs(0, &pk_script, 10000))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_witness_v0_signature_hash_of_bip143_native_p2wpkh_example() -> Result<(), Box<dyn Error>>
+    {
+        let raw_transaction = hex_to_bytes(
+            "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+        )?;
+        let mut transaction = Transaction::unmarshalling(&raw_transaction, &mut 0)?;
+        let pk_script = hex_to_bytes("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1")?;
+        let amount: i64 = 600_000_000;
+        let script_code = generate_p2wpkh_script_code(&pk_script[2..]);
+        assert_eq!(
+            transaction
+                .witness_v0_signature_hash(1, &script_code, amount, 1)
+                .to_vec(),
+            hex_to_bytes("c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670")?
+        );
+
+        let signature = hex_to_bytes("304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01")?;
+        let pubkey =
+            hex_to_bytes("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357")?;
+        transaction.tx_in[1].set_witness(vec![signature, pubkey]);
+        assert!(transaction
+            .validate_input(1, &spent_outputs(1, &pk_script, amount))
+            .is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use bitcoin_hashes::{sha256, Hash};
+
+use super::{p2wpkh_script::generate_witness_pubkey_script, script_opcodes::ScriptOpcodes};
+
+const BYTES_TO_PUSH: u8 = 32;
+const P2WSH_SCRIPT_LENGTH: usize = 34;
+//      scriptSig: (empty)    witness: <...stack items...> <witnessScript>
+//
+// scriptPubKey: OP_0 <bytes_to_push> <scriptHash>
+// HEXA:         0x00 <bytes_to_push> <scriptHash>
+// bytes length:  1 + 1 + 32 = 34
+// <scriptHash>: 32 bytes. The witness program, result of a single sha256 hash to the witness script.
+// The witness script is evaluated over the rest of the witness and the signatures commit to the
+// amount of the output being spent (BIP143).
+
+/// Generates the pubkey script that pays to the hash of a witness script with segwit.
+pub fn generate_p2wsh_pubkey_script(witness_script: &[u8]) -> Vec<u8> {
+    let script_hash = sha256::Hash::hash(witness_script);
+    generate_witness_pubkey_script(0, script_hash.as_byte_array())
+}
+
+/// Returns true if the received pubkey script has the P2WSH format.
+pub fn is_p2wsh(pk_script: &[u8]) -> bool {
+    pk_script.len() == P2WSH_SCRIPT_LENGTH
+        && pk_script[0] == ScriptOpcodes::OP_0
+        && pk_script[1] == BYTES_TO_PUSH
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use super::{generate_p2wsh_pubkey_script, is_p2wsh};
+    use crate::{
+        address_decoder::{decode_segwit_address, generate_p2wsh_address},
+        chain_params::TESTNET_PARAMS,
+        compact_size_uint::CompactSizeUint,
+        transactions::{
+            script::{
+                interpreter::Script, p2pkh_script::generate_pubkey_script,
+                p2wpkh_script::is_p2wpkh, script_opcodes::ScriptOpcodes,
+                signature_checker::SignatureChecker,
+            },
+            tx_out::TxOut,
+        },
+    };
+
+    /// Checker used to evaluate scripts without signatures
+    struct NoSignatureChecker;
+
+    impl SignatureChecker for NoSignatureChecker {
+        fn check_sig(&self, _sig: &[u8], _pubkey: &[u8], _script_code: &[u8]) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_p2wsh_address_generates_the_p2wsh_script_of_the_witness_script(
+    ) -> Result<(), Box<dyn Error>> {
+        let witness_script = vec![ScriptOpcodes::OP_2, ScriptOpcodes::OP_EQUAL];
+        let address = generate_p2wsh_address(&witness_script, &TESTNET_PARAMS);
+        assert!(address.starts_with("tb1q"));
+        let (witness_version, witness_program) = decode_segwit_address(&address, &TESTNET_PARAMS)?;
+        assert_eq!(witness_version, 0);
+        assert_eq!(witness_program.len(), 32);
+        let pk_script = generate_pubkey_script(&address, &TESTNET_PARAMS)?;
+        assert!(is_p2wsh(&pk_script));
+        assert!(!is_p2wpkh(&pk_script));
+        assert_eq!(pk_script, generate_p2wsh_pubkey_script(&witness_script));
+        let tx_out = TxOut::new(
+            1000,
+            CompactSizeUint::new(pk_script.len() as u128),
+            pk_script,
+        );
+        assert_eq!(tx_out.get_address(&TESTNET_PARAMS)?, address);
+        assert_eq!(tx_out.script_type(), "P2WSH");
+        Ok(())
+    }
+
+    #[test]
+    fn test_witness_script_is_evaluated_with_the_rest_of_the_witness() {
+        // witness script: 2 EQUAL
+        let witness_script = vec![ScriptOpcodes::OP_2, ScriptOpcodes::OP_EQUAL];
+        let pk_script = Script::new(generate_p2wsh_pubkey_script(&witness_script));
+        let result = Script::verify_with_witness(
+            &Script::new(vec![]),
+            &pk_script,
+            &[vec![2], witness_script.clone()],
+            &NoSignatureChecker,
+        );
+        assert!(result.is_ok());
+
+        let result = Script::verify_with_witness(
+            &Script::new(vec![]),
+            &pk_script,
+            &[vec![3], witness_script],
+            &NoSignatureChecker,
+        );
+        assert!(result.is_err());
+    }
+}
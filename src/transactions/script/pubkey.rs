@@ -1,4 +1,9 @@
-use super::script_opcodes::ScriptOpcodes;
+use super::{
+    interpreter::Script, multisig_script::is_multisig, p2pkh_script::is_p2pkh,
+    p2sh_script::is_p2sh, p2tr_script::is_p2tr, p2wpkh_script::is_p2wpkh, p2wsh_script::is_p2wsh,
+    script_opcodes::ScriptOpcodes,
+};
+use crate::{address_decoder::encode_segwit_address, chain_params::ChainParams};
 use k256::sha2::Digest;
 use k256::sha2::Sha256;
 
@@ -17,25 +22,35 @@ impl Pubkey {
     pub fn bytes(&self) -> &Vec<u8> {
         &self.bytes
     }
-    /// Generate the address from the pubkey.
-    pub fn generate_address(&self) -> Result<String, &'static str> {
+    /// Generate the address of the network of the chain params from the pubkey.
+    pub fn generate_address(&self, params: &ChainParams) -> Result<String, &'static str> {
         // vec that generates the address
-        let mut adress_bytes: Vec<u8> = vec![0x6f];
+        let mut adress_bytes: Vec<u8> = vec![params.p2pkh_address_version];
         let bytes = &self.bytes;
         let length: usize = bytes.len();
         if length <= 3 {
             return Err("The pubkey field is too short");
         }
 
-        let first_byte = self.bytes[0];
-        if first_byte == 0x00 {
-            // the transaction is of the P2WPKH type
-            adress_bytes.extend_from_slice(&bytes[2..length]);
+        let script = Script::new(bytes.clone());
+        if let Some((witness_version, witness_program)) = script.witness_program() {
+            // the transaction is of the P2WPKH, P2WSH or P2TR type, the address is in bech32 or bech32m format
+            return Ok(encode_segwit_address(
+                witness_version,
+                witness_program,
+                params,
+            ));
         }
+        let first_byte = self.bytes[0];
         if first_byte == ScriptOpcodes::OP_DUP {
             // the transaction is of the P2PKH type
             adress_bytes.extend_from_slice(&bytes[3..(length - 2)]);
         }
+        if is_p2sh(bytes) {
+            // the transaction is of the P2SH type
+            adress_bytes = vec![params.p2sh_address_version];
+            adress_bytes.extend_from_slice(&bytes[2..(length - 1)]);
+        }
         let copy_adress_bytes: Vec<u8> = adress_bytes.clone();
         let checksum = Sha256::digest(Sha256::digest(copy_adress_bytes));
         adress_bytes.extend_from_slice(&checksum[..4]);
@@ -43,4 +58,27 @@ impl Pubkey {
         let string = encoded.into_string();
         Ok(string)
     }
+
+    /// Returns the name of the type of the pubkey script.
+    pub fn script_type(&self) -> &'static str {
+        if is_p2pkh(&self.bytes) {
+            return "P2PKH";
+        }
+        if is_p2sh(&self.bytes) {
+            return "P2SH";
+        }
+        if is_p2wpkh(&self.bytes) {
+            return "P2WPKH";
+        }
+        if is_p2wsh(&self.bytes) {
+            return "P2WSH";
+        }
+        if is_p2tr(&self.bytes) {
+            return "P2TR";
+        }
+        if is_multisig(&self.bytes) {
+            return "Multisig";
+        }
+        "Non standard"
+    }
 }
@@ -2,8 +2,277 @@
 pub struct ScriptOpcodes;
 
 impl ScriptOpcodes {
+    // push value
+    pub const OP_0: u8 = 0x00;
+    pub const OP_PUSHDATA1: u8 = 0x4C;
+    pub const OP_PUSHDATA2: u8 = 0x4D;
+    pub const OP_PUSHDATA4: u8 = 0x4E;
+    pub const OP_1NEGATE: u8 = 0x4F;
+    pub const OP_RESERVED: u8 = 0x50;
+    pub const OP_1: u8 = 0x51;
+    pub const OP_2: u8 = 0x52;
+    pub const OP_3: u8 = 0x53;
+    pub const OP_16: u8 = 0x60;
+
+    // control
+    pub const OP_NOP: u8 = 0x61;
+    pub const OP_VER: u8 = 0x62;
+    pub const OP_IF: u8 = 0x63;
+    pub const OP_NOTIF: u8 = 0x64;
+    pub const OP_VERIF: u8 = 0x65;
+    pub const OP_VERNOTIF: u8 = 0x66;
+    pub const OP_ELSE: u8 = 0x67;
+    pub const OP_ENDIF: u8 = 0x68;
+    pub const OP_VERIFY: u8 = 0x69;
+    pub const OP_RETURN: u8 = 0x6A;
+
+    // stack ops
+    pub const OP_TOALTSTACK: u8 = 0x6B;
+    pub const OP_FROMALTSTACK: u8 = 0x6C;
+    pub const OP_2DROP: u8 = 0x6D;
+    pub const OP_2DUP: u8 = 0x6E;
+    pub const OP_3DUP: u8 = 0x6F;
+    pub const OP_2OVER: u8 = 0x70;
+    pub const OP_2ROT: u8 = 0x71;
+    pub const OP_2SWAP: u8 = 0x72;
+    pub const OP_IFDUP: u8 = 0x73;
+    pub const OP_DEPTH: u8 = 0x74;
+    pub const OP_DROP: u8 = 0x75;
     pub const OP_DUP: u8 = 0x76;
-    pub const OP_HASH160: u8 = 0xA9;
+    pub const OP_NIP: u8 = 0x77;
+    pub const OP_OVER: u8 = 0x78;
+    pub const OP_PICK: u8 = 0x79;
+    pub const OP_ROLL: u8 = 0x7A;
+    pub const OP_ROT: u8 = 0x7B;
+    pub const OP_SWAP: u8 = 0x7C;
+    pub const OP_TUCK: u8 = 0x7D;
+
+    // splice ops
+    pub const OP_CAT: u8 = 0x7E;
+    pub const OP_SUBSTR: u8 = 0x7F;
+    pub const OP_LEFT: u8 = 0x80;
+    pub const OP_RIGHT: u8 = 0x81;
+    pub const OP_SIZE: u8 = 0x82;
+
+    // bit logic
+    pub const OP_INVERT: u8 = 0x83;
+    pub const OP_AND: u8 = 0x84;
+    pub const OP_OR: u8 = 0x85;
+    pub const OP_XOR: u8 = 0x86;
+    pub const OP_EQUAL: u8 = 0x87;
     pub const OP_EQUALVERIFY: u8 = 0x88;
+    pub const OP_RESERVED1: u8 = 0x89;
+    pub const OP_RESERVED2: u8 = 0x8A;
+
+    // numeric
+    pub const OP_1ADD: u8 = 0x8B;
+    pub const OP_1SUB: u8 = 0x8C;
+    pub const OP_2MUL: u8 = 0x8D;
+    pub const OP_2DIV: u8 = 0x8E;
+    pub const OP_NEGATE: u8 = 0x8F;
+    pub const OP_ABS: u8 = 0x90;
+    pub const OP_NOT: u8 = 0x91;
+    pub const OP_0NOTEQUAL: u8 = 0x92;
+    pub const OP_ADD: u8 = 0x93;
+    pub const OP_SUB: u8 = 0x94;
+    pub const OP_MUL: u8 = 0x95;
+    pub const OP_DIV: u8 = 0x96;
+    pub const OP_MOD: u8 = 0x97;
+    pub const OP_LSHIFT: u8 = 0x98;
+    pub const OP_RSHIFT: u8 = 0x99;
+    pub const OP_BOOLAND: u8 = 0x9A;
+    pub const OP_BOOLOR: u8 = 0x9B;
+    pub const OP_NUMEQUAL: u8 = 0x9C;
+    pub const OP_NUMEQUALVERIFY: u8 = 0x9D;
+    pub const OP_NUMNOTEQUAL: u8 = 0x9E;
+    pub const OP_LESSTHAN: u8 = 0x9F;
+    pub const OP_GREATERTHAN: u8 = 0xA0;
+    pub const OP_LESSTHANOREQUAL: u8 = 0xA1;
+    pub const OP_GREATERTHANOREQUAL: u8 = 0xA2;
+    pub const OP_MIN: u8 = 0xA3;
+    pub const OP_MAX: u8 = 0xA4;
+    pub const OP_WITHIN: u8 = 0xA5;
+
+    // crypto
+    pub const OP_RIPEMD160: u8 = 0xA6;
+    pub const OP_SHA1: u8 = 0xA7;
+    pub const OP_SHA256: u8 = 0xA8;
+    pub const OP_HASH160: u8 = 0xA9;
+    pub const OP_HASH256: u8 = 0xAA;
+    pub const OP_CODESEPARATOR: u8 = 0xAB;
     pub const OP_CHECKSIG: u8 = 0xAC;
+    pub const OP_CHECKSIGVERIFY: u8 = 0xAD;
+    pub const OP_CHECKMULTISIG: u8 = 0xAE;
+    pub const OP_CHECKMULTISIGVERIFY: u8 = 0xAF;
+
+    // expansion
+    pub const OP_NOP1: u8 = 0xB0;
+    pub const OP_NOP2: u8 = 0xB1;
+    pub const OP_NOP3: u8 = 0xB2;
+    pub const OP_CHECKLOCKTIMEVERIFY: u8 = 0xB1;
+    pub const OP_CHECKSEQUENCEVERIFY: u8 = 0xB2;
+    pub const OP_NOP4: u8 = 0xB3;
+    pub const OP_NOP10: u8 = 0xB9;
+
+    /// Returns the name of the received opcode, without the OP_ prefix.
+    /// Returns None if the opcode is a push of data or it is not defined.
+    pub fn name(opcode: u8) -> Option<&'static str> {
+        OPCODE_NAMES
+            .iter()
+            .find(|(_, value)| *value == opcode)
+            .map(|(name, _)| *name)
+    }
+
+    /// Returns the opcode that corresponds to the received name, with or without the OP_ prefix.
+    /// Returns None if there is no opcode with that name.
+    pub fn from_name(name: &str) -> Option<u8> {
+        let name = name.strip_prefix("OP_").unwrap_or(name);
+        OPCODE_NAMES
+            .iter()
+            .find(|(opcode_name, _)| *opcode_name == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Returns true if the opcode is disabled. A script that contains a disabled opcode is invalid,
+    /// even if the opcode is located in a branch that is not executed.
+    pub fn is_disabled(opcode: u8) -> bool {
+        matches!(
+            opcode,
+            Self::OP_CAT
+                | Self::OP_SUBSTR
+                | Self::OP_LEFT
+                | Self::OP_RIGHT
+                | Self::OP_INVERT
+                | Self::OP_AND
+                | Self::OP_OR
+                | Self::OP_XOR
+                | Self::OP_2MUL
+                | Self::OP_2DIV
+                | Self::OP_MUL
+                | Self::OP_DIV
+                | Self::OP_MOD
+                | Self::OP_LSHIFT
+                | Self::OP_RSHIFT
+        )
+    }
 }
+
+/// Names of the opcodes that are not a push of data.
+/// The names of the NOP2 and NOP3 opcodes are kept as aliases of CHECKLOCKTIMEVERIFY and CHECKSEQUENCEVERIFY.
+const OPCODE_NAMES: &[(&str, u8)] = &[
+    ("0", 0x00),
+    ("FALSE", 0x00),
+    ("PUSHDATA1", 0x4C),
+    ("PUSHDATA2", 0x4D),
+    ("PUSHDATA4", 0x4E),
+    ("1NEGATE", 0x4F),
+    ("RESERVED", 0x50),
+    ("1", 0x51),
+    ("TRUE", 0x51),
+    ("2", 0x52),
+    ("3", 0x53),
+    ("4", 0x54),
+    ("5", 0x55),
+    ("6", 0x56),
+    ("7", 0x57),
+    ("8", 0x58),
+    ("9", 0x59),
+    ("10", 0x5A),
+    ("11", 0x5B),
+    ("12", 0x5C),
+    ("13", 0x5D),
+    ("14", 0x5E),
+    ("15", 0x5F),
+    ("16", 0x60),
+    ("NOP", 0x61),
+    ("VER", 0x62),
+    ("IF", 0x63),
+    ("NOTIF", 0x64),
+    ("VERIF", 0x65),
+    ("VERNOTIF", 0x66),
+    ("ELSE", 0x67),
+    ("ENDIF", 0x68),
+    ("VERIFY", 0x69),
+    ("RETURN", 0x6A),
+    ("TOALTSTACK", 0x6B),
+    ("FROMALTSTACK", 0x6C),
+    ("2DROP", 0x6D),
+    ("2DUP", 0x6E),
+    ("3DUP", 0x6F),
+    ("2OVER", 0x70),
+    ("2ROT", 0x71),
+    ("2SWAP", 0x72),
+    ("IFDUP", 0x73),
+    ("DEPTH", 0x74),
+    ("DROP", 0x75),
+    ("DUP", 0x76),
+    ("NIP", 0x77),
+    ("OVER", 0x78),
+    ("PICK", 0x79),
+    ("ROLL", 0x7A),
+    ("ROT", 0x7B),
+    ("SWAP", 0x7C),
+    ("TUCK", 0x7D),
+    ("CAT", 0x7E),
+    ("SUBSTR", 0x7F),
+    ("LEFT", 0x80),
+    ("RIGHT", 0x81),
+    ("SIZE", 0x82),
+    ("INVERT", 0x83),
+    ("AND", 0x84),
+    ("OR", 0x85),
+    ("XOR", 0x86),
+    ("EQUAL", 0x87),
+    ("EQUALVERIFY", 0x88),
+    ("RESERVED1", 0x89),
+    ("RESERVED2", 0x8A),
+    ("1ADD", 0x8B),
+    ("1SUB", 0x8C),
+    ("2MUL", 0x8D),
+    ("2DIV", 0x8E),
+    ("NEGATE", 0x8F),
+    ("ABS", 0x90),
+    ("NOT", 0x91),
+    ("0NOTEQUAL", 0x92),
+    ("ADD", 0x93),
+    ("SUB", 0x94),
+    ("MUL", 0x95),
+    ("DIV", 0x96),
+    ("MOD", 0x97),
+    ("LSHIFT", 0x98),
+    ("RSHIFT", 0x99),
+    ("BOOLAND", 0x9A),
+    ("BOOLOR", 0x9B),
+    ("NUMEQUAL", 0x9C),
+    ("NUMEQUALVERIFY", 0x9D),
+    ("NUMNOTEQUAL", 0x9E),
+    ("LESSTHAN", 0x9F),
+    ("GREATERTHAN", 0xA0),
+    ("LESSTHANOREQUAL", 0xA1),
+    ("GREATERTHANOREQUAL", 0xA2),
+    ("MIN", 0xA3),
+    ("MAX", 0xA4),
+    ("WITHIN", 0xA5),
+    ("RIPEMD160", 0xA6),
+    ("SHA1", 0xA7),
+    ("SHA256", 0xA8),
+    ("HASH160", 0xA9),
+    ("HASH256", 0xAA),
+    ("CODESEPARATOR", 0xAB),
+    ("CHECKSIG", 0xAC),
+    ("CHECKSIGVERIFY", 0xAD),
+    ("CHECKMULTISIG", 0xAE),
+    ("CHECKMULTISIGVERIFY", 0xAF),
+    ("NOP1", 0xB0),
+    ("NOP2", 0xB1),
+    ("CHECKLOCKTIMEVERIFY", 0xB1),
+    ("NOP3", 0xB2),
+    ("CHECKSEQUENCEVERIFY", 0xB2),
+    ("NOP4", 0xB3),
+    ("NOP5", 0xB4),
+    ("NOP6", 0xB5),
+    ("NOP7", 0xB6),
+    ("NOP8", 0xB7),
+    ("NOP9", 0xB8),
+    ("NOP10", 0xB9),
+];
@@ -1,4 +1,7 @@
 use crate::account::Account;
+use crate::address_decoder;
+use crate::transactions::script::interpreter::push_data_bytes;
+use crate::transactions::script::script_opcodes::ScriptOpcodes;
 use k256::ecdsa;
 use k256::elliptic_curve;
 use k256::schnorr::signature::SignatureEncoding;
@@ -59,6 +62,68 @@ impl SigScript {
         Ok(sig_script)
     }
 
+    /// Returns the signature of the received hash with the private key of the account,
+    /// followed by the sighash type byte. It is used to sign the inputs of a multisig.
+    pub fn generate_signature(
+        hash_transaction: [u8; 32],
+        account: &Account,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        Self::generate_sig(hash_transaction, account.get_private_key()?)
+    }
+
+    /// Returns the signature script of an input that spends a multisig output: the dummy element
+    /// consumed by OP_CHECKMULTISIG and the signatures, followed by the redeem script if the output is P2SH.
+    pub fn generate_multisig_sig_script(
+        signatures: &[Vec<u8>],
+        redeem_script: Option<&[u8]>,
+    ) -> SigScript {
+        let mut sig_script_bytes: Vec<u8> = vec![ScriptOpcodes::OP_0];
+        for sig in signatures {
+            sig_script_bytes.extend_from_slice(&push_data_bytes(sig));
+        }
+        if let Some(redeem_script) = redeem_script {
+            sig_script_bytes.extend_from_slice(&push_data_bytes(redeem_script));
+        }
+        Self::new(sig_script_bytes)
+    }
+
+    /// Returns the witness of an input that spends a P2WSH multisig output: the empty dummy element,
+    /// the signatures and the witness script.
+    pub fn generate_multisig_witness(
+        signatures: &[Vec<u8>],
+        witness_script: &[u8],
+    ) -> Vec<Vec<u8>> {
+        let mut witness: Vec<Vec<u8>> = vec![vec![]];
+        witness.extend(signatures.iter().cloned());
+        witness.push(witness_script.to_vec());
+        witness
+    }
+
+    /// Returns the witness of an input that spends a P2WPKH output: the signature of the received hash
+    /// and the compressed public key.
+    pub fn generate_witness(
+        hash_transaction: [u8; 32],
+        account: &Account,
+    ) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        let private_key = account.get_private_key()?;
+        let sig = Self::generate_sig(hash_transaction, private_key)?;
+        let bytes_public_key = account.get_pubkey_compressed()?;
+        Ok(vec![sig, bytes_public_key.to_vec()])
+    }
+
+    /// Returns the witness of a key path spend of the taproot output of the account: the Schnorr
+    /// signature of the received signature hash with the tweaked private key, with the default sighash type.
+    pub fn generate_taproot_witness(
+        signature_hash: [u8; 32],
+        account: &Account,
+    ) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        let secp: secp256k1::Secp256k1<secp256k1::All> = secp256k1::Secp256k1::new();
+        let key_pair = address_decoder::get_taproot_key_pair(&secp, &account.get_private_key()?)?;
+        let message = secp256k1::Message::from_slice(&signature_hash)?;
+        let signature = secp.sign_schnorr_no_aux_rand(&message, &key_pair);
+        Ok(vec![signature.as_ref().to_vec()])
+    }
+
     /// Receives the hash, sig and public key.
     /// Returns true or false depending if the sig is correct.
     pub fn verify_sig(
@@ -72,12 +137,69 @@ impl SigScript {
         let signature = ecdsa::Signature::from_der(signature_bytes_without_flag)?;
         Ok(verifying_key.verify(hash, &signature).is_ok())
     }
+
+    /// Receives the signature hash of a taproot input, the Schnorr signature (64 bytes, without the sighash
+    /// type) and the x only public key (32 bytes). Returns true if the signature is valid (BIP340).
+    /// Malformed signatures or keys are not valid.
+    pub fn verify_schnorr_signature(
+        signature_hash: [u8; 32],
+        sig_bytes: &[u8],
+        public_key: &[u8],
+    ) -> bool {
+        let signature = match secp256k1::schnorr::Signature::from_slice(sig_bytes) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+        let public_key = match secp256k1::XOnlyPublicKey::from_slice(public_key) {
+            Ok(public_key) => public_key,
+            Err(_) => return false,
+        };
+        let message = match secp256k1::Message::from_slice(&signature_hash) {
+            Ok(message) => message,
+            Err(_) => return false,
+        };
+        let secp = secp256k1::Secp256k1::verification_only();
+        secp.verify_schnorr(&signature, &message, &public_key)
+            .is_ok()
+    }
+
+    /// Receives the signature hash of an input (already double hashed), the sig with the sighash type byte
+    /// at the end and the public key (compressed or uncompressed SEC).
+    /// Returns true or false depending if the sig is correct. Malformed sigs or keys are not valid.
+    pub fn verify_signature_hash(
+        signature_hash: [u8; 32],
+        sig_bytes: &[u8],
+        public_key: &[u8],
+    ) -> Result<bool, Box<dyn Error>> {
+        if sig_bytes.is_empty() {
+            return Ok(false);
+        }
+        // removes the byte of the sighash type. The strict DER encoding is checked by the interpreter (BIP66),
+        // so the lax parser is used as Bitcoin Core does
+        let signature_bytes_without_flag = &sig_bytes[0..sig_bytes.len() - 1];
+        let mut signature =
+            match secp256k1::ecdsa::Signature::from_der_lax(signature_bytes_without_flag) {
+                Ok(signature) => signature,
+                Err(_) => return Ok(false),
+            };
+        // signatures with high S are accepted by consensus, the library only verifies low S
+        signature.normalize_s();
+        let public_key = match secp256k1::PublicKey::from_slice(public_key) {
+            Ok(public_key) => public_key,
+            Err(_) => return Ok(false),
+        };
+        let message = secp256k1::Message::from_slice(&signature_hash)?;
+        let secp = secp256k1::Secp256k1::verification_only();
+        Ok(secp.verify_ecdsa(&message, &signature, &public_key).is_ok())
+    }
 }
 #[cfg(test)]
 mod test {
     use std::error::Error;
 
-    use crate::{account::Account, transactions::script::sig_script::SigScript};
+    use crate::{
+        account::Account, chain_params::TESTNET_PARAMS, transactions::script::sig_script::SigScript,
+    };
 
     #[test]
     fn test_script_sig_length_is_71_bytes_with_key_type() -> Result<(), Box<dyn Error>> {
@@ -105,7 +227,7 @@ mod test {
         let address_expected: String = String::from("mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV");
         let private_key: String =
             String::from("cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR");
-        let account = Account::new(private_key, address_expected)?;
+        let account = Account::new(private_key, address_expected, &TESTNET_PARAMS)?;
         let sig = SigScript::generate_sig(hash.clone(), account.get_private_key()?)?;
         assert!(SigScript::verify_sig(
             &hash,
@@ -115,4 +237,3 @@ mod test {
         Ok(())
     }
 }
-
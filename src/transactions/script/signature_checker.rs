@@ -0,0 +1,175 @@
+use super::sig_script::SigScript;
+use crate::transactions::{
+    transaction::{
+        Transaction, LOCKTIME_THRESHOLD, SEQUENCE_FINAL, SEQUENCE_LOCKTIME_DISABLE_FLAG,
+        SEQUENCE_LOCKTIME_MASK, SEQUENCE_LOCKTIME_TYPE_FLAG,
+    },
+    tx_out::TxOut,
+};
+
+/// Length of a Schnorr signature that uses the default sighash type (BIP341).
+const SCHNORR_SIG_LEN: usize = 64;
+
+/// Checks the signatures found while evaluating a script.
+/// It allows to evaluate scripts without depending on the transaction that contains them.
+pub trait SignatureChecker {
+    /// Receives the signature (with the sighash type byte at the end), the public key and
+    /// the script code (the part of the script that is signed).
+    /// Returns true if the signature is valid.
+    fn check_sig(&self, sig: &[u8], pubkey: &[u8], script_code: &[u8]) -> bool;
+
+    /// Receives the signature, the public key and the script code of a signature found while
+    /// evaluating the script of a witness v0 program.
+    /// Returns true if the signature is valid for the signature hash defined in BIP143.
+    fn check_witness_v0_sig(&self, _sig: &[u8], _pubkey: &[u8], _script_code: &[u8]) -> bool {
+        false
+    }
+
+    /// Receives the Schnorr signature of a taproot key path spend (with the sighash type byte at the end if
+    /// it is not the default one), the output key and the annex of the witness, if it has one.
+    /// Returns true if the signature is valid for the signature hash defined in BIP341.
+    fn check_schnorr_sig(&self, _sig: &[u8], _pubkey: &[u8], _annex: Option<&[u8]>) -> bool {
+        false
+    }
+
+    /// Receives the lock time of an OP_CHECKLOCKTIMEVERIFY.
+    /// Returns true if the lock time of the transaction satisfies it.
+    fn check_lock_time(&self, _lock_time: i64) -> bool {
+        false
+    }
+
+    /// Receives the relative lock time of an OP_CHECKSEQUENCEVERIFY.
+    /// Returns true if the sequence of the input satisfies it.
+    fn check_sequence(&self, _sequence: i64) -> bool {
+        false
+    }
+}
+
+/// Checks the signatures against the signature hash of an input of a transaction.
+/// The outputs spent by the inputs of the transaction (in the same order, None if they are unknown)
+/// are needed to check the signatures of witness programs: witness v0 signatures commit to the amount
+/// spent by the input and taproot signatures commit to the amounts and pubkey scripts of all of them.
+pub struct TransactionSignatureChecker<'a> {
+    transaction: &'a Transaction,
+    tx_in_index: usize,
+    spent_outputs: &'a [Option<TxOut>],
+}
+
+impl<'a> TransactionSignatureChecker<'a> {
+    /// Creates the checker of the input at the received index of the transaction.
+    pub fn new(transaction: &'a Transaction, tx_in_index: usize) -> Self {
+        Self::new_with_spent_outputs(transaction, tx_in_index, &[])
+    }
+
+    /// Creates the checker of the input at the received index of the transaction, whose inputs
+    /// spend the received outputs.
+    pub fn new_with_spent_outputs(
+        transaction: &'a Transaction,
+        tx_in_index: usize,
+        spent_outputs: &'a [Option<TxOut>],
+    ) -> Self {
+        TransactionSignatureChecker {
+            transaction,
+            tx_in_index,
+            spent_outputs,
+        }
+    }
+
+    /// Returns the amount of the output spent by the input, or 0 if it is unknown.
+    fn amount(&self) -> i64 {
+        match self.spent_outputs.get(self.tx_in_index) {
+            Some(Some(tx_out)) => tx_out.value(),
+            _ => 0,
+        }
+    }
+}
+
+impl SignatureChecker for TransactionSignatureChecker<'_> {
+    fn check_sig(&self, sig: &[u8], pubkey: &[u8], script_code: &[u8]) -> bool {
+        if sig.is_empty() {
+            return false;
+        }
+        let sighash_type = sig[sig.len() - 1] as u32;
+        let signature_hash =
+            self.transaction
+                .signature_hash(self.tx_in_index, script_code, sighash_type);
+        SigScript::verify_signature_hash(signature_hash, sig, pubkey).unwrap_or(false)
+    }
+
+    fn check_witness_v0_sig(&self, sig: &[u8], pubkey: &[u8], script_code: &[u8]) -> bool {
+        if sig.is_empty() {
+            return false;
+        }
+        let sighash_type = sig[sig.len() - 1] as u32;
+        let signature_hash = self.transaction.witness_v0_signature_hash(
+            self.tx_in_index,
+            script_code,
+            self.amount(),
+            sighash_type,
+        );
+        SigScript::verify_signature_hash(signature_hash, sig, pubkey).unwrap_or(false)
+    }
+
+    fn check_schnorr_sig(&self, sig: &[u8], pubkey: &[u8], annex: Option<&[u8]>) -> bool {
+        let hash_type = match sig.len() {
+            SCHNORR_SIG_LEN => 0,
+            // the default sighash type can not be explicit
+            len if len == SCHNORR_SIG_LEN + 1 && sig[SCHNORR_SIG_LEN] != 0 => sig[SCHNORR_SIG_LEN],
+            _ => return false,
+        };
+        // the signature hash commits to all the spent outputs, so all of them must be known
+        let spent_outputs: Option<Vec<TxOut>> = self.spent_outputs.iter().cloned().collect();
+        let spent_outputs = match spent_outputs {
+            Some(spent_outputs) if spent_outputs.len() == self.transaction.tx_in.len() => {
+                spent_outputs
+            }
+            _ => return false,
+        };
+        match self.transaction.taproot_signature_hash(
+            self.tx_in_index,
+            &spent_outputs,
+            hash_type,
+            annex,
+        ) {
+            Some(signature_hash) => {
+                SigScript::verify_schnorr_signature(signature_hash, &sig[..SCHNORR_SIG_LEN], pubkey)
+            }
+            None => false,
+        }
+    }
+
+    fn check_lock_time(&self, lock_time: i64) -> bool {
+        let tx_lock_time = self.transaction.lock_time as i64;
+        let threshold = LOCKTIME_THRESHOLD as i64;
+        // both lock times must be heights or both must be timestamps
+        if (tx_lock_time < threshold) != (lock_time < threshold) || lock_time > tx_lock_time {
+            return false;
+        }
+        // if the input is final the lock time of the transaction is not enforced
+        match self.transaction.tx_in.get(self.tx_in_index) {
+            Some(tx_in) => tx_in.sequence() != SEQUENCE_FINAL,
+            None => false,
+        }
+    }
+
+    fn check_sequence(&self, sequence: i64) -> bool {
+        let tx_sequence = match self.transaction.tx_in.get(self.tx_in_index) {
+            Some(tx_in) => tx_in.sequence() as i64,
+            None => return false,
+        };
+        // relative lock times are only enforced in transactions of version 2 or greater
+        if self.transaction.version < 2 || tx_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG as i64 != 0
+        {
+            return false;
+        }
+        let mask = (SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK) as i64;
+        let type_flag = SEQUENCE_LOCKTIME_TYPE_FLAG as i64;
+        let tx_sequence = tx_sequence & mask;
+        let sequence = sequence & mask;
+        // both must be in blocks or both must be in units of 512 seconds
+        if (tx_sequence < type_flag) != (sequence < type_flag) {
+            return false;
+        }
+        sequence <= tx_sequence
+    }
+}
@@ -9,22 +9,56 @@ use bitcoin_hashes::{sha256, sha256d, Hash};
 use gtk::glib;
 
 use crate::{
-    account::Account, compact_size_uint::CompactSizeUint, custom_errors::NodeCustomErrors,
-    gtk::ui_events::UIEvent, logwriter::log_writer::LogSender, utxo_tuple::UtxoTuple,
+    account::Account,
+    address_decoder::{hash_160, tagged_hash},
+    blocks::block_undo::SpentOutput,
+    chain_params::ChainParams,
+    compact_size_uint::CompactSizeUint,
+    custom_errors::NodeCustomErrors,
+    gtk::ui_events::UIEvent,
+    logwriter::log_writer::LogSender,
+    utxo_tuple::UtxoTuple,
 };
 
 use super::{
     outpoint::Outpoint,
     script::{
-        p2pkh_script::{self, generate_pubkey_script},
+        interpreter::Script,
+        p2pkh_script::generate_pubkey_script,
+        p2sh_script::generate_p2sh_pubkey_script,
+        p2tr_script::is_p2tr,
+        p2wpkh_script::{generate_p2wpkh_script_code, is_p2wpkh},
+        p2wsh_script::generate_p2wsh_pubkey_script,
         sig_script::SigScript,
+        signature_checker::TransactionSignatureChecker,
     },
     tx_in::TxIn,
     tx_out::TxOut,
 };
 
+/// Sighash type of the taproot signatures that sign all the inputs and outputs without the sighash byte
+const SIG_HASH_DEFAULT: u8 = 0x00;
 const SIG_HASH_ALL: u32 = 0x00000001;
+const SIG_HASH_NONE: u32 = 0x00000002;
+const SIG_HASH_SINGLE: u32 = 0x00000003;
+const SIG_HASH_ANYONECANPAY: u32 = 0x00000080;
 const TRANSACTION_VERSION: i32 = 0x00000002;
+const SEGWIT_MARKER: u8 = 0x00;
+const SEGWIT_FLAG: u8 = 0x01;
+/// Maximum amount in satoshis that an output or the sum of the outputs of a transaction can have (21 million BTC).
+pub const MAX_MONEY: i64 = 21_000_000 * 100_000_000;
+/// Lock times below this value are block heights and the rest are timestamps.
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+/// Sequence of an input that does not enable the lock time nor a relative lock time.
+pub const SEQUENCE_FINAL: u32 = 0xffffffff;
+/// If this bit of the sequence is set, the sequence is not a relative lock time (BIP68).
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+/// If this bit of the sequence is set, the relative lock time is in units of 512 seconds, otherwise in blocks.
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+/// Bits of the sequence that contain the value of the relative lock time.
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000ffff;
+/// Relative lock times by time are in units of 2^9 = 512 seconds.
+const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 9;
 
 #[derive(Debug, PartialEq, Clone)]
 /// Represents a bitcoin transaction
@@ -70,15 +104,34 @@ impl Transaction {
         version_bytes.copy_from_slice(&bytes[*offset..(*offset + 4)]);
         *offset += 4;
         let version = i32::from_le_bytes(version_bytes);
+        // BIP144: the segwit serialization has a marker (0x00) and a flag (0x01) before the txin count
+        let has_witness = bytes.len() > *offset + 1 && bytes[*offset] == SEGWIT_MARKER;
+        if has_witness {
+            if bytes[*offset + 1] != SEGWIT_FLAG {
+                return Err("The flag of the segwit transaction is invalid.");
+            }
+            *offset += 2;
+        }
         let txin_count: CompactSizeUint = CompactSizeUint::unmarshalling(bytes, &mut *offset)?;
         let amount_txin: u64 = txin_count.decoded_value();
-        let tx_in: Vec<TxIn> = TxIn::unmarshalling_txins(bytes, amount_txin, &mut *offset)?; // update offset
+        let mut tx_in: Vec<TxIn> = TxIn::unmarshalling_txins(bytes, amount_txin, &mut *offset)?; // update offset
+        if tx_in.is_empty() {
+            return Err("A transaction must have at least one txin.");
+        }
         if tx_in[0].is_coinbase() && txin_count.decoded_value() != 1 {
             return Err("A coinbase transaction must have only one txin.");
         }
         let txout_count: CompactSizeUint = CompactSizeUint::unmarshalling(bytes, &mut *offset)?;
         let amount_txout: u64 = txout_count.decoded_value();
         let tx_out: Vec<TxOut> = TxOut::unmarshalling_txouts(bytes, amount_txout, &mut *offset)?; // update offset
+        if has_witness {
+            for tx_in in tx_in.iter_mut() {
+                tx_in.unmarshalling_witness(bytes, offset)?;
+            }
+        }
+        if bytes.len() < *offset + 4 {
+            return Err("The byte array is too short to contain the lock time of the transaction.");
+        }
         let mut lock_time_bytes: [u8; 4] = [0; 4];
         lock_time_bytes.copy_from_slice(&bytes[*offset..(*offset + 4)]);
         *offset += 4;
@@ -94,10 +147,26 @@ impl Transaction {
     }
 
     /// Marshalls the transaction.
+    /// If any of the inputs has witness data, the segwit serialization (BIP144) is used.
     /// Stores the bytes in the reference of the received vector.
     pub fn marshalling(&self, bytes: &mut Vec<u8>) {
+        self.marshalling_with_format(bytes, self.has_witness());
+    }
+
+    /// Marshalls the transaction in the legacy format, without the witness data.
+    /// It is the serialization used to calculate the txid.
+    pub fn marshalling_without_witness(&self, bytes: &mut Vec<u8>) {
+        self.marshalling_with_format(bytes, false);
+    }
+
+    /// Marshalls the transaction including the marker, the flag and the witnesses if with_witness is true.
+    fn marshalling_with_format(&self, bytes: &mut Vec<u8>, with_witness: bool) {
         let version_bytes: [u8; 4] = self.version.to_le_bytes();
         bytes.extend_from_slice(&version_bytes);
+        if with_witness {
+            bytes.push(SEGWIT_MARKER);
+            bytes.push(SEGWIT_FLAG);
+        }
         bytes.extend_from_slice(&self.txin_count.marshalling());
         for tx_in in &self.tx_in {
             tx_in.marshalling(bytes);
@@ -106,31 +175,256 @@ impl Transaction {
         for tx_out in &self.tx_out {
             tx_out.marshalling(bytes);
         }
+        if with_witness {
+            for tx_in in &self.tx_in {
+                tx_in.marshalling_witness(bytes);
+            }
+        }
         let locktime_bytes: [u8; 4] = self.lock_time.to_le_bytes();
         bytes.extend_from_slice(&locktime_bytes);
     }
-    /// Returs the hash of the transaction
+
+    /// Returns true if any of the inputs of the transaction has witness data
+    pub fn has_witness(&self) -> bool {
+        self.tx_in.iter().any(|tx_in| tx_in.has_witness())
+    }
+
+    /// Returs the hash of the transaction (txid).
+    /// It is calculated over the serialization without the witness data.
     pub fn hash(&self) -> [u8; 32] {
-        self.hash_message(false)
+        let mut raw_transaction_bytes: Vec<u8> = Vec::new();
+        self.marshalling_without_witness(&mut raw_transaction_bytes);
+        let hash_transaction = sha256d::Hash::hash(&raw_transaction_bytes);
+        *hash_transaction.as_byte_array()
     }
-    /// Hashes the transaction.
-    /// If it receives true, it pushes the bytes corresponding to the SIGHASH_ALL inside the vector.
-    /// Otherwise, it hashes normally.
-    fn hash_message(&self, is_message: bool) -> [u8; 32] {
+
+    /// Returns the witness hash of the transaction (wtxid).
+    /// It is calculated over the serialization with the witness data, so it is equal
+    /// to the txid if the transaction has no witnesses.
+    pub fn wtxid(&self) -> [u8; 32] {
         let mut raw_transaction_bytes: Vec<u8> = Vec::new();
         self.marshalling(&mut raw_transaction_bytes);
-        if is_message {
-            let bytes = SIG_HASH_ALL.to_le_bytes();
-            raw_transaction_bytes.extend_from_slice(&bytes);
-        }
-        if is_message {
-            let hash_transaction = sha256::Hash::hash(&raw_transaction_bytes);
-            return *hash_transaction.as_byte_array();
-        }
         let hash_transaction = sha256d::Hash::hash(&raw_transaction_bytes);
         *hash_transaction.as_byte_array()
     }
 
+    /// Generates the message that the signature of the input at tx_in_index commits to.
+    /// The signature script of that input is replaced by the script_code (the pubkey script of the
+    /// output being spent), the other ones are emptied and the inputs and outputs are modified
+    /// according to the sighash type. The sighash type is pushed at the end of the message.
+    /// Returns None if the sighash type is SIGHASH_SINGLE and there is no output with the index of the input.
+    fn signature_message(
+        &self,
+        tx_in_index: usize,
+        script_code: &[u8],
+        sighash_type: u32,
+    ) -> Option<Vec<u8>> {
+        let mut tx_copy = self.clone();
+        let base_type = sighash_type & 0x1f;
+        for (index, tx_in) in tx_copy.tx_in.iter_mut().enumerate() {
+            if index == tx_in_index {
+                tx_in.set_signature_script(script_code.to_vec());
+                continue;
+            }
+            tx_in.set_signature_script(vec![]);
+            // with NONE and SINGLE the other inputs can be updated, so their sequence is not signed
+            if base_type == SIG_HASH_NONE || base_type == SIG_HASH_SINGLE {
+                tx_in.set_sequence(0);
+            }
+        }
+        if base_type == SIG_HASH_NONE {
+            tx_copy.tx_out.clear();
+        } else if base_type == SIG_HASH_SINGLE {
+            if tx_in_index >= tx_copy.tx_out.len() {
+                return None;
+            }
+            tx_copy.tx_out.truncate(tx_in_index + 1);
+            for tx_out in tx_copy.tx_out.iter_mut().take(tx_in_index) {
+                *tx_out = TxOut::new(-1, CompactSizeUint::new(0), vec![]);
+            }
+        }
+        if sighash_type & SIG_HASH_ANYONECANPAY != 0 {
+            let input_to_sign = tx_copy.tx_in[tx_in_index].clone();
+            tx_copy.tx_in = vec![input_to_sign];
+        }
+        tx_copy.txin_count = CompactSizeUint::new(tx_copy.tx_in.len() as u128);
+        tx_copy.txout_count = CompactSizeUint::new(tx_copy.tx_out.len() as u128);
+        let mut message: Vec<u8> = Vec::new();
+        tx_copy.marshalling_without_witness(&mut message);
+        message.extend_from_slice(&sighash_type.to_le_bytes());
+        Some(message)
+    }
+
+    /// Returns the hash (double sha256) that the signature of the input at tx_in_index must sign,
+    /// receiving the pubkey script of the output being spent and the sighash type of the signature.
+    pub fn signature_hash(
+        &self,
+        tx_in_index: usize,
+        script_code: &[u8],
+        sighash_type: u32,
+    ) -> [u8; 32] {
+        match self.signature_message(tx_in_index, script_code, sighash_type) {
+            Some(message) => *sha256d::Hash::hash(&message).as_byte_array(),
+            // SIGHASH_SINGLE without matching output signs the number one, as the reference client does
+            None => {
+                let mut one: [u8; 32] = [0; 32];
+                one[0] = 1;
+                one
+            }
+        }
+    }
+
+    /// Generates the message that the signature of the input at tx_in_index commits to when it spends
+    /// a witness v0 program, as defined in BIP143. Unlike the legacy message, it commits to the amount of
+    /// the output being spent and the hashes of the outpoints, sequences and outputs can be reused
+    /// between the inputs.
+    fn witness_v0_signature_message(
+        &self,
+        tx_in_index: usize,
+        script_code: &[u8],
+        amount: i64,
+        sighash_type: u32,
+    ) -> Vec<u8> {
+        let base_type = sighash_type & 0x1f;
+        let anyone_can_pay = sighash_type & SIG_HASH_ANYONECANPAY != 0;
+        let mut hash_prevouts: [u8; 32] = [0; 32];
+        if !anyone_can_pay {
+            let mut outpoints: Vec<u8> = Vec::new();
+            for tx_in in &self.tx_in {
+                tx_in.outpoint().marshalling(&mut outpoints);
+            }
+            hash_prevouts = *sha256d::Hash::hash(&outpoints).as_byte_array();
+        }
+        let mut hash_sequence: [u8; 32] = [0; 32];
+        if !anyone_can_pay && base_type != SIG_HASH_SINGLE && base_type != SIG_HASH_NONE {
+            let mut sequences: Vec<u8> = Vec::new();
+            for tx_in in &self.tx_in {
+                sequences.extend_from_slice(&tx_in.sequence().to_le_bytes());
+            }
+            hash_sequence = *sha256d::Hash::hash(&sequences).as_byte_array();
+        }
+        let mut hash_outputs: [u8; 32] = [0; 32];
+        if base_type != SIG_HASH_SINGLE && base_type != SIG_HASH_NONE {
+            let mut outputs: Vec<u8> = Vec::new();
+            for tx_out in &self.tx_out {
+                tx_out.marshalling(&mut outputs);
+            }
+            hash_outputs = *sha256d::Hash::hash(&outputs).as_byte_array();
+        } else if base_type == SIG_HASH_SINGLE && tx_in_index < self.tx_out.len() {
+            let mut output: Vec<u8> = Vec::new();
+            self.tx_out[tx_in_index].marshalling(&mut output);
+            hash_outputs = *sha256d::Hash::hash(&output).as_byte_array();
+        }
+        let tx_in = &self.tx_in[tx_in_index];
+        let mut message: Vec<u8> = Vec::new();
+        message.extend_from_slice(&self.version.to_le_bytes());
+        message.extend_from_slice(&hash_prevouts);
+        message.extend_from_slice(&hash_sequence);
+        tx_in.outpoint().marshalling(&mut message);
+        message.extend_from_slice(&CompactSizeUint::new(script_code.len() as u128).marshalling());
+        message.extend_from_slice(script_code);
+        message.extend_from_slice(&amount.to_le_bytes());
+        message.extend_from_slice(&tx_in.sequence().to_le_bytes());
+        message.extend_from_slice(&hash_outputs);
+        message.extend_from_slice(&self.lock_time.to_le_bytes());
+        message.extend_from_slice(&sighash_type.to_le_bytes());
+        message
+    }
+
+    /// Returns the hash (double sha256) that the signature of the input at tx_in_index must sign when it
+    /// spends a witness v0 program, receiving the script code, the amount of the output being spent and
+    /// the sighash type of the signature (BIP143).
+    pub fn witness_v0_signature_hash(
+        &self,
+        tx_in_index: usize,
+        script_code: &[u8],
+        amount: i64,
+        sighash_type: u32,
+    ) -> [u8; 32] {
+        let message =
+            self.witness_v0_signature_message(tx_in_index, script_code, amount, sighash_type);
+        *sha256d::Hash::hash(&message).as_byte_array()
+    }
+
+    /// Returns the hash that the Schnorr signature of a taproot key path spend of the input at tx_in_index
+    /// must sign, as defined in BIP341. Receives the outputs spent by all the inputs of the transaction
+    /// (in the same order), the sighash type and the annex of the witness, if it has one.
+    /// Returns None if the sighash type is invalid or if it is SIGHASH_SINGLE and there is no output with
+    /// the index of the input.
+    pub fn taproot_signature_hash(
+        &self,
+        tx_in_index: usize,
+        spent_outputs: &[TxOut],
+        hash_type: u8,
+        annex: Option<&[u8]>,
+    ) -> Option<[u8; 32]> {
+        let base_type = (hash_type & 0x03) as u32;
+        let anyone_can_pay = hash_type as u32 & SIG_HASH_ANYONECANPAY != 0;
+        if !matches!(hash_type, 0x00..=0x03 | 0x81..=0x83)
+            || spent_outputs.len() != self.tx_in.len()
+            || tx_in_index >= self.tx_in.len()
+        {
+            return None;
+        }
+        let serialize_pubkey_script = |tx_out: &TxOut, bytes: &mut Vec<u8>| {
+            let pubkey_script = tx_out.get_pub_key_script();
+            bytes.extend_from_slice(
+                &CompactSizeUint::new(pubkey_script.len() as u128).marshalling(),
+            );
+            bytes.extend_from_slice(pubkey_script);
+        };
+        // epoch
+        let mut message: Vec<u8> = vec![0x00];
+        message.push(hash_type);
+        message.extend_from_slice(&self.version.to_le_bytes());
+        message.extend_from_slice(&self.lock_time.to_le_bytes());
+        if !anyone_can_pay {
+            let mut outpoints: Vec<u8> = Vec::new();
+            let mut amounts: Vec<u8> = Vec::new();
+            let mut pubkey_scripts: Vec<u8> = Vec::new();
+            let mut sequences: Vec<u8> = Vec::new();
+            for (tx_in, spent_output) in self.tx_in.iter().zip(spent_outputs) {
+                tx_in.outpoint().marshalling(&mut outpoints);
+                amounts.extend_from_slice(&spent_output.value().to_le_bytes());
+                serialize_pubkey_script(spent_output, &mut pubkey_scripts);
+                sequences.extend_from_slice(&tx_in.sequence().to_le_bytes());
+            }
+            for data in [outpoints, amounts, pubkey_scripts, sequences] {
+                message.extend_from_slice(sha256::Hash::hash(&data).as_byte_array());
+            }
+        }
+        if base_type != SIG_HASH_NONE && base_type != SIG_HASH_SINGLE {
+            let mut outputs: Vec<u8> = Vec::new();
+            for tx_out in &self.tx_out {
+                tx_out.marshalling(&mut outputs);
+            }
+            message.extend_from_slice(sha256::Hash::hash(&outputs).as_byte_array());
+        }
+        // the key path spend does not have extension, only the annex is indicated
+        let spend_type: u8 = annex.is_some() as u8;
+        message.push(spend_type);
+        if anyone_can_pay {
+            let tx_in = &self.tx_in[tx_in_index];
+            tx_in.outpoint().marshalling(&mut message);
+            message.extend_from_slice(&spent_outputs[tx_in_index].value().to_le_bytes());
+            serialize_pubkey_script(&spent_outputs[tx_in_index], &mut message);
+            message.extend_from_slice(&tx_in.sequence().to_le_bytes());
+        } else {
+            message.extend_from_slice(&(tx_in_index as u32).to_le_bytes());
+        }
+        if let Some(annex) = annex {
+            let mut annex_bytes = CompactSizeUint::new(annex.len() as u128).marshalling();
+            annex_bytes.extend_from_slice(annex);
+            message.extend_from_slice(sha256::Hash::hash(&annex_bytes).as_byte_array());
+        }
+        if base_type == SIG_HASH_SINGLE {
+            let mut output: Vec<u8> = Vec::new();
+            self.tx_out.get(tx_in_index)?.marshalling(&mut output);
+            message.extend_from_slice(sha256::Hash::hash(&output).as_byte_array());
+        }
+        Some(tagged_hash("TapSighash", &message))
+    }
+
     /// Receives a reference to a vector of bytes and the amount of transactions to deserialize.
     /// Returns a vector with the transactions or an error. Updates the offset.
     pub fn unmarshalling_transactions(
@@ -152,41 +446,54 @@ impl Transaction {
         self.tx_in[0].is_coinbase()
     }
 
+    /// Returns the type of the pubkey script of the first output, which is the one that receives
+    /// the payment in the transactions made by the wallet.
+    pub fn script_type(&self) -> &'static str {
+        match self.tx_out.first() {
+            Some(tx_out) => tx_out.script_type(),
+            None => "Non standard",
+        }
+    }
+
     /// Returns a copy of the tx_out of the transaction
     pub fn get_txout(&self) -> Vec<TxOut> {
         self.tx_out.clone()
     }
 
-    /// Checks the inputs of the transaction and removes the utxos that were spent
+    /// Checks the inputs of the transaction and removes the utxos that were spent.
+    /// Returns the spent outputs, with their outpoint and the height in which they were created.
     pub fn remove_utxos(
         &self,
         utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<Vec<SpentOutput>, Box<dyn Error>> {
+        let mut spent_outputs = Vec::new();
+        let mut utxo_set = utxo_set
+            .write()
+            .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
         // If the tx spends an existing output in our utxo_set, we remove it
         for txin in &self.tx_in {
-            let txid = &txin.get_previous_output_hash();
+            let txid = txin.get_previous_output_hash();
             let output_index = txin.get_previous_output_index();
-            if utxo_set
-                .read()
-                .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-                .contains_key(txid)
-            {
-                if let Some(utxo) = utxo_set
-                    .write()
-                    .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
-                    .get_mut(txid)
-                {
-                    utxo.remove_utxo(output_index);
+            if let Some(utxo) = utxo_set.get_mut(&txid) {
+                if let Some(tx_out) = utxo.remove_utxo(output_index) {
+                    spent_outputs.push(SpentOutput::new(
+                        Outpoint::new(txid, output_index as u32),
+                        tx_out,
+                        utxo.height,
+                        utxo.is_coinbase,
+                    ));
                 }
             }
         }
-        Ok(())
+        Ok(spent_outputs)
     }
 
-    /// Generates the UtxoTuple and saves it in the utxo_set
+    /// Generates the UtxoTuple with the height of the block that contains the transaction
+    /// and saves it in the utxo_set
     pub fn load_utxos(
         &self,
         utxo_set: Arc<RwLock<HashMap<[u8; 32], UtxoTuple>>>,
+        height: usize,
     ) -> Result<(), Box<dyn Error>> {
         let hash = self.hash();
         let mut utxos_and_index = Vec::new();
@@ -194,7 +501,12 @@ impl Transaction {
             let utxo_and_index = (utxo.clone(), position);
             utxos_and_index.push(utxo_and_index);
         }
-        let utxo_tuple = UtxoTuple::new(hash, utxos_and_index);
+        let utxo_tuple = UtxoTuple::new(
+            hash,
+            utxos_and_index,
+            height,
+            self.is_coinbase_transaction(),
+        );
         utxo_set
             .write()
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
@@ -235,13 +547,17 @@ impl Transaction {
     }
     /// Generates the unsigned transaction, the parameters indicate the address
     /// where the amount (value) will be sent, the reward for adding the new transaction
-    /// to the block (fee) and the address to return the change in case it is generated (change_address).
+    /// to the block (fee), the address to return the change in case it is generated (change_address),
+    /// the lock time (block height or timestamp before which it can not be mined, 0 if it is not locked)
+    /// and the chain params of the network of the addresses.
     pub fn generate_unsigned_transaction(
         address_receiver: &str,
         change_adress: &str,
         value: i64,
         fee: i64,
         utxos_to_spend: &Vec<UtxoTuple>,
+        lock_time: u32,
+        params: &ChainParams,
     ) -> Result<Transaction, Box<dyn Error>> {
         let mut tx_ins: Vec<TxIn> = Vec::new();
         let mut input_balance: i64 = 0;
@@ -253,7 +569,11 @@ impl Transaction {
             let indexes: Vec<usize> = utxo.get_indexes_from_utxos();
             for index in indexes {
                 let previous_output: Outpoint = Outpoint::new(tx_id, index as u32);
-                let tx_in: TxIn = TxIn::incomplete_txin(previous_output);
+                let mut tx_in: TxIn = TxIn::incomplete_txin(previous_output);
+                // the lock time is only enforced if some input does not have the final sequence
+                if lock_time != 0 {
+                    tx_in.set_sequence(SEQUENCE_FINAL - 1);
+                }
                 tx_ins.push(tx_in);
             }
         }
@@ -264,22 +584,20 @@ impl Transaction {
         // Vec containing the outputs of our transaction
         let mut tx_outs: Vec<TxOut> = Vec::new();
         // Creation of the pubkey_script where we transfer the satoshis
-        let target_pk_script: Vec<u8> = generate_pubkey_script(address_receiver)?;
+        let target_pk_script: Vec<u8> = generate_pubkey_script(address_receiver, params)?;
         let target_pk_script_bytes: CompactSizeUint =
             CompactSizeUint::new(target_pk_script.len() as u128);
         // Creation of the txOut (utxo) referenced to the address that was sent to us.
         let utxo_to_send: TxOut = TxOut::new(value, target_pk_script_bytes, target_pk_script);
         tx_outs.push(utxo_to_send);
         // Creation of the pubkey_script where we will send the change of our tx.
-        let change_pk_script: Vec<u8> = generate_pubkey_script(change_adress)?;
+        let change_pk_script: Vec<u8> = generate_pubkey_script(change_adress, params)?;
         let change_pk_script_bytes: CompactSizeUint =
             CompactSizeUint::new(change_pk_script.len() as u128);
         let change_utxo: TxOut =
             TxOut::new(change_amount, change_pk_script_bytes, change_pk_script);
         tx_outs.push(change_utxo);
         let txout_count = CompactSizeUint::new(tx_outs.len() as u128);
-        // lock_time = 0 => Not locked
-        let lock_time: u32 = 0;
         let incomplete_transaction = Transaction::new(
             TRANSACTION_VERSION,
             txin_count,
@@ -293,88 +611,360 @@ impl Transaction {
 
     /// Signs the transaction.
     /// Receives the list of utxos to spend and adds the signature_script to each TxIn.
+    /// The inputs that spend P2WPKH outputs are signed as defined in BIP143, and their signature
+    /// and public key are added to the witness instead of the signature_script.
+    /// The inputs that spend P2TR outputs are signed with a key path spend (BIP341), whose Schnorr
+    /// signature is the only element of the witness.
     pub fn sign(
         &mut self,
         account: &Account,
-        utxos_to_spend: &Vec<UtxoTuple>,
+        utxos_to_spend: &[UtxoTuple],
     ) -> Result<(), Box<dyn Error>> {
+        let spent_outputs = self.spent_outputs(utxos_to_spend);
         let mut signatures = Vec::new();
+        let mut witnesses = Vec::new();
         for index in 0..self.tx_in.len() {
             // add signature to each input
-            let z = self.generate_message_to_sign(index, utxos_to_spend);
-            signatures.push(SigScript::generate_sig_script(z, account)?);
+            let previous_output = spent_outputs[index].as_ref();
+            let pubkey_script = previous_output.map(|tx_out| tx_out.get_pub_key_script());
+            if pubkey_script.is_some_and(|script| is_p2tr(script)) {
+                let signature_hash = spent_outputs
+                    .iter()
+                    .cloned()
+                    .collect::<Option<Vec<TxOut>>>()
+                    .and_then(|outputs| {
+                        self.taproot_signature_hash(index, &outputs, SIG_HASH_DEFAULT, None)
+                    })
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            io::ErrorKind::Other,
+                            "The outputs spent by the transaction were not found",
+                        )
+                    })?;
+                signatures.push(SigScript::new(vec![]));
+                witnesses.push(SigScript::generate_taproot_witness(
+                    signature_hash,
+                    account,
+                )?);
+                continue;
+            }
+            let z = self.generate_message_to_sign(index, previous_output);
+            if pubkey_script.is_some_and(|script| is_p2wpkh(script)) {
+                signatures.push(SigScript::new(vec![]));
+                witnesses.push(SigScript::generate_witness(z, account)?);
+            } else {
+                signatures.push(SigScript::generate_sig_script(z, account)?);
+                witnesses.push(vec![]);
+            }
         }
-        for (index, signature) in signatures.into_iter().enumerate() {
+        for (index, (signature, witness)) in signatures.into_iter().zip(witnesses).enumerate() {
             self.tx_in[index].add(signature);
+            self.tx_in[index].set_witness(witness);
         }
         Ok(())
     }
 
+    /// Signs the inputs of the transaction with the key of the account, which must be one of the keys of
+    /// the received multisig script. Every input must spend an output locked by the multisig script, either
+    /// directly (bare multisig), through the hash of the redeem script (P2SH) or of the witness script (P2WSH).
+    /// Returns the signature of each input, in the same order, so that they can be combined with the
+    /// signatures of the other keys.
+    pub fn generate_multisig_signatures(
+        &self,
+        account: &Account,
+        multisig_script: &[u8],
+        utxos_to_spend: &[UtxoTuple],
+    ) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        let spent_outputs = self.spent_outputs(utxos_to_spend);
+        let mut signatures = Vec::new();
+        for (index, previous_output) in spent_outputs.iter().enumerate() {
+            let z = self.generate_multisig_message_to_sign(
+                index,
+                previous_output.as_ref(),
+                multisig_script,
+            )?;
+            signatures.push(SigScript::generate_signature(z, account)?);
+        }
+        Ok(signatures)
+    }
+
+    /// Adds to each input the signatures of the keys of the multisig script, which must be in the same
+    /// order as the keys. Receives a list of signatures for each input.
+    /// The inputs that spend P2WSH outputs receive the signatures and the witness script in the witness,
+    /// the other ones in the signature_script (followed by the redeem script if they spend P2SH outputs).
+    pub fn add_multisig_signatures(
+        &mut self,
+        multisig_script: &[u8],
+        signatures: &[Vec<Vec<u8>>],
+        utxos_to_spend: &[UtxoTuple],
+    ) -> Result<(), Box<dyn Error>> {
+        if signatures.len() != self.tx_in.len() {
+            return Err(Box::new(std::io::Error::new(
+                io::ErrorKind::Other,
+                "There must be a list of signatures for each input of the transaction",
+            )));
+        }
+        let spent_outputs = self.spent_outputs(utxos_to_spend);
+        for (index, input_signatures) in signatures.iter().enumerate() {
+            let pubkey_script = match &spent_outputs[index] {
+                Some(tx_out) => tx_out.get_pub_key_script().clone(),
+                None => vec![],
+            };
+            if pubkey_script == generate_p2wsh_pubkey_script(multisig_script) {
+                self.tx_in[index].add(SigScript::new(vec![]));
+                self.tx_in[index].set_witness(SigScript::generate_multisig_witness(
+                    input_signatures,
+                    multisig_script,
+                ));
+            } else if pubkey_script == generate_p2sh_pubkey_script(&hash_160(multisig_script)) {
+                self.tx_in[index].add(SigScript::generate_multisig_sig_script(
+                    input_signatures,
+                    Some(multisig_script),
+                ));
+            } else if pubkey_script == multisig_script {
+                self.tx_in[index].add(SigScript::generate_multisig_sig_script(
+                    input_signatures,
+                    None,
+                ));
+            } else {
+                return Err(Box::new(std::io::Error::new(
+                    io::ErrorKind::Other,
+                    "The input does not spend an output of the multisig",
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Generates the hash that the keys of the multisig script sign for the input at tx_in_index.
+    /// The multisig script is the script code: the legacy message is generated for bare and P2SH outputs
+    /// and the BIP143 message for P2WSH outputs.
+    /// Returns the hash (single sha256, the signer applies the second one) or an error if the input does
+    /// not spend an output of the multisig.
+    fn generate_multisig_message_to_sign(
+        &self,
+        tx_in_index: usize,
+        previous_output: Option<&TxOut>,
+        multisig_script: &[u8],
+    ) -> Result<[u8; 32], Box<dyn Error>> {
+        let message = match previous_output {
+            Some(tx_out)
+                if *tx_out.get_pub_key_script()
+                    == generate_p2wsh_pubkey_script(multisig_script) =>
+            {
+                self.witness_v0_signature_message(
+                    tx_in_index,
+                    multisig_script,
+                    tx_out.value(),
+                    SIG_HASH_ALL,
+                )
+            }
+            Some(tx_out)
+                if *tx_out.get_pub_key_script()
+                    == generate_p2sh_pubkey_script(&hash_160(multisig_script))
+                    || tx_out.get_pub_key_script() == multisig_script =>
+            {
+                self.signature_message(tx_in_index, multisig_script, SIG_HASH_ALL)
+                    .unwrap_or_default()
+            }
+            _ => {
+                return Err(Box::new(std::io::Error::new(
+                    io::ErrorKind::Other,
+                    "The input does not spend an output of the multisig",
+                )))
+            }
+        };
+        Ok(*sha256::Hash::hash(&message).as_byte_array())
+    }
+
+    /// Returns the outputs spent by the inputs of the transaction, in the same order,
+    /// searching them in the received utxos. The outputs that are not found are None.
+    fn spent_outputs(&self, utxos_to_spend: &[UtxoTuple]) -> Vec<Option<TxOut>> {
+        self.tx_in
+            .iter()
+            .map(|tx_in| {
+                utxos_to_spend.iter().find_map(|utxos| {
+                    if utxos.hash() != tx_in.get_previous_output_hash() {
+                        return None;
+                    }
+                    utxos.get_tx_out(tx_in.get_previous_output_index()).cloned()
+                })
+            })
+            .collect()
+    }
+
     /// Generates the txin with the previous pubkey of the received tx_in.
-    /// Returns the hash.
+    /// If the previous output is P2WPKH, generates the BIP143 message with the amount it contains.
+    /// Returns the hash (single sha256, the signer applies the second one).
     fn generate_message_to_sign(
         &self,
         tx_in_index: usize,
-        utxos_to_spend: &Vec<UtxoTuple>,
+        previous_output: Option<&TxOut>,
     ) -> [u8; 32] {
-        let mut tx_copy = self.clone();
-        let mut script = Vec::new();
-        let input_to_sign = &tx_copy.tx_in[tx_in_index];
-        for utxos in utxos_to_spend {
-            let pubkey = utxos.find(
-                input_to_sign.get_previous_output_hash(),
-                input_to_sign.get_previous_output_index(),
-            );
-            script = match pubkey {
-                Some(value) => value.to_vec(),
-                None => continue,
-            };
-        }
-        tx_copy.tx_in[tx_in_index].set_signature_script(script);
-        tx_copy.hash_message(true)
+        let script = previous_output
+            .map(|tx_out| tx_out.get_pub_key_script().clone())
+            .unwrap_or_default();
+        let message = match previous_output {
+            Some(tx_out) if is_p2wpkh(&script) => {
+                let script_code = generate_p2wpkh_script_code(&script[2..]);
+                self.witness_v0_signature_message(
+                    tx_in_index,
+                    &script_code,
+                    tx_out.value(),
+                    SIG_HASH_ALL,
+                )
+            }
+            _ => self
+                .signature_message(tx_in_index, &script, SIG_HASH_ALL)
+                .unwrap_or_default(),
+        };
+        *sha256::Hash::hash(&message).as_byte_array()
     }
 
     /// Validates the transaction.
-    /// Executes the script and returns an error if it does not pass the validation.
-    pub fn validate(&self, utxos_to_spend: &Vec<UtxoTuple>) -> Result<(), Box<dyn Error>> {
-        let mut p2pkh_scripts = Vec::new();
-        for utxo in utxos_to_spend {
-            for (txout, _) in &utxo.utxo_set {
-                p2pkh_scripts.push(txout.get_pub_key_script())
-            }
+    /// Executes the scriptSig and the witness of each input and the pubkey script of the output it spends, verifying the signatures.
+    /// Returns an error if the spent output is not in the received utxos or if a script does not pass the validation.
+    pub fn validate(&self, utxos_to_spend: &[UtxoTuple]) -> Result<(), Box<dyn Error>> {
+        let spent_outputs = self.spent_outputs(utxos_to_spend);
+        if spent_outputs.iter().any(|tx_out| tx_out.is_none()) {
+            return Err(Box::new(std::io::Error::new(
+                io::ErrorKind::Other,
+                "The output spent by the transaction was not found",
+            )));
         }
+        for index in 0..self.tx_in.len() {
+            self.validate_input(index, &spent_outputs)?;
+        }
+        Ok(())
+    }
 
-        for (index, txin) in self.tx_in.iter().enumerate() {
-            //txin.
-            if !p2pkh_script::validate(p2pkh_scripts[index], txin.signature_script.get_bytes())? {
+    /// Executes the scriptSig and the witness of the input at the received index and the pubkey script of the
+    /// output it spends. Receives the outputs spent by all the inputs of the transaction, in the same order (None
+    /// if they are unknown), because the signatures of witness programs commit to them.
+    /// Returns an error with the reason if the output spent by the input is unknown or if the scripts are not valid.
+    pub fn validate_input(
+        &self,
+        tx_in_index: usize,
+        spent_outputs: &[Option<TxOut>],
+    ) -> Result<(), Box<dyn Error>> {
+        let pubkey_script = match spent_outputs.get(tx_in_index) {
+            Some(Some(tx_out)) => tx_out.get_pub_key_script().clone(),
+            _ => {
                 return Err(Box::new(std::io::Error::new(
                     io::ErrorKind::Other,
-                    "The p2pkh script is not valid",
-                )));
+                    "The output spent by the input is unknown",
+                )))
             }
-        }
+        };
+        let tx_in = &self.tx_in[tx_in_index];
+        let script_sig = Script::new(tx_in.signature_script.get_bytes().clone());
+        let checker =
+            TransactionSignatureChecker::new_with_spent_outputs(self, tx_in_index, spent_outputs);
+        Script::verify_with_witness(
+            &script_sig,
+            &Script::new(pubkey_script),
+            tx_in.witness(),
+            &checker,
+        )?;
         Ok(())
     }
 
+    /// Returns true if the transaction can be included in a block of the received height, whose time
+    /// is the received one (the median time past of the previous block, as defined in BIP113).
+    /// The lock time is not enforced if it is zero or if all the inputs have the final sequence.
+    pub fn is_final(&self, height: usize, block_time: i64) -> bool {
+        if self.lock_time == 0 {
+            return true;
+        }
+        let limit = if self.lock_time < LOCKTIME_THRESHOLD {
+            height as i64
+        } else {
+            block_time
+        };
+        if (self.lock_time as i64) < limit {
+            return true;
+        }
+        self.tx_in
+            .iter()
+            .all(|tx_in| tx_in.sequence() == SEQUENCE_FINAL)
+    }
+
+    /// Checks the relative lock times of the inputs (BIP68) for a block of the received height, being the
+    /// median_time_past the one of the previous block. Receives the height of the block that created the output
+    /// spent by each input (None if it is unknown, in which case the input is not checked) and a function that
+    /// returns the median time past of the block of the active chain at a height.
+    /// Returns true if the relative lock times are satisfied. They are only enforced from version 2.
+    pub fn sequence_locks_are_satisfied(
+        &self,
+        previous_heights: &[Option<usize>],
+        height: usize,
+        median_time_past: i64,
+        median_time_past_at: impl Fn(usize) -> i64,
+    ) -> bool {
+        if self.version < 2 {
+            return true;
+        }
+        // the last height and time in which the transaction is not valid yet
+        let mut min_height: i64 = -1;
+        let mut min_time: i64 = -1;
+        for (tx_in, previous_height) in self.tx_in.iter().zip(previous_heights) {
+            let sequence = tx_in.sequence();
+            let previous_height = match previous_height {
+                Some(previous_height) if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0 => {
+                    *previous_height
+                }
+                _ => continue,
+            };
+            let value = (sequence & SEQUENCE_LOCKTIME_MASK) as i64;
+            if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+                // the time is counted from the median time past of the block previous to the one of the output
+                let coin_time = median_time_past_at(previous_height.saturating_sub(1));
+                min_time = min_time.max(coin_time + (value << SEQUENCE_LOCKTIME_GRANULARITY) - 1);
+            } else {
+                min_height = min_height.max(previous_height as i64 + value - 1);
+            }
+        }
+        min_height < height as i64 && min_time < median_time_past
+    }
+
     /// Returns the amount of the transaction.
     pub fn amount(&self) -> i64 {
-        let mut amount = 0;
+        let mut amount: i64 = 0;
         for txout in &self.tx_out {
-            amount += txout.value();
+            amount = amount.saturating_add(txout.value());
         }
         amount
     }
+
+    /// Returns the amount of the transaction, or None if the value of an output or the sum of them
+    /// is out of the range of valid amounts (from 0 to MAX_MONEY).
+    pub fn checked_amount(&self) -> Option<i64> {
+        let mut amount: i64 = 0;
+        for txout in &self.tx_out {
+            if !(0..=MAX_MONEY).contains(&txout.value()) {
+                return None;
+            }
+            amount = amount
+                .checked_add(txout.value())
+                .filter(|amount| *amount <= MAX_MONEY)?;
+        }
+        Some(amount)
+    }
     /// Returns the height of the block in which the transaction is located.
     /// Valid only for coinbase transactions.
     pub fn get_height(&self) -> u32 {
         self.tx_in[0].get_height()
     }
 
-    /// Returns the amount sent to addresses other than the one received by parameter.
-    pub fn amount_spent_by_account(&self, address: &String) -> Result<i64, Box<dyn Error>> {
+    /// Returns the amount sent to addresses other than the one received by parameter, of the network of the chain params.
+    pub fn amount_spent_by_account(
+        &self,
+        address: &String,
+        params: &ChainParams,
+    ) -> Result<i64, Box<dyn Error>> {
         let mut amount = 0;
         for txout in &self.tx_out {
-            if !txout.is_sent_to_account(address)? {
+            if !txout.is_sent_to_account(address, params)? {
                 amount += txout.value();
             }
         }
@@ -387,11 +977,14 @@ impl Transaction {
 mod test {
     use super::Transaction;
     use crate::{
+        chain_params::TESTNET_PARAMS,
         compact_size_uint::CompactSizeUint,
         transactions::script::sig_script::SigScript,
         transactions::{outpoint::Outpoint, tx_in::TxIn, tx_out::TxOut},
+        utxo_tuple::UtxoTuple,
     };
     use bitcoin_hashes::{sha256d, Hash};
+    use std::error::Error;
 
     /// Auxiliar function that creates the txin
     fn create_txin(amount: u128) -> Vec<TxIn> {
@@ -681,4 +1274,191 @@ mod test {
         assert_eq!(transactions.len(), 2);
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_signature_hash_with_sighash_single_without_matching_output_is_one() {
+        let transaction = Transaction::new(
+            1,
+            CompactSizeUint::new(2),
+            create_txin(2),
+            CompactSizeUint::new(1),
+            create_txout(1),
+            0,
+        );
+        let mut expected_hash: [u8; 32] = [0; 32];
+        expected_hash[0] = 1;
+        assert_eq!(transaction.signature_hash(1, &[], 0x03), expected_hash);
+    }
+
+    #[test]
+    fn test_signature_hash_with_anyonecanpay_does_not_commit_to_other_inputs() {
+        let transaction = Transaction::new(
+            1,
+            CompactSizeUint::new(2),
+            create_txin(2),
+            CompactSizeUint::new(1),
+            create_txout(1),
+            0,
+        );
+        let mut modified_transaction = transaction.clone();
+        modified_transaction.tx_in[1].set_sequence(5);
+        assert_eq!(
+            transaction.signature_hash(0, &[], 0x81),
+            modified_transaction.signature_hash(0, &[], 0x81)
+        );
+        assert_ne!(
+            transaction.signature_hash(0, &[], 0x01),
+            modified_transaction.signature_hash(0, &[], 0x01)
+        );
+    }
+
+    #[test]
+    fn test_validate_transaction_that_spends_unknown_output_returns_error() {
+        let transaction = Transaction::new(
+            1,
+            CompactSizeUint::new(1),
+            create_txin(1),
+            CompactSizeUint::new(1),
+            create_txout(1),
+            0,
+        );
+        assert!(transaction.validate(&[]).is_err());
+    }
+
+    /// Auxiliar function that creates a transaction with one input with the received sequence
+    fn create_locked_transaction(version: i32, lock_time: u32, sequence: u32) -> Transaction {
+        let mut tx_in = create_txin(1);
+        tx_in[0].set_sequence(sequence);
+        Transaction::new(
+            version,
+            CompactSizeUint::new(1),
+            tx_in,
+            CompactSizeUint::new(1),
+            create_txout(1),
+            lock_time,
+        )
+    }
+
+    #[test]
+    fn test_transaction_is_final_after_its_lock_time() {
+        let transaction = create_locked_transaction(1, 100, 0xfffffffe);
+        assert!(!transaction.is_final(100, 0));
+        assert!(transaction.is_final(101, 0));
+        let transaction = create_locked_transaction(1, 1_600_000_000, 0xfffffffe);
+        assert!(!transaction.is_final(1_000_000, 1_600_000_000));
+        assert!(transaction.is_final(0, 1_600_000_001));
+    }
+
+    #[test]
+    fn test_transaction_with_final_inputs_is_final_with_any_lock_time() {
+        let transaction = create_locked_transaction(1, 100, 0xffffffff);
+        assert!(transaction.is_final(1, 0));
+    }
+
+    #[test]
+    fn test_sequence_locks_by_height_are_satisfied_after_the_amount_of_blocks() {
+        let transaction = create_locked_transaction(2, 0, 10);
+        assert!(!transaction.sequence_locks_are_satisfied(&[Some(100)], 109, 0, |_| 0));
+        assert!(transaction.sequence_locks_are_satisfied(&[Some(100)], 110, 0, |_| 0));
+        assert!(transaction.sequence_locks_are_satisfied(&[None], 101, 0, |_| 0));
+    }
+
+    #[test]
+    fn test_sequence_locks_by_time_are_satisfied_after_the_amount_of_seconds() {
+        // 2 units of 512 seconds since the median time past of the block previous to the output
+        let transaction = create_locked_transaction(2, 0, (1 << 22) | 2);
+        let median_time_past_at = |height: usize| if height == 99 { 1_000_000 } else { 0 };
+        let unlock_time = 1_000_000 + 2 * 512;
+        assert!(!transaction.sequence_locks_are_satisfied(
+            &[Some(100)],
+            200,
+            unlock_time - 1,
+            median_time_past_at
+        ));
+        assert!(transaction.sequence_locks_are_satisfied(
+            &[Some(100)],
+            200,
+            unlock_time,
+            median_time_past_at
+        ));
+    }
+
+    #[test]
+    fn test_sequence_locks_are_not_enforced_in_version_1_or_with_the_disable_flag() {
+        let transaction = create_locked_transaction(1, 0, 10);
+        assert!(transaction.sequence_locks_are_satisfied(&[Some(100)], 101, 0, |_| 0));
+        let transaction = create_locked_transaction(2, 0, (1 << 31) | 10);
+        assert!(transaction.sequence_locks_are_satisfied(&[Some(100)], 101, 0, |_| 0));
+    }
+
+    #[test]
+    fn test_unsigned_transaction_with_lock_time_has_inputs_that_are_not_final(
+    ) -> Result<(), Box<dyn Error>> {
+        let tx_out = TxOut::new(10000, CompactSizeUint::new(0), Vec::new());
+        let utxos = vec![UtxoTuple::new([7; 32], vec![(tx_out, 0)], 0, false)];
+        let transaction = Transaction::generate_unsigned_transaction(
+            "mpzx6iZ1WX8hLSeDRKdkLatXXPN1GDWVaF",
+            "mnEvYsxexfDEkCx2YLEfzhjrwKKcyAhMqV",
+            5000,
+            1000,
+            &utxos,
+            100,
+            &TESTNET_PARAMS,
+        )?;
+        assert_eq!(transaction.lock_time, 100);
+        assert_eq!(transaction.tx_in[0].sequence(), 0xfffffffe);
+        assert!(!transaction.is_final(100, 0));
+        assert!(transaction.is_final(101, 0));
+        Ok(())
+    }
+
+    /// Auxiliar function that creates a transaction whose inputs have witness data
+    fn create_segwit_transaction() -> Transaction {
+        let mut tx_in = create_txin(2);
+        tx_in[0].set_witness(vec![vec![0x30; 72], vec![0x02; 33]]);
+        Transaction::new(
+            2,
+            CompactSizeUint::new(2),
+            tx_in,
+            CompactSizeUint::new(1),
+            create_txout(1),
+            0,
+        )
+    }
+
+    #[test]
+    fn test_marshalling_segwit_transaction_and_unmarshalling_it_returns_the_same_transaction(
+    ) -> Result<(), &'static str> {
+        let transaction = create_segwit_transaction();
+        let mut bytes: Vec<u8> = Vec::new();
+        transaction.marshalling(&mut bytes);
+        assert_eq!(bytes[4..6], [0x00, 0x01]);
+        let mut offset: usize = 0;
+        let unmarshalled_transaction = Transaction::unmarshalling(&bytes, &mut offset)?;
+        assert_eq!(unmarshalled_transaction, transaction);
+        assert_eq!(offset, bytes.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_txid_of_segwit_transaction_does_not_commit_to_the_witness() {
+        let transaction = create_segwit_transaction();
+        let mut transaction_without_witness = transaction.clone();
+        transaction_without_witness.tx_in[0].set_witness(vec![]);
+        assert_eq!(transaction.hash(), transaction_without_witness.hash());
+        assert_ne!(transaction.wtxid(), transaction.hash());
+        assert_eq!(
+            transaction_without_witness.wtxid(),
+            transaction_without_witness.hash()
+        );
+    }
+
+    #[test]
+    fn test_unmarshalling_segwit_transaction_with_invalid_flag_returns_error() {
+        let mut bytes: Vec<u8> = Vec::new();
+        create_segwit_transaction().marshalling(&mut bytes);
+        bytes[5] = 0x02;
+        let mut offset: usize = 0;
+        assert!(Transaction::unmarshalling(&bytes, &mut offset).is_err());
+    }
+}
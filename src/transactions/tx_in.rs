@@ -10,6 +10,7 @@ pub struct TxIn {
     pub height: Option<Vec<u8>>,
     pub signature_script: SigScript,
     sequence: u32,
+    witness: Vec<Vec<u8>>,
 }
 
 impl TxIn {
@@ -27,6 +28,7 @@ impl TxIn {
             height,
             signature_script,
             sequence,
+            witness: Vec::new(),
         }
     }
 
@@ -87,6 +89,7 @@ impl TxIn {
             height,
             signature_script: SigScript::new(signature_script),
             sequence,
+            witness: Vec::new(),
         })
     }
 
@@ -122,6 +125,56 @@ impl TxIn {
         bytes.extend_from_slice(&sequence_bytes);
     }
 
+    /// Unmarshalls the witness of the TxIn (the stack of items that comes after the outputs
+    /// in a segwit transaction) and saves it in the TxIn. Updates the offset.
+    pub fn unmarshalling_witness(
+        &mut self,
+        bytes: &[u8],
+        offset: &mut usize,
+    ) -> Result<(), &'static str> {
+        let items_count: CompactSizeUint = CompactSizeUint::unmarshalling(bytes, offset)?;
+        let mut witness: Vec<Vec<u8>> = Vec::new();
+        for _ in 0..items_count.decoded_value() {
+            let item_size: CompactSizeUint = CompactSizeUint::unmarshalling(bytes, offset)?;
+            let item_size = item_size.decoded_value() as usize;
+            // the size is compared with the remaining bytes so that a huge size read from the network does not overflow
+            if item_size > bytes.len().saturating_sub(*offset) {
+                return Err(
+                    "The bytes received do not correspond to a witness, there are not enough bytes",
+                );
+            }
+            witness.push(bytes[*offset..(*offset + item_size)].to_vec());
+            *offset += item_size;
+        }
+        self.witness = witness;
+        Ok(())
+    }
+
+    /// Marshalls the witness of the TxIn according to BIP144.
+    /// Saves the bytes in the vector received by parameter.
+    pub fn marshalling_witness(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&CompactSizeUint::new(self.witness.len() as u128).marshalling());
+        for item in &self.witness {
+            bytes.extend_from_slice(&CompactSizeUint::new(item.len() as u128).marshalling());
+            bytes.extend_from_slice(item);
+        }
+    }
+
+    /// Returns the witness of the TxIn. It is empty if the input is not segwit.
+    pub fn witness(&self) -> &Vec<Vec<u8>> {
+        &self.witness
+    }
+
+    /// Sets the witness of the TxIn
+    pub fn set_witness(&mut self, witness: Vec<Vec<u8>>) {
+        self.witness = witness;
+    }
+
+    /// Returns true if the TxIn has witness data
+    pub fn has_witness(&self) -> bool {
+        !self.witness.is_empty()
+    }
+
     /// Returns true or false depending on whether the TxIn is from a coinbase transaction.
     pub fn is_coinbase(&self) -> bool {
         self.height.is_some()
@@ -133,21 +186,22 @@ impl TxIn {
     }
 
     /// Returns the height of the block in which the transaction is located.
-    /// If it is a coinbase transaction it returns the height of the block in which it is located.
+    /// If it is a coinbase transaction it returns the height of the block in which it is located,
+    /// which is pushed at the beginning of its script (BIP34) with OP_1 to OP_16 or with a push of up to 3 bytes.
     /// If it is not a coinbase transaction it returns 0.
     pub fn get_height(&self) -> u32 {
-        let mut bytes: Vec<u8> = vec![0];
-        let height = &self.height;
-        let mut bytes_from_height: Vec<u8>;
-        match height {
-            Some(value) => bytes_from_height = value.clone(),
+        let bytes_from_height = match &self.height {
+            Some(value) => value,
             None => return 0,
+        };
+        match bytes_from_height.first() {
+            Some(opcode @ 0x51..=0x60) => (opcode - 0x50) as u32,
+            Some(length @ 1..=3) => bytes_from_height[1..=*length as usize]
+                .iter()
+                .rev()
+                .fold(0, |height, byte| (height << 8) | *byte as u32),
+            _ => 0,
         }
-        bytes_from_height.reverse();
-        bytes.extend_from_slice(&bytes_from_height[..bytes_from_height.len() - 1]);
-        let mut aux_bytes: [u8; 4] = [0; 4];
-        aux_bytes.copy_from_slice(&bytes);
-        u32::from_be_bytes(aux_bytes)
     }
 
     /// Compares the received hash with the previous output hash of the TxIn
@@ -172,6 +226,14 @@ impl TxIn {
     pub fn get_previous_output_index(&self) -> usize {
         self.previous_output.index()
     }
+    /// Returns the sequence number of the TxIn
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+    /// Sets the sequence number of the TxIn
+    pub fn set_sequence(&mut self, sequence: u32) {
+        self.sequence = sequence;
+    }
 }
 #[cfg(test)]
 
@@ -202,6 +264,7 @@ mod test {
             height,
             signature_script: SigScript::new(signature_script),
             sequence,
+            witness: Vec::new(),
         };
         txin_to_marshalling.marshalling(&mut bytes_txin);
         bytes_txin
@@ -368,6 +431,41 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_marshalling_witness_and_unmarshalling_it_returns_the_same_witness(
+    ) -> Result<(), &'static str> {
+        let mut tx_in = TxIn::incomplete_txin(Outpoint::new([1; 32], 0));
+        let witness: Vec<Vec<u8>> = vec![vec![0x30; 71], vec![], vec![0x02; 33]];
+        tx_in.set_witness(witness.clone());
+        let mut bytes: Vec<u8> = Vec::new();
+        tx_in.marshalling_witness(&mut bytes);
+        assert_eq!(bytes.len(), 1 + (1 + 71) + 1 + (1 + 33));
+        let mut unmarshalled_tx_in = TxIn::incomplete_txin(Outpoint::new([1; 32], 0));
+        let mut offset: usize = 0;
+        unmarshalled_tx_in.unmarshalling_witness(&bytes, &mut offset)?;
+        assert_eq!(*unmarshalled_tx_in.witness(), witness);
+        assert_eq!(offset, bytes.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unmarshalling_witness_with_missing_bytes_returns_error() {
+        let bytes: Vec<u8> = vec![1, 5, 0, 0];
+        let mut tx_in = TxIn::incomplete_txin(Outpoint::new([1; 32], 0));
+        let mut offset: usize = 0;
+        assert!(tx_in.unmarshalling_witness(&bytes, &mut offset).is_err());
+    }
+
+    #[test]
+    fn test_unmarshalling_witness_with_oversized_item_length_returns_error() {
+        let mut bytes: Vec<u8> = vec![1, 0xff];
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        bytes.extend_from_slice(&[0; 10]);
+        let mut tx_in = TxIn::incomplete_txin(Outpoint::new([1; 32], 0));
+        let mut offset: usize = 0;
+        assert!(tx_in.unmarshalling_witness(&bytes, &mut offset).is_err());
+    }
+
     #[test]
     fn test_marshalling_tx_in_serializes_sequence_correctly() -> Result<(), &'static str> {
         let tx_id: [u8; 32] = [1; 32];
@@ -4,6 +4,7 @@ use gtk::glib;
 
 use crate::{
     account::Account,
+    chain_params::ChainParams,
     compact_size_uint::CompactSizeUint,
     custom_errors::NodeCustomErrors,
     gtk::ui_events::{send_event_to_ui, UIEvent},
@@ -84,10 +85,15 @@ impl TxOut {
         self.value
     }
 
-    /// Gets the address of the receiver of the TxOut
-    pub fn get_address(&self) -> Result<String, &'static str> {
-        self.pk_script.generate_address()
+    /// Gets the address of the receiver of the TxOut in the network of the chain params
+    pub fn get_address(&self, params: &ChainParams) -> Result<String, &'static str> {
+        self.pk_script.generate_address(params)
     }
+    /// Returns the type of the pub key script (P2PKH, P2SH...)
+    pub fn script_type(&self) -> &'static str {
+        self.pk_script.script_type()
+    }
+
     /// Returns the pub key script
     pub fn get_pub_key_script(&self) -> &Vec<u8> {
         self.pk_script.bytes()
@@ -118,7 +124,7 @@ impl TxOut {
                 .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?
                 .contains(&tx)
             {
-                let tx_asociate_address = match self.get_address() {
+                let tx_asociate_address = match self.get_address(account.chain_params) {
                     Ok(address) => address,
                     Err(e) => e.to_string(),
                 };
@@ -148,9 +154,14 @@ impl TxOut {
         Ok(())
     }
 
-    /// Returns true or false depending on whether the transaction was sent to the account received by parameter.
-    pub fn is_sent_to_account(&self, address: &String) -> Result<bool, &'static str> {
-        let tx_asociate_address = self.get_address()?;
+    /// Returns true or false depending on whether the transaction was sent to the account received by parameter,
+    /// whose address is of the network of the chain params.
+    pub fn is_sent_to_account(
+        &self,
+        address: &String,
+        params: &ChainParams,
+    ) -> Result<bool, &'static str> {
+        let tx_asociate_address = self.get_address(params)?;
         if tx_asociate_address.eq(address) {
             return Ok(true);
         }
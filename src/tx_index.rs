@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+use crate::{blocks::block::Block, custom_errors::NodeCustomErrors, record_log::RecordLog};
+
+const TX_INDEX_FILE_NAME: &str = "txindex.dat";
+// Records of the transaction index file, all the numbers in little endian:
+// connected:    0x01 <block hash 32> <previous block hash 32> <tx count u32> <txid 32>...
+// disconnected: 0x02 <block hash 32> <previous block hash 32> <tx count u32> <txid 32>...
+// The txids are in the order of the block, so the position of a transaction is its index in the record.
+// When the file is compacted the previous block hash is not written, and a txid that is indexed in a later
+// block is written as zeros to keep the position of the next ones.
+const CONNECTED_RECORD: u8 = 0x01;
+const DISCONNECTED_RECORD: u8 = 0x02;
+const RECORD_HEADER_SIZE: usize = 1 + 32 + 32 + 4;
+
+/// Represents the hash of the block that contains a transaction and its position in the block.
+pub type TxLocation = ([u8; 32], usize);
+/// Represents a record of the file: its kind, the hash of the block, the hash of the previous block and the txids.
+type TxIndexRecord = (u8, [u8; 32], [u8; 32], Vec<[u8; 32]>);
+
+#[derive(Debug)]
+/// Maps the txid of the transactions of the active chain to the hash of their block and their
+/// position in it, and keeps them in the txindex.dat record log with a record for each connected or
+/// disconnected block. The hash of the last connected block is kept to know from where it must be synchronized.
+pub struct TxIndex {
+    log: RecordLog,
+    locations: HashMap<[u8; 32], TxLocation>,
+    best_block_hash: [u8; 32],
+    indexed_blocks: usize,
+}
+
+impl TxIndex {
+    /// Opens the transaction index of the received directory, creating it if it does not exist, and
+    /// loads the locations of the transactions. Returns an error if the directory or the file can not be read.
+    pub fn open(directory: &str) -> Result<TxIndex, NodeCustomErrors> {
+        let mut tx_index = TxIndex {
+            log: RecordLog::open(directory, TX_INDEX_FILE_NAME)?,
+            locations: HashMap::new(),
+            best_block_hash: [0; 32],
+            indexed_blocks: 0,
+        };
+        for (kind, block_hash, previous_hash, txids) in tx_index.log.load(unmarshalling_record)? {
+            match kind {
+                CONNECTED_RECORD => tx_index.apply_connected(block_hash, &txids),
+                _ => tx_index.apply_disconnected(block_hash, previous_hash, &txids),
+            }
+        }
+        Ok(tx_index)
+    }
+
+    /// Returns the hash of the block and the position in it of the transaction with the received txid,
+    /// or None if it is not indexed.
+    pub fn get(&self, txid: &[u8; 32]) -> Option<TxLocation> {
+        self.locations.get(txid).copied()
+    }
+
+    /// Returns the hash of the last block indexed, or zeros if the index is empty.
+    pub fn best_block_hash(&self) -> [u8; 32] {
+        self.best_block_hash
+    }
+
+    /// Returns the amount of transactions indexed.
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Returns true if there are no transactions indexed.
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Adds the transactions of the block connected to the active chain to the index.
+    /// Returns an error if the record can not be written.
+    pub fn block_connected(&mut self, block: &Block) -> Result<(), NodeCustomErrors> {
+        let txids = block_txids(block);
+        self.log
+            .append(&marshalling_record(CONNECTED_RECORD, block, &txids))?;
+        self.apply_connected(block.hash(), &txids);
+        self.compact_if_needed()
+    }
+
+    /// Removes the transactions of the block disconnected from the active chain from the index.
+    /// Returns an error if the record can not be written.
+    pub fn block_disconnected(&mut self, block: &Block) -> Result<(), NodeCustomErrors> {
+        let txids = block_txids(block);
+        self.log
+            .append(&marshalling_record(DISCONNECTED_RECORD, block, &txids))?;
+        self.apply_disconnected(
+            block.hash(),
+            block.block_header.previous_block_header_hash,
+            &txids,
+        );
+        self.compact_if_needed()
+    }
+
+    /// Removes all the transactions of the index, so it can be built again.
+    /// Returns an error if the file can not be written.
+    pub fn reset(&mut self) -> Result<(), NodeCustomErrors> {
+        self.log.reset()?;
+        self.locations.clear();
+        self.best_block_hash = [0; 32];
+        self.indexed_blocks = 0;
+        Ok(())
+    }
+
+    /// Compacts the file if most of its records belong to blocks that were disconnected.
+    fn compact_if_needed(&mut self) -> Result<(), NodeCustomErrors> {
+        match self.log.needs_compaction(self.indexed_blocks + 1) {
+            true => self.compact(),
+            false => Ok(()),
+        }
+    }
+
+    /// Rewrites the file with a connected record for each block with indexed transactions, followed by the
+    /// record of the best block. Returns an error if the file can not be written.
+    fn compact(&mut self) -> Result<(), NodeCustomErrors> {
+        let mut blocks: HashMap<[u8; 32], Vec<[u8; 32]>> = HashMap::new();
+        for (txid, (block_hash, position)) in &self.locations {
+            let txids = blocks.entry(*block_hash).or_default();
+            if txids.len() <= *position {
+                txids.resize(position + 1, [0; 32]);
+            }
+            txids[*position] = *txid;
+        }
+        let best_block_txids = blocks.remove(&self.best_block_hash).unwrap_or_default();
+        let mut records: Vec<Vec<u8>> = blocks
+            .iter()
+            .map(|(block_hash, txids)| record_bytes(CONNECTED_RECORD, block_hash, &[0; 32], txids))
+            .collect();
+        records.push(record_bytes(
+            CONNECTED_RECORD,
+            &self.best_block_hash,
+            &[0; 32],
+            &best_block_txids,
+        ));
+        self.log.compact(&records)?;
+        self.indexed_blocks = records.len();
+        Ok(())
+    }
+
+    /// Inserts the location of the txids in the block and makes it the best block.
+    fn apply_connected(&mut self, block_hash: [u8; 32], txids: &[[u8; 32]]) {
+        for (position, txid) in txids.iter().enumerate() {
+            // the zeros keep the position of a transaction indexed in a later block
+            if *txid != [0; 32] {
+                self.locations.insert(*txid, (block_hash, position));
+            }
+        }
+        self.best_block_hash = block_hash;
+        self.indexed_blocks += 1;
+    }
+
+    /// Removes the txids that are located in the block and makes its previous block the best block.
+    fn apply_disconnected(
+        &mut self,
+        block_hash: [u8; 32],
+        previous_hash: [u8; 32],
+        txids: &[[u8; 32]],
+    ) {
+        for txid in txids {
+            if let Some((hash, _)) = self.locations.get(txid) {
+                if *hash == block_hash {
+                    self.locations.remove(txid);
+                }
+            }
+        }
+        self.best_block_hash = previous_hash;
+        self.indexed_blocks = self.indexed_blocks.saturating_sub(1);
+    }
+}
+
+/// Returns the bytes of the record with the txids of the block.
+fn marshalling_record(kind: u8, block: &Block, txids: &[[u8; 32]]) -> Vec<u8> {
+    record_bytes(
+        kind,
+        &block.hash(),
+        &block.block_header.previous_block_header_hash,
+        txids,
+    )
+}
+
+/// Returns the bytes of a record with the hash of the block, the hash of the previous block and the txids.
+fn record_bytes(
+    kind: u8,
+    block_hash: &[u8; 32],
+    previous_hash: &[u8; 32],
+    txids: &[[u8; 32]],
+) -> Vec<u8> {
+    let mut record: Vec<u8> = Vec::with_capacity(RECORD_HEADER_SIZE + txids.len() * 32);
+    record.push(kind);
+    record.extend_from_slice(block_hash);
+    record.extend_from_slice(previous_hash);
+    record.extend_from_slice(&(txids.len() as u32).to_le_bytes());
+    for txid in txids {
+        record.extend_from_slice(txid);
+    }
+    record
+}
+
+/// Returns the txids of the transactions of the block, in order.
+fn block_txids(block: &Block) -> Vec<[u8; 32]> {
+    block.txn.iter().map(|tx| tx.hash()).collect()
+}
+
+/// Reads the record that starts in the offset and moves the offset to the next one. Returns None if
+/// the record was not completely written or has an unknown kind.
+fn unmarshalling_record(data: &[u8], offset: &mut usize) -> Option<TxIndexRecord> {
+    let header = data.get(*offset..*offset + RECORD_HEADER_SIZE)?;
+    let kind = header[0];
+    if kind != CONNECTED_RECORD && kind != DISCONNECTED_RECORD {
+        return None;
+    }
+    let mut block_hash = [0u8; 32];
+    block_hash.copy_from_slice(&header[1..33]);
+    let mut previous_hash = [0u8; 32];
+    previous_hash.copy_from_slice(&header[33..65]);
+    let mut count_bytes = [0u8; 4];
+    count_bytes.copy_from_slice(&header[65..69]);
+    let count = u32::from_le_bytes(count_bytes) as usize;
+    let txids_start = *offset + RECORD_HEADER_SIZE;
+    let txids_bytes = data.get(txids_start..txids_start + count * 32)?;
+    let txids = txids_bytes
+        .chunks_exact(32)
+        .map(|chunk| {
+            let mut txid = [0u8; 32];
+            txid.copy_from_slice(chunk);
+            txid
+        })
+        .collect();
+    *offset = txids_start + count * 32;
+    Some((kind, block_hash, previous_hash, txids))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{TxIndex, TX_INDEX_FILE_NAME};
+    use crate::{
+        blocks::{block::Block, block_header::BlockHeader},
+        compact_size_uint::CompactSizeUint,
+        custom_errors::NodeCustomErrors,
+        temp_directory::TempDirectory,
+        transactions::{
+            outpoint::Outpoint, script::sig_script::SigScript, transaction::Transaction,
+            tx_in::TxIn, tx_out::TxOut,
+        },
+    };
+
+    /// Creates a transaction with an output with the received value
+    fn create_transaction(value: i64) -> Transaction {
+        let tx_in = TxIn::new(
+            Outpoint::new([1; 32], 0),
+            CompactSizeUint::new(0),
+            None,
+            SigScript::new(vec![]),
+            0xffffffff,
+        );
+        let tx_out = TxOut::new(value, CompactSizeUint::new(1), vec![0x51]);
+        Transaction::new(
+            1,
+            CompactSizeUint::new(1),
+            vec![tx_in],
+            CompactSizeUint::new(1),
+            vec![tx_out],
+            0,
+        )
+    }
+
+    /// Creates a block with the received previous hash and transactions
+    fn create_block(previous_hash: [u8; 32], txn: Vec<Transaction>) -> Block {
+        let block_header = BlockHeader::new(1, previous_hash, [2; 32], 0, 0x1d00ffff, 0);
+        Block::new(block_header, CompactSizeUint::new(txn.len() as u128), txn)
+    }
+
+    #[test]
+    fn test_indexed_transactions_are_found_when_the_index_is_opened_again(
+    ) -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("tx_index_reopen");
+        let directory_str = directory.path_string();
+        let first_block = create_block([0; 32], vec![create_transaction(1)]);
+        let second_block = create_block(
+            first_block.hash(),
+            vec![create_transaction(2), create_transaction(3)],
+        );
+        {
+            let mut tx_index = TxIndex::open(&directory_str)?;
+            tx_index.block_connected(&first_block)?;
+            tx_index.block_connected(&second_block)?;
+        }
+        let tx_index = TxIndex::open(&directory_str)?;
+        assert_eq!(tx_index.len(), 3);
+        assert_eq!(tx_index.best_block_hash(), second_block.hash());
+        assert_eq!(
+            tx_index.get(&second_block.txn[1].hash()),
+            Some((second_block.hash(), 1))
+        );
+        assert_eq!(
+            tx_index.get(&first_block.txn[0].hash()),
+            Some((first_block.hash(), 0))
+        );
+        assert_eq!(tx_index.get(&[7; 32]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transactions_of_disconnected_block_are_removed() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("tx_index_disconnect");
+        let directory_str = directory.path_string();
+        let first_block = create_block([0; 32], vec![create_transaction(1)]);
+        let second_block = create_block(first_block.hash(), vec![create_transaction(2)]);
+        {
+            let mut tx_index = TxIndex::open(&directory_str)?;
+            tx_index.block_connected(&first_block)?;
+            tx_index.block_connected(&second_block)?;
+            tx_index.block_disconnected(&second_block)?;
+            assert_eq!(tx_index.get(&second_block.txn[0].hash()), None);
+        }
+        let tx_index = TxIndex::open(&directory_str)?;
+        assert_eq!(tx_index.len(), 1);
+        assert_eq!(tx_index.best_block_hash(), first_block.hash());
+        assert_eq!(tx_index.get(&second_block.txn[0].hash()), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_that_was_not_completely_written_is_discarded() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("tx_index_torn");
+        let directory_str = directory.path_string();
+        let first_block = create_block([0; 32], vec![create_transaction(1)]);
+        let second_block = create_block(first_block.hash(), vec![create_transaction(2)]);
+        {
+            let mut tx_index = TxIndex::open(&directory_str)?;
+            tx_index.block_connected(&first_block)?;
+            tx_index.block_connected(&second_block)?;
+        }
+        // the node stopped while writing the record of the second block
+        let path = directory.path().join(TX_INDEX_FILE_NAME);
+        let data = fs::read(&path).unwrap_or_default();
+        let _ = fs::write(&path, &data[..data.len() - 10]);
+
+        let mut tx_index = TxIndex::open(&directory_str)?;
+        assert_eq!(tx_index.len(), 1);
+        assert_eq!(tx_index.best_block_hash(), first_block.hash());
+        tx_index.block_connected(&second_block)?;
+        let tx_index = TxIndex::open(&directory_str)?;
+        assert_eq!(
+            tx_index.get(&second_block.txn[0].hash()),
+            Some((second_block.hash(), 0))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compacted_index_keeps_the_transactions_of_the_connected_blocks(
+    ) -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("tx_index_compact");
+        let directory_str = directory.path_string();
+        let first_block = create_block([0; 32], vec![create_transaction(1)]);
+        let second_block = create_block(
+            first_block.hash(),
+            vec![create_transaction(2), create_transaction(3)],
+        );
+        let stale_block = create_block(second_block.hash(), vec![create_transaction(4)]);
+        // the third block has a transaction with the txid of the first one of the second block
+        let third_block = create_block(
+            second_block.hash(),
+            vec![create_transaction(5), create_transaction(2)],
+        );
+        {
+            let mut tx_index = TxIndex::open(&directory_str)?;
+            tx_index.block_connected(&first_block)?;
+            tx_index.block_connected(&second_block)?;
+            tx_index.block_connected(&stale_block)?;
+            tx_index.block_disconnected(&stale_block)?;
+            tx_index.block_connected(&third_block)?;
+            tx_index.compact()?;
+        }
+        let tx_index = TxIndex::open(&directory_str)?;
+        assert_eq!(tx_index.len(), 4);
+        assert_eq!(tx_index.best_block_hash(), third_block.hash());
+        assert_eq!(tx_index.get(&stale_block.txn[0].hash()), None);
+        assert_eq!(
+            tx_index.get(&second_block.txn[0].hash()),
+            Some((third_block.hash(), 1))
+        );
+        assert_eq!(
+            tx_index.get(&second_block.txn[1].hash()),
+            Some((second_block.hash(), 1))
+        );
+        assert_eq!(tx_index.get(&[0; 32]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_index_is_empty() -> Result<(), NodeCustomErrors> {
+        let directory = TempDirectory::new("tx_index_reset");
+        let directory_str = directory.path_string();
+        let block = create_block([0; 32], vec![create_transaction(1)]);
+        let mut tx_index = TxIndex::open(&directory_str)?;
+        tx_index.block_connected(&block)?;
+        tx_index.reset()?;
+        assert!(tx_index.is_empty());
+        assert_eq!(tx_index.best_block_hash(), [0; 32]);
+        let tx_index = TxIndex::open(&directory_str)?;
+        assert!(tx_index.is_empty());
+        Ok(())
+    }
+}
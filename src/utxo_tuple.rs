@@ -1,27 +1,43 @@
+use crate::chain_params::ChainParams;
 use crate::transactions::tx_out::TxOut;
 
 #[derive(Debug, Clone)]
 /// Stores the hash of the transaction and an array with the unspent TxOut, referring to that transaction
 /// The tuple stores the TxOut and the index in which it is located in the tx
+/// The height is the one of the block that contains the transaction
+/// and is_coinbase indicates if the transaction is a coinbase
 pub struct UtxoTuple {
     pub hash: [u8; 32],
     pub utxo_set: Vec<(TxOut, usize)>,
+    pub height: usize,
+    pub is_coinbase: bool,
 }
 
 impl UtxoTuple {
 
     /// Creates a new UtxoTuple
-    pub fn new(hash: [u8; 32], utxo_set: Vec<(TxOut, usize)>) -> Self {
-        UtxoTuple { hash, utxo_set }
+    pub fn new(
+        hash: [u8; 32],
+        utxo_set: Vec<(TxOut, usize)>,
+        height: usize,
+        is_coinbase: bool,
+    ) -> Self {
+        UtxoTuple {
+            hash,
+            utxo_set,
+            height,
+            is_coinbase,
+        }
     }
 
-    /// Returns the utxoTuple with the TxOut that reference the received address
+    /// Returns the utxoTuple with the TxOut that reference the received address of the network of the chain params
+    /// Witness v0 outputs reference the bech32 address of their witness program.
     /// If it does not find any, returns None
-    pub fn referenced_utxos(&self, address: &str) -> Option<UtxoTuple> {
+    pub fn referenced_utxos(&self, address: &str, params: &ChainParams) -> Option<UtxoTuple> {
         let hash = self.hash;
         let mut utxo_set: Vec<(TxOut, usize)> = Vec::new();
         for utxo in &self.utxo_set {
-            match utxo.0.get_address() {
+            match utxo.0.get_address(params) {
                 Ok(value) => {
                     if *address == value {
                         utxo_set.push(utxo.clone());
@@ -35,7 +51,12 @@ impl UtxoTuple {
         if utxo_set.is_empty() {
             return None;
         }
-        Some(UtxoTuple { hash, utxo_set })
+        Some(UtxoTuple {
+            hash,
+            utxo_set,
+            height: self.height,
+            is_coinbase: self.is_coinbase,
+        })
     }
 
     /// Returns the amount in satoshis of the TxOut of the Utxo
@@ -77,7 +98,7 @@ impl UtxoTuple {
             }
             position += 1;
         }
-        Self::new(self.hash, utxos_to_spend)
+        Self::new(self.hash, utxos_to_spend, self.height, self.is_coinbase)
     }
 
     /// Search the utxo that corresponds to the received hash and index.
@@ -94,13 +115,28 @@ impl UtxoTuple {
         None
     }
 
-    /// Removes the output that contains the received index.
-    pub fn remove_utxo(&mut self, output_index: usize) {
-        for index in 0..self.utxo_set.len() {
-            if self.utxo_set[index].1 == output_index {
-                self.utxo_set.remove(index);
-                break;
-            }
+    /// Returns the unspent TxOut with the received index or None if it is not in the UtxoTuple.
+    pub fn get_tx_out(&self, output_index: usize) -> Option<&TxOut> {
+        self.utxo_set
+            .iter()
+            .find(|utxo| utxo.1 == output_index)
+            .map(|utxo| &utxo.0)
+    }
+
+    /// Adds the output with the received index, if it is not already in the UtxoTuple.
+    pub fn add_utxo(&mut self, tx_out: TxOut, output_index: usize) {
+        if self.utxo_set.iter().all(|utxo| utxo.1 != output_index) {
+            self.utxo_set.push((tx_out, output_index));
         }
     }
+
+    /// Removes the output that contains the received index.
+    /// Returns the removed TxOut or None if it is not in the UtxoTuple.
+    pub fn remove_utxo(&mut self, output_index: usize) -> Option<TxOut> {
+        let position = self
+            .utxo_set
+            .iter()
+            .position(|utxo| utxo.1 == output_index)?;
+        Some(self.utxo_set.remove(position).0)
+    }
 }
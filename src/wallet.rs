@@ -8,6 +8,7 @@ use gtk::glib;
 
 use crate::{
     account::Account,
+    blockchain::TransactionSearchResult,
     blocks::{
         block::Block,
         block_header::BlockHeader,
@@ -15,6 +16,8 @@ use crate::{
     },
     custom_errors::NodeCustomErrors,
     gtk::ui_events::{send_event_to_ui, UIEvent},
+    logwriter::log_writer::LogSender,
+    multisig_account::MultisigAccount,
     node::Node,
     transactions::transaction::Transaction,
 };
@@ -40,14 +43,15 @@ impl Wallet {
     }
 
     /// Makes a transaction with the current account of the wallet and broadcasts it.
-    /// Receives the address of the receiver, amount and fee.
-    /// Returns an error if something fails.
+    /// Receives the address of the receiver, amount, fee and lock time (0 if it is not locked).
+    /// Returns an error if something fails or if the lock time does not allow to mine the transaction in the next block.
     pub fn make_transaction(
         &self,
         ui_sender: &Option<glib::Sender<UIEvent>>,
         address_receiver: &str,
         amount: i64,
         fee: i64,
+        lock_time: u32,
     ) -> Result<(), Box<dyn Error>> {
         let account_index = match self.current_account_index {
             Some(index) => index,
@@ -59,11 +63,17 @@ impl Wallet {
             }
         };
         validate_transaction_data(amount, fee)?;
+        if !self.node.blockchain.lock_time_is_reached(lock_time) {
+            return Err(Box::new(std::io::Error::new(
+                io::ErrorKind::Other,
+                "Error trying to make transaction. The lock time has not been reached yet",
+            )));
+        }
         let transaction: Transaction = self
             .accounts
             .write()
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?[account_index]
-            .make_transaction(address_receiver, amount, fee)?;
+            .make_transaction(address_receiver, amount, fee, lock_time)?;
         self.node.broadcast_tx(transaction.hash())?;
         send_event_to_ui(ui_sender, UIEvent::NewPendingTx());
         Ok(())
@@ -78,10 +88,11 @@ impl Wallet {
         wif_private_key: String,
         address: String,
     ) -> Result<(), NodeCustomErrors> {
-        let mut account = Account::new(wif_private_key, address).map_err(|err| {
-            send_event_to_ui(ui_sender, UIEvent::AddAccountError(err.to_string()));
-            NodeCustomErrors::UnmarshallingError(err.to_string())
-        })?;
+        let mut account = Account::new(wif_private_key, address, self.node.blockchain.chain_params)
+            .map_err(|err| {
+                send_event_to_ui(ui_sender, UIEvent::AddAccountError(err.to_string()));
+                NodeCustomErrors::UnmarshallingError(err.to_string())
+            })?;
         self.load_data(&mut account)
             .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
         self.accounts
@@ -92,11 +103,26 @@ impl Wallet {
         Ok(())
     }
 
-    /// Loads the respective utxos associated with the account
+    /// Loads the respective utxos associated with the account and its confirmed transactions
     fn load_data(&self, account: &mut Account) -> Result<(), Box<dyn Error>> {
         let address = account.get_address().clone();
         let utxos_to_account = self.node.utxos_referenced_to_account(&address)?;
         account.load_utxos(utxos_to_account);
+        let transactions_of_account = self.node.transactions_referenced_to_account(&address)?;
+        account.load_confirmed_transactions(transactions_of_account)?;
+        Ok(())
+    }
+
+    /// Loads the utxos of the multisig account from the utxo set of the node, so that it can be spent
+    /// with the signatures of its keys.
+    pub fn load_multisig_account(
+        &self,
+        multisig_account: &mut MultisigAccount,
+    ) -> Result<(), Box<dyn Error>> {
+        let utxos_to_account = self
+            .node
+            .utxos_referenced_to_account(multisig_account.get_address())?;
+        multisig_account.load_utxos(utxos_to_account);
         Ok(())
     }
 
@@ -249,6 +275,42 @@ impl Wallet {
     pub fn search_header(&self, hash: [u8; 32]) -> Option<(BlockHeader, usize)> {
         self.node.search_header(hash)
     }
+
+    /// Search a transaction in the blockchain
+    /// Receives the hash of the transaction in hex format
+    /// Returns the transaction with the header and height of its block and its confirmations if found, None otherwise
+    pub fn search_transaction(&self, tx_hash_hex: String) -> TransactionSearchResult {
+        let mut tx_hash: [u8; 32] = string_to_bytes(&tx_hash_hex)
+            .map_err(|err| NodeCustomErrors::OtherError(err.to_string()))?;
+        tx_hash.reverse();
+        self.node.search_transaction(tx_hash)
+    }
+
+    /// Request the node to mine the amount of blocks received paying to the current account (only in regtest)
+    /// Returns the hashes of the blocks mined
+    pub fn generate_blocks(
+        &self,
+        log_sender: &LogSender,
+        ui_sender: &Option<glib::Sender<UIEvent>>,
+        amount_of_blocks: usize,
+    ) -> Result<Vec<[u8; 32]>, NodeCustomErrors> {
+        let address = match self.get_current_account() {
+            Some(account) => account.address,
+            None => {
+                return Err(NodeCustomErrors::OtherError(
+                    "Error trying to mine blocks. No account selected".to_string(),
+                ))
+            }
+        };
+        self.node
+            .generate_to_address(log_sender, ui_sender, &address, amount_of_blocks)
+    }
+
+    /// Request the node to build again the transaction index
+    /// Returns the amount of blocks indexed
+    pub fn reindex_transactions(&self) -> Result<usize, NodeCustomErrors> {
+        self.node.reindex_transactions()
+    }
 }
 
 /// Validates that the amount and fee are greater than zero
@@ -11,6 +11,7 @@ type WifPrivateKey = String;
 type AccountIndex = usize;
 type Amount = i64;
 type Fee = i64;
+type LockTime = u32;
 type BlockHash = [u8; 32];
 type BlockHashString = String;
 type TransactionHash = String;
@@ -19,7 +20,7 @@ type TransactionHash = String;
 pub enum WalletEvent {
     Start,
     AddAccountRequest(WifPrivateKey, Address),
-    MakeTransaction(Address, Amount, Fee),
+    MakeTransaction(Address, Amount, Fee, LockTime),
     PoiOfTransactionRequest(BlockHashString, TransactionHash),
     Finish,
     ChangeAccount(AccountIndex),
@@ -27,6 +28,7 @@ pub enum WalletEvent {
     GetTransactionsRequest,
     SearchBlock(BlockHash),
     SearchHeader(BlockHash),
+    SearchTransaction(TransactionHash),
 }
 
 /// Received a sender that sends events to the UI, a receiver that receives events from the UI and a wallet
@@ -48,8 +50,8 @@ pub fn handle_ui_request(
             WalletEvent::GetAccountRequest => {
                 handle_get_account(ui_sender, wallet);
             }
-            WalletEvent::MakeTransaction(address, amount, fee) => {
-                handle_make_transaction(ui_sender, wallet, address, amount, fee)
+            WalletEvent::MakeTransaction(address, amount, fee, lock_time) => {
+                handle_make_transaction(ui_sender, wallet, address, amount, fee, lock_time)
             }
             WalletEvent::PoiOfTransactionRequest(block_hash, transaction_hash) => {
                 handle_poi(ui_sender, wallet, block_hash, transaction_hash);
@@ -60,6 +62,9 @@ pub fn handle_ui_request(
             WalletEvent::SearchHeader(block_hash) => {
                 handle_search_header(ui_sender, wallet, block_hash);
             }
+            WalletEvent::SearchTransaction(transaction_hash) => {
+                handle_search_transaction(ui_sender, wallet, transaction_hash);
+            }
             WalletEvent::GetTransactionsRequest => {
                 handle_get_transactions(ui_sender, wallet);
             }
@@ -109,7 +114,7 @@ fn handle_get_account(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wa
     }
 }
 
-/// Receives a sender that sends events to the UI, a wallet, an address, an amount and a fee
+/// Receives a sender that sends events to the UI, a wallet, an address, an amount, a fee and a lock time
 /// It is responsible for calling the method of the wallet that makes a transaction. In case of error when making the transaction
 /// sends an event to the UI to show the error. In case the transaction is made correctly, it sends an event
 /// to the UI to show that the transaction was made correctly
@@ -119,8 +124,9 @@ fn handle_make_transaction(
     address: String,
     amount: i64,
     fee: i64,
+    lock_time: u32,
 ) {
-    if let Err(err) = wallet.make_transaction(ui_sender, &address, amount, fee) {
+    if let Err(err) = wallet.make_transaction(ui_sender, &address, amount, fee, lock_time) {
         send_event_to_ui(ui_sender, UIEvent::MakeTransactionStatus(err.to_string()));
     } else {
         send_event_to_ui(
@@ -189,6 +195,25 @@ fn handle_search_header(
     }
 }
 
+/// Receives a sender that sends events to the UI, a wallet and a transaction hash
+/// It is responsible for calling the method of the wallet that searches for a transaction by its hash. If the transaction
+/// exists sends an event to the UI to show the transaction and its block. If the transaction does not exist, it sends
+/// an event to the UI to show that the transaction was not found, and in case of error it sends the error
+fn handle_search_transaction(
+    ui_sender: &Option<glib::Sender<UIEvent>>,
+    wallet: &mut Wallet,
+    transaction_hash: String,
+) {
+    match wallet.search_transaction(transaction_hash) {
+        Ok(Some((transaction, header, height, confirmations))) => send_event_to_ui(
+            ui_sender,
+            UIEvent::TransactionFound(transaction, header, height, confirmations),
+        ),
+        Ok(None) => send_event_to_ui(ui_sender, UIEvent::NotFound),
+        Err(err) => send_event_to_ui(ui_sender, UIEvent::SearchTransactionError(err.to_string())),
+    }
+}
+
 /// Request the wallet to send the transactions of the current account to the UI
 pub fn handle_get_transactions(ui_sender: &Option<glib::Sender<UIEvent>>, wallet: &mut Wallet) {
     if let Some(transactions) = wallet.get_transactions() {
@@ -134,7 +134,7 @@ fn test_block_with_2_transactions_has_expected_utxo_list_length() -> Result<(),
     let pointer_to_utxo_set: UtxoSetPointer = Arc::new(RwLock::new(HashMap::new()));
 
     block
-        .give_me_utxos(pointer_to_utxo_set.clone())
+        .give_me_utxos(pointer_to_utxo_set.clone(), 0)
         .map_err(|err| NodeCustomErrors::LockError(err.to_string()))?;
 
     let mut amount_utxos = 0;
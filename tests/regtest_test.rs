@@ -0,0 +1,369 @@
+use std::{
+    error::Error,
+    fs,
+    net::{IpAddr, SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant},
+};
+
+use bitcoin::{
+    address_decoder::{decode_wif_private_key, generate_address},
+    blockchain_download::initial_block_download,
+    chain_params::REGTEST_PARAMS,
+    config::Config,
+    handshake::handshake_with_nodes,
+    logwriter::log_writer::{set_up_loggers, shutdown_loggers, LogSender, LogSenderHandles},
+    network::{get_active_nodes_from_dns_seed, open_address_manager},
+    node::Node,
+    server::NodeServer,
+    wallet::Wallet,
+};
+
+const MINER_PRIVATE_KEY: &str = "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR";
+const RECEIVER_PRIVATE_KEY: &str = "cQojsQ5fSonENC5EnrzzTAWSGX8PB4TBh6GunBxcCdGMJJiLULwZ";
+const OTHER_PRIVATE_KEY: &str = "cNzfV2EAoKW8Xxi72kDyKRg3CoLtF7AkiB8HoYghfhhvMQV9EwY8";
+const BLOCK_SUBSIDY: i64 = 5_000_000_000;
+const COINBASE_MATURITY: usize = 100;
+const WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Node of a regtest network that runs in the same process, with its wallet, its server and the folder
+/// where it stores its data.
+struct RegtestNode {
+    node: Node,
+    wallet: Wallet,
+    server: NodeServer,
+    log_sender: LogSender,
+    log_sender_handles: LogSenderHandles,
+    directory: PathBuf,
+    port: u16,
+}
+
+impl RegtestNode {
+    /// Starts a regtest node that listens in a free port of 127.0.0.1 and connects to the nodes of the ports received
+    fn start(name: &str, peer_ports: &[u16]) -> Result<Self, Box<dyn Error>> {
+        let custom_nodes: Vec<String> = peer_ports
+            .iter()
+            .map(|peer_port| format!("127.0.0.1:{}", peer_port))
+            .collect();
+        Self::start_listening_in(name, "127.0.0.1".parse()?, &custom_nodes)
+    }
+
+    /// Starts a regtest node that listens in a free port of the ip received and connects to the nodes received.
+    /// The listener is bound before the node starts and given to its server, so no other test can take its port.
+    fn start_listening_in(
+        name: &str,
+        listen_ip: IpAddr,
+        custom_nodes: &[String],
+    ) -> Result<Self, Box<dyn Error>> {
+        let listener = TcpListener::bind(SocketAddr::new(listen_ip, 0))?;
+        let port = listener.local_addr()?.port();
+        let directory =
+            std::env::temp_dir().join(format!("regtest_test_{}_{}_{}", name, process::id(), port));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory)?;
+        let config_path = directory.join("nodo.conf");
+        fs::write(
+            &config_path,
+            regtest_config(&directory, port, listen_ip, custom_nodes),
+        )?;
+        let config = Config::from(&[String::new(), config_path.to_string_lossy().to_string()])?;
+        let (log_sender, log_sender_handles) = set_up_loggers(&config)?;
+        let address_manager = open_address_manager(&config, &log_sender);
+        let node_ips = get_active_nodes_from_dns_seed(&config, &log_sender, &address_manager)?;
+        let nodes = handshake_with_nodes(&config, &log_sender, node_ips, &address_manager)?;
+        let blockchain = initial_block_download(&config, &log_sender, &None, nodes.clone())?;
+        let mut node = Node::new(&log_sender, &None, nodes, blockchain, address_manager)?;
+        let wallet = Wallet::new(node.clone())?;
+        let server =
+            NodeServer::with_listeners(&config, &log_sender, &None, &mut node, vec![listener])?;
+        Ok(RegtestNode {
+            node,
+            wallet,
+            server,
+            log_sender,
+            log_sender_handles,
+            directory,
+            port,
+        })
+    }
+
+    /// Returns the hash of the tip of the active chain of the node
+    fn tip_hash(&self) -> Result<[u8; 32], Box<dyn Error>> {
+        let headers = self
+            .node
+            .blockchain
+            .headers
+            .read()
+            .map_err(|err| err.to_string())?;
+        Ok(headers.last().ok_or("There are no headers")?.hash())
+    }
+
+    /// Mines the amount of blocks received over the tip of the node, paying to the address received. Returns their hashes.
+    fn mine_blocks(&self, address: &str, amount: usize) -> Result<Vec<[u8; 32]>, Box<dyn Error>> {
+        Ok(self
+            .node
+            .generate_to_address(&self.log_sender, &None, address, amount)?)
+    }
+
+    /// Shuts down the node, its server and its loggers and removes its folder
+    fn shutdown(self) -> Result<(), Box<dyn Error>> {
+        self.node.shutdown_node()?;
+        self.server.shutdown_server()?;
+        shutdown_loggers(self.log_sender, self.log_sender_handles)?;
+        fs::remove_dir_all(&self.directory)?;
+        Ok(())
+    }
+}
+
+/// Returns the content of the configuration file of a regtest node that stores its data in the directory received
+fn regtest_config(
+    directory: &Path,
+    port: u16,
+    listen_ip: IpAddr,
+    custom_nodes: &[String],
+) -> String {
+    let directory = directory.to_string_lossy();
+    format!(
+        "NUMBER_OF_NODES=8
+NETWORK=regtest
+DNS_SEED=
+CONNECT_TO_DNS_NODES=false
+CUSTOM_NODES_IPS={}
+NET_PORT={}
+PROTOCOL_VERSION=70015
+USER_AGENT=/Satoshi:23.0.0/
+N_THREADS=8
+CONNECT_TIMEOUT=5
+MAX_CONNECTIONS=15
+ERROR_LOG_PATH=error.txt
+INFO_LOG_PATH=info.txt
+MESSAGE_LOG_PATH=message.txt
+BLOCKS_DOWNLOAD_PER_NODE=16
+DATE_FIRST_BLOCK_TO_DOWNLOAD=2011-01-01 00:00:00
+DATE_FORMAT=%Y-%m-%d %H:%M:%S
+AMOUNT_OF_HEADERS_TO_STORE_IN_DISK=0
+READ_HEADERS_FROM_DISK=true
+DOWNLOAD_FULL_BLOCKCHAIN_FROM_SINGLE_NODE=true
+HEIGHT_FIRST_BLOCK_TO_DOWNLOAD=1
+HEADERS_FILE={directory}/headers.dat
+LOGS_FOLDER={directory}/logs
+BLOCKS_FOLDER={directory}/blocks
+CHAINSTATE_FOLDER={directory}/chainstate
+PRUNE_TARGET_MB=0
+TX_INDEX=false
+ADDRESS_INDEX=false
+BLOCK_FILTER_INDEX=false
+INDEXES_FOLDER={directory}/indexes
+PEERS_FILE={directory}/peers.dat
+LISTEN_IPS={listen_ip}",
+        custom_nodes.join(","),
+        port,
+    )
+}
+
+/// Returns the wif private key received with its P2PKH address in regtest
+fn regtest_key(wif_private_key: &str) -> Result<(String, String), Box<dyn Error>> {
+    let address = generate_address(&decode_wif_private_key(wif_private_key)?, &REGTEST_PARAMS)?;
+    Ok((wif_private_key.to_string(), address))
+}
+
+/// Waits until the condition is met or the timeout expires. Returns whether the condition was met.
+fn wait_until(
+    mut condition: impl FnMut() -> Result<bool, Box<dyn Error>>,
+) -> Result<bool, Box<dyn Error>> {
+    let start = Instant::now();
+    while start.elapsed() < WAIT_TIMEOUT {
+        if condition()? {
+            return Ok(true);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    Ok(false)
+}
+
+#[test]
+fn test_blocks_mined_in_a_node_are_relayed_through_the_network() -> Result<(), Box<dyn Error>> {
+    let (_, address) = regtest_key(MINER_PRIVATE_KEY)?;
+    let node_a = RegtestNode::start("relay_a", &[])?;
+    let node_b = RegtestNode::start("relay_b", &[node_a.port])?;
+    // node c is only connected to node b, so the blocks of node a reach it if node b relays them
+    let node_c = RegtestNode::start("relay_c", &[node_b.port])?;
+
+    node_a.mine_blocks(&address, 3)?;
+    let tip_hash = node_a.tip_hash()?;
+
+    assert!(wait_until(|| Ok(node_b.tip_hash()? == tip_hash))?);
+    assert!(wait_until(|| Ok(node_c.tip_hash()? == tip_hash))?);
+    // a block mined in the last node travels through the network in the other direction
+    let hashes = node_c.mine_blocks(&address, 1)?;
+    assert!(wait_until(|| Ok(node_a.tip_hash()? == hashes[0]))?);
+    assert_eq!(
+        node_a
+            .node
+            .blockchain
+            .headers
+            .read()
+            .map_err(|err| err.to_string())?
+            .len(),
+        5
+    );
+    node_c.shutdown()?;
+    node_b.shutdown()?;
+    node_a.shutdown()?;
+    Ok(())
+}
+
+#[test]
+fn test_node_started_later_downloads_the_regtest_chain() -> Result<(), Box<dyn Error>> {
+    let (_, address) = regtest_key(MINER_PRIVATE_KEY)?;
+    let node_a = RegtestNode::start("download_a", &[])?;
+    let hashes = node_a.mine_blocks(&address, 5)?;
+
+    let node_b = RegtestNode::start("download_b", &[node_a.port])?;
+
+    assert_eq!(node_b.tip_hash()?, node_a.tip_hash()?);
+    for hash in hashes {
+        assert!(node_b.node.search_block(hash).is_some());
+    }
+    node_b.shutdown()?;
+    node_a.shutdown()?;
+    Ok(())
+}
+
+#[test]
+fn test_transaction_sent_by_the_wallet_is_received_and_confirmed_in_other_node(
+) -> Result<(), Box<dyn Error>> {
+    let (miner_key, miner_address) = regtest_key(MINER_PRIVATE_KEY)?;
+    let (receiver_key, receiver_address) = regtest_key(RECEIVER_PRIVATE_KEY)?;
+    let (_, other_address) = regtest_key(OTHER_PRIVATE_KEY)?;
+    let mut node_a = RegtestNode::start("wallet_a", &[])?;
+    let mut node_b = RegtestNode::start("wallet_b", &[node_a.port])?;
+    // the coinbase of the miner can be spent once it has 100 confirmations
+    node_a.mine_blocks(&miner_address, 1)?;
+    node_a.mine_blocks(&other_address, COINBASE_MATURITY)?;
+    let tip_hash = node_a.tip_hash()?;
+    assert!(wait_until(|| Ok(node_b.tip_hash()? == tip_hash))?);
+    node_a.wallet.add_account(&None, miner_key, miner_address)?;
+    node_a.wallet.change_account(&None, 0)?;
+    node_b
+        .wallet
+        .add_account(&None, receiver_key, receiver_address.clone())?;
+    node_b.wallet.change_account(&None, 0)?;
+
+    node_a
+        .wallet
+        .make_transaction(&None, &receiver_address, 100_000_000, 10_000, 0)?;
+    let transaction = node_a
+        .wallet
+        .get_current_account()
+        .ok_or("There is no current account")?
+        .pending_transactions
+        .read()
+        .map_err(|err| err.to_string())?[0]
+        .clone();
+    let receiver_account = node_b
+        .wallet
+        .get_current_account()
+        .ok_or("There is no current account")?;
+
+    assert!(wait_until(|| Ok(receiver_account
+        .pending_transactions
+        .read()
+        .map_err(|err| err.to_string())?
+        .contains(&transaction)))?);
+    // the pending transaction of the wallet is included in the next block mined, whose coinbase collects its fee
+    let hashes = node_a.mine_blocks(&other_address, 1)?;
+    let block = node_a
+        .node
+        .search_block(hashes[0])
+        .ok_or("The block mined is not found")?;
+    assert!(block.txn.contains(&transaction));
+    assert_eq!(block.txn[0].tx_out[0].value(), BLOCK_SUBSIDY + 10_000);
+    assert!(wait_until(|| {
+        let accounts = node_b
+            .wallet
+            .accounts
+            .read()
+            .map_err(|err| err.to_string())?;
+        Ok(accounts[0].balance() == 100_000_000)
+    })?);
+    assert_eq!(
+        node_a
+            .wallet
+            .accounts
+            .read()
+            .map_err(|err| err.to_string())?[0]
+            .balance(),
+        BLOCK_SUBSIDY - 100_000_000 - 10_000
+    );
+    node_b.shutdown()?;
+    node_a.shutdown()?;
+    Ok(())
+}
+
+#[test]
+fn test_addresses_of_nodes_are_learned_from_other_nodes() -> Result<(), Box<dyn Error>> {
+    let node_a = RegtestNode::start("addr_a", &[])?;
+    let node_b = RegtestNode::start("addr_b", &[node_a.port])?;
+    // node c only knows node b, which answers its getaddr with the address of node a
+    let node_c = RegtestNode::start("addr_c", &[node_b.port])?;
+    let address_a: SocketAddr = format!("127.0.0.1:{}", node_a.port).parse()?;
+    let address_b: SocketAddr = format!("127.0.0.1:{}", node_b.port).parse()?;
+
+    assert!(wait_until(|| Ok(node_c
+        .node
+        .node_pointers
+        .address_manager
+        .read()
+        .map_err(|err| err.to_string())?
+        .contains(&address_a)))?);
+    let address_manager_c = node_c
+        .node
+        .node_pointers
+        .address_manager
+        .read()
+        .map_err(|err| err.to_string())?;
+    assert!(address_manager_c.is_tried(&address_b));
+    assert!(!address_manager_c.is_tried(&address_a));
+    drop(address_manager_c);
+    node_c.shutdown()?;
+    node_b.shutdown()?;
+    node_a.shutdown()?;
+    Ok(())
+}
+
+#[test]
+fn test_nodes_connect_through_ipv6_and_ipv4_to_a_dual_stack_node() -> Result<(), Box<dyn Error>> {
+    if TcpListener::bind("[::1]:0").is_err() {
+        eprintln!("Skipping the test, Ipv6 is not available in this host");
+        return Ok(());
+    }
+    let (_, address) = regtest_key(MINER_PRIVATE_KEY)?;
+    let node_a = RegtestNode::start_listening_in("ipv6_a", "::".parse()?, &[])?;
+    let address_a_v6: SocketAddr = format!("[::1]:{}", node_a.port).parse()?;
+    let address_a_v4: SocketAddr = format!("127.0.0.1:{}", node_a.port).parse()?;
+    let node_b =
+        RegtestNode::start_listening_in("ipv6_b", "::1".parse()?, &[address_a_v6.to_string()])?;
+    let node_c = RegtestNode::start_listening_in(
+        "ipv6_c",
+        "127.0.0.1".parse()?,
+        &[address_a_v4.to_string()],
+    )?;
+
+    let hashes = node_a.mine_blocks(&address, 2)?;
+
+    assert!(wait_until(|| Ok(node_b.tip_hash()? == hashes[1]))?);
+    assert!(wait_until(|| Ok(node_c.tip_hash()? == hashes[1]))?);
+    assert!(node_b
+        .node
+        .node_pointers
+        .address_manager
+        .read()
+        .map_err(|err| err.to_string())?
+        .is_tried(&address_a_v6));
+    node_c.shutdown()?;
+    node_b.shutdown()?;
+    node_a.shutdown()?;
+    Ok(())
+}
@@ -0,0 +1,874 @@
+use std::error::Error;
+
+use bitcoin::{
+    address_decoder::{decode_wif_private_key, get_pubkey_compressed, hash_160},
+    compact_size_uint::CompactSizeUint,
+    transactions::{
+        outpoint::Outpoint,
+        script::{
+            interpreter::{encode_number, push_data_bytes, Script},
+            script_opcodes::ScriptOpcodes,
+            sig_script::SigScript,
+            signature_checker::TransactionSignatureChecker,
+        },
+        transaction::Transaction,
+        tx_in::TxIn,
+        tx_out::TxOut,
+    },
+};
+
+const PRIVATE_KEY_1: &str = "cMoBjaYS6EraKLNqrNN8DvN93Nnt6pJNfWkYM8pUufYQB5EVZ7SR";
+const PRIVATE_KEY_2: &str = "cQojsQ5fSonENC5EnrzzTAWSGX8PB4TBh6GunBxcCdGMJJiLULwZ";
+
+/// Test vectors taken from the script_tests.json file of Bitcoin Core.
+/// Each one has the scriptSig, the scriptPubKey and the expected result ("OK" or the name of the error).
+/// The vectors that depend on policy flags (MINIMALDATA, STRICTENC, LOW_S, ...) are not included, but the ones
+/// of DERSIG (BIP66) and NULLDUMMY (BIP147) are, as they are consensus rules.
+/// NOP2 and NOP3 are always evaluated as CHECKLOCKTIMEVERIFY and CHECKSEQUENCEVERIFY, so they are not used as NOPs.
+const SCRIPT_TESTS: &[(&str, &str, &str)] = &[
+    ("", "DEPTH 0 EQUAL", "OK"),
+    ("  ", "DEPTH 0 EQUAL", "OK"),
+    ("1 2", "2 EQUALVERIFY 1 EQUAL", "OK"),
+    ("1 2", "  2 EQUALVERIFY   1 EQUAL  ", "OK"),
+    ("0x01 0x0b", "11 EQUAL", "OK"),
+    ("0x02 0x417a", "'Az' EQUAL", "OK"),
+    ("0x4c 0x01 0x07", "7 EQUAL", "OK"),
+    ("0x4d 0x0100 0x08", "8 EQUAL", "OK"),
+    ("0x4e 0x01000000 0x09", "9 EQUAL", "OK"),
+    ("0x4c 0x00", "0 EQUAL", "OK"),
+    ("0x4d 0x0000", "0 EQUAL", "OK"),
+    ("0x4e 0x00000000", "0 EQUAL", "OK"),
+    ("0x4f 1000 ADD", "999 EQUAL", "OK"),
+    ("0", "IF 0x50 ENDIF 1", "OK"),
+    ("0x51", "0x5f ADD 0x60 EQUAL", "OK"),
+    ("1", "NOP", "OK"),
+    ("0", "IF VER ELSE 1 ENDIF", "OK"),
+    ("0", "IF RESERVED RESERVED1 RESERVED2 ELSE 1 ENDIF", "OK"),
+    ("1", "DUP IF ENDIF", "OK"),
+    ("1", "IF 1 ENDIF", "OK"),
+    ("1", "DUP IF ELSE ENDIF", "OK"),
+    ("1", "IF 1 ELSE ENDIF", "OK"),
+    ("0", "IF ELSE 1 ENDIF", "OK"),
+    ("1 1", "IF IF 1 ELSE 0 ENDIF ENDIF", "OK"),
+    ("1 0", "IF IF 1 ELSE 0 ENDIF ENDIF", "OK"),
+    ("1 1", "IF IF 1 ELSE 0 ENDIF ELSE IF 0 ELSE 1 ENDIF ENDIF", "OK"),
+    ("0 0", "IF IF 1 ELSE 0 ENDIF ELSE IF 0 ELSE 1 ENDIF ENDIF", "OK"),
+    ("1 0", "NOTIF IF 1 ELSE 0 ENDIF ENDIF", "OK"),
+    ("1 1", "NOTIF IF 1 ELSE 0 ENDIF ENDIF", "OK"),
+    ("1 0", "NOTIF IF 1 ELSE 0 ENDIF ELSE IF 0 ELSE 1 ENDIF ENDIF", "OK"),
+    ("0 1", "NOTIF IF 1 ELSE 0 ENDIF ELSE IF 0 ELSE 1 ENDIF ENDIF", "OK"),
+    ("0", "IF 0 ELSE 1 ELSE 0 ENDIF", "OK"),
+    ("1", "IF 1 ELSE 0 ELSE ENDIF", "OK"),
+    ("1", "IF ELSE 0 ELSE 1 ENDIF", "OK"),
+    ("1", "IF 1 ELSE 0 ELSE 1 ENDIF ADD 2 EQUAL", "OK"),
+    (
+        "'' 1",
+        "IF SHA1 ENDIF 0x14 0xda39a3ee5e6b4b0d3255bfef95601890afd80709 EQUAL",
+        "OK",
+    ),
+    ("1", "NOTIF 0 ELSE 1 ELSE 0 ENDIF", "OK"),
+    ("0", "NOTIF 1 ELSE 0 ELSE 1 ENDIF", "OK"),
+    (
+        "0",
+        "IF 1 IF RETURN ELSE RETURN ELSE RETURN ENDIF ELSE 1 IF 1 ELSE RETURN ELSE 1 ENDIF ELSE RETURN ENDIF ADD 2 EQUAL",
+        "OK",
+    ),
+    ("0", "IF RETURN ENDIF 1", "OK"),
+    ("1 1", "VERIFY", "OK"),
+    ("1 0x05 0x01 0x00 0x00 0x00 0x00", "VERIFY", "OK"),
+    ("1 0x01 0x80", "IF 0 ENDIF", "OK"),
+    ("10 0 11 TOALTSTACK DROP FROMALTSTACK", "ADD 21 EQUAL", "OK"),
+    (
+        "'gavin_was_here' TOALTSTACK 11 FROMALTSTACK",
+        "'gavin_was_here' EQUALVERIFY 11 EQUAL",
+        "OK",
+    ),
+    ("0 IFDUP", "DEPTH 1 EQUALVERIFY 0 EQUAL", "OK"),
+    ("1 IFDUP", "DEPTH 2 EQUALVERIFY 1 EQUALVERIFY 1 EQUAL", "OK"),
+    (
+        "0x05 0x0100000000 IFDUP",
+        "DEPTH 2 EQUALVERIFY 0x05 0x0100000000 EQUAL",
+        "OK",
+    ),
+    ("0 DROP", "DEPTH 0 EQUAL", "OK"),
+    ("0", "DUP 1 ADD 1 EQUALVERIFY 0 EQUAL", "OK"),
+    ("0 1", "NIP", "OK"),
+    ("1 0", "OVER DEPTH 3 EQUALVERIFY", "OK"),
+    ("22 21 20", "0 PICK 20 EQUALVERIFY DEPTH 3 EQUAL", "OK"),
+    ("22 21 20", "1 PICK 21 EQUALVERIFY DEPTH 3 EQUAL", "OK"),
+    ("22 21 20", "2 PICK 22 EQUALVERIFY DEPTH 3 EQUAL", "OK"),
+    ("22 21 20", "0 ROLL 20 EQUALVERIFY DEPTH 2 EQUAL", "OK"),
+    ("22 21 20", "1 ROLL 21 EQUALVERIFY DEPTH 2 EQUAL", "OK"),
+    ("22 21 20", "2 ROLL 22 EQUALVERIFY DEPTH 2 EQUAL", "OK"),
+    ("22 21 20", "ROT 22 EQUAL", "OK"),
+    ("22 21 20", "ROT DROP 20 EQUAL", "OK"),
+    ("22 21 20", "ROT DROP DROP 21 EQUAL", "OK"),
+    ("22 21 20", "ROT ROT 21 EQUAL", "OK"),
+    ("22 21 20", "ROT ROT ROT 20 EQUAL", "OK"),
+    ("25 24 23 22 21 20", "2ROT 24 EQUAL", "OK"),
+    ("25 24 23 22 21 20", "2ROT DROP 25 EQUAL", "OK"),
+    ("25 24 23 22 21 20", "2ROT 2DROP 20 EQUAL", "OK"),
+    ("25 24 23 22 21 20", "2ROT 2DROP DROP 21 EQUAL", "OK"),
+    ("25 24 23 22 21 20", "2ROT 2DROP 2DROP 22 EQUAL", "OK"),
+    ("25 24 23 22 21 20", "2ROT 2DROP 2DROP DROP 23 EQUAL", "OK"),
+    ("25 24 23 22 21 20", "2ROT 2ROT 22 EQUAL", "OK"),
+    ("25 24 23 22 21 20", "2ROT 2ROT 2ROT 20 EQUAL", "OK"),
+    ("1 0", "SWAP 1 EQUALVERIFY 0 EQUAL", "OK"),
+    ("0 1", "TUCK DEPTH 3 EQUALVERIFY SWAP 2DROP", "OK"),
+    ("13 14", "2DUP ROT EQUALVERIFY EQUAL", "OK"),
+    (
+        "-1 0 1 2",
+        "3DUP DEPTH 7 EQUALVERIFY ADD ADD 3 EQUALVERIFY 2DROP 0 EQUALVERIFY",
+        "OK",
+    ),
+    (
+        "1 2 3 5",
+        "2OVER ADD ADD 8 EQUALVERIFY ADD ADD 6 EQUAL",
+        "OK",
+    ),
+    ("1 3 5 7", "2SWAP ADD 4 EQUALVERIFY ADD 12 EQUAL", "OK"),
+    ("0", "SIZE 0 EQUAL", "OK"),
+    ("1", "SIZE 1 EQUAL", "OK"),
+    ("127", "SIZE 1 EQUAL", "OK"),
+    ("128", "SIZE 2 EQUAL", "OK"),
+    ("32767", "SIZE 2 EQUAL", "OK"),
+    ("32768", "SIZE 3 EQUAL", "OK"),
+    ("-1", "SIZE 1 EQUAL", "OK"),
+    ("-128", "SIZE 2 EQUAL", "OK"),
+    ("'abcdefghijklmnopqrstuvwxyz'", "SIZE 26 EQUAL", "OK"),
+    ("2 -2 ADD", "0 EQUAL", "OK"),
+    ("2147483647 -2147483647 ADD", "0 EQUAL", "OK"),
+    ("-1 -1 ADD", "-2 EQUAL", "OK"),
+    ("0 0", "EQUAL", "OK"),
+    ("1 1 ADD", "2 EQUAL", "OK"),
+    ("1 1ADD", "2 EQUAL", "OK"),
+    ("111 1SUB", "110 EQUAL", "OK"),
+    ("111 1 ADD 12 SUB", "100 EQUAL", "OK"),
+    ("0 ABS", "0 EQUAL", "OK"),
+    ("16 ABS", "16 EQUAL", "OK"),
+    ("-16 ABS", "-16 NEGATE EQUAL", "OK"),
+    ("0 NOT", "NOP", "OK"),
+    ("1 NOT", "0 EQUAL", "OK"),
+    ("11 NOT", "0 EQUAL", "OK"),
+    ("0 0NOTEQUAL", "0 EQUAL", "OK"),
+    ("1 0NOTEQUAL", "1 EQUAL", "OK"),
+    ("111 0NOTEQUAL", "1 EQUAL", "OK"),
+    ("-111 0NOTEQUAL", "1 EQUAL", "OK"),
+    ("1 1 BOOLAND", "NOP", "OK"),
+    ("1 0 BOOLAND", "NOT", "OK"),
+    ("0 1 BOOLAND", "NOT", "OK"),
+    ("0 0 BOOLAND", "NOT", "OK"),
+    ("16 17 BOOLAND", "NOP", "OK"),
+    ("1 1 BOOLOR", "NOP", "OK"),
+    ("1 0 BOOLOR", "NOP", "OK"),
+    ("0 1 BOOLOR", "NOP", "OK"),
+    ("0 0 BOOLOR", "NOT", "OK"),
+    ("16 17 BOOLOR", "NOP", "OK"),
+    ("11 10 1 ADD", "NUMEQUAL", "OK"),
+    ("11 10 1 ADD", "NUMEQUALVERIFY 1", "OK"),
+    ("11 10 1 ADD", "NUMNOTEQUAL NOT", "OK"),
+    ("111 10 1 ADD", "NUMNOTEQUAL", "OK"),
+    ("11 10", "LESSTHAN NOT", "OK"),
+    ("4 4", "LESSTHAN NOT", "OK"),
+    ("10 11", "LESSTHAN", "OK"),
+    ("-11 11", "LESSTHAN", "OK"),
+    ("-11 -10", "LESSTHAN", "OK"),
+    ("11 10", "GREATERTHAN", "OK"),
+    ("4 4", "GREATERTHAN NOT", "OK"),
+    ("10 11", "GREATERTHAN NOT", "OK"),
+    ("11 10", "LESSTHANOREQUAL NOT", "OK"),
+    ("4 4", "LESSTHANOREQUAL", "OK"),
+    ("10 11", "LESSTHANOREQUAL", "OK"),
+    ("11 10", "GREATERTHANOREQUAL", "OK"),
+    ("4 4", "GREATERTHANOREQUAL", "OK"),
+    ("10 11", "GREATERTHANOREQUAL NOT", "OK"),
+    ("1 0", "MIN 0 NUMEQUAL", "OK"),
+    ("0 1", "MIN 0 NUMEQUAL", "OK"),
+    ("-1 0", "MIN -1 NUMEQUAL", "OK"),
+    ("0 -2147483647", "MAX 0 NUMEQUAL", "OK"),
+    ("2147483647 0", "MAX 2147483647 NUMEQUAL", "OK"),
+    ("0 0 1", "WITHIN", "OK"),
+    ("1 0 1", "WITHIN NOT", "OK"),
+    ("0 -2147483647 2147483647", "WITHIN", "OK"),
+    ("-1 -100 100", "WITHIN", "OK"),
+    ("11 -100 100", "WITHIN", "OK"),
+    ("0 -2147483647 100", "WITHIN", "OK"),
+    ("-2147483647 -100 100", "WITHIN NOT", "OK"),
+    ("2147483647 -100 100", "WITHIN NOT", "OK"),
+    ("2147483647 2147483647 SUB", "0 EQUAL", "OK"),
+    ("2147483647 DUP ADD", "4294967294 EQUAL", "OK"),
+    ("2147483647 NEGATE DUP ADD", "-4294967294 EQUAL", "OK"),
+    (
+        "''",
+        "RIPEMD160 0x14 0x9c1185a5c5e9fc54612808977ee8f548b2258d31 EQUAL",
+        "OK",
+    ),
+    (
+        "'a'",
+        "RIPEMD160 0x14 0x0bdc9d2d256b3ee9daae347be6f4dc835a467ffe EQUAL",
+        "OK",
+    ),
+    (
+        "'abcdefghijklmnopqrstuvwxyz'",
+        "RIPEMD160 0x14 0xf71c27109c692c1b56bbdceb5b9d2865b3708dbc EQUAL",
+        "OK",
+    ),
+    (
+        "''",
+        "SHA1 0x14 0xda39a3ee5e6b4b0d3255bfef95601890afd80709 EQUAL",
+        "OK",
+    ),
+    (
+        "'a'",
+        "SHA1 0x14 0x86f7e437faa5a7fce15d1ddcb9eaeaea377667b8 EQUAL",
+        "OK",
+    ),
+    (
+        "'abcdefghijklmnopqrstuvwxyz'",
+        "SHA1 0x14 0x32d10c7b8cf96570ca04ce37f2a19d84240d3a89 EQUAL",
+        "OK",
+    ),
+    (
+        "''",
+        "SHA256 0x20 0xe3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 EQUAL",
+        "OK",
+    ),
+    (
+        "'a'",
+        "SHA256 0x20 0xca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb EQUAL",
+        "OK",
+    ),
+    (
+        "'abcdefghijklmnopqrstuvwxyz'",
+        "SHA256 0x20 0x71c480df93d6ae2f1efad1447c66c9525e316218cf51fc8d9ed832f2daf18b73 EQUAL",
+        "OK",
+    ),
+    ("''", "DUP HASH160 SWAP SHA256 RIPEMD160 EQUAL", "OK"),
+    ("''", "DUP HASH256 SWAP SHA256 SHA256 EQUAL", "OK"),
+    (
+        "''",
+        "NOP HASH160 0x14 0xb472a266d0bd89c13706a4132ccfb16f7c3b9fcb EQUAL",
+        "OK",
+    ),
+    (
+        "'a'",
+        "HASH160 NOP 0x14 0x994355199e516ff76c4fa4aab39337b9d84cf12b EQUAL",
+        "OK",
+    ),
+    (
+        "''",
+        "HASH256 0x20 0x5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456 EQUAL",
+        "OK",
+    ),
+    (
+        "'a'",
+        "HASH256 0x20 0xbf5d3affb73efd2ec6c36ad3112dd933efed63c4e1cbffcfa88e2759c144f2d8 EQUAL",
+        "OK",
+    ),
+    (
+        "1",
+        "NOP1 NOP4 NOP5 NOP6 NOP7 NOP8 NOP9 NOP10 1 EQUAL",
+        "OK",
+    ),
+    (
+        "'NOP_1_to_10' NOP1 NOP4 NOP5 NOP6 NOP7 NOP8 NOP9 NOP10",
+        "'NOP_1_to_10' EQUAL",
+        "OK",
+    ),
+    ("0", "IF 0xba ELSE 1 ENDIF", "OK"),
+    ("0", "IF 0xff ELSE 1 ENDIF", "OK"),
+    ("", "0 0 0 CHECKMULTISIG VERIFY DEPTH 0 EQUAL", "OK"),
+    ("", "0 0 0 CHECKMULTISIGVERIFY DEPTH 0 EQUAL", "OK"),
+    ("", "0 0 0 1 CHECKMULTISIG VERIFY DEPTH 0 EQUAL", "OK"),
+    ("", "0 0 0 1 CHECKMULTISIGVERIFY DEPTH 0 EQUAL", "OK"),
+    ("", "0 0 'a' 'b' 2 CHECKMULTISIG VERIFY DEPTH 0 EQUAL", "OK"),
+    (
+        "",
+        "0 0 'a' 'b' 'c' 'd' 'e' 'f' 'g' 'h' 'i' 'j' 'k' 'l' 'm' 'n' 'o' 'p' 'q' 'r' 's' 't' 20 CHECKMULTISIG VERIFY DEPTH 0 EQUAL",
+        "OK",
+    ),
+    ("", "DEPTH", "EVAL_FALSE"),
+    ("", "", "EVAL_FALSE"),
+    ("", "NOP", "EVAL_FALSE"),
+    ("", "NOP DEPTH", "EVAL_FALSE"),
+    ("NOP", "", "EVAL_FALSE"),
+    ("NOP", "DEPTH", "EVAL_FALSE"),
+    ("NOP", "NOP", "EVAL_FALSE"),
+    ("NOP", "NOP DEPTH", "EVAL_FALSE"),
+    ("DEPTH", "", "EVAL_FALSE"),
+    ("0x4c01", "0x01 NOP", "BAD_OPCODE"),
+    ("0x4d0200ff", "0x01 NOP", "BAD_OPCODE"),
+    ("0x4e03000000ffff", "0x01 NOP", "BAD_OPCODE"),
+    ("1", "IF 0x50 ENDIF 1", "BAD_OPCODE"),
+    ("0x52", "0x5f ADD 0x60 EQUAL", "EVAL_FALSE"),
+    ("0", "NOP", "EVAL_FALSE"),
+    ("1", "IF VER ELSE 1 ENDIF", "BAD_OPCODE"),
+    ("0", "IF VERIF ELSE 1 ENDIF", "BAD_OPCODE"),
+    ("0", "IF ELSE 1 ELSE VERIF ENDIF", "BAD_OPCODE"),
+    ("0", "IF VERNOTIF ELSE 1 ENDIF", "BAD_OPCODE"),
+    ("0", "IF ELSE 1 ELSE VERNOTIF ENDIF", "BAD_OPCODE"),
+    ("0", "DUP IF ENDIF", "EVAL_FALSE"),
+    ("0", "IF 1 ENDIF", "EVAL_FALSE"),
+    ("0", "DUP IF ELSE ENDIF", "EVAL_FALSE"),
+    ("0", "IF 1 ELSE ENDIF", "EVAL_FALSE"),
+    ("0", "NOTIF ELSE 1 ENDIF", "EVAL_FALSE"),
+    ("0 1", "IF IF 1 ELSE 0 ENDIF ENDIF", "EVAL_FALSE"),
+    ("0 0", "IF IF 1 ELSE 0 ENDIF ENDIF", "EVAL_FALSE"),
+    ("1 0", "IF IF 1 ELSE 0 ENDIF ELSE IF 0 ELSE 1 ENDIF ENDIF", "EVAL_FALSE"),
+    ("0 1", "IF IF 1 ELSE 0 ENDIF ELSE IF 0 ELSE 1 ENDIF ENDIF", "EVAL_FALSE"),
+    ("0 0", "NOTIF IF 1 ELSE 0 ENDIF ENDIF", "EVAL_FALSE"),
+    ("0 1", "NOTIF IF 1 ELSE 0 ENDIF ENDIF", "EVAL_FALSE"),
+    ("1 1", "NOTIF IF 1 ELSE 0 ENDIF ELSE IF 0 ELSE 1 ENDIF ENDIF", "EVAL_FALSE"),
+    ("0 0", "NOTIF IF 1 ELSE 0 ENDIF ELSE IF 0 ELSE 1 ENDIF ENDIF", "EVAL_FALSE"),
+    ("1", "IF RETURN ELSE ELSE 1 ENDIF", "OP_RETURN"),
+    ("1", "IF 1 ELSE ELSE RETURN ENDIF", "OP_RETURN"),
+    ("1", "ENDIF", "UNBALANCED_CONDITIONAL"),
+    ("1", "ELSE ENDIF", "UNBALANCED_CONDITIONAL"),
+    ("1", "ENDIF ELSE", "UNBALANCED_CONDITIONAL"),
+    ("1", "ENDIF ELSE IF", "UNBALANCED_CONDITIONAL"),
+    ("1", "IF ELSE ENDIF ELSE", "UNBALANCED_CONDITIONAL"),
+    ("1", "IF ELSE ENDIF ELSE ENDIF", "UNBALANCED_CONDITIONAL"),
+    ("1", "IF ENDIF ENDIF", "UNBALANCED_CONDITIONAL"),
+    ("1", "IF ELSE ELSE ENDIF ENDIF", "UNBALANCED_CONDITIONAL"),
+    ("1", "RETURN", "OP_RETURN"),
+    ("1", "DUP IF RETURN ENDIF", "OP_RETURN"),
+    ("1", "RETURN 'data'", "OP_RETURN"),
+    ("0 IF", "RETURN ENDIF 1", "UNBALANCED_CONDITIONAL"),
+    ("1 IF 1 ELSE", "0xff ENDIF", "UNBALANCED_CONDITIONAL"),
+    ("0", "VERIFY 1", "VERIFY"),
+    ("1", "VERIFY", "EVAL_FALSE"),
+    ("1", "VERIFY 0", "EVAL_FALSE"),
+    ("1 TOALTSTACK", "FROMALTSTACK 1", "INVALID_ALTSTACK_OPERATION"),
+    ("IFDUP", "DEPTH 0 EQUAL", "INVALID_STACK_OPERATION"),
+    ("DROP", "DEPTH 0 EQUAL", "INVALID_STACK_OPERATION"),
+    ("DUP", "DEPTH 0 EQUAL", "INVALID_STACK_OPERATION"),
+    ("1", "DUP 1 ADD 2 EQUALVERIFY 0 EQUAL", "EVAL_FALSE"),
+    ("NOP", "NIP", "INVALID_STACK_OPERATION"),
+    ("NOP", "1 NIP", "INVALID_STACK_OPERATION"),
+    ("NOP", "1 0 NIP", "EVAL_FALSE"),
+    ("NOP", "OVER 1", "INVALID_STACK_OPERATION"),
+    ("1", "OVER", "INVALID_STACK_OPERATION"),
+    ("0 1", "OVER DEPTH 3 EQUALVERIFY", "EVAL_FALSE"),
+    (
+        "19 20 21",
+        "PICK 19 EQUALVERIFY DEPTH 2 EQUAL",
+        "INVALID_STACK_OPERATION",
+    ),
+    ("NOP", "0 PICK", "INVALID_STACK_OPERATION"),
+    ("1", "-1 PICK", "INVALID_STACK_OPERATION"),
+    ("19 20 21", "0 PICK 20 EQUALVERIFY DEPTH 3 EQUAL", "EQUALVERIFY"),
+    ("19 20 21", "1 PICK 21 EQUALVERIFY DEPTH 3 EQUAL", "EQUALVERIFY"),
+    ("19 20 21", "2 PICK 22 EQUALVERIFY DEPTH 3 EQUAL", "EQUALVERIFY"),
+    ("NOP", "0 ROLL", "INVALID_STACK_OPERATION"),
+    ("1", "-1 ROLL", "INVALID_STACK_OPERATION"),
+    ("19 20 21", "0 ROLL 20 EQUALVERIFY DEPTH 2 EQUAL", "EQUALVERIFY"),
+    ("19 20 21", "1 ROLL 21 EQUALVERIFY DEPTH 2 EQUAL", "EQUALVERIFY"),
+    ("19 20 21", "2 ROLL 22 EQUALVERIFY DEPTH 2 EQUAL", "EQUALVERIFY"),
+    ("NOP", "ROT 1", "INVALID_STACK_OPERATION"),
+    ("NOP", "1 ROT 1", "INVALID_STACK_OPERATION"),
+    ("NOP", "1 2 ROT 1", "INVALID_STACK_OPERATION"),
+    ("NOP", "0 1 2 ROT", "EVAL_FALSE"),
+    ("NOP", "SWAP 1", "INVALID_STACK_OPERATION"),
+    ("1", "SWAP 1", "INVALID_STACK_OPERATION"),
+    ("0 1", "SWAP 1 EQUALVERIFY", "EQUALVERIFY"),
+    ("NOP", "TUCK 1", "INVALID_STACK_OPERATION"),
+    ("1", "TUCK 1", "INVALID_STACK_OPERATION"),
+    ("1 0", "TUCK DEPTH 3 EQUALVERIFY SWAP 2DROP", "EVAL_FALSE"),
+    ("NOP", "2DUP 1", "INVALID_STACK_OPERATION"),
+    ("1", "2DUP 1", "INVALID_STACK_OPERATION"),
+    ("NOP", "3DUP 1", "INVALID_STACK_OPERATION"),
+    ("1", "3DUP 1", "INVALID_STACK_OPERATION"),
+    ("1 2", "3DUP 1", "INVALID_STACK_OPERATION"),
+    ("NOP", "2OVER 1", "INVALID_STACK_OPERATION"),
+    ("1", "2 3 2OVER 1", "INVALID_STACK_OPERATION"),
+    ("NOP", "2SWAP 1", "INVALID_STACK_OPERATION"),
+    ("1", "2 3 2SWAP 1", "INVALID_STACK_OPERATION"),
+    ("'a' 'b'", "CAT", "DISABLED_OPCODE"),
+    ("'a' 'b' 0", "IF CAT ELSE 1 ENDIF", "DISABLED_OPCODE"),
+    ("'abc' 1 1", "SUBSTR", "DISABLED_OPCODE"),
+    ("'abc' 1 1 0", "IF SUBSTR ELSE 1 ENDIF", "DISABLED_OPCODE"),
+    ("'abc' 2 0", "IF LEFT ELSE 1 ENDIF", "DISABLED_OPCODE"),
+    ("'abc' 2 0", "IF RIGHT ELSE 1 ENDIF", "DISABLED_OPCODE"),
+    ("NOP", "SIZE 1", "INVALID_STACK_OPERATION"),
+    ("'abc'", "IF INVERT ELSE 1 ENDIF", "DISABLED_OPCODE"),
+    ("1 2 0 IF AND ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("1 2 0 IF OR ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("1 2 0 IF XOR ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("2 0 IF 2MUL ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("2 0 IF 2DIV ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("2 2 0 IF MUL ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("2 2 0 IF DIV ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("2 2 0 IF MOD ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("2 2 0 IF LSHIFT ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("2 2 0 IF RSHIFT ELSE 1 ENDIF", "NOP", "DISABLED_OPCODE"),
+    ("", "EQUAL NOT", "INVALID_STACK_OPERATION"),
+    ("'a' 'b'", "EQUAL", "EVAL_FALSE"),
+    ("'a' 'b'", "EQUALVERIFY 1", "EQUALVERIFY"),
+    ("2147483648 0 ADD", "NOP", "UNKNOWN_ERROR"),
+    ("-2147483648 0 ADD", "NOP", "UNKNOWN_ERROR"),
+    ("2147483647 DUP ADD", "4294967294 NUMEQUAL", "UNKNOWN_ERROR"),
+    ("'abcdef' NOT", "0 EQUAL", "UNKNOWN_ERROR"),
+    ("2 DUP MUL", "4 EQUAL", "DISABLED_OPCODE"),
+    ("2147483648", "1ADD 1", "UNKNOWN_ERROR"),
+    ("-2147483648", "1ADD 1", "UNKNOWN_ERROR"),
+    (
+        "1",
+        "NOP1 NOP4 NOP5 NOP6 NOP7 NOP8 NOP9 NOP10 2 EQUAL",
+        "EVAL_FALSE",
+    ),
+    (
+        "'NOP_1_to_10' NOP1 NOP4 NOP5 NOP6 NOP7 NOP8 NOP9 NOP10",
+        "'NOP_1_to_11' EQUAL",
+        "EVAL_FALSE",
+    ),
+    ("0x50", "1", "BAD_OPCODE"),
+    ("1", "IF 0xba ELSE 1 ENDIF", "BAD_OPCODE"),
+    ("1", "IF 0xc0 ELSE 1 ENDIF", "BAD_OPCODE"),
+    ("1", "IF 0xff ELSE 1 ENDIF", "BAD_OPCODE"),
+    ("NOP", "RIPEMD160", "INVALID_STACK_OPERATION"),
+    ("NOP", "SHA1", "INVALID_STACK_OPERATION"),
+    ("NOP", "SHA256", "INVALID_STACK_OPERATION"),
+    ("NOP", "HASH160", "INVALID_STACK_OPERATION"),
+    ("NOP", "HASH256", "INVALID_STACK_OPERATION"),
+    ("1", "VER", "BAD_OPCODE"),
+    ("1", "VERIF", "BAD_OPCODE"),
+    ("1", "VERNOTIF", "BAD_OPCODE"),
+    ("1", "RESERVED", "BAD_OPCODE"),
+    ("1", "RESERVED1", "BAD_OPCODE"),
+    ("1", "RESERVED2", "BAD_OPCODE"),
+    ("1", "1 ENDIF", "UNBALANCED_CONDITIONAL"),
+    (
+        "",
+        "0 0 'a' 'b' 'c' 'd' 'e' 'f' 'g' 'h' 'i' 'j' 'k' 'l' 'm' 'n' 'o' 'p' 'q' 'r' 's' 't' 'u' 21 CHECKMULTISIG",
+        "PUBKEY_COUNT",
+    ),
+    ("", "0 2 'a' 1 CHECKMULTISIG 1", "SIG_COUNT"),
+    ("", "0 'a' 'b' 2 'c' 1 CHECKMULTISIG", "INVALID_STACK_OPERATION"),
+    ("", "CHECKLOCKTIMEVERIFY 1", "INVALID_STACK_OPERATION"),
+    ("-1", "CHECKLOCKTIMEVERIFY", "NEGATIVE_LOCKTIME"),
+    ("0", "CHECKLOCKTIMEVERIFY 1", "UNSATISFIED_LOCKTIME"),
+    ("", "CHECKSEQUENCEVERIFY 1", "INVALID_STACK_OPERATION"),
+    ("-1", "CHECKSEQUENCEVERIFY", "NEGATIVE_LOCKTIME"),
+    ("0", "CHECKSEQUENCEVERIFY 1", "UNSATISFIED_LOCKTIME"),
+    ("0x05 0x0000008000", "CHECKSEQUENCEVERIFY", "OK"),
+    (
+        "0x4a 0x0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        "0 CHECKSIG NOT",
+        "SIG_DER",
+    ),
+    (
+        "0x25 0x30220220000000000000000000000000000000000000000000000000000000000000000000",
+        "0 CHECKSIG NOT",
+        "SIG_DER",
+    ),
+    (
+        "0x27 0x3024021077777777777777777777777777777777020a7777777777777777777777777777777777",
+        "0 CHECKSIG NOT",
+        "SIG_DER",
+    ),
+    (
+        "0x27 0x302403107777777777777777777777777777777702107777777777777777777777777777777777",
+        "0 CHECKSIG NOT",
+        "SIG_DER",
+    ),
+    (
+        "0x27 0x302402107777777777777777777777777777777703107777777777777777777777777777777777",
+        "0 CHECKSIG NOT",
+        "SIG_DER",
+    ),
+    ("0x17 0x3014020002107777777777777777777777777777777777", "0 CHECKSIG NOT", "SIG_DER"),
+    ("0x17 0x3014021077777777777777777777777777777777020077", "0 CHECKSIG NOT", "SIG_DER"),
+    (
+        "0x27 0x302402107777777777777777777777777777777702108777777777777777777777777777777777",
+        "0 CHECKSIG NOT",
+        "SIG_DER",
+    ),
+    (
+        "0x27 0x302402108777777777777777777777777777777702107777777777777777777777777777777777",
+        "0 CHECKSIG NOT",
+        "SIG_DER",
+    ),
+    ("0x09 0x300602010102010101", "0 CHECKSIG NOT", "OK"),
+    ("0", "0 CHECKSIG NOT", "OK"),
+    (
+        "0 0x17 0x3014020002107777777777777777777777777777777777",
+        "1 0 0 2 CHECKMULTISIG NOT",
+        "SIG_DER",
+    ),
+    ("0 0x09 0x300602010102010101", "1 0 0 2 CHECKMULTISIG NOT", "OK"),
+    ("0 0", "1 0 1 CHECKMULTISIG NOT", "OK"),
+    ("1", "0 0 CHECKMULTISIG", "SIG_NULLDUMMY"),
+    ("0x01 0x00", "0 0 CHECKMULTISIG", "SIG_NULLDUMMY"),
+    ("1 0", "1 0 1 CHECKMULTISIG NOT", "SIG_NULLDUMMY"),
+];
+
+/// Parses a script written in the format used by the test vectors of Bitcoin Core.
+/// Numbers are pushed as script numbers, 0x prefixed values are inserted as raw bytes,
+/// quoted strings are pushed as data and the rest are names of opcodes.
+fn parse_script(asm: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut script: Vec<u8> = Vec::new();
+    for word in asm.split_whitespace() {
+        let is_number = word
+            .strip_prefix('-')
+            .unwrap_or(word)
+            .chars()
+            .all(|c| c.is_ascii_digit());
+        if is_number {
+            let number: i64 = word.parse()?;
+            if number == -1 {
+                script.push(ScriptOpcodes::OP_1NEGATE);
+            } else if number == 0 {
+                script.push(ScriptOpcodes::OP_0);
+            } else if (1..=16).contains(&number) {
+                script.push(ScriptOpcodes::OP_1 + number as u8 - 1);
+            } else {
+                script.extend_from_slice(&push_data_bytes(&encode_number(number)));
+            }
+        } else if let Some(hex) = word.strip_prefix("0x") {
+            for position in (0..hex.len()).step_by(2) {
+                script.push(u8::from_str_radix(&hex[position..position + 2], 16)?);
+            }
+        } else if word.len() >= 2 && word.starts_with('\'') && word.ends_with('\'') {
+            script.extend_from_slice(&push_data_bytes(word[1..word.len() - 1].as_bytes()));
+        } else {
+            match ScriptOpcodes::from_name(word) {
+                Some(opcode) => script.push(opcode),
+                None => return Err(format!("Unknown opcode {}", word).into()),
+            }
+        }
+    }
+    Ok(script)
+}
+
+/// Creates the transaction that creates the output with the script_pubkey, as Bitcoin Core does in its tests.
+fn build_crediting_transaction(script_pubkey: &[u8]) -> Transaction {
+    let tx_in = TxIn::new(
+        Outpoint::new([0; 32], 0xffffffff),
+        CompactSizeUint::new(2),
+        None,
+        SigScript::new(vec![0x00, 0x00]),
+        0xffffffff,
+    );
+    let tx_out = TxOut::new(
+        0,
+        CompactSizeUint::new(script_pubkey.len() as u128),
+        script_pubkey.to_vec(),
+    );
+    Transaction::new(
+        1,
+        CompactSizeUint::new(1),
+        vec![tx_in],
+        CompactSizeUint::new(1),
+        vec![tx_out],
+        0,
+    )
+}
+
+/// Creates the transaction that spends the output of the crediting transaction with the script_sig.
+fn build_spending_transaction(
+    script_sig: &[u8],
+    crediting_transaction: &Transaction,
+) -> Transaction {
+    let tx_in = TxIn::new(
+        Outpoint::new(crediting_transaction.hash(), 0),
+        CompactSizeUint::new(script_sig.len() as u128),
+        None,
+        SigScript::new(script_sig.to_vec()),
+        0xffffffff,
+    );
+    let tx_out = TxOut::new(0, CompactSizeUint::new(0), vec![]);
+    Transaction::new(
+        1,
+        CompactSizeUint::new(1),
+        vec![tx_in],
+        CompactSizeUint::new(1),
+        vec![tx_out],
+        0,
+    )
+}
+
+/// Verifies the script_sig against the script_pubkey in the context of the spending transaction.
+fn verify(script_sig: &[u8], script_pubkey: &[u8]) -> Result<(), &'static str> {
+    let crediting_transaction = build_crediting_transaction(script_pubkey);
+    let spending_transaction = build_spending_transaction(script_sig, &crediting_transaction);
+    let checker = TransactionSignatureChecker::new(&spending_transaction, 0);
+    Script::verify(
+        &Script::new(script_sig.to_vec()),
+        &Script::new(script_pubkey.to_vec()),
+        &checker,
+    )
+}
+
+/// Signs the input of the spending transaction of the script_pubkey with the private key (WIF) and the sighash type.
+/// The script_code is the part of the script_pubkey that is signed.
+fn sign(
+    script_pubkey: &[u8],
+    script_code: &[u8],
+    private_key: &str,
+    sighash_type: u8,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let crediting_transaction = build_crediting_transaction(script_pubkey);
+    let spending_transaction = build_spending_transaction(&[], &crediting_transaction);
+    let signature_hash = spending_transaction.signature_hash(0, script_code, sighash_type as u32);
+    let secp = secp256k1::Secp256k1::new();
+    let secret_key = secp256k1::SecretKey::from_slice(&decode_wif_private_key(private_key)?)?;
+    let message = secp256k1::Message::from_slice(&signature_hash)?;
+    let mut sig = secp
+        .sign_ecdsa(&message, &secret_key)
+        .serialize_der()
+        .to_vec();
+    sig.push(sighash_type);
+    Ok(sig)
+}
+
+/// Verifies an empty script_sig against the script_pubkey in the context of a spending transaction
+/// with the received version, lock time and sequence of its input.
+fn verify_with_lock_times(
+    script_pubkey: &[u8],
+    version: i32,
+    lock_time: u32,
+    sequence: u32,
+) -> Result<(), &'static str> {
+    let crediting_transaction = build_crediting_transaction(script_pubkey);
+    let mut spending_transaction = build_spending_transaction(&[], &crediting_transaction);
+    spending_transaction.version = version;
+    spending_transaction.lock_time = lock_time;
+    spending_transaction.tx_in[0].set_sequence(sequence);
+    let checker = TransactionSignatureChecker::new(&spending_transaction, 0);
+    Script::verify(
+        &Script::new(vec![]),
+        &Script::new(script_pubkey.to_vec()),
+        &checker,
+    )
+}
+
+/// Returns the script that pushes each of the received elements.
+fn push_all(elements: &[&[u8]]) -> Vec<u8> {
+    let mut script: Vec<u8> = Vec::new();
+    for element in elements {
+        script.extend_from_slice(&push_data_bytes(element));
+    }
+    script
+}
+
+#[test]
+fn test_script_vectors_of_bitcoin_core_are_evaluated_correctly() -> Result<(), Box<dyn Error>> {
+    for (script_sig, script_pubkey, expected) in SCRIPT_TESTS {
+        let result = verify(&parse_script(script_sig)?, &parse_script(script_pubkey)?);
+        assert_eq!(
+            result.is_ok(),
+            *expected == "OK",
+            "scriptSig: [{}] scriptPubKey: [{}] expected: {} result: {:?}",
+            script_sig,
+            script_pubkey,
+            expected,
+            result
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_p2pk_with_valid_signature_is_valid() -> Result<(), Box<dyn Error>> {
+    let pubkey = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let mut script_pubkey = push_data_bytes(&pubkey);
+    script_pubkey.push(ScriptOpcodes::OP_CHECKSIG);
+    let sig = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_1, 0x01)?;
+    assert!(verify(&push_data_bytes(&sig), &script_pubkey).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_p2pk_with_signature_of_another_key_is_not_valid() -> Result<(), Box<dyn Error>> {
+    let pubkey = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let mut script_pubkey = push_data_bytes(&pubkey);
+    script_pubkey.push(ScriptOpcodes::OP_CHECKSIG);
+    let sig = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_2, 0x01)?;
+    assert!(verify(&push_data_bytes(&sig), &script_pubkey).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_p2pkh_is_valid_only_with_the_pubkey_of_the_hash() -> Result<(), Box<dyn Error>> {
+    let pubkey = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let script_pubkey = parse_script(&format!(
+        "DUP HASH160 0x14 0x{} EQUALVERIFY CHECKSIG",
+        bytes_to_hex(&hash_160(&pubkey))
+    ))?;
+    let sig = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_1, 0x01)?;
+    assert!(verify(&push_all(&[&sig, &pubkey]), &script_pubkey).is_ok());
+
+    let another_pubkey = get_pubkey_compressed(PRIVATE_KEY_2)?;
+    let sig = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_2, 0x01)?;
+    assert_eq!(
+        verify(&push_all(&[&sig, &another_pubkey]), &script_pubkey),
+        Err("The script failed an OP_EQUALVERIFY operation")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_signature_with_modified_sighash_type_is_not_valid() -> Result<(), Box<dyn Error>> {
+    let pubkey = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let mut script_pubkey = push_data_bytes(&pubkey);
+    script_pubkey.push(ScriptOpcodes::OP_CHECKSIG);
+    for sighash_type in [0x01, 0x02, 0x03, 0x81, 0x82, 0x83] {
+        let mut sig = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_1, sighash_type)?;
+        assert!(verify(&push_data_bytes(&sig), &script_pubkey).is_ok());
+        let last = sig.len() - 1;
+        sig[last] ^= 0x80;
+        assert!(verify(&push_data_bytes(&sig), &script_pubkey).is_err());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_checksigverify_fails_with_invalid_signature() -> Result<(), Box<dyn Error>> {
+    let pubkey = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let mut script_pubkey = push_data_bytes(&pubkey);
+    script_pubkey.push(ScriptOpcodes::OP_CHECKSIGVERIFY);
+    script_pubkey.push(ScriptOpcodes::OP_1);
+    let sig = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_1, 0x01)?;
+    assert!(verify(&push_data_bytes(&sig), &script_pubkey).is_ok());
+    let sig = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_2, 0x01)?;
+    assert_eq!(
+        verify(&push_data_bytes(&sig), &script_pubkey),
+        Err("The script failed an OP_CHECKSIGVERIFY operation")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_multisig_2_of_2_needs_the_signatures_in_order() -> Result<(), Box<dyn Error>> {
+    let pubkey_1 = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let pubkey_2 = get_pubkey_compressed(PRIVATE_KEY_2)?;
+    let mut script_pubkey = vec![ScriptOpcodes::OP_1 + 1];
+    script_pubkey.extend_from_slice(&push_all(&[&pubkey_1, &pubkey_2]));
+    script_pubkey.push(ScriptOpcodes::OP_1 + 1);
+    script_pubkey.push(ScriptOpcodes::OP_CHECKMULTISIG);
+    let sig_1 = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_1, 0x01)?;
+    let sig_2 = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_2, 0x01)?;
+
+    let mut script_sig = vec![ScriptOpcodes::OP_0];
+    script_sig.extend_from_slice(&push_all(&[&sig_1, &sig_2]));
+    assert!(verify(&script_sig, &script_pubkey).is_ok());
+
+    let mut script_sig = vec![ScriptOpcodes::OP_0];
+    script_sig.extend_from_slice(&push_all(&[&sig_2, &sig_1]));
+    assert!(verify(&script_sig, &script_pubkey).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_multisig_1_of_2_is_valid_with_any_of_the_keys() -> Result<(), Box<dyn Error>> {
+    let pubkey_1 = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let pubkey_2 = get_pubkey_compressed(PRIVATE_KEY_2)?;
+    let mut script_pubkey = vec![ScriptOpcodes::OP_1];
+    script_pubkey.extend_from_slice(&push_all(&[&pubkey_1, &pubkey_2]));
+    script_pubkey.push(ScriptOpcodes::OP_1 + 1);
+    script_pubkey.push(ScriptOpcodes::OP_CHECKMULTISIG);
+    for private_key in [PRIVATE_KEY_1, PRIVATE_KEY_2] {
+        let sig = sign(&script_pubkey, &script_pubkey, private_key, 0x01)?;
+        let mut script_sig = vec![ScriptOpcodes::OP_0];
+        script_sig.extend_from_slice(&push_data_bytes(&sig));
+        assert!(verify(&script_sig, &script_pubkey).is_ok());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_multisig_with_valid_signature_needs_an_empty_dummy() -> Result<(), Box<dyn Error>> {
+    let pubkey = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let mut script_pubkey = vec![ScriptOpcodes::OP_1];
+    script_pubkey.extend_from_slice(&push_data_bytes(&pubkey));
+    script_pubkey.push(ScriptOpcodes::OP_1);
+    script_pubkey.push(ScriptOpcodes::OP_CHECKMULTISIG);
+    let sig = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_1, 0x01)?;
+    let mut script_sig = vec![ScriptOpcodes::OP_1];
+    script_sig.extend_from_slice(&push_data_bytes(&sig));
+    assert_eq!(
+        verify(&script_sig, &script_pubkey),
+        Err("The dummy element of a multisig is not empty")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_signature_commits_only_to_the_script_after_the_codeseparator() -> Result<(), Box<dyn Error>>
+{
+    let pubkey = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let mut script_code = push_data_bytes(&pubkey);
+    script_code.push(ScriptOpcodes::OP_CHECKSIG);
+    let mut script_pubkey = vec![ScriptOpcodes::OP_CODESEPARATOR];
+    script_pubkey.extend_from_slice(&script_code);
+
+    let sig = sign(&script_pubkey, &script_code, PRIVATE_KEY_1, 0x01)?;
+    assert!(verify(&push_data_bytes(&sig), &script_pubkey).is_ok());
+    let sig = sign(&script_pubkey, &script_pubkey, PRIVATE_KEY_1, 0x01)?;
+    assert!(verify(&push_data_bytes(&sig), &script_pubkey).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_checklocktimeverify_needs_a_greater_lock_time_of_the_same_type(
+) -> Result<(), Box<dyn Error>> {
+    let script_pubkey = parse_script("100 CHECKLOCKTIMEVERIFY DROP 1")?;
+    assert!(verify_with_lock_times(&script_pubkey, 1, 100, 0xfffffffe).is_ok());
+    assert!(verify_with_lock_times(&script_pubkey, 1, 99, 0xfffffffe).is_err());
+    assert!(verify_with_lock_times(&script_pubkey, 1, 500_000_000, 0xfffffffe).is_err());
+    let script_pubkey = parse_script("500000000 CHECKLOCKTIMEVERIFY DROP 1")?;
+    assert!(verify_with_lock_times(&script_pubkey, 1, 500_000_001, 0xfffffffe).is_ok());
+    assert!(verify_with_lock_times(&script_pubkey, 1, 100, 0xfffffffe).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_checklocktimeverify_fails_if_the_input_is_final() -> Result<(), Box<dyn Error>> {
+    let script_pubkey = parse_script("100 CHECKLOCKTIMEVERIFY DROP 1")?;
+    assert!(verify_with_lock_times(&script_pubkey, 1, 100, 0xffffffff).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_checksequenceverify_needs_a_greater_sequence_of_the_same_type() -> Result<(), Box<dyn Error>>
+{
+    let script_pubkey = parse_script("10 CHECKSEQUENCEVERIFY DROP 1")?;
+    assert!(verify_with_lock_times(&script_pubkey, 2, 0, 10).is_ok());
+    assert!(verify_with_lock_times(&script_pubkey, 2, 0, 9).is_err());
+    assert!(verify_with_lock_times(&script_pubkey, 2, 0, (1 << 22) | 10).is_err());
+    assert!(verify_with_lock_times(&script_pubkey, 2, 0, 1 << 31).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_checksequenceverify_fails_in_transactions_of_version_1() -> Result<(), Box<dyn Error>> {
+    let script_pubkey = parse_script("10 CHECKSEQUENCEVERIFY DROP 1")?;
+    assert!(verify_with_lock_times(&script_pubkey, 1, 0, 10).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_p2sh_output_is_unlocked_by_the_redeem_script_and_its_signature(
+) -> Result<(), Box<dyn Error>> {
+    let pubkey = get_pubkey_compressed(PRIVATE_KEY_1)?;
+    let mut redeem_script = push_data_bytes(&pubkey);
+    redeem_script.push(ScriptOpcodes::OP_CHECKSIG);
+    let script_pubkey = parse_script(&format!(
+        "HASH160 0x14 0x{} EQUAL",
+        bytes_to_hex(&hash_160(&redeem_script))
+    ))?;
+
+    // the signature commits to the redeem script
+    let sig = sign(&script_pubkey, &redeem_script, PRIVATE_KEY_1, 0x01)?;
+    let script_sig = push_all(&[&sig, &redeem_script]);
+    assert!(verify(&script_sig, &script_pubkey).is_ok());
+    let sig = sign(&script_pubkey, &redeem_script, PRIVATE_KEY_2, 0x01)?;
+    let script_sig = push_all(&[&sig, &redeem_script]);
+    assert!(verify(&script_sig, &script_pubkey).is_err());
+    Ok(())
+}
+
+/// Converts the bytes to an hexadecimal string
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}